@@ -0,0 +1,323 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+#![warn(missing_docs)]
+
+//! This library provides decoders for the [buildkite](https://buildkite.com/) build api.
+//!
+//! The requests are authenticated with an api access token, which needs the `read_builds`
+//! and `read_artifacts` scopes.
+//!
+//! Here is an example usage:
+//!
+//! ```no_run
+//! # fn main() {
+//! let url = url::Url::parse("https://buildkite.com/my-org/my-pipeline/builds/42").unwrap();
+//! let build_url = buildkite_build::parse_build_url(&url).unwrap();
+//! let client = buildkite_build::Client::from_env();
+//! let build = client.get_build(&ureq::Agent::new(), &build_url).unwrap();
+//! println!("{}: {}", build.number, build.state);
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use url::Url;
+
+/// The buildkite error.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The provided url is not usable.
+    #[error("bad api url: {0}")]
+    BadUrl(#[from] url::ParseError),
+
+    /// The api reply couldn't be decoded.
+    #[error("bad api reply: {0}")]
+    BadReply(#[from] std::io::Error),
+
+    /// The api query failed.
+    #[error("bad api query: {0}")]
+    BadQuery(#[from] Box<ureq::Error>),
+}
+
+/// The public api url.
+pub const API_URL: &str = "https://api.buildkite.com/v2/";
+
+/// The number of items per page, the api maximum.
+const PAGE_SIZE: usize = 100;
+
+/// The location of a build.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildUrl {
+    /// The organization slug.
+    pub org: Box<str>,
+    /// The pipeline slug.
+    pub pipeline: Box<str>,
+    /// The build number.
+    pub number: u64,
+}
+
+/// Decode a build url, such as `https://buildkite.com/my-org/my-pipeline/builds/42#job-id`.
+pub fn parse_build_url(url: &Url) -> Option<BuildUrl> {
+    if url.host_str()? != "buildkite.com" {
+        return None;
+    }
+    match url.path_segments()?.collect::<Vec<_>>()[..] {
+        [org, pipeline, "builds", number] | [org, pipeline, "builds", number, ""] => {
+            Some(BuildUrl {
+                org: org.into(),
+                pipeline: pipeline.into(),
+                number: number.parse().ok()?,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// A build pipeline.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pipeline {
+    /// The branch of the pipeline main builds.
+    pub default_branch: Option<Box<str>>,
+}
+
+/// A build job.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    /// The job uuid.
+    pub id: Box<str>,
+    /// The job type, only the `script` jobs have a log.
+    #[serde(rename = "type")]
+    pub kind: Box<str>,
+    /// The step label.
+    pub name: Option<Box<str>>,
+    /// The step key, which is stable when the label contains variables.
+    pub step_key: Option<Box<str>>,
+    /// The url of the log text.
+    pub raw_log_url: Option<Url>,
+}
+
+impl Job {
+    /// The job name, using the step key when it is set.
+    pub fn label(&self) -> &str {
+        self.step_key
+            .as_deref()
+            .or(self.name.as_deref())
+            .unwrap_or(self.id.as_ref())
+    }
+}
+
+/// A build result.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Build {
+    /// The build number.
+    pub number: u64,
+    /// The build state, e.g. `passed` or `failed`.
+    pub state: Box<str>,
+    /// The build branch.
+    pub branch: Box<str>,
+    /// The build page.
+    pub web_url: Url,
+    /// The build jobs.
+    #[serde(default)]
+    pub jobs: Vec<Job>,
+    /// The build pipeline.
+    pub pipeline: Option<Pipeline>,
+}
+
+/// A build artifact.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// The artifact uuid.
+    pub id: Box<str>,
+    /// The uuid of the job that uploaded the artifact.
+    pub job_id: Box<str>,
+    /// The artifact path.
+    pub path: Box<str>,
+    /// The download url, which redirects to the artifact storage.
+    pub download_url: Url,
+}
+
+/// The buildkite api client.
+#[derive(Clone, Debug)]
+pub struct Client {
+    /// The api url, terminated with a slash.
+    pub api_url: Url,
+    /// The api access token.
+    pub token: Option<String>,
+}
+
+impl Client {
+    /// Create a client using the `BUILDKITE_API_TOKEN` variable.
+    pub fn from_env() -> Client {
+        Client {
+            api_url: Url::parse(API_URL).expect("Valid api url"),
+            token: std::env::var("BUILDKITE_API_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+        }
+    }
+
+    /// Check if the url is an api url that needs the token.
+    pub fn is_api_url(&self, url: &Url) -> bool {
+        url.as_str().starts_with(self.api_url.as_str())
+    }
+
+    /// Create a request, with the token when the url is an api url.
+    pub fn request(&self, agent: &ureq::Agent, method: &str, url: &Url) -> ureq::Request {
+        let request = agent.request_url(method, url);
+        match &self.token {
+            Some(token) if self.is_api_url(url) => {
+                request.set("Authorization", &format!("Bearer {}", token))
+            }
+            _ => request,
+        }
+    }
+
+    fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        agent: &ureq::Agent,
+        url: &Url,
+    ) -> Result<T, Error> {
+        tracing::debug!(url = url.as_str(), "Querying buildkite api");
+        self.request(agent, "GET", url)
+            .call()
+            .map_err(|e| Error::BadQuery(Box::new(e)))?
+            .into_json()
+            .map_err(Error::BadReply)
+    }
+
+    fn builds_url(&self, org: &str, pipeline: &str) -> Result<Url, Error> {
+        Ok(self.api_url.join(&format!(
+            "organizations/{}/pipelines/{}/builds/",
+            org, pipeline
+        ))?)
+    }
+
+    /// Get a build and its jobs.
+    pub fn get_build(&self, agent: &ureq::Agent, build: &BuildUrl) -> Result<Build, Error> {
+        let url = self
+            .builds_url(&build.org, &build.pipeline)?
+            .join(&build.number.to_string())?;
+        self.get_json(agent, &url)
+    }
+
+    /// List the artifacts of a build.
+    pub fn get_artifacts(
+        &self,
+        agent: &ureq::Agent,
+        build: &BuildUrl,
+    ) -> Result<Vec<Artifact>, Error> {
+        let url = self
+            .builds_url(&build.org, &build.pipeline)?
+            .join(&format!("{}/artifacts", build.number))?;
+        let mut artifacts = Vec::new();
+        for page in 1.. {
+            let mut page_url = url.clone();
+            page_url
+                .query_pairs_mut()
+                .append_pair("page", &page.to_string())
+                .append_pair("per_page", &PAGE_SIZE.to_string());
+            let items: Vec<Artifact> = self.get_json(agent, &page_url)?;
+            let done = items.len() < PAGE_SIZE;
+            artifacts.extend(items);
+            if done {
+                break;
+            }
+        }
+        Ok(artifacts)
+    }
+
+    /// Get the last passed build of a branch.
+    pub fn last_passed_build(
+        &self,
+        agent: &ureq::Agent,
+        org: &str,
+        pipeline: &str,
+        branch: &str,
+    ) -> Result<Option<Build>, Error> {
+        let mut url = self.builds_url(org, pipeline)?;
+        url.query_pairs_mut()
+            .append_pair("branch", branch)
+            .append_pair("state", "passed")
+            .append_pair("per_page", "1");
+        let builds: Vec<Build> = self.get_json(agent, &url)?;
+        Ok(builds.into_iter().next())
+    }
+}
+
+#[test]
+fn test_parse_build_url() {
+    let url = Url::parse("https://buildkite.com/my-org/my-pipeline/builds/42#0189-job").unwrap();
+    assert_eq!(
+        parse_build_url(&url),
+        Some(BuildUrl {
+            org: "my-org".into(),
+            pipeline: "my-pipeline".into(),
+            number: 42,
+        })
+    );
+    for url in [
+        "https://buildkite.com/my-org/my-pipeline/builds",
+        "https://buildkite.com/my-org/my-pipeline/builds/latest",
+        "https://example.com/my-org/my-pipeline/builds/42",
+    ] {
+        assert_eq!(parse_build_url(&Url::parse(url).unwrap()), None, "{}", url);
+    }
+}
+
+#[test]
+fn test_get_build() {
+    use mockito::Matcher;
+    let mut server = mockito::Server::new();
+    let build_mock = server
+        .mock("GET", "/organizations/my-org/pipelines/app/builds/42")
+        .match_header("authorization", "Bearer secret")
+        .with_body(format!(
+            r#"{{"number":42,"state":"failed","branch":"feature","web_url":"https://buildkite.com/my-org/app/builds/42","pipeline":{{"slug":"app","default_branch":"main"}},"jobs":[{{"id":"j1","type":"waiter"}},{{"id":"j2","type":"script","name":":hammer: Build","step_key":"build","raw_log_url":"{}/organizations/my-org/pipelines/app/builds/42/jobs/j2/log.txt"}}]}}"#,
+            server.url()
+        ))
+        .expect(1)
+        .create();
+    let artifacts_mock = server
+        .mock("GET", "/organizations/my-org/pipelines/app/builds/42/artifacts")
+        .match_query(Matcher::UrlEncoded("page".into(), "1".into()))
+        .with_body(r#"[{"id":"a1","job_id":"j2","path":"logs/app.log","download_url":"https://api.buildkite.com/v2/a1/download"}]"#)
+        .expect(1)
+        .create();
+    let passed_mock = server
+        .mock("GET", "/organizations/my-org/pipelines/app/builds/")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("branch".into(), "main".into()),
+            Matcher::UrlEncoded("state".into(), "passed".into()),
+        ]))
+        .with_body(r#"[{"number":40,"state":"passed","branch":"main","web_url":"https://buildkite.com/my-org/app/builds/40"}]"#)
+        .expect(1)
+        .create();
+
+    let client = Client {
+        api_url: Url::parse(&format!("{}/", server.url())).unwrap(),
+        token: Some("secret".into()),
+    };
+    let agent = ureq::Agent::new();
+    let build_url = BuildUrl {
+        org: "my-org".into(),
+        pipeline: "app".into(),
+        number: 42,
+    };
+    let build = client.get_build(&agent, &build_url).unwrap();
+    assert_eq!(build.state.as_ref(), "failed");
+    assert_eq!(build.jobs[1].label(), "build");
+    assert!(client.is_api_url(build.jobs[1].raw_log_url.as_ref().unwrap()));
+
+    let artifacts = client.get_artifacts(&agent, &build_url).unwrap();
+    assert_eq!(artifacts[0].path.as_ref(), "logs/app.log");
+
+    let baseline = client
+        .last_passed_build(&agent, "my-org", "app", "main")
+        .unwrap();
+    assert_eq!(baseline.map(|build| build.number), Some(40));
+    build_mock.assert();
+    artifacts_mock.assert();
+    passed_mock.assert();
+}
@@ -34,6 +34,10 @@ mod filename {
         format!("{}/{}", new_base(prefix, url), new('2', url.as_str()))
     }
 
+    pub fn validators(prefix: usize, url: &Url) -> String {
+        format!("{}/{}", new_base(prefix, url), new('6', url.as_str()))
+    }
+
     pub fn head_success(prefix: usize, url: &Url) -> String {
         format!("{}/{}", new_base(prefix, url), new('3', url.as_str()))
     }
@@ -42,6 +46,14 @@ mod filename {
         format!("{}/{}", new_base(prefix, url), new('4', url.as_str()))
     }
 
+    pub fn model_ref(url: &str) -> String {
+        format!("models/{}", new('5', url))
+    }
+
+    pub fn model_blob(hash: &str) -> String {
+        format!("models/{}.bin", hash)
+    }
+
     pub fn drop(path: Option<std::path::PathBuf>) -> Result<()> {
         path.map_or_else(
             || Ok(()),
@@ -50,6 +62,50 @@ mod filename {
     }
 }
 
+/// Compute the content hash of a model.
+pub fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// The validators of a cached response, to check if the remote content changed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Validators {
+    /// The `ETag` header value.
+    pub etag: Option<String>,
+    /// The `Last-Modified` header value.
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    fn encode(&self) -> String {
+        let mut lines = String::new();
+        if let Some(etag) = &self.etag {
+            lines.push_str(&format!("ETag: {}\n", etag));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            lines.push_str(&format!("Last-Modified: {}\n", last_modified));
+        }
+        lines
+    }
+
+    fn decode(lines: &str) -> Validators {
+        let mut validators = Validators::default();
+        for line in lines.lines() {
+            if let Some(etag) = line.strip_prefix("ETag: ") {
+                validators.etag = Some(etag.into());
+            } else if let Some(last_modified) = line.strip_prefix("Last-Modified: ") {
+                validators.last_modified = Some(last_modified.into());
+            }
+        }
+        validators
+    }
+}
+
 /// The Cache object to read and write cached content.
 pub struct Cache {
     xdg: xdg::BaseDirectories,
@@ -63,6 +119,17 @@ impl Cache {
             .context("Failed to get xdg cache directory")
     }
 
+    /// Check that the cache directory is writable, returning its location.
+    pub fn check(&self) -> Result<std::path::PathBuf> {
+        let probe = self
+            .xdg
+            .place_cache_file("probe")
+            .context("Failed to create the cache directory")?;
+        File::create(&probe).context("Failed to write to the cache directory")?;
+        std::fs::remove_file(&probe).context("Failed to remove the cache probe")?;
+        Ok(self.xdg.get_cache_home())
+    }
+
     /// Get a cached head result.
     pub fn head(&self, prefix: usize, path: &Url) -> Option<bool> {
         match self.get(&filename::head_success(prefix, path)) {
@@ -100,6 +167,27 @@ impl Cache {
         })
     }
 
+    /// Get the validators of a cached file, when the remote provided them.
+    pub fn remote_validators(&self, prefix: usize, path: &Url) -> Option<Validators> {
+        let lines = std::fs::read_to_string(self.get(&filename::validators(prefix, path))?).ok()?;
+        Some(Validators::decode(&lines)).filter(|validators| !validators.is_empty())
+    }
+
+    /// Store the validators of a cached file, to revalidate it on the next read.
+    pub fn remote_set_validators(
+        &self,
+        prefix: usize,
+        path: &Url,
+        validators: &Validators,
+    ) -> Result<()> {
+        if validators.is_empty() {
+            return Ok(());
+        }
+        self.create(&filename::validators(prefix, path))?
+            .write_all(validators.encode().as_bytes())
+            .context("Failed to write the cache validators")
+    }
+
     /// Get a cached httpdir.
     pub fn httpdir_get(&self, url: &Url) -> Option<Result<Vec<UrlResult>>> {
         self.get(&filename::httpdir(url)).map(|buf| {
@@ -114,8 +202,55 @@ impl Cache {
         bincode::serialize_into(fp, paths).context("Failed to serialize httpdir save")
     }
 
+    /// Get the content hash of a cached model.
+    pub fn model_get(&self, url: &str) -> Option<String> {
+        let hash = std::fs::read_to_string(self.get(&filename::model_ref(url))?).ok()?;
+        self.get(&filename::model_blob(&hash)).map(|_| hash)
+    }
+
+    /// Read a cached model, verifying its integrity.
+    pub fn model_read(&self, hash: &str) -> Result<Vec<u8>> {
+        let path = self
+            .get(&filename::model_blob(hash))
+            .context("Missing cached model")?;
+        let data = std::fs::read(&path).context("Failed to read cached model")?;
+        if sha256_hex(&data) == hash {
+            Ok(data)
+        } else {
+            filename::drop(Some(path))?;
+            Err(anyhow::anyhow!("Cached model {} is corrupted", hash))
+        }
+    }
+
+    /// Add a model to the cache, verifying its integrity when the expected hash is provided.
+    pub fn model_add(&self, url: &str, expected: Option<&str>, data: &[u8]) -> Result<String> {
+        let hash = sha256_hex(data);
+        if let Some(expected) = expected {
+            if expected != hash {
+                return Err(anyhow::anyhow!(
+                    "Model integrity error, expected {} got {}",
+                    expected,
+                    hash
+                ));
+            }
+        }
+        // The blobs are content addressed, so an existing blob does not need to be written again.
+        if self.get(&filename::model_blob(&hash)).is_none() {
+            self.create(&filename::model_blob(&hash))?
+                .write_all(data)
+                .context("Failed to write cached model")?;
+        }
+        let ref_path = self
+            .xdg
+            .place_cache_file(filename::model_ref(url))
+            .context("Failed to create the model directory")?;
+        std::fs::write(ref_path, &hash).context("Failed to write model ref")?;
+        Ok(hash)
+    }
+
     /// Remove a remote file from the cache.
     pub fn remote_drop(&self, prefix: usize, path: &Url) -> Result<()> {
+        filename::drop(self.get(&filename::validators(prefix, path)))?;
         filename::drop(self.get(&filename::http(prefix, path)))
     }
 
@@ -219,3 +354,33 @@ fn test_remote() {
     );
     assert_eq!(data, new_data);
 }
+
+#[test]
+fn test_model() {
+    let cache = Cache::new().unwrap();
+    let url = "http://localhost/api/models/test/blob";
+    let data = b"model data";
+
+    assert!(cache.model_add(url, Some("bad"), data).is_err());
+    let hash = cache.model_add(url, None, data).unwrap();
+    assert_eq!(cache.model_get(url), Some(hash.clone()));
+    assert_eq!(cache.model_read(&hash).unwrap(), data);
+}
+
+#[test]
+fn test_remote_validators() {
+    let cache = Cache::new().unwrap();
+    let path = Url::parse("http://localhost/builds/etag/job-output.txt").unwrap();
+    let validators = Validators {
+        etag: Some("\"5f3c-1a\"".into()),
+        last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".into()),
+    };
+
+    cache.remote_drop(0, &path).unwrap();
+    assert_eq!(cache.remote_validators(0, &path), None);
+    cache.remote_set_validators(0, &path, &validators).unwrap();
+    assert_eq!(cache.remote_validators(0, &path), Some(validators));
+
+    cache.remote_drop(0, &path).unwrap();
+    assert_eq!(cache.remote_validators(0, &path), None);
+}
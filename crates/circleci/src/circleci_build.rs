@@ -0,0 +1,370 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+#![warn(missing_docs)]
+
+//! This library provides decoders for the [circleci](https://circleci.com/) build api.
+//!
+//! The jobs steps output and artifacts are read with the v1.1 api, and the requests
+//! are authenticated with a personal api token for the private projects.
+//!
+//! Here is an example usage:
+//!
+//! ```no_run
+//! # fn main() {
+//! let url = url::Url::parse("https://app.circleci.com/pipelines/github/org/repo/12/workflows/0d1c/jobs/42").unwrap();
+//! let job_url = circleci_build::parse_job_url(&url).unwrap();
+//! let client = circleci_build::Client::from_env();
+//! let job = client.get_job(&ureq::Agent::new(), &job_url).unwrap();
+//! println!("{}: {}", job.build_num, job.status);
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use url::Url;
+
+/// The circleci error.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The provided url is not usable.
+    #[error("bad api url: {0}")]
+    BadUrl(#[from] url::ParseError),
+
+    /// The api reply couldn't be decoded.
+    #[error("bad api reply: {0}")]
+    BadReply(#[from] std::io::Error),
+
+    /// The api query failed.
+    #[error("bad api query: {0}")]
+    BadQuery(#[from] Box<ureq::Error>),
+}
+
+/// The public api url.
+pub const API_URL: &str = "https://circleci.com/api/";
+
+/// The host of the artifacts, which needs the token for the private projects.
+const ARTIFACTS_HOST: &str = "circle-artifacts.com";
+
+/// The location of a job.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobUrl {
+    /// The project slug, e.g. `gh/org/repo`.
+    pub slug: Box<str>,
+    /// The job number.
+    pub number: u64,
+}
+
+fn vcs_slug(vcs: &str) -> Option<&'static str> {
+    match vcs {
+        "gh" | "github" => Some("gh"),
+        "bb" | "bitbucket" => Some("bb"),
+        "circleci" => Some("circleci"),
+        _ => None,
+    }
+}
+
+/// Decode a job url, either the `https://app.circleci.com/pipelines/github/org/repo/12/workflows/id/jobs/42`
+/// page or the legacy `https://circleci.com/gh/org/repo/42` page.
+pub fn parse_job_url(url: &Url) -> Option<JobUrl> {
+    let segments = url.path_segments()?.collect::<Vec<_>>();
+    let (vcs, org, repo, number) = match (url.host_str()?, &segments[..]) {
+        (
+            "app.circleci.com",
+            ["pipelines", vcs, org, repo, _, "workflows", _, "jobs", number, ..],
+        ) => (vcs, org, repo, number),
+        ("circleci.com", [vcs, org, repo, number, ..]) => (vcs, org, repo, number),
+        _ => return None,
+    };
+    Some(JobUrl {
+        slug: format!("{}/{}/{}", vcs_slug(vcs)?, org, repo).into(),
+        number: number.parse().ok()?,
+    })
+}
+
+/// The workflow of a job.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Workflow {
+    /// The job name in the workflow.
+    pub job_name: Box<str>,
+    /// The workflow name.
+    pub workflow_name: Box<str>,
+}
+
+/// A step action, which runs on a parallel node.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Action {
+    /// The node index.
+    pub index: u64,
+    /// The step index.
+    pub step: u64,
+    /// If the action has an output.
+    #[serde(default)]
+    pub has_output: bool,
+}
+
+/// A job step.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    /// The step name.
+    pub name: Box<str>,
+    /// The step actions, one per node.
+    #[serde(default)]
+    pub actions: Vec<Action>,
+}
+
+/// A job result.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    /// The job number.
+    pub build_num: u64,
+    /// The job status, e.g. `success` or `failed`.
+    pub status: Box<str>,
+    /// The job branch, missing for the tags.
+    pub branch: Option<Box<str>>,
+    /// The job workflow.
+    pub workflows: Option<Workflow>,
+    /// The job steps, missing from the jobs listing.
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+/// A job artifact.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// The artifact path.
+    pub path: Box<str>,
+    /// The artifact url.
+    pub url: Url,
+}
+
+/// A line of a step output.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Output {
+    /// The output message, which may contain multiple lines.
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Project {
+    vcs_info: VcsInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct VcsInfo {
+    default_branch: Option<Box<str>>,
+}
+
+/// The circleci api client.
+#[derive(Clone, Debug)]
+pub struct Client {
+    /// The api url, terminated with a slash.
+    pub api_url: Url,
+    /// The personal api token.
+    pub token: Option<String>,
+}
+
+impl Client {
+    /// Create a client using the `CIRCLECI_TOKEN` or `CIRCLE_TOKEN` variable.
+    pub fn from_env() -> Client {
+        Client {
+            api_url: Url::parse(API_URL).expect("Valid api url"),
+            token: ["CIRCLECI_TOKEN", "CIRCLE_TOKEN"]
+                .iter()
+                .find_map(|name| std::env::var(name).ok().filter(|token| !token.is_empty())),
+        }
+    }
+
+    /// Check if the url is an api or artifact url that needs the token.
+    pub fn is_api_url(&self, url: &Url) -> bool {
+        url.as_str().starts_with(self.api_url.as_str())
+            || url
+                .host_str()
+                .is_some_and(|host| host.ends_with(ARTIFACTS_HOST))
+    }
+
+    /// Create a request, with the token when the url is an api url.
+    pub fn request(&self, agent: &ureq::Agent, method: &str, url: &Url) -> ureq::Request {
+        let request = agent.request_url(method, url);
+        match &self.token {
+            Some(token) if self.is_api_url(url) => request.set("Circle-Token", token),
+            _ => request,
+        }
+    }
+
+    fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        agent: &ureq::Agent,
+        url: &Url,
+    ) -> Result<T, Error> {
+        tracing::debug!(url = url.as_str(), "Querying circleci api");
+        self.request(agent, "GET", url)
+            .call()
+            .map_err(|e| Error::BadQuery(Box::new(e)))?
+            .into_json()
+            .map_err(Error::BadReply)
+    }
+
+    /// The v1.1 url of a job.
+    pub fn job_api_url(&self, job: &JobUrl) -> Result<Url, Error> {
+        Ok(self
+            .api_url
+            .join(&format!("v1.1/project/{}/{}/", job.slug, job.number))?)
+    }
+
+    /// Get a job and its steps.
+    pub fn get_job(&self, agent: &ureq::Agent, job: &JobUrl) -> Result<Job, Error> {
+        let url = self.job_api_url(job)?;
+        self.get_json(agent, &url)
+    }
+
+    /// The url of a step action output.
+    pub fn output_url(&self, job: &JobUrl, action: &Action) -> Result<Url, Error> {
+        Ok(self
+            .job_api_url(job)?
+            .join(&format!("output/{}/{}", action.step, action.index))?)
+    }
+
+    /// Read a step action output.
+    pub fn get_output(&self, agent: &ureq::Agent, url: &Url) -> Result<Vec<Output>, Error> {
+        self.get_json(agent, url)
+    }
+
+    /// List the artifacts of a job.
+    pub fn get_artifacts(&self, agent: &ureq::Agent, job: &JobUrl) -> Result<Vec<Artifact>, Error> {
+        let url = self.job_api_url(job)?.join("artifacts")?;
+        self.get_json(agent, &url)
+    }
+
+    /// Get the project default branch.
+    pub fn default_branch(
+        &self,
+        agent: &ureq::Agent,
+        slug: &str,
+    ) -> Result<Option<Box<str>>, Error> {
+        let url = self.api_url.join(&format!("v2/project/{}", slug))?;
+        let project: Project = self.get_json(agent, &url)?;
+        Ok(project.vcs_info.default_branch)
+    }
+
+    /// Get the last successful job of the same workflow job on a branch.
+    pub fn last_successful_job(
+        &self,
+        agent: &ureq::Agent,
+        slug: &str,
+        branch: &str,
+        workflow: &Workflow,
+    ) -> Result<Option<Job>, Error> {
+        let mut url = self.api_url.join(&format!("v1.1/project/{}/tree/", slug))?;
+        url.path_segments_mut()
+            .map_err(|()| url::ParseError::RelativeUrlWithCannotBeABaseBase)?
+            .pop_if_empty()
+            .push(branch);
+        url.query_pairs_mut()
+            .append_pair("filter", "successful")
+            .append_pair("shallow", "true")
+            .append_pair("limit", "100");
+        let jobs: Vec<Job> = self.get_json(agent, &url)?;
+        Ok(jobs
+            .into_iter()
+            .find(|job| job.workflows.as_ref() == Some(workflow)))
+    }
+}
+
+#[test]
+fn test_parse_job_url() {
+    let expected = Some(JobUrl {
+        slug: "gh/org/repo".into(),
+        number: 42,
+    });
+    for url in [
+        "https://app.circleci.com/pipelines/github/org/repo/12/workflows/0d1c-42/jobs/42",
+        "https://app.circleci.com/pipelines/github/org/repo/12/workflows/0d1c-42/jobs/42/artifacts",
+        "https://circleci.com/gh/org/repo/42",
+    ] {
+        assert_eq!(
+            parse_job_url(&Url::parse(url).unwrap()),
+            expected,
+            "{}",
+            url
+        );
+    }
+    for url in [
+        "https://app.circleci.com/pipelines/github/org/repo/12",
+        "https://circleci.com/gh/org/repo",
+        "https://example.com/gh/org/repo/42",
+    ] {
+        assert_eq!(parse_job_url(&Url::parse(url).unwrap()), None, "{}", url);
+    }
+}
+
+#[test]
+fn test_get_job() {
+    use mockito::Matcher;
+    let mut server = mockito::Server::new();
+    let workflows = r#"{"job_name":"test","workflow_name":"ci"}"#;
+    let job_mock = server
+        .mock("GET", "/v1.1/project/gh/org/repo/42/")
+        .match_header("circle-token", "secret")
+        .with_body(format!(
+            r#"{{"build_num":42,"status":"failed","branch":"feature","workflows":{workflows},"steps":[{{"name":"Run tests","actions":[{{"index":0,"step":102,"has_output":true}}]}}]}}"#
+        ))
+        .expect(1)
+        .create();
+    let output_mock = server
+        .mock("GET", "/v1.1/project/gh/org/repo/42/output/102/0")
+        .with_body(
+            r#"[{"type":"out","time":"2023-10-15T08:00:00Z","message":"first\r\nsecond\r\n"}]"#,
+        )
+        .expect(1)
+        .create();
+    let project_mock = server
+        .mock("GET", "/v2/project/gh/org/repo")
+        .with_body(
+            r#"{"slug":"gh/org/repo","vcs_info":{"provider":"GitHub","default_branch":"main"}}"#,
+        )
+        .expect(1)
+        .create();
+    let tree_mock = server
+        .mock("GET", "/v1.1/project/gh/org/repo/tree/main")
+        .match_query(Matcher::UrlEncoded("filter".into(), "successful".into()))
+        .with_body(format!(
+            r#"[{{"build_num":41,"status":"success","workflows":{{"job_name":"lint","workflow_name":"ci"}}}},{{"build_num":40,"status":"success","workflows":{workflows}}}]"#
+        ))
+        .expect(1)
+        .create();
+
+    let client = Client {
+        api_url: Url::parse(&format!("{}/", server.url())).unwrap(),
+        token: Some("secret".into()),
+    };
+    let agent = ureq::Agent::new();
+    let job_url = JobUrl {
+        slug: "gh/org/repo".into(),
+        number: 42,
+    };
+    let job = client.get_job(&agent, &job_url).unwrap();
+    assert_eq!(job.status.as_ref(), "failed");
+    let url = client
+        .output_url(&job_url, &job.steps[0].actions[0])
+        .unwrap();
+    assert!(client.is_api_url(&url));
+    let output = client.get_output(&agent, &url).unwrap();
+    assert_eq!(output[0].message, "first\r\nsecond\r\n");
+
+    let branch = client.default_branch(&agent, &job_url.slug).unwrap();
+    assert_eq!(branch.as_deref(), Some("main"));
+    let baseline = client
+        .last_successful_job(
+            &agent,
+            &job_url.slug,
+            "main",
+            job.workflows.as_ref().unwrap(),
+        )
+        .unwrap();
+    assert_eq!(baseline.map(|job| job.build_num), Some(40));
+    job_mock.assert();
+    output_mock.assert();
+    project_mock.assert();
+    tree_mock.assert();
+}
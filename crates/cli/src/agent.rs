@@ -0,0 +1,89 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the logic for the agent command.
+//! The agent analyzes local logs with a model fetched from a logjuicer-api,
+//! and it uploads the resulting report so that the raw logs stay on the build node.
+//! The upload uses the `LOGJUICER_API_TOKEN` environment variable as a bearer token.
+
+use anyhow::{Context, Result};
+use std::io::Read;
+
+use logjuicer_model::env::Env;
+use logjuicer_model::{content_from_input, FeaturesMatrix, Input, Model};
+use logjuicer_report::report_row::{ReportID, ReportStatus};
+use logjuicer_report::Report;
+
+pub fn run(env: &Env, server: &str, model_ref: &str, path: String) -> Result<()> {
+    let server = server.trim_end_matches('/');
+    let model = fetch_model(env, &format!("{}/api/models/{}/blob", server, model_ref))?;
+    let content = content_from_input(env, Input::Path(path))?;
+    let report = model.report(env, content)?;
+    for warning in &report.warnings {
+        tracing::warn!("{}", warning);
+    }
+    let report_id = upload_report(env, server, model_ref, &report)?;
+    println!("Report uploaded: {}/report/{}", server, report_id);
+    Ok(())
+}
+
+/// Fetch a model, using the env cache when the model did not change.
+pub fn fetch_model(env: &Env, url: &str) -> Result<Model<FeaturesMatrix>> {
+    let cached = env
+        .cache
+        .as_ref()
+        .and_then(|cache| Some((cache, cache.model_get(url)?)));
+    if env.offline {
+        let (cache, hash) =
+            cached.with_context(|| format!("Offline mode, the model {} is not cached", url))?;
+        let data = cache.model_read(&hash)?;
+        return Model::load_reader(&data[..]).context("Failed to load the model");
+    }
+    env.debug_or_progress(&format!("Fetching model {}", url));
+    let mut request = env.client_for(&url::Url::parse(url)?).get(url);
+    if let Some((_, hash)) = &cached {
+        request = request.set("If-None-Match", &format!("\"{}\"", hash));
+    }
+    let resp = request
+        .call()
+        .with_context(|| format!("Failed to get the model {}", url))?;
+    let data = match cached {
+        Some((cache, hash)) if resp.status() == 304 => {
+            tracing::debug!(hash = hash.as_str(), "Using cached model");
+            cache.model_read(&hash)?
+        }
+        _ => {
+            let expected = resp
+                .header("ETag")
+                .map(|etag| etag.trim_start_matches("W/").trim_matches('"').to_string());
+            let mut data = Vec::new();
+            resp.into_reader()
+                .read_to_end(&mut data)
+                .context("Failed to download the model")?;
+            if let Some(cache) = &env.cache {
+                cache.model_add(url, expected.as_deref(), &data)?;
+            }
+            data
+        }
+    };
+    Model::load_reader(&data[..]).context("Failed to load the model")
+}
+
+fn upload_report(env: &Env, server: &str, model_ref: &str, report: &Report) -> Result<ReportID> {
+    let mut body = Vec::new();
+    report.save_writer(&mut body)?;
+    let url = format!("{}/api/report/upload", server);
+    env.debug_or_progress(&format!("Uploading report to {}", url));
+    let mut request = env.client_for(&url::Url::parse(&url)?).put(&url);
+    if let Ok(token) = std::env::var("LOGJUICER_API_TOKEN") {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    let (report_id, _): (ReportID, ReportStatus) = request
+        .query("target", &report.target.to_string())
+        .query("model", model_ref)
+        .send_bytes(&body)
+        .with_context(|| format!("Failed to upload the report {}", url))?
+        .into_json()
+        .context("Failed to decode the upload response")?;
+    Ok(report_id)
+}
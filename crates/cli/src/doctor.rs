@@ -0,0 +1,185 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the logic for the doctor command.
+//! It validates a setup end-to-end and prints actionable failures.
+
+use anyhow::Result;
+use std::path::PathBuf;
+
+use logjuicer_model::config::Config;
+use logjuicer_model::env::{Env, OutputMode};
+use logjuicer_model::process::{ChunkProcessor, IndexTrainer};
+use logjuicer_model::unordered::KnownLines;
+use logjuicer_model::FeaturesMatrixBuilder;
+
+const SAMPLE_BASELINE: &str = "Starting service
+Loading config from /etc/service.conf
+Listening on 0.0.0.0:8080
+Request 42 completed in 12ms
+Request 43 completed in 9ms
+Shutting down
+";
+
+const SAMPLE_TARGET: &str = "Starting service
+Loading config from /etc/service.conf
+Listening on 0.0.0.0:8080
+Request 42 completed in 11ms
+Traceback (most recent call last): KeyError: 'database'
+Shutting down
+";
+
+/// The result of a single check.
+enum Check {
+    Ok(String),
+    Skip(String),
+    Fail(String, &'static str),
+}
+
+impl Check {
+    fn print(&self) {
+        match self {
+            Check::Ok(msg) => println!("[ok]   {}", msg),
+            Check::Skip(msg) => println!("[skip] {}", msg),
+            Check::Fail(msg, hint) => println!("[fail] {}\n       hint: {}", msg, hint),
+        }
+    }
+}
+
+pub fn run(
+    config: Option<PathBuf>,
+    output: OutputMode,
+    urls: &[String],
+    api_url: Option<String>,
+) -> Result<()> {
+    let mut checks = Vec::new();
+    let mut report = |check: Check| {
+        check.print();
+        checks.push(check);
+    };
+
+    report(check_config(config.as_ref()));
+
+    // Use the default config when the provided one is invalid so that the other checks can proceed.
+    let env =
+        Env::new_with_settings(config, output).or_else(|_| Env::new_with_settings(None, output))?;
+
+    report(check_cache(&env));
+    for url in urls {
+        report(check_url(&env, url));
+    }
+    match api_url {
+        Some(api_url) => report(check_api(&env, &api_url)),
+        None => report(Check::Skip(
+            "service database (use --api-url to check a logjuicer-api)".into(),
+        )),
+    }
+    report(check_sample());
+
+    let failures = checks
+        .iter()
+        .filter(|c| matches!(c, Check::Fail(_, _)))
+        .count();
+    if failures == 0 {
+        println!("All checks passed");
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("{} check(s) failed", failures))
+    }
+}
+
+fn check_config(config: Option<&PathBuf>) -> Check {
+    match config {
+        None => Check::Ok("config: using the default excludes".into()),
+        Some(path) => match Config::from_path(path.clone()) {
+            Ok(_) => Check::Ok(format!("config: {:?} is valid", path)),
+            Err(e) => Check::Fail(
+                format!("config: {:?} is invalid: {}", path, e),
+                "the config must be a .yaml or .json file with includes/excludes regex lists",
+            ),
+        },
+    }
+}
+
+fn check_cache(env: &Env) -> Check {
+    match &env.cache {
+        None => Check::Skip("cache: disabled (set LOGJUICER_CACHE=1 to enable it)".into()),
+        Some(cache) => match cache.check() {
+            Ok(path) => Check::Ok(format!("cache: {:?} is writable", path)),
+            Err(e) => Check::Fail(
+                format!("cache: {:#}", e),
+                "check the XDG_CACHE_HOME permissions, or unset LOGJUICER_CACHE",
+            ),
+        },
+    }
+}
+
+fn check_url(env: &Env, url: &str) -> Check {
+    let parsed = match url::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return Check::Fail(
+                format!("network: {} is not a valid url: {}", url, e),
+                "provide a full url, for example https://zuul.example.com/api",
+            )
+        }
+    };
+    match env.client_for(&parsed).get(url).call() {
+        Ok(resp) => Check::Ok(format!("network: {} replied {}", url, resp.status())),
+        Err(e) => Check::Fail(
+            format!("network: {} is not reachable: {}", url, e),
+            "check the HTTPS_PROXY/NO_PROXY, LOGJUICER_CA_BUNDLE/LOGJUICER_CA_EXTRA and tls settings",
+        ),
+    }
+}
+
+fn check_api(env: &Env, api_url: &str) -> Check {
+    let url = format!("{}/api/reports", api_url.trim_end_matches('/'));
+    match env.client.get(&url).call() {
+        Ok(_) => Check::Ok(format!("service: {} database is reachable", api_url)),
+        Err(e) => Check::Fail(
+            format!("service: {} failed: {}", url, e),
+            "check that the service is running and that its data/ directory is writable",
+        ),
+    }
+}
+
+/// Train a tiny model and make sure the expected anomaly is found.
+fn check_sample() -> Check {
+    match run_sample() {
+        Ok(anomalies) if anomalies.iter().any(|line| line.contains("KeyError")) => Check::Ok(
+            format!("sample analysis: found {} anomaly", anomalies.len()),
+        ),
+        Ok(anomalies) => Check::Fail(
+            format!("sample analysis: unexpected result {:?}", anomalies),
+            "the model does not behave as expected, please report a bug",
+        ),
+        Err(e) => Check::Fail(
+            format!("sample analysis: {:#}", e),
+            "the model does not behave as expected, please report a bug",
+        ),
+    }
+}
+
+fn run_sample() -> Result<Vec<String>> {
+    let index = IndexTrainer::single(
+        FeaturesMatrixBuilder::default(),
+        false,
+        std::io::Cursor::new(SAMPLE_BASELINE),
+    )?;
+    let mut skip_lines = KnownLines::new();
+    ChunkProcessor::new(
+        std::io::Cursor::new(SAMPLE_TARGET),
+        &index,
+        false,
+        false,
+        &mut skip_lines,
+    )
+    .map(|anomaly| anomaly.map(|anomaly| anomaly.anomaly.line.to_string()))
+    .collect()
+}
+
+#[test]
+fn test_doctor_sample() {
+    assert!(matches!(check_sample(), Check::Ok(_)));
+}
@@ -7,17 +7,21 @@ use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use itertools::Itertools;
 use logjuicer_model::env::{Env, OutputMode};
+use logjuicer_model::progress::{Progress, ProgressCounters};
 use logjuicer_model::{
     content_discover_baselines, content_from_input, content_get_sources, group_sources, Content,
-    FeaturesMatrix, FeaturesMatrixBuilder, Input, Model, Source,
+    FeaturesMatrix, FeaturesMatrixBuilder, Input, Model,
 };
-use logjuicer_report::{bytes_to_mb, Report};
+use logjuicer_report::{bytes_to_mb, AnnotationKind, Compression, Report};
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::time::Instant;
 use time_humanize::{Accuracy, HumanTime, Tense};
 
+mod agent;
 mod dataset;
+mod doctor;
+mod revalidate;
 
 #[derive(Parser)]
 #[clap(version, about, long_about = None)]
@@ -26,9 +30,25 @@ struct Cli {
     #[clap(long, help = "LogJuicer configuration", value_name = "FILE")]
     config: Option<PathBuf>,
 
-    #[clap(long, help = "Create an html report")]
+    #[clap(long, alias = "output", help = "Create an html report")]
     report: Option<PathBuf>,
 
+    #[clap(
+        long,
+        alias = "format",
+        value_enum,
+        help = "The report format, defaults to the report file extension",
+        value_name = "FORMAT"
+    )]
+    report_format: Option<ReportFormat>,
+
+    #[clap(
+        long,
+        help = "Compress the binary report with zstd, e.g. 19 for the smallest file",
+        value_name = "LEVEL"
+    )]
+    zstd_level: Option<i32>,
+
     #[clap(
         hide = true,
         long,
@@ -39,10 +59,98 @@ struct Cli {
     #[clap(long, help = "Load or save the model", value_name = "FILE")]
     model: Option<PathBuf>,
 
+    #[clap(
+        long,
+        help = "Estimate the download size and processing time, without running the analysis"
+    )]
+    estimate: bool,
+
+    #[clap(
+        long,
+        help = "Forbid the network access, the remote contents are read from the local cache"
+    )]
+    offline: bool,
+
+    #[clap(
+        long,
+        help = "Stop the analysis after reading this many bytes",
+        value_name = "BYTES"
+    )]
+    max_bytes: Option<u64>,
+
+    #[clap(
+        long,
+        help = "Stop the analysis after this many files",
+        value_name = "COUNT"
+    )]
+    max_sources: Option<usize>,
+
+    #[clap(
+        long,
+        help = "Only analyze the beginning of the files bigger than this size",
+        value_name = "BYTES"
+    )]
+    max_file_bytes: Option<u64>,
+
+    #[clap(
+        long,
+        help = "Stop the analysis after this duration",
+        value_name = "SECONDS"
+    )]
+    timeout: Option<u64>,
+
     #[clap(subcommand)]
     command: Commands,
 }
 
+/// The report output formats.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum ReportFormat {
+    /// The binary report, with an html page to read it (.bin or .gz)
+    Bin,
+    /// The SARIF 2.1 json, for the code scanning tools (.sarif)
+    Sarif,
+    /// The JUnit XML, for the CI dashboards (.xml)
+    Junit,
+    /// A self-contained html page, to share the results without the web service (.html)
+    Html,
+    /// A compact Markdown summary, for the review comments (.md)
+    Markdown,
+    /// The versioned public json, for the downstream tools (.json)
+    JsonV1,
+    /// The binary report written incrementally, for the huge targets (.ljs)
+    Stream,
+}
+
+/// The report output settings.
+#[derive(Debug)]
+struct ReportOutput {
+    file: PathBuf,
+    format: Option<ReportFormat>,
+    zstd_level: Option<i32>,
+    web_package_url: Option<String>,
+}
+
+impl ReportOutput {
+    fn is_stream(&self) -> bool {
+        match self.format {
+            Some(format) => matches!(format, ReportFormat::Stream),
+            None => self.extension() == Some("ljs"),
+        }
+    }
+
+    fn extension(&self) -> Option<&str> {
+        self.file.extension().and_then(std::ffi::OsStr::to_str)
+    }
+
+    fn compression(&self) -> Compression {
+        match self.zstd_level {
+            Some(level) => Compression::Zstd(level),
+            None => Compression::from_path(&self.file),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     #[clap(about = "Compare targets", allow_missing_positional = true)]
@@ -51,13 +159,255 @@ enum Commands {
     #[clap(about = "Analyze a path")]
     Path { path: String },
 
-    #[clap(about = "Analyze a url")]
-    Url { url: String },
+    #[clap(about = "Analyze a url, the reports of multiple urls are combined")]
+    Url {
+        #[clap(required = true)]
+        url: Vec<String>,
+    },
+
+    #[clap(about = "Analyze the logs of a kubernetes pod, using the kubeconfig credentials")]
+    K8s {
+        #[clap(help = "The pod, e.g. namespace/pod[:container]", value_name = "POD")]
+        pod: String,
+
+        #[clap(
+            long,
+            help = "Read the logs of the previous container instance, e.g. after a crash"
+        )]
+        previous: bool,
+    },
+
+    #[clap(
+        about = "Analyze the steps logs of a Tekton PipelineRun, using the kubeconfig credentials"
+    )]
+    Tekton {
+        #[clap(
+            help = "The pipeline run, e.g. namespace/name",
+            value_name = "PIPELINE_RUN"
+        )]
+        pipeline_run: String,
+    },
+
+    #[clap(about = "Analyze the logs of a docker or podman container, which may be stopped")]
+    Container {
+        #[clap(help = "The container name or id", value_name = "CONTAINER")]
+        container: String,
+
+        #[clap(
+            long,
+            help = "The engine api address, default to DOCKER_HOST or the docker and podman sockets",
+            value_name = "HOST"
+        )]
+        host: Option<String>,
+
+        #[clap(
+            long,
+            help = "The baseline container, default to another container of the same image",
+            value_name = "CONTAINER"
+        )]
+        baseline: Option<String>,
+    },
+
+    #[clap(about = "Analyze the result of a Loki LogQL query for a time range")]
+    Loki {
+        #[clap(long, help = "The loki url, e.g. http://loki:3100", value_name = "URL")]
+        url: String,
+
+        #[clap(
+            long,
+            help = "The start of the time range, a RFC 3339 date or a duration ago, e.g. 2h",
+            value_name = "TIME",
+            default_value = "1h"
+        )]
+        since: String,
+
+        #[clap(
+            long,
+            help = "The end of the time range",
+            value_name = "TIME",
+            default_value = "now"
+        )]
+        until: String,
+
+        #[clap(
+            long,
+            help = "The baseline time range offset, default to one week before",
+            value_name = "DURATION"
+        )]
+        baseline_offset: Option<String>,
+
+        #[clap(help = "The LogQL query, e.g. '{app=\"nginx\"}'", value_name = "QUERY")]
+        query: String,
+    },
+
+    #[clap(
+        about = "Analyze the documents of an Elasticsearch or OpenSearch query for a time range",
+        alias = "opensearch"
+    )]
+    Elasticsearch {
+        #[clap(
+            long,
+            help = "The cluster url, e.g. http://elasticsearch:9200",
+            value_name = "URL"
+        )]
+        url: String,
+
+        #[clap(long, help = "The index name or pattern", value_name = "INDEX")]
+        index: String,
+
+        #[clap(
+            long,
+            help = "The document field of the log line",
+            value_name = "FIELD",
+            default_value = "message"
+        )]
+        message_field: String,
+
+        #[clap(
+            long,
+            help = "The document field of the timestamp",
+            value_name = "FIELD",
+            default_value = "@timestamp"
+        )]
+        time_field: String,
+
+        #[clap(
+            long,
+            help = "The start of the time range, a RFC 3339 date or a duration ago, e.g. 2h",
+            value_name = "TIME",
+            default_value = "1h"
+        )]
+        since: String,
+
+        #[clap(
+            long,
+            help = "The end of the time range",
+            value_name = "TIME",
+            default_value = "now"
+        )]
+        until: String,
+
+        #[clap(
+            long,
+            help = "The baseline time range offset, default to one week before",
+            value_name = "DURATION"
+        )]
+        baseline_offset: Option<String>,
+
+        #[clap(
+            help = "The query string, e.g. 'kubernetes.namespace_name:ci'",
+            value_name = "QUERY",
+            default_value = "*"
+        )]
+        query: String,
+    },
+
+    #[clap(about = "Analyze the events of an AWS CloudWatch log group for a time range")]
+    Cloudwatch {
+        #[clap(
+            long,
+            help = "The log stream name, by default all the group streams are read",
+            value_name = "STREAM"
+        )]
+        stream: Vec<String>,
+
+        #[clap(
+            long,
+            help = "The start of the time range, a RFC 3339 date or a duration ago, e.g. 2h",
+            value_name = "TIME",
+            default_value = "1h"
+        )]
+        since: String,
+
+        #[clap(
+            long,
+            help = "The end of the time range",
+            value_name = "TIME",
+            default_value = "now"
+        )]
+        until: String,
+
+        #[clap(
+            long,
+            help = "The baseline time range offset, default to one week before",
+            value_name = "DURATION"
+        )]
+        baseline_offset: Option<String>,
+
+        #[clap(
+            help = "The log group name, e.g. /aws/lambda/my-function",
+            value_name = "GROUP"
+        )]
+        group: String,
+    },
+
+    #[clap(about = "Analyze the local systemd journal", alias = "journald")]
+    Journal {
+        #[clap(
+            long,
+            help = "The unit name, by default the whole journal is read",
+            value_name = "UNIT"
+        )]
+        unit: Vec<String>,
+
+        #[clap(
+            long,
+            help = "The boot offset or id, e.g. 0 for the current boot, instead of the time range",
+            value_name = "BOOT",
+            allow_hyphen_values = true
+        )]
+        boot: Option<String>,
+
+        #[clap(
+            long,
+            help = "The start of the time range, a RFC 3339 date or a duration ago, e.g. 2h",
+            value_name = "TIME",
+            default_value = "1h"
+        )]
+        since: String,
+
+        #[clap(
+            long,
+            help = "The end of the time range",
+            value_name = "TIME",
+            default_value = "now"
+        )]
+        until: String,
+
+        #[clap(
+            long,
+            help = "The baseline boot offset or id, default to the previous boot",
+            value_name = "BOOT",
+            allow_hyphen_values = true
+        )]
+        baseline_boot: Option<String>,
+
+        #[clap(
+            long,
+            help = "The baseline time range offset, default to one week before",
+            value_name = "DURATION"
+        )]
+        baseline_offset: Option<String>,
+    },
+
+    #[clap(
+        about = "Analyze the lines piped into the standard input, printing the anomalies as they are found"
+    )]
+    Stdin {
+        #[clap(
+            long,
+            help = "The baseline to train the model, instead of the --model file",
+            value_name = "FILE"
+        )]
+        baseline: Vec<String>,
 
-    #[clap(about = "Analyze systemd-journal", allow_missing_positional = true)]
-    Journald {
-        start: Option<String>,
-        range: String,
+        #[clap(
+            long,
+            help = "The stream file name, to select the model index, e.g. job-output.txt",
+            value_name = "NAME",
+            default_value = "stdin"
+        )]
+        name: String,
     },
 
     #[clap(about = "When running in CI, analyze the current build")]
@@ -69,12 +419,37 @@ enum Commands {
         log_root: PathBuf,
     },
 
-    #[clap(about = "Train a model")]
+    #[clap(about = "Analyze local logs and upload the report to a logjuicer-api")]
+    Agent {
+        #[clap(long, help = "The logjuicer-api url", value_name = "URL")]
+        server: String,
+
+        #[clap(long, help = "The name of the model to use", value_name = "NAME")]
+        model_ref: String,
+
+        #[clap(help = "The logs path", value_name = "PATH", default_value = ".")]
+        path: String,
+    },
+
+    #[clap(about = "Train a model, to reuse it with the run command")]
     Train {
-        #[clap(required = true)]
+        #[clap(long, help = "The baseline url or path", value_name = "URL")]
+        baseline: Vec<String>,
+
+        #[clap(short, long, help = "The model file", value_name = "FILE")]
+        output: Option<PathBuf>,
+
         baselines: Vec<String>,
     },
 
+    #[clap(about = "Analyze a target with a pre-built model")]
+    Run {
+        #[clap(long, help = "The model file or url", value_name = "FILE")]
+        model: PathBuf,
+
+        target: String,
+    },
+
     #[clap(about = "Evaluate datasets from the logjuicer-tests project")]
     Test {
         #[clap(required = true)]
@@ -87,9 +462,33 @@ enum Commands {
         max_age: Option<usize>,
     },
 
+    #[clap(about = "Manage the models")]
+    Model {
+        #[clap(subcommand)]
+        command: ModelCommands,
+    },
+
     #[clap(about = "Read a report")]
     ReadReport,
 
+    #[clap(about = "Manage the reports")]
+    Report {
+        #[clap(subcommand)]
+        command: ReportCommands,
+    },
+
+    #[clap(about = "Validate the setup")]
+    Doctor {
+        #[clap(long, help = "The logjuicer-api url to check", value_name = "URL")]
+        api_url: Option<String>,
+
+        #[clap(
+            help = "Endpoints to check, e.g. the Zuul or Prow urls",
+            value_name = "URL"
+        )]
+        urls: Vec<String>,
+    },
+
     // Secret options to debug specific part of the process
     #[clap(hide = true, about = "List http directory urls")]
     HttpLs { url: String },
@@ -110,67 +509,382 @@ enum Commands {
     #[clap(hide = true, about = "Debug index name")]
     DebugIndexname { path: String },
 
-    // Debug saved model
-    #[clap(hide = true, about = "Debug index name")]
-    DebugModel,
+    #[clap(about = "Inspect a model: its baselines, trainer settings and indexes")]
+    DebugModel {
+        #[clap(
+            help = "The model file, default to the --model argument",
+            value_name = "FILE"
+        )]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModelCommands {
+    #[clap(
+        about = "Process the models baselines again to find the ones that need to be retrained"
+    )]
+    Revalidate {
+        #[clap(long, help = "The models directory", value_name = "DIR")]
+        models: PathBuf,
+    },
+
+    #[clap(about = "Convert a model to the portable archive format")]
+    Export {
+        #[clap(help = "The model file", value_name = "FILE")]
+        model: PathBuf,
+
+        #[clap(short, long, help = "The archive path", value_name = "FILE")]
+        output: PathBuf,
+    },
+
+    #[clap(about = "Convert a portable archive to a model")]
+    Import {
+        #[clap(help = "The archive file", value_name = "FILE")]
+        archive: PathBuf,
+
+        #[clap(short, long, help = "The model path", value_name = "FILE")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReportCommands {
+    #[clap(
+        about = "Combine the reports of multiple targets, deduplicating the identical anomalies"
+    )]
+    Merge {
+        #[clap(required = true, help = "The reports to merge", value_name = "FILE")]
+        reports: Vec<PathBuf>,
+
+        #[clap(short, long, help = "The merged report path", value_name = "FILE")]
+        output: PathBuf,
+    },
+
+    #[clap(about = "Show the new, removed and persistent anomalies between two reports")]
+    Diff {
+        #[clap(help = "The previous report", value_name = "FILE")]
+        old: PathBuf,
+
+        #[clap(help = "The current report", value_name = "FILE")]
+        new: PathBuf,
+    },
+
+    #[clap(about = "Print the anomalies of a report")]
+    Show {
+        #[clap(help = "The report", value_name = "FILE")]
+        report: PathBuf,
+
+        #[clap(
+            long,
+            help = "The minimum anomaly distance",
+            value_name = "DISTANCE",
+            default_value = "0"
+        )]
+        min_distance: f32,
+
+        #[clap(
+            long,
+            help = "The source pattern, e.g. '*job-output*'",
+            value_name = "PATTERN"
+        )]
+        source: Option<String>,
+
+        #[clap(long, help = "The regex of the anomaly lines", value_name = "REGEX")]
+        grep: Option<String>,
+    },
+
+    #[clap(about = "Record a triage note for an anomaly, it is kept when the report is merged")]
+    Annotate {
+        #[clap(help = "The report", value_name = "FILE")]
+        report: PathBuf,
+
+        #[clap(
+            help = "The anomaly source path and line number, e.g. 'job-output.txt:42'",
+            value_name = "PATH:LINE"
+        )]
+        anomaly: String,
+
+        #[clap(
+            long,
+            help = "One of: note, false-positive, root-cause",
+            value_name = "KIND",
+            default_value = "note"
+        )]
+        kind: String,
+
+        #[clap(long, help = "The note text", value_name = "TEXT", default_value = "")]
+        note: String,
+
+        #[clap(long, help = "The author, default to $USER", value_name = "NAME")]
+        author: Option<String>,
+    },
 }
 
 impl Cli {
     fn run(self, output: OutputMode) -> Result<()> {
-        let env = Env::new_with_settings(self.config, output)?;
+        if let Commands::Doctor { api_url, urls } = self.command {
+            // The doctor command validates the configuration itself.
+            return doctor::run(self.config, output, &urls, api_url);
+        }
+        let mut env = Env::new_with_settings(self.config, output)?;
+        // The command line limits replace the configuration ones.
+        let limits = env.config.limits().clone();
+        env.config.set_limits(logjuicer_model::config::Limits {
+            max_bytes: self.max_bytes.or(limits.max_bytes),
+            max_sources: self.max_sources.or(limits.max_sources),
+            max_file_bytes: self.max_file_bytes.or(limits.max_file_bytes),
+            timeout_secs: self.timeout.or(limits.timeout_secs),
+            ..limits
+        });
+        if matches!(self.command, Commands::K8s { .. } | Commands::Tekton { .. }) {
+            // The cluster api requests need the kubeconfig credentials.
+            logjuicer_model::k8s::KubeContext::load()?.install(&mut env)?;
+        }
+        if self.offline {
+            env.set_offline()?;
+        }
+        if matches!(output, OutputMode::FastTerminal) {
+            env.progress = std::sync::Arc::new(ProgressBar::default());
+        }
+        if self.estimate {
+            return match self.command {
+                Commands::Path { path } => estimate(&env, None, Input::Path(path)),
+                Commands::Url { url } => url
+                    .into_iter()
+                    .try_for_each(|url| estimate(&env, None, Input::Url(url))),
+                Commands::ZuulBuild { log_root, api_url } => {
+                    estimate(&env, None, Input::ZuulBuild(log_root, api_url))
+                }
+                Commands::K8s { pod, previous } => {
+                    estimate(&env, None, Input::KubernetesPod(pod, previous))
+                }
+                Commands::Diff { src, dst } => estimate(
+                    &env,
+                    Some(src.into_iter().map(Input::from_string).collect()),
+                    Input::from_string(dst),
+                ),
+                _ => Err(anyhow::anyhow!(
+                    "--estimate only works with the path, url, zuul-build, k8s and diff commands"
+                )),
+            };
+        }
+        let report_output = self.report.map(|file| ReportOutput {
+            file,
+            format: self.report_format,
+            zstd_level: self.zstd_level,
+            web_package_url: self.web_package_url,
+        });
         match self.command {
             // Discovery commands
-            Commands::Path { path } => process(
+            Commands::Path { path } => {
+                process(&env, report_output, self.model, None, Input::Path(path))
+            }
+            Commands::Url { url } => process_targets(
+                &env,
+                report_output,
+                self.model,
+                url.into_iter().map(Input::Url).collect(),
+            ),
+            Commands::ZuulBuild { log_root, api_url } => process(
                 &env,
-                self.report,
-                self.web_package_url,
+                report_output,
                 self.model,
                 None,
-                Input::Path(path),
+                Input::ZuulBuild(log_root, api_url),
             ),
-            Commands::Url { url } => process(
+            Commands::K8s { pod, previous } => process(
                 &env,
-                self.report,
-                self.web_package_url,
+                report_output,
                 self.model,
                 None,
-                Input::Url(url),
+                Input::KubernetesPod(pod, previous),
             ),
-            Commands::ZuulBuild { log_root, api_url } => process(
+            Commands::Tekton { pipeline_run } => process(
                 &env,
-                self.report,
-                self.web_package_url,
+                report_output,
                 self.model,
                 None,
-                Input::ZuulBuild(log_root, api_url),
+                Input::TektonPipelineRun(pipeline_run),
+            ),
+            Commands::Container {
+                container,
+                host,
+                baseline,
+            } => process(
+                &env,
+                report_output,
+                self.model,
+                baseline.map(|baseline| vec![Input::Container(host.clone(), baseline)]),
+                Input::Container(host, container),
             ),
-            Commands::Journald { .. } => todo!(),
+            Commands::Loki {
+                url,
+                since,
+                until,
+                baseline_offset,
+                query,
+            } => {
+                let query = logjuicer_model::loki::new_query(&url, &query, &since, &until)?;
+                let baselines = match baseline_offset {
+                    Some(offset) => {
+                        let offset = logjuicer_model::timerange::parse_duration(&offset)?;
+                        Some(vec![Input::LokiQuery(Box::new(query.shift(offset)))])
+                    }
+                    None => None,
+                };
+                process(
+                    &env,
+                    report_output,
+                    self.model,
+                    baselines,
+                    Input::LokiQuery(Box::new(query)),
+                )
+            }
+            Commands::Elasticsearch {
+                url,
+                index,
+                message_field,
+                time_field,
+                since,
+                until,
+                baseline_offset,
+                query,
+            } => {
+                let query = logjuicer_model::elastic::new_query(
+                    &url,
+                    &index,
+                    &query,
+                    &message_field,
+                    &time_field,
+                    &since,
+                    &until,
+                )?;
+                let baselines = match baseline_offset {
+                    Some(offset) => {
+                        let offset = logjuicer_model::timerange::parse_duration(&offset)?;
+                        Some(vec![Input::ElasticQuery(Box::new(query.shift(offset)))])
+                    }
+                    None => None,
+                };
+                process(
+                    &env,
+                    report_output,
+                    self.model,
+                    baselines,
+                    Input::ElasticQuery(Box::new(query)),
+                )
+            }
+            Commands::Cloudwatch {
+                stream,
+                since,
+                until,
+                baseline_offset,
+                group,
+            } => {
+                let query =
+                    logjuicer_model::cloudwatch::new_query(&group, &stream, &since, &until)?;
+                let baselines = match baseline_offset {
+                    Some(offset) => {
+                        let offset = logjuicer_model::timerange::parse_duration(&offset)?;
+                        Some(vec![Input::CloudWatchQuery(Box::new(query.shift(offset)))])
+                    }
+                    None => None,
+                };
+                process(
+                    &env,
+                    report_output,
+                    self.model,
+                    baselines,
+                    Input::CloudWatchQuery(Box::new(query)),
+                )
+            }
+            Commands::Journal {
+                unit,
+                boot,
+                since,
+                until,
+                baseline_boot,
+                baseline_offset,
+            } => {
+                use logjuicer_model::journalctl;
+                let query = journalctl::new_query(&unit, boot.as_deref(), &since, &until)?;
+                let baseline = match (baseline_boot, baseline_offset) {
+                    (Some(boot), _) => Some(journalctl::with_boot(&query, &boot)?),
+                    (None, Some(offset)) => Some(journalctl::shift(
+                        &query,
+                        logjuicer_model::timerange::parse_duration(&offset)?,
+                    )),
+                    (None, None) => None,
+                };
+                process(
+                    &env,
+                    report_output,
+                    self.model,
+                    baseline.map(|baseline| vec![Input::JournalQuery(Box::new(baseline))]),
+                    Input::JournalQuery(Box::new(query)),
+                )
+            }
+            Commands::Stdin { baseline, name } => match report_output {
+                None => process_stdin(&env, self.model, baseline, &name),
+                Some(_) => Err(anyhow::anyhow!(
+                    "--report is not supported with stdin, the anomalies are printed"
+                )),
+            },
+            Commands::Agent {
+                server,
+                model_ref,
+                path,
+            } => agent::run(&env, &server, &model_ref, path),
 
             // Manual commands
             Commands::Diff { src, dst } => process(
                 &env,
-                self.report,
-                self.web_package_url,
+                report_output,
                 self.model,
                 Some(src.into_iter().map(Input::from_string).collect()),
                 Input::from_string(dst),
             ),
-            Commands::Train { baselines } => {
-                let model_path = self.model.ok_or_else(|| {
+            Commands::Train {
+                baseline,
+                output,
+                baselines,
+            } => {
+                let model_path = output.or(self.model).ok_or_else(|| {
                     anyhow::anyhow!(
-                        "A output file path is required, please add a `--model FILE` argument"
+                        "A output file path is required, please add a `-o FILE` argument"
                     )
                 })?;
-                let model = Model::train::<FeaturesMatrixBuilder>(
-                    &env,
-                    baselines
-                        .into_iter()
-                        .map(Input::from_string)
-                        .map(|x| content_from_input(&env, x))
-                        .collect::<Result<Vec<_>>>()?,
-                )?;
+                let baselines = baseline
+                    .into_iter()
+                    .chain(baselines)
+                    .map(Input::from_string)
+                    .map(|x| content_from_input(&env, x))
+                    .collect::<Result<Vec<_>>>()?;
+                if baselines.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "A baseline is required, please add a `--baseline URL` argument"
+                    ));
+                }
+                let model = Model::train::<FeaturesMatrixBuilder>(&env, baselines)?;
                 model.save(&model_path)
             }
+            Commands::Run { model, target } => {
+                // The model is never trained, so that the run stays fast.
+                if !is_url(&model) && !model.exists() {
+                    return Err(anyhow::anyhow!(
+                        "{}: model not found, use the train command to create it",
+                        model.display()
+                    ));
+                }
+                process(
+                    &env,
+                    report_output,
+                    Some(model),
+                    None,
+                    Input::from_string(target),
+                )
+            }
 
             Commands::CheckModel { max_age } => {
                 let model_path = self.model.ok_or_else(|| {
@@ -196,8 +910,22 @@ impl Cli {
                 Ok(())
             }
 
+            Commands::Model { command } => match command {
+                ModelCommands::Revalidate { models } => revalidate::run(&env, &models),
+                ModelCommands::Export { model, output } => {
+                    let model = Model::<FeaturesMatrix>::load(&model)?;
+                    let file =
+                        std::fs::File::create(&output).context("Can't create the archive")?;
+                    logjuicer_model::export::export(&model, std::io::BufWriter::new(file))
+                }
+                ModelCommands::Import { archive, output } => {
+                    let file = std::fs::File::open(&archive).context("Can't open the archive")?;
+                    logjuicer_model::export::import(std::io::BufReader::new(file))?.save(&output)
+                }
+            },
+
             Commands::ReadReport => {
-                let report_path = self.report.ok_or_else(|| {
+                let report_path = report_output.map(|output| output.file).ok_or_else(|| {
                     anyhow::anyhow!(
                         "read-report requires a report, please add a `--report FILE` argument"
                     )
@@ -207,7 +935,40 @@ impl Cli {
                 Ok(())
             }
 
+            Commands::Report { command } => match command {
+                ReportCommands::Merge { reports, output } => merge_reports(&reports, &output),
+                ReportCommands::Diff { old, new } => diff_reports(&old, &new),
+                ReportCommands::Show {
+                    report,
+                    min_distance,
+                    source,
+                    grep,
+                } => {
+                    let mut report = Report::load(&report)?;
+                    logjuicer_report::filter::ReportFilter::new(
+                        min_distance,
+                        source,
+                        grep.as_deref(),
+                    )?
+                    .apply(&mut report);
+                    print_report(report);
+                    Ok(())
+                }
+                ReportCommands::Annotate {
+                    report,
+                    anomaly,
+                    kind,
+                    note,
+                    author,
+                } => {
+                    // The notes may quote the logs, they are redacted like the anomalies.
+                    let note = env.config.redactor().redact(&note);
+                    annotate_report(&report, &anomaly, &kind, &note, author)
+                }
+            },
+
             Commands::Test { datasets } => dataset::test_datasets(&env, &datasets),
+            Commands::Doctor { .. } => unreachable!(),
 
             // Debug handlers
             Commands::HttpLs { url } => {
@@ -222,7 +983,8 @@ impl Cli {
                 Ok(())
             }
             Commands::DebugIndexname { path } => {
-                println!("{}", logjuicer_model::IndexName::from_path(&path));
+                let source = logjuicer_model::Source::from_pathbuf(path.into());
+                println!("{}", logjuicer_model::indexname_from_source(&env, &source));
                 Ok(())
             }
             Commands::DebugIterator { path } => {
@@ -231,15 +993,9 @@ impl Cli {
                 let sources = content_get_sources(&content, &env)?;
                 match sources.first() {
                     Some(source) => {
-                        let reader = match source {
-                            Source::Local(_, path_buf) => {
-                                logjuicer_model::files::file_open(path_buf.as_path())?
-                            }
-                            Source::Remote(prefix, url) => {
-                                logjuicer_model::urls::url_open(&env, *prefix, url)?
-                            }
-                        };
-                        for line in logjuicer_iterator::BytesLines::new(reader, source.is_json()) {
+                        let reader = logjuicer_model::source_open(&env, source)?;
+                        let is_json = logjuicer_model::source_is_json(source);
+                        for line in logjuicer_iterator::BytesLines::new(reader, is_json) {
                             match line {
                                 Ok((bytes, nr)) => match std::str::from_utf8(&bytes) {
                                     Ok(txt) => println!("{} | {}", nr, txt),
@@ -253,11 +1009,9 @@ impl Cli {
                 }
                 Ok(())
             }
-            Commands::DebugModel => {
-                let model_path = self.model.ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "debug-model requires a path, please add a `--model FILE` argument"
-                    )
+            Commands::DebugModel { path } => {
+                let model_path = path.or(self.model).ok_or_else(|| {
+                    anyhow::anyhow!("debug-model requires a path, e.g. `debug-model model.bin`")
                 })?;
                 let model = Model::load(&model_path)?;
                 debug_model(model)
@@ -325,37 +1079,90 @@ fn main() -> Result<()> {
     })
 }
 
+fn is_url(path: &std::path::Path) -> bool {
+    path.to_str()
+        .is_some_and(|s| s.starts_with("http://") || s.starts_with("https://"))
+}
+
+/// Lookup the baselines, when they are not provided.
+fn resolve_baselines(
+    env: &Env,
+    content: &Content,
+    baselines: Option<Vec<Input>>,
+) -> Result<Vec<Content>> {
+    tracing::debug!("Finding baselines");
+    match baselines {
+        None => content_discover_baselines(content, env),
+        Some(baselines) => baselines
+            .into_iter()
+            .map(|x| content_from_input(env, x))
+            .collect::<Result<Vec<_>>>(),
+    }
+}
+
+/// Estimate the size of the target and its baselines.
+fn estimate(env: &Env, baselines: Option<Vec<Input>>, input: Input) -> Result<()> {
+    let content = content_from_input(env, input)?;
+    let mut contents = resolve_baselines(env, &content, baselines)?;
+    contents.push(content);
+    let estimate = logjuicer_model::estimate::estimate(env, &contents)?;
+    clear_progress(env.output);
+    println!(
+        "{} sources, {:.2} MB ({} with unknown size), expected processing time: {}",
+        estimate.sources,
+        bytes_to_mb(estimate.bytes as usize),
+        estimate.unknown,
+        HumanTime::from(std::time::Duration::from_secs(estimate.seconds))
+            .to_text_en(Accuracy::Rough, Tense::Present)
+    );
+    for warning in &estimate.warnings {
+        println!("\x1b[1;33mwarning\x1b[0m: {}", warning);
+    }
+    Ok(())
+}
+
 /// process is the logjuicer implementation after command line parsing.
 #[tracing::instrument(level = "debug", skip(env))]
 fn process(
     env: &Env,
-    report: Option<PathBuf>,
-    web_package_url: Option<String>,
+    report_output: Option<ReportOutput>,
     model_path: Option<PathBuf>,
     baselines: Option<Vec<Input>>,
     input: Input,
 ) -> Result<()> {
     // Convert user Input to target Content.
     let content = content_from_input(env, input)?;
+    let model = load_model(env, &model_path, baselines, &content)?;
 
+    tracing::debug!("Inspecting");
+    match report_output {
+        None => process_live(env, &content, &model),
+        Some(output) if output.is_stream() => write_stream(env, &model, content, &output.file),
+        Some(output) => write_report(env, model.report(env, content)?, output),
+    }
+}
+
+/// Load, fetch or train the model of a target, the trained model is saved when a path is provided.
+fn load_model(
+    env: &Env,
+    model_path: &Option<PathBuf>,
+    baselines: Option<Vec<Input>>,
+    content: &Content,
+) -> Result<Model<FeaturesMatrix>> {
     let train_model = |baselines: Option<Vec<Input>>| {
-        // Lookup baselines.
-        tracing::debug!("Finding baselines");
-        let baselines = match baselines {
-            None => content_discover_baselines(&content, env),
-            Some(baselines) => baselines
-                .into_iter()
-                .map(|x| content_from_input(env, x))
-                .collect::<Result<Vec<_>>>(),
-        }?;
+        let baselines = resolve_baselines(env, content, baselines)?;
 
-        // Create the model. TODO: enable custom index.
+        // Create the model, or reuse the stored one. TODO: enable custom index.
         tracing::debug!("Building model");
-        Model::<FeaturesMatrix>::train::<FeaturesMatrixBuilder>(env, baselines)
+        logjuicer_model::store::train::<FeaturesMatrix, FeaturesMatrixBuilder>(env, baselines)
     };
 
     let model = match model_path {
-        Some(ref path) if path.exists() => match baselines {
+        Some(path) if is_url(path) => match baselines {
+            None => agent::fetch_model(env, &path.to_string_lossy()),
+            Some(_) => Err(anyhow::anyhow!("Ambiguous baselines and model provided")),
+        },
+        Some(path) if path.exists() => match baselines {
             None => match Model::load(path) {
                 Ok(model) => Ok(model),
                 Err(e) => {
@@ -370,42 +1177,156 @@ fn process(
     }?;
 
     match model_path {
-        Some(ref path) if !path.exists() => {
+        Some(path) if !path.exists() && !is_url(path) => {
             clear_progress(env.output);
             model.save(path)
         }
         _ => Ok(()),
     }?;
+    Ok(model)
+}
 
-    tracing::debug!("Inspecting");
-    match report {
-        None => process_live(env, &content, &model),
-        Some(file) => {
-            let report = model.report(env, content)?;
-
-            match file.extension().and_then(std::ffi::OsStr::to_str) {
-                Some("bin") | Some("gz") => {
-                    report
-                        .save(&file)
-                        .context("Failed to write the binary report")?;
-                    write_html(&file, web_package_url)
-                }
-                .context("Failed to write the report"),
-                _ => Err(anyhow::anyhow!("Unknown report extension {:?}", file)),
-            }?;
-            tracing::info!("Wrote report {:?}", file);
-            Ok(())
+/// Analyze multiple targets, each with their own baselines, and combine their reports.
+fn process_targets(
+    env: &Env,
+    report_output: Option<ReportOutput>,
+    model_path: Option<PathBuf>,
+    mut inputs: Vec<Input>,
+) -> Result<()> {
+    if inputs.len() == 1 {
+        return process(env, report_output, model_path, None, inputs.remove(0));
+    }
+    if let Some(path) = &model_path {
+        if !path.exists() && !is_url(path) {
+            // The model of the first target would be used for the other targets.
+            return Err(anyhow::anyhow!(
+                "The model must exist to analyze multiple targets: {:?}",
+                path
+            ));
+        }
+    }
+    match report_output {
+        None => inputs
+            .into_iter()
+            .try_for_each(|input| process(env, None, model_path.clone(), None, input)),
+        Some(output) => {
+            let reports = inputs
+                .into_iter()
+                .map(|input| {
+                    let content = content_from_input(env, input)?;
+                    let model = load_model(env, &model_path, None, &content)?;
+                    env.debug_or_progress(&format!("Analyzing {}", content));
+                    model.report(env, content)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let report = logjuicer_report::merge::merge(reports)
+                .ok_or_else(|| anyhow::anyhow!("No reports to merge"))?;
+            write_report(env, report, output)
         }
     }
 }
 
-fn process_live(env: &Env, content: &Content, model: &Model<FeaturesMatrix>) -> Result<()> {
+/// Write a complete report, according to the output format.
+fn write_report(env: &Env, report: Report, output: ReportOutput) -> Result<()> {
+    clear_progress(env.output);
+    for warning in &report.warnings {
+        tracing::warn!("{}", warning);
+    }
+
+    let file = &output.file;
+    let extension = file.extension().and_then(std::ffi::OsStr::to_str);
+    match (output.format, extension) {
+        (Some(ReportFormat::Bin), _) | (None, Some("bin") | Some("gz") | Some("zst")) => {
+            let compression = output.compression();
+            report
+                .save_compressed(file, compression)
+                .context("Failed to write the binary report")?;
+            // The web client can not decode the zstd reports.
+            if matches!(compression, Compression::Zstd(_)) {
+                Ok(())
+            } else {
+                write_html(file, output.web_package_url)
+            }
+        }
+        .context("Failed to write the report"),
+        (Some(ReportFormat::Sarif), _) | (None, Some("sarif")) => write_sarif(&report, file),
+        (Some(ReportFormat::Junit), _) | (None, Some("xml")) => write_junit(&report, file),
+        (Some(ReportFormat::Html), _) | (None, Some("html")) => write_static_html(&report, file),
+        (Some(ReportFormat::Markdown), _) | (None, Some("md")) => write_markdown(&report, file),
+        (Some(ReportFormat::JsonV1), _) | (None, Some("json")) => write_json_v1(&report, file),
+        (Some(ReportFormat::Stream), _) | (None, Some("ljs")) => write_stream_report(report, file),
+        (None, _) => Err(anyhow::anyhow!("Unknown report extension {:?}", file)),
+    }?;
+    tracing::info!("Wrote report {:?}", file);
+    Ok(())
+}
+
+/// Write a complete report in the streaming format.
+fn write_stream_report(mut report: Report, file: &std::path::Path) -> Result<()> {
+    let dest = std::io::BufWriter::new(
+        std::fs::File::create(file).with_context(|| format!("Failed to create {:?}", file))?,
+    );
+    let mut writer = logjuicer_report::stream::StreamWriter::new(dest)?;
+    for lr in std::mem::take(&mut report.log_reports) {
+        writer.append(&lr)?;
+    }
+    writer
+        .finish(&report)
+        .context("Failed to write the report")?;
+    Ok(())
+}
+
+/// Write the log reports as soon as they are created, to keep the memory usage low.
+fn write_stream(
+    env: &Env,
+    model: &Model<FeaturesMatrix>,
+    content: Content,
+    file: &std::path::Path,
+) -> Result<()> {
+    let dest = std::io::BufWriter::new(
+        std::fs::File::create(file).with_context(|| format!("Failed to create {:?}", file))?,
+    );
+    let mut writer = logjuicer_report::stream::StreamWriter::new(dest)?;
+    let report = model.report_with(env, content, |lr| Ok(writer.append(&lr)?))?;
+    clear_progress(env.output);
+    for warning in &report.warnings {
+        tracing::warn!("{}", warning);
+    }
+    writer
+        .finish(&report)
+        .context("Failed to write the report")?;
+    tracing::info!("Wrote report {:?}", file);
+    Ok(())
+}
+
+/// Print an anomaly with its context, separating the non contiguous anomalies.
+fn print_anomaly(last_pos: &mut Option<usize>, anomaly: &logjuicer_model::AnomalyContext) {
     let print_context = |pos: usize, xs: &[Rc<str>]| {
         xs.iter()
             .enumerate()
             .for_each(|(idx, line)| println!("   {} | {}", pos + idx, line))
     };
+    let context_size = 1 + anomaly.before.len();
+    let starting_pos = anomaly.anomaly.pos.saturating_sub(context_size);
+    if let Some(last_pos) = last_pos {
+        if *last_pos < starting_pos {
+            println!("--");
+        }
+    }
+
+    print_context(starting_pos, &anomaly.before);
+    println!(
+        "{:02.0} {} | {}",
+        anomaly.anomaly.distance * 99.0,
+        anomaly.anomaly.pos,
+        anomaly.anomaly.line
+    );
+    print_context(anomaly.anomaly.pos, &anomaly.after);
+
+    *last_pos = Some(anomaly.anomaly.pos + anomaly.after.len());
+}
 
+fn process_live(env: &Env, content: &Content, model: &Model<FeaturesMatrix>) -> Result<()> {
     let mut progress_sep_shown = false;
     let mut total_line_count = 0;
     let mut total_byte_count = 0;
@@ -413,35 +1334,10 @@ fn process_live(env: &Env, content: &Content, model: &Model<FeaturesMatrix>) ->
     let start_time = Instant::now();
 
     for source in content_get_sources(content, env)? {
-        let index_name = logjuicer_model::indexname_from_source(&source);
+        let index_name = logjuicer_model::indexname_from_source(env, &source);
         match model.get_index(&index_name) {
             Some(index) => {
                 let mut last_pos = None;
-                let mut print_anomaly = |anomaly: logjuicer_model::AnomalyContext| {
-                    total_anomaly_count += 1;
-                    let context_size = 1 + anomaly.before.len();
-                    let starting_pos = if anomaly.anomaly.pos > context_size {
-                        anomaly.anomaly.pos - context_size
-                    } else {
-                        0
-                    };
-                    if let Some(last_pos) = last_pos {
-                        if last_pos < starting_pos {
-                            println!("--");
-                        }
-                    }
-
-                    print_context(starting_pos, &anomaly.before);
-                    println!(
-                        "{:02.0} {} | {}",
-                        anomaly.anomaly.distance * 99.0,
-                        anomaly.anomaly.pos,
-                        anomaly.anomaly.line
-                    );
-                    print_context(anomaly.anomaly.pos, &anomaly.after);
-
-                    last_pos = Some(anomaly.anomaly.pos + anomaly.after.len());
-                };
                 progress_sep_shown = false;
                 match index.get_processor(
                     env,
@@ -456,7 +1352,10 @@ fn process_live(env: &Env, content: &Content, model: &Model<FeaturesMatrix>) ->
                                 progress_sep_shown = true;
                             }
                             match anomaly {
-                                Ok(anomaly) => print_anomaly(anomaly),
+                                Ok(anomaly) => {
+                                    total_anomaly_count += 1;
+                                    print_anomaly(&mut last_pos, &anomaly)
+                                }
                                 Err(err) => {
                                     println!("Could not read {}: {}", &source, err);
                                     break;
@@ -496,6 +1395,55 @@ fn process_live(env: &Env, content: &Content, model: &Model<FeaturesMatrix>) ->
     Ok(())
 }
 
+/// Analyze the standard input with a model, printing the anomalies as soon as they are found.
+fn process_stdin(
+    env: &Env,
+    model_path: Option<PathBuf>,
+    baselines: Vec<String>,
+    name: &str,
+) -> Result<()> {
+    let model = match (model_path, baselines.is_empty()) {
+        (Some(path), true) if is_url(&path) => agent::fetch_model(env, &path.to_string_lossy()),
+        (Some(path), true) => Model::load(&path),
+        (None, false) => Model::train::<FeaturesMatrixBuilder>(
+            env,
+            baselines
+                .into_iter()
+                .map(Input::from_string)
+                .map(|x| content_from_input(env, x))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        (Some(_), false) => Err(anyhow::anyhow!("Ambiguous baselines and model provided")),
+        (None, true) => Err(anyhow::anyhow!(
+            "stdin requires a model, please add a `--model FILE` or a `--baseline FILE` argument"
+        )),
+    }?;
+    clear_progress(env.output);
+
+    let source = logjuicer_model::Source::from_pathbuf(name.into());
+    let index_name = logjuicer_model::indexname_from_source(env, &source);
+    let index = model.get_index(&index_name).ok_or_else(|| {
+        anyhow::anyhow!(
+            "No baselines for {}, please use the `--name` of one of: {}",
+            index_name,
+            model.indexes.keys().sorted().format(", ")
+        )
+    })?;
+    let reader = logjuicer_model::stream_open(&source, std::io::stdin())?;
+    let mut skip_lines = logjuicer_model::unordered::KnownLines::new();
+    let mut processor = index.get_reader_processor(env, &source, reader, &mut skip_lines);
+    let mut last_pos = None;
+    for anomaly in processor.by_ref() {
+        print_anomaly(&mut last_pos, &anomaly?);
+    }
+    tracing::debug!(
+        lines = processor.line_count,
+        bytes = processor.byte_count,
+        "Completed stdin"
+    );
+    Ok(())
+}
+
 fn debug_groups(env: &Env, input: Input) -> Result<()> {
     let content = content_from_input(env, input)?;
     for (index_name, sources) in group_sources(env, &[content])?
@@ -519,6 +1467,8 @@ fn print_created(time: std::time::SystemTime) {
 
 fn debug_model(model: Model<FeaturesMatrix>) -> Result<()> {
     print_created(model.created_at);
+    println!("trainer: {}", model.trainer());
+    println!("hash: {}", model.hash());
     println!("baselines:");
     model.baselines.iter().for_each(|content| {
         println!("  {}", content);
@@ -539,18 +1489,122 @@ fn debug_model(model: Model<FeaturesMatrix>) -> Result<()> {
             println!("  samples: {}", index.samples_count());
             index.sources.iter().for_each(|source| {
                 println!("  from: {}", source);
+            });
+            index.samples.iter().for_each(|line| {
+                println!("  line: {}", line);
             })
         });
     Ok(())
 }
 
+/// The number of cluster anomalies displayed by print_report.
+const CLUSTER_MEMBERS: usize = 3;
+
 fn print_report(report: Report) {
     print_created(report.created_at);
     println!("target: {}", report.target);
+    if !report.summary.is_empty() {
+        println!("top anomalies:");
+        report.summary.iter().for_each(|entry| {
+            println!(
+                "  {}x {:.2} {}:{}: {}",
+                entry.count, entry.distance, entry.source, entry.pos, entry.line
+            )
+        });
+    }
+    if !report.clusters.is_empty() {
+        println!("clusters:");
+        report.clusters.iter().for_each(|cluster| {
+            println!(
+                "  {} anomalies in {} sources: {}",
+                cluster.members.len(),
+                cluster.source_count(),
+                cluster.line
+            );
+            cluster
+                .anomalies(&report.log_reports)
+                .take(CLUSTER_MEMBERS)
+                .for_each(|(log_report, anomaly_context)| {
+                    println!("    {}:{}", log_report.source, anomaly_context.anomaly.pos)
+                });
+            if cluster.members.len() > CLUSTER_MEMBERS {
+                println!("    and {} more", cluster.members.len() - CLUSTER_MEMBERS);
+            }
+        });
+    }
+    if !report.failed_tests.is_empty() {
+        println!("failed tests:");
+        report.failed_tests.iter().for_each(|test| {
+            println!("  {}: {}", test.name, test.message);
+            test.anomalies(&report.log_reports)
+                .for_each(|(log_report, anomaly_context)| {
+                    println!(
+                        "    {}:{}: {}",
+                        log_report.source,
+                        anomaly_context.anomaly.pos,
+                        anomaly_context.anomaly.line
+                    )
+                });
+        });
+    }
     println!("baselines:");
     report.baselines.iter().for_each(|content| {
         println!("  {}", content);
     });
+    if let Some(provenance) = &report.provenance {
+        println!("provenance:");
+        println!("  version: {}", provenance.version);
+        println!("  model: {}", provenance.model_hash);
+        println!("  trainer: {}", provenance.trainer);
+        println!("  baseline builds: {}", provenance.baseline_count);
+        provenance.baseline_sources.iter().for_each(|source| {
+            println!("  from: {}", source);
+        });
+    }
+    if !report.merged_targets.is_empty() {
+        println!("merged targets:");
+        report
+            .target_breakdown()
+            .iter()
+            .enumerate()
+            .for_each(|(idx, (content, count))| {
+                println!("  {}: {} ({} anomalies)", idx, content, count)
+            });
+    }
+    println!("categories:");
+    report
+        .category_breakdown()
+        .iter()
+        .for_each(|(category, count)| println!("  {}: {}", category, count));
+    println!("policies:");
+    report.category_policies.iter().for_each(|policy| {
+        println!(
+            "  {}: threshold {}, context {}, gate {}",
+            policy.category, policy.threshold, policy.context, policy.gate
+        )
+    });
+    println!("gating anomalies: {}", report.gating_anomaly_count());
+    if !report.warnings.is_empty() {
+        println!("warnings:");
+        report
+            .warnings
+            .iter()
+            .for_each(|warning| println!("  {}", warning));
+    }
+    if !report.source_stats.is_empty() {
+        println!("slowest sources:");
+        report.slowest_sources(10).iter().for_each(|stats| {
+            println!(
+                "  {}: {} msec download, {} msec analysis, {:.2} MB, {}/{} unique lines",
+                stats.source,
+                stats.download_time.as_millis(),
+                stats.analysis_time.as_millis(),
+                bytes_to_mb(stats.byte_count),
+                stats.unique_line_count,
+                stats.line_count
+            )
+        });
+    }
     println!("logs:");
     report.log_reports.iter().for_each(|log_report| {
         println!("- {}", log_report.source);
@@ -560,21 +1614,178 @@ fn print_report(report: Report) {
             log_report.line_count,
             bytes_to_mb(log_report.byte_count)
         );
+        if let Some(category) = &log_report.category {
+            println!("  category: {}", category);
+        }
         log_report.anomalies.iter().for_each(|anomaly_context| {
             println!(
                 "  {}: {}",
                 anomaly_context.anomaly.pos, anomaly_context.anomaly.line
             );
+            if !anomaly_context.targets.is_empty() {
+                println!("    targets: {:?}", anomaly_context.targets);
+            }
+            report
+                .annotations_of(log_report, anomaly_context)
+                .for_each(|annotation| println!("    annotation: {}", annotation));
         })
     })
 }
 
+fn merge_reports(paths: &[PathBuf], output: &std::path::Path) -> Result<()> {
+    let reports = paths
+        .iter()
+        .map(|path| Report::load(path).with_context(|| format!("Failed to load {:?}", path)))
+        .collect::<Result<Vec<_>>>()?;
+    let report = logjuicer_report::merge::merge(reports)
+        .ok_or_else(|| anyhow::anyhow!("No reports to merge"))?;
+    report
+        .save(output)
+        .context("Failed to write the merged report")?;
+    println!(
+        "{:?}: {} anomalies from {} targets",
+        output,
+        report.anomaly_count(),
+        report.merged_targets.len()
+    );
+    Ok(())
+}
+
+fn annotate_report(
+    path: &std::path::Path,
+    anomaly: &str,
+    kind: &str,
+    note: &str,
+    author: Option<String>,
+) -> Result<()> {
+    let kind = match kind {
+        "note" | "false-positive" | "root-cause" => AnnotationKind::from(kind),
+        _ => anyhow::bail!("Unknown annotation kind: {}", kind),
+    };
+    let (source, pos) = anomaly
+        .rsplit_once(':')
+        .and_then(|(source, pos)| Some((source, pos.parse::<usize>().ok()?)))
+        .ok_or_else(|| anyhow::anyhow!("Invalid anomaly {}, expected PATH:LINE", anomaly))?;
+    let author = author
+        .or_else(|| std::env::var("USER").ok())
+        .unwrap_or_default();
+    logjuicer_report::annotate::annotate_file(path, source, pos, kind, note, &author)
+        .with_context(|| format!("Failed to annotate {:?}", path))?
+        .ok_or_else(|| anyhow::anyhow!("No anomaly found at {}", anomaly))?;
+    println!("{:?}: annotated {}", path, anomaly);
+    Ok(())
+}
+
+fn diff_reports(old: &std::path::Path, new: &std::path::Path) -> Result<()> {
+    let old = Report::load(old).with_context(|| format!("Failed to load {:?}", old))?;
+    let new = Report::load(new).with_context(|| format!("Failed to load {:?}", new))?;
+    let diff = logjuicer_report::diff::diff(&old, &new);
+    let print_anomalies =
+        |title: &str, mark: &str, anomalies: &[logjuicer_report::diff::AnomalyRef]| {
+            println!("{}: {}", title, anomalies.len());
+            anomalies.iter().for_each(|(log_report, anomaly_context)| {
+                println!(
+                    "{} {}:{}: {}",
+                    mark,
+                    log_report.source.get_relative(),
+                    anomaly_context.anomaly.pos,
+                    anomaly_context.anomaly.line
+                )
+            });
+        };
+    print_anomalies("new", "+", &diff.added);
+    print_anomalies("removed", "-", &diff.removed);
+    print_anomalies("persistent", "=", &diff.persistent);
+    if diff.is_same() {
+        println!("The reports have the same anomalies");
+    }
+    Ok(())
+}
+
 fn clear_progress(output_mode: OutputMode) {
     if output_mode.inlined() {
         print!("\r\x1b[K");
     }
 }
 
+/// The progress bar of the terminal output.
+#[derive(Default)]
+struct ProgressBar {
+    counters: ProgressCounters,
+}
+
+impl ProgressBar {
+    const WIDTH: usize = 20;
+
+    fn draw(&self) {
+        use std::io::Write;
+        let (analyzed, files) = (self.counters.analyzed(), self.counters.files());
+        let filled = (analyzed.min(files) * Self::WIDTH)
+            .checked_div(files)
+            .unwrap_or(0);
+        print!(
+            "\r\x1b[K\x1b[1;33m[+]\x1b[0m [{}{}] {}",
+            "#".repeat(filled),
+            " ".repeat(Self::WIDTH - filled),
+            self.counters
+        );
+        let _ = std::io::stdout().flush();
+    }
+}
+
+impl Progress for ProgressBar {
+    fn files_discovered(&self, count: usize) {
+        self.counters.files_discovered(count);
+        self.draw()
+    }
+
+    fn bytes_downloaded(&self, source: &logjuicer_report::Source, count: usize) {
+        self.counters.bytes_downloaded(source, count)
+    }
+
+    fn file_analyzed(&self, source: &logjuicer_report::Source) {
+        self.counters.file_analyzed(source);
+        self.draw()
+    }
+
+    fn anomalies_found(&self, source: &logjuicer_report::Source, count: usize) {
+        self.counters.anomalies_found(source, count)
+    }
+}
+
+fn write_sarif(report: &Report, file: &std::path::Path) -> Result<()> {
+    let sarif = logjuicer_report::sarif::Sarif::from_report(report, env!("CARGO_PKG_VERSION"));
+    let dest = std::fs::File::create(file).context("Failed to create the sarif file")?;
+    sarif
+        .save_writer(std::io::BufWriter::new(dest))
+        .context("Failed to write the sarif report")
+}
+
+fn write_junit(report: &Report, file: &std::path::Path) -> Result<()> {
+    let dest = std::fs::File::create(file).context("Failed to create the junit file")?;
+    logjuicer_report::junit::save_writer(report, std::io::BufWriter::new(dest))
+        .context("Failed to write the junit report")
+}
+
+fn write_static_html(report: &Report, file: &std::path::Path) -> Result<()> {
+    let dest = std::fs::File::create(file).context("Failed to create the html file")?;
+    logjuicer_report::html::save_writer(report, std::io::BufWriter::new(dest))
+        .context("Failed to write the html report")
+}
+
+fn write_markdown(report: &Report, file: &std::path::Path) -> Result<()> {
+    let dest = std::fs::File::create(file).context("Failed to create the markdown file")?;
+    logjuicer_report::markdown::save_writer(report, std::io::BufWriter::new(dest))
+        .context("Failed to write the markdown report")
+}
+
+fn write_json_v1(report: &Report, file: &std::path::Path) -> Result<()> {
+    let json = logjuicer_report::json_v1::ReportV1::from_report(report);
+    let dest = std::fs::File::create(file).context("Failed to create the json file")?;
+    json.save_writer(std::io::BufWriter::new(dest))
+        .context("Failed to write the json report")
+}
+
 fn write_html(report: &std::path::Path, web_package_url: Option<String>) -> Result<()> {
     let version = env!("CARGO_PKG_VERSION");
     let assets_url = match web_package_url {
@@ -0,0 +1,104 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the logic for the model revalidate command.
+//! The baselines of the stored models are processed again with the current tokenizer and index code:
+//! a baseline line should never be an anomaly of its own model, otherwise the model needs to be retrained.
+
+use anyhow::Result;
+use std::path::Path;
+
+use logjuicer_model::env::Env;
+use logjuicer_model::unordered::KnownLines;
+use logjuicer_model::{FeaturesMatrix, Model};
+
+/// The result of a model revalidation.
+enum Status {
+    Valid { lines: usize },
+    Drift { anomalies: usize, lines: usize },
+    Unavailable(String),
+    Invalid(String),
+}
+
+impl Status {
+    fn needs_retraining(&self) -> bool {
+        matches!(self, Status::Drift { .. } | Status::Invalid(_))
+    }
+
+    fn print(&self, path: &Path) {
+        let path = path.display();
+        match self {
+            Status::Valid { lines } => println!("[ok]      {}: {} baseline lines", path, lines),
+            Status::Drift { anomalies, lines } => println!(
+                "[retrain] {}: {} anomalies found in {} baseline lines",
+                path, anomalies, lines
+            ),
+            Status::Unavailable(err) => println!("[skip]    {}: {}", path, err),
+            Status::Invalid(err) => println!("[retrain] {}: {}", path, err),
+        }
+    }
+}
+
+pub fn run(env: &Env, models: &Path) -> Result<()> {
+    let mut paths = std::fs::read_dir(models)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "bin"));
+    paths.sort();
+
+    let mut retrain = 0;
+    for path in &paths {
+        let status = revalidate(env, path);
+        status.print(path);
+        if status.needs_retraining() {
+            retrain += 1;
+        }
+    }
+    if retrain == 0 {
+        println!("{} model(s) are valid", paths.len());
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("{} model(s) need to be retrained", retrain))
+    }
+}
+
+fn revalidate(env: &Env, path: &Path) -> Status {
+    let model = match Model::<FeaturesMatrix>::load(path) {
+        Ok(model) => model,
+        Err(e) => return Status::Invalid(format!("{:#}", e)),
+    };
+    let tokenizer = env.config.tokenizer_settings();
+    if model.tokenizer.as_ref() != tokenizer {
+        return Status::Invalid(format!(
+            "the model was trained with '{}', but the current settings are '{}'",
+            model.tokenizer, tokenizer
+        ));
+    }
+    let (mut anomalies, mut lines) = (0, 0);
+    for (index_name, index) in &model.indexes {
+        env.debug_or_progress(&format!(
+            "Revalidating {} index {}",
+            path.display(),
+            index_name
+        ));
+        let mut skip_lines = KnownLines::new();
+        for source in &index.sources {
+            let mut processor = match index.get_processor(env, source, &mut skip_lines) {
+                Ok(processor) => processor,
+                Err(e) => return Status::Unavailable(format!("{}: {:#}", source, e)),
+            };
+            for anomaly in processor.by_ref() {
+                match anomaly {
+                    Ok(_) => anomalies += 1,
+                    Err(e) => return Status::Unavailable(format!("{}: {:#}", source, e)),
+                }
+            }
+            lines += processor.line_count;
+        }
+    }
+    if anomalies == 0 {
+        Status::Valid { lines }
+    } else {
+        Status::Drift { anomalies, lines }
+    }
+}
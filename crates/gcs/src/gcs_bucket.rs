@@ -0,0 +1,411 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+#![warn(missing_docs)]
+
+//! This library provides a minimal client for the [gcs](https://cloud.google.com/storage) buckets.
+//!
+//! The objects are addressed with `gs://bucket/name` urls. The public buckets, such as the prow
+//! artifacts, are accessed anonymously, otherwise a service account key can be used to get an access token.
+//!
+//! Here is an example usage:
+//!
+//! ```no_run
+//! # fn main() {
+//! let client = gcs_bucket::Client::from_env().unwrap();
+//! let agent = ureq::Agent::new();
+//! for object in client.list_objects(&agent, "origin-ci-test", "pr-logs/directory/my-job/").unwrap() {
+//!     println!("{}: {}", object.name, object.size);
+//! }
+//! # }
+//! ```
+
+use serde::Deserialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use thiserror::Error;
+use url::Url;
+
+/// The gcs error.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The provided url is not usable.
+    #[error("bad url: {0}")]
+    BadUrl(#[from] url::ParseError),
+
+    /// The api reply couldn't be read.
+    #[error("bad api reply: {0}")]
+    BadReply(#[from] std::io::Error),
+
+    /// The api query failed.
+    #[error("bad api query: {0}")]
+    BadQuery(#[from] Box<ureq::Error>),
+
+    /// The service account key is not usable.
+    #[error("bad service account: {0}")]
+    BadServiceAccount(String),
+}
+
+/// The read-only scope requested for the access token.
+const SCOPE: &str = "https://www.googleapis.com/auth/devstorage.read_only";
+
+/// The location of an object, or of an objects prefix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectUrl {
+    /// The bucket name.
+    pub bucket: String,
+    /// The object name, without the leading slash.
+    pub name: String,
+}
+
+/// Decode a `gs://bucket/name` url.
+pub fn parse_gs_url(url: &Url) -> Option<ObjectUrl> {
+    if url.scheme() != "gs" {
+        return None;
+    }
+    let bucket = url.host_str().filter(|bucket| !bucket.is_empty())?;
+    Some(ObjectUrl {
+        bucket: bucket.to_string(),
+        name: percent_decode(url.path().trim_start_matches('/')),
+    })
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let decoded = if bytes[pos] == b'%' {
+            value
+                .get(pos + 1..pos + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match decoded {
+            Some(byte) => {
+                result.push(byte);
+                pos += 3;
+            }
+            None => {
+                result.push(bytes[pos]);
+                pos += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+/// Create the `gs://bucket/name` url of an object.
+pub fn object_url(bucket: &str, name: &str) -> Result<Url, Error> {
+    let mut url = Url::parse(&format!("gs://{}/", bucket))?;
+    url.path_segments_mut()
+        .map_err(|()| Error::BadUrl(url::ParseError::RelativeUrlWithCannotBeABaseBase))?
+        .pop_if_empty()
+        .extend(name.split('/'));
+    Ok(url)
+}
+
+/// A bucket object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Object {
+    /// The object name.
+    pub name: String,
+    /// The object size in bytes.
+    pub size: u64,
+}
+
+/// The service account key, as downloaded from the cloud console.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ServiceAccount {
+    /// The account email.
+    pub client_email: String,
+    /// The PKCS#8 private key, in the PEM format.
+    pub private_key: String,
+    /// The url to exchange the signed assertion for an access token.
+    pub token_uri: String,
+}
+
+/// The bucket client.
+#[derive(Debug)]
+pub struct Client {
+    /// The api endpoint, e.g. `https://storage.googleapis.com/`.
+    pub endpoint: Url,
+    /// The service account, the requests are anonymous when it is missing.
+    pub service_account: Option<ServiceAccount>,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+#[derive(Deserialize)]
+struct TokenReply {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct ObjectReply {
+    name: String,
+    // The api returns the int64 values as strings.
+    #[serde(default)]
+    size: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListReply {
+    #[serde(default)]
+    items: Vec<ObjectReply>,
+    next_page_token: Option<String>,
+}
+
+fn base64url(data: &[u8]) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(data)
+}
+
+impl ServiceAccount {
+    /// Create the signed JWT assertion.
+    fn assertion(&self, now: u64) -> Result<String, Error> {
+        let bad_key = |e: &dyn std::fmt::Display| Error::BadServiceAccount(e.to_string());
+        let der = rustls_pemfile::pkcs8_private_keys(&mut self.private_key.as_bytes())?
+            .pop()
+            .ok_or_else(|| bad_key(&"missing private key"))?;
+        let key_pair = ring::signature::RsaKeyPair::from_pkcs8(&der).map_err(|e| bad_key(&e))?;
+        let header = base64url(br#"{"alg":"RS256","typ":"JWT"}"#);
+        let claims = base64url(
+            serde_json::json!({
+                "iss": self.client_email,
+                "scope": SCOPE,
+                "aud": self.token_uri,
+                "iat": now,
+                "exp": now + 3600,
+            })
+            .to_string()
+            .as_bytes(),
+        );
+        let message = format!("{}.{}", header, claims);
+        let mut signature = vec![0; key_pair.public_modulus_len()];
+        key_pair
+            .sign(
+                &ring::signature::RSA_PKCS1_SHA256,
+                &ring::rand::SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|e| bad_key(&e))?;
+        Ok(format!("{}.{}", message, base64url(&signature)))
+    }
+}
+
+impl Client {
+    /// Create an anonymous client, for the public buckets.
+    pub fn anonymous() -> Client {
+        Client::new(None)
+    }
+
+    /// Create a client using the service account key of the `GOOGLE_APPLICATION_CREDENTIALS` file,
+    /// or an anonymous client when the variable is not set.
+    /// The `STORAGE_EMULATOR_HOST` variable can be used to change the endpoint.
+    pub fn from_env() -> Result<Client, Error> {
+        let mut client = match std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS") {
+            Some(path) => {
+                let file = std::fs::File::open(path)?;
+                let account = serde_json::from_reader(std::io::BufReader::new(file))
+                    .map_err(|e| Error::BadServiceAccount(e.to_string()))?;
+                Client::new(Some(account))
+            }
+            None => Client::anonymous(),
+        };
+        if let Ok(endpoint) = std::env::var("STORAGE_EMULATOR_HOST") {
+            client.endpoint = Url::parse(&endpoint)?;
+        }
+        Ok(client)
+    }
+
+    fn new(service_account: Option<ServiceAccount>) -> Client {
+        Client {
+            endpoint: Url::parse("https://storage.googleapis.com/").expect("Valid endpoint"),
+            service_account,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Get the authorization header value, the access token is renewed before it expires.
+    fn authorization(&self, agent: &ureq::Agent) -> Result<Option<String>, Error> {
+        let account = match &self.service_account {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+        let mut token = self.token.lock().expect("Token lock");
+        match &*token {
+            Some((_, expires)) if *expires > Instant::now() => {}
+            _ => {
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                tracing::debug!(email = account.client_email.as_str(), "Requesting token");
+                let reply: TokenReply = agent
+                    .post(&account.token_uri)
+                    .send_form(&[
+                        ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                        ("assertion", &account.assertion(now)?),
+                    ])
+                    .map_err(|e| Error::BadQuery(Box::new(e)))?
+                    .into_json()?;
+                // Renew the token one minute before it expires.
+                let expires = Instant::now() + Duration::from_secs(reply.expires_in.max(60) - 60);
+                *token = Some((reply.access_token, expires));
+            }
+        }
+        Ok(token
+            .as_ref()
+            .map(|(access_token, _)| format!("Bearer {}", access_token)))
+    }
+
+    fn request(
+        &self,
+        agent: &ureq::Agent,
+        method: &str,
+        url: &Url,
+    ) -> Result<ureq::Request, Error> {
+        let request = agent.request_url(method, url);
+        Ok(match self.authorization(agent)? {
+            Some(authorization) => request.set("Authorization", &authorization),
+            None => request,
+        })
+    }
+
+    /// Create the request of an object, for example to GET or HEAD it.
+    pub fn object_request(
+        &self,
+        agent: &ureq::Agent,
+        method: &str,
+        object: &ObjectUrl,
+    ) -> Result<ureq::Request, Error> {
+        let mut url = self.endpoint.clone();
+        url.path_segments_mut()
+            .map_err(|()| Error::BadUrl(url::ParseError::RelativeUrlWithCannotBeABaseBase))?
+            .pop_if_empty()
+            .push(&object.bucket)
+            .extend(object.name.split('/'));
+        tracing::debug!(url = url.as_str(), "Requesting object");
+        self.request(agent, method, &url)
+    }
+
+    /// Read a small object, such as the prow `finished.json`.
+    pub fn read_object(&self, agent: &ureq::Agent, object: &ObjectUrl) -> Result<String, Error> {
+        Ok(self
+            .object_request(agent, "GET", object)?
+            .call()
+            .map_err(|e| Error::BadQuery(Box::new(e)))?
+            .into_string()?)
+    }
+
+    /// List the objects of a prefix.
+    pub fn list_objects(
+        &self,
+        agent: &ureq::Agent,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<Object>, Error> {
+        let mut objects = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut url = self.endpoint.clone();
+            url.path_segments_mut()
+                .map_err(|()| Error::BadUrl(url::ParseError::RelativeUrlWithCannotBeABaseBase))?
+                .pop_if_empty()
+                .extend(&["storage", "v1", "b", bucket, "o"]);
+            url.query_pairs_mut()
+                .append_pair("prefix", prefix)
+                .append_pair("fields", "items(name,size),nextPageToken");
+            if let Some(page_token) = &page_token {
+                url.query_pairs_mut().append_pair("pageToken", page_token);
+            }
+            tracing::debug!(url = url.as_str(), "Listing objects");
+            let reply: ListReply = self
+                .request(agent, "GET", &url)?
+                .call()
+                .map_err(|e| Error::BadQuery(Box::new(e)))?
+                .into_json()?;
+            objects.extend(
+                reply
+                    .items
+                    .into_iter()
+                    // Skip the directory placeholders.
+                    .filter(|object| !object.name.ends_with('/'))
+                    .map(|object| Object {
+                        size: object.size.parse().unwrap_or(0),
+                        name: object.name,
+                    }),
+            );
+            match reply.next_page_token {
+                Some(next) => page_token = Some(next),
+                None => break,
+            }
+        }
+        Ok(objects)
+    }
+}
+
+#[test]
+fn test_gs_url() {
+    let url = object_url("origin-ci-test", "pr-logs/pull/42/build-log.txt").unwrap();
+    assert_eq!(
+        url.as_str(),
+        "gs://origin-ci-test/pr-logs/pull/42/build-log.txt"
+    );
+    assert_eq!(
+        parse_gs_url(&url),
+        Some(ObjectUrl {
+            bucket: "origin-ci-test".into(),
+            name: "pr-logs/pull/42/build-log.txt".into()
+        })
+    );
+    let url = object_url("logs", "artifacts/job output.txt").unwrap();
+    assert_eq!(parse_gs_url(&url).unwrap().name, "artifacts/job output.txt");
+    assert_eq!(parse_gs_url(&Url::parse("s3://logs/key").unwrap()), None);
+}
+
+#[test]
+fn test_list_objects() {
+    let mut server = mockito::Server::new();
+    let first = server
+        .mock("GET", "/storage/v1/b/origin-ci-test/o")
+        .match_query(mockito::Matcher::Regex(
+            "^prefix=pr-logs%2F42%2F&fields=[^&]+$".into(),
+        ))
+        .with_body(r#"{"items":[{"name":"pr-logs/42/","size":"0"},{"name":"pr-logs/42/build-log.txt","size":"42"}],"nextPageToken":"next"}"#)
+        .expect(1)
+        .create();
+    let second = server
+        .mock("GET", "/storage/v1/b/origin-ci-test/o")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "pageToken".into(),
+            "next".into(),
+        ))
+        .with_body(r#"{"items":[{"name":"pr-logs/42/finished.json","size":"7"}]}"#)
+        .expect(1)
+        .create();
+    let mut client = Client::anonymous();
+    client.endpoint = Url::parse(&server.url()).unwrap();
+    let objects = client
+        .list_objects(&ureq::Agent::new(), "origin-ci-test", "pr-logs/42/")
+        .unwrap();
+    assert_eq!(
+        objects,
+        vec![
+            Object {
+                name: "pr-logs/42/build-log.txt".into(),
+                size: 42
+            },
+            Object {
+                name: "pr-logs/42/finished.json".into(),
+                size: 7
+            },
+        ]
+    );
+    first.assert();
+    second.assert();
+}
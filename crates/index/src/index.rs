@@ -56,6 +56,41 @@ pub fn load_mat(buf: &[u8]) -> FeaturesMatrix {
     deserialize(buf).unwrap()
 }
 
+/// The raw parts of a csr matrix: the row pointers, the column indices and the values.
+pub struct MatParts {
+    pub rows: usize,
+    pub cols: usize,
+    pub indptr: Vec<usize>,
+    pub indices: Vec<usize>,
+    pub data: Vec<F>,
+}
+
+pub fn mat_to_parts(mat: &FeaturesMatrix) -> MatParts {
+    MatParts {
+        rows: mat.rows(),
+        cols: mat.cols(),
+        indptr: mat.indptr().raw_storage().to_vec(),
+        indices: mat.indices().to_vec(),
+        data: mat.data().to_vec(),
+    }
+}
+
+/// Create a csr matrix from its raw parts, checking the structure.
+pub fn mat_from_parts(parts: MatParts) -> Result<FeaturesMatrix, String> {
+    FeaturesMatrix::try_new(
+        (parts.rows, parts.cols),
+        parts.indptr,
+        parts.indices,
+        parts.data,
+    )
+    .map_err(|(_, _, _, err)| format!("invalid matrix: {}", err))
+}
+
+/// The number of features of the hashing vectorizer.
+pub fn features_size() -> usize {
+    SIZE
+}
+
 /// Index implementation using a csr matrix storage.
 /// Use the [`FeaturesMatrixBuilder`] for a streaming implementation.
 pub fn index_mat(lines: &[String]) -> FeaturesMatrix {
@@ -275,4 +310,21 @@ mod tests {
         dbg!(distances);
         assert!(true)
     }
+
+    #[test]
+    fn test_mat_parts() {
+        let model = index_mat(&["the first line".to_string(), "an error".to_string()]);
+        let parts = mat_to_parts(&model);
+        assert_eq!((parts.rows, parts.cols), (2, features_size()));
+        assert_eq!(parts.indptr, vec![0, 3, 5]);
+        assert_eq!(mat_from_parts(parts), Ok(model));
+        let bad = MatParts {
+            rows: 1,
+            cols: 1,
+            indptr: vec![0, 2],
+            indices: vec![0],
+            data: vec![1.0],
+        };
+        assert!(mat_from_parts(bad).is_err());
+    }
 }
@@ -8,7 +8,8 @@
 //! - Split sub line to handle cmd output embedded as a long oneliner.
 //! - Work with Read object, such as file decompressors or network endpoints.
 //! - Constant memory usage by using zero copy [Bytes] slices.
-//! - Line length limit to prevent overflow on invalid data.
+//! - Line length limit to prevent overflow on invalid data, the overlong lines are truncated.
+//! - Strip the ANSI escape sequences, such as the terminal color codes.
 //!
 //! Here is an example usage:
 //!
@@ -30,6 +31,12 @@
 use bytes::{Buf, Bytes, BytesMut};
 use std::io::{Read, Result};
 
+/// The default maximum line length.
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 6000;
+
+/// The annotation added to the truncated lines.
+const TRUNCATED: &[u8] = b" [truncated]";
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum Sep {
     // A line return: '\n'
@@ -90,10 +97,11 @@ enum State {
 // There are two situations to handle lines that are over the length limits:
 //
 // I: [XXXXXXXXXXXXXXXXXXXX\nbbbb]          < the next line if in the buffer.
-// I:                        ⭩- the buffer position advance
+// I:  ╰---⮡ the truncated slice  ⭩- the buffer position advance
 // I: [                      bbbb]          < we resume the iterator.
 //
 // J: [XXXXXXXXXXXXXXXXXXXXXXXXXX]          < the next line is not in the buffer.
+// J:  ╰---⮡ the truncated slice
 // J: [                          ]          < we clear the buffer and repeat until we reach Step I.
 pub struct BytesLines<R: Read> {
     reader: R,
@@ -105,6 +113,9 @@ pub struct BytesLines<R: Read> {
     split_json: Option<JsonState>,
     prev_pos: usize,
     escaped: bool,
+    strip_ansi: bool,
+    // The rest of a truncated line needs to be dropped.
+    dropping: bool,
 }
 
 struct JsonState {
@@ -120,6 +131,10 @@ impl<R: Read> Iterator for BytesLines<R> {
     fn next(&mut self) -> Option<Self::Item> {
         match self.state {
             State::EoF => None,
+            State::Scanning(_) if self.dropping => {
+                self.dropping = false;
+                self.drop_until_next_line()
+            }
             State::Scanning(_) if self.buf.is_empty() => self.read_slice(),
             State::Scanning(_) => self.get_slice(),
         }
@@ -133,9 +148,9 @@ impl<R: Read> BytesLines<R> {
     /// * `[1,2]` becomes `["1", "2"]`
     /// * `{a: b, c: {key:value}` becomes `["a: b", "c: ", "key: value"]`
     pub fn new(reader: R, split_json: bool) -> BytesLines<R> {
-        // TODO: make these configurable
+        // TODO: make this configurable
         let chunk_size = 8192;
-        let max_line_length = 6000;
+        let max_line_length = DEFAULT_MAX_LINE_LENGTH;
         let split_json = if split_json {
             Some(JsonState { in_string: false })
         } else {
@@ -151,9 +166,50 @@ impl<R: Read> BytesLines<R> {
             prev_pos: 0,
             escaped: false,
             split_json,
+            strip_ansi: true,
+            dropping: false,
+        }
+    }
+
+    /// Get a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Set the maximum line length, the longer lines are truncated with a ` [truncated]` annotation.
+    pub fn with_max_line_length(mut self, max_line_length: usize) -> BytesLines<R> {
+        self.max_line_length = max_line_length.max(1);
+        self
+    }
+
+    /// Indicate if the ANSI escape sequences are removed, which is enabled by default.
+    pub fn with_strip_ansi(mut self, strip_ansi: bool) -> BytesLines<R> {
+        self.strip_ansi = strip_ansi;
+        self
+    }
+
+    // Remove the ANSI escape sequences when needed
+    fn clean_line(&self, line: Bytes) -> Bytes {
+        if self.strip_ansi {
+            strip_ansi(line)
+        } else {
+            line
         }
     }
 
+    // Keep the beginning of an overlong line, with an annotation
+    fn truncate_line(&self, line: &[u8]) -> Bytes {
+        let mut end = self.max_line_length.min(line.len());
+        // Cut on a character boundary to keep the line valid utf-8.
+        while end > 0 && end < line.len() && (line[end] & 0xc0) == 0x80 {
+            end -= 1;
+        }
+        let mut result = BytesMut::with_capacity(end + TRUNCATED.len());
+        result.extend_from_slice(&line[..end]);
+        result.extend_from_slice(TRUNCATED);
+        self.clean_line(result.freeze())
+    }
+
     // Read a new chunk and call get_slice
     fn read_slice(&mut self) -> Option<Result<LogLine>> {
         let pos = self.buf.len();
@@ -169,11 +225,17 @@ impl<R: Read> BytesLines<R> {
             // Step H: We reached the end of the reader, but we have left-overs.
             Ok(_) if pos > 0 => {
                 self.update_line_counter(State::EoF);
-                Some(Ok((self.buf.split_to(pos).freeze(), self.line_count)))
+                let line = self.buf.split_to(pos).freeze();
+                let line = self.clean_line(line);
+                Some(Ok((line, self.line_count)))
             }
 
             // We reached the end of the reader, this is the end.
-            Ok(_) => None,
+            Ok(_) => {
+                self.buf.clear();
+                self.state = State::EoF;
+                None
+            }
 
             // There was a reading error, we return it.
             Err(e) => Some(Err(e)),
@@ -185,17 +247,27 @@ impl<R: Read> BytesLines<R> {
         match self.find_next_line() {
             // Step J: The current line is over the limit, and we don't know where it ends.
             None if self.buf.len() > self.max_line_length => {
+                // The line counter is updated when the end of the line is found.
+                let line_number = if self.state == State::Scanning(Sep::NewLine) {
+                    self.line_count + 1
+                } else {
+                    self.line_count
+                };
+                let line = self.truncate_line(&self.buf);
                 self.prev_pos = 0;
                 self.buf.reserve(self.chunk_size);
-                self.drop_until_next_line()
+                // The rest of the line is dropped on the next iteration.
+                self.dropping = true;
+                Some(Ok((line, line_number)))
             }
 
-            // Step I: The current line is over the limit, we need to discard it.
+            // Step I: The current line is over the limit, we need to truncate it.
             Some((pos, sep)) if pos > self.max_line_length => {
                 self.prev_pos = 0;
+                let line = self.truncate_line(&self.buf[..pos]);
                 // The next line is already in the buffer, so we can just advance.
                 self.buf.advance(pos + sep.len());
-                self.get_slice()
+                Some(Ok((line, self.line_count)))
             }
 
             // Step E: We haven't found the end of the line, we need more data.
@@ -211,6 +283,7 @@ impl<R: Read> BytesLines<R> {
                 self.prev_pos = 0;
                 // Step C: split_to() creates a new zero copy reference to the buffer.
                 let res = self.buf.split_to(pos).freeze();
+                let res = self.clean_line(res);
                 // Step D: advance the starting position
                 self.buf.advance(sep.len());
                 if res.is_empty() {
@@ -312,6 +385,53 @@ fn match_json_kv(c: char, json_state: &mut JsonState) -> Option<Sep> {
     }
 }
 
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// Remove the ANSI escape sequences, the line is copied only when it contains a sequence.
+pub fn strip_ansi(line: Bytes) -> Bytes {
+    if !line.contains(&ESC) {
+        return line;
+    }
+    let mut result = Vec::with_capacity(line.len());
+    let mut pos = 0;
+    while pos < line.len() {
+        if line[pos] != ESC {
+            result.push(line[pos]);
+            pos += 1;
+            continue;
+        }
+        pos += 1;
+        match line.get(pos) {
+            // Control Sequence Introducer: ESC [ parameters intermediates final
+            Some(b'[') => {
+                pos += 1;
+                while pos < line.len() && !(0x40..=0x7e).contains(&line[pos]) {
+                    pos += 1;
+                }
+                pos += 1;
+            }
+            // Operating System Command: ESC ] data (BEL | ESC \)
+            Some(b']') => {
+                pos += 1;
+                while pos < line.len() && line[pos] != BEL && line[pos] != ESC {
+                    pos += 1;
+                }
+                if line.get(pos) == Some(&ESC) {
+                    pos += 1;
+                }
+                pos += 1;
+            }
+            // Character set designation: ESC ( charset
+            Some(b'(') | Some(b')') => pos += 2,
+            // Other two bytes sequences
+            Some(_) => pos += 1,
+            None => {}
+        }
+    }
+    result.into()
+}
+
 pub fn clone_bytes_to_string(bytes: &Bytes) -> Option<std::rc::Rc<str>> {
     std::str::from_utf8(&bytes[..]).ok().map(|s| s.into())
 }
@@ -353,7 +473,31 @@ fn test_long_line() {
     let lines: Vec<LogLine> = BytesLines::new(std::io::Cursor::new(input), false)
         .collect::<Result<Vec<_>>>()
         .unwrap();
-    assert_eq!(lines, vec![("second".into(), 2), ("third".into(), 3)])
+    let mut truncated = "a".repeat(DEFAULT_MAX_LINE_LENGTH);
+    truncated.push_str(" [truncated]");
+    assert_eq!(
+        lines,
+        vec![
+            (truncated.into(), 1),
+            ("second".into(), 2),
+            ("third".into(), 3)
+        ]
+    );
+
+    // The line is truncated when it is already in the buffer.
+    let lines: Vec<LogLine> =
+        BytesLines::new(std::io::Cursor::new("first\nlong line\nlast"), false)
+            .with_max_line_length(4)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+    assert_eq!(
+        lines,
+        vec![
+            ("firs [truncated]".into(), 1),
+            ("long [truncated]".into(), 2),
+            ("last".into(), 3)
+        ]
+    );
 }
 
 #[test]
@@ -366,7 +510,8 @@ fn test_last_line() {
     let lines: Vec<LogLine> = BytesLines::new(std::io::Cursor::new(input), false)
         .collect::<Result<Vec<_>>>()
         .unwrap();
-    assert_eq!(lines.len(), 1)
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1], ("test".into(), 2));
 }
 
 #[test]
@@ -391,3 +536,23 @@ fn test_json_iterator() {
         ]
     );
 }
+
+#[test]
+fn test_strip_ansi() {
+    let get_lines = |reader: &'static str, strip: bool| -> Vec<LogLine> {
+        BytesLines::new(std::io::Cursor::new(reader), false)
+            .with_strip_ansi(strip)
+            .collect::<Result<Vec<_>>>()
+            .unwrap()
+    };
+    let input = "\x1b[1;31mERROR\x1b[0m: failed\n\x1b]0;title\x07ok\x1b(B\n\x1b[Kdone";
+    assert_eq!(
+        get_lines(input, true),
+        vec![
+            ("ERROR: failed".into(), 1),
+            ("ok".into(), 2),
+            ("done".into(), 3)
+        ]
+    );
+    assert_eq!(get_lines(input, false)[0].0.len(), 24);
+}
@@ -0,0 +1,162 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+#![warn(missing_docs)]
+
+//! This library provides decoders for the [jenkins](https://www.jenkins.io/) build api.
+//!
+//! The jobs can be nested in folders, for example the multibranch pipelines,
+//! in which case the build url contains one `job/<name>` segment per level.
+//!
+//! Here is an example usage:
+//!
+//! ```no_run
+//! # fn main() {
+//! let url = url::Url::parse("https://jenkins.example.com/job/project/job/main/42/").unwrap();
+//! let build_url = jenkins_build::parse_build_url(&url).unwrap();
+//! let build = jenkins_build::get_build(&ureq::Agent::new(), &build_url.url).unwrap();
+//! println!("{}: {:?}", build_url.job_name, build.result);
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use url::Url;
+
+/// The jenkins error.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The provided url is not usable.
+    #[error("bad api url: {0}")]
+    BadUrl(#[from] url::ParseError),
+
+    /// The api reply couldn't be decoded.
+    #[error("bad api reply: {0}")]
+    BadReply(#[from] std::io::Error),
+
+    /// The api query failed.
+    #[error("bad api query: {0}")]
+    BadQuery(#[from] Box<ureq::Error>),
+}
+
+/// The location of a build.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildUrl {
+    /// The build url, terminated with a slash.
+    pub url: Url,
+    /// The full job name, with the folders separated by a slash.
+    pub job_name: Box<str>,
+    /// The build number.
+    pub number: u64,
+}
+
+/// The pages of a build that can be used in place of the build url.
+const BUILD_PAGES: &[&str] = &["", "console", "consoleFull", "consoleText"];
+
+/// Decode a build url, such as `https://jenkins/job/folder/job/name/42/console`.
+pub fn parse_build_url(url: &Url) -> Option<BuildUrl> {
+    let segments = url.path_segments()?.collect::<Vec<_>>();
+    let start = segments.iter().position(|segment| *segment == "job")?;
+    let mut names = Vec::new();
+    let mut pos = start;
+    while pos + 1 < segments.len() && segments[pos] == "job" {
+        names.push(segments[pos + 1]);
+        pos += 2;
+    }
+    let number = segments.get(pos)?.parse().ok()?;
+    match segments[pos + 1..] {
+        [] => {}
+        [page] if BUILD_PAGES.contains(&page) => {}
+        _ => return None,
+    }
+    let mut build_url = url.clone();
+    build_url.set_query(None);
+    build_url.set_fragment(None);
+    build_url.set_path(&format!("/{}/", segments[..pos + 1].join("/")));
+    Some(BuildUrl {
+        url: build_url,
+        job_name: names.join("/").into(),
+        number,
+    })
+}
+
+/// A build artifact.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// The artifact path, relative to the `artifact/` url of the build.
+    #[serde(rename = "relativePath")]
+    pub relative_path: Box<str>,
+}
+
+/// A build result.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Build {
+    /// The build number.
+    pub number: u64,
+    /// The build url.
+    pub url: Url,
+    /// The build result, missing when the build is running.
+    pub result: Option<Box<str>>,
+    /// The build archived artifacts.
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+}
+
+/// The low-level function to query a build, the url may also be a permalink such as `job/name/lastSuccessfulBuild/`.
+pub fn get_build(client: &ureq::Agent, build_url: &Url) -> Result<Build, Error> {
+    let mut api_url = build_url.join("api/json")?;
+    api_url.set_query(Some("tree=number,url,result,artifacts[relativePath]"));
+    tracing::debug!(url = api_url.as_str(), "Querying jenkins build");
+    client
+        .request_url("GET", &api_url)
+        .call()
+        .map_err(|e| Error::BadQuery(Box::new(e)))?
+        .into_json()
+        .map_err(Error::BadReply)
+}
+
+#[test]
+fn test_parse_build_url() {
+    let url = Url::parse("https://jenkins.example.com/ci/job/project/job/feature%252Fx/42/console")
+        .unwrap();
+    assert_eq!(
+        parse_build_url(&url),
+        Some(BuildUrl {
+            url: Url::parse("https://jenkins.example.com/ci/job/project/job/feature%252Fx/42/")
+                .unwrap(),
+            job_name: "project/feature%252Fx".into(),
+            number: 42,
+        })
+    );
+    for url in [
+        "https://jenkins.example.com/job/project/",
+        "https://jenkins.example.com/job/project/42/artifact/logs/",
+        "https://jenkins.example.com/project/42/",
+    ] {
+        assert_eq!(parse_build_url(&Url::parse(url).unwrap()), None, "{}", url);
+    }
+}
+
+#[test]
+fn test_get_build() {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("GET", "/job/project/lastSuccessfulBuild/api/json")
+        .match_query(mockito::Matcher::Any)
+        .with_body(format!(
+            r#"{{"_class":"hudson.model.FreeStyleBuild","artifacts":[{{"_class":"hudson.model.Run$Artifact","relativePath":"logs/app.log"}}],"number":41,"result":"SUCCESS","url":"{}/job/project/41/"}}"#,
+            server.url()
+        ))
+        .expect(1)
+        .create();
+    let url = Url::parse(&format!(
+        "{}/job/project/lastSuccessfulBuild/",
+        server.url()
+    ))
+    .unwrap();
+    let build = get_build(&ureq::Agent::new(), &url).unwrap();
+    assert_eq!(build.number, 41);
+    assert_eq!(build.result.as_deref(), Some("SUCCESS"));
+    assert_eq!(build.artifacts[0].relative_path.as_ref(), "logs/app.log");
+    mock.assert();
+}
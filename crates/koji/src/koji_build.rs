@@ -0,0 +1,589 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+#![warn(missing_docs)]
+
+//! This library provides decoders for the [koji](https://koji.build/) build system hub api.
+//!
+//! The hub is queried with XML-RPC, and the logs are served by the file server,
+//! in the task work directory, or in the package directory once the build completed.
+//!
+//! Here is an example usage:
+//!
+//! ```no_run
+//! # fn main() {
+//! let url = url::Url::parse("https://koji.fedoraproject.org/koji/taskinfo?taskID=42").unwrap();
+//! let koji_url = koji_build::parse_koji_url(&url).unwrap();
+//! let client = koji_build::Client::new(koji_url.hub_url);
+//! if let koji_build::Target::Task(task_id) = koji_url.target {
+//!     let task = client.get_task_info(&ureq::Agent::new(), task_id).unwrap();
+//!     println!("{}: {}", task.method, koji_build::task_state_name(task.state));
+//! }
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use url::Url;
+
+/// The koji error.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The provided url is not usable.
+    #[error("bad api url: {0}")]
+    BadUrl(#[from] url::ParseError),
+
+    /// The api reply couldn't be read.
+    #[error("bad api reply: {0}")]
+    BadReply(#[from] std::io::Error),
+
+    /// The api reply couldn't be decoded.
+    #[error("bad api xml: {0}")]
+    BadXml(String),
+
+    /// The api reply doesn't have the expected value.
+    #[error("bad api value: {0}")]
+    BadValue(#[from] serde_json::Error),
+
+    /// The api query failed.
+    #[error("bad api query: {0}")]
+    BadQuery(#[from] Box<ureq::Error>),
+
+    /// The hub returned a fault.
+    #[error("api fault: {0}")]
+    Fault(String),
+}
+
+/// The build or task to analyze.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    /// A task id, either a build task or an architecture buildArch task.
+    Task(u64),
+    /// A build id.
+    Build(u64),
+}
+
+/// The location of a koji build.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KojiUrl {
+    /// The hub api url.
+    pub hub_url: Url,
+    /// The build or task.
+    pub target: Target,
+}
+
+/// Decode a web page url, such as `https://koji.fedoraproject.org/koji/taskinfo?taskID=42`
+/// or `https://koji.fedoraproject.org/koji/buildinfo?buildID=42`.
+pub fn parse_koji_url(url: &Url) -> Option<KojiUrl> {
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .and_then(|(_, value)| value.parse().ok())
+    };
+    let target = match url.path_segments()?.next_back()? {
+        "taskinfo" => Target::Task(param("taskID")?),
+        "buildinfo" => Target::Build(param("buildID")?),
+        _ => return None,
+    };
+    Some(KojiUrl {
+        hub_url: url.join("/kojihub").ok()?,
+        target,
+    })
+}
+
+/// The file server url, which is `/kojifiles/` on the hub host, except for the fedora instance.
+pub fn top_url(hub_url: &Url) -> Result<Url, Error> {
+    match hub_url.host_str() {
+        Some("koji.fedoraproject.org") => Ok(Url::parse("https://kojipkgs.fedoraproject.org/")?),
+        _ => Ok(hub_url.join("/kojifiles/")?),
+    }
+}
+
+/// The work directory of a task, where the logs are stored until the task is garbage collected.
+pub fn task_dir(top_url: &Url, task_id: u64) -> Result<Url, Error> {
+    Ok(top_url.join(&format!("work/tasks/{}/{}/", task_id % 10000, task_id))?)
+}
+
+/// The logs directory of a completed build architecture.
+pub fn build_logs_dir(top_url: &Url, build: &Build, arch: &str) -> Result<Url, Error> {
+    Ok(top_url.join(&format!(
+        "packages/{}/{}/{}/data/logs/{}/",
+        build.package_name, build.version, build.release, arch
+    ))?)
+}
+
+/// The name of a task state.
+pub fn task_state_name(state: u64) -> &'static str {
+    match state {
+        0 => "FREE",
+        1 => "OPEN",
+        2 => "CLOSED",
+        3 => "CANCELED",
+        4 => "ASSIGNED",
+        5 => "FAILED",
+        _ => "UNKNOWN",
+    }
+}
+
+/// The completed build state.
+pub const BUILD_COMPLETE: u64 = 1;
+
+/// The name of a build state.
+pub fn build_state_name(state: u64) -> &'static str {
+    match state {
+        0 => "BUILDING",
+        BUILD_COMPLETE => "COMPLETE",
+        2 => "DELETED",
+        3 => "FAILED",
+        4 => "CANCELED",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Split a `name-version-release` into its package name, version and release.
+pub fn split_nvr(nvr: &str) -> Option<(&str, &str, &str)> {
+    let mut parts = nvr.rsplitn(3, '-');
+    let release = parts.next()?;
+    let version = parts.next()?;
+    let name = parts.next()?;
+    Some((name, version, release))
+}
+
+/// A task.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Task {
+    /// The task id.
+    pub id: u64,
+    /// The task method, e.g. `build` or `buildArch`.
+    pub method: String,
+    /// The task architecture.
+    pub arch: String,
+    /// The task state.
+    pub state: u64,
+    /// The task parameters.
+    #[serde(default)]
+    pub request: Vec<Value>,
+}
+
+impl Task {
+    /// The source rpm name-version-release of a buildArch task.
+    pub fn srpm_nvr(&self) -> Option<&str> {
+        let path = self.request.first()?.as_str()?;
+        let name = path.rsplit('/').next()?;
+        name.strip_suffix(".src.rpm")
+    }
+}
+
+/// A build.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Build {
+    /// The build id.
+    pub build_id: u64,
+    /// The package name.
+    pub package_name: String,
+    /// The package version.
+    pub version: String,
+    /// The package release.
+    pub release: String,
+    /// The package name-version-release.
+    pub nvr: String,
+    /// The build state.
+    pub state: u64,
+    /// The build task, which is not set for the imported builds.
+    pub task_id: Option<u64>,
+}
+
+/// The koji hub client.
+#[derive(Clone, Debug)]
+pub struct Client {
+    /// The hub api url.
+    pub hub_url: Url,
+}
+
+impl Client {
+    /// Create a client.
+    pub fn new(hub_url: Url) -> Client {
+        Client { hub_url }
+    }
+
+    /// Call a hub method.
+    pub fn call(
+        &self,
+        agent: &ureq::Agent,
+        method: &str,
+        params: &[Value],
+    ) -> Result<Value, Error> {
+        tracing::debug!(url = self.hub_url.as_str(), method, "Querying koji hub");
+        let mut body = format!(
+            "<?xml version='1.0'?><methodCall><methodName>{}</methodName><params>",
+            method
+        );
+        for param in params {
+            body.push_str("<param>");
+            encode_value(param, &mut body);
+            body.push_str("</param>");
+        }
+        body.push_str("</params></methodCall>");
+        let reply = agent
+            .request_url("POST", &self.hub_url)
+            .set("Content-Type", "text/xml")
+            .send_string(&body)
+            .map_err(|e| Error::BadQuery(Box::new(e)))?
+            .into_string()?;
+        decode_response(&reply)
+    }
+
+    /// Get a task with its parameters.
+    pub fn get_task_info(&self, agent: &ureq::Agent, task_id: u64) -> Result<Task, Error> {
+        let task = self.call(agent, "getTaskInfo", &[task_id.into(), true.into()])?;
+        Ok(serde_json::from_value(task)?)
+    }
+
+    /// Get the sub tasks of a task.
+    pub fn get_task_children(&self, agent: &ureq::Agent, task_id: u64) -> Result<Vec<Task>, Error> {
+        let tasks = self.call(agent, "getTaskChildren", &[task_id.into(), true.into()])?;
+        Ok(serde_json::from_value(tasks)?)
+    }
+
+    /// Get a build.
+    pub fn get_build(&self, agent: &ureq::Agent, build_id: u64) -> Result<Build, Error> {
+        let build = self.call(agent, "getBuild", &[build_id.into()])?;
+        Ok(serde_json::from_value(build)?)
+    }
+
+    /// Get the last complete build of a package.
+    pub fn last_complete_build(
+        &self,
+        agent: &ureq::Agent,
+        package: &str,
+    ) -> Result<Option<Build>, Error> {
+        let package_id = match self.call(agent, "getPackageID", &[package.into()])? {
+            Value::Null => return Ok(None),
+            package_id => package_id,
+        };
+        // The keyword arguments are passed with the `__starstar` struct.
+        let builds = self.call(
+            agent,
+            "listBuilds",
+            &[serde_json::json!({
+                "__starstar": true,
+                "packageID": package_id,
+                "state": BUILD_COMPLETE,
+                "queryOpts": {"order": "-build_id", "limit": 1},
+            })],
+        )?;
+        let builds: Vec<Build> = serde_json::from_value(builds)?;
+        Ok(builds.into_iter().next())
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn encode_value(value: &Value, out: &mut String) {
+    out.push_str("<value>");
+    match value {
+        Value::Null => out.push_str("<nil/>"),
+        Value::Bool(value) => out.push_str(&format!("<boolean>{}</boolean>", *value as u8)),
+        Value::Number(value) if value.is_f64() => {
+            out.push_str(&format!("<double>{}</double>", value))
+        }
+        Value::Number(value) => out.push_str(&format!("<int>{}</int>", value)),
+        Value::String(value) => out.push_str(&format!("<string>{}</string>", xml_escape(value))),
+        Value::Array(values) => {
+            out.push_str("<array><data>");
+            for value in values {
+                encode_value(value, out);
+            }
+            out.push_str("</data></array>");
+        }
+        Value::Object(members) => {
+            out.push_str("<struct>");
+            for (name, value) in members {
+                out.push_str(&format!("<member><name>{}</name>", xml_escape(name)));
+                encode_value(value, out);
+                out.push_str("</member>");
+            }
+            out.push_str("</struct>");
+        }
+    }
+    out.push_str("</value>");
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Open(&'a str),
+    Close(&'a str),
+    Empty(&'a str),
+    Text(&'a str),
+}
+
+fn tokenize(body: &str) -> Result<Vec<Token<'_>>, Error> {
+    let mut tokens = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find('<') {
+        if !rest[..start].trim().is_empty() {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        let end = rest[start..]
+            .find('>')
+            .ok_or_else(|| Error::BadXml("unterminated tag".into()))?
+            + start;
+        let tag = &rest[start + 1..end];
+        if let Some(name) = tag.strip_prefix('/') {
+            tokens.push(Token::Close(name.trim()));
+        } else if let Some(name) = tag.strip_suffix('/') {
+            tokens.push(Token::Empty(name.trim()));
+        } else if !tag.starts_with('?') && !tag.starts_with('!') {
+            tokens.push(Token::Open(tag.split_whitespace().next().unwrap_or(tag)));
+        }
+        rest = &rest[end + 1..];
+    }
+    Ok(tokens)
+}
+
+struct Decoder<'a> {
+    tokens: std::iter::Peekable<std::vec::IntoIter<Token<'a>>>,
+}
+
+impl Decoder<'_> {
+    fn expect(&mut self, expected: Token) -> Result<(), Error> {
+        match self.tokens.next() {
+            Some(token) if token == expected => Ok(()),
+            token => Err(Error::BadXml(format!(
+                "expected {:?}, got {:?}",
+                expected, token
+            ))),
+        }
+    }
+
+    /// Read the text of an element, the opening tag being consumed.
+    fn text(&mut self, tag: &str) -> Result<String, Error> {
+        let text = match self.tokens.peek() {
+            Some(Token::Text(text)) => xml_unescape(text),
+            _ => String::new(),
+        };
+        if !text.is_empty() {
+            self.tokens.next();
+        }
+        self.expect(Token::Close(tag))?;
+        Ok(text)
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        match self.tokens.next() {
+            Some(Token::Open("value")) => {}
+            Some(Token::Empty("value")) => return Ok(Value::String(String::new())),
+            token => return Err(Error::BadXml(format!("expected value, got {:?}", token))),
+        }
+        let value = match self.tokens.next() {
+            // An untyped value is a string.
+            Some(Token::Text(text)) => {
+                self.expect(Token::Close("value"))?;
+                return Ok(Value::String(xml_unescape(text)));
+            }
+            Some(Token::Close("value")) => return Ok(Value::String(String::new())),
+            Some(Token::Empty("nil")) => Value::Null,
+            Some(Token::Empty("string")) => Value::String(String::new()),
+            Some(Token::Empty("array")) => Value::Array(Vec::new()),
+            Some(Token::Empty("struct")) => Value::Object(serde_json::Map::new()),
+            Some(Token::Open("array")) => {
+                let mut values = Vec::new();
+                match self.tokens.next() {
+                    Some(Token::Empty("data")) => {}
+                    Some(Token::Open("data")) => {
+                        while matches!(
+                            self.tokens.peek(),
+                            Some(Token::Open("value") | Token::Empty("value"))
+                        ) {
+                            values.push(self.value()?);
+                        }
+                        self.expect(Token::Close("data"))?;
+                    }
+                    token => return Err(Error::BadXml(format!("expected data, got {:?}", token))),
+                }
+                self.expect(Token::Close("array"))?;
+                Value::Array(values)
+            }
+            Some(Token::Open("struct")) => {
+                let mut members = serde_json::Map::new();
+                while let Some(Token::Open("member")) = self.tokens.peek() {
+                    self.tokens.next();
+                    self.expect(Token::Open("name"))?;
+                    let name = self.text("name")?;
+                    members.insert(name, self.value()?);
+                    self.expect(Token::Close("member"))?;
+                }
+                self.expect(Token::Close("struct"))?;
+                Value::Object(members)
+            }
+            Some(Token::Open(kind)) => {
+                let text = self.text(kind)?;
+                let bad_value = || Error::BadXml(format!("bad {} {}", kind, text));
+                match kind {
+                    "int" | "i4" | "i8" => {
+                        text.trim().parse::<i64>().map_err(|_| bad_value())?.into()
+                    }
+                    "boolean" => Value::Bool(text.trim() == "1"),
+                    "double" => text.trim().parse::<f64>().map_err(|_| bad_value())?.into(),
+                    "nil" => Value::Null,
+                    // The strings, dates and base64 values are kept as text.
+                    _ => Value::String(text),
+                }
+            }
+            token => return Err(Error::BadXml(format!("unexpected {:?}", token))),
+        };
+        self.expect(Token::Close("value"))?;
+        Ok(value)
+    }
+}
+
+/// Decode a methodResponse.
+fn decode_response(body: &str) -> Result<Value, Error> {
+    let tokens = tokenize(body)?;
+    let fault = tokens.contains(&Token::Open("fault"));
+    let start = tokens
+        .iter()
+        .position(|token| matches!(token, Token::Open("value") | Token::Empty("value")))
+        .ok_or_else(|| Error::BadXml("missing value".into()))?;
+    let mut decoder = Decoder {
+        tokens: tokens
+            .into_iter()
+            .skip(start)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .peekable(),
+    };
+    let value = decoder.value()?;
+    if fault {
+        Err(Error::Fault(
+            value
+                .get("faultString")
+                .and_then(|fault| fault.as_str())
+                .unwrap_or("unknown")
+                .into(),
+        ))
+    } else {
+        Ok(value)
+    }
+}
+
+#[test]
+fn test_parse_koji_url() {
+    let url = Url::parse("https://koji.fedoraproject.org/koji/taskinfo?taskID=42").unwrap();
+    let koji_url = parse_koji_url(&url).unwrap();
+    assert_eq!(koji_url.target, Target::Task(42));
+    assert_eq!(
+        koji_url.hub_url.as_str(),
+        "https://koji.fedoraproject.org/kojihub"
+    );
+    assert_eq!(
+        top_url(&koji_url.hub_url).unwrap().as_str(),
+        "https://kojipkgs.fedoraproject.org/"
+    );
+    assert_eq!(
+        task_dir(&top_url(&koji_url.hub_url).unwrap(), 108_242_513)
+            .unwrap()
+            .as_str(),
+        "https://kojipkgs.fedoraproject.org/work/tasks/2513/108242513/"
+    );
+
+    let url = Url::parse("https://kojihub.stream.centos.org/koji/buildinfo?buildID=7").unwrap();
+    let koji_url = parse_koji_url(&url).unwrap();
+    assert_eq!(koji_url.target, Target::Build(7));
+    assert_eq!(
+        top_url(&koji_url.hub_url).unwrap().as_str(),
+        "https://kojihub.stream.centos.org/kojifiles/"
+    );
+
+    for url in [
+        "https://koji.fedoraproject.org/koji/taskinfo",
+        "https://koji.fedoraproject.org/koji/packageinfo?packageID=42",
+    ] {
+        assert_eq!(parse_koji_url(&Url::parse(url).unwrap()), None, "{}", url);
+    }
+    assert_eq!(
+        split_nvr("python-foo-1.0-1.fc40"),
+        Some(("python-foo", "1.0", "1.fc40"))
+    );
+}
+
+#[test]
+fn test_xmlrpc() {
+    let mut body = String::new();
+    encode_value(
+        &serde_json::json!({"__starstar": true, "name": "a<b", "ids": [1, null]}),
+        &mut body,
+    );
+    assert_eq!(
+        body,
+        "<value><struct><member><name>__starstar</name><value><boolean>1</boolean></value></member><member><name>ids</name><value><array><data><value><int>1</int></value><value><nil/></value></data></array></value></member><member><name>name</name><value><string>a&lt;b</string></value></member></struct></value>"
+    );
+
+    let reply = r#"<?xml version='1.0'?>
+<methodResponse>
+<params>
+<param>
+<value><array><data>
+<value><struct>
+<member>
+<name>id</name>
+<value><int>43</int></value>
+</member>
+<member>
+<name>method</name>
+<value><string>buildArch</string></value>
+</member>
+<member>
+<name>arch</name>
+<value>x86_64</value>
+</member>
+<member>
+<name>state</name>
+<value><int>5</int></value>
+</member>
+<member>
+<name>parent</name>
+<value><nil/></value>
+</member>
+<member>
+<name>request</name>
+<value><array><data>
+<value><string>tasks/42/42/foo-1.0-1.fc40.src.rpm</string></value>
+<value><i4>1</i4></value>
+<value><boolean>0</boolean></value>
+<value><struct></struct></value>
+</data></array></value>
+</member>
+</struct></value>
+</data></array></value>
+</param>
+</params>
+</methodResponse>
+"#;
+    let tasks: Vec<Task> = serde_json::from_value(decode_response(reply).unwrap()).unwrap();
+    assert_eq!(tasks[0].method, "buildArch");
+    assert_eq!(task_state_name(tasks[0].state), "FAILED");
+    assert_eq!(tasks[0].srpm_nvr(), Some("foo-1.0-1.fc40"));
+
+    let fault = r#"<?xml version='1.0'?>
+<methodResponse><fault><value><struct>
+<member><name>faultCode</name><value><int>1000</int></value></member>
+<member><name>faultString</name><value><string>No such task</string></value></member>
+</struct></value></fault></methodResponse>"#;
+    assert!(matches!(decode_response(fault), Err(Error::Fault(fault)) if fault == "No such task"));
+}
@@ -0,0 +1,263 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the failed Ansible tasks of the Zuul builds, using the `job-output.json` playbook results.
+//!
+//! With the `ansible_failed_tasks` configuration, the job-output.txt lines outside of the failed tasks are blanked,
+//! so that the line numbers are preserved, and only the logs of the failing hosts are analyzed.
+//! The builds without a failed task, such as the baselines, are analyzed completely.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+use url::Url;
+
+use crate::env::Env;
+use crate::reader::DecompressReader;
+use crate::Source;
+
+const JOB_OUTPUT: &str = "job-output.txt";
+const JOB_OUTPUT_JSON: &str = "job-output.json";
+
+/// A task which failed on some hosts.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FailedTask {
+    /// The play name.
+    pub play: String,
+    /// The task name, as displayed in the job-output.txt, e.g. `role : name`.
+    pub task: String,
+    /// The failing hosts.
+    pub hosts: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Playbook {
+    #[serde(default)]
+    plays: Vec<Play>,
+}
+
+#[derive(Deserialize)]
+struct Play {
+    play: Named,
+    #[serde(default)]
+    tasks: Vec<Task>,
+}
+
+#[derive(Deserialize)]
+struct Task {
+    task: Named,
+    role: Option<Named>,
+    #[serde(default)]
+    hosts: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+    #[serde(default)]
+    name: String,
+}
+
+fn is_true(result: &serde_json::Value, key: &str) -> bool {
+    result.get(key).and_then(serde_json::Value::as_bool) == Some(true)
+}
+
+fn is_failed(result: &serde_json::Value) -> bool {
+    (is_true(result, "failed") || is_true(result, "unreachable"))
+        && !is_true(result, "ignore_errors")
+        && !is_true(result, "_ansible_ignore_errors")
+}
+
+/// Decode the failed tasks of a job-output.json.
+pub fn failed_tasks<R: Read>(reader: R) -> Result<Vec<FailedTask>> {
+    let playbooks: Vec<Playbook> =
+        serde_json::from_reader(reader).context("Can't decode job-output.json")?;
+    Ok(playbooks
+        .into_iter()
+        .flat_map(|playbook| playbook.plays)
+        .flat_map(|play| {
+            let play_name = play.play.name;
+            play.tasks.into_iter().filter_map(move |task| {
+                let hosts = task
+                    .hosts
+                    .iter()
+                    .filter(|(_, result)| is_failed(result))
+                    .map(|(host, _)| host.clone())
+                    .collect::<Vec<_>>();
+                if hosts.is_empty() {
+                    return None;
+                }
+                let task_name = match task.role {
+                    Some(role) if !role.name.is_empty() => {
+                        format!("{} : {}", role.name, task.task.name)
+                    }
+                    _ => task.task.name,
+                };
+                Some(FailedTask {
+                    play: play_name.clone(),
+                    task: task_name,
+                    hosts,
+                })
+            })
+        })
+        .collect())
+}
+
+/// Read the failed tasks of the job-output.json found next to a source.
+/// The missing or unreadable files are ignored, as the build is then analyzed completely.
+pub fn read_failed_tasks(env: &Env, source: &Source) -> Vec<FailedTask> {
+    let reader = match source {
+        Source::Local(_, path) => crate::reader::from_path(&path.with_file_name(JOB_OUTPUT_JSON)),
+        Source::Remote(prefix, url) => url
+            .join(JOB_OUTPUT_JSON)
+            .map_err(anyhow::Error::from)
+            .and_then(|url| crate::url_open(env, *prefix, &url)),
+    };
+    match reader.and_then(failed_tasks) {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            tracing::debug!("{}: no failed tasks found: {}", source, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Read the failed tasks of a build log url.
+pub fn read_build_failed_tasks(env: &Env, log_url: &Url) -> Vec<FailedTask> {
+    let prefix = log_url.as_str().trim_end_matches('/').len() + 1;
+    match log_url.join(JOB_OUTPUT) {
+        Ok(url) => read_failed_tasks(env, &Source::Remote(prefix, url)),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Check if a source belongs to the failed tasks: the job-output.txt or the logs of a failing host.
+pub fn is_failed_source(tasks: &[FailedTask], source: &Source) -> bool {
+    let relative = source.get_relative().trim_start_matches('/');
+    relative == JOB_OUTPUT
+        || relative.split('/').take(2).any(|part| {
+            tasks
+                .iter()
+                .any(|task| task.hosts.iter().any(|host| host == part))
+        })
+}
+
+/// Check if a source is a job-output.txt.
+pub fn is_job_output(source: &Source) -> bool {
+    match source {
+        Source::Local(_, path) => path.file_name() == Some(Path::new(JOB_OUTPUT).as_os_str()),
+        Source::Remote(_, url) => url.path().ends_with(&format!("/{}", JOB_OUTPUT)),
+    }
+}
+
+/// Get the name of a `PLAY [name]` or `TASK [name]` header.
+fn header<'a>(message: &'a str, kind: &str) -> Option<&'a str> {
+    let name = message.strip_prefix(kind)?.strip_prefix(" [")?;
+    name.rfind(']').map(|end| &name[..end])
+}
+
+/// Blank the lines which are outside of the failed tasks.
+pub fn failed_lines(content: &[u8], tasks: &[FailedTask]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(content.len());
+    let mut play = None;
+    let mut in_failed = false;
+    for line in content.split_inclusive(|c| *c == b'\n') {
+        let text = String::from_utf8_lossy(line);
+        // The lines are prefixed with a timestamp, e.g. `2023-06-01 10:00:00.123456 | TASK [name]`
+        let message = text
+            .split_once(" | ")
+            .map_or(text.as_ref(), |(_, message)| message)
+            .trim_end();
+        if let Some(name) = header(message, "PLAY") {
+            play = Some(name.to_string());
+            in_failed = false;
+        } else if let Some(name) = header(message, "TASK") {
+            in_failed = tasks
+                .iter()
+                .any(|task| play.as_deref() == Some(task.play.as_str()) && task.task == name);
+        } else if message.starts_with("PLAY RECAP") {
+            in_failed = false;
+        }
+        if in_failed {
+            output.extend_from_slice(line);
+        } else if line.ends_with(b"\n") {
+            output.push(b'\n');
+        }
+    }
+    output
+}
+
+/// Restrict a job-output.txt to the failed tasks, when the build has some.
+pub fn open(env: &Env, source: &Source, mut reader: DecompressReader) -> Result<DecompressReader> {
+    let tasks = read_failed_tasks(env, source);
+    if tasks.is_empty() {
+        return Ok(reader);
+    }
+    tracing::debug!("{}: analyzing the failed tasks {:?}", source, tasks);
+    let mut content = Vec::new();
+    reader
+        .read_to_end(&mut content)
+        .with_context(|| format!("{}: failed to read", source))?;
+    Ok(DecompressReader::RemoteDecoder(Box::new(
+        std::io::Cursor::new(failed_lines(&content, &tasks)),
+    )))
+}
+
+#[test]
+fn test_failed_tasks() -> Result<()> {
+    let json = r#"[{
+      "phase": "run",
+      "playbook": "playbooks/run.yaml",
+      "plays": [{
+        "play": {"name": "all", "id": "1"},
+        "tasks": [
+          {"task": {"name": "Setup", "id": "2"}, "hosts": {"controller": {"changed": true}}},
+          {"task": {"name": "Ignored", "id": "3"}, "hosts": {"controller": {"failed": true, "ignore_errors": true}}},
+          {"task": {"name": "Run tests", "id": "4"}, "role": {"name": "tox", "id": "5"},
+           "hosts": {"controller": {"failed": true, "rc": 1}, "compute": {"changed": false}}}
+        ]
+      }],
+      "stats": {}
+    }]"#;
+    let tasks = failed_tasks(json.as_bytes())?;
+    assert_eq!(
+        tasks,
+        vec![FailedTask {
+            play: "all".into(),
+            task: "tox : Run tests".into(),
+            hosts: vec!["controller".into()],
+        }]
+    );
+
+    let content = "\
+2023-06-01 10:00:00.000001 | PLAY [all]
+2023-06-01 10:00:00.000002 | TASK [Setup]
+2023-06-01 10:00:00.000003 | controller | ok
+2023-06-01 10:00:00.000004 | TASK [tox : Run tests]
+2023-06-01 10:00:00.000005 | controller | Traceback
+2023-06-01 10:00:00.000006 | PLAY RECAP
+2023-06-01 10:00:00.000007 | controller | ok=1 failed=1
+";
+    assert_eq!(
+        String::from_utf8(failed_lines(content.as_bytes(), &tasks))?,
+        "\n\n\n2023-06-01 10:00:00.000004 | TASK [tox : Run tests]\n2023-06-01 10:00:00.000005 | controller | Traceback\n\n\n"
+    );
+
+    let source = |path: &str| Source::from_pathbuf(path.into());
+    assert!(is_failed_source(
+        &tasks,
+        &Source::Local(5, "logs/job-output.txt".into())
+    ));
+    assert!(is_failed_source(
+        &tasks,
+        &Source::Local(5, "logs/controller/logs/syslog.txt".into())
+    ));
+    assert!(!is_failed_source(
+        &tasks,
+        &Source::Local(5, "logs/compute/logs/syslog.txt".into())
+    ));
+    assert!(is_job_output(&source("logs/job-output.txt")));
+    assert!(!is_job_output(&source("logs/job-output.json")));
+    Ok(())
+}
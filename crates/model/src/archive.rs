@@ -0,0 +1,303 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides helpers to work with tar and zip archive members.
+//!
+//! The archive members are represented with a local source using
+//! the `archive.tar!member/path` convention.
+//!
+//! The tar archives can't be read at a member position, so their members are extracted
+//! with a single pass over the archive, when the first one is read.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::env::Env;
+use crate::reader::DecompressReader;
+use crate::Source;
+
+const TAR_EXTENSIONS: &[&str] = &[".tar", ".tar.gz", ".tar.zst"];
+const ZIP_EXTENSIONS: &[&str] = &[".zip"];
+
+/// The separator between the archive path and the member path.
+pub const MEMBER_SEPARATOR: char = '!';
+
+/// Check if a file name is a supported tar archive.
+pub fn is_tar_name(name: &str) -> bool {
+    TAR_EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+}
+
+/// Check if a file name is a supported zip archive.
+pub fn is_zip_name(name: &str) -> bool {
+    ZIP_EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+}
+
+/// Check if a path is a supported archive.
+pub fn is_archive(path: &Path) -> bool {
+    path.to_str()
+        .map(|name| is_tar_name(name) || is_zip_name(name))
+        .unwrap_or(false)
+}
+
+/// Split a member path into the archive path and the member name.
+pub fn split_member(path: &Path) -> Option<(&Path, &str)> {
+    let path_str = path.to_str()?;
+    path_str
+        .match_indices(MEMBER_SEPARATOR)
+        .map(|(pos, _)| (&path_str[..pos], &path_str[pos + 1..]))
+        .find(|(archive, _)| is_tar_name(archive) || is_zip_name(archive))
+        .map(|(archive, member)| (Path::new(archive), member))
+}
+
+fn member_source(base_len: usize, path: &Path, member: &Path) -> Source {
+    let member_path = format!("{}{}{}", path.display(), MEMBER_SEPARATOR, member.display());
+    Source::Local(base_len, PathBuf::from(member_path))
+}
+
+/// List the regular files of an archive.
+pub fn archive_sources(base_len: usize, path: &Path) -> Result<Vec<Source>> {
+    if path.to_str().map(is_zip_name).unwrap_or(false) {
+        zip_sources(base_len, path)
+    } else {
+        tar_sources(base_len, path)
+    }
+}
+
+/// Open an archive member.
+pub fn member_open(env: &Env, path: &Path, member: &str) -> Result<DecompressReader> {
+    if path.to_str().map(is_zip_name).unwrap_or(false) {
+        zip_member_open(path, member)
+    } else {
+        env.archives.open(path, member)
+    }
+}
+
+fn tar_open(path: &Path) -> Result<tar::Archive<DecompressReader>> {
+    Ok(tar::Archive::new(crate::reader::from_path(path)?))
+}
+
+/// List the regular files of a tar archive.
+fn tar_sources(base_len: usize, path: &Path) -> Result<Vec<Source>> {
+    let mut archive = tar_open(path)?;
+    let mut sources = Vec::new();
+    for entry in archive.entries().context("Failed to read archive")? {
+        let entry = entry.context("Failed to read archive entry")?;
+        if entry.header().entry_type().is_file() {
+            let member = entry.path().context("Invalid archive member path")?;
+            sources.push(member_source(base_len, path, &member));
+        }
+    }
+    Ok(sources)
+}
+
+/// Extract the regular files of a tar archive into a directory, with a single pass over the archive entries.
+/// The extracted files are returned by member name.
+fn tar_extract(path: &Path, dir: &Path) -> Result<HashMap<String, PathBuf>> {
+    let mut archive = tar_open(path)?;
+    let mut extracted = HashMap::new();
+    for entry in archive.entries().context("Failed to read archive")? {
+        let mut entry = entry.context("Failed to read archive entry")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let member = match entry.path().ok().and_then(|p| p.to_str().map(String::from)) {
+            Some(member) if !extracted.contains_key(&member) => member,
+            _ => continue,
+        };
+        let file_path = dir.join(extracted.len().to_string());
+        let mut file = File::create(&file_path).context("Failed to create extracted member")?;
+        std::io::copy(&mut entry, &mut file)
+            .with_context(|| format!("Failed to extract {} from {:?}", member, path))?;
+        extracted.insert(member, file_path);
+    }
+    Ok(extracted)
+}
+
+/// The extracted tar archives, which are removed with the [Env].
+#[derive(Default)]
+pub struct Extractions(Mutex<Extracted>);
+
+#[derive(Default)]
+struct Extracted {
+    // The extraction directory, created on the first extraction.
+    dir: Option<tempfile::TempDir>,
+    // The extracted members, by archive.
+    archives: HashMap<PathBuf, HashMap<String, PathBuf>>,
+}
+
+impl Extractions {
+    /// Open a tar archive member, extracting the archive on the first read.
+    fn open(&self, path: &Path, member: &str) -> Result<DecompressReader> {
+        let mut extracted = self.0.lock().unwrap();
+        if !extracted.archives.contains_key(path) {
+            if extracted.dir.is_none() {
+                extracted.dir =
+                    Some(tempfile::tempdir().context("Failed to create extraction directory")?);
+            }
+            let archive_dir = extracted
+                .dir
+                .as_ref()
+                .unwrap()
+                .path()
+                .join(extracted.archives.len().to_string());
+            std::fs::create_dir(&archive_dir).context("Failed to create extraction directory")?;
+            tracing::debug!(path = path.to_str(), "Extracting archive");
+            let members = tar_extract(path, &archive_dir)?;
+            extracted.archives.insert(path.to_path_buf(), members);
+        }
+        match extracted.archives[path].get(member) {
+            Some(file_path) => Ok(DecompressReader::Flat(
+                File::open(file_path).context("Failed to open archive member")?,
+            )),
+            None => Err(anyhow::anyhow!("{} not found in {:?}", member, path)),
+        }
+    }
+}
+
+fn zip_open(path: &Path) -> Result<zip::ZipArchive<File>> {
+    zip::ZipArchive::new(File::open(path)?).context("Failed to read zip archive")
+}
+
+/// List the regular files of a zip archive.
+fn zip_sources(base_len: usize, path: &Path) -> Result<Vec<Source>> {
+    let mut archive = zip_open(path)?;
+    let mut sources = Vec::new();
+    for idx in 0..archive.len() {
+        let entry = archive
+            .by_index_raw(idx)
+            .context("Failed to read zip entry")?;
+        // Skip the entries with an unsafe path, such as absolute path or parent dir.
+        // The member keeps the raw name, to be found again with [zip::ZipArchive::by_name].
+        if entry.is_file() && entry.enclosed_name().is_some() {
+            sources.push(member_source(base_len, path, Path::new(entry.name())));
+        }
+    }
+    Ok(sources)
+}
+
+/// Open a zip archive member.
+fn zip_member_open(path: &Path, member: &str) -> Result<DecompressReader> {
+    // Find the member data position in the archive file.
+    let (position, size, method) = {
+        let mut archive = zip_open(path)?;
+        let entry = archive
+            .by_name(member)
+            .with_context(|| format!("{} not found in {:?}", member, path))?;
+        (
+            entry.data_start(),
+            entry.compressed_size(),
+            entry.compression(),
+        )
+    };
+
+    // Re-open the archive at the member data, so that the content is streamed.
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(position))
+        .context("Failed to seek archive member")?;
+    let data = file.take(size);
+    match method {
+        zip::CompressionMethod::Stored => Ok(DecompressReader::ZipStored(data)),
+        zip::CompressionMethod::Deflated => Ok(DecompressReader::ZipDeflated(
+            flate2::read::DeflateDecoder::new(data),
+        )),
+        method => Err(anyhow::anyhow!(
+            "{}: unsupported zip compression {}",
+            member,
+            method
+        )),
+    }
+}
+
+#[test]
+fn test_split_member() {
+    assert_eq!(
+        split_member(Path::new("/tmp/sosreport.tar.zst!var/log/messages")),
+        Some((Path::new("/tmp/sosreport.tar.zst"), "var/log/messages"))
+    );
+    assert_eq!(
+        split_member(Path::new("/tmp/logs.zip!build/step.txt")),
+        Some((Path::new("/tmp/logs.zip"), "build/step.txt"))
+    );
+    assert_eq!(split_member(Path::new("/tmp/messages!1")), None);
+}
+
+#[test]
+fn test_tar_sources() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive_path = dir.path().join("must-gather.tar");
+    let mut builder = tar::Builder::new(std::fs::File::create(&archive_path).unwrap());
+    for (name, data) in [("pods/api.log", "api started\n"), ("events.log", "event\n")] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, data.as_bytes())
+            .unwrap();
+    }
+    builder.finish().unwrap();
+
+    let sources = archive_sources(0, &archive_path).unwrap();
+    assert_eq!(sources.len(), 2);
+    assert!(sources[0]
+        .as_str()
+        .ends_with("must-gather.tar!pods/api.log"));
+
+    // The members are read out of the archive order, from a single extraction.
+    let env = Env::new();
+    for (source, expected) in sources.iter().rev().zip(["event\n", "api started\n"]) {
+        let (path, member) = split_member(Path::new(source.as_str())).unwrap();
+        let mut content = String::new();
+        member_open(&env, path, member)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, expected);
+    }
+    assert_eq!(env.archives.0.lock().unwrap().archives.len(), 1);
+    assert!(member_open(&env, &archive_path, "missing.log").is_err());
+}
+
+#[test]
+fn test_zip_sources() {
+    use std::io::Write;
+    let dir = tempfile::tempdir().unwrap();
+    let archive_path = dir.path().join("logs.zip");
+    let mut writer = zip::ZipWriter::new(File::create(&archive_path).unwrap());
+    for (name, method, data) in [
+        (
+            "build/1_setup.txt",
+            zip::CompressionMethod::Stored,
+            "setup\n",
+        ),
+        (
+            "build/2_test.txt",
+            zip::CompressionMethod::Deflated,
+            "test failed\n",
+        ),
+        ("../escape.txt", zip::CompressionMethod::Stored, "unsafe\n"),
+    ] {
+        let options = zip::write::FileOptions::default().compression_method(method);
+        writer.start_file(name, options).unwrap();
+        writer.write_all(data.as_bytes()).unwrap();
+    }
+    writer.add_directory("empty/", Default::default()).unwrap();
+    writer.finish().unwrap();
+
+    let sources = archive_sources(0, &archive_path).unwrap();
+    assert_eq!(sources.len(), 2);
+    let env = Env::new();
+    for (source, expected) in sources.iter().zip(["setup\n", "test failed\n"]) {
+        let (path, member) = split_member(Path::new(source.as_str())).unwrap();
+        let mut content = String::new();
+        member_open(&env, path, member)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, expected);
+    }
+}
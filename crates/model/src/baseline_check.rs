@@ -0,0 +1,151 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the validation of the discovered baselines, before the training.
+//!
+//! A candidate is rejected when its build did not succeed, when its logs are not available,
+//! or when it is older than the `baseline_max_age_days` configuration.
+
+use crate::env::Env;
+use crate::Content;
+
+/// The verdict of a baseline candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaselineCheck {
+    pub baseline: Content,
+    /// Why the candidate was accepted or rejected.
+    pub reason: String,
+    pub accepted: bool,
+}
+
+impl std::fmt::Display for BaselineCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let verdict = if self.accepted {
+            "accepted"
+        } else {
+            "rejected"
+        };
+        write!(f, "{} {}: {}", verdict, self.baseline, self.reason)
+    }
+}
+
+/// The build result of a content, and the expected value of a successful build.
+fn build_result(content: &Content) -> Option<(&str, &str)> {
+    match content {
+        Content::Zuul(build) | Content::LocalZuulBuild(_, build) => {
+            Some((&build.result, "SUCCESS"))
+        }
+        Content::Jenkins(build) => Some((&build.result, "SUCCESS")),
+        Content::Buildkite(build) => Some((&build.state, "passed")),
+        Content::CircleCi(job) => Some((&job.status, "success")),
+        Content::Koji(build) => Some((&build.state, "COMPLETE")),
+        _ => None,
+    }
+}
+
+/// The age of a content in days, when it is known.
+fn age_days(content: &Content) -> Option<i64> {
+    match content {
+        Content::Zuul(build) | Content::LocalZuulBuild(_, build) => Some(
+            chrono::Utc::now()
+                .signed_duration_since(build.end_time)
+                .num_days(),
+        ),
+        _ => None,
+    }
+}
+
+/// Check a baseline candidate.
+pub fn check(env: &Env, baseline: &Content) -> Result<String, String> {
+    let mut reasons = Vec::new();
+    if let Some((result, success)) = build_result(baseline) {
+        if result != success {
+            return Err(format!("the build result is {}", result));
+        }
+        reasons.push(format!("the build result is {}", result));
+    }
+    if let Some(age) = age_days(baseline) {
+        match env.config.baseline_max_age_days() {
+            Some(max_age) if age > max_age as i64 => {
+                return Err(format!(
+                    "the build is {} days old, the maximum is {}",
+                    age, max_age
+                ))
+            }
+            _ => reasons.push(format!("the build is {} days old", age)),
+        }
+    }
+    match crate::content_get_sources_iter(baseline, env).next() {
+        Some(Ok(_)) => reasons.push("the logs are available".into()),
+        Some(Err(err)) => return Err(format!("the logs are not available: {}", err)),
+        None => return Err("the build has no logs".into()),
+    }
+    Ok(reasons.join(", "))
+}
+
+/// Check the baseline candidates, in their discovery order.
+pub fn check_all(env: &Env, baselines: Vec<Content>) -> Vec<BaselineCheck> {
+    baselines
+        .into_iter()
+        .map(|baseline| {
+            let (accepted, reason) = match check(env, &baseline) {
+                Ok(reason) => (true, reason),
+                Err(reason) => (false, reason),
+            };
+            let check = BaselineCheck {
+                baseline,
+                reason,
+                accepted,
+            };
+            tracing::info!("Baseline {}", check);
+            check
+        })
+        .collect()
+}
+
+#[test]
+fn test_baseline_check() {
+    use logjuicer_report::{Source, ZuulBuild};
+
+    let env = crate::env::Env {
+        config: crate::config::config_from_yaml("baseline_max_age_days: 30"),
+        ..Env::new()
+    };
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("job-output.txt"), "ok\n").unwrap();
+    let directory = Content::Directory(Source::from_pathbuf(dir.path().into()));
+    let empty = tempfile::tempdir().unwrap();
+    let failed = {
+        let mut build = ZuulBuild::sample("test");
+        build.result = "FAILURE".into();
+        Content::Zuul(Box::new(build))
+    };
+    let old = {
+        let mut build = ZuulBuild::sample("test");
+        build.result = "SUCCESS".into();
+        build.end_time = chrono::Utc::now() - chrono::Duration::days(42);
+        Content::Zuul(Box::new(build))
+    };
+    let checks = check_all(
+        &env,
+        vec![
+            directory.clone(),
+            Content::Directory(Source::from_pathbuf(empty.path().into())),
+            failed,
+            old,
+        ],
+    );
+    assert_eq!(
+        checks
+            .iter()
+            .map(|check| (check.accepted, check.reason.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            (true, "the logs are available"),
+            (false, "the build has no logs"),
+            (false, "the build result is FAILURE"),
+            (false, "the build is 42 days old, the maximum is 30"),
+        ]
+    );
+    assert_eq!(checks[0].baseline, directory);
+}
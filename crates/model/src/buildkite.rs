@@ -0,0 +1,182 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the Buildkite builds, where the sources are the jobs log and the build artifacts.
+//!
+//! The api requests use the `BUILDKITE_API_TOKEN` variable,
+//! and the baseline is the last passed build of the pipeline default branch.
+
+use anyhow::Result;
+use buildkite_build::{BuildUrl, Client};
+use url::Url;
+
+use crate::env::Env;
+use crate::{Baselines, Content, Source};
+use logjuicer_report::BuildkiteBuild;
+
+lazy_static::lazy_static! {
+    static ref CLIENT: Client = Client::from_env();
+}
+
+/// Check if the url needs the api token.
+pub fn is_api_url(url: &Url) -> bool {
+    CLIENT.is_api_url(url)
+}
+
+/// Create the request of an api url.
+pub fn request(client: &ureq::Agent, method: &str, url: &Url) -> ureq::Request {
+    CLIENT.request(client, method, url)
+}
+
+fn to_build_url(build: &BuildkiteBuild) -> BuildUrl {
+    BuildUrl {
+        org: build.org.clone(),
+        pipeline: build.pipeline.clone(),
+        number: build.number,
+    }
+}
+
+fn to_content(build_url: &BuildUrl, build: buildkite_build::Build) -> Content {
+    Content::Buildkite(Box::new(BuildkiteBuild {
+        url: build.web_url,
+        org: build_url.org.clone(),
+        pipeline: build_url.pipeline.clone(),
+        number: build.number,
+        branch: build.branch,
+        state: build.state,
+    }))
+}
+
+/// Check if the url is a buildkite build.
+pub fn content_from_buildkite_url(env: &Env, url: &Url) -> Option<Result<Content>> {
+    let build_url = buildkite_build::parse_build_url(url)?;
+    Some(get_content(&CLIENT, env, &build_url))
+}
+
+fn get_content(client: &Client, env: &Env, build_url: &BuildUrl) -> Result<Content> {
+    let build = client.get_build(&env.client, build_url)?;
+    Ok(to_content(build_url, build))
+}
+
+/// Use the last passed build of the pipeline default branch, or of the fallback branches.
+pub fn discover_baselines(build: &BuildkiteBuild, env: &Env) -> Result<Baselines> {
+    discover_baselines_with(&CLIENT, build, env)
+}
+
+fn discover_baselines_with(
+    client: &Client,
+    build: &BuildkiteBuild,
+    env: &Env,
+) -> Result<Baselines> {
+    tracing::info!("Discovering baselines for {}", build);
+    let build_url = to_build_url(build);
+    let branch = client
+        .get_build(&env.client, &build_url)?
+        .pipeline
+        .and_then(|pipeline| pipeline.default_branch)
+        .unwrap_or_else(|| "main".into());
+    // The fallback branches are used when the pipeline never passed on the default branch.
+    for branch in
+        std::iter::once(&*branch).chain(env.config.fallback_branches().iter().map(String::as_str))
+    {
+        match client.last_passed_build(&env.client, &build.org, &build.pipeline, branch)? {
+            Some(baseline) if baseline.number != build.number => {
+                return Ok(vec![to_content(&build_url, baseline)])
+            }
+            _ => {}
+        }
+    }
+    Ok(vec![])
+}
+
+pub fn sources_iter(build: &BuildkiteBuild, env: &Env) -> Box<dyn Iterator<Item = Result<Source>>> {
+    match get_sources(&CLIENT, build, env) {
+        Ok(sources) => Box::new(sources.into_iter().map(Ok)),
+        Err(err) => Box::new(std::iter::once(Err(anyhow::anyhow!(
+            "Failed to list the jobs of {}: {}",
+            build,
+            err
+        )))),
+    }
+}
+
+fn get_sources(client: &Client, build: &BuildkiteBuild, env: &Env) -> Result<Vec<Source>> {
+    let build_url = to_build_url(build);
+    let mut sources = Vec::new();
+    for job in client.get_build(&env.client, &build_url)?.jobs {
+        if let Some(mut url) = job.raw_log_url.clone() {
+            // The api ignores the extra name parameter, which is the job source relative path.
+            let name =
+                url::form_urlencoded::byte_serialize(job.label().as_bytes()).collect::<String>();
+            url.query_pairs_mut().append_pair("name", job.label());
+            sources.push(Source::Remote(url.as_str().len() - name.len(), url));
+        }
+    }
+    for artifact in client.get_artifacts(&env.client, &build_url)? {
+        // Similarly, the artifact path is the relative path.
+        let url = Url::parse(&format!("{}?path={}", artifact.download_url, artifact.path))?;
+        let prefix = url.as_str().rfind("?path=").unwrap_or(0) + "?path=".len();
+        sources.push(Source::Remote(prefix, url));
+    }
+    Ok(sources)
+}
+
+#[test]
+fn test_buildkite_build() -> Result<()> {
+    let env = Env::new();
+    let mut server = mockito::Server::new();
+    let api = server.url();
+    let build = |number: u64, state: &str| {
+        format!(
+            r#"{{"number":{number},"state":"{state}","branch":"main","web_url":"https://buildkite.com/org/app/builds/{number}","pipeline":{{"default_branch":"main"}},"jobs":[{{"id":"w1","type":"waiter"}},{{"id":"j{number}","type":"script","name":":hammer: Build","raw_log_url":"{api}/organizations/org/pipelines/app/builds/{number}/jobs/j{number}/log.txt"}}]}}"#
+        )
+    };
+    let build_mock = server
+        .mock("GET", "/organizations/org/pipelines/app/builds/42")
+        .with_body(build(42, "failed"))
+        .expect(3)
+        .create();
+    let artifacts_mock = server
+        .mock("GET", "/organizations/org/pipelines/app/builds/42/artifacts")
+        .match_query(mockito::Matcher::Any)
+        .with_body(format!(
+            r#"[{{"id":"a1","job_id":"j42","path":"logs/app.log","download_url":"{api}/organizations/org/pipelines/app/builds/42/jobs/j42/artifacts/a1/download"}}]"#
+        ))
+        .expect(1)
+        .create();
+    let passed_mock = server
+        .mock("GET", "/organizations/org/pipelines/app/builds/")
+        .match_query(mockito::Matcher::UrlEncoded("branch".into(), "main".into()))
+        .with_body(format!("[{}]", build(41, "passed")))
+        .expect(1)
+        .create();
+
+    let client = Client {
+        api_url: Url::parse(&format!("{}/", api))?,
+        token: None,
+    };
+    let url = Url::parse("https://buildkite.com/org/app/builds/42#j42")?;
+    let build_url = buildkite_build::parse_build_url(&url).unwrap();
+    let build = match get_content(&client, &env, &build_url)? {
+        Content::Buildkite(build) => build,
+        _ => panic!("Expected a buildkite build"),
+    };
+    assert_eq!(build.state.as_ref(), "failed");
+
+    let sources = get_sources(&client, &build, &env)?;
+    let paths = sources
+        .iter()
+        .map(|source| source.get_relative())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec!["%3Ahammer%3A+Build", "logs/app.log"]);
+
+    let baselines = discover_baselines_with(&client, &build, &env)?;
+    assert_eq!(
+        baselines[0].to_string(),
+        "Buildkite(https://buildkite.com/org/app/builds/41)"
+    );
+    build_mock.assert();
+    artifacts_mock.assert();
+    passed_mock.assert();
+    Ok(())
+}
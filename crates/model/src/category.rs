@@ -0,0 +1,234 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the source category classifier.
+//!
+//! A category is assigned by the first rule matching either the source path or an anomaly line.
+//! Each category can define its own analysis policy, which is resolved from the source path,
+//! before the content is read.
+
+use logjuicer_report::{AnomalyContext, CategoryPolicy, Source};
+use regex::RegexSet;
+use serde::{Deserialize, Serialize};
+
+/// A category rule, as defined in the configuration file.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CategoryRule {
+    pub name: String,
+    #[serde(default)]
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub content: Vec<String>,
+    /// The minimum anomaly distance, between 0.0 and 1.0.
+    pub threshold: Option<f32>,
+    /// The number of context lines before and after an anomaly.
+    pub context: Option<usize>,
+    /// Indicate if the category anomalies fail the report.
+    pub gate: Option<bool>,
+}
+
+/// The analysis policy of a source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Policy {
+    pub threshold: f32,
+    pub context: usize,
+    pub gate: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            threshold: crate::process::THRESHOLD,
+            context: crate::process::CTX_DISTANCE,
+            gate: true,
+        }
+    }
+}
+
+struct Rule {
+    name: Box<str>,
+    paths: RegexSet,
+    content: RegexSet,
+    policy: Policy,
+}
+
+pub struct Classifier {
+    rules: Vec<Rule>,
+}
+
+fn default_rule(name: &str, paths: &[&str], content: &[&str]) -> CategoryRule {
+    CategoryRule {
+        name: name.into(),
+        paths: paths.iter().map(|s| s.to_string()).collect(),
+        content: content.iter().map(|s| s.to_string()).collect(),
+        threshold: None,
+        context: None,
+        gate: None,
+    }
+}
+
+/// The rules used when the configuration does not define categories.
+pub fn default_rules() -> Vec<CategoryRule> {
+    vec![
+        default_rule(
+            "ci-infra",
+            &["job-output", "zuul", "ansible", "build-log", "prow"],
+            &[],
+        ),
+        default_rule(
+            "kernel",
+            &["dmesg", "kern.log", "kernel"],
+            &["kernel: ", "Call Trace:"],
+        ),
+        default_rule(
+            "database",
+            &[
+                "mysql", "mariadb", "postgres", "redis", "mongo", "etcd", "galera",
+            ],
+            &[],
+        ),
+        default_rule(
+            "network",
+            &[
+                "network",
+                "neutron",
+                "ovn",
+                "openvswitch",
+                "ovs-",
+                "dnsmasq",
+                "haproxy",
+                "iptables",
+            ],
+            &[],
+        ),
+        // The last rule matches every source.
+        default_rule("application", &[""], &[]),
+    ]
+}
+
+impl Classifier {
+    pub fn new(rules: &[CategoryRule]) -> Result<Classifier, crate::config::Error> {
+        let default = Policy::default();
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                // At least one line of context is needed to complete an anomaly.
+                if rule.context == Some(0) {
+                    return Err(crate::config::Error::BadContext(rule.name.clone()));
+                }
+                Ok(Rule {
+                    name: rule.name.as_str().into(),
+                    paths: RegexSet::new(&rule.paths)?,
+                    content: RegexSet::new(&rule.content)?,
+                    policy: Policy {
+                        threshold: rule.threshold.unwrap_or(default.threshold),
+                        context: rule.context.unwrap_or(default.context),
+                        gate: rule.gate.unwrap_or(default.gate),
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>, crate::config::Error>>()?;
+        Ok(Classifier { rules })
+    }
+
+    /// Find the first rule matching either the source path or an anomaly line.
+    fn resolve(&self, source: &Source, anomalies: &[AnomalyContext]) -> Option<&Rule> {
+        let path = source.get_relative();
+        self.rules.iter().find(|rule| {
+            rule.paths.is_match(path)
+                || (!rule.content.is_empty()
+                    && anomalies
+                        .iter()
+                        .any(|ctx| rule.content.is_match(&ctx.anomaly.line)))
+        })
+    }
+
+    /// Find the category of a source.
+    pub fn classify(&self, source: &Source, anomalies: &[AnomalyContext]) -> Option<Box<str>> {
+        self.resolve(source, anomalies)
+            .map(|rule| rule.name.clone())
+    }
+
+    /// Find the policy of a source, before its content is read.
+    pub fn policy(&self, source: &Source) -> Policy {
+        self.resolve(source, &[])
+            .map(|rule| rule.policy)
+            .unwrap_or_default()
+    }
+
+    /// The policies to be recorded in the report.
+    pub fn policies(&self) -> Vec<CategoryPolicy> {
+        self.rules
+            .iter()
+            .map(|rule| CategoryPolicy {
+                category: rule.name.clone(),
+                threshold: rule.policy.threshold,
+                context: rule.policy.context,
+                gate: rule.policy.gate,
+            })
+            .collect()
+    }
+}
+
+impl Default for Classifier {
+    fn default() -> Self {
+        Classifier::new(&default_rules()).unwrap()
+    }
+}
+
+#[test]
+fn test_classifier() {
+    let classifier = Classifier::default();
+    let check = |path: &str, line: &str| {
+        let anomalies = vec![AnomalyContext {
+            before: vec![],
+            anomaly: logjuicer_report::Anomaly {
+                distance: 0.5,
+                pos: 1,
+                line: line.into(),
+            },
+            after: vec![],
+            targets: vec![],
+        }];
+        classifier
+            .classify(&Source::from_pathbuf(path.into()), &anomalies)
+            .map(|c| c.to_string())
+    };
+    assert_eq!(check("job-output.txt", "failed"), Some("ci-infra".into()));
+    assert_eq!(
+        check("var/log/mariadb/mariadb.log", ""),
+        Some("database".into())
+    );
+    assert_eq!(
+        check("var/log/messages", "kernel: Out of memory"),
+        Some("kernel".into())
+    );
+    assert_eq!(
+        check("api/service.log", "error"),
+        Some("application".into())
+    );
+}
+
+#[test]
+fn test_policy() {
+    let mut rules = default_rules();
+    rules[1].threshold = Some(0.5);
+    rules[1].gate = Some(false);
+    let classifier = Classifier::new(&rules).unwrap();
+    let policy = |path: &str| classifier.policy(&Source::from_pathbuf(path.into()));
+    assert_eq!(
+        policy("var/log/dmesg.txt"),
+        Policy {
+            threshold: 0.5,
+            context: 3,
+            gate: false
+        }
+    );
+    assert_eq!(policy("api/service.log"), Policy::default());
+    assert_eq!(classifier.policies()[1].category.as_ref(), "kernel");
+
+    // The anomaly context can not be empty.
+    rules[1].context = Some(0);
+    assert!(Classifier::new(&rules).is_err());
+}
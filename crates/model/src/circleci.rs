@@ -0,0 +1,237 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the CircleCI jobs, where the sources are the steps output and the job artifacts.
+//!
+//! The api requests use the `CIRCLECI_TOKEN` variable,
+//! and the baseline is the last successful run of the workflow job on the project default branch.
+
+use anyhow::Result;
+use circleci_build::{Client, JobUrl, Workflow};
+use url::Url;
+
+use crate::env::Env;
+use crate::reader::DecompressReader;
+use crate::{Baselines, Content, Source};
+use logjuicer_report::CircleCiJob;
+
+lazy_static::lazy_static! {
+    static ref CLIENT: Client = Client::from_env();
+}
+
+/// Check if the url needs the api token.
+pub fn is_api_url(url: &Url) -> bool {
+    CLIENT.is_api_url(url)
+}
+
+/// Create the request of an api url.
+pub fn request(client: &ureq::Agent, method: &str, url: &Url) -> ureq::Request {
+    CLIENT.request(client, method, url)
+}
+
+/// Check if the url is a step output, which is served as a list of json messages.
+pub fn is_output_url(url: &Url) -> bool {
+    CLIENT.is_api_url(url) && url.path().contains("/output/")
+}
+
+fn to_job_url(job: &CircleCiJob) -> JobUrl {
+    JobUrl {
+        slug: job.slug.clone(),
+        number: job.number,
+    }
+}
+
+fn to_content(slug: &str, job: circleci_build::Job) -> Content {
+    let (job_name, workflow) = match job.workflows {
+        Some(workflow) => (workflow.job_name, workflow.workflow_name),
+        None => ("".into(), "".into()),
+    };
+    Content::CircleCi(Box::new(CircleCiJob {
+        slug: slug.into(),
+        number: job.build_num,
+        job_name,
+        workflow,
+        branch: job.branch.unwrap_or_else(|| "".into()),
+        status: job.status,
+    }))
+}
+
+/// Check if the url is a circleci job.
+pub fn content_from_circleci_url(env: &Env, url: &Url) -> Option<Result<Content>> {
+    let job_url = circleci_build::parse_job_url(url)?;
+    Some(get_content(&CLIENT, env, &job_url))
+}
+
+fn get_content(client: &Client, env: &Env, job_url: &JobUrl) -> Result<Content> {
+    let job = client.get_job(&env.client, job_url)?;
+    Ok(to_content(&job_url.slug, job))
+}
+
+/// Use the last successful run of the workflow job on the project default branch, or on the fallback branches.
+pub fn discover_baselines(job: &CircleCiJob, env: &Env) -> Result<Baselines> {
+    discover_baselines_with(&CLIENT, job, env)
+}
+
+fn discover_baselines_with(client: &Client, job: &CircleCiJob, env: &Env) -> Result<Baselines> {
+    tracing::info!("Discovering baselines for {}", job);
+    if job.workflow.is_empty() {
+        // The job is not part of a workflow.
+        return Ok(vec![]);
+    }
+    let branch = client
+        .default_branch(&env.client, &job.slug)?
+        .unwrap_or_else(|| "main".into());
+    let workflow = Workflow {
+        job_name: job.job_name.clone(),
+        workflow_name: job.workflow.clone(),
+    };
+    // The fallback branches are used when the job never succeeded on the default branch.
+    for branch in
+        std::iter::once(&*branch).chain(env.config.fallback_branches().iter().map(String::as_str))
+    {
+        match client.last_successful_job(&env.client, &job.slug, branch, &workflow)? {
+            Some(baseline) if baseline.build_num != job.number => {
+                return Ok(vec![to_content(&job.slug, baseline)])
+            }
+            _ => {}
+        }
+    }
+    Ok(vec![])
+}
+
+pub fn sources_iter(job: &CircleCiJob, env: &Env) -> Box<dyn Iterator<Item = Result<Source>>> {
+    match get_sources(&CLIENT, job, env) {
+        Ok(sources) => Box::new(sources.into_iter().map(Ok)),
+        Err(err) => Box::new(std::iter::once(Err(anyhow::anyhow!(
+            "Failed to list the steps of {}: {}",
+            job,
+            err
+        )))),
+    }
+}
+
+fn get_sources(client: &Client, job: &CircleCiJob, env: &Env) -> Result<Vec<Source>> {
+    let job_url = to_job_url(job);
+    let mut sources = Vec::new();
+    for step in client.get_job(&env.client, &job_url)?.steps {
+        let parallel = step.actions.len() > 1;
+        for action in step.actions.iter().filter(|action| action.has_output) {
+            let name = if parallel {
+                format!("{} {}", step.name, action.index)
+            } else {
+                step.name.to_string()
+            };
+            // The api ignores the extra name parameter, which is the step source relative path.
+            let mut url = client.output_url(&job_url, action)?;
+            let encoded = url::form_urlencoded::byte_serialize(name.as_bytes()).collect::<String>();
+            url.query_pairs_mut().append_pair("name", &name);
+            sources.push(Source::Remote(url.as_str().len() - encoded.len(), url));
+        }
+    }
+    for artifact in client.get_artifacts(&env.client, &job_url)? {
+        // The artifact url ends with its path.
+        let prefix = artifact
+            .url
+            .as_str()
+            .len()
+            .saturating_sub(artifact.path.len());
+        sources.push(Source::Remote(prefix, artifact.url));
+    }
+    Ok(sources)
+}
+
+/// Open a step output, joining the messages.
+pub fn open(env: &Env, url: &Url) -> Result<DecompressReader> {
+    open_with(&CLIENT, env, url)
+}
+
+fn open_with(client: &Client, env: &Env, url: &Url) -> Result<DecompressReader> {
+    let mut text = String::new();
+    for output in client.get_output(&env.client, url)? {
+        text.push_str(&output.message.replace("\r\n", "\n"));
+    }
+    Ok(DecompressReader::RemoteDecoder(Box::new(
+        std::io::Cursor::new(text.into_bytes()),
+    )))
+}
+
+#[test]
+fn test_circleci_job() -> Result<()> {
+    use std::io::Read;
+    let env = Env::new();
+    let mut server = mockito::Server::new();
+    let api = server.url();
+    let job = |number: u64, status: &str| {
+        format!(
+            r#"{{"build_num":{number},"status":"{status}","branch":"main","workflows":{{"job_name":"test","workflow_name":"ci"}},"steps":[{{"name":"Checkout code","actions":[{{"index":0,"step":0,"has_output":false}}]}},{{"name":"Run tests","actions":[{{"index":0,"step":1,"has_output":true}},{{"index":1,"step":1,"has_output":true}}]}}]}}"#
+        )
+    };
+    let job_mock = server
+        .mock("GET", "/v1.1/project/gh/org/repo/42/")
+        .with_body(job(42, "failed"))
+        .expect(2)
+        .create();
+    let artifacts_mock = server
+        .mock("GET", "/v1.1/project/gh/org/repo/42/artifacts")
+        .with_body(r#"[{"path":"logs/app.log","url":"https://output.circle-artifacts.com/output/job/0d1c/artifacts/0/logs/app.log"}]"#)
+        .expect(1)
+        .create();
+    let project_mock = server
+        .mock("GET", "/v2/project/gh/org/repo")
+        .with_body(r#"{"vcs_info":{"default_branch":"main"}}"#)
+        .expect(1)
+        .create();
+    let tree_mock = server
+        .mock("GET", "/v1.1/project/gh/org/repo/tree/main")
+        .match_query(mockito::Matcher::Any)
+        .with_body(format!("[{}]", job(41, "success")))
+        .expect(1)
+        .create();
+    let output_mock = server
+        .mock("GET", "/v1.1/project/gh/org/repo/42/output/1/1")
+        .match_query(mockito::Matcher::Any)
+        .with_body(
+            r#"[{"type":"out","message":"first\r\nsec"},{"type":"err","message":"ond\r\n"}]"#,
+        )
+        .expect(1)
+        .create();
+
+    let client = Client {
+        api_url: Url::parse(&format!("{}/", api))?,
+        token: None,
+    };
+    let url = Url::parse("https://circleci.com/gh/org/repo/42")?;
+    let job_url = circleci_build::parse_job_url(&url).unwrap();
+    let job = match get_content(&client, &env, &job_url)? {
+        Content::CircleCi(job) => job,
+        _ => panic!("Expected a circleci job"),
+    };
+    assert_eq!(job.job_name.as_ref(), "test");
+
+    let sources = get_sources(&client, &job, &env)?;
+    let paths = sources
+        .iter()
+        .map(|source| source.get_relative())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec!["Run+tests+0", "Run+tests+1", "logs/app.log"]);
+
+    let output_url = match &sources[1] {
+        Source::Remote(_, url) => url.clone(),
+        _ => panic!("Expected a remote source"),
+    };
+    let mut lines = String::new();
+    match open_with(&client, &env, &output_url)? {
+        DecompressReader::RemoteDecoder(mut reader) => reader.read_to_string(&mut lines)?,
+        _ => panic!("Expected a remote reader"),
+    };
+    assert_eq!(lines, "first\nsecond\n");
+
+    let baselines = discover_baselines_with(&client, &job, &env)?;
+    assert_eq!(baselines[0].to_string(), "CircleCi(gh/org/repo/41)");
+    job_mock.assert();
+    artifacts_mock.assert();
+    project_mock.assert();
+    tree_mock.assert();
+    output_mock.assert();
+    Ok(())
+}
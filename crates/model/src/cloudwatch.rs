@@ -0,0 +1,160 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the AWS CloudWatch log groups, where the sources are the events of the log streams for a time range.
+//!
+//! The baseline is the same log group for an earlier time range, by default one week before.
+
+use anyhow::{Context, Result};
+use chrono::Duration;
+use std::io::Read;
+use url::Url;
+
+use crate::env::Env;
+use crate::reader::DecompressReader;
+use crate::timerange::{parse_range, BASELINE_OFFSET_DAYS};
+use crate::{Baselines, Content, Source};
+use logjuicer_report::CloudWatchQuery;
+
+lazy_static::lazy_static! {
+    static ref CLIENT: cloudwatch_logs::Client = cloudwatch_logs::Client::from_env();
+}
+
+pub fn is_cloudwatch_url(url: &Url) -> bool {
+    url.scheme() == "cloudwatch"
+}
+
+/// Create the query for the `since` and `until` time range.
+pub fn new_query(
+    log_group: &str,
+    log_streams: &[String],
+    since: &str,
+    until: &str,
+) -> Result<CloudWatchQuery> {
+    let (start, end) = parse_range(since, until)?;
+    Ok(CloudWatchQuery {
+        log_group: log_group.into(),
+        log_streams: log_streams.iter().map(|s| s.as_str().into()).collect(),
+        start,
+        end,
+    })
+}
+
+/// Use the same log group, one week before.
+pub fn discover_baselines(query: &CloudWatchQuery, _env: &Env) -> Result<Baselines> {
+    Ok(vec![Content::CloudWatch(Box::new(
+        query.shift(Duration::days(BASELINE_OFFSET_DAYS)),
+    ))])
+}
+
+pub fn sources_iter(query: &CloudWatchQuery) -> Box<dyn Iterator<Item = Result<Source>>> {
+    match query.sources() {
+        Ok(sources) => Box::new(sources.into_iter().map(Ok)),
+        Err(e) => Box::new(std::iter::once(Err(e.into()))),
+    }
+}
+
+/// Decode a `cloudwatch://logs/` url.
+fn parse_filter(url: &Url) -> Result<cloudwatch_logs::Filter> {
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    let time = |name: &str| -> Result<i64> {
+        param(name)
+            .ok_or_else(|| anyhow::anyhow!("{}: missing {}", url, name))?
+            .parse()
+            .with_context(|| format!("{}: bad {}", url, name))
+    };
+    Ok(cloudwatch_logs::Filter {
+        log_group: param("group").ok_or_else(|| anyhow::anyhow!("{}: missing group", url))?,
+        log_streams: param("stream").into_iter().collect(),
+        start_time: time("start")?,
+        end_time: time("end")?,
+    })
+}
+
+/// Open the log events.
+pub fn open(env: &Env, url: &Url) -> Result<DecompressReader> {
+    Ok(DecompressReader::RemoteDecoder(Box::new(EventsReader {
+        client: &CLIENT,
+        agent: env.client.clone(),
+        filter: parse_filter(url)?,
+        next_token: Some(None),
+        buffer: std::io::Cursor::new(Vec::new()),
+    })))
+}
+
+/// Read the events message, requesting the next page when the buffer is consumed.
+struct EventsReader {
+    client: &'static cloudwatch_logs::Client,
+    agent: ureq::Agent,
+    filter: cloudwatch_logs::Filter,
+    /// The token of the next page, None when the range is completed.
+    next_token: Option<Option<String>>,
+    buffer: std::io::Cursor<Vec<u8>>,
+}
+
+impl Read for EventsReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let size = self.buffer.read(buf)?;
+            if size > 0 || buf.is_empty() {
+                return Ok(size);
+            }
+            let next_token = match self.next_token.take() {
+                Some(next_token) => next_token,
+                None => return Ok(0),
+            };
+            let page = self
+                .client
+                .filter_log_events(&self.agent, &self.filter, next_token.as_deref())
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
+            let mut buffer = Vec::new();
+            for event in page.events {
+                buffer.extend_from_slice(event.message.trim_end_matches('\n').as_bytes());
+                buffer.push(b'\n');
+            }
+            // The pages may be empty before the end of the range.
+            self.next_token = page.next_token.map(Some);
+            self.buffer = std::io::Cursor::new(buffer);
+        }
+    }
+}
+
+#[test]
+fn test_cloudwatch_query() -> Result<()> {
+    let query = new_query(
+        "/aws/lambda/app",
+        &["2023/10/15/[$LATEST]42".into()],
+        "1970-01-01T00:00:00Z",
+        "1970-01-01T00:01:00Z",
+    )?;
+    let sources = sources_iter(&query).collect::<Result<Vec<_>>>()?;
+    assert_eq!(
+        sources[0].get_relative(),
+        "2023%2F10%2F15%2F%5B%24LATEST%5D42"
+    );
+    let url = match &sources[0] {
+        Source::Remote(_, url) => url.clone(),
+        _ => panic!("Expected a remote source"),
+    };
+    assert!(is_cloudwatch_url(&url));
+    assert_eq!(
+        parse_filter(&url)?,
+        cloudwatch_logs::Filter {
+            log_group: "/aws/lambda/app".into(),
+            log_streams: vec!["2023/10/15/[$LATEST]42".into()],
+            start_time: 0,
+            end_time: 60000,
+        }
+    );
+
+    let baselines = discover_baselines(&query, &Env::new())?;
+    assert_eq!(
+        baselines[0].to_string(),
+        "CloudWatch(/aws/lambda/app:2023/10/15/[$LATEST]42 [1969-12-25T00:00:00Z, 1969-12-25T00:01:00Z])"
+    );
+    Ok(())
+}
@@ -3,17 +3,50 @@
 
 //! This module provides a model configuration.
 
-use logjuicer_report::Source;
-use regex::RegexSet;
+use crate::category::{CategoryRule, Classifier, Policy};
+use logjuicer_report::redact::Redactor;
+use logjuicer_report::{AnomalyContext, CategoryPolicy, LinkTemplate, Source};
+use logjuicer_tokenizer::rules::Rules;
+use regex::{Regex, RegexSet};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use thiserror::Error;
+use url::Url;
 
 mod default_excludes;
 
 pub struct Config {
     includes: Option<RegexSet>,
     excludes: RegexSet,
+    classifier: Classifier,
+    logfmt: RegexSet,
+    csv: Vec<(RegexSet, CsvRule)>,
+    tokenizer_rules: Rules,
+    limits: Limits,
+    strip_ansi: bool,
+    locale: Locale,
+    redactor: Redactor,
+    link_templates: Vec<LinkTemplate>,
+    zuul: Vec<ZuulSettings>,
+    ansible_failed_tasks: bool,
+    baseline_builds: usize,
+    baseline_pins: Vec<(Regex, BaselinePin)>,
+    fallback_branches: Vec<String>,
+    baseline_max_age_days: Option<u32>,
+    model_store: Option<ModelStoreSettings>,
+    groups: Vec<(RegexSet, String)>,
+    tls: Option<TlsSettings>,
+    http: HttpSettings,
+}
+
+/// The baseline of the jobs matching a pin rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BaselinePin {
+    /// The job is not analyzed.
+    Skip,
+    /// The baseline is this url, instead of the discovered one.
+    Url(Url),
 }
 
 #[derive(Error, Debug)]
@@ -32,6 +65,15 @@ pub enum Error {
 
     #[error("unknown format: {0}")]
     UnknownFormat(String),
+
+    #[error("invalid token: {0}, expected an uppercase word starting with %")]
+    BadToken(String),
+
+    #[error("invalid baseline url: {0}")]
+    BadUrl(#[from] url::ParseError),
+
+    #[error("invalid context for the {0} category, expected at least one line")]
+    BadContext(String),
 }
 
 impl Config {
@@ -69,11 +111,88 @@ impl Config {
         } else {
             RegexSet::new(&cf.excludes)
         }?;
-        Ok(Config { includes, excludes })
+        let classifier = if cf.categories.is_empty() {
+            Classifier::default()
+        } else {
+            Classifier::new(&cf.categories)?
+        };
+        let logfmt = RegexSet::new(&cf.logfmt)?;
+        let csv = cf
+            .csv
+            .iter()
+            .map(|rule| Ok((RegexSet::new(&rule.paths)?, rule.clone())))
+            .collect::<Result<Vec<_>, Error>>()?;
+        if let Some(rule) = cf
+            .tokenizer_rules
+            .iter()
+            .find(|rule| !is_token(&rule.token))
+        {
+            return Err(Error::BadToken(rule.token.clone()));
+        }
+        let tokenizer_rules = Rules::new(
+            &cf.tokenizer_rules
+                .iter()
+                .map(|rule| (&rule.pattern, &rule.token))
+                .collect::<Vec<_>>(),
+        )?
+        .with_preserve(&cf.preserve_tokens)?;
+        let redactor = Redactor::new(cf.redact.builtins, &cf.redact.patterns)?;
+        let link_templates = cf
+            .links
+            .iter()
+            .map(|(provider, template)| LinkTemplate {
+                provider: provider.as_str().into(),
+                template: template.as_str().into(),
+            })
+            .collect();
+        let baseline_pins = cf
+            .baseline_pins
+            .iter()
+            .map(|rule| {
+                let pin = match rule.baseline.as_str() {
+                    "skip" => BaselinePin::Skip,
+                    url => BaselinePin::Url(Url::parse(url)?),
+                };
+                Ok((Regex::new(&rule.job)?, pin))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let groups = cf
+            .groups
+            .iter()
+            .map(|rule| Ok((RegexSet::new(&rule.paths)?, rule.name.clone())))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Config {
+            includes,
+            excludes,
+            classifier,
+            logfmt,
+            csv,
+            tokenizer_rules,
+            limits: cf.limits.clone(),
+            strip_ansi: cf.strip_ansi,
+            locale: cf.locale,
+            redactor,
+            link_templates,
+            zuul: cf.zuul.clone(),
+            ansible_failed_tasks: cf.ansible_failed_tasks,
+            baseline_builds: cf.baseline_builds.unwrap_or(1).max(1),
+            baseline_pins,
+            fallback_branches: cf.fallback_branches.clone(),
+            baseline_max_age_days: cf.baseline_max_age_days,
+            model_store: cf.model_store.clone(),
+            groups,
+            tls: cf.tls.clone(),
+            http: cf.http.clone(),
+        })
     }
 
     pub fn is_source_valid(&self, source: &Source) -> bool {
-        let fp = source.get_relative().trim_end_matches(".gz");
+        let fp = source
+            .get_relative()
+            .trim_end_matches(".gz")
+            .trim_end_matches(".zst")
+            .trim_end_matches(".xz")
+            .trim_end_matches(".bz2");
         if let Some(includes) = &self.includes {
             if !includes.is_match(fp) {
                 return false;
@@ -81,6 +200,169 @@ impl Config {
         }
         !self.excludes.is_match(fp)
     }
+
+    /// Check if the source lines are formatted with logfmt.
+    pub fn is_logfmt(&self, source: &Source) -> bool {
+        self.logfmt.is_match(source.get_relative())
+    }
+
+    /// Get the CSV format of the source lines.
+    pub fn csv_format(&self, source: &Source) -> Option<logjuicer_tokenizer::csv::Format> {
+        let path = source.get_relative();
+        let (_, rule) = self.csv.iter().find(|(paths, _)| paths.is_match(path))?;
+        let delimiter =
+            rule.delimiter
+                .unwrap_or_else(|| if path.contains(".tsv") { '\t' } else { ',' });
+        Some(logjuicer_tokenizer::csv::Format {
+            delimiter,
+            message: rule.message.clone(),
+        })
+    }
+
+    /// The user-defined normalization rules and preserved patterns, applied before the tokenization.
+    pub fn tokenizer_rules(&self) -> &Rules {
+        &self.tokenizer_rules
+    }
+
+    /// A description of the settings that change the tokens, to detect incompatible models.
+    pub fn tokenizer_settings(&self) -> String {
+        let mut settings = vec![format!("strip_ansi={}", self.strip_ansi)];
+        if self.locale.normalize {
+            settings.push("locale=normalize".into());
+        }
+        if !self.logfmt.is_empty() {
+            settings.push(format!("logfmt={}", self.logfmt.patterns().join(",")));
+        }
+        for (paths, rule) in &self.csv {
+            settings.push(format!(
+                "csv={}:{:?}",
+                paths.patterns().join(","),
+                rule.message
+            ));
+        }
+        for (pattern, token) in self.tokenizer_rules.iter() {
+            settings.push(format!("rule={}:{}", pattern, token));
+        }
+        for pattern in self.tokenizer_rules.preserved() {
+            settings.push(format!("preserve={}", pattern));
+        }
+        if let Some(max_line_length) = self.limits.max_line_length {
+            settings.push(format!("max_line_length={}", max_line_length));
+        }
+        for (paths, name) in &self.groups {
+            settings.push(format!("group={}:{}", name, paths.patterns().join(",")));
+        }
+        settings.join(" ")
+    }
+
+    /// Check if the ANSI escape sequences are removed.
+    pub fn strip_ansi(&self) -> bool {
+        self.strip_ansi
+    }
+
+    pub fn locale(&self) -> &Locale {
+        &self.locale
+    }
+
+    /// The secrets redaction, applied to the report lines.
+    pub fn redactor(&self) -> &Redactor {
+        &self.redactor
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Replace the limits, e.g. with the command line arguments.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits
+    }
+
+    /// The maximum line length, the longer lines are truncated.
+    pub fn max_line_length(&self) -> usize {
+        self.limits
+            .max_line_length
+            .unwrap_or(logjuicer_iterator::DEFAULT_MAX_LINE_LENGTH)
+    }
+
+    /// The number of remote sources downloaded concurrently, ahead of the analysis.
+    pub fn downloads(&self) -> usize {
+        self.limits.downloads.unwrap_or(DEFAULT_DOWNLOADS).max(1)
+    }
+
+    pub fn categorize(&self, source: &Source, anomalies: &[AnomalyContext]) -> Option<Box<str>> {
+        self.classifier.classify(source, anomalies)
+    }
+
+    pub fn source_policy(&self, source: &Source) -> Policy {
+        self.classifier.policy(source)
+    }
+
+    pub fn category_policies(&self) -> Vec<CategoryPolicy> {
+        self.classifier.policies()
+    }
+
+    /// Restrict the zuul builds analysis to the failed ansible tasks.
+    pub fn ansible_failed_tasks(&self) -> bool {
+        self.ansible_failed_tasks
+    }
+
+    /// The number of successful builds discovered to train the model.
+    pub fn baseline_builds(&self) -> usize {
+        self.baseline_builds
+    }
+
+    /// The settings of the zuul deployment serving an url.
+    pub fn zuul_settings(&self, url: &Url) -> Option<&ZuulSettings> {
+        self.zuul.iter().find(|settings| settings.matches(url))
+    }
+
+    /// The pinned baseline of a job, which replaces the automatic discovery.
+    pub fn baseline_pin(&self, job_name: &str) -> Option<&BaselinePin> {
+        self.baseline_pins
+            .iter()
+            .find(|(re, _)| re.is_match(job_name))
+            .map(|(_, pin)| pin)
+    }
+
+    /// The other branches to look for a baseline, when the job never succeeded on its branch.
+    pub fn fallback_branches(&self) -> &[String] {
+        &self.fallback_branches
+    }
+
+    /// The maximum age of the discovered baselines.
+    pub fn baseline_max_age_days(&self) -> Option<u32> {
+        self.baseline_max_age_days
+    }
+
+    /// The location of the trained models, reused for the same baselines.
+    pub fn model_store(&self) -> Option<&ModelStoreSettings> {
+        self.model_store.as_ref()
+    }
+
+    /// The additional certificates of the http client.
+    pub fn tls(&self) -> Option<&TlsSettings> {
+        self.tls.as_ref()
+    }
+
+    /// The timeouts and the retries of the http client.
+    pub fn http(&self) -> &HttpSettings {
+        &self.http
+    }
+
+    /// The model group of a source, using the first rule matching the source path.
+    pub fn group_name(&self, source: &Source) -> Option<&str> {
+        let path = source.get_relative();
+        self.groups
+            .iter()
+            .find(|(paths, _)| paths.is_match(path))
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// The anomaly deep link formats, recorded in the report.
+    pub fn link_templates(&self) -> Vec<LinkTemplate> {
+        self.link_templates.clone()
+    }
 }
 
 impl Default for Config {
@@ -98,18 +380,283 @@ struct ConfigFile {
     excludes: Vec<String>,
     #[serde(default = "default_default_excludes")]
     default_excludes: bool,
+    #[serde(default)]
+    categories: Vec<CategoryRule>,
+    #[serde(default)]
+    logfmt: Vec<String>,
+    #[serde(default)]
+    csv: Vec<CsvRule>,
+    #[serde(default)]
+    tokenizer_rules: Vec<TokenizerRule>,
+    #[serde(default)]
+    preserve_tokens: Vec<String>,
+    #[serde(default)]
+    limits: Limits,
+    #[serde(default = "default_strip_ansi")]
+    strip_ansi: bool,
+    #[serde(default)]
+    locale: Locale,
+    #[serde(default)]
+    redact: Redact,
+    /// The anomaly link templates per content kind, e.g. `zuul: "{url}#L{line}"`.
+    #[serde(default)]
+    links: BTreeMap<String, String>,
+    #[serde(default)]
+    zuul: Vec<ZuulSettings>,
+    #[serde(default)]
+    ansible_failed_tasks: bool,
+    #[serde(default)]
+    baseline_builds: Option<usize>,
+    #[serde(default)]
+    baseline_pins: Vec<BaselinePinRule>,
+    #[serde(default)]
+    fallback_branches: Vec<String>,
+    #[serde(default)]
+    baseline_max_age_days: Option<u32>,
+    #[serde(default)]
+    model_store: Option<ModelStoreSettings>,
+    #[serde(default)]
+    groups: Vec<GroupRule>,
+    #[serde(default)]
+    tls: Option<TlsSettings>,
+    #[serde(default)]
+    http: HttpSettings,
+}
+
+/// The CSV and TSV format of the matching sources.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CsvRule {
+    /// The list of path regex.
+    pub paths: Vec<String>,
+    /// The column delimiter, defaults to a tab for the `.tsv` files and a comma otherwise.
+    pub delimiter: Option<char>,
+    /// The positions of the message columns, starting at 0.
+    #[serde(default)]
+    pub message: Vec<usize>,
+}
+
+/// A user-defined normalization rule.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TokenizerRule {
+    /// The regex of the varying words, e.g. `tenant-[a-z]+`.
+    pub pattern: String,
+    /// The replacement token, e.g. `%TENANT`.
+    pub token: String,
+}
+
+// The token must be a single word, so that it is not modified by the tokenizer.
+fn is_token(token: &str) -> bool {
+    token
+        .strip_prefix('%')
+        .and_then(|name| name.chars().next().map(|c| (c, name)))
+        .is_some_and(|(first, name)| {
+            first.is_ascii_uppercase()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        })
+}
+
+/// The policy for the lines written in a non english language.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Locale {
+    /// Replace the localized words with a `%LOCALE` token.
+    #[serde(default)]
+    pub normalize: bool,
+    /// The factor applied to the localized lines distance, between 0.0 and 1.0.
+    pub weight: Option<f32>,
+}
+
+/// The secrets redaction of the report lines.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Redact {
+    /// Use the built-in patterns for the passwords, the tokens and the AWS keys.
+    #[serde(default = "default_redact_builtins")]
+    pub builtins: bool,
+    /// The list of extra regex, the matches are replaced with `[REDACTED]`.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+impl Default for Redact {
+    fn default() -> Self {
+        Redact {
+            builtins: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+fn default_redact_builtins() -> bool {
+    true
+}
+
+/// The settings of a zuul deployment.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ZuulSettings {
+    /// The url prefix of the builds, e.g. `https://zuul.example.com/`.
+    pub url: String,
+    /// The api url, instead of the one inferred from the build url.
+    pub api_url: Option<String>,
+    /// The tenant name, instead of the one found in the build url.
+    pub tenant: Option<String>,
+    /// The environment variable of the api token, instead of `ZUUL_AUTH_TOKEN`.
+    pub token_env: Option<String>,
+    /// The extra headers of the api requests.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl ZuulSettings {
+    // The prefixes are matched up to a path separator, so that the token is not sent to another host.
+    fn matches(&self, url: &Url) -> bool {
+        std::iter::once(&self.url)
+            .chain(self.api_url.as_ref())
+            .any(|prefix| {
+                url.as_str()
+                    .starts_with(&format!("{}/", prefix.trim_end_matches('/')))
+            })
+    }
+}
+
+/// The baseline of the jobs matching a name regex.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BaselinePinRule {
+    /// The job name regex.
+    pub job: String,
+    /// The baseline url, or `skip` to not analyze the job.
+    pub baseline: String,
+}
+
+/// The trained models storage.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ModelStoreSettings {
+    /// The directory of the models.
+    pub path: PathBuf,
+    /// The number of hours before a model is trained again, by default 24.
+    pub ttl_hours: Option<u64>,
+}
+
+/// The sources sharing a model, instead of the one of their file name.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GroupRule {
+    /// The group name.
+    pub name: String,
+    /// The source path regexes.
+    pub paths: Vec<String>,
 }
 
+/// The certificates of the http client, in PEM format.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TlsSettings {
+    /// The additional certificate authorities, e.g. an internal CA.
+    #[serde(default)]
+    pub ca_certs: Vec<PathBuf>,
+    /// The client certificate.
+    pub client_cert: Option<PathBuf>,
+    /// The client key, when it is not stored in the client certificate file.
+    pub client_key: Option<PathBuf>,
+}
+
+/// The http client timeouts and retries.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct HttpSettings {
+    /// The connection timeout in seconds.
+    pub connect_timeout_secs: Option<u64>,
+    /// The maximum time without receiving data, in seconds.
+    pub read_timeout_secs: Option<u64>,
+    /// The number of retries of the transient failures, by default 2.
+    pub retries: Option<u32>,
+    /// The delay before the first retry in milliseconds, doubled for each retry, by default 500.
+    pub backoff_ms: Option<u64>,
+    /// The maximum time of a download in seconds, including its retries.
+    pub deadline_secs: Option<u64>,
+}
+
+/// The analysis limits.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    /// The maximum number of bytes to download, the remaining sources are not analyzed.
+    pub max_bytes: Option<u64>,
+    /// The maximum number of sources, the remaining sources are not analyzed.
+    pub max_sources: Option<usize>,
+    /// The maximum size of a source, the rest of the source is not analyzed.
+    pub max_file_bytes: Option<u64>,
+    /// The maximum duration of the analysis in seconds, the remaining sources are not analyzed.
+    pub timeout_secs: Option<u64>,
+    /// The maximum line length, the longer lines are truncated.
+    pub max_line_length: Option<usize>,
+    /// The number of concurrent downloads.
+    pub downloads: Option<usize>,
+}
+
+impl Limits {
+    /// Apply the lower limits of the other, e.g. the ones requested by a web api client.
+    pub fn restrict(&self, other: &Limits) -> Limits {
+        fn min<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            }
+        }
+        Limits {
+            max_bytes: min(self.max_bytes, other.max_bytes),
+            max_sources: min(self.max_sources, other.max_sources),
+            max_file_bytes: min(self.max_file_bytes, other.max_file_bytes),
+            timeout_secs: min(self.timeout_secs, other.timeout_secs),
+            ..self.clone()
+        }
+    }
+}
+
+/// The default number of concurrent downloads.
+pub const DEFAULT_DOWNLOADS: usize = 4;
+
 fn default_default_excludes() -> bool {
     true
 }
 
+fn default_strip_ansi() -> bool {
+    true
+}
+
 impl Default for ConfigFile {
     fn default() -> Self {
         ConfigFile {
             includes: Vec::new(),
             excludes: Vec::new(),
             default_excludes: true,
+            categories: Vec::new(),
+            logfmt: Vec::new(),
+            csv: Vec::new(),
+            tokenizer_rules: Vec::new(),
+            preserve_tokens: Vec::new(),
+            limits: Limits::default(),
+            strip_ansi: true,
+            locale: Locale::default(),
+            redact: Redact::default(),
+            links: BTreeMap::new(),
+            zuul: Vec::new(),
+            ansible_failed_tasks: false,
+            baseline_builds: None,
+            baseline_pins: Vec::new(),
+            fallback_branches: Vec::new(),
+            baseline_max_age_days: None,
+            model_store: None,
+            groups: Vec::new(),
+            tls: None,
+            http: HttpSettings::default(),
         }
     }
 }
@@ -138,7 +685,7 @@ fn test_config_default() {
 }
 
 #[cfg(test)]
-fn config_from_yaml(yaml: &str) -> Config {
+pub(crate) fn config_from_yaml(yaml: &str) -> Config {
     Config::from_reader("config.yaml".into(), std::io::Cursor::new(yaml)).unwrap()
 }
 
@@ -203,3 +750,242 @@ fn test_config_bad() {
         .is_err()
     );
 }
+
+#[test]
+fn test_config_categories() {
+    let config = config_from_yaml(
+        "
+categories:
+  - name: storage
+    paths: [ceph]
+    threshold: 0.6
+    gate: false
+",
+    );
+    let policy = config.source_policy(&Source::from_pathbuf("ceph/osd.log".into()));
+    assert_eq!(
+        (policy.threshold, policy.context, policy.gate),
+        (0.6, 3, false)
+    );
+    assert_eq!(
+        config.categorize(&Source::from_pathbuf("ceph/osd.log".into()), &[]),
+        Some("storage".into())
+    );
+    assert_eq!(
+        config.categorize(&Source::from_pathbuf("api.log".into()), &[]),
+        None
+    );
+}
+
+#[test]
+fn test_config_logfmt() {
+    let config = config_from_yaml(
+        "
+logfmt:
+  - prometheus/
+",
+    );
+    assert!(config.is_logfmt(&Source::from_pathbuf("logs/prometheus/server.log".into())));
+    assert!(!config.is_logfmt(&Source::from_pathbuf("logs/api.log".into())));
+    assert!(!Config::default().is_logfmt(&Source::from_pathbuf("api.log".into())));
+}
+
+#[test]
+fn test_config_csv() {
+    let config = config_from_yaml(
+        "
+csv:
+  - paths: [pg_log/]
+    message: [13]
+",
+    );
+    let format = config
+        .csv_format(&Source::from_pathbuf("logs/pg_log/postgresql.tsv".into()))
+        .unwrap();
+    assert_eq!((format.delimiter, format.message), ('\t', vec![13]));
+    assert_eq!(
+        config.csv_format(&Source::from_pathbuf("logs/api.log".into())),
+        None
+    );
+}
+
+#[test]
+fn test_config_tokenizer_rules() {
+    let config = config_from_yaml(
+        "
+tokenizer_rules:
+  - pattern: tenant-[a-z]+
+    token: '%TENANT'
+",
+    );
+    assert_eq!(
+        config.tokenizer_rules().apply("Deleting tenant-acme"),
+        "Deleting  %TENANT "
+    );
+    assert!(config
+        .tokenizer_settings()
+        .contains("rule=tenant-[a-z]+:%TENANT"));
+    assert!(Config::from_reader(
+        "config.yaml".into(),
+        std::io::Cursor::new("tokenizer_rules: [{pattern: tenant, token: tenant}]")
+    )
+    .is_err());
+}
+
+#[test]
+fn test_config_preserve_tokens() {
+    let config = config_from_yaml(
+        "
+preserve_tokens:
+  - '\\bE[0-9]{4}\\b'
+",
+    );
+    assert_eq!(
+        config
+            .tokenizer_rules()
+            .apply("error[E0308]: mismatched types"),
+        "error[ %KEEP=E0308 ]: mismatched types"
+    );
+    assert!(config
+        .tokenizer_settings()
+        .contains("preserve=\\bE[0-9]{4}\\b"));
+}
+
+#[test]
+fn test_config_locale() {
+    let config = config_from_yaml(
+        "
+locale:
+  normalize: true
+  weight: 0.5
+",
+    );
+    assert!(config.locale().normalize);
+    assert_eq!(config.locale().weight, Some(0.5));
+    assert_eq!(Config::default().locale().weight, None);
+}
+
+#[test]
+fn test_config_redact() {
+    let config = config_from_yaml(
+        "
+redact:
+  patterns: ['internal-[0-9]+']
+",
+    );
+    assert_eq!(
+        config.redactor().redact("password=x on internal-42"),
+        "password=[REDACTED] on [REDACTED]"
+    );
+    let config = config_from_yaml("redact: {builtins: false}");
+    assert!(config.redactor().is_empty());
+}
+
+#[test]
+fn test_config_links() {
+    let config = config_from_yaml(
+        "
+links:
+  zuul: '{url}#L{line}'
+",
+    );
+    let links = config.link_templates();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].provider.as_ref(), "zuul");
+    assert_eq!(links[0].template.as_ref(), "{url}#L{line}");
+}
+
+#[test]
+fn test_config_zuul() {
+    let config = config_from_yaml(
+        "
+zuul:
+  - url: https://zuul.example.com
+    tenant: internal
+    headers:
+      X-Team: ci
+",
+    );
+    let check = |url: &str| {
+        config
+            .zuul_settings(&Url::parse(url).unwrap())
+            .and_then(|settings| settings.tenant.as_deref())
+    };
+    assert_eq!(
+        check("https://zuul.example.com/t/public/build/42"),
+        Some("internal")
+    );
+    assert_eq!(check("https://zuul.example.com.evil/build/42"), None);
+    assert_eq!(check("https://other.example.com/build/42"), None);
+}
+
+#[test]
+fn test_config_baseline_pins() {
+    let config = config_from_yaml(
+        "
+baseline_pins:
+  - job: ^tox-py3
+    baseline: https://zuul.example.com/t/public/build/golden
+  - job: flaky
+    baseline: skip
+",
+    );
+    assert_eq!(
+        config.baseline_pin("tox-py311"),
+        Some(&BaselinePin::Url(
+            Url::parse("https://zuul.example.com/t/public/build/golden").unwrap()
+        ))
+    );
+    assert_eq!(
+        config.baseline_pin("periodic-flaky-job"),
+        Some(&BaselinePin::Skip)
+    );
+    assert_eq!(config.baseline_pin("linters"), None);
+    assert!(Config::from_reader(
+        "config.yaml".into(),
+        std::io::Cursor::new("baseline_pins: [{job: linters, baseline: golden}]")
+    )
+    .is_err());
+}
+
+#[test]
+fn test_config_groups() {
+    let config = config_from_yaml(
+        "
+groups:
+  - name: pods
+    paths: ['pod-[^/]*\\.log$']
+  - name: syslog
+    paths: ['/syslog', '/messages']
+",
+    );
+    let check = |path: &str| config.group_name(&Source::from_pathbuf(path.into()));
+    assert_eq!(check("logs/pod-api-5f6d.log"), Some("pods"));
+    assert_eq!(check("logs/pod-db-7a8b.log"), Some("pods"));
+    assert_eq!(check("var/log/messages"), Some("syslog"));
+    assert_eq!(check("var/log/syslog.gz"), Some("syslog"));
+    assert_eq!(check("logs/job-output.txt"), None);
+}
+
+#[test]
+fn test_config_limits_restrict() {
+    let config = config_from_yaml(
+        "
+limits:
+  max_bytes: 1000
+  timeout_secs: 60
+  downloads: 2
+",
+    );
+    let limits = config.limits().restrict(&Limits {
+        max_bytes: Some(5000),
+        max_sources: Some(10),
+        timeout_secs: Some(30),
+        ..Limits::default()
+    });
+    assert_eq!(limits.max_bytes, Some(1000));
+    assert_eq!(limits.max_sources, Some(10));
+    assert_eq!(limits.max_file_bytes, None);
+    assert_eq!(limits.timeout_secs, Some(30));
+    assert_eq!(limits.downloads, Some(2));
+}
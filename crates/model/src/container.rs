@@ -0,0 +1,306 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the docker and podman containers, where the source is the container logs.
+//!
+//! The engine api is accessed over its socket, podman providing the docker compatible api,
+//! and the baseline is another container of the same image, e.g. the one of a previous compose run.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::io::{BufRead, BufReader, Read, Write};
+use url::Url;
+
+use crate::reader::DecompressReader;
+use crate::{Baselines, Content, Source};
+use logjuicer_report::Container;
+
+pub fn is_container_url(url: &Url) -> bool {
+    url.scheme() == "container"
+}
+
+/// The engine api address, using the `DOCKER_HOST` or `CONTAINER_HOST` setting, or the first available socket.
+pub fn default_host() -> String {
+    if let Some(host) = ["DOCKER_HOST", "CONTAINER_HOST"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+    {
+        return host;
+    }
+    let mut sockets = vec!["/var/run/docker.sock".to_string()];
+    if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
+        sockets.push(format!("{}/podman/podman.sock", runtime_dir));
+    }
+    sockets.push("/run/podman/podman.sock".to_string());
+    let socket = sockets
+        .iter()
+        .find(|socket| std::path::Path::new(socket).exists())
+        .unwrap_or(&sockets[0]);
+    format!("unix://{}", socket)
+}
+
+trait Connection: Read + Write {}
+impl<T: Read + Write> Connection for T {}
+
+fn connect(host: &str) -> Result<Box<dyn Connection>> {
+    if let Some(path) = host.strip_prefix("unix://") {
+        let stream = std::os::unix::net::UnixStream::connect(path)
+            .with_context(|| format!("Connecting to {}", host))?;
+        Ok(Box::new(stream))
+    } else if let Some(addr) = host.strip_prefix("tcp://") {
+        let stream = std::net::TcpStream::connect(addr)
+            .with_context(|| format!("Connecting to {}", host))?;
+        Ok(Box::new(stream))
+    } else {
+        Err(anyhow::anyhow!(
+            "Unsupported container host {}, expected unix:// or tcp://",
+            host
+        ))
+    }
+}
+
+/// Perform a GET request, returning the response body.
+/// The HTTP/1.0 protocol is used so that the body is neither chunked nor kept alive.
+fn get(host: &str, path: &str) -> Result<BufReader<Box<dyn Connection>>> {
+    tracing::debug!(host, path, "Querying container api");
+    let mut conn = connect(host)?;
+    write!(conn, "GET {} HTTP/1.0\r\nHost: localhost\r\n\r\n", path)?;
+    let mut reader = BufReader::new(conn);
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    let code = status
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow::anyhow!("{}: bad response {}", path, status.trim()))?;
+    // Skip the headers.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    if code >= 400 {
+        let mut body = String::new();
+        reader.read_to_string(&mut body)?;
+        return Err(anyhow::anyhow!("{}: {} {}", path, code, body.trim()));
+    }
+    Ok(reader)
+}
+
+fn get_json<T: serde::de::DeserializeOwned>(host: &str, path: &str) -> Result<T> {
+    serde_json::from_reader(get(host, path)?).with_context(|| format!("Decoding {}", path))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerInfo {
+    id: String,
+    name: String,
+    created: chrono::DateTime<chrono::Utc>,
+    config: ContainerConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerConfig {
+    image: String,
+    #[serde(default)]
+    tty: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerSummary {
+    id: String,
+    names: Vec<String>,
+    /// The creation time, in seconds since the epoch.
+    created: i64,
+}
+
+fn inspect(host: &str, name: &str) -> Result<ContainerInfo> {
+    get_json(host, &format!("/containers/{}/json", name))
+}
+
+/// Resolve the container name or id, which may be stopped.
+pub fn content_from_container(host: Option<&str>, name: &str) -> Result<Content> {
+    let host = host.map_or_else(default_host, |host| host.to_string());
+    let info = inspect(&host, name)?;
+    Ok(Content::Container(Box::new(Container {
+        host: host.into(),
+        id: info.id.into(),
+        name: info.name.trim_start_matches('/').into(),
+        image: info.config.image.into(),
+    })))
+}
+
+/// Use the latest container of the same image created before, or the latest other one.
+pub fn discover_baselines(container: &Container) -> Result<Baselines> {
+    tracing::info!("Discovering baselines for {}", container);
+    let created = inspect(&container.host, &container.id)?.created.timestamp();
+    let filters = serde_json::json!({ "ancestor": [container.image] }).to_string();
+    let containers: Vec<ContainerSummary> = get_json(
+        &container.host,
+        &format!(
+            "/containers/json?all=true&filters={}",
+            url::form_urlencoded::byte_serialize(filters.as_bytes()).collect::<String>()
+        ),
+    )?;
+    Ok(containers
+        .into_iter()
+        .filter(|other| other.id != container.id.as_ref())
+        .max_by_key(|other| (other.created < created, other.created))
+        .map(|other| {
+            Content::Container(Box::new(Container {
+                host: container.host.clone(),
+                name: match other.names.first() {
+                    Some(name) => name.trim_start_matches('/').into(),
+                    None => other.id.as_str().into(),
+                },
+                id: other.id.into(),
+                image: container.image.clone(),
+            }))
+        })
+        .into_iter()
+        .collect())
+}
+
+pub fn sources_iter(container: &Container) -> Box<dyn Iterator<Item = Result<Source>>> {
+    Box::new(std::iter::once(
+        container.source().map_err(anyhow::Error::from),
+    ))
+}
+
+/// Open the container logs, including the stderr lines.
+pub fn open(url: &Url) -> Result<DecompressReader> {
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .ok_or_else(|| anyhow::anyhow!("{}: missing {}", url, name))
+    };
+    let (host, id) = (param("host")?, param("id")?);
+    let info = inspect(&host, &id)?;
+    let logs = get(
+        &host,
+        &format!("/containers/{}/logs?stdout=true&stderr=true", id),
+    )?;
+    Ok(DecompressReader::RemoteDecoder(if info.config.tty {
+        // The terminal output is not multiplexed.
+        Box::new(logs)
+    } else {
+        Box::new(StreamReader {
+            inner: logs,
+            remaining: 0,
+        })
+    }))
+}
+
+/// Read the multiplexed stdout and stderr frames, which start with an 8 bytes header including the frame size.
+struct StreamReader<R: Read> {
+    inner: R,
+    remaining: usize,
+}
+
+impl<R: Read> Read for StreamReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.remaining == 0 {
+            let mut header = [0; 8];
+            let mut pos = 0;
+            while pos < header.len() {
+                match self.inner.read(&mut header[pos..])? {
+                    0 if pos == 0 => return Ok(0),
+                    0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                    size => pos += size,
+                }
+            }
+            self.remaining =
+                u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        }
+        let max = buf.len().min(self.remaining);
+        let size = self.inner.read(&mut buf[..max])?;
+        if size == 0 && max > 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= size;
+        Ok(size)
+    }
+}
+
+#[test]
+fn test_container() -> Result<()> {
+    use std::os::unix::net::UnixListener;
+    let frame = |stream: u8, data: &str| {
+        let mut frame = vec![stream, 0, 0, 0];
+        frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        frame.extend_from_slice(data.as_bytes());
+        frame
+    };
+    let info = |id: &str, name: &str| {
+        format!(
+            r#"{{"Id":"{id}","Name":"/{name}","Created":"2023-10-15T08:00:00.42Z","Config":{{"Image":"app:latest","Tty":false}}}}"#
+        )
+    };
+    let dir = tempfile::tempdir()?;
+    let socket = dir.path().join("docker.sock");
+    let listener = UnixListener::bind(&socket)?;
+    let server = std::thread::spawn(move || {
+        for stream in listener.incoming().take(5) {
+            let mut stream = stream.unwrap();
+            let mut request = String::new();
+            BufReader::new(&stream).read_line(&mut request).unwrap();
+            let path = request.split_whitespace().nth(1).unwrap().to_string();
+            let body = match path.split_once('?').map_or(path.as_str(), |(path, _)| path) {
+                "/containers/app-2/json" | "/containers/c2/json" => {
+                    info("c2", "app-2").into_bytes()
+                }
+                "/containers/c1/json" => info("c1", "app-1").into_bytes(),
+                "/containers/json" => {
+                    assert!(
+                        path.contains("filters=%7B%22ancestor%22%3A%5B%22app%3Alatest%22%5D%7D")
+                    );
+                    // The epoch of the 2023-10-15T08:00:00Z creation date is 1697356800.
+                    br#"[{"Id":"c3","Names":["/app-3"],"Created":1697360400},{"Id":"c2","Names":["/app-2"],"Created":1697356800},{"Id":"c1","Names":["/app-1"],"Created":1697353200},{"Id":"c0","Names":["/app-0"],"Created":1697349600}]"#.to_vec()
+                }
+                "/containers/c1/logs" => {
+                    let mut logs = frame(1, "first\nsec");
+                    logs.extend(frame(2, "ond\n"));
+                    logs
+                }
+                _ => panic!("Unexpected request {}", path),
+            };
+            stream
+                .write_all(b"HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n")
+                .unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    });
+
+    let host = format!("unix://{}", socket.display());
+    let container = match content_from_container(Some(&host), "app-2")? {
+        Content::Container(container) => container,
+        _ => panic!("Expected a container"),
+    };
+    assert_eq!(container.to_string(), "app-2 (app:latest)");
+
+    let baselines = discover_baselines(&container)?;
+    assert_eq!(baselines[0].to_string(), "Container(app-1 (app:latest))");
+    let baseline = match &baselines[0] {
+        Content::Container(container) => container,
+        _ => panic!("Expected a container"),
+    };
+
+    let sources = sources_iter(baseline).collect::<Result<Vec<_>>>()?;
+    assert_eq!(sources[0].get_relative(), "app%3Alatest");
+    let url = match &sources[0] {
+        Source::Remote(_, url) => url.clone(),
+        _ => panic!("Expected a remote source"),
+    };
+    assert!(is_container_url(&url));
+    let mut lines = String::new();
+    match open(&url)? {
+        DecompressReader::RemoteDecoder(mut reader) => reader.read_to_string(&mut lines)?,
+        _ => panic!("Expected a remote reader"),
+    };
+    assert_eq!(lines, "first\nsecond\n");
+    server.join().unwrap();
+    Ok(())
+}
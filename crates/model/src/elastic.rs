@@ -0,0 +1,310 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the Elasticsearch and OpenSearch queries, where the source is the message field of the matching documents.
+//!
+//! The documents are read in the time order with the scroll api, which is supported by both services,
+//! and the baseline is the same query for an earlier time range, by default one week before.
+
+use anyhow::{Context, Result};
+use chrono::Duration;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Read;
+use url::Url;
+
+use crate::env::Env;
+use crate::reader::DecompressReader;
+use crate::timerange::{parse_range, BASELINE_OFFSET_DAYS};
+use crate::{Baselines, Content, Source};
+use logjuicer_report::ElasticQuery;
+
+/// The number of documents per request, the default `index.max_result_window` is 10000.
+const PAGE_SIZE: usize = 5000;
+
+/// The scroll context keep alive, between two requests.
+const SCROLL_TIMEOUT: &str = "5m";
+
+pub fn is_search_url(url: &Url) -> bool {
+    url.path().ends_with("/_search") && url.query_pairs().any(|(key, _)| key == "message_field")
+}
+
+/// Create the query for the `since` and `until` time range.
+pub fn new_query(
+    url: &str,
+    index: &str,
+    query: &str,
+    message_field: &str,
+    time_field: &str,
+    since: &str,
+    until: &str,
+) -> Result<ElasticQuery> {
+    let mut url = Url::parse(url).with_context(|| format!("Bad elasticsearch url {}", url))?;
+    if !url.path().ends_with('/') {
+        // The index path is joined to the url, which needs to be a directory.
+        url.set_path(&format!("{}/", url.path()));
+    }
+    let (start, end) = parse_range(since, until)?;
+    Ok(ElasticQuery {
+        url,
+        index: index.into(),
+        query: query.into(),
+        message_field: message_field.into(),
+        time_field: time_field.into(),
+        start,
+        end,
+    })
+}
+
+/// Use the same query, one week before.
+pub fn discover_baselines(query: &ElasticQuery, _env: &Env) -> Result<Baselines> {
+    Ok(vec![Content::Elasticsearch(Box::new(
+        query.shift(Duration::days(BASELINE_OFFSET_DAYS)),
+    ))])
+}
+
+pub fn sources_iter(query: &ElasticQuery) -> Box<dyn Iterator<Item = Result<Source>>> {
+    Box::new(std::iter::once(query.source().map_err(anyhow::Error::from)))
+}
+
+/// Open the search result.
+pub fn open(env: &Env, url: &Url) -> Result<DecompressReader> {
+    Ok(DecompressReader::RemoteDecoder(Box::new(
+        SearchReader::new(env.client_for(url).clone(), url, PAGE_SIZE)?,
+    )))
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    #[serde(rename = "_scroll_id")]
+    scroll_id: Option<String>,
+    hits: Hits,
+}
+
+#[derive(Debug, Deserialize)]
+struct Hits {
+    hits: Vec<Hit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Hit {
+    #[serde(rename = "_source", default)]
+    source: Value,
+}
+
+/// Get a document field, either a top level key such as `log.message`, or a nested object path.
+fn get_field<'a>(document: &'a Value, field: &str) -> Option<&'a Value> {
+    document.get(field).or_else(|| {
+        field
+            .split('.')
+            .try_fold(document, |value, key| value.get(key))
+    })
+}
+
+enum ScrollState {
+    Start(Value),
+    Next(String),
+    Done,
+}
+
+/// Read the documents message, requesting the next page when the buffer is consumed.
+struct SearchReader {
+    client: ureq::Agent,
+    /// The index search url.
+    search_url: Url,
+    /// The cluster scroll url.
+    scroll_url: Url,
+    message_field: String,
+    state: ScrollState,
+    buffer: std::io::Cursor<Vec<u8>>,
+}
+
+impl SearchReader {
+    fn new(client: ureq::Agent, url: &Url, size: usize) -> Result<Self> {
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+                .ok_or_else(|| anyhow::anyhow!("{}: missing {}", url, name))
+        };
+        let time_field = param("time_field")?;
+        let query = match param("query")? {
+            query if query.is_empty() => "*".to_string(),
+            query => query,
+        };
+        let body = json!({
+            "size": size,
+            "sort": [{ &time_field: "asc" }],
+            "query": {"bool": {
+                "must": [{"query_string": {"query": query}}],
+                "filter": [{"range": {&time_field: {
+                    "gte": param("start")?.parse::<i64>().context("Bad query start")?,
+                    "lt": param("end")?.parse::<i64>().context("Bad query end")?,
+                    "format": "epoch_millis",
+                }}}],
+            }},
+        });
+        let mut search_url = url.clone();
+        search_url.set_query(None);
+        Ok(SearchReader {
+            client,
+            scroll_url: search_url.join("../_search/scroll")?,
+            search_url,
+            message_field: param("message_field")?,
+            state: ScrollState::Start(body),
+            buffer: std::io::Cursor::new(Vec::new()),
+        })
+    }
+
+    fn request(&self, method: &str, url: &Url) -> ureq::Request {
+        let request = self.client.request_url(method, url);
+        if let Ok(api_key) = std::env::var("ELASTICSEARCH_API_KEY") {
+            request.set("Authorization", &format!("ApiKey {}", api_key))
+        } else if let (Ok(username), Ok(password)) = (
+            std::env::var("ELASTICSEARCH_USERNAME"),
+            std::env::var("ELASTICSEARCH_PASSWORD"),
+        ) {
+            use base64::Engine;
+            let credentials = base64::engine::general_purpose::STANDARD
+                .encode(format!("{}:{}", username, password));
+            request.set("Authorization", &format!("Basic {}", credentials))
+        } else {
+            request
+        }
+    }
+
+    fn next_page(&mut self, state: ScrollState) -> Result<()> {
+        let resp = match state {
+            ScrollState::Start(body) => {
+                let mut url = self.search_url.clone();
+                url.query_pairs_mut().append_pair("scroll", SCROLL_TIMEOUT);
+                self.request("POST", &url).send_json(body)
+            }
+            ScrollState::Next(scroll_id) => self
+                .request("POST", &self.scroll_url)
+                .send_json(json!({"scroll": SCROLL_TIMEOUT, "scroll_id": scroll_id})),
+            ScrollState::Done => return Ok(()),
+        };
+        let resp: SearchResponse = resp
+            .context("Can't search the documents")?
+            .into_json()
+            .context("Can't decode the search result")?;
+        let mut buffer = Vec::new();
+        for hit in &resp.hits.hits {
+            match get_field(&hit.source, &self.message_field) {
+                Some(Value::String(message)) => {
+                    buffer.extend_from_slice(message.trim_end_matches('\n').as_bytes())
+                }
+                Some(value) => buffer.extend_from_slice(value.to_string().as_bytes()),
+                // The document doesn't have a message.
+                None => continue,
+            }
+            buffer.push(b'\n');
+        }
+        self.state = match resp.scroll_id {
+            Some(scroll_id) if !resp.hits.hits.is_empty() => ScrollState::Next(scroll_id),
+            Some(scroll_id) => {
+                // Release the scroll context early, the service also removes it after the timeout.
+                if let Err(err) = self
+                    .request("DELETE", &self.scroll_url)
+                    .send_json(json!({ "scroll_id": scroll_id }))
+                {
+                    tracing::debug!("Failed to clear the scroll: {}", err);
+                }
+                ScrollState::Done
+            }
+            None => ScrollState::Done,
+        };
+        self.buffer = std::io::Cursor::new(buffer);
+        Ok(())
+    }
+}
+
+impl Read for SearchReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let size = self.buffer.read(buf)?;
+            if size > 0 || buf.is_empty() {
+                return Ok(size);
+            }
+            match std::mem::replace(&mut self.state, ScrollState::Done) {
+                ScrollState::Done => return Ok(0),
+                state => self
+                    .next_page(state)
+                    .map_err(|e| std::io::Error::other(format!("{:?}", e)))?,
+            }
+        }
+    }
+}
+
+#[test]
+fn test_elasticsearch_query() -> Result<()> {
+    use mockito::Matcher;
+    let env = Env::new();
+    let mut server = mockito::Server::new();
+    let search_mock = server
+        .mock("POST", "/ci-logs/_search")
+        .match_query(Matcher::UrlEncoded("scroll".into(), SCROLL_TIMEOUT.into()))
+        .match_body(Matcher::PartialJson(json!({
+            "sort": [{"@timestamp": "asc"}],
+            "query": {"bool": {
+                "must": [{"query_string": {"query": "level:error"}}],
+                "filter": [{"range": {"@timestamp": {"gte": 0, "lt": 60000}}}],
+            }},
+        })))
+        .with_body(
+            r#"{"_scroll_id":"s1","hits":{"hits":[{"_source":{"log":{"message":"first\n"}}},{"_source":{"log.message":"second"}}]}}"#,
+        )
+        .expect(1)
+        .create();
+    let scroll_mock = server
+        .mock("POST", "/_search/scroll")
+        .match_body(Matcher::PartialJson(json!({"scroll_id": "s1"})))
+        .with_body(r#"{"_scroll_id":"s2","hits":{"hits":[{"_source":{}},{"_source":{"log":{"message":"third"}}}]}}"#)
+        .expect(1)
+        .create();
+    let last_mock = server
+        .mock("POST", "/_search/scroll")
+        .match_body(Matcher::PartialJson(json!({"scroll_id": "s2"})))
+        .with_body(r#"{"_scroll_id":"s2","hits":{"hits":[]}}"#)
+        .expect(1)
+        .create();
+    let clear_mock = server
+        .mock("DELETE", "/_search/scroll")
+        .match_body(Matcher::PartialJson(json!({"scroll_id": "s2"})))
+        .with_body("{}")
+        .expect(1)
+        .create();
+
+    let query = new_query(
+        &server.url(),
+        "ci-logs",
+        "level:error",
+        "log.message",
+        "@timestamp",
+        "1970-01-01T00:00:00Z",
+        "1970-01-01T00:01:00Z",
+    )?;
+    let sources = sources_iter(&query).collect::<Result<Vec<_>>>()?;
+    assert_eq!(sources[0].get_relative(), "level%3Aerror");
+    let url = match &sources[0] {
+        Source::Remote(_, url) => url.clone(),
+        _ => panic!("Expected a remote source"),
+    };
+    assert!(is_search_url(&url));
+
+    let mut lines = String::new();
+    SearchReader::new(env.client.clone(), &url, 2)?.read_to_string(&mut lines)?;
+    assert_eq!(lines, "first\nsecond\nthird\n");
+    search_mock.assert();
+    scroll_mock.assert();
+    last_mock.assert();
+    clear_mock.assert();
+
+    let baselines = discover_baselines(&query, &env)?;
+    assert_eq!(
+        baselines[0].to_string(),
+        "Elasticsearch(ci-logs/level:error [1969-12-25T00:00:00Z, 1969-12-25T00:01:00Z])"
+    );
+    Ok(())
+}
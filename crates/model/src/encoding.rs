@@ -0,0 +1,188 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the text encoding detection.
+//!
+//! The byte order marks are removed, and the UTF-16 files, such as the logs produced by
+//! the Windows agents, are transcoded to UTF-8.
+
+use std::io::{Cursor, Read, Result};
+
+use crate::reader::DecompressReader;
+
+/// The size of the prefix used to detect the encoding.
+const PREFIX_SIZE: usize = 4;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8 { bom: bool },
+    Utf16 { big_endian: bool, bom: bool },
+}
+
+/// Detect the encoding from the first bytes of a file.
+pub fn detect(prefix: &[u8]) -> Encoding {
+    match prefix {
+        [0xef, 0xbb, 0xbf, ..] => Encoding::Utf8 { bom: true },
+        [0xff, 0xfe, ..] => Encoding::Utf16 {
+            big_endian: false,
+            bom: true,
+        },
+        [0xfe, 0xff, ..] => Encoding::Utf16 {
+            big_endian: true,
+            bom: true,
+        },
+        // Without a bom, look for ascii characters encoded with two bytes.
+        [a, 0, b, 0, ..] if *a != 0 && *b != 0 => Encoding::Utf16 {
+            big_endian: false,
+            bom: false,
+        },
+        [0, a, 0, b, ..] if *a != 0 && *b != 0 => Encoding::Utf16 {
+            big_endian: true,
+            bom: false,
+        },
+        _ => Encoding::Utf8 { bom: false },
+    }
+}
+
+/// A reader with its first bytes read back, after the encoding detection.
+pub type Prefixed<R> = std::io::Chain<Cursor<Vec<u8>>, R>;
+
+/// Detect the encoding of a reader, and wrap it to produce UTF-8 without a bom.
+pub fn decode(mut reader: DecompressReader) -> Result<DecompressReader> {
+    let mut prefix = Vec::with_capacity(PREFIX_SIZE);
+    (&mut reader)
+        .take(PREFIX_SIZE as u64)
+        .read_to_end(&mut prefix)?;
+    let encoding = detect(&prefix);
+    let bom_size = match encoding {
+        Encoding::Utf8 { bom: true } => 3,
+        Encoding::Utf16 { bom: true, .. } => 2,
+        _ => 0,
+    };
+    let prefixed = Cursor::new(prefix.split_off(bom_size)).chain(reader);
+    Ok(match encoding {
+        Encoding::Utf8 { .. } => DecompressReader::Prefixed(Box::new(prefixed)),
+        Encoding::Utf16 { big_endian, .. } => {
+            DecompressReader::Utf16(Box::new(Utf16Reader::new(prefixed, big_endian)))
+        }
+    })
+}
+
+/// A reader that transcodes UTF-16 to UTF-8.
+pub struct Utf16Reader<R: Read> {
+    reader: R,
+    big_endian: bool,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> Utf16Reader<R> {
+    pub fn new(reader: R, big_endian: bool) -> Self {
+        Utf16Reader {
+            reader,
+            big_endian,
+            input: Vec::new(),
+            output: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Decode the input buffer, keeping the incomplete code units for the next read.
+    fn decode(&mut self) {
+        let mut units: Vec<u16> = self
+            .input
+            .chunks_exact(2)
+            .map(|b| {
+                if self.big_endian {
+                    u16::from_be_bytes([b[0], b[1]])
+                } else {
+                    u16::from_le_bytes([b[0], b[1]])
+                }
+            })
+            .collect();
+        // A high surrogate needs the next unit.
+        if units
+            .last()
+            .is_some_and(|unit| (0xd800..0xdc00).contains(unit))
+        {
+            units.pop();
+        }
+        for c in char::decode_utf16(units.iter().copied()) {
+            let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+            let mut buf = [0; 4];
+            self.output
+                .extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        self.input.drain(..units.len() * 2);
+    }
+}
+
+impl<R: Read> Read for Utf16Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while self.pos >= self.output.len() {
+            self.output.clear();
+            self.pos = 0;
+            let mut chunk = [0; 8192];
+            let size = self.reader.read(&mut chunk)?;
+            if size == 0 {
+                if self.input.is_empty() {
+                    return Ok(0);
+                }
+                // The file ends with an incomplete character.
+                self.input.clear();
+                self.output
+                    .extend_from_slice(char::REPLACEMENT_CHARACTER.to_string().as_bytes());
+            } else {
+                self.input.extend_from_slice(&chunk[..size]);
+                self.decode();
+            }
+        }
+        let size = buf.len().min(self.output.len() - self.pos);
+        buf[..size].copy_from_slice(&self.output[self.pos..self.pos + size]);
+        self.pos += size;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+fn utf16_bytes(s: &str, big_endian: bool, bom: bool) -> Vec<u8> {
+    let mut data = Vec::new();
+    let units = if bom {
+        std::iter::once(0xfeff).chain(s.encode_utf16()).collect()
+    } else {
+        s.encode_utf16().collect::<Vec<u16>>()
+    };
+    for unit in units {
+        if big_endian {
+            data.extend_from_slice(&unit.to_be_bytes())
+        } else {
+            data.extend_from_slice(&unit.to_le_bytes())
+        }
+    }
+    data
+}
+
+#[test]
+fn test_utf16() {
+    let text = "Build started\nÉchec: 😱\n";
+    for (big_endian, bom) in [(false, true), (true, true), (false, false), (true, false)] {
+        let data = utf16_bytes(text, big_endian, bom);
+        assert_eq!(detect(&data), Encoding::Utf16 { big_endian, bom });
+        // Read with a small buffer to check the surrogates split between reads.
+        let mut reader =
+            Utf16Reader::new(Cursor::new(&data[if bom { 2 } else { 0 }..]), big_endian);
+        let mut result = Vec::new();
+        let mut buf = [0; 3];
+        loop {
+            match reader.read(&mut buf).unwrap() {
+                0 => break,
+                n => result.extend_from_slice(&buf[..n]),
+            }
+        }
+        assert_eq!(String::from_utf8(result).unwrap(), text);
+    }
+    assert_eq!(detect(b"\xef\xbb\xbfhello"), Encoding::Utf8 { bom: true });
+    assert_eq!(detect(b"hello"), Encoding::Utf8 { bom: false });
+    assert_eq!(detect(b"h"), Encoding::Utf8 { bom: false });
+}
@@ -3,12 +3,35 @@
 
 //! This module provides a global environment.
 
-use crate::config::Config;
-use anyhow::Result;
+use crate::config::{Config, HttpSettings, TlsSettings};
+use crate::progress::{NoProgress, Progress};
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
 
 pub struct Env {
     pub cache: Option<logjuicer_cache::Cache>,
+    /// The http client, using the proxy when it is set.
     pub client: ureq::Agent,
+    /// The http client of the hosts excluded from the proxy.
+    pub direct_client: Option<ureq::Agent>,
+    pub no_proxy: NoProxy,
+    /// The kubeconfig current context, for the cluster api requests.
+    pub kube: Option<crate::k8s::KubeContext>,
+    /// The network access is forbidden, the contents are read from the cache.
+    pub offline: bool,
+    /// The cached urls that are already revalidated, they are not checked again.
+    pub revalidated: Mutex<HashSet<Url>>,
+    /// The cancellation of the running operations.
+    pub cancel: CancelToken,
+    /// The progress hooks of the running operations.
+    pub progress: Arc<dyn Progress>,
+    /// The extracted archives, see [crate::archive::member_open].
+    pub archives: crate::archive::Extractions,
     pub output: OutputMode,
     pub config: Config,
 }
@@ -30,14 +53,93 @@ impl Env {
         } else {
             None
         };
+        let tls = tls_config(config.tls())?;
+        let no_proxy = NoProxy::from_env();
+        let direct_client = if http_proxy().is_ok() && !no_proxy.is_empty() {
+            Some(new_agent(ureq::builder(), &config, &tls))
+        } else {
+            None
+        };
         Ok(Env {
             cache,
-            client: new_agent(),
+            client: new_agent(agent_builder()?, &config, &tls),
+            direct_client,
+            no_proxy,
+            kube: None,
+            offline: false,
+            revalidated: Mutex::new(HashSet::new()),
+            cancel: CancelToken::default(),
+            progress: Arc::new(NoProgress),
+            archives: Default::default(),
             output,
             config,
         })
     }
 
+    /// The http client of an url, without the proxy when the host is listed in `NO_PROXY`.
+    pub fn client_for(&self, url: &Url) -> &ureq::Agent {
+        match &self.direct_client {
+            Some(client) if self.no_proxy.matches(url) => client,
+            _ => &self.client,
+        }
+    }
+
+    /// The retry policy of the http requests.
+    pub fn retry(&self) -> RetryPolicy {
+        let policy = RetryPolicy::new(self.config.http());
+        if self.offline {
+            RetryPolicy {
+                retries: 0,
+                ..policy
+            }
+        } else {
+            policy
+        }
+    }
+
+    /// Check if a cached url was already revalidated.
+    pub fn is_revalidated(&self, url: &Url) -> bool {
+        self.revalidated.lock().unwrap().contains(url)
+    }
+
+    /// Record a cached url revalidation, or download.
+    pub fn set_revalidated(&self, url: &Url) {
+        self.revalidated.lock().unwrap().insert(url.clone());
+    }
+
+    /// Forbid the network access, the remote contents are read from the cache.
+    #[allow(clippy::result_large_err)]
+    pub fn set_offline(&mut self) -> Result<()> {
+        if self.cache.is_none() {
+            self.cache = Some(logjuicer_cache::Cache::new()?);
+        }
+        self.client = ureq::builder()
+            .middleware(
+                |req: ureq::Request,
+                 _next: ureq::MiddlewareNext|
+                 -> Result<ureq::Response, ureq::Error> {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::NotConnected,
+                        format!("offline mode, {} is not cached", req.url()),
+                    )
+                    .into())
+                },
+            )
+            .build();
+        self.direct_client = None;
+        self.offline = true;
+        Ok(())
+    }
+
+    /// Check that the network is available, for the contents that are not read with the http client.
+    pub fn check_online(&self, url: &Url) -> Result<()> {
+        if self.offline {
+            Err(anyhow::anyhow!("offline mode, {} can't be read", url))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Helper function to debug
     pub fn debug_or_progress(&self, msg: &str) {
         match self.output {
@@ -48,10 +150,6 @@ impl Env {
     }
 }
 
-fn new_agent() -> ureq::Agent {
-    new_agent_safe().expect("ureq agent creation failed")
-}
-
 fn http_proxy() -> Result<String, std::env::VarError> {
     std::env::var("HTTPS_PROXY")
         .or_else(|_| std::env::var("https_proxy"))
@@ -59,6 +157,49 @@ fn http_proxy() -> Result<String, std::env::VarError> {
         .or_else(|_| std::env::var("http_proxy"))
 }
 
+/// The hosts excluded from the proxy, e.g. `NO_PROXY=localhost,.example.com`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NoProxy(Vec<String>);
+
+impl NoProxy {
+    pub fn new(hosts: &str) -> NoProxy {
+        NoProxy(
+            hosts
+                .split(',')
+                .map(|host| host.trim().trim_start_matches('.').to_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect(),
+        )
+    }
+
+    fn from_env() -> NoProxy {
+        std::env::var("NO_PROXY")
+            .or_else(|_| std::env::var("no_proxy"))
+            .map(|hosts| NoProxy::new(&hosts))
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Check if the url host, or one of its parent domains, is excluded.
+    pub fn matches(&self, url: &Url) -> bool {
+        match url.host_str() {
+            Some(host) => {
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+                self.0.iter().any(|entry| {
+                    entry == "*"
+                        || host == entry
+                        || (host.ends_with(entry.as_str())
+                            && host[..host.len() - entry.len()].ends_with('.'))
+                })
+            }
+            None => false,
+        }
+    }
+}
+
 fn default_ca_bundle() -> Option<std::ffi::OsString> {
     let path = std::path::Path::new("/etc/pki/tls/certs/ca-bundle.crt");
     if path.exists() {
@@ -88,42 +229,253 @@ fn tls_ca_extra() -> Option<std::ffi::OsString> {
     std::env::var_os("LOGJUICER_CA_EXTRA").or_else(default_ca_extra)
 }
 
-// Copied from https://github.com/PyO3/maturin/blob/23158969c97418b07a3c4d31282d220ec08c3c10/src/upload.rs#L395-L418
-fn new_agent_safe() -> Result<ureq::Agent, std::io::Error> {
-    use std::sync::Arc;
-
-    let mut builder = ureq::builder();
+/// The agent builder with the proxy settings.
+pub(crate) fn agent_builder() -> Result<ureq::AgentBuilder, std::io::Error> {
+    let builder = ureq::builder();
     if let Ok(proxy) = http_proxy() {
-        let proxy = ureq::Proxy::new(proxy)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        builder = builder.proxy(proxy);
+        let proxy = ureq::Proxy::new(proxy).map_err(std::io::Error::other)?;
+        Ok(builder.proxy(proxy))
+    } else {
+        Ok(builder)
+    }
+}
+
+pub(crate) fn new_agent(
+    builder: ureq::AgentBuilder,
+    config: &Config,
+    tls: &Option<Arc<rustls::ClientConfig>>,
+) -> ureq::Agent {
+    // Keep a connection per concurrent download, so that they are reused.
+    let mut builder = builder.max_idle_connections_per_host(config.downloads());
+    let http = config.http();
+    if let Some(secs) = http.connect_timeout_secs {
+        builder = builder.timeout_connect(Duration::from_secs(secs));
+    }
+    if let Some(secs) = http.read_timeout_secs {
+        // A stalled connection fails instead of blocking the run.
+        builder = builder
+            .timeout_read(Duration::from_secs(secs))
+            .timeout_write(Duration::from_secs(secs));
+    }
+    match tls {
+        Some(client_config) => builder.tls_config(client_config.clone()).build(),
+        None => builder.build(),
+    }
+}
+
+fn read_certs(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Can't open the certificates {}", path.display()))?;
+    rustls_pemfile::certs(&mut std::io::BufReader::new(file))
+        .with_context(|| format!("Can't read the certificates {}", path.display()))
+}
+
+/// Read the first private key of a PEM document.
+pub(crate) fn read_private_key(pem: &[u8]) -> Result<rustls::PrivateKey> {
+    rustls_pemfile::read_all(&mut &pem[..])?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow::anyhow!("Missing the client key"))
+}
+
+// Adapted from https://github.com/PyO3/maturin/blob/23158969c97418b07a3c4d31282d220ec08c3c10/src/upload.rs#L395-L418
+/// The tls settings, when they differ from the ureq defaults.
+fn tls_config(settings: Option<&TlsSettings>) -> Result<Option<Arc<rustls::ClientConfig>>> {
+    let default = TlsSettings::default();
+    let settings = settings.unwrap_or(&default);
+    if tls_ca_bundle().is_none()
+        && tls_ca_extra().is_none()
+        && settings.ca_certs.is_empty()
+        && settings.client_cert.is_none()
+    {
+        return Ok(None);
+    }
+
+    let builder = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_certs(settings)?);
+    let client_config = match client_cert(settings)? {
+        Some((certs, key)) => builder.with_client_auth_cert(certs, key)?,
+        None => builder.with_no_client_auth(),
     };
-    let ca_bundle = tls_ca_bundle();
-    let ca_extra = tls_ca_extra();
-    if let Some(ca_path) = ca_bundle.as_ref().or(ca_extra.as_ref()) {
-        let mut reader = std::io::BufReader::new(std::fs::File::open(ca_path)?);
-        let certs = rustls_pemfile::certs(&mut reader)?;
-        let mut root_certs = rustls::RootCertStore::empty();
-        root_certs.add_parsable_certificates(&certs);
-
-        if ca_extra.is_some() {
-            // Add mozilla certificates too, as done by ureq:rtls:root_certs
-            root_certs.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
-                rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
-                    ta.subject,
-                    ta.spki,
-                    ta.name_constraints,
-                )
-            }));
-        }
+    Ok(Some(Arc::new(client_config)))
+}
 
-        let client_config = rustls::ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates(root_certs)
-            .with_no_client_auth();
-        Ok(builder.tls_config(Arc::new(client_config)).build())
+/// The trusted certificates: the CA bundle, or the mozilla roots, with the extra CA certificates.
+pub(crate) fn root_certs(settings: &TlsSettings) -> Result<rustls::RootCertStore> {
+    let mut root_certs = rustls::RootCertStore::empty();
+    if let Some(ca_path) = &tls_ca_bundle() {
+        root_certs.add_parsable_certificates(&read_certs(Path::new(ca_path))?);
     } else {
-        Ok(builder.build())
+        // Add mozilla certificates, as done by ureq:rtls:root_certs
+        root_certs.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+    }
+    for ca_path in tls_ca_extra()
+        .iter()
+        .map(Path::new)
+        .chain(settings.ca_certs.iter().map(|path| path.as_path()))
+    {
+        root_certs.add_parsable_certificates(&read_certs(ca_path)?);
+    }
+    Ok(root_certs)
+}
+
+/// The client certificate and its key.
+type ClientCert = (Vec<rustls::Certificate>, rustls::PrivateKey);
+
+/// The client certificate of the tls settings.
+pub(crate) fn client_cert(settings: &TlsSettings) -> Result<Option<ClientCert>> {
+    match &settings.client_cert {
+        Some(cert_path) => {
+            let certs = read_certs(cert_path)?
+                .into_iter()
+                .map(rustls::Certificate)
+                .collect();
+            // The key may be stored along with the certificate.
+            let key_path = settings.client_key.as_ref().unwrap_or(cert_path);
+            let key = std::fs::read(key_path)
+                .map_err(anyhow::Error::from)
+                .and_then(|pem| read_private_key(&pem))
+                .with_context(|| format!("Can't read the client key {}", key_path.display()))?;
+            Ok(Some((certs, key)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// The reason of an aborted operation.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cancellation {
+    #[error("the operation was cancelled")]
+    Requested,
+    #[error("the operation timed out")]
+    TimedOut,
+}
+
+/// A cancellation flag shared with the running operations, which stop at their next check.
+#[derive(Debug, Default, Clone)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Cancel the operations when they run for longer than the timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> CancelToken {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed)
+    }
+
+    /// Check if the operation must stop.
+    pub fn check(&self) -> Result<(), Cancellation> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            Err(Cancellation::Requested)
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Err(Cancellation::TimedOut)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The default number of retries of the transient http failures.
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// The default delay before the first retry.
+pub const DEFAULT_BACKOFF_MS: u64 = 500;
+
+/// The retries of the transient http failures, with an exponential backoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    /// The delay before the first retry, doubled for each retry.
+    pub backoff: Duration,
+    /// The maximum time of a request, including its retries and the response reading.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(&HttpSettings::default())
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(settings: &HttpSettings) -> RetryPolicy {
+        RetryPolicy {
+            retries: settings.retries.unwrap_or(DEFAULT_RETRIES),
+            backoff: Duration::from_millis(settings.backoff_ms.unwrap_or(DEFAULT_BACKOFF_MS)),
+            deadline: settings.deadline_secs.map(Duration::from_secs),
+        }
+    }
+
+    /// The delay before a retry.
+    fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt.min(16)))
+    }
+
+    /// Call a request, creating a new one for each attempt.
+    #[allow(clippy::result_large_err)]
+    pub fn call<F>(&self, url: &Url, request: F) -> Result<ureq::Response, ureq::Error>
+    where
+        F: Fn() -> ureq::Request,
+    {
+        let start_time = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let mut req = request();
+            if let Some(deadline) = self.deadline {
+                req = req.timeout(deadline.saturating_sub(start_time.elapsed()));
+            }
+            match req.call() {
+                Err(err) if attempt < self.retries && is_transient(&err) => {
+                    let delay = self.delay(attempt);
+                    if let Some(deadline) = self.deadline {
+                        if start_time.elapsed() + delay >= deadline {
+                            return Err(err);
+                        }
+                    }
+                    tracing::warn!(url = url.as_str(), "Retrying in {:?}: {}", delay, err);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Check if a request failure may succeed when it is retried.
+fn is_transient(err: &ureq::Error) -> bool {
+    match err {
+        ureq::Error::Status(code, _) => matches!(code, 408 | 429 | 500 | 502 | 503 | 504),
+        ureq::Error::Transport(transport) => matches!(
+            transport.kind(),
+            ureq::ErrorKind::Dns | ureq::ErrorKind::ConnectionFailed | ureq::ErrorKind::Io
+        ),
     }
 }
 
@@ -148,3 +500,95 @@ impl OutputMode {
         matches!(self, OutputMode::FastTerminal)
     }
 }
+
+#[test]
+fn test_no_proxy() {
+    let no_proxy = NoProxy::new("localhost, .example.com,10.0.0.1,");
+    let check = |url: &str| no_proxy.matches(&Url::parse(url).unwrap());
+    assert!(check("http://localhost:3000/api"));
+    assert!(check("https://example.com/logs"));
+    assert!(check("https://logs.example.com/logs"));
+    assert!(check("http://10.0.0.1/logs"));
+    assert!(!check("https://badexample.com/logs"));
+    assert!(!check("https://example.org/logs"));
+    assert!(NoProxy::new("*").matches(&Url::parse("https://example.org").unwrap()));
+    assert!(NoProxy::new(" , ").is_empty());
+}
+
+#[test]
+#[allow(clippy::result_large_err)]
+fn test_retry_policy() {
+    let mut server = mockito::Server::new();
+    let unavailable = server
+        .mock("GET", "/unavailable")
+        .with_status(503)
+        .expect(3)
+        .create();
+    let missing = server
+        .mock("GET", "/missing")
+        .with_status(404)
+        .expect(1)
+        .create();
+    let retry = RetryPolicy::new(&HttpSettings {
+        backoff_ms: Some(1),
+        ..HttpSettings::default()
+    });
+    let client = ureq::Agent::new();
+    let get = |path: &str| {
+        let url = Url::parse(&format!("{}{}", server.url(), path)).unwrap();
+        retry.call(&url, || client.request_url("GET", &url))
+    };
+    assert!(matches!(
+        get("/unavailable"),
+        Err(ureq::Error::Status(503, _))
+    ));
+    assert!(matches!(get("/missing"), Err(ureq::Error::Status(404, _))));
+    unavailable.assert();
+    missing.assert();
+    assert_eq!(retry.delay(0), Duration::from_millis(1));
+    assert_eq!(retry.delay(3), Duration::from_millis(8));
+}
+
+#[test]
+fn test_offline() -> Result<()> {
+    use std::io::Read;
+
+    let mut env = Env::new();
+    env.set_offline()?;
+    let cache = env.cache.as_ref().unwrap();
+    let url = Url::parse("http://localhost/offline/job-output.txt")?;
+    cache.remote_drop(0, &url)?;
+    let err = crate::reader::from_url(&env, 0, &url).err().unwrap();
+    assert!(format!("{:#}", err).contains("offline mode"), "{:#}", err);
+
+    // The cached contents are read without revalidation.
+    cache
+        .remote_add(0, &url, std::io::Cursor::new("cached line\n"))?
+        .read_to_end(&mut Vec::new())?;
+    cache.remote_set_validators(
+        0,
+        &url,
+        &logjuicer_cache::Validators {
+            etag: Some("\"v1\"".into()),
+            last_modified: None,
+        },
+    )?;
+    let mut content = String::new();
+    crate::reader::from_url(&env, 0, &url)?.read_to_string(&mut content)?;
+    assert_eq!(content, "cached line\n");
+    assert!(env.check_online(&url).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_cancel_token() {
+    let token = CancelToken::new();
+    let shared = token.clone();
+    assert_eq!(token.check(), Ok(()));
+    shared.cancel();
+    assert_eq!(token.check(), Err(Cancellation::Requested));
+    assert_eq!(
+        CancelToken::new().with_timeout(Duration::ZERO).check(),
+        Err(Cancellation::TimedOut)
+    );
+}
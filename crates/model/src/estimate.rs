@@ -0,0 +1,137 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the pre-flight estimation of the content size.
+//!
+//! The sizes are read from the zuul-manifest when available, otherwise from the local file
+//! metadata or with HEAD requests. The processing time is derived from the expected throughput.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Limits;
+use crate::env::Env;
+use crate::{content_get_sources, Content, Source};
+
+/// The expected processing throughput, including the download, in bytes per second.
+const BYTES_PER_SECOND: u64 = 8 * 1024 * 1024;
+
+/// The content size estimation.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Estimate {
+    /// The number of sources.
+    pub sources: usize,
+    /// The total size of the sources, the compressed files are counted with their compressed size.
+    pub bytes: u64,
+    /// The number of sources with an unknown size.
+    pub unknown: usize,
+    /// The expected processing time in seconds.
+    pub seconds: u64,
+    /// The exceeded limits.
+    pub warnings: Vec<String>,
+}
+
+impl Estimate {
+    fn add(&mut self, size: Option<u64>) {
+        self.sources += 1;
+        match size {
+            Some(size) => self.bytes += size,
+            None => self.unknown += 1,
+        }
+    }
+
+    fn finalize(mut self, limits: &Limits) -> Estimate {
+        self.seconds = self.bytes / BYTES_PER_SECOND;
+        if let Some(max_bytes) = limits.max_bytes {
+            if self.bytes > max_bytes {
+                self.warnings.push(format!(
+                    "The content size {} exceeds the limit of {} bytes",
+                    self.bytes, max_bytes
+                ));
+            }
+        }
+        if let Some(max_sources) = limits.max_sources {
+            if self.sources > max_sources {
+                self.warnings.push(format!(
+                    "The {} sources exceed the limit of {} sources",
+                    self.sources, max_sources
+                ));
+            }
+        }
+        self
+    }
+}
+
+/// Get the size of a source without reading it.
+pub fn source_size(env: &Env, source: &Source) -> Option<u64> {
+    match source {
+        Source::Local(_, path_buf) => std::fs::metadata(path_buf).ok().map(|meta| meta.len()),
+        Source::Remote(_, url) => {
+            let resp = if crate::s3::is_s3_url(url) {
+                crate::s3::object_request(&env.client, "HEAD", url).ok()?
+            } else if crate::gcs::is_gs_url(url) {
+                crate::gcs::object_request(&env.client, "HEAD", url).ok()?
+            } else {
+                env.client_for(url).head(url.as_str())
+            }
+            .call()
+            .ok()?;
+            resp.header("Content-Length")?.parse().ok()
+        }
+    }
+}
+
+fn add_content(env: &Env, estimate: &mut Estimate, content: &Content) -> Result<()> {
+    let sized_sources = match content {
+        Content::Zuul(build) => crate::zuul::sized_sources(build, env),
+        _ => None,
+    };
+    match sized_sources {
+        Some(sources) => {
+            for (source, size) in sources {
+                if env.config.is_source_valid(&source) {
+                    estimate.add(size.or_else(|| source_size(env, &source)));
+                }
+            }
+        }
+        None => {
+            for source in content_get_sources(content, env)? {
+                estimate.add(source_size(env, &source));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Estimate the size and processing time of the contents.
+pub fn estimate(env: &Env, contents: &[Content]) -> Result<Estimate> {
+    let mut estimate = Estimate::default();
+    for content in contents {
+        env.debug_or_progress(&format!("Estimating {}", content));
+        add_content(env, &mut estimate, content)?;
+    }
+    Ok(estimate.finalize(env.config.limits()))
+}
+
+#[test]
+fn test_estimate() {
+    let env = Env::new();
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.log"), "0123456789").unwrap();
+    std::fs::write(dir.path().join("b.log"), "01234").unwrap();
+    let content = Content::Directory(Source::from_pathbuf(dir.path().into()));
+    let result = estimate(&env, &[content]).unwrap();
+    assert_eq!((result.sources, result.bytes, result.unknown), (2, 15, 0));
+    assert!(result.warnings.is_empty());
+
+    let limits = Limits {
+        max_bytes: Some(10),
+        ..Limits::default()
+    };
+    let mut result = Estimate::default();
+    result.add(Some(12));
+    result.add(None);
+    let result = result.finalize(&limits);
+    assert_eq!(result.unknown, 1);
+    assert_eq!(result.warnings.len(), 1);
+}
@@ -0,0 +1,221 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the portable model archive, to move the models between logjuicer versions and architectures.
+//!
+//! The archive is a gzip compressed json document, independent of the binary model encoding:
+//!
+//! - `format`: always `logjuicer-model`, and `format_version`: the archive version, currently 1.
+//! - `metadata`: the `logjuicer_version`, the `model_version` of the tokenizer, the `created_at` date,
+//!   the `tokenizer` settings and the `baselines` contents.
+//! - `vocabulary`: the `hash` function of the tokens, `fxhash32`, and the number of hashed `features`.
+//! - `indexes`: the index `name`, `sources`, `line_count`, `byte_count`, `train_time_ms`, `created_at`, `samples`
+//!   and the normalized tokens `matrix`, stored as a compressed sparse row matrix with its
+//!   `rows`, `cols`, `indptr`, `indices` and `data`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use logjuicer_index::FeaturesMatrix;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use crate::{Content, Index, IndexName, Model, Source, MODEL_VERSION};
+
+const FORMAT: &str = "logjuicer-model";
+const FORMAT_VERSION: u32 = 1;
+const HASH: &str = "fxhash32";
+
+#[derive(Serialize, Deserialize)]
+struct Archive {
+    format: String,
+    format_version: u32,
+    metadata: Metadata,
+    vocabulary: Vocabulary,
+    indexes: Vec<ArchiveIndex>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Metadata {
+    logjuicer_version: String,
+    model_version: usize,
+    created_at: DateTime<Utc>,
+    tokenizer: String,
+    baselines: Vec<Content>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Vocabulary {
+    hash: String,
+    features: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchiveIndex {
+    name: String,
+    sources: Vec<Source>,
+    line_count: usize,
+    byte_count: usize,
+    train_time_ms: u64,
+    created_at: DateTime<Utc>,
+    samples: Vec<Box<str>>,
+    matrix: Matrix,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Matrix {
+    rows: usize,
+    cols: usize,
+    indptr: Vec<usize>,
+    indices: Vec<usize>,
+    data: Vec<f32>,
+}
+
+/// Write the portable archive of a model.
+pub fn export<W: Write>(model: &Model<FeaturesMatrix>, writer: W) -> Result<()> {
+    let mut indexes = model
+        .indexes
+        .iter()
+        .map(|(name, index)| {
+            let parts = logjuicer_index::mat_to_parts(&index.index);
+            ArchiveIndex {
+                name: name.to_string(),
+                sources: index.sources.clone(),
+                line_count: index.line_count,
+                byte_count: index.byte_count,
+                train_time_ms: index.train_time.as_millis() as u64,
+                created_at: index.created_at.into(),
+                samples: index.samples.clone(),
+                matrix: Matrix {
+                    rows: parts.rows,
+                    cols: parts.cols,
+                    indptr: parts.indptr,
+                    indices: parts.indices,
+                    data: parts.data,
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+    indexes.sort_by(|a, b| a.name.cmp(&b.name));
+    let archive = Archive {
+        format: FORMAT.into(),
+        format_version: FORMAT_VERSION,
+        metadata: Metadata {
+            logjuicer_version: env!("CARGO_PKG_VERSION").into(),
+            model_version: MODEL_VERSION,
+            created_at: model.created_at.into(),
+            tokenizer: model.tokenizer.to_string(),
+            baselines: model.baselines.clone(),
+        },
+        vocabulary: Vocabulary {
+            hash: HASH.into(),
+            features: logjuicer_index::features_size(),
+        },
+        indexes,
+    };
+    let mut output = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+    serde_json::to_writer(&mut output, &archive).context("Can't encode the model archive")?;
+    output.finish().context("Can't write the model archive")?;
+    Ok(())
+}
+
+/// Read a portable archive.
+pub fn import<R: Read>(reader: R) -> Result<Model<FeaturesMatrix>> {
+    let archive: Archive = serde_json::from_reader(flate2::read::GzDecoder::new(reader))
+        .context("Can't decode the model archive")?;
+    if archive.format != FORMAT || archive.format_version > FORMAT_VERSION {
+        return Err(anyhow::anyhow!(
+            "Unsupported archive {} version {}, expected {} version {}",
+            archive.format,
+            archive.format_version,
+            FORMAT,
+            FORMAT_VERSION
+        ));
+    }
+    let features = logjuicer_index::features_size();
+    if archive.vocabulary.hash != HASH || archive.vocabulary.features != features {
+        return Err(anyhow::anyhow!(
+            "Incompatible vocabulary {} with {} features, expected {} with {}",
+            archive.vocabulary.hash,
+            archive.vocabulary.features,
+            HASH,
+            features
+        ));
+    }
+    if archive.metadata.model_version != MODEL_VERSION {
+        tracing::warn!(
+            "The model was created by logjuicer {} with the model version {}, the current version is {}",
+            archive.metadata.logjuicer_version,
+            archive.metadata.model_version,
+            MODEL_VERSION
+        );
+    }
+    let indexes = archive
+        .indexes
+        .into_iter()
+        .map(|index| {
+            let name = &index.name;
+            let matrix = logjuicer_index::mat_from_parts(logjuicer_index::MatParts {
+                rows: index.matrix.rows,
+                cols: index.matrix.cols,
+                indptr: index.matrix.indptr,
+                indices: index.matrix.indices,
+                data: index.matrix.data,
+            })
+            .map_err(|err| anyhow::anyhow!("{}: {}", name, err))?;
+            Ok((
+                IndexName(index.name.into()),
+                Index {
+                    created_at: index.created_at.into(),
+                    train_time: Duration::from_millis(index.train_time_ms),
+                    sources: index.sources,
+                    index: matrix,
+                    line_count: index.line_count,
+                    byte_count: index.byte_count,
+                    samples: index.samples,
+                },
+            ))
+        })
+        .collect::<Result<_>>()?;
+    Ok(Model {
+        created_at: archive.metadata.created_at.into(),
+        tokenizer: archive.metadata.tokenizer.into(),
+        baselines: archive.metadata.baselines,
+        indexes,
+    })
+}
+
+#[test]
+fn test_export_import() -> Result<()> {
+    use logjuicer_index::FeaturesMatrixBuilder;
+
+    let logs = crate::TestLogs::new()?;
+    let baseline = logs.content("baseline", &[("app.log", "first line\nsecond line\n")])?;
+    let env = crate::env::Env::new();
+    let model = Model::<FeaturesMatrix>::train::<FeaturesMatrixBuilder>(&env, vec![baseline])?;
+
+    let mut archive = Vec::new();
+    export(&model, &mut archive)?;
+    let imported = import(&archive[..])?;
+    assert_eq!(imported.hash(), model.hash());
+    assert_eq!(imported.baselines, model.baselines);
+    let (name, index) = model.indexes.iter().next().unwrap();
+    let imported_index = imported.get_index(name).unwrap();
+    assert_eq!(imported_index.index, index.index);
+    assert_eq!(imported_index.samples, index.samples);
+
+    // The archive is a documented json document.
+    let mut json = String::new();
+    flate2::read::GzDecoder::new(&archive[..]).read_to_string(&mut json)?;
+    let value: serde_json::Value = serde_json::from_str(&json)?;
+    assert_eq!(value["format"], "logjuicer-model");
+    assert_eq!(value["vocabulary"]["hash"], "fxhash32");
+    assert_eq!(value["indexes"][0]["samples"][0], "first line");
+
+    let mut bad = Vec::new();
+    let mut encoder = flate2::write::GzEncoder::new(&mut bad, flate2::Compression::default());
+    encoder.write_all(json.replace("fxhash32", "md5").as_bytes())?;
+    encoder.finish()?;
+    assert!(import(&bad[..]).is_err());
+    Ok(())
+}
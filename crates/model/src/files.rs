@@ -12,7 +12,7 @@ use crate::{Baselines, Content, Input, Source};
 pub fn content_from_path(path: &Path) -> Result<Content> {
     let src = Source::Local(0, path.to_path_buf());
 
-    if path.is_dir() {
+    if path.is_dir() || crate::archive::is_archive(path) {
         Ok(Content::Directory(src))
     } else if path.is_file() {
         Ok(Content::File(src))
@@ -30,9 +30,13 @@ pub fn discover_baselines_from_path(env: &Env, path: &Path) -> Result<Baselines>
     Ok(vec![baseline])
 }
 
-pub fn file_open(path: &Path) -> Result<crate::reader::DecompressReader> {
+pub fn file_open(env: &Env, path: &Path) -> Result<crate::reader::DecompressReader> {
     tracing::debug!(path = path.to_str(), "Opening file");
-    crate::reader::from_path(path).context("Failed to open file")
+    match crate::archive::split_member(path) {
+        Some((archive, member)) => crate::archive::member_open(env, archive, member)
+            .context("Failed to open archive member"),
+        None => crate::reader::from_path(path).context("Failed to open file"),
+    }
 }
 
 // A file source only has one source
@@ -66,8 +70,25 @@ pub fn dir_iter(path: &Path) -> impl Iterator<Item = Result<Source>> {
     walkdir::WalkDir::new(path)
         .into_iter()
         .filter(keep_path)
-        .map(move |res| match res {
-            Err(e) => Err(e.into()),
-            Ok(res) => Ok(Source::Local(base_len, res.into_path())),
+        .flat_map(move |res| -> Box<dyn Iterator<Item = Result<Source>>> {
+            match res {
+                Err(e) => Box::new(std::iter::once(Err(e.into()))),
+                Ok(res) if crate::archive::is_archive(res.path()) => {
+                    // When the archive is the root, the members are relative to the archive.
+                    let base_len = if res.depth() == 0 {
+                        base_len + 1
+                    } else {
+                        base_len
+                    };
+                    match crate::archive::archive_sources(base_len, res.path()) {
+                        Ok(sources) => Box::new(sources.into_iter().map(Ok)),
+                        Err(e) => Box::new(std::iter::once(Err(e))),
+                    }
+                }
+                Ok(res) => Box::new(std::iter::once(Ok(Source::Local(
+                    base_len,
+                    res.into_path(),
+                )))),
+            }
         })
 }
@@ -0,0 +1,85 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the Google Cloud Storage buckets, where the sources are the objects of a `gs://bucket/prefix/` url.
+//!
+//! The requests are anonymous, unless the `GOOGLE_APPLICATION_CREDENTIALS` variable points to a service account key.
+
+use anyhow::Result;
+use url::Url;
+
+use crate::env::Env;
+use crate::{Content, Source};
+
+lazy_static::lazy_static! {
+    static ref CLIENT: gcs_bucket::Client = gcs_bucket::Client::from_env().unwrap_or_else(|e| {
+        tracing::error!("Failed to load the service account, using anonymous access: {}", e);
+        gcs_bucket::Client::anonymous()
+    });
+}
+
+pub fn is_gs_url(url: &Url) -> bool {
+    url.scheme() == "gs"
+}
+
+pub fn content_from_gs_url(url: Url) -> Result<Content> {
+    if gcs_bucket::parse_gs_url(&url).is_none() {
+        Err(anyhow::anyhow!(
+            "Bad gs url {}, expected gs://bucket/name",
+            url
+        ))
+    } else if url.path().ends_with('/') || url.path().is_empty() {
+        Ok(Content::Directory(Source::Remote(0, url)))
+    } else {
+        Ok(Content::File(Source::Remote(0, url)))
+    }
+}
+
+fn parse(url: &Url) -> Result<gcs_bucket::ObjectUrl> {
+    gcs_bucket::parse_gs_url(url).ok_or_else(|| anyhow::anyhow!("Bad gs url {}", url))
+}
+
+/// Create the request of an object.
+pub fn object_request(client: &ureq::Agent, method: &str, url: &Url) -> Result<ureq::Request> {
+    Ok(CLIENT.object_request(client, method, &parse(url)?)?)
+}
+
+/// Read a small object, such as a build metadata file.
+pub fn read_object(env: &Env, url: &Url) -> Result<String> {
+    Ok(CLIENT.read_object(&env.client, &parse(url)?)?)
+}
+
+/// List the objects urls of a prefix.
+pub fn list_objects(env: &Env, url: &Url) -> Result<Vec<Url>> {
+    let prefix = parse(url)?;
+    CLIENT
+        .list_objects(&env.client, &prefix.bucket, &prefix.name)?
+        .into_iter()
+        .map(|object| Ok(gcs_bucket::object_url(&prefix.bucket, &object.name)?))
+        .collect()
+}
+
+#[tracing::instrument(level = "debug", skip(env))]
+pub fn sources_iter(url: &Url, env: &Env) -> Box<dyn Iterator<Item = Result<Source>>> {
+    let base_len = url.as_str().trim_end_matches('/').len() + 1;
+    let maybe_cached = env.cache.as_ref().and_then(|cache| cache.httpdir_get(url));
+    let urls = if let Some(cached) = maybe_cached {
+        cached
+    } else {
+        list_objects(env, url)
+            .map(|urls| urls.into_iter().map(Ok).collect::<Vec<_>>())
+            .and_then(|urls| match &env.cache {
+                Some(cache) => cache.httpdir_add(url, &urls).map(|()| urls),
+                None => Ok(urls),
+            })
+    };
+
+    match urls {
+        Ok(urls) => Box::new(urls.into_iter().map(move |url_result| {
+            url_result
+                .map_err(anyhow::Error::msg)
+                .map(|url| Source::Remote(base_len, url))
+        })),
+        Err(e) => Box::new(std::iter::once(Err(e))),
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the Jenkins builds, where the sources are the console log and the archived artifacts.
+
+use anyhow::Result;
+use url::Url;
+
+use crate::env::Env;
+use crate::{Baselines, Content, Source};
+use logjuicer_report::JenkinsBuild;
+
+/// Check if the url is a jenkins build.
+/// When the build api is not available, the url is processed as a regular url.
+pub fn content_from_jenkins_url(env: &Env, url: &Url) -> Option<Result<Content>> {
+    let build_url = jenkins_build::parse_build_url(url)?;
+    match jenkins_build::get_build(env.client_for(url), &build_url.url) {
+        Ok(build) => Some(Ok(Content::Jenkins(Box::new(JenkinsBuild {
+            url: build_url.url,
+            job_name: build_url.job_name,
+            number: build_url.number,
+            result: build.result.unwrap_or_else(|| "RUNNING".into()),
+        })))),
+        Err(err) => {
+            tracing::debug!("{} is not a jenkins build: {}", url, err);
+            None
+        }
+    }
+}
+
+/// The maximum number of builds to check when looking for the previous successful ones.
+const MAX_BUILDS: usize = 50;
+
+/// Use the last successful builds of the same job.
+pub fn discover_baselines(build: &JenkinsBuild, env: &Env) -> Result<Baselines> {
+    let job_url = build.job_url();
+    tracing::info!("Discovering baselines for {}", build);
+    let baseline = match jenkins_build::get_build(
+        env.client_for(&job_url),
+        &job_url.join("lastSuccessfulBuild/")?,
+    ) {
+        Ok(baseline) => baseline,
+        // The job never succeeded.
+        Err(jenkins_build::Error::BadQuery(err)) if matches!(*err, ureq::Error::Status(404, _)) => {
+            return Ok(vec![])
+        }
+        Err(err) => return Err(anyhow::anyhow!("Failed to discover baseline: {}", err)),
+    };
+    let max_baselines = env.config.baseline_builds();
+    let mut numbers = Vec::new();
+    if baseline.number != build.number {
+        numbers.push((baseline.number, baseline.result.unwrap_or_default()));
+    }
+    // Walk back the previous builds to find the other successful ones.
+    for number in (1..baseline.number)
+        .rev()
+        .take(MAX_BUILDS)
+        .filter(|number| *number != build.number)
+    {
+        if numbers.len() >= max_baselines {
+            break;
+        }
+        match jenkins_build::get_build(
+            env.client_for(&job_url),
+            &job_url.join(&format!("{}/", number))?,
+        ) {
+            Ok(previous) if previous.result.as_deref() == Some("SUCCESS") => {
+                numbers.push((number, previous.result.unwrap_or_default()))
+            }
+            Ok(_) => {}
+            // The build was removed.
+            Err(err) => tracing::debug!("{}: skipping build {}: {}", build, number, err),
+        }
+    }
+    numbers
+        .into_iter()
+        .map(|(number, result)| {
+            // The api url may use the jenkins root url setting, so the baseline url is derived from the target.
+            Ok(Content::Jenkins(Box::new(JenkinsBuild {
+                url: job_url.join(&format!("{}/", number))?,
+                job_name: build.job_name.clone(),
+                number,
+                result,
+            })))
+        })
+        .collect()
+}
+
+pub fn sources_iter(build: &JenkinsBuild, env: &Env) -> Box<dyn Iterator<Item = Result<Source>>> {
+    let prefix = build.url.as_str().trim_end_matches('/').len() + 1;
+    let artifacts = match jenkins_build::get_build(env.client_for(&build.url), &build.url) {
+        Ok(api_build) => api_build.artifacts,
+        Err(err) => {
+            return Box::new(std::iter::once(Err(anyhow::anyhow!(
+                "Failed to list the artifacts of {}: {}",
+                build,
+                err
+            ))))
+        }
+    };
+    let url = build.url.clone();
+    Box::new(
+        std::iter::once("consoleText".to_string())
+            .chain(
+                artifacts
+                    .into_iter()
+                    .map(|artifact| format!("artifact/{}", artifact.relative_path)),
+            )
+            .map(move |path| {
+                // The artifact path segments are percent-encoded, e.g. when they contain a `#`.
+                let mut source_url = url.clone();
+                source_url
+                    .path_segments_mut()
+                    .map_err(|_| anyhow::anyhow!("Invalid build url: {}", url))?
+                    .pop_if_empty()
+                    .extend(path.split('/'));
+                Ok(Source::Remote(prefix, source_url))
+            }),
+    )
+}
+
+#[test]
+fn test_jenkins_build() -> Result<()> {
+    let env = Env::new();
+    let mut server = mockito::Server::new();
+    let build_mock = server
+        .mock("GET", "/job/folder/job/project/42/api/json")
+        .match_query(mockito::Matcher::Any)
+        .with_body(
+            r#"{"artifacts":[{"relativePath":"logs/app.log"},{"relativePath":"logs/test #1?.log"}],"number":42,"result":"FAILURE","url":"http://jenkins/job/folder/job/project/42/"}"#,
+        )
+        .expect(2)
+        .create();
+    let baseline_mock = server
+        .mock("GET", "/job/folder/job/project/lastSuccessfulBuild/api/json")
+        .match_query(mockito::Matcher::Any)
+        .with_body(
+            r#"{"artifacts":[],"number":41,"result":"SUCCESS","url":"http://jenkins/job/folder/job/project/41/"}"#,
+        )
+        .expect(2)
+        .create();
+
+    let url = Url::parse(&format!(
+        "{}/job/folder/job/project/42/console",
+        server.url()
+    ))?;
+    let build = match content_from_jenkins_url(&env, &url) {
+        Some(Ok(Content::Jenkins(build))) => build,
+        _ => panic!("Expected a jenkins build"),
+    };
+    assert_eq!(build.job_name.as_ref(), "folder/project");
+    assert_eq!(build.result.as_ref(), "FAILURE");
+
+    let sources = sources_iter(&build, &env).collect::<Result<Vec<_>>>()?;
+    let paths = sources
+        .iter()
+        .map(|source| source.get_relative())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        vec![
+            "consoleText",
+            "artifact/logs/app.log",
+            "artifact/logs/test%20%231%3F.log"
+        ]
+    );
+
+    let baselines = discover_baselines(&build, &env)?;
+    assert_eq!(
+        baselines[0].to_string(),
+        format!("Jenkins({}/job/folder/job/project/41/)", server.url())
+    );
+
+    // The previous successful builds are used when more baselines are configured.
+    let previous_mocks = [(40, "FAILURE"), (39, "SUCCESS")]
+        .iter()
+        .map(|(number, result)| {
+            server
+                .mock(
+                    "GET",
+                    format!("/job/folder/job/project/{}/api/json", number).as_str(),
+                )
+                .match_query(mockito::Matcher::Any)
+                .with_body(format!(
+                    r#"{{"artifacts":[],"number":{},"result":"{}","url":"http://jenkins/job/folder/job/project/{}/"}}"#,
+                    number, result, number
+                ))
+                .create()
+        })
+        .collect::<Vec<_>>();
+    let env = Env {
+        config: crate::config::config_from_yaml("baseline_builds: 2"),
+        ..Env::new()
+    };
+    let baselines = discover_baselines(&build, &env)?;
+    assert_eq!(
+        baselines
+            .iter()
+            .map(|baseline| match baseline {
+                Content::Jenkins(baseline) => baseline.number,
+                _ => panic!("Expected a jenkins build"),
+            })
+            .collect::<Vec<_>>(),
+        vec![41, 39]
+    );
+    build_mock.assert();
+    baseline_mock.assert();
+    for mock in previous_mocks {
+        mock.assert();
+    }
+    Ok(())
+}
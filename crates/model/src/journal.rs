@@ -0,0 +1,198 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides a reader for the systemd journal exports.
+//!
+//! Both the `journalctl -o export` and `journalctl -o json` formats are supported.
+//! Each entry is converted to a `unit: message` line, so that the anomalies keep their unit context.
+
+use std::io::{BufRead, BufReader, Read, Result};
+
+/// The maximum size of a binary field, larger values are skipped.
+const MAX_FIELD_SIZE: u64 = 1024 * 1024;
+
+/// The fields used to identify the entry unit, by order of preference.
+const UNIT_FIELDS: &[&str] = &["_SYSTEMD_UNIT", "SYSLOG_IDENTIFIER", "_COMM"];
+
+/// Check if a file name is a journal export.
+pub fn is_journal_name(name: &str) -> bool {
+    let name = name
+        .trim_end_matches(".gz")
+        .trim_end_matches(".zst")
+        .trim_end_matches(".xz")
+        .trim_end_matches(".bz2");
+    name.ends_with(".export") || (name.contains("journal") && name.ends_with(".json"))
+}
+
+#[derive(Default)]
+struct Entry {
+    unit: Option<(usize, String)>,
+    message: Option<String>,
+}
+
+impl Entry {
+    fn add_field(&mut self, key: &[u8], value: &[u8]) {
+        if key == b"MESSAGE" {
+            self.message = Some(String::from_utf8_lossy(value).into_owned());
+        } else if let Some(rank) = UNIT_FIELDS.iter().position(|f| f.as_bytes() == key) {
+            if self.unit.as_ref().is_none_or(|(prev, _)| rank < *prev) {
+                self.unit = Some((rank, String::from_utf8_lossy(value).into_owned()));
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.unit.is_none() && self.message.is_none()
+    }
+
+    /// Render the entry, or None when it doesn't have a message.
+    fn render(self, output: &mut Vec<u8>) -> bool {
+        match self.message {
+            Some(message) => {
+                if let Some((_, unit)) = self.unit {
+                    output.extend_from_slice(unit.as_bytes());
+                    output.extend_from_slice(b": ");
+                }
+                output.extend_from_slice(message.as_bytes());
+                output.push(b'\n');
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A reader that converts journal entries into text lines.
+pub struct JournalReader<R: Read> {
+    reader: BufReader<R>,
+    output: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> JournalReader<R> {
+    pub fn new(reader: R) -> Self {
+        JournalReader {
+            reader: BufReader::new(reader),
+            output: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Read the next entry into the output buffer, returns false at the end of the reader.
+    fn read_entry(&mut self) -> Result<bool> {
+        let mut entry = Entry::default();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                // The end of the reader, the last entry may not have a trailing empty line.
+                return Ok(entry.render(&mut self.output));
+            }
+            if line.ends_with(b"\n") {
+                line.pop();
+            }
+            if line.is_empty() {
+                // The end of an export entry.
+                if !entry.is_empty() {
+                    entry.render(&mut self.output);
+                    return Ok(true);
+                }
+            } else if line[0] == b'{' {
+                // A json entry.
+                read_json_entry(&line, &mut entry);
+                entry.render(&mut self.output);
+                return Ok(true);
+            } else if let Some(pos) = line.iter().position(|c| *c == b'=') {
+                entry.add_field(&line[..pos], &line[pos + 1..]);
+            } else {
+                // A binary field: the size is a 64bit little endian, followed by the data and a new line.
+                let mut size = [0; 8];
+                self.reader.read_exact(&mut size)?;
+                let size = u64::from_le_bytes(size);
+                if size > MAX_FIELD_SIZE {
+                    std::io::copy(&mut (&mut self.reader).take(size), &mut std::io::sink())?;
+                } else {
+                    let mut value = Vec::with_capacity(size as usize);
+                    (&mut self.reader).take(size).read_to_end(&mut value)?;
+                    entry.add_field(&line, &value);
+                }
+                self.reader.read_exact(&mut [0])?;
+            }
+        }
+    }
+}
+
+fn read_json_entry(line: &[u8], entry: &mut Entry) {
+    use serde_json::Value;
+    if let Ok(Value::Object(fields)) = serde_json::from_slice(line) {
+        for (key, value) in fields {
+            match value {
+                Value::String(s) => entry.add_field(key.as_bytes(), s.as_bytes()),
+                // Binary values are exported as a list of bytes.
+                Value::Array(xs) => {
+                    let bytes: Vec<u8> = xs
+                        .iter()
+                        .filter_map(|x| x.as_u64().map(|b| b as u8))
+                        .collect();
+                    entry.add_field(key.as_bytes(), &bytes)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for JournalReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while self.pos >= self.output.len() {
+            self.output.clear();
+            self.pos = 0;
+            if !self.read_entry()? && self.output.is_empty() {
+                return Ok(0);
+            }
+        }
+        let size = buf.len().min(self.output.len() - self.pos);
+        buf[..size].copy_from_slice(&self.output[self.pos..self.pos + size]);
+        self.pos += size;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+fn read_journal(data: &[u8]) -> String {
+    let mut content = String::new();
+    JournalReader::new(data)
+        .read_to_string(&mut content)
+        .unwrap();
+    content
+}
+
+#[test]
+fn test_journal_export() {
+    let mut data = Vec::new();
+    data.extend_from_slice(b"__CURSOR=s=1\n_COMM=sshd\n_SYSTEMD_UNIT=sshd.service\n");
+    data.extend_from_slice(b"MESSAGE=Accepted publickey\n\n");
+    data.extend_from_slice(b"__CURSOR=s=2\nSYSLOG_IDENTIFIER=kernel\nMESSAGE\n");
+    data.extend_from_slice(&10u64.to_le_bytes());
+    data.extend_from_slice(b"oops\nline2\n\n");
+    data.extend_from_slice(b"__CURSOR=s=3\n_SYSTEMD_UNIT=init.scope\n");
+    assert_eq!(
+        read_journal(&data),
+        "sshd.service: Accepted publickey\nkernel: oops\nline2\n"
+    );
+}
+
+#[test]
+fn test_journal_json() {
+    let data = concat!(
+        "{\"__CURSOR\": \"s=1\", \"_SYSTEMD_UNIT\": \"nova.service\", \"MESSAGE\": \"Started\"}\n",
+        "{\"__CURSOR\": \"s=2\", \"SYSLOG_IDENTIFIER\": \"bash\", \"MESSAGE\": [104, 105]}\n",
+    );
+    assert_eq!(
+        read_journal(data.as_bytes()),
+        "nova.service: Started\nbash: hi\n"
+    );
+    assert!(is_journal_name("logs/journal.json.gz"));
+    assert!(is_journal_name("logs/node.export"));
+    assert!(!is_journal_name("logs/config.json"));
+}
@@ -0,0 +1,228 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the local systemd journal, where the sources are the units entries read with `journalctl`.
+//!
+//! The entries are exported with `journalctl -o export`, and the baseline is either the previous boot,
+//! or the same time range one week before.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::process::Command;
+use url::Url;
+
+use crate::env::Env;
+use crate::journal::JournalReader;
+use crate::reader::{CommandReader, DecompressReader};
+use crate::timerange::{parse_range, BASELINE_OFFSET_DAYS};
+use crate::{Baselines, Content, Source};
+use logjuicer_report::JournalQuery;
+
+const JOURNALCTL: &str = "journalctl";
+
+pub fn is_journal_url(url: &Url) -> bool {
+    url.scheme() == "journal"
+}
+
+/// The boots of `journalctl --list-boots`, from the oldest to the current one.
+fn list_boots() -> Result<Vec<(i64, String)>> {
+    let output = Command::new(JOURNALCTL)
+        .args(["--list-boots", "--no-pager"])
+        .output()
+        .context("Failed to run journalctl")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "journalctl --list-boots failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(parse_boots(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse the `IDX BOOT_ID FIRST_ENTRY LAST_ENTRY` lines, skipping the header of the recent versions.
+fn parse_boots(output: &str) -> Vec<(i64, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let idx = fields.next()?.parse().ok()?;
+            let boot_id = fields.next()?;
+            Some((idx, boot_id.to_string()))
+        })
+        .collect()
+}
+
+/// Resolve a boot offset, such as `-1`, or a boot id.
+fn resolve_boot(boot: &str) -> Result<String> {
+    match boot.parse::<i64>() {
+        Ok(offset) => list_boots()?
+            .into_iter()
+            .find(|(idx, _)| *idx == offset)
+            .map(|(_, boot_id)| boot_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown boot {}", boot)),
+        Err(_) => Ok(boot.to_string()),
+    }
+}
+
+/// Create the query for a boot, or for the `since` and `until` time range.
+pub fn new_query(
+    units: &[String],
+    boot: Option<&str>,
+    since: &str,
+    until: &str,
+) -> Result<JournalQuery> {
+    let units = units.iter().map(|unit| unit.as_str().into()).collect();
+    Ok(match boot {
+        Some(boot) => JournalQuery {
+            units,
+            boot_id: Some(resolve_boot(boot)?.into()),
+            start: None,
+            end: None,
+        },
+        None => {
+            let (start, end) = parse_range(since, until)?;
+            JournalQuery {
+                units,
+                boot_id: None,
+                start: Some(start),
+                end: Some(end),
+            }
+        }
+    })
+}
+
+/// The same query for an earlier boot, such as `-1`, or a boot id.
+pub fn with_boot(query: &JournalQuery, boot: &str) -> Result<JournalQuery> {
+    Ok(JournalQuery {
+        boot_id: Some(resolve_boot(boot)?.into()),
+        ..query.clone()
+    })
+}
+
+/// The same query for an earlier time range.
+pub fn shift(query: &JournalQuery, offset: Duration) -> JournalQuery {
+    let shift = |dt: Option<DateTime<Utc>>| dt.map(|dt| dt - offset);
+    JournalQuery {
+        start: shift(query.start),
+        end: shift(query.end),
+        ..query.clone()
+    }
+}
+
+/// Use the previous boot, or the same time range one week before.
+pub fn discover_baselines(query: &JournalQuery, _env: &Env) -> Result<Baselines> {
+    let baseline = match &query.boot_id {
+        Some(boot_id) => {
+            let boots = list_boots()?;
+            let previous = boots
+                .iter()
+                .position(|(_, id)| id == boot_id.as_ref())
+                .and_then(|pos| pos.checked_sub(1))
+                .and_then(|pos| boots.get(pos));
+            match previous {
+                Some((_, previous)) => JournalQuery {
+                    boot_id: Some(previous.as_str().into()),
+                    ..query.clone()
+                },
+                // This is the first boot.
+                None => return Ok(vec![]),
+            }
+        }
+        None => shift(query, Duration::days(BASELINE_OFFSET_DAYS)),
+    };
+    Ok(vec![Content::Journal(Box::new(baseline))])
+}
+
+pub fn sources_iter(query: &JournalQuery) -> Box<dyn Iterator<Item = Result<Source>>> {
+    match query.sources() {
+        Ok(sources) => Box::new(sources.into_iter().map(Ok)),
+        Err(e) => Box::new(std::iter::once(Err(e.into()))),
+    }
+}
+
+/// The journalctl arguments of a `journal://localhost/` url.
+fn journalctl_args(url: &Url) -> Vec<String> {
+    let mut args = vec![
+        "-o".to_string(),
+        "export".to_string(),
+        "--no-pager".to_string(),
+    ];
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "boot" => args.push(format!("--boot={}", value)),
+            "since" => args.push(format!("--since=@{}", value)),
+            "until" => args.push(format!("--until=@{}", value)),
+            "unit" => args.push(format!("--unit={}", value)),
+            _ => {}
+        }
+    }
+    args
+}
+
+/// Open the journal entries.
+pub fn open(url: &Url) -> Result<DecompressReader> {
+    let mut command = Command::new(JOURNALCTL);
+    command.args(journalctl_args(url));
+    let reader = DecompressReader::RemoteDecoder(Box::new(CommandReader::spawn(&mut command)?));
+    Ok(DecompressReader::Journal(Box::new(JournalReader::new(
+        reader,
+    ))))
+}
+
+#[test]
+fn test_journal_query() -> Result<()> {
+    let boots = parse_boots(
+        "IDX BOOT ID                          FIRST ENTRY                 LAST ENTRY
+ -1 8f1e4b2c9a7d4e6f8a1b2c3d4e5f6a7b Sat 2023-10-14 08:00:00 CEST Sat 2023-10-14 20:00:00 CEST
+  0 0a1b2c3d4e5f60718293a4b5c6d7e8f9 Sun 2023-10-15 08:00:00 CEST Sun 2023-10-15 12:00:00 CEST
+",
+    );
+    assert_eq!(
+        boots,
+        vec![
+            (-1, "8f1e4b2c9a7d4e6f8a1b2c3d4e5f6a7b".into()),
+            (0, "0a1b2c3d4e5f60718293a4b5c6d7e8f9".into())
+        ]
+    );
+
+    let query = new_query(
+        &["sshd.service".into()],
+        None,
+        "1970-01-01T00:00:00Z",
+        "1970-01-01T00:01:00Z",
+    )?;
+    let sources = sources_iter(&query).collect::<Result<Vec<_>>>()?;
+    assert_eq!(sources[0].get_relative(), "sshd.service");
+    let url = match &sources[0] {
+        Source::Remote(_, url) => url.clone(),
+        _ => panic!("Expected a remote source"),
+    };
+    assert!(is_journal_url(&url));
+    assert_eq!(
+        journalctl_args(&url),
+        vec![
+            "-o",
+            "export",
+            "--no-pager",
+            "--since=@0",
+            "--until=@60",
+            "--unit=sshd.service"
+        ]
+    );
+
+    let baselines = discover_baselines(&query, &Env::new())?;
+    assert_eq!(
+        baselines[0].to_string(),
+        "Journal(journal:sshd.service [1969-12-25T00:00:00Z, 1969-12-25T00:01:00Z])"
+    );
+
+    let query = JournalQuery {
+        units: vec![],
+        boot_id: Some("0a1b2c3d".into()),
+        start: None,
+        end: None,
+    };
+    let sources = sources_iter(&query).collect::<Result<Vec<_>>>()?;
+    assert_eq!(sources[0].get_relative(), "journal");
+    Ok(())
+}
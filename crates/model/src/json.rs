@@ -0,0 +1,170 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the pretty-printed json support.
+//!
+//! The records printed across multiple lines, such as some Kubernetes audit logs,
+//! are reassembled into single lines before tokenization, like a json lines file.
+
+use std::io::{Cursor, Read, Result};
+
+use crate::reader::DecompressReader;
+
+/// The size of the prefix used to detect the pretty-printed json.
+const PREFIX_SIZE: usize = 256;
+
+/// Check if the data starts with an object or an array opened on its own line,
+/// followed by a key or a nested value.
+pub fn is_pretty(prefix: &[u8]) -> bool {
+    let text = match std::str::from_utf8(prefix) {
+        Ok(text) => text,
+        // The prefix may end in the middle of a character.
+        Err(e) => std::str::from_utf8(&prefix[..e.valid_up_to()]).unwrap_or(""),
+    };
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    match (lines.next(), lines.next()) {
+        (Some("{" | "[" | "[{"), Some(next)) => next.starts_with(['"', '{', '[']),
+        _ => false,
+    }
+}
+
+/// Detect the pretty-printed json, and wrap the reader to produce a record per line.
+pub fn detect(mut reader: DecompressReader) -> Result<DecompressReader> {
+    let mut prefix = Vec::with_capacity(PREFIX_SIZE);
+    (&mut reader)
+        .take(PREFIX_SIZE as u64)
+        .read_to_end(&mut prefix)?;
+    let is_pretty = is_pretty(&prefix);
+    let prefixed = Box::new(Cursor::new(prefix).chain(reader));
+    Ok(if is_pretty {
+        DecompressReader::JsonRecords(Box::new(JsonRecordsReader::new(prefixed)))
+    } else {
+        DecompressReader::Prefixed(prefixed)
+    })
+}
+
+/// A reader that removes the new lines of the json records.
+/// When the document is an array, each element is a record.
+pub struct JsonRecordsReader<R: Read> {
+    reader: R,
+    started: bool,
+    array_root: bool,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    skip_indent: bool,
+    output: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> JsonRecordsReader<R> {
+    pub fn new(reader: R) -> Self {
+        JsonRecordsReader {
+            reader,
+            started: false,
+            array_root: false,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            skip_indent: false,
+            output: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    fn push(&mut self, c: u8) {
+        if self.in_string {
+            self.output.push(c);
+            if self.escaped {
+                self.escaped = false;
+            } else if c == b'\\' {
+                self.escaped = true;
+            } else if c == b'"' {
+                self.in_string = false;
+            }
+            return;
+        }
+        match c {
+            b'\n' | b'\r' => {
+                self.skip_indent = true;
+                return;
+            }
+            b' ' | b'\t' if self.skip_indent => return,
+            _ => self.skip_indent = false,
+        }
+        let record_depth = usize::from(self.array_root);
+        match c {
+            b'[' if !self.started => {
+                self.started = true;
+                self.array_root = true;
+                self.depth = 1;
+            }
+            b']' if self.array_root && self.depth == 1 => self.depth = 0,
+            // The separators between the records are removed.
+            b',' | b' ' | b'\t' if self.depth == record_depth => {}
+            b'{' | b'[' => {
+                self.started = true;
+                self.depth += 1;
+                self.output.push(c);
+            }
+            b'}' | b']' => {
+                self.depth = self.depth.saturating_sub(1);
+                self.output.push(c);
+                if self.depth == record_depth {
+                    self.output.push(b'\n');
+                }
+            }
+            _ => {
+                self.in_string = c == b'"';
+                self.started = true;
+                self.output.push(c);
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for JsonRecordsReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while self.pos >= self.output.len() {
+            self.output.clear();
+            self.pos = 0;
+            let mut chunk = [0; 8192];
+            let size = self.reader.read(&mut chunk)?;
+            if size == 0 {
+                return Ok(0);
+            }
+            for c in &chunk[..size] {
+                self.push(*c);
+            }
+        }
+        let size = buf.len().min(self.output.len() - self.pos);
+        buf[..size].copy_from_slice(&self.output[self.pos..self.pos + size]);
+        self.pos += size;
+        Ok(size)
+    }
+}
+
+#[test]
+fn test_json_records() {
+    let read_all = |data: &str| {
+        let mut result = String::new();
+        JsonRecordsReader::new(Cursor::new(data.to_string()))
+            .read_to_string(&mut result)
+            .unwrap();
+        result
+    };
+    let stream = "{\n  \"kind\": \"Event\",\n  \"user\": {\n    \"name\": \"a b\"\n  }\n}\n{\n  \"kind\": \"Event\",\n  \"msg\": \"}\\\"\\n\"\n}\n";
+    assert!(is_pretty(stream.as_bytes()));
+    assert_eq!(
+        read_all(stream),
+        "{\"kind\": \"Event\",\"user\": {\"name\": \"a b\"}}\n{\"kind\": \"Event\",\"msg\": \"}\\\"\\n\"}\n"
+    );
+
+    let array = "[\n  {\n    \"a\": [1, 2]\n  },\n  {\n    \"b\": 3\n  }\n]\n";
+    assert!(is_pretty(array.as_bytes()));
+    assert_eq!(read_all(array), "{\"a\": [1, 2]}\n{\"b\": 3}\n");
+
+    assert!(!is_pretty(b"{\"a\": 1}\n{\"a\": 2}\n"));
+    assert!(!is_pretty(b"Starting service\n"));
+    assert!(!is_pretty(b"{\n  Name: value\n}\n"));
+}
@@ -0,0 +1,567 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the Kubernetes pods, where the sources are the containers logs.
+//!
+//! The cluster api is accessed with the kubeconfig current context credentials,
+//! and the baseline is a healthy replica of the pod, e.g. another pod of the same deployment.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use url::Url;
+
+use crate::config::TlsSettings;
+use crate::env::Env;
+use crate::{Baselines, Content, Source};
+use logjuicer_report::KubernetesPod;
+
+#[derive(Debug, Deserialize)]
+struct KubeConfig {
+    #[serde(rename = "current-context", default)]
+    current_context: String,
+    #[serde(default)]
+    contexts: Vec<NamedContext>,
+    #[serde(default)]
+    clusters: Vec<NamedCluster>,
+    #[serde(default)]
+    users: Vec<NamedUser>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamedContext {
+    name: String,
+    context: KubeContextRef,
+}
+
+#[derive(Debug, Deserialize)]
+struct KubeContextRef {
+    cluster: String,
+    #[serde(default)]
+    user: String,
+    namespace: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamedCluster {
+    name: String,
+    cluster: Cluster,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Cluster {
+    server: String,
+    certificate_authority: Option<PathBuf>,
+    certificate_authority_data: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamedUser {
+    name: String,
+    user: User,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct User {
+    token: Option<String>,
+    #[serde(rename = "tokenFile")]
+    token_file: Option<PathBuf>,
+    client_certificate: Option<PathBuf>,
+    client_certificate_data: Option<String>,
+    client_key: Option<PathBuf>,
+    client_key_data: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+/// Read a kubeconfig value, either inlined in base64 or from a file relative to the kubeconfig.
+fn read_data(base: &Path, data: Option<&str>, path: Option<&Path>) -> Result<Option<Vec<u8>>> {
+    use base64::Engine;
+    if let Some(data) = data {
+        Ok(Some(
+            base64::engine::general_purpose::STANDARD
+                .decode(data.trim())
+                .context("Decoding kubeconfig data")?,
+        ))
+    } else if let Some(path) = path {
+        let path = base.join(path);
+        Ok(Some(
+            std::fs::read(&path).with_context(|| format!("Reading {:?}", path))?,
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The credentials of the kubeconfig current context.
+pub struct KubeContext {
+    /// The cluster api url, terminated with a slash.
+    pub server: Url,
+    /// The context default namespace.
+    pub namespace: Option<String>,
+    ca: Option<Vec<u8>>,
+    client_cert: Option<(Vec<u8>, Vec<u8>)>,
+    authorization: Option<String>,
+}
+
+impl KubeContext {
+    /// Load the `KUBECONFIG` file, or the default `~/.kube/config`.
+    pub fn load() -> Result<KubeContext> {
+        let path = match std::env::var_os("KUBECONFIG") {
+            // Only the first file of the list is used.
+            Some(paths) => std::env::split_paths(&paths)
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Empty KUBECONFIG"))?,
+            None => Path::new(&std::env::var_os("HOME").unwrap_or_default())
+                .join(".kube")
+                .join("config"),
+        };
+        let file = std::fs::File::open(&path).with_context(|| format!("Opening {:?}", path))?;
+        let config = serde_yaml::from_reader(file).context("Decoding kubeconfig")?;
+        KubeContext::from_config(config, path.parent().unwrap_or_else(|| Path::new(".")))
+    }
+
+    fn from_config(config: KubeConfig, base: &Path) -> Result<KubeContext> {
+        let current_context = config.current_context;
+        let context = config
+            .contexts
+            .into_iter()
+            .find(|context| context.name == current_context)
+            .ok_or_else(|| anyhow::anyhow!("Unknown context: {}", current_context))?
+            .context;
+        let cluster = config
+            .clusters
+            .into_iter()
+            .find(|cluster| cluster.name == context.cluster)
+            .ok_or_else(|| anyhow::anyhow!("Unknown cluster: {}", context.cluster))?
+            .cluster;
+        let user = config
+            .users
+            .into_iter()
+            .find(|user| user.name == context.user)
+            .map(|user| user.user)
+            .unwrap_or_default();
+
+        let mut server = Url::parse(&cluster.server).context("Decoding the cluster server")?;
+        if !server.path().ends_with('/') {
+            server.set_path(&format!("{}/", server.path()));
+        }
+        let token = match (&user.token, &user.token_file) {
+            (Some(token), _) => Some(token.clone()),
+            (None, Some(path)) => Some(
+                std::fs::read_to_string(base.join(path))
+                    .context("Reading the token file")?
+                    .trim()
+                    .to_string(),
+            ),
+            (None, None) => None,
+        };
+        let authorization = match (token, &user.username, &user.password) {
+            (Some(token), _, _) => Some(format!("Bearer {}", token)),
+            (None, Some(username), Some(password)) => {
+                use base64::Engine;
+                let credentials = base64::engine::general_purpose::STANDARD
+                    .encode(format!("{}:{}", username, password));
+                Some(format!("Basic {}", credentials))
+            }
+            _ => None,
+        };
+        let client_cert = match (
+            read_data(
+                base,
+                user.client_certificate_data.as_deref(),
+                user.client_certificate.as_deref(),
+            )?,
+            read_data(
+                base,
+                user.client_key_data.as_deref(),
+                user.client_key.as_deref(),
+            )?,
+        ) {
+            (Some(cert), Some(key)) => Some((cert, key)),
+            _ => None,
+        };
+        Ok(KubeContext {
+            server,
+            namespace: context.namespace,
+            ca: read_data(
+                base,
+                cluster.certificate_authority_data.as_deref(),
+                cluster.certificate_authority.as_deref(),
+            )?,
+            client_cert,
+            authorization,
+        })
+    }
+
+    /// The tls configuration of the env, with the cluster CA and the client certificate.
+    fn tls_config(&self, env: &Env) -> Result<rustls::ClientConfig> {
+        let default = TlsSettings::default();
+        let settings = env.config.tls().unwrap_or(&default);
+        let mut root_certs = crate::env::root_certs(settings)?;
+        if let Some(ca) = &self.ca {
+            let certs = rustls_pemfile::certs(&mut ca.as_slice())?;
+            root_certs.add_parsable_certificates(&certs);
+        }
+        let builder = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(root_certs);
+        let client_cert = match &self.client_cert {
+            Some((cert, key)) => Some((
+                rustls_pemfile::certs(&mut cert.as_slice())?
+                    .into_iter()
+                    .map(rustls::Certificate)
+                    .collect(),
+                crate::env::read_private_key(key)?,
+            )),
+            None => crate::env::client_cert(settings)?,
+        };
+        Ok(match client_cert {
+            Some((certs, key)) => builder.with_client_auth_cert(certs, key)?,
+            None => builder.with_no_client_auth(),
+        })
+    }
+
+    /// Add the credentials to the cluster api requests.
+    #[allow(clippy::result_large_err)]
+    fn authenticate(&self, builder: ureq::AgentBuilder) -> ureq::AgentBuilder {
+        match &self.authorization {
+            Some(authorization) => {
+                let server = self.server.as_str().to_string();
+                let authorization = authorization.clone();
+                // Only the cluster api requests get the credentials.
+                builder.middleware(move |req: ureq::Request, next: ureq::MiddlewareNext| {
+                    if req.url().starts_with(&server) {
+                        next.handle(req.set("Authorization", &authorization))
+                    } else {
+                        next.handle(req)
+                    }
+                })
+            }
+            None => builder,
+        }
+    }
+
+    /// Use the context for the env http clients, keeping the proxy and the tls settings.
+    pub fn install(self, env: &mut Env) -> Result<()> {
+        let tls = Some(Arc::new(self.tls_config(env)?));
+        env.client = crate::env::new_agent(
+            self.authenticate(crate::env::agent_builder()?),
+            &env.config,
+            &tls,
+        );
+        if env.direct_client.is_some() {
+            env.direct_client = Some(crate::env::new_agent(
+                self.authenticate(ureq::builder()),
+                &env.config,
+                &tls,
+            ));
+        }
+        env.kube = Some(self);
+        Ok(())
+    }
+}
+
+/// The kubeconfig context installed in the env.
+pub fn env_context(env: &Env) -> Result<&KubeContext> {
+    env.kube
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("The kubeconfig context is not loaded"))
+}
+
+#[derive(Debug, Deserialize)]
+struct PodList {
+    items: Vec<Pod>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Pod {
+    metadata: PodMetadata,
+    spec: PodSpec,
+    #[serde(default)]
+    status: PodStatus,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PodMetadata {
+    name: String,
+    #[serde(default)]
+    owner_references: Vec<OwnerReference>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OwnerReference {
+    uid: String,
+    #[serde(default)]
+    controller: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct PodSpec {
+    containers: Vec<Container>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Container {
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PodStatus {
+    #[serde(default)]
+    phase: String,
+    #[serde(default)]
+    container_statuses: Vec<ContainerStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContainerStatus {
+    ready: bool,
+    restart_count: u32,
+}
+
+impl Pod {
+    fn controller(&self) -> Option<&str> {
+        self.metadata
+            .owner_references
+            .iter()
+            .find(|owner| owner.controller)
+            .map(|owner| owner.uid.as_str())
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.status.phase == "Running"
+            && self
+                .status
+                .container_statuses
+                .iter()
+                .all(|status| status.ready)
+    }
+
+    fn restart_count(&self) -> u32 {
+        self.status
+            .container_statuses
+            .iter()
+            .map(|status| status.restart_count)
+            .sum()
+    }
+}
+
+pub(crate) fn get_json<T: serde::de::DeserializeOwned>(env: &Env, url: &Url) -> Result<T> {
+    tracing::debug!(url = url.as_str(), "Querying kubernetes api");
+    env.client
+        .request_url("GET", url)
+        .call()
+        .with_context(|| format!("Querying {}", url))?
+        .into_json()
+        .with_context(|| format!("Decoding {}", url))
+}
+
+fn get_pod(env: &Env, api_url: &Url, namespace: &str, pod: &str) -> Result<Pod> {
+    get_json(
+        env,
+        &api_url.join(&format!("api/v1/namespaces/{}/pods/{}", namespace, pod))?,
+    )
+}
+
+/// Decode the `namespace/pod[:container]` argument, the namespace defaults to the context namespace.
+pub fn parse_pod_spec(
+    spec: &str,
+    namespace: Option<&str>,
+) -> Result<(String, String, Option<String>)> {
+    let (namespace, pod) = match spec.split_once('/') {
+        Some((namespace, pod)) => (namespace, pod),
+        None => (namespace.unwrap_or("default"), spec),
+    };
+    let (pod, container) = match pod.split_once(':') {
+        Some((pod, container)) => (pod, Some(container.to_string())),
+        None => (pod, None),
+    };
+    if namespace.is_empty() || pod.is_empty() {
+        Err(anyhow::anyhow!("Bad pod {}, expected namespace/pod", spec))
+    } else {
+        Ok((namespace.to_string(), pod.to_string(), container))
+    }
+}
+
+/// Resolve the pod containers.
+pub fn content_from_pod(
+    env: &Env,
+    api_url: &Url,
+    spec: &str,
+    namespace: Option<&str>,
+    previous: bool,
+) -> Result<Content> {
+    let (namespace, pod, container) = parse_pod_spec(spec, namespace)?;
+    let info = get_pod(env, api_url, &namespace, &pod)?;
+    let containers = match container {
+        Some(container) => vec![container.into()],
+        None => info
+            .spec
+            .containers
+            .into_iter()
+            .map(|container| container.name.into())
+            .collect(),
+    };
+    Ok(Content::Kubernetes(Box::new(KubernetesPod {
+        api_url: api_url.clone(),
+        namespace: namespace.into(),
+        pod: pod.into(),
+        containers,
+        previous,
+    })))
+}
+
+/// Use a healthy pod of the same controller, e.g. the deployment replica set, with the fewest restarts.
+pub fn discover_baselines(pod: &KubernetesPod, env: &Env) -> Result<Baselines> {
+    tracing::info!("Discovering baselines for {}", pod);
+    let info = get_pod(env, &pod.api_url, &pod.namespace, &pod.pod)?;
+    let controller = match info.controller() {
+        Some(controller) => controller,
+        // The pod doesn't have replicas.
+        None => return Ok(vec![]),
+    };
+    let pods: PodList = get_json(
+        env,
+        &pod.api_url
+            .join(&format!("api/v1/namespaces/{}/pods", pod.namespace))?,
+    )?;
+    Ok(pods
+        .items
+        .into_iter()
+        .filter(|replica| {
+            replica.metadata.name != pod.pod.as_ref()
+                && replica.controller() == Some(controller)
+                && replica.is_healthy()
+        })
+        .min_by_key(|replica| replica.restart_count())
+        .map(|replica| {
+            Content::Kubernetes(Box::new(KubernetesPod {
+                api_url: pod.api_url.clone(),
+                namespace: pod.namespace.clone(),
+                pod: replica.metadata.name.into(),
+                containers: pod.containers.clone(),
+                previous: false,
+            }))
+        })
+        .into_iter()
+        .collect())
+}
+
+pub fn sources_iter(pod: &KubernetesPod) -> Box<dyn Iterator<Item = Result<Source>>> {
+    let sources = pod
+        .containers
+        .iter()
+        .map(|container| Ok(pod.log_source(container)?))
+        .collect::<Vec<_>>();
+    Box::new(sources.into_iter())
+}
+
+#[test]
+fn test_kubeconfig() -> Result<()> {
+    let config = serde_yaml::from_str(
+        r#"
+apiVersion: v1
+kind: Config
+current-context: dev
+contexts:
+- name: dev
+  context: {cluster: dev-cluster, user: dev-user, namespace: demo}
+clusters:
+- name: dev-cluster
+  cluster: {server: "https://api.dev:6443/k8s"}
+users:
+- name: dev-user
+  user: {token: secret}
+"#,
+    )?;
+    let context = KubeContext::from_config(config, Path::new("."))?;
+    assert_eq!(context.server.as_str(), "https://api.dev:6443/k8s/");
+    assert_eq!(context.namespace.as_deref(), Some("demo"));
+    assert_eq!(context.authorization.as_deref(), Some("Bearer secret"));
+
+    assert_eq!(
+        parse_pod_spec("app-42:sidecar", context.namespace.as_deref())?,
+        ("demo".into(), "app-42".into(), Some("sidecar".into()))
+    );
+    assert!(parse_pod_spec("/app-42", None).is_err());
+
+    // The env clients authenticate the cluster api requests.
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("GET", "/api/v1/namespaces")
+        .match_header("authorization", "Bearer secret")
+        .create();
+    let mut env = Env::new();
+    KubeContext {
+        server: Url::parse(&format!("{}/", server.url()))?,
+        ..context
+    }
+    .install(&mut env)?;
+    env.client
+        .get(&format!("{}/api/v1/namespaces", server.url()))
+        .call()?;
+    mock.assert();
+    assert_eq!(env_context(&env)?.namespace.as_deref(), Some("demo"));
+    Ok(())
+}
+
+#[test]
+fn test_kubernetes_pod() -> Result<()> {
+    let env = Env::new();
+    let mut server = mockito::Server::new();
+    let pod = |name: &str, phase: &str, restarts: u32| {
+        format!(
+            r#"{{"metadata":{{"name":"{name}","ownerReferences":[{{"uid":"rs-1","controller":true}}]}},"spec":{{"containers":[{{"name":"app"}},{{"name":"proxy"}}]}},"status":{{"phase":"{phase}","containerStatuses":[{{"ready":{},"restartCount":{restarts}}}]}}}}"#,
+            phase == "Running"
+        )
+    };
+    let pod_mock = server
+        .mock("GET", "/api/v1/namespaces/demo/pods/app-1")
+        .with_body(pod("app-1", "Pending", 5))
+        .expect(2)
+        .create();
+    let list_mock = server
+        .mock("GET", "/api/v1/namespaces/demo/pods")
+        .with_body(format!(
+            r#"{{"items":[{},{},{}]}}"#,
+            pod("app-1", "Pending", 5),
+            pod("app-2", "Running", 3),
+            pod("app-3", "Running", 0)
+        ))
+        .expect(1)
+        .create();
+
+    let api_url = Url::parse(&format!("{}/", server.url()))?;
+    let pod = match content_from_pod(&env, &api_url, "demo/app-1", None, true)? {
+        Content::Kubernetes(pod) => pod,
+        _ => panic!("Expected a kubernetes pod"),
+    };
+    assert_eq!(
+        pod.containers,
+        vec![Box::<str>::from("app"), "proxy".into()]
+    );
+
+    let sources = sources_iter(&pod).collect::<Result<Vec<_>>>()?;
+    assert_eq!(sources[0].get_relative(), "app");
+    assert_eq!(
+        sources[1].as_str(),
+        format!(
+            "{}/api/v1/namespaces/demo/pods/app-1/log?previous=true&container=proxy",
+            server.url()
+        )
+    );
+
+    let baselines = discover_baselines(&pod, &env)?;
+    assert_eq!(baselines.len(), 1);
+    assert_eq!(baselines[0].to_string(), "Kubernetes(demo/app-3)");
+    pod_mock.assert();
+    list_mock.assert();
+    Ok(())
+}
@@ -0,0 +1,220 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the Koji builds, where the sources are the `build.log` and `root.log` of each architecture.
+//!
+//! The target logs are read from the task work directory, and the baseline is the last complete build
+//! of the same package, which logs are kept in the package directory.
+
+use anyhow::Result;
+use koji_build::{Client, Target, Task};
+use url::Url;
+
+use crate::env::Env;
+use crate::{Baselines, Content, Source};
+use logjuicer_report::{KojiBuild, KojiLogs};
+
+/// The logs of the buildArch tasks.
+const LOGS: &[&str] = &["build.log", "root.log"];
+
+/// Check if the url is a koji task or build page.
+pub fn content_from_koji_url(env: &Env, url: &Url) -> Option<Result<Content>> {
+    let koji_url = koji_build::parse_koji_url(url)?;
+    let client = Client::new(koji_url.hub_url);
+    Some(
+        match koji_url.target {
+            Target::Task(task_id) => from_task(&client, env, url, task_id),
+            Target::Build(build_id) => client
+                .get_build(env.client_for(&client.hub_url), build_id)
+                .map_err(anyhow::Error::from)
+                .and_then(|build| from_build(&client, env, url.clone(), build)),
+        }
+        .map(|build| Content::Koji(Box::new(build))),
+    )
+}
+
+/// Get the buildArch tasks of a build task.
+fn arch_tasks(client: &Client, env: &Env, task: Task) -> Result<Vec<Task>> {
+    let tasks = if task.method == "buildArch" {
+        vec![task]
+    } else {
+        client
+            .get_task_children(env.client_for(&client.hub_url), task.id)?
+            .into_iter()
+            .filter(|child| child.method == "buildArch")
+            .collect()
+    };
+    if tasks.is_empty() {
+        Err(anyhow::anyhow!("No buildArch tasks found"))
+    } else {
+        Ok(tasks)
+    }
+}
+
+fn from_task(client: &Client, env: &Env, url: &Url, task_id: u64) -> Result<KojiBuild> {
+    let task = client.get_task_info(env.client_for(&client.hub_url), task_id)?;
+    let state = koji_build::task_state_name(task.state);
+    let tasks = arch_tasks(client, env, task)?;
+    let nvr = tasks[0]
+        .srpm_nvr()
+        .ok_or_else(|| anyhow::anyhow!("Unknown source rpm of task {}", tasks[0].id))?
+        .to_string();
+    let top_url = koji_build::top_url(&client.hub_url)?;
+    let logs = tasks
+        .iter()
+        .map(|task| {
+            Ok(KojiLogs {
+                arch: task.arch.as_str().into(),
+                url: koji_build::task_dir(&top_url, task.id)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(KojiBuild {
+        url: url.clone(),
+        hub_url: client.hub_url.clone(),
+        package: koji_build::split_nvr(&nvr)
+            .map_or(nvr.as_str(), |(name, _, _)| name)
+            .into(),
+        nvr: nvr.as_str().into(),
+        state: state.into(),
+        logs,
+    })
+}
+
+/// Resolve the logs of a build, which are moved to the package directory once the build completed.
+fn from_build(client: &Client, env: &Env, url: Url, build: koji_build::Build) -> Result<KojiBuild> {
+    let task_id = build
+        .task_id
+        .ok_or_else(|| anyhow::anyhow!("The build {} doesn't have a task", build.nvr))?;
+    let task = client.get_task_info(env.client_for(&client.hub_url), task_id)?;
+    let top_url = koji_build::top_url(&client.hub_url)?;
+    let logs = arch_tasks(client, env, task)?
+        .iter()
+        .map(|task| {
+            Ok(KojiLogs {
+                arch: task.arch.as_str().into(),
+                url: if build.state == koji_build::BUILD_COMPLETE {
+                    koji_build::build_logs_dir(&top_url, &build, &task.arch)?
+                } else {
+                    koji_build::task_dir(&top_url, task.id)?
+                },
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(KojiBuild {
+        url,
+        hub_url: client.hub_url.clone(),
+        package: build.package_name.as_str().into(),
+        nvr: build.nvr.as_str().into(),
+        state: koji_build::build_state_name(build.state).into(),
+        logs,
+    })
+}
+
+/// Use the last complete build of the package.
+pub fn discover_baselines(build: &KojiBuild, env: &Env) -> Result<Baselines> {
+    tracing::info!("Discovering baselines for {}", build);
+    let client = Client::new(build.hub_url.clone());
+    match client.last_complete_build(env.client_for(&build.hub_url), &build.package)? {
+        Some(baseline) if baseline.nvr != build.nvr.as_ref() => {
+            let url = build
+                .hub_url
+                .join(&format!("/koji/buildinfo?buildID={}", baseline.build_id))?;
+            Ok(vec![Content::Koji(Box::new(from_build(
+                &client, env, url, baseline,
+            )?))])
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+pub fn sources_iter(build: &KojiBuild) -> Box<dyn Iterator<Item = Result<Source>>> {
+    let sources = build
+        .logs
+        .iter()
+        .flat_map(|logs| {
+            LOGS.iter()
+                .map(move |name| Ok(build.log_source(logs, name)?))
+        })
+        .collect::<Vec<_>>();
+    Box::new(sources.into_iter())
+}
+
+#[test]
+fn test_koji_build() -> Result<()> {
+    let env = Env::new();
+    let mut server = mockito::Server::new();
+    let response = |value: &str| {
+        format!(
+            "<?xml version='1.0'?><methodResponse><params><param><value>{}</value></param></params></methodResponse>",
+            value
+        )
+    };
+    let task = |id: u64, method: &str, arch: &str, state: u64| {
+        format!(
+            "<struct><member><name>id</name><value><int>{id}</int></value></member><member><name>method</name><value><string>{method}</string></value></member><member><name>arch</name><value><string>{arch}</string></value></member><member><name>state</name><value><int>{state}</int></value></member><member><name>request</name><value><array><data><value><string>tasks/{id}/{id}/foo-1.1-1.fc40.src.rpm</string></value></data></array></value></member></struct>"
+        )
+    };
+    let mut call = |method: &str, body: String| {
+        server
+            .mock("POST", "/kojihub")
+            .match_body(mockito::Matcher::Regex(format!(
+                "<methodName>{}</methodName>",
+                method
+            )))
+            .with_body(response(&body))
+            .create()
+    };
+    let _task_mock = call("getTaskInfo", task(4242, "build", "noarch", 5));
+    let _children_mock = call(
+        "getTaskChildren",
+        format!(
+            "<array><data><value>{}</value><value>{}</value></data></array>",
+            task(4243, "buildArch", "x86_64", 5),
+            task(4244, "tagBuild", "noarch", 3)
+        ),
+    );
+    let _package_mock = call("getPackageID", "<int>7</int>".into());
+    let _builds_mock = call(
+        "listBuilds",
+        "<array><data><value><struct><member><name>build_id</name><value><int>41</int></value></member><member><name>package_name</name><value><string>foo</string></value></member><member><name>version</name><value><string>1.0</string></value></member><member><name>release</name><value><string>1.fc40</string></value></member><member><name>nvr</name><value><string>foo-1.0-1.fc40</string></value></member><member><name>state</name><value><int>1</int></value></member><member><name>task_id</name><value><int>4242</int></value></member></struct></value></data></array>".into(),
+    );
+
+    let url = Url::parse(&format!("{}/koji/taskinfo?taskID=4242", server.url()))?;
+    let build = match content_from_koji_url(&env, &url).unwrap()? {
+        Content::Koji(build) => build,
+        _ => panic!("Expected a koji build"),
+    };
+    assert_eq!(build.to_string(), "foo-1.1-1.fc40");
+    assert_eq!(build.state.as_ref(), "FAILED");
+    let sources = sources_iter(&build).collect::<Result<Vec<_>>>()?;
+    assert_eq!(
+        sources
+            .iter()
+            .map(|source| source.get_relative())
+            .collect::<Vec<_>>(),
+        vec!["x86_64/build.log", "x86_64/root.log"]
+    );
+    assert_eq!(
+        sources[0].as_str(),
+        format!(
+            "{}/kojifiles/work/tasks/4243/4243/build.log?name=x86_64/build.log",
+            server.url()
+        )
+    );
+
+    let baselines = discover_baselines(&build, &env)?;
+    let baseline = match &baselines[..] {
+        [Content::Koji(baseline)] => baseline,
+        _ => panic!("Expected a koji baseline"),
+    };
+    assert_eq!(baseline.nvr.as_ref(), "foo-1.0-1.fc40");
+    assert_eq!(
+        baseline.logs[0].url.as_str(),
+        format!(
+            "{}/kojifiles/packages/foo/1.0/1.fc40/data/logs/x86_64/",
+            server.url()
+        )
+    );
+    Ok(())
+}
@@ -0,0 +1,253 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the Grafana Loki queries, where the source is the result of a LogQL query for a time range.
+//!
+//! The result is read page by page in the forward direction,
+//! and the baseline is the same query for an earlier time range, by default one week before.
+
+use anyhow::{Context, Result};
+use chrono::Duration;
+use serde::Deserialize;
+use std::io::Read;
+use url::Url;
+
+use crate::env::Env;
+use crate::reader::DecompressReader;
+use crate::timerange::{parse_range, BASELINE_OFFSET_DAYS};
+use crate::{Baselines, Content, Source};
+use logjuicer_report::LokiQuery;
+
+/// The number of entries per request, the loki default `max_entries_limit_per_query`.
+const PAGE_LIMIT: usize = 5000;
+
+const QUERY_RANGE_PATH: &str = "/loki/api/v1/query_range";
+
+pub fn is_query_url(url: &Url) -> bool {
+    url.path().ends_with(QUERY_RANGE_PATH)
+}
+
+/// Create the query for the `since` and `until` time range.
+pub fn new_query(url: &str, query: &str, since: &str, until: &str) -> Result<LokiQuery> {
+    let mut url = Url::parse(url).with_context(|| format!("Bad loki url {}", url))?;
+    if !url.path().ends_with('/') {
+        // The api path is joined to the url, which needs to be a directory.
+        url.set_path(&format!("{}/", url.path()));
+    }
+    let (start, end) = parse_range(since, until)?;
+    Ok(LokiQuery {
+        url,
+        query: query.into(),
+        start,
+        end,
+    })
+}
+
+/// Use the same query, one week before.
+pub fn discover_baselines(query: &LokiQuery, _env: &Env) -> Result<Baselines> {
+    Ok(vec![Content::Loki(Box::new(
+        query.shift(Duration::days(BASELINE_OFFSET_DAYS)),
+    ))])
+}
+
+pub fn sources_iter(query: &LokiQuery) -> Box<dyn Iterator<Item = Result<Source>>> {
+    Box::new(std::iter::once(query.source().map_err(anyhow::Error::from)))
+}
+
+/// Open the query result.
+pub fn open(env: &Env, url: &Url) -> Result<DecompressReader> {
+    Ok(DecompressReader::RemoteDecoder(Box::new(QueryReader::new(
+        env.client_for(url).clone(),
+        url,
+        PAGE_LIMIT,
+    )?)))
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryResponse {
+    data: QueryData,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryData {
+    #[serde(rename = "resultType")]
+    result_type: String,
+    #[serde(default)]
+    result: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct Stream {
+    /// The entries timestamp in nanoseconds, and the line.
+    values: Vec<(String, String)>,
+}
+
+/// Read the query result lines, requesting the next page when the buffer is consumed.
+struct QueryReader {
+    client: ureq::Agent,
+    /// The query url, without the start parameter.
+    url: Url,
+    limit: usize,
+    /// The start of the next page, None when the range is completed.
+    start: Option<i64>,
+    buffer: std::io::Cursor<Vec<u8>>,
+}
+
+impl QueryReader {
+    fn new(client: ureq::Agent, url: &Url, limit: usize) -> Result<Self> {
+        let mut start = None;
+        let mut params = Vec::new();
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "start" => start = Some(value.parse().context("Bad query start")?),
+                "direction" | "limit" => {}
+                _ => params.push((key.into_owned(), value.into_owned())),
+            }
+        }
+        let start = start.ok_or_else(|| anyhow::anyhow!("{}: missing start", url))?;
+        let mut url = url.clone();
+        url.query_pairs_mut()
+            .clear()
+            .extend_pairs(params)
+            .append_pair("direction", "forward")
+            .append_pair("limit", &limit.to_string());
+        Ok(QueryReader {
+            client,
+            url,
+            limit,
+            start: Some(start),
+            buffer: std::io::Cursor::new(Vec::new()),
+        })
+    }
+
+    fn request(&self, start: i64) -> ureq::Request {
+        let mut url = self.url.clone();
+        url.query_pairs_mut()
+            .append_pair("start", &start.to_string());
+        let mut request = self.client.request_url("GET", &url);
+        // The logcli settings, for the multi-tenant and the protected deployments.
+        if let Ok(org_id) = std::env::var("LOKI_ORG_ID") {
+            request = request.set("X-Scope-OrgID", &org_id);
+        }
+        if let (Ok(username), Ok(password)) = (
+            std::env::var("LOKI_USERNAME"),
+            std::env::var("LOKI_PASSWORD"),
+        ) {
+            use base64::Engine;
+            let credentials = base64::engine::general_purpose::STANDARD
+                .encode(format!("{}:{}", username, password));
+            request = request.set("Authorization", &format!("Basic {}", credentials));
+        }
+        request
+    }
+
+    fn next_page(&mut self, start: i64) -> Result<()> {
+        let resp: QueryResponse = self
+            .request(start)
+            .call()
+            .context("Can't query loki")?
+            .into_json()
+            .context("Can't decode the loki result")?;
+        if resp.data.result_type != "streams" {
+            return Err(anyhow::anyhow!(
+                "The query result is {}, expected log streams",
+                resp.data.result_type
+            ));
+        }
+        let streams: Vec<Stream> = serde_json::from_value(resp.data.result)?;
+        let mut entries = Vec::new();
+        for stream in streams {
+            for (ts, line) in stream.values {
+                entries.push((ts.parse::<i64>().context("Bad entry timestamp")?, line));
+            }
+        }
+        // The streams are merged in the time order.
+        entries.sort_by_key(|(ts, _)| *ts);
+        // The next page starts after the last entry, the entries sharing its timestamp past the limit are skipped.
+        self.start = match entries.last() {
+            Some((ts, _)) if entries.len() >= self.limit => Some(ts + 1),
+            _ => None,
+        };
+        let mut buffer = Vec::new();
+        for (_, line) in entries {
+            buffer.extend_from_slice(line.trim_end_matches('\n').as_bytes());
+            buffer.push(b'\n');
+        }
+        self.buffer = std::io::Cursor::new(buffer);
+        Ok(())
+    }
+}
+
+impl Read for QueryReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let size = self.buffer.read(buf)?;
+            if size > 0 || buf.is_empty() {
+                return Ok(size);
+            }
+            match self.start.take() {
+                Some(start) => self
+                    .next_page(start)
+                    .map_err(|e| std::io::Error::other(format!("{:?}", e)))?,
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_loki_query() -> Result<()> {
+    use mockito::Matcher;
+    let env = Env::new();
+    let mut server = mockito::Server::new();
+    let mut page = |start: &str, body: &str| {
+        server
+            .mock("GET", "/loki/api/v1/query_range")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("query".into(), r#"{app="nginx"}"#.into()),
+                Matcher::UrlEncoded("start".into(), start.into()),
+                Matcher::UrlEncoded("limit".into(), "2".into()),
+            ]))
+            .with_body(format!(
+                r#"{{"status":"success","data":{{"resultType":"streams","result":[{}]}}}}"#,
+                body
+            ))
+            .expect(1)
+            .create()
+    };
+    let first_page = page(
+        "0",
+        r#"{"stream":{"pod":"a"},"values":[["20","second"]]},{"stream":{"pod":"b"},"values":[["10","first"]]}"#,
+    );
+    let last_page = page(
+        "21",
+        r#"{"stream":{"pod":"a"},"values":[["30","third\n"]]}"#,
+    );
+
+    let query = new_query(
+        &server.url(),
+        r#"{app="nginx"}"#,
+        "1970-01-01T00:00:00Z",
+        "1970-01-01T00:01:00Z",
+    )?;
+    let sources = sources_iter(&query).collect::<Result<Vec<_>>>()?;
+    assert_eq!(sources[0].get_relative(), "%7Bapp%3D%22nginx%22%7D");
+    let url = match &sources[0] {
+        Source::Remote(_, url) => url.clone(),
+        _ => panic!("Expected a remote source"),
+    };
+    assert!(is_query_url(&url));
+
+    let mut lines = String::new();
+    QueryReader::new(env.client.clone(), &url, 2)?.read_to_string(&mut lines)?;
+    assert_eq!(lines, "first\nsecond\nthird\n");
+    first_page.assert();
+    last_page.assert();
+
+    let baselines = discover_baselines(&query, &env)?;
+    assert_eq!(
+        baselines[0].to_string(),
+        r#"Loki({app="nginx"} [1969-12-25T00:00:00Z, 1969-12-25T00:01:00Z])"#
+    );
+    Ok(())
+}
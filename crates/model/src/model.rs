@@ -17,21 +17,54 @@ use url::Url;
 pub use logjuicer_tokenizer::index_name::IndexName;
 
 pub use logjuicer_report::{
-    AnomalyContext, ApiUrl, Content, IndexReport, LogReport, ProwBuild, Report, Source, ZuulBuild,
+    AnomalyContext, ApiUrl, BuildkiteBuild, CircleCiJob, CloudWatchQuery, Container, Content,
+    ElasticQuery, IndexReport, JournalQuery, KojiBuild, LogReport, LokiQuery, ObsBuild, Provenance,
+    ProwBuild, Report, RunWarning, Source, SourceStats, TektonPipelineRun, WarningKind, ZuulBuild,
 };
 
 pub use logjuicer_index::{FeaturesMatrix, FeaturesMatrixBuilder};
 
 use crate::env::Env;
 use crate::files::{dir_iter, file_iter, file_open};
+use crate::prefetch::Prefetch;
 use crate::unordered::KnownLines;
 use crate::urls::{httpdir_iter, url_open};
+pub mod ansible;
+pub mod archive;
+pub mod baseline_check;
+pub mod buildkite;
+pub mod category;
+pub mod circleci;
+pub mod cloudwatch;
 pub mod config;
+pub mod container;
+pub mod elastic;
+pub mod encoding;
 pub mod env;
+pub mod estimate;
+pub mod export;
 pub mod files;
+pub mod gcs;
+pub mod jenkins;
+pub mod journal;
+pub mod journalctl;
+pub mod json;
+pub mod k8s;
+pub mod koji;
+pub mod loki;
+pub mod obs;
+pub mod prefetch;
 pub mod process;
+pub mod progress;
 pub mod prow;
 mod reader;
+pub mod rotation;
+pub mod s3;
+pub mod sftp;
+pub mod store;
+pub mod tekton;
+pub mod test_results;
+pub mod timerange;
 pub mod unordered;
 pub mod urls;
 pub mod zuul;
@@ -41,7 +74,10 @@ use logjuicer_index::traits::*;
 const MODEL_MAGIC: &str = "LGRD";
 
 // Remember to bump this value when changing the tokenizer or the vectorizer to avoid using incompatible models.
-const MODEL_VERSION: usize = 7;
+const MODEL_VERSION: usize = 9;
+
+/// The age after which a baseline is considered stale.
+const STALE_BASELINE_DAYS: i64 = 30;
 
 /// The user input.
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,11 +85,30 @@ pub enum Input {
     Path(String),
     Url(String),
     ZuulBuild(PathBuf, String),
+    /// A `namespace/pod[:container]` and if the previous containers logs are requested.
+    KubernetesPod(String, bool),
+    /// A LogQL query for a time range.
+    LokiQuery(Box<LokiQuery>),
+    /// An Elasticsearch or OpenSearch query for a time range.
+    ElasticQuery(Box<ElasticQuery>),
+    /// A CloudWatch log group for a time range.
+    CloudWatchQuery(Box<CloudWatchQuery>),
+    /// The local systemd journal.
+    JournalQuery(Box<JournalQuery>),
+    /// A docker or podman container, and the engine api address.
+    Container(Option<String>, String),
+    /// A `namespace/name` Tekton PipelineRun.
+    TektonPipelineRun(String),
 }
 
 impl Input {
     pub fn from_string(s: String) -> Input {
-        match s.starts_with("http") {
+        match s.starts_with("http")
+            || s.starts_with("s3://")
+            || s.starts_with("gs://")
+            || s.starts_with("sftp://")
+            || s.starts_with("scp://")
+        {
             true => Input::Url(s),
             false => Input::Path(s),
         }
@@ -70,12 +125,18 @@ type Baselines = Vec<Content>;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Model<IR: IndexReader> {
     pub created_at: SystemTime,
+    /// The tokenizer settings used to train the model.
+    pub tokenizer: Box<str>,
     pub baselines: Baselines,
     pub indexes: HashMap<IndexName, Index<IR>>,
 }
 
-pub fn indexname_from_source(source: &Source) -> IndexName {
-    IndexName::from_path(source.get_relative())
+/// The index name of a source, using the configured groups or else the source file name.
+pub fn indexname_from_source(env: &Env, source: &Source) -> IndexName {
+    match env.config.group_name(source) {
+        Some(name) => IndexName(name.into()),
+        None => IndexName::from_path(source.get_relative()),
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -86,6 +147,8 @@ pub struct Index<IR: IndexReader> {
     index: IR,
     pub line_count: usize,
     pub byte_count: usize,
+    /// The first indexed lines.
+    pub samples: Vec<Box<str>>,
 }
 
 impl<IR: IndexReader> Index<IR> {
@@ -102,30 +165,68 @@ impl<IR: IndexReader> Index<IR> {
 }
 
 impl<IR: IndexReader> Index<IR> {
-    #[tracing::instrument(level = "debug", name = "Index::train", skip(env, builder))]
     pub fn train<IB>(env: &Env, builder: IB, sources: &[Source]) -> Result<Index<IR>>
+    where
+        IB: IndexBuilder<Reader = IR>,
+    {
+        Index::train_with(env, &mut Prefetch::new(env, sources), builder, sources)
+    }
+
+    /// Train the index, reading the sources downloaded by the prefetch pool.
+    #[tracing::instrument(level = "debug", name = "Index::train", skip(env, prefetch, builder))]
+    pub fn train_with<IB>(
+        env: &Env,
+        prefetch: &mut Prefetch,
+        builder: IB,
+        sources: &[Source],
+    ) -> Result<Index<IR>>
     where
         IB: IndexBuilder<Reader = IR>,
     {
         let created_at = SystemTime::now();
         let start_time = Instant::now();
         let is_json = if let Some(source) = sources.first() {
-            source.is_json()
+            source_is_json(source)
         } else {
             false
         };
-        let mut trainer = process::IndexTrainer::new(builder, is_json);
+        let is_logfmt = sources
+            .first()
+            .is_some_and(|source| env.config.is_logfmt(source));
+        let mut trainer = process::IndexTrainer::new(builder, is_json)
+            .with_logfmt(is_logfmt)
+            .with_csv(
+                sources
+                    .first()
+                    .and_then(|source| env.config.csv_format(source)),
+            )
+            .with_rules(env.config.tokenizer_rules().clone())
+            .with_strip_ansi(env.config.strip_ansi())
+            .with_locale(env.config.locale())
+            .with_max_line_length(env.config.max_line_length())
+            .with_cancel(env.cancel.clone());
         for source in sources {
-            let reader = match source {
-                Source::Local(_, path_buf) => file_open(path_buf.as_path())?,
-                Source::Remote(prefix, url) => url_open(env, *prefix, url)?,
+            env.cancel.check()?;
+            let reader = match source_open_with(env, prefetch, source) {
+                Ok(reader) => reader,
+                // A missing baseline source does not prevent the training.
+                Err(e) => {
+                    tracing::error!("{}: failed to open: {:#}", source, e);
+                    env.progress.file_analyzed(source);
+                    continue;
+                }
             };
+            let byte_count = trainer.byte_count;
             if let Err(e) = trainer.add(reader) {
                 tracing::error!("{}: failed to load: {}", source, e)
             }
+            env.progress
+                .bytes_downloaded(source, trainer.byte_count - byte_count);
+            env.progress.file_analyzed(source);
         }
         let line_count = trainer.line_count;
         let byte_count = trainer.byte_count;
+        let samples = std::mem::take(&mut trainer.samples);
         let index = trainer.build();
         let sources = sources.to_vec();
         let train_time = start_time.elapsed();
@@ -136,6 +237,7 @@ impl<IR: IndexReader> Index<IR> {
             train_time,
             line_count,
             byte_count,
+            samples,
         })
     }
 
@@ -145,22 +247,66 @@ impl<IR: IndexReader> Index<IR> {
         source: &Source,
         skip_lines: &'a mut KnownLines,
     ) -> Result<process::ChunkProcessor<IR, crate::reader::DecompressReader>> {
-        let fp = match source {
-            Source::Local(_, path_buf) => file_open(path_buf.as_path()),
-            Source::Remote(prefix, url) => url_open(env, *prefix, url),
-        }?;
+        self.get_rotated_processor(env, std::slice::from_ref(source), skip_lines)
+    }
+
+    /// Get a processor for a rotation set, the sources are sorted from the oldest to the current file.
+    pub fn get_rotated_processor<'a>(
+        &'a self,
+        env: &Env,
+        sources: &[Source],
+        skip_lines: &'a mut KnownLines,
+    ) -> Result<process::ChunkProcessor<'a, IR, crate::reader::DecompressReader>> {
+        self.get_prefetched_processor(env, &mut Prefetch::default(), sources, skip_lines)
+    }
+
+    /// Get a processor for a rotation set, reading the sources downloaded by the prefetch pool.
+    pub fn get_prefetched_processor<'a>(
+        &'a self,
+        env: &Env,
+        prefetch: &mut Prefetch,
+        sources: &[Source],
+        skip_lines: &'a mut KnownLines,
+    ) -> Result<process::ChunkProcessor<'a, IR, crate::reader::DecompressReader>> {
+        let source = sources.last().context("Empty rotation set")?;
+        let fp = rotation::open_with(env, prefetch, sources)?;
+        Ok(self.get_reader_processor(env, source, fp, skip_lines))
+    }
+
+    /// Get a processor for an opened reader, such as a [stream_open] result.
+    pub fn get_reader_processor<'a>(
+        &'a self,
+        env: &Env,
+        source: &Source,
+        fp: crate::reader::DecompressReader,
+        skip_lines: &'a mut KnownLines,
+    ) -> process::ChunkProcessor<'a, IR, crate::reader::DecompressReader> {
         let is_job_output = if let Some((_, file_name)) = source.as_str().rsplit_once('/') {
             file_name.starts_with("job-output")
         } else {
             false
         };
-        Ok(process::ChunkProcessor::new(
+        process::ChunkProcessor::new(
             fp,
             &self.index,
-            source.is_json(),
+            source_is_json(source),
             is_job_output,
             skip_lines,
-        ))
+        )
+        .with_policy(&env.config.source_policy(source))
+        .with_logfmt(env.config.is_logfmt(source))
+        .with_csv(env.config.csv_format(source))
+        .with_rules(env.config.tokenizer_rules().clone())
+        .with_strip_ansi(env.config.strip_ansi())
+        .with_locale(env.config.locale())
+        .with_max_line_length(env.config.max_line_length())
+        .with_max_bytes(
+            env.config
+                .limits()
+                .max_file_bytes
+                .map(|max_bytes| max_bytes as usize),
+        )
+        .with_cancel(env.cancel.clone())
     }
 
     #[tracing::instrument(level = "debug", name = "Index::inspect", skip(self, env))]
@@ -178,6 +324,94 @@ impl<IR: IndexReader> Index<IR> {
     }
 }
 
+/// Open a source, converting the journal exports, the UTF-16 files and the pretty-printed json to text lines.
+pub fn source_open(env: &Env, source: &Source) -> Result<crate::reader::DecompressReader> {
+    source_open_with(env, &mut Prefetch::default(), source)
+}
+
+/// Open a source, using the download started by the prefetch pool.
+pub fn source_open_with(
+    env: &Env,
+    prefetch: &mut Prefetch,
+    source: &Source,
+) -> Result<crate::reader::DecompressReader> {
+    let reader = match source {
+        Source::Local(_, path_buf) => file_open(env, path_buf.as_path()),
+        Source::Remote(_, url) if crate::loki::is_query_url(url) => crate::loki::open(env, url),
+        Source::Remote(_, url) if crate::elastic::is_search_url(url) => {
+            crate::elastic::open(env, url)
+        }
+        Source::Remote(_, url) if crate::cloudwatch::is_cloudwatch_url(url) => {
+            crate::cloudwatch::open(env, url)
+        }
+        Source::Remote(_, url) if crate::journalctl::is_journal_url(url) => {
+            crate::journalctl::open(url)
+        }
+        Source::Remote(_, url) if crate::container::is_container_url(url) => {
+            crate::container::open(url)
+        }
+        Source::Remote(_, url) if crate::sftp::is_sftp_url(url) => {
+            env.check_online(url)?;
+            crate::sftp::open(url)
+        }
+        Source::Remote(_, url) if crate::circleci::is_output_url(url) => {
+            crate::circleci::open(env, url)
+        }
+        Source::Remote(prefix, url) => prefetch.open(env, *prefix, url),
+    }?;
+    let reader = if env.config.ansible_failed_tasks() && ansible::is_job_output(source) {
+        ansible::open(env, source, reader)?
+    } else {
+        reader
+    };
+    source_decode(source, reader)
+}
+
+/// Open a stream, such as the standard input, as if it was read from the given source.
+pub fn stream_open<R: Read + 'static>(
+    source: &Source,
+    reader: R,
+) -> Result<crate::reader::DecompressReader> {
+    source_decode(
+        source,
+        crate::reader::DecompressReader::RemoteDecoder(Box::new(reader)),
+    )
+}
+
+fn source_decode(
+    source: &Source,
+    reader: crate::reader::DecompressReader,
+) -> Result<crate::reader::DecompressReader> {
+    let reader = encoding::decode(reader)
+        .with_context(|| format!("{}: failed to detect the encoding", source))?;
+    if journal::is_journal_name(source.get_relative()) {
+        Ok(crate::reader::DecompressReader::Journal(Box::new(
+            journal::JournalReader::new(reader),
+        )))
+    } else if source.is_json() {
+        // The json files are already split on the object separators.
+        Ok(reader)
+    } else {
+        json::detect(reader).with_context(|| format!("{}: failed to read", source))
+    }
+}
+
+/// Check if a remote url is read with a plain download, instead of a custom reader in [source_open_with].
+pub(crate) fn is_download_url(url: &Url) -> bool {
+    !(crate::loki::is_query_url(url)
+        || crate::elastic::is_search_url(url)
+        || crate::cloudwatch::is_cloudwatch_url(url)
+        || crate::journalctl::is_journal_url(url)
+        || crate::container::is_container_url(url)
+        || crate::sftp::is_sftp_url(url)
+        || crate::circleci::is_output_url(url))
+}
+
+/// Check if the source lines need to be split as json.
+pub fn source_is_json(source: &Source) -> bool {
+    source.is_json() && !journal::is_journal_name(source.get_relative())
+}
+
 /// Apply convertion rules to convert the user Input to Content.
 #[tracing::instrument(level = "debug", skip(env), ret)]
 pub fn content_from_input(env: &Env, input: Input) -> Result<Content> {
@@ -197,17 +431,112 @@ pub fn content_from_input(env: &Env, input: Input) -> Result<Content> {
                 Box::new(crate::zuul::from_inventory(url, inventory_obj)?),
             ))
         }
+        Input::KubernetesPod(spec, previous) => {
+            let context = crate::k8s::env_context(env)?;
+            crate::k8s::content_from_pod(
+                env,
+                &context.server,
+                &spec,
+                context.namespace.as_deref(),
+                previous,
+            )
+        }
+        Input::TektonPipelineRun(spec) => {
+            let context = crate::k8s::env_context(env)?;
+            crate::tekton::content_from_pipeline_run(
+                env,
+                &context.server,
+                &spec,
+                context.namespace.as_deref(),
+            )
+        }
+        Input::LokiQuery(query) => Ok(Content::Loki(query)),
+        Input::ElasticQuery(query) => Ok(Content::Elasticsearch(query)),
+        Input::CloudWatchQuery(query) => Ok(Content::CloudWatch(query)),
+        Input::JournalQuery(query) => Ok(Content::Journal(query)),
+        Input::Container(host, name) => {
+            crate::container::content_from_container(host.as_deref(), &name)
+        }
     }
 }
 
+/// The model version and the tokenizer settings used to train a model.
+pub(crate) fn trainer_settings(tokenizer: &str) -> String {
+    format!("model_version={} {}", MODEL_VERSION, tokenizer)
+}
+
 /// Create content from raw file path, usefule for testing.
 pub fn content_from_pathbuf(p: PathBuf) -> Content {
     Content::File(Source::from_pathbuf(p))
 }
 
+/// A temporary directory to write the logs of the tests.
+#[cfg(test)]
+pub(crate) struct TestLogs(tempfile::TempDir);
+
+#[cfg(test)]
+impl TestLogs {
+    pub(crate) fn new() -> Result<TestLogs> {
+        // The default excludes skip the hidden directories, such as the default tempdir.
+        Ok(TestLogs(
+            tempfile::Builder::new().prefix("logjuicer").tempdir()?,
+        ))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        self.0.path()
+    }
+
+    /// Write the files of a directory, such as the baseline or the target, and return its content.
+    pub(crate) fn content<D: AsRef<[u8]>>(
+        &self,
+        name: &str,
+        files: &[(&str, D)],
+    ) -> Result<Content> {
+        let dir = self.path().join(name);
+        std::fs::create_dir_all(&dir)?;
+        for (file, data) in files {
+            std::fs::write(dir.join(file), data)?;
+        }
+        crate::files::content_from_path(&dir)
+    }
+}
+
+/// The job name of a build, used to find its pinned baseline.
+fn content_job_name(content: &Content) -> Option<&str> {
+    match content {
+        Content::Zuul(build) | Content::LocalZuulBuild(_, build) => Some(&build.job_name),
+        Content::Prow(build) => Some(&build.job_name),
+        Content::Jenkins(build) => Some(&build.job_name),
+        Content::Buildkite(build) => Some(&build.pipeline),
+        Content::CircleCi(job) => Some(&job.job_name),
+        Content::Tekton(run) if !run.pipeline.is_empty() => Some(&run.pipeline),
+        _ => None,
+    }
+}
+
 /// Discover the baselines for this Content.
 #[tracing::instrument(level = "debug", skip(env))]
 pub fn content_discover_baselines(content: &Content, env: &Env) -> Result<Baselines> {
+    if let Some(job_name) = content_job_name(content) {
+        match env.config.baseline_pin(job_name) {
+            Some(crate::config::BaselinePin::Skip) => {
+                return Err(anyhow::anyhow!(
+                    "Baselines discovery is skipped for the {} job by the configuration.",
+                    job_name
+                ))
+            }
+            Some(crate::config::BaselinePin::Url(url)) => {
+                tracing::info!(
+                    "Using the pinned baseline {} for the {} job.",
+                    url,
+                    job_name
+                );
+                return Ok(vec![crate::urls::content_from_url(env, url.clone())?]);
+            }
+            None => {}
+        }
+    }
     (match content {
         Content::File(src) => match src {
             Source::Local(_, pathbuf) => {
@@ -221,9 +550,41 @@ pub fn content_discover_baselines(content: &Content, env: &Env) -> Result<Baseli
             "Use the diff command to process directory.",
         )),
         Content::Prow(build) => crate::prow::discover_baselines(build, env),
+        Content::Jenkins(build) => crate::jenkins::discover_baselines(build, env),
+        Content::Kubernetes(pod) => crate::k8s::discover_baselines(pod, env),
+        Content::Loki(query) => crate::loki::discover_baselines(query, env),
+        Content::Elasticsearch(query) => crate::elastic::discover_baselines(query, env),
+        Content::CloudWatch(query) => crate::cloudwatch::discover_baselines(query, env),
+        Content::Journal(query) => crate::journalctl::discover_baselines(query, env),
+        Content::Container(container) => crate::container::discover_baselines(container),
+        Content::Tekton(run) => crate::tekton::discover_baselines(run, env),
+        Content::Buildkite(build) => crate::buildkite::discover_baselines(build, env),
+        Content::CircleCi(job) => crate::circleci::discover_baselines(job, env),
+        Content::Koji(build) => crate::koji::discover_baselines(build, env),
+        Content::Obs(build) => crate::obs::discover_baselines(build),
         Content::Zuul(build) => crate::zuul::discover_baselines(build, env),
         Content::LocalZuulBuild(_, build) => crate::zuul::discover_baselines(build, env),
     })
+    .map(|baselines| baseline_check::check_all(env, baselines))
+    .and_then(|checks| {
+        let rejected = checks
+            .iter()
+            .filter(|check| !check.accepted)
+            .map(|check| check.to_string())
+            .collect::<Vec<_>>();
+        let baselines = checks
+            .into_iter()
+            .filter(|check| check.accepted)
+            .map(|check| check.baseline)
+            .collect::<Vec<_>>();
+        if baselines.is_empty() && !rejected.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Baselines discovery failed: {}. Use the diff command to provide the baseline.",
+                rejected.join("; ")
+            ));
+        }
+        Ok(baselines)
+    })
     .and_then(|baselines| match baselines.len() {
         0 => Err(anyhow::anyhow!(
             "Baselines discovery failed, use the diff command to provide the baseline."
@@ -264,10 +625,31 @@ pub fn content_get_sources_iter(
         Content::File(src) => Box::new(file_iter(src)),
         Content::Directory(src) => match src {
             Source::Local(_, pathbuf) => Box::new(dir_iter(pathbuf.as_path())),
+            Source::Remote(_, url) if crate::s3::is_s3_url(url) => {
+                Box::new(crate::s3::sources_iter(url, env))
+            }
+            Source::Remote(_, url) if crate::gcs::is_gs_url(url) => {
+                Box::new(crate::gcs::sources_iter(url, env))
+            }
+            Source::Remote(_, url) if crate::sftp::is_sftp_url(url) => {
+                Box::new(crate::sftp::sources_iter(url, env))
+            }
             Source::Remote(_, url) => Box::new(httpdir_iter(url, env)),
         },
         Content::Zuul(build) => Box::new(crate::zuul::sources_iter(build, env)),
         Content::Prow(build) => Box::new(crate::prow::sources_iter(build, env)),
+        Content::Jenkins(build) => Box::new(crate::jenkins::sources_iter(build, env)),
+        Content::Kubernetes(pod) => Box::new(crate::k8s::sources_iter(pod)),
+        Content::Loki(query) => Box::new(crate::loki::sources_iter(query)),
+        Content::Elasticsearch(query) => Box::new(crate::elastic::sources_iter(query)),
+        Content::CloudWatch(query) => Box::new(crate::cloudwatch::sources_iter(query)),
+        Content::Journal(query) => Box::new(crate::journalctl::sources_iter(query)),
+        Content::Container(container) => Box::new(crate::container::sources_iter(container)),
+        Content::Tekton(run) => Box::new(crate::tekton::sources_iter(run)),
+        Content::Buildkite(build) => Box::new(crate::buildkite::sources_iter(build, env)),
+        Content::CircleCi(job) => Box::new(crate::circleci::sources_iter(job, env)),
+        Content::Koji(build) => Box::new(crate::koji::sources_iter(build)),
+        Content::Obs(build) => Box::new(crate::obs::sources_iter(build)),
         Content::LocalZuulBuild(src, _) => Box::new(dir_iter(src.as_path())),
     }
 }
@@ -277,7 +659,7 @@ pub fn group_sources(env: &Env, baselines: &[Content]) -> Result<HashMap<IndexNa
     for baseline in baselines {
         for source in content_get_sources(baseline, env)? {
             groups
-                .entry(indexname_from_source(&source))
+                .entry(indexname_from_source(env, &source))
                 .or_insert_with(Vec::new)
                 .push(source);
         }
@@ -285,10 +667,68 @@ pub fn group_sources(env: &Env, baselines: &[Content]) -> Result<HashMap<IndexNa
     Ok(groups)
 }
 
+/// Group the target sources, along with the test results files found before the sources filter.
+/// The target sources grouped by index name, and the test results sources.
+type TargetSources = (HashMap<IndexName, Vec<Source>>, Vec<Source>);
+
+fn group_target_sources(env: &Env, target: &Content) -> Result<TargetSources> {
+    let mut groups = HashMap::new();
+    let mut results_sources = Vec::new();
+    for source in content_get_sources_iter(target, env) {
+        let source = source?;
+        if test_results::is_results_source(&source) {
+            results_sources.push(source.clone());
+        }
+        if env.config.is_source_valid(&source) {
+            groups
+                .entry(indexname_from_source(env, &source))
+                .or_insert_with(Vec::new)
+                .push(source);
+        }
+    }
+    if groups.is_empty() {
+        Err(anyhow::anyhow!(format!("Empty sources: {}", target)))
+    } else {
+        Ok((groups, results_sources))
+    }
+}
+
+/// The failure of a source analysis.
+#[derive(Debug)]
+enum SourceError {
+    /// The source is not valid utf-8, it is reported as a warning.
+    Encoding(String),
+    /// The source couldn't be read.
+    Read(String),
+}
+
+impl From<anyhow::Error> for SourceError {
+    fn from(err: anyhow::Error) -> SourceError {
+        let is_encoding = err.chain().any(|cause| {
+            cause.downcast_ref::<std::io::Error>().is_some_and(|err| {
+                err.kind() == std::io::ErrorKind::InvalidData
+                    && err
+                        .get_ref()
+                        .is_some_and(|inner| inner.is::<std::str::Utf8Error>())
+            })
+        });
+        if is_encoding {
+            SourceError::Encoding(format!("{}", err))
+        } else {
+            SourceError::Read(format!("{}", err))
+        }
+    }
+}
+
 #[derive(Debug)]
 struct LineCounters {
     line_count: usize,
+    byte_count: usize,
+    source_count: usize,
     anomaly_count: usize,
+    source_stats: Vec<SourceStats>,
+    /// The sources that exceed the max_file_bytes limit.
+    truncated: Vec<Source>,
 }
 
 impl Default for LineCounters {
@@ -301,7 +741,32 @@ impl LineCounters {
     fn new() -> Self {
         LineCounters {
             line_count: 0,
+            byte_count: 0,
+            source_count: 0,
             anomaly_count: 0,
+            source_stats: Vec::new(),
+            truncated: Vec::new(),
+        }
+    }
+
+    /// Check the run limits, before analyzing the next source.
+    fn limit_reached(&self, limits: &crate::config::Limits, start_time: Instant) -> Option<String> {
+        match (limits.max_sources, limits.max_bytes, limits.timeout_secs) {
+            (Some(max_sources), _, _) if self.source_count >= max_sources => {
+                Some(format!("the limit of {} sources is reached", max_sources))
+            }
+            (_, Some(max_bytes), _) if self.byte_count as u64 >= max_bytes => {
+                Some(format!("the limit of {} bytes is reached", max_bytes))
+            }
+            (_, _, Some(timeout_secs))
+                if start_time.elapsed() >= Duration::from_secs(timeout_secs) =>
+            {
+                Some(format!(
+                    "the timeout of {} seconds is reached",
+                    timeout_secs
+                ))
+            }
+            _ => None,
         }
     }
 }
@@ -315,80 +780,278 @@ impl<IR: IndexReader> Model<IR> {
     ) -> Result<Model<IR>> {
         let created_at = SystemTime::now();
         let mut indexes = HashMap::new();
-        for (index_name, sources) in group_sources(env, &baselines)?.drain() {
+        let groups = group_sources(env, &baselines)?
+            .into_iter()
+            .collect::<Vec<_>>();
+        // Download the sources in the order they are read.
+        let sources = groups
+            .iter()
+            .flat_map(|(_, sources)| sources.iter().cloned())
+            .collect::<Vec<_>>();
+        env.progress.files_discovered(sources.len());
+        let mut prefetch = Prefetch::new(env, &sources);
+        for (index_name, sources) in groups {
             env.debug_or_progress(&format!(
                 "Loading index {} with {}",
                 index_name,
                 sources.iter().format(", ")
             ));
             let builder = IB::default();
-            let index = Index::train(env, builder, &sources)?;
+            let index = Index::train_with(env, &mut prefetch, builder, &sources)?;
             indexes.insert(index_name, index);
         }
         Ok(Model {
             created_at,
+            tokenizer: env.config.tokenizer_settings().into(),
             baselines,
             indexes,
         })
     }
 
+    /// A digest of the model content, to identify the model used by a report.
+    pub fn hash(&self) -> String {
+        use sha2::{Digest, Sha256};
+        let mut hasher = Sha256::new();
+        hasher.update(self.trainer().as_bytes());
+        for baseline in &self.baselines {
+            hasher.update(format!("baseline={}\n", baseline).as_bytes());
+        }
+        for (index_name, index) in self.indexes.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+            hasher.update(
+                format!(
+                    "index={} lines={} bytes={} rows={}\n",
+                    index_name,
+                    index.line_count,
+                    index.byte_count,
+                    index.samples_count()
+                )
+                .as_bytes(),
+            );
+            for source in &index.sources {
+                hasher.update(format!("source={}\n", source).as_bytes());
+            }
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// The parameters that change the model content.
+    pub fn trainer(&self) -> String {
+        trainer_settings(&self.tokenizer)
+    }
+
+    /// The details to reproduce the reports created with this model.
+    pub fn provenance(&self) -> Provenance {
+        Provenance {
+            version: env!("CARGO_PKG_VERSION").into(),
+            model_hash: self.hash().into(),
+            model_created_at: self.created_at,
+            trainer: self.trainer().into(),
+            baseline_sources: self
+                .indexes
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, index)| index.sources.iter().cloned())
+                .collect(),
+            baseline_count: self.baselines.len(),
+        }
+    }
+
     /// Get the matching index for a given Source.
     pub fn get_index<'a>(&'a self, index_name: &IndexName) -> Option<&'a Index<IR>> {
         lookup_or_single(&self.indexes, index_name)
     }
 
+    /// Score a line as if it was read from the given source path, to debug the anomaly detection.
+    pub fn preview_line(
+        &self,
+        env: &Env,
+        path: &str,
+        line: &str,
+    ) -> Option<(IndexName, process::LinePreview)> {
+        let source = Source::from_pathbuf(path.into());
+        let index_name = indexname_from_source(env, &source);
+        let index = self.get_index(&index_name)?;
+        let line = if env.config.strip_ansi() {
+            let bytes = logjuicer_iterator::strip_ansi(line.to_string().into());
+            logjuicer_iterator::clone_bytes_to_string(&bytes).unwrap_or_else(|| line.into())
+        } else {
+            line.into()
+        };
+        let preview = process::preview_line(
+            &index.index,
+            &line,
+            env.config.is_logfmt(&source),
+            env.config.csv_format(&source).as_ref(),
+            env.config.tokenizer_rules(),
+            env.config.locale(),
+            &env.config.source_policy(&source),
+        );
+        Some((index_name, preview))
+    }
+
     /// Create an individual LogReport.
-    #[tracing::instrument(level = "debug", skip(env, self, index, skip_lines))]
+    #[tracing::instrument(level = "debug", skip(env, prefetch, self, index, skip_lines))]
+    #[allow(clippy::too_many_arguments)]
     pub fn report_source(
         &self,
         env: &Env,
+        prefetch: &mut Prefetch,
         index: &Index<IR>,
         index_name: &IndexName,
         counters: &mut LineCounters,
         skip_lines: &mut KnownLines,
-        source: &Source,
-    ) -> std::result::Result<Option<LogReport>, String> {
+        sources: &[Source],
+    ) -> std::result::Result<Option<LogReport>, SourceError> {
         let start_time = Instant::now();
         let mut anomalies = Vec::new();
-        match index.get_processor(env, source, skip_lines) {
+        let (source, rotated) = sources
+            .split_last()
+            .ok_or_else(|| SourceError::Read("Empty rotation set".into()))?;
+        match index.get_prefetched_processor(env, prefetch, sources, skip_lines) {
             Ok(mut processor) => {
                 for anomaly in processor.by_ref() {
                     match anomaly {
                         Ok(anomaly) => anomalies.push(anomaly),
-                        Err(err) => return Err(format!("{}", err)),
+                        Err(err) => return Err(err.into()),
                     }
                 }
                 counters.line_count += processor.line_count;
+                counters.byte_count += processor.byte_count;
+                if processor.truncated {
+                    counters.truncated.push(source.clone());
+                }
+                env.progress.bytes_downloaded(source, processor.byte_count);
+                let read_time = processor.read_time();
+                counters.source_stats.push(SourceStats {
+                    source: source.clone(),
+                    download_time: read_time,
+                    analysis_time: start_time.elapsed().saturating_sub(read_time),
+                    byte_count: processor.byte_count,
+                    line_count: processor.line_count,
+                    unique_line_count: processor.unique_line_count,
+                });
                 if !anomalies.is_empty() {
                     counters.anomaly_count += anomalies.len();
+                    env.progress.anomalies_found(source, anomalies.len());
 
                     Ok(Some(LogReport {
                         test_time: start_time.elapsed(),
+                        category: env.config.categorize(source, &anomalies),
                         anomalies,
                         source: source.clone(),
                         index_name: index_name.clone(),
                         line_count: processor.line_count,
                         byte_count: processor.byte_count,
+                        rotated: rotated.to_vec(),
                     }))
                 } else {
                     Ok(None)
                 }
             }
-            Err(err) => Err(format!("{}", err)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Check the model validity for the current environment.
+    fn model_warnings(&self, env: &Env) -> Vec<RunWarning> {
+        let mut warnings = Vec::new();
+        let tokenizer = env.config.tokenizer_settings();
+        if self.tokenizer.as_ref() != tokenizer {
+            warnings.push(RunWarning {
+                kind: WarningKind::TokenizerVersion,
+                message: format!(
+                    "the model was trained with '{}', but the current settings are '{}'",
+                    self.tokenizer, tokenizer
+                )
+                .into(),
+                source: None,
+            });
+        }
+        let now = chrono::Utc::now();
+        for baseline in &self.baselines {
+            let end_time = match baseline {
+                Content::Zuul(build) | Content::LocalZuulBuild(_, build) => build.end_time,
+                _ => continue,
+            };
+            let age = now.signed_duration_since(end_time).num_days();
+            if age > STALE_BASELINE_DAYS {
+                warnings.push(RunWarning {
+                    kind: WarningKind::StaleBaseline,
+                    message: format!("the baseline {} is {} days old", baseline, age).into(),
+                    source: None,
+                });
+            }
+        }
+        warnings
+    }
+
+    /// The report of an analysis that is not completed, without any results.
+    /// The log reports given to the [Model::report_with] sink can be added to preview the anomalies found so far.
+    pub fn pending_report(&self, env: &Env, target: Content) -> Report {
+        Report {
+            created_at: SystemTime::now(),
+            run_time: Duration::ZERO,
+            target,
+            baselines: self.baselines.clone(),
+            log_reports: Vec::new(),
+            index_reports: HashMap::new(),
+            unknown_files: HashMap::new(),
+            read_errors: Vec::new(),
+            total_line_count: 0,
+            total_anomaly_count: 0,
+            category_policies: env.config.category_policies(),
+            warnings: self.model_warnings(env),
+            merged_targets: Vec::new(),
+            source_stats: Vec::new(),
+            provenance: Some(self.provenance()),
+            summary: Vec::new(),
+            clusters: Vec::new(),
+            annotations: Vec::new(),
+            link_templates: env.config.link_templates(),
+            failed_tests: Vec::new(),
         }
     }
 
     /// Create the final report.
     #[tracing::instrument(level = "debug", skip(env, self))]
     pub fn report(&self, env: &Env, target: Content) -> Result<Report> {
-        let start_time = Instant::now();
-        let created_at = SystemTime::now();
-        let mut index_reports = HashMap::new();
         let mut log_reports = Vec::new();
-        let mut unknown_files = HashMap::new();
-        let mut read_errors = Vec::new();
+        let mut report = self.report_with(env, target, |lr| {
+            log_reports.push(lr);
+            Ok(())
+        })?;
+        report.set_log_reports(log_reports);
+        Ok(report)
+    }
+
+    /// Create the report without keeping the log reports in memory: they are given to the sink as soon as
+    /// they are created, e.g. to be written with a [logjuicer_report::stream::StreamWriter].
+    /// The returned report has no log reports, no summary, no clusters and no failed tests anomalies.
+    #[tracing::instrument(level = "debug", skip(env, self, sink))]
+    pub fn report_with(
+        &self,
+        env: &Env,
+        target: Content,
+        mut sink: impl FnMut(LogReport) -> Result<()>,
+    ) -> Result<Report> {
+        let start_time = Instant::now();
+        let mut report = self.pending_report(env, target);
         let mut counters = LineCounters::new();
-        for (index_name, sources) in group_sources(env, &[target.clone()])?.drain() {
+        // The reason why the analysis stopped, and the number of sources that were not analyzed.
+        let mut limit = None;
+        let mut skipped_count = 0;
+        let (groups, results_sources) = group_target_sources(env, &report.target)?;
+        let groups = groups.into_iter().collect::<Vec<_>>();
+        // Download the sources in the order they are reported.
+        let sources = groups
+            .iter()
+            .filter(|(index_name, _)| self.get_index(index_name).is_some())
+            .flat_map(|(_, sources)| rotation::group(sources.clone()))
+            .collect::<Vec<_>>();
+        // The rotated files are analyzed with their current file.
+        env.progress.files_discovered(sources.len());
+        let mut prefetch = Prefetch::new(env, &sources.into_iter().flatten().collect::<Vec<_>>());
+        for (index_name, sources) in groups {
             let mut skip_lines = KnownLines::new();
             match self.get_index(&index_name) {
                 Some(index) => {
@@ -397,26 +1060,50 @@ impl<IR: IndexReader> Model<IR> {
                         index_name,
                         sources.iter().take(5).format(", ")
                     ));
-                    for source in sources {
+                    // The rotated files are reported with their current file.
+                    for sources in rotation::group(sources) {
+                        env.cancel.check()?;
+                        if limit.is_none() {
+                            limit = counters.limit_reached(env.config.limits(), start_time);
+                        }
+                        if limit.is_some() {
+                            skipped_count += 1;
+                            continue;
+                        }
+                        counters.source_count += 1;
+                        let source = &sources[sources.len() - 1];
                         match self.report_source(
                             env,
+                            &mut prefetch,
                             index,
                             &index_name,
                             &mut counters,
                             &mut skip_lines,
-                            &source,
+                            &sources,
                         ) {
-                            Ok(Some(lr)) => {
-                                if !index_reports.contains_key(&index_name) {
-                                    index_reports.insert(index_name.clone(), index.to_report());
+                            Ok(Some(mut lr)) => {
+                                if !report.index_reports.contains_key(&index_name) {
+                                    report
+                                        .index_reports
+                                        .insert(index_name.clone(), index.to_report());
                                 };
-                                log_reports.push(lr)
+                                env.config.redactor().apply_log_report(&mut lr);
+                                sink(lr)?
                             }
                             Ok(None) => {}
-                            Err(err) => {
-                                read_errors.push((source.clone(), err.into()));
+                            // The invalid utf-8 errors are reported as a warning.
+                            Err(SourceError::Encoding(err)) => {
+                                report.warnings.push(RunWarning {
+                                    kind: WarningKind::Encoding,
+                                    message: err.into(),
+                                    source: Some(source.clone()),
+                                });
+                            }
+                            Err(SourceError::Read(err)) => {
+                                report.read_errors.push((source.clone(), err.into()));
                             }
                         }
+                        env.progress.file_analyzed(source);
                     }
                     tracing::debug!(skip_lines = skip_lines.len(), "reported one source");
                 }
@@ -426,22 +1113,38 @@ impl<IR: IndexReader> Model<IR> {
                         index_name,
                         sources.len()
                     ));
-                    let _ = unknown_files.insert(index_name, sources);
+                    let _ = report.unknown_files.insert(index_name, sources);
                 }
             }
         }
-        Ok(Report {
-            created_at,
-            run_time: start_time.elapsed(),
-            target,
-            baselines: self.baselines.clone(),
-            log_reports: LogReport::sorted(log_reports),
-            index_reports,
-            unknown_files,
-            read_errors,
-            total_line_count: counters.line_count,
-            total_anomaly_count: counters.anomaly_count,
-        })
+        // The last source may have been interrupted.
+        env.cancel.check()?;
+        if let Some(limit) = limit {
+            report.warnings.push(RunWarning {
+                kind: WarningKind::LimitReached,
+                message: format!("{}, {} sources were not analyzed", limit, skipped_count).into(),
+                source: None,
+            });
+        }
+        for source in std::mem::take(&mut counters.truncated) {
+            report.warnings.push(RunWarning {
+                kind: WarningKind::SkippedFile,
+                message: format!(
+                    "the file exceeds the limit of {} bytes, the rest was not analyzed",
+                    env.config.limits().max_file_bytes.unwrap_or_default()
+                )
+                .into(),
+                source: Some(source),
+            });
+        }
+        report.run_time = start_time.elapsed();
+        report.total_line_count = counters.line_count;
+        report.total_anomaly_count = counters.anomaly_count;
+        report.source_stats = counters.source_stats;
+        report.failed_tests = test_results::read_failed_tests(env, &results_sources);
+        // The log reports are redacted before the sink, the other messages are redacted here.
+        env.config.redactor().apply_messages(&mut report);
+        Ok(report)
     }
 }
 
@@ -481,15 +1184,23 @@ impl<IR: IndexReader + Serialize + serde::de::DeserializeOwned> Model<IR> {
     }
 
     pub fn check(path: &Path) -> Result<SystemTime> {
-        let mut input =
-            flate2::read::GzDecoder::new(std::fs::File::open(path).context("Can't open file")?);
+        Model::<IR>::check_reader(std::fs::File::open(path).context("Can't open file")?)
+    }
+
+    /// Validate the model header and return its creation time.
+    pub fn check_reader<R: Read>(read: R) -> Result<SystemTime> {
+        let mut input = flate2::read::GzDecoder::new(read);
         Model::<IR>::validate(&mut input)
     }
 
     pub fn load(path: &Path) -> Result<Model<IR>> {
         tracing::info!(path = path.to_str(), "Loading provided model");
-        let mut input =
-            flate2::read::GzDecoder::new(std::fs::File::open(path).context("Can't open file")?);
+        Model::<IR>::load_reader(std::fs::File::open(path).context("Can't open file")?)
+    }
+
+    /// Load a model from a reader, for example when it is downloaded.
+    pub fn load_reader<R: Read>(read: R) -> Result<Model<IR>> {
+        let mut input = flate2::read::GzDecoder::new(read);
         Model::<IR>::validate(&mut input)?;
         bincode::deserialize_from(input).context("Can't load model")
     }
@@ -527,11 +1238,92 @@ fn lookup_or_single<'a, K: Eq + std::hash::Hash, V>(hm: &'a HashMap<K, V>, k: &K
 fn test_save_load() {
     let model: Model<logjuicer_index::FeaturesMatrix> = Model {
         created_at: SystemTime::now(),
+        tokenizer: "".into(),
         baselines: Vec::new(),
         indexes: HashMap::new(),
     };
     let dir = tempfile::tempdir().expect("tmpdir");
     let model_path = dir.path().join("model.bin");
     model.save(&model_path).expect("save");
-    Model::<logjuicer_index::FeaturesMatrix>::load(&model_path).expect("load");
+    let loaded = Model::<logjuicer_index::FeaturesMatrix>::load(&model_path).expect("load");
+    assert_eq!(loaded.hash(), model.hash());
+}
+
+#[test]
+fn test_baseline_pins() {
+    let env = Env {
+        config: crate::config::config_from_yaml(
+            "
+baseline_pins:
+  - job: ^flaky
+    baseline: skip
+  - job: .
+    baseline: s3://logs/golden/
+",
+        ),
+        ..Env::new()
+    };
+    let baselines =
+        content_discover_baselines(&Content::Zuul(Box::new(ZuulBuild::sample("test"))), &env)
+            .unwrap();
+    assert_eq!(baselines.len(), 1);
+    assert!(
+        matches!(&baselines[0], Content::Directory(Source::Remote(_, url)) if url.as_str() == "s3://logs/golden/")
+    );
+    assert!(
+        content_discover_baselines(&Content::Zuul(Box::new(ZuulBuild::sample("flaky"))), &env)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_report_limits() -> Result<()> {
+    use logjuicer_index::FeaturesMatrixBuilder;
+
+    let logs = TestLogs::new()?;
+    let files = |line| [("a.log", line), ("b.log", line), ("c.log", line)];
+    let baseline = logs.content("baseline", &files("first line\nsecond line\n"))?;
+    let target = logs.content("target", &files("first line\nkernel panic\n"))?;
+    let env = Env {
+        config: crate::config::config_from_yaml("limits: {max_sources: 2, max_file_bytes: 15}"),
+        ..Env::new()
+    };
+    let model = Model::<FeaturesMatrix>::train::<FeaturesMatrixBuilder>(&env, vec![baseline])?;
+    let report = model.report(&env, target)?;
+    let warnings = report
+        .warnings
+        .iter()
+        .map(|warning| (warning.kind, warning.message.as_ref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        warnings[0],
+        (
+            WarningKind::LimitReached,
+            "the limit of 2 sources is reached, 1 sources were not analyzed"
+        )
+    );
+    // The analyzed files are truncated before their anomaly.
+    assert_eq!(warnings.len(), 3);
+    assert_eq!(warnings[1].0, WarningKind::SkippedFile);
+    assert_eq!(report.total_anomaly_count, 0);
+    Ok(())
+}
+
+#[test]
+fn test_report_encoding_warning() -> Result<()> {
+    use logjuicer_index::FeaturesMatrixBuilder;
+
+    let logs = TestLogs::new()?;
+    let baseline = logs.content("baseline", &[("a.log", "first line\nsecond line\n")])?;
+    let target = logs.content("target", &[("a.log", b"first line\nkernel \xff panic\n")])?;
+    let env = Env::new();
+    let model = Model::<FeaturesMatrix>::train::<FeaturesMatrixBuilder>(&env, vec![baseline])?;
+    let report = model.report(&env, target)?;
+    let warnings = report
+        .warnings
+        .iter()
+        .map(|warning| warning.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(warnings, vec![WarningKind::Encoding]);
+    Ok(())
 }
@@ -0,0 +1,132 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the Open Build Service packages, where the sources are the build log of each target.
+//!
+//! The failed repositories and architectures are analyzed, and the baseline is the log of their last succeeded build.
+
+use anyhow::Result;
+use obs_build::Client;
+use url::Url;
+
+use crate::env::Env;
+use crate::{Baselines, Content, Source};
+use logjuicer_report::{ObsBuild, ObsTarget};
+
+/// Check if the url is an obs package page.
+pub fn content_from_obs_url(env: &Env, url: &Url) -> Option<Result<Content>> {
+    let package_url = obs_build::parse_package_url(url)?;
+    Some(get_content(env, package_url))
+}
+
+fn get_content(env: &Env, package_url: obs_build::PackageUrl) -> Result<Content> {
+    let targets = match package_url.target {
+        Some((repository, arch)) => vec![ObsTarget { repository, arch }],
+        None => {
+            let client = Client::new(package_url.api_url.clone());
+            let results =
+                client.get_results(&env.client, &package_url.project, &package_url.package)?;
+            // Use the failed targets, or all the built targets when none failed.
+            let failed = results.iter().any(|result| result.code == "failed");
+            results
+                .into_iter()
+                .filter(|result| result.code == "failed" || (!failed && result.code == "succeeded"))
+                .map(|result| ObsTarget {
+                    repository: result.repository.into(),
+                    arch: result.arch.into(),
+                })
+                .collect()
+        }
+    };
+    if targets.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No build found for {}/{}",
+            package_url.project,
+            package_url.package
+        ));
+    }
+    Ok(Content::Obs(Box::new(ObsBuild {
+        api_url: package_url.api_url,
+        project: package_url.project,
+        package: package_url.package,
+        targets,
+        last_succeeded: false,
+    })))
+}
+
+/// Use the last succeeded build of the same targets.
+pub fn discover_baselines(build: &ObsBuild) -> Result<Baselines> {
+    tracing::info!("Discovering baselines for {}", build);
+    if build.last_succeeded {
+        return Ok(vec![]);
+    }
+    Ok(vec![Content::Obs(Box::new(ObsBuild {
+        last_succeeded: true,
+        ..build.clone()
+    }))])
+}
+
+pub fn sources_iter(build: &ObsBuild) -> Box<dyn Iterator<Item = Result<Source>>> {
+    let sources = build
+        .targets
+        .iter()
+        .map(|target| Ok(build.log_source(target)?))
+        .collect::<Vec<_>>();
+    Box::new(sources.into_iter())
+}
+
+#[test]
+fn test_obs_build() -> Result<()> {
+    let env = Env::new();
+    let mut server = mockito::Server::new();
+    let results_mock = server
+        .mock("GET", "/public/build/home:user/_result")
+        .match_query(mockito::Matcher::Any)
+        .with_body(
+            r#"<resultlist state="c0ffee">
+  <result project="home:user" repository="openSUSE_Tumbleweed" arch="x86_64" code="published" state="published">
+    <status package="bash" code="failed"/>
+  </result>
+  <result project="home:user" repository="openSUSE_Tumbleweed" arch="aarch64" code="published" state="published">
+    <status package="bash" code="succeeded"/>
+  </result>
+</resultlist>"#,
+        )
+        .expect(1)
+        .create();
+    let package_url = obs_build::PackageUrl {
+        api_url: Url::parse(&format!("{}/", server.url()))?,
+        project: "home:user".into(),
+        package: "bash".into(),
+        target: None,
+    };
+    let build = match get_content(&env, package_url)? {
+        Content::Obs(build) => build,
+        _ => panic!("Expected an obs build"),
+    };
+    assert_eq!(build.targets.len(), 1);
+    let sources = sources_iter(&build).collect::<Result<Vec<_>>>()?;
+    assert_eq!(
+        sources[0].get_relative(),
+        "openSUSE_Tumbleweed/x86_64/build.log"
+    );
+
+    let baselines = discover_baselines(&build)?;
+    assert_eq!(
+        baselines[0].to_string(),
+        "Obs(home:user/bash (last succeeded))"
+    );
+    let baseline = match &baselines[0] {
+        Content::Obs(baseline) => baseline,
+        _ => panic!("Expected an obs build"),
+    };
+    assert_eq!(
+        sources_iter(baseline).next().unwrap()?.as_str(),
+        format!(
+            "{}/public/build/home:user/openSUSE_Tumbleweed/x86_64/bash/_log?lastsucceeded=1&name=openSUSE_Tumbleweed/x86_64/build.log",
+            server.url()
+        )
+    );
+    results_mock.assert();
+    Ok(())
+}
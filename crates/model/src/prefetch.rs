@@ -0,0 +1,159 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides a download pool, to fetch the next remote sources while the current one is analyzed.
+//!
+//! The sources are downloaded in the order they are read, and at most `limits.downloads` sources
+//! are fetched or kept in memory ahead of the analysis.
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver};
+use threadpool::ThreadPool;
+use url::Url;
+
+use crate::env::{Env, RetryPolicy};
+use crate::reader::DecompressReader;
+use crate::Source;
+use logjuicer_cache::Validators;
+
+/// A download started by the pool.
+struct Download {
+    prefix: usize,
+    url: Url,
+    rx: Receiver<Result<(Vec<u8>, Validators)>>,
+}
+
+/// The downloads of the sources, in their reading order.
+#[derive(Default)]
+pub struct Prefetch {
+    // The pool, unset when there is nothing to download.
+    pool: Option<ThreadPool>,
+    retry: RetryPolicy,
+    // The sources to download, with their client.
+    queue: VecDeque<(usize, Url, ureq::Agent)>,
+    // The started downloads.
+    pending: VecDeque<Download>,
+    window: usize,
+}
+
+impl Prefetch {
+    /// Start downloading the remote sources that are not already cached.
+    pub fn new(env: &Env, sources: &[Source]) -> Prefetch {
+        let queue = sources
+            .iter()
+            .filter_map(|source| match source {
+                Source::Remote(prefix, url)
+                    if crate::is_download_url(url)
+                        && !crate::reader::is_cached(env, *prefix, url) =>
+                {
+                    Some((*prefix, url.clone(), env.client_for(url).clone()))
+                }
+                _ => None,
+            })
+            .collect::<VecDeque<_>>();
+        if queue.is_empty() {
+            return Prefetch::default();
+        }
+        let window = env.config.downloads();
+        let mut prefetch = Prefetch {
+            pool: Some(ThreadPool::new(window.min(queue.len()))),
+            retry: env.retry(),
+            queue,
+            pending: VecDeque::new(),
+            window,
+        };
+        prefetch.fill();
+        prefetch
+    }
+
+    /// Start the next downloads, until the window is full.
+    fn fill(&mut self) {
+        if let Some(pool) = &self.pool {
+            while self.pending.len() < self.window {
+                let (prefix, url, client) = match self.queue.pop_front() {
+                    Some(next) => next,
+                    None => break,
+                };
+                let (tx, rx) = channel();
+                let download_url = url.clone();
+                let retry = self.retry;
+                pool.execute(move || {
+                    tracing::debug!(url = download_url.as_str(), "Downloading url");
+                    // The result is ignored when the source is no longer needed.
+                    let _ = tx.send(crate::reader::download(&client, &retry, &download_url));
+                });
+                self.pending.push_back(Download { prefix, url, rx });
+            }
+        }
+    }
+
+    /// Read a remote url, waiting for its download when it was started by the pool.
+    pub fn open(&mut self, env: &Env, prefix: usize, url: &Url) -> Result<DecompressReader> {
+        env.cancel.check()?;
+        let download = match self
+            .pending
+            .iter()
+            .position(|download| download.prefix == prefix && &download.url == url)
+        {
+            // The previous downloads are dropped, as their sources were skipped.
+            Some(pos) => self.pending.drain(..=pos).next_back(),
+            None => None,
+        };
+        self.fill();
+        match download {
+            Some(download) => {
+                let body = download
+                    .rx
+                    .recv()
+                    .map_err(|_| anyhow::anyhow!("{}: download process panic", url))??;
+                crate::reader::from_body(env, prefix, url, body)
+            }
+            None => crate::urls::url_open(env, prefix, url),
+        }
+    }
+}
+
+#[test]
+fn test_prefetch() -> Result<()> {
+    use std::io::Read;
+
+    let env = Env::new();
+    let mut server = mockito::Server::new();
+    let mocks = ["a.log", "b.log", "c.log"]
+        .iter()
+        .map(|name| {
+            server
+                .mock("GET", format!("/logs/{}", name).as_str())
+                .with_body(format!("{} content\n", name))
+                .create()
+        })
+        .collect::<Vec<_>>();
+    let sources = ["a.log", "b.log", "c.log"]
+        .iter()
+        .map(|name| {
+            let url = Url::parse(&format!("{}/logs/{}", server.url(), name))?;
+            Ok(Source::Remote(url.as_str().len() - name.len(), url))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut prefetch = Prefetch::new(&env, &sources);
+    assert_eq!(prefetch.pending.len(), 3);
+    let read = |prefetch: &mut Prefetch, source: &Source| -> Result<String> {
+        let mut content = String::new();
+        crate::source_open_with(&env, prefetch, source)?.read_to_string(&mut content)?;
+        Ok(content)
+    };
+    // The skipped sources are dropped.
+    assert_eq!(read(&mut prefetch, &sources[1])?, "b.log content\n");
+    assert_eq!(prefetch.pending.len(), 1);
+    assert_eq!(read(&mut prefetch, &sources[2])?, "c.log content\n");
+    assert!(prefetch.pending.is_empty());
+    mocks[1].assert();
+    mocks[2].assert();
+
+    // The local sources are not downloaded.
+    let prefetch = Prefetch::new(&env, &[Source::from_pathbuf("/tmp/a.log".into())]);
+    assert!(prefetch.pool.is_none());
+    Ok(())
+}
@@ -7,25 +7,44 @@ use anyhow::Result;
 use std::collections::VecDeque;
 use std::io::Read;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
+use crate::env::CancelToken;
 use crate::unordered::KnownLines;
 use logjuicer_index::traits::*;
 use logjuicer_iterator::LogLine;
 use logjuicer_report::{Anomaly, AnomalyContext};
-
-const THRESHOLD: logjuicer_index::F = 0.3;
-const CTX_DISTANCE: usize = 3;
+use logjuicer_tokenizer::csv::Format as CsvFormat;
+use logjuicer_tokenizer::rules::Rules;
+use serde::Serialize;
+
+pub(crate) const THRESHOLD: logjuicer_index::F = 0.3;
+pub(crate) const CTX_DISTANCE: usize = 3;
+/// The maximum after context of an anomaly followed by a stack trace.
+const TRACE_DISTANCE: usize = 64;
 const CHUNK_SIZE: usize = 512;
 
 /// Helper struct to manage indexing multiples readers.
 pub struct IndexTrainer<IB: IndexBuilder> {
     builder: IB,
     is_json: bool,
+    is_logfmt: bool,
+    csv: Option<CsvFormat>,
+    rules: Rules,
+    strip_ansi: bool,
+    normalize_locale: bool,
+    max_line_length: usize,
     skip_lines: KnownLines,
+    cancel: CancelToken,
     pub line_count: usize,
     pub byte_count: usize,
+    /// The first indexed lines, to inspect the model.
+    pub samples: Vec<Box<str>>,
 }
 
+/// The number of indexed lines kept as samples.
+pub const SAMPLE_LINES: usize = 10;
+
 impl<IB> IndexTrainer<IB>
 where
     IB: IndexBuilder,
@@ -34,9 +53,17 @@ where
         Self {
             builder,
             is_json,
+            is_logfmt: false,
+            csv: None,
+            rules: Rules::default(),
+            strip_ansi: true,
+            normalize_locale: false,
+            max_line_length: logjuicer_iterator::DEFAULT_MAX_LINE_LENGTH,
             skip_lines: KnownLines::new(),
+            cancel: CancelToken::default(),
             line_count: 0,
             byte_count: 0,
+            samples: Vec::new(),
         }
     }
 
@@ -47,18 +74,75 @@ where
         Ok(trainer.build())
     }
 
+    /// Parse the lines as logfmt.
+    pub fn with_logfmt(mut self, is_logfmt: bool) -> Self {
+        self.is_logfmt = is_logfmt;
+        self
+    }
+
+    /// Parse the lines as CSV.
+    pub fn with_csv(mut self, csv: Option<CsvFormat>) -> Self {
+        self.csv = csv;
+        self
+    }
+
+    /// Apply the user-defined tokenizer rules.
+    pub fn with_rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Remove the ANSI escape sequences.
+    pub fn with_strip_ansi(mut self, strip_ansi: bool) -> Self {
+        self.strip_ansi = strip_ansi;
+        self
+    }
+
+    /// Apply the locale policy.
+    pub fn with_locale(mut self, locale: &crate::config::Locale) -> Self {
+        self.normalize_locale = locale.normalize;
+        self
+    }
+
+    /// Truncate the overlong lines.
+    pub fn with_max_line_length(mut self, max_line_length: usize) -> Self {
+        self.max_line_length = max_line_length;
+        self
+    }
+
+    /// Stop the training when the token is cancelled.
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
     #[tracing::instrument(level = "debug", name = "Trainer::add", skip_all)]
     pub fn add<R: Read>(&mut self, read: R) -> Result<()> {
-        for line in logjuicer_iterator::BytesLines::new(read, self.is_json) {
+        for line in logjuicer_iterator::BytesLines::new(read, self.is_json)
+            .with_strip_ansi(self.strip_ansi)
+            .with_max_line_length(self.max_line_length)
+        {
             let line = line?;
             let raw_str = std::str::from_utf8(&line.0[..])
                 .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
             self.line_count += 1;
             self.byte_count += line.0.len();
-            let tokens = logjuicer_tokenizer::process(raw_str);
+            if self.line_count.is_multiple_of(CHUNK_SIZE) {
+                self.cancel.check()?;
+            }
+            let (tokens, _) = tokenize(
+                raw_str,
+                self.is_logfmt,
+                self.csv.as_ref(),
+                &self.rules,
+                self.normalize_locale,
+            );
 
             if self.skip_lines.insert(&tokens) {
                 self.builder.add(&tokens);
+                if self.samples.len() < SAMPLE_LINES {
+                    self.samples.push(raw_str.into());
+                }
             }
         }
         tracing::debug!(skip_lines = self.skip_lines.len(), "added one source");
@@ -70,11 +154,26 @@ where
     }
 }
 
+/// A reader that measures the time spent reading, e.g. to download a remote source.
+pub struct TimedReader<R: Read> {
+    inner: R,
+    elapsed: Duration,
+}
+
+impl<R: Read> Read for TimedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let start = Instant::now();
+        let result = self.inner.read(buf);
+        self.elapsed += start.elapsed();
+        result
+    }
+}
+
 /// Helper struct to manage the log lines and the unique tokenized lines.
 /// The goal is to perform the index search on unique lines, while keeping a
 /// buffer of the raw line to manage the surrounding context.
 pub struct ChunkProcessor<'a, IR: IndexReader, R: Read> {
-    reader: logjuicer_iterator::BytesLines<R>,
+    reader: logjuicer_iterator::BytesLines<TimedReader<R>>,
     index: &'a IR,
     /// The raw log line with their global position
     buffer: Vec<(logjuicer_iterator::LogLine, usize)>,
@@ -96,8 +195,31 @@ pub struct ChunkProcessor<'a, IR: IndexReader, R: Read> {
     pub line_count: usize,
     /// Total bytes count
     pub byte_count: usize,
+    /// The count of lines that were not already seen
+    pub unique_line_count: usize,
     /// Indicate if run-logjuicer needs to be checked
     is_job_output: bool,
+    /// The minimum distance of an anomaly
+    threshold: logjuicer_index::F,
+    /// The number of context lines around an anomaly
+    ctx_distance: usize,
+    /// Indicate if the lines are parsed as logfmt
+    is_logfmt: bool,
+    csv: Option<CsvFormat>,
+    rules: Rules,
+    /// The localized lines policy
+    locale: crate::config::Locale,
+    /// Indicate if the targets are localized
+    targets_localized: Vec<bool>,
+    /// The stack traces state
+    traces: logjuicer_tokenizer::stacktrace::Tracker,
+    /// Indicate if the buffer lines continue a stack trace
+    buffer_in_trace: Vec<bool>,
+    cancel: CancelToken,
+    /// The maximum number of bytes to read
+    max_bytes: Option<usize>,
+    /// Indicate if the source was not read until its end
+    pub truncated: bool,
 }
 
 impl<'a, IR: IndexReader, R: Read> Iterator for ChunkProcessor<'a, IR, R> {
@@ -125,7 +247,13 @@ impl<'a, IR: IndexReader, R: Read> ChunkProcessor<'a, IR, R> {
         skip_lines: &'a mut KnownLines,
     ) -> ChunkProcessor<'a, IR, R> {
         ChunkProcessor {
-            reader: logjuicer_iterator::BytesLines::new(read, is_json),
+            reader: logjuicer_iterator::BytesLines::new(
+                TimedReader {
+                    inner: read,
+                    elapsed: Duration::ZERO,
+                },
+                is_json,
+            ),
             index,
             is_job_output,
             buffer: Vec::new(),
@@ -138,17 +266,104 @@ impl<'a, IR: IndexReader, R: Read> ChunkProcessor<'a, IR, R> {
             coord: 0,
             line_count: 0,
             byte_count: 0,
+            unique_line_count: 0,
+            threshold: THRESHOLD,
+            ctx_distance: CTX_DISTANCE,
+            is_logfmt: false,
+            csv: None,
+            rules: Rules::default(),
+            locale: crate::config::Locale::default(),
+            targets_localized: Vec::with_capacity(CHUNK_SIZE),
+            traces: Default::default(),
+            buffer_in_trace: Vec::new(),
+            cancel: CancelToken::default(),
+            max_bytes: None,
+            truncated: false,
         }
     }
 
+    /// Apply a category policy to the processor.
+    pub fn with_policy(mut self, policy: &crate::category::Policy) -> Self {
+        self.threshold = policy.threshold;
+        self.ctx_distance = policy.context;
+        self
+    }
+
+    /// Parse the lines as logfmt, using the message field as the displayed line.
+    pub fn with_logfmt(mut self, is_logfmt: bool) -> Self {
+        self.is_logfmt = is_logfmt;
+        self
+    }
+
+    /// Parse the lines as CSV, using the message column as the displayed line.
+    pub fn with_csv(mut self, csv: Option<CsvFormat>) -> Self {
+        self.csv = csv;
+        self
+    }
+
+    /// Apply the user-defined tokenizer rules, the displayed line is not modified.
+    pub fn with_rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Apply the locale policy to normalize and down-weight the localized lines.
+    pub fn with_locale(mut self, locale: &crate::config::Locale) -> Self {
+        self.locale = *locale;
+        self
+    }
+
+    /// Remove the ANSI escape sequences before the tokenization and the context collection.
+    pub fn with_strip_ansi(mut self, strip_ansi: bool) -> Self {
+        self.reader = self.reader.with_strip_ansi(strip_ansi);
+        self
+    }
+
+    /// Truncate the overlong lines.
+    pub fn with_max_line_length(mut self, max_line_length: usize) -> Self {
+        self.reader = self.reader.with_max_line_length(max_line_length);
+        self
+    }
+
+    /// Stop the processing when the token is cancelled.
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Stop the processing after the maximum number of bytes.
+    pub fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// The time spent reading the source.
+    pub fn read_time(&self) -> Duration {
+        self.reader.get_ref().elapsed
+    }
+
     fn read_anomalies(&mut self) -> Result<()> {
+        if self.truncated {
+            // The last chunk was already processed.
+            return Ok(());
+        }
         while let Some(line) = self.reader.next() {
             let line = line?;
+            if self
+                .max_bytes
+                .is_some_and(|max_bytes| self.byte_count + line.0.len() > max_bytes)
+            {
+                self.truncated = true;
+                break;
+            }
             let raw_str = std::str::from_utf8(&line.0[..])
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
             self.line_count += 1;
             self.byte_count += line.0.len();
             self.coord += 1;
+            if self.line_count.is_multiple_of(CHUNK_SIZE) {
+                self.cancel.check()?;
+            }
 
             // Special check to break when we are processing ourself
             if self.is_job_output && raw_str.contains("TASK [run-logjuicer") {
@@ -156,14 +371,32 @@ impl<'a, IR: IndexReader, R: Read> ChunkProcessor<'a, IR, R> {
             }
 
             // Call the static method of the ChunkIndex trait
-            let tokens = logjuicer_tokenizer::process(raw_str);
+            let (tokens, message) = tokenize(
+                raw_str,
+                self.is_logfmt,
+                self.csv.as_ref(),
+                &self.rules,
+                self.locale.normalize,
+            );
+            let is_localized =
+                self.locale.weight.is_some() && logjuicer_tokenizer::locale::is_localized(raw_str);
+            let in_trace = self
+                .traces
+                .is_continuation(logjuicer_tokenizer::syslog::strip_header(raw_str));
+            let line = match message.map(|msg| line.0.slice_ref(msg.as_bytes())) {
+                Some(message) => (message, line.1),
+                None => line,
+            };
 
             // Keep in the buffer all the lines until we get CHUNK_SIZE unique lines
             self.buffer.push((line, self.coord));
+            self.buffer_in_trace.push(in_trace);
 
             if self.skip_lines.insert(&tokens) {
+                self.unique_line_count += 1;
                 self.targets.push(tokens);
                 self.targets_coord.push(self.coord);
+                self.targets_localized.push(is_localized);
 
                 if self.targets.len() == CHUNK_SIZE {
                     self.do_search_anomalies();
@@ -194,13 +427,23 @@ impl<'a, IR: IndexReader, R: Read> ChunkProcessor<'a, IR, R> {
 
     /// Helper function for the anomalies_from_reader implementation.
     fn do_search_anomalies(&mut self) {
-        let distances = self.index.distance(&self.targets);
+        let mut distances = self.index.distance(&self.targets);
+        if let Some(weight) = self.locale.weight {
+            // Reduce the distance of the lines that may only differ because of the locale.
+            for (distance, _) in distances
+                .iter_mut()
+                .zip(self.targets_localized.iter())
+                .filter(|(_, is_localized)| **is_localized)
+            {
+                *distance *= weight;
+            }
+        }
 
         let mut buffer_pos = 0;
         let mut last_context_pos = 0;
 
         for (distance, coord) in distances.iter().zip(self.targets_coord.iter()) {
-            let is_anomaly = distance > &THRESHOLD;
+            let is_anomaly = distance > &self.threshold;
 
             // The distances and coords are out of sync with the buffer, because they only contains unique line.
             // Thus for each distance, we need to find the matching raw lines in the buffer.
@@ -219,7 +462,8 @@ impl<'a, IR: IndexReader, R: Read> ChunkProcessor<'a, IR, R> {
                     // In that case, we add the log line to the after context.
                     let raw_str = logjuicer_iterator::clone_bytes_to_string(bytes).unwrap();
                     anomaly.after.push(raw_str);
-                    if anomaly.after.len() >= CTX_DISTANCE {
+                    let in_trace = self.buffer_in_trace[buffer_pos - 1];
+                    if is_completed(anomaly, self.ctx_distance, in_trace) {
                         // The current anomaly is completed. TODO: try using std::mem::replace
                         self.anomalies.push_back(anomaly.clone());
                         self.current_anomaly = None;
@@ -233,6 +477,16 @@ impl<'a, IR: IndexReader, R: Read> ChunkProcessor<'a, IR, R> {
             }
 
             if let Some((log_line, log_pos)) = target_str {
+                if let Some(anomaly) = &mut self.current_anomaly {
+                    if self.buffer_in_trace[buffer_pos - 1] && anomaly.after.len() < TRACE_DISTANCE
+                    {
+                        // The line is part of the current anomaly stack trace.
+                        anomaly.after.push(log_line);
+                        anomaly.anomaly.distance = anomaly.anomaly.distance.max(*distance);
+                        last_context_pos = buffer_pos;
+                        continue;
+                    }
+                }
                 if let Some(anomaly) = &self.current_anomaly {
                     // We can push the current anomaly because any needed after context would overlap with the current anomaly.
                     self.anomalies.push_back(anomaly.clone());
@@ -241,6 +495,7 @@ impl<'a, IR: IndexReader, R: Read> ChunkProcessor<'a, IR, R> {
 
                 // Grab before context
                 let before = collect_before(
+                    self.ctx_distance,
                     buffer_pos - 1,
                     last_context_pos,
                     &self.buffer,
@@ -257,6 +512,7 @@ impl<'a, IR: IndexReader, R: Read> ChunkProcessor<'a, IR, R> {
                         pos: *log_pos,
                         line: log_line,
                     },
+                    targets: Vec::new(),
                 });
             } else if is_anomaly {
                 panic!(
@@ -269,10 +525,13 @@ impl<'a, IR: IndexReader, R: Read> ChunkProcessor<'a, IR, R> {
         // Handle the last anomaly after context
         if let Some(anomaly) = &mut self.current_anomaly {
             if last_context_pos < self.buffer.len() {
-                for ((bytes, _), _) in &self.buffer[last_context_pos..] {
+                for (((bytes, _), _), in_trace) in self.buffer[last_context_pos..]
+                    .iter()
+                    .zip(&self.buffer_in_trace[last_context_pos..])
+                {
                     let raw_str = logjuicer_iterator::clone_bytes_to_string(bytes).unwrap();
                     anomaly.after.push(raw_str);
-                    if anomaly.after.len() >= CTX_DISTANCE {
+                    if is_completed(anomaly, self.ctx_distance, *in_trace) {
                         // The current anomaly is completed. TODO: try using std::mem::replace
                         self.anomalies.push_back(anomaly.clone());
                         self.current_anomaly = None;
@@ -287,12 +546,13 @@ impl<'a, IR: IndexReader, R: Read> ChunkProcessor<'a, IR, R> {
     fn reset(&mut self, left_overs_pos: usize) {
         self.targets.clear();
         self.targets_coord.clear();
+        self.targets_localized.clear();
 
         // Keep the buffer left over as potential prev context for the next anomaly.
-        let min_left_overs_pos = if self.buffer.len() < CTX_DISTANCE {
+        let min_left_overs_pos = if self.buffer.len() < self.ctx_distance {
             0
         } else {
-            self.buffer.len() - CTX_DISTANCE
+            self.buffer.len() - self.ctx_distance
         };
         let max_left_overs_pos = left_overs_pos.max(min_left_overs_pos);
         self.left_overs = self.buffer[max_left_overs_pos..]
@@ -301,24 +561,107 @@ impl<'a, IR: IndexReader, R: Read> ChunkProcessor<'a, IR, R> {
             .map(|((bytes, _), _)| logjuicer_iterator::clone_bytes_to_string(bytes).unwrap())
             .collect();
         self.buffer.clear();
+        self.buffer_in_trace.clear();
+    }
+}
+
+/// Check if the after context of an anomaly is completed, the stack traces are kept together.
+fn is_completed(anomaly: &AnomalyContext, ctx_distance: usize, in_trace: bool) -> bool {
+    if in_trace && anomaly.after.len() < TRACE_DISTANCE {
+        false
+    } else {
+        anomaly.after.len() >= ctx_distance
+    }
+}
+
+/// Tokenize a line, returning the logfmt or CSV message to be displayed when available.
+/// The syslog headers are removed so that the lines of different hosts are comparable.
+fn tokenize<'a>(
+    raw_str: &'a str,
+    is_logfmt: bool,
+    csv: Option<&CsvFormat>,
+    rules: &Rules,
+    normalize_locale: bool,
+) -> (String, Option<&'a str>) {
+    use logjuicer_tokenizer::{locale, logfmt};
+    let raw_str = logjuicer_tokenizer::syslog::strip_header(raw_str);
+    let fields = if is_logfmt {
+        logfmt::parse(raw_str)
+    } else {
+        None
+    };
+    let columns = csv.and_then(|format| {
+        logjuicer_tokenizer::csv::parse(raw_str, format.delimiter).map(|columns| (format, columns))
+    });
+    let (tokens, message) = match (fields, columns) {
+        (Some(fields), _) => (logfmt::process(&fields, rules), logfmt::message(&fields)),
+        (None, Some((format, columns))) => (
+            logjuicer_tokenizer::csv::process(&columns, format, rules),
+            logjuicer_tokenizer::csv::message(&columns, format),
+        ),
+        (None, None) => (logjuicer_tokenizer::rules::process(raw_str, rules), None),
+    };
+    if normalize_locale && locale::is_localized(raw_str) {
+        (locale::normalize(&tokens), message)
+    } else {
+        (tokens, message)
+    }
+}
+
+/// The scoring details of a single line, to understand why it was, or was not, an anomaly.
+#[derive(Debug, Serialize)]
+pub struct LinePreview {
+    /// The tokenized form of the line, as compared with the baselines.
+    pub tokens: String,
+    /// The distance to the closest baseline line, between 0.0 and 1.0.
+    pub distance: logjuicer_index::F,
+    /// The minimum distance of an anomaly.
+    pub threshold: logjuicer_index::F,
+    pub is_anomaly: bool,
+}
+
+/// Score a single line against an index, using the same normalization as the ChunkProcessor.
+pub fn preview_line<IR: IndexReader>(
+    index: &IR,
+    raw_str: &str,
+    is_logfmt: bool,
+    csv: Option<&CsvFormat>,
+    rules: &Rules,
+    locale: &crate::config::Locale,
+    policy: &crate::category::Policy,
+) -> LinePreview {
+    let (tokens, _) = tokenize(raw_str, is_logfmt, csv, rules, locale.normalize);
+    let mut distance = index
+        .distance(std::slice::from_ref(&tokens))
+        .first()
+        .copied()
+        .unwrap_or(1.0);
+    if let Some(weight) = locale.weight {
+        if logjuicer_tokenizer::locale::is_localized(raw_str) {
+            distance *= weight;
+        }
+    }
+    LinePreview {
+        tokens,
+        distance,
+        threshold: policy.threshold,
+        is_anomaly: distance > policy.threshold,
     }
 }
 
 /// Build the before context from the buffer and the left_overs
 ///
+/// * `ctx_distance` - the number of context lines.
 /// * `buffer_pos` - the current position in the buffer.
 /// * `last_context_pos` - the position of the last context (to be excluded).
 fn collect_before(
+    ctx_distance: usize,
     buffer_pos: usize,
     last_context_pos: usize,
     buffer: &[(LogLine, usize)],
     left_overs: &[Rc<str>],
 ) -> Vec<Rc<str>> {
-    let min_pos = if buffer_pos < CTX_DISTANCE {
-        0
-    } else {
-        buffer_pos - CTX_DISTANCE
-    };
+    let min_pos = buffer_pos.saturating_sub(ctx_distance);
     // The before context starts either at the last context pos, or the min pos.
     let before_context_pos = last_context_pos.max(min_pos);
     let mut before = buffer[before_context_pos..buffer_pos]
@@ -326,9 +669,9 @@ fn collect_before(
         // TODO: use direct bytes -> str conversion.
         .map(|((bytes, _), _)| logjuicer_iterator::clone_bytes_to_string(bytes).unwrap())
         .collect::<Vec<Rc<str>>>();
-    if before_context_pos == 0 && before.len() < CTX_DISTANCE {
+    if before_context_pos == 0 && before.len() < ctx_distance {
         // The anomaly happens at the begining of the buffer
-        let need = CTX_DISTANCE - before.len();
+        let need = ctx_distance - before.len();
         let available = left_overs.len();
         let want = need.min(available);
         let mut before_extra: Vec<Rc<str>> = left_overs[(available - want)..].to_vec();
@@ -354,23 +697,26 @@ fn test_leftovers() {
 
     // Without left-overs
     assert_eq!(
-        collect_before(0, 0, &cp.buffer, &cp.left_overs).len(),
+        collect_before(CTX_DISTANCE, 0, 0, &cp.buffer, &cp.left_overs).len(),
         0,
         "We are at position 0, no before context available"
     );
     assert_eq!(
-        collect_before(1, 0, &cp.buffer, &cp.left_overs),
+        collect_before(CTX_DISTANCE, 1, 0, &cp.buffer, &cp.left_overs),
         vec!["001 log line".into()],
         "We are at position 1, only 1 before is available"
     );
     assert_eq!(
-        collect_before(1, 1, &cp.buffer, &cp.left_overs).len(),
+        collect_before(CTX_DISTANCE, 1, 1, &cp.buffer, &cp.left_overs).len(),
         0,
         "If the last context is also at one, then no before context can be found"
     );
-    assert_eq!(collect_before(2, 2, &cp.buffer, &cp.left_overs).len(), 0);
     assert_eq!(
-        collect_before(4, 0, &cp.buffer, &cp.left_overs),
+        collect_before(CTX_DISTANCE, 2, 2, &cp.buffer, &cp.left_overs).len(),
+        0
+    );
+    assert_eq!(
+        collect_before(CTX_DISTANCE, 4, 0, &cp.buffer, &cp.left_overs),
         vec![
             "002 log line".into(),
             "003 log line".into(),
@@ -388,7 +734,7 @@ fn test_leftovers() {
     );
     cp.buffer.push((("006 log line".into(), 6), 6));
     assert_eq!(
-        collect_before(1, 0, &cp.buffer, &cp.left_overs),
+        collect_before(CTX_DISTANCE, 1, 0, &cp.buffer, &cp.left_overs),
         vec![
             "004 log line".into(),
             "005 log line".into(),
@@ -437,6 +783,7 @@ fn test_chunk_processor() {
                 pos: 3,
                 line: "Traceback oops".into(),
             },
+            targets: Vec::new(),
         },
         AnomalyContext {
             before: Vec::new(),
@@ -446,6 +793,7 @@ fn test_chunk_processor() {
                 pos: 5,
                 line: "another Traceback".into(),
             },
+            targets: Vec::new(),
         },
     ];
     assert_eq!(anomalies.len(), expected.len());
@@ -460,3 +808,137 @@ fn test_chunk_processor() {
             assert_eq!(got.after, expected.after);
         });
 }
+
+#[test]
+fn test_logfmt_processor() {
+    let baseline = std::io::Cursor::new(
+        [
+            "time=1 level=info msg=\"listening\" port=8080",
+            "time=2 level=info msg=\"request done\" status=200",
+        ]
+        .join("\n"),
+    );
+    let mut trainer = IndexTrainer::new(logjuicer_index::FeaturesMatrixBuilder::default(), false)
+        .with_logfmt(true);
+    trainer.add(baseline).unwrap();
+    let index = trainer.build();
+
+    let data = std::io::Cursor::new(
+        [
+            "time=3 level=info msg=\"listening\" port=8080",
+            "time=4 level=error msg=\"database unreachable\" err=\"connection refused\"",
+        ]
+        .join("\n"),
+    );
+    let mut skip_lines = KnownLines::new();
+    let anomalies: Vec<AnomalyContext> =
+        ChunkProcessor::new(data, &index, false, false, &mut skip_lines)
+            .with_logfmt(true)
+            .map(|anomaly| anomaly.unwrap())
+            .collect();
+    assert_eq!(anomalies.len(), 1);
+    assert_eq!(anomalies[0].anomaly.line.as_ref(), "database unreachable");
+    assert_eq!(anomalies[0].before, vec!["listening".into()]);
+}
+
+#[test]
+fn test_stacktrace_processor() {
+    let baseline = std::io::Cursor::new(["Starting service", "Stopping service"].join("\n"));
+    let builder = logjuicer_index::FeaturesMatrixBuilder::default();
+    let index = IndexTrainer::single(builder, false, baseline).unwrap();
+
+    let trace = [
+        "Traceback (most recent call last):",
+        "  File \"service.py\", line 12, in <module>",
+        "    main()",
+        "  File \"service.py\", line 8, in main",
+        "    connect(host)",
+        "ConnectionRefusedError: connection refused",
+    ];
+    let data = std::io::Cursor::new(
+        std::iter::once("Starting service")
+            .chain(trace)
+            .chain(std::iter::once("Stopping service"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+    let mut skip_lines = KnownLines::new();
+    let anomalies: Vec<AnomalyContext> =
+        ChunkProcessor::new(data, &index, false, false, &mut skip_lines)
+            .map(|anomaly| anomaly.unwrap())
+            .collect();
+    assert_eq!(anomalies.len(), 1);
+    assert_eq!(anomalies[0].anomaly.line.as_ref(), trace[0]);
+    assert_eq!(anomalies[0].after.len(), trace.len());
+}
+
+#[test]
+fn test_preview_line() {
+    let baseline = std::io::Cursor::new(["Starting service 42", "Listening on :8080"].join("\n"));
+    let builder = logjuicer_index::FeaturesMatrixBuilder::default();
+    let index = IndexTrainer::single(builder, false, baseline).unwrap();
+    let locale = crate::config::Locale::default();
+    let policy = crate::category::Policy::default();
+
+    let rules = Rules::default();
+    let preview = preview_line(
+        &index,
+        "Starting service 43",
+        false,
+        None,
+        &rules,
+        &locale,
+        &policy,
+    );
+    assert!(!preview.is_anomaly);
+    assert!(preview.distance < 0.001);
+
+    let preview = preview_line(
+        &index,
+        "Traceback oops",
+        false,
+        None,
+        &rules,
+        &locale,
+        &policy,
+    );
+    assert!(preview.is_anomaly);
+    assert_eq!(
+        preview.tokens,
+        logjuicer_tokenizer::process("Traceback oops")
+    );
+}
+
+#[test]
+fn test_cancel_processor() {
+    let lines = (0..CHUNK_SIZE * 2)
+        .map(|pos| format!("{:04} log line", pos))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let cancel = CancelToken::new();
+    cancel.cancel();
+
+    let mut trainer = IndexTrainer::new(logjuicer_index::FeaturesMatrixBuilder::default(), false)
+        .with_cancel(cancel.clone());
+    let err = trainer
+        .add(std::io::Cursor::new(lines.clone()))
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<crate::env::Cancellation>(),
+        Some(&crate::env::Cancellation::Requested)
+    );
+    assert_eq!(trainer.line_count, CHUNK_SIZE);
+
+    let index = logjuicer_index::index_mat(&[]);
+    let mut skip_lines = KnownLines::new();
+    let mut processor = ChunkProcessor::new(
+        std::io::Cursor::new(lines),
+        &index,
+        false,
+        false,
+        &mut skip_lines,
+    )
+    .with_cancel(cancel);
+    assert!(matches!(processor.next(), Some(Err(_))));
+    assert_eq!(processor.line_count, CHUNK_SIZE);
+}
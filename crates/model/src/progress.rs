@@ -0,0 +1,117 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the progress hooks of the model operations.
+//!
+//! The training and the reports call the [Progress] of the [crate::env::Env] from the thread running
+//! the operation, so the hooks must return quickly. The cli displays a progress bar and
+//! the web service emits the progress to the report monitor.
+
+use logjuicer_report::{bytes_to_mb, Source};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The progress hooks, the default implementations ignore the events.
+pub trait Progress: Send + Sync {
+    /// The sources to read were listed.
+    fn files_discovered(&self, _count: usize) {}
+
+    /// A source was read, the count is the size of its decompressed content.
+    fn bytes_downloaded(&self, _source: &Source, _count: usize) {}
+
+    /// A source was processed, by the training or by the report.
+    fn file_analyzed(&self, _source: &Source) {}
+
+    /// Anomalies were found in a target source.
+    fn anomalies_found(&self, _source: &Source, _count: usize) {}
+}
+
+/// The hooks of the operations without a progress display.
+pub struct NoProgress;
+
+impl Progress for NoProgress {}
+
+/// The totals of the progress events, e.g. `3/12 files, 4.20 MB, 5 anomalies`.
+#[derive(Debug, Default)]
+pub struct ProgressCounters {
+    files: AtomicUsize,
+    analyzed: AtomicUsize,
+    bytes: AtomicUsize,
+    anomalies: AtomicUsize,
+}
+
+impl ProgressCounters {
+    pub fn new() -> ProgressCounters {
+        ProgressCounters::default()
+    }
+
+    pub fn files(&self) -> usize {
+        self.files.load(Ordering::Relaxed)
+    }
+
+    pub fn analyzed(&self) -> usize {
+        self.analyzed.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn anomalies(&self) -> usize {
+        self.anomalies.load(Ordering::Relaxed)
+    }
+}
+
+impl Progress for ProgressCounters {
+    fn files_discovered(&self, count: usize) {
+        self.files.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn bytes_downloaded(&self, _source: &Source, count: usize) {
+        self.bytes.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn file_analyzed(&self, _source: &Source) {
+        self.analyzed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn anomalies_found(&self, _source: &Source, count: usize) {
+        self.anomalies.fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+impl std::fmt::Display for ProgressCounters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} files, {:.2} MB, {} anomalies",
+            self.analyzed(),
+            self.files(),
+            bytes_to_mb(self.bytes()),
+            self.anomalies()
+        )
+    }
+}
+
+#[test]
+fn test_progress() -> anyhow::Result<()> {
+    use logjuicer_index::{FeaturesMatrix, FeaturesMatrixBuilder};
+    use std::sync::Arc;
+
+    let logs = crate::TestLogs::new()?;
+    let baseline = logs.content("baseline", &[("app.log", "first line\nsecond line\n")])?;
+    let target = logs.content("target", &[("app.log", "first line\nkernel panic\n")])?;
+    let counters = Arc::new(ProgressCounters::new());
+    let env = crate::env::Env {
+        progress: counters.clone(),
+        ..crate::env::Env::new()
+    };
+    let model =
+        crate::Model::<FeaturesMatrix>::train::<FeaturesMatrixBuilder>(&env, vec![baseline])?;
+    model.report(&env, target)?;
+    assert_eq!(counters.files(), 2);
+    assert_eq!(counters.analyzed(), 2);
+    assert_eq!(counters.bytes(), 43);
+    assert_eq!(counters.anomalies(), 1);
+    assert_eq!(counters.to_string(), "2/2 files, 0.00 MB, 1 anomalies");
+    Ok(())
+}
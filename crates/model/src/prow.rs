@@ -3,6 +3,7 @@
 
 use anyhow::{Context, Result};
 use regex::Regex;
+use serde::Deserialize;
 use std::io::Read;
 use url::Url;
 
@@ -129,7 +130,80 @@ fn from_build_result(build: &ProwBuild, br: prow_build::BuildResult) -> Result<P
     })
 }
 
+/// The maximum number of builds to check when looking for a successful one.
+const MAX_BUILDS: usize = 200;
+
+/// The bucket location of a build stored in gcs, e.g. `gs://origin-ci-test/pr-logs/pull/org_repo/42/job/uid/`.
+fn build_bucket_url(build: &ProwBuild) -> Option<Url> {
+    if build.storage_type.as_ref() != "gs" {
+        return None;
+    }
+    let path = build.url.path().strip_prefix("/view/gs/")?;
+    Url::parse(&format!("gs://{}/", path.trim_end_matches('/'))).ok()
+}
+
+/// The `pr-logs/directory/<job>/<id>.txt` links of the other builds, newest first.
+fn build_links<'a>(links: &'a [Url], uid: &str) -> Vec<&'a Url> {
+    let mut builds = links
+        .iter()
+        .filter_map(|link| {
+            let name = link.path().rsplit('/').next()?.strip_suffix(".txt")?;
+            match name.parse::<u64>() {
+                Ok(id) if name != uid => Some((id, link)),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    builds.sort_by_key(|build| std::cmp::Reverse(build.0));
+    builds.into_iter().map(|(_, link)| link).collect()
+}
+
+#[derive(Deserialize)]
+struct Finished {
+    result: Option<String>,
+}
+
+/// Find the last successful builds using the bucket, where each build is linked from the job directory.
+fn discover_bucket_baselines(build: &ProwBuild, env: &Env) -> Result<Baselines> {
+    let max_baselines = env.config.baseline_builds();
+    let mut baselines = Vec::new();
+    let directory = gcs_bucket::object_url(
+        &build.storage_path,
+        &format!("pr-logs/directory/{}/", build.job_name),
+    )?;
+    let links = crate::gcs::list_objects(env, &directory)?;
+    for link in build_links(&links, &build.uid).into_iter().take(MAX_BUILDS) {
+        let location = crate::gcs::read_object(env, link)?;
+        let location = location.trim().trim_end_matches('/');
+        let finished = Url::parse(&format!("{}/finished.json", location))?;
+        match crate::gcs::read_object(env, &finished) {
+            Ok(finished) => match serde_json::from_str::<Finished>(&finished) {
+                Ok(finished) if finished.result.as_deref() == Some("SUCCESS") => {
+                    let path = location.strip_prefix("gs://").unwrap_or(location);
+                    let url = build.url.join(&format!("/view/gs/{}", path))?;
+                    if let Some(baseline) = parse_prow_url(&url) {
+                        baselines.push(Content::Prow(Box::new(baseline?)));
+                        if baselines.len() == max_baselines {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            // The build is still running.
+            Err(e) => tracing::debug!("{}: {}", finished, e),
+        }
+    }
+    Ok(baselines)
+}
+
 pub fn discover_baselines(build: &ProwBuild, env: &Env) -> Result<Baselines> {
+    if build.storage_type.as_ref() == "gs" {
+        match discover_bucket_baselines(build, env) {
+            Ok(baselines) => return Ok(baselines),
+            Err(e) => tracing::warn!("Failed to discover baseline from the bucket: {}", e),
+        }
+    }
     let client = prow_build::Client {
         client: env.client.clone(),
         api_url: build.url.clone(),
@@ -137,24 +211,66 @@ pub fn discover_baselines(build: &ProwBuild, env: &Env) -> Result<Baselines> {
         storage_path: build.storage_path.as_ref().into(),
     };
     tracing::info!("Discovering baselines for {}", build);
-    for baseline in prow_build::BuildIterator::new(&client, &build.job_name).take(200) {
+    let max_baselines = env.config.baseline_builds();
+    let mut baselines = Vec::new();
+    for baseline in prow_build::BuildIterator::new(&client, &build.job_name).take(MAX_BUILDS) {
         match baseline {
             Err(e) => return Err(anyhow::anyhow!("Failed to discover baseline: {}", e)),
             Ok(build_result) if build_result.result.as_ref() == "SUCCESS" => {
-                return Ok(vec![Content::Prow(Box::new(from_build_result(
+                baselines.push(Content::Prow(Box::new(from_build_result(
                     build,
                     build_result,
-                )?))])
+                )?)));
+                if baselines.len() == max_baselines {
+                    break;
+                }
             }
             Ok(_) => {}
         }
     }
-    Ok(vec![])
+    Ok(baselines)
 }
 
 pub fn sources_iter(build: &ProwBuild, env: &Env) -> Box<dyn Iterator<Item = Result<Source>>> {
+    if let Some(url) = build_bucket_url(build) {
+        return crate::gcs::sources_iter(&url, env);
+    }
     match get_prow_artifact_url(env, &build.url) {
         Err(e) => Box::new(std::iter::once(Err(e))),
         Ok(url) => crate::httpdir_iter(&url, env),
     }
 }
+
+#[test]
+fn test_build_bucket() {
+    let url = Url::parse("https://prow.ci.openshift.org/view/gs/origin-ci-test/pr-logs/pull/openstack-k8s-operators_ci-framework/437/pull-ci-openstack-k8s-operators-ci-framework-main-ansible-test/1689624623181729792").unwrap();
+    let build = parse_prow_url(&url).unwrap().unwrap();
+    assert_eq!(
+        build_bucket_url(&build).unwrap().as_str(),
+        "gs://origin-ci-test/pr-logs/pull/openstack-k8s-operators_ci-framework/437/pull-ci-openstack-k8s-operators-ci-framework-main-ansible-test/1689624623181729792/"
+    );
+
+    let links = [
+        "1689624623181729792",
+        "1689000000000000000",
+        "latest-build",
+        "1690000000000000000",
+    ]
+    .iter()
+    .map(|id| {
+        Url::parse(&format!(
+            "gs://origin-ci-test/pr-logs/directory/job/{}.txt",
+            id
+        ))
+        .unwrap()
+    })
+    .collect::<Vec<_>>();
+    let ids = build_links(&links, &build.uid)
+        .into_iter()
+        .map(|link| link.path().rsplit('/').next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ids,
+        vec!["1690000000000000000.txt", "1689000000000000000.txt"]
+    );
+}
@@ -6,34 +6,75 @@
 use anyhow::{Context, Result};
 use std::io::Read;
 use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
 use url::Url;
 
 use std::fs::File;
 
-use crate::env::Env;
+use crate::env::{Env, RetryPolicy};
+use bzip2::read::BzDecoder;
 use flate2::read::GzDecoder;
+use logjuicer_cache::Validators;
+use xz2::read::XzDecoder;
+
+type ZstdDecoder<R> = zstd::stream::read::Decoder<'static, std::io::BufReader<R>>;
 
 /// Handle remote object.
 use ureq::{Agent, Response};
 mod remote {
     use super::*;
 
-    pub fn get_url(client: &Agent, url: &Url) -> Result<Response> {
-        client
-            .request_url("GET", url)
-            .call()
-            .context("Can't get url")
+    fn request(client: &Agent, method: &str, url: &Url) -> Result<ureq::Request> {
+        if crate::s3::is_s3_url(url) {
+            crate::s3::object_request(client, method, url)
+        } else if crate::gcs::is_gs_url(url) {
+            crate::gcs::object_request(client, method, url)
+        } else if crate::buildkite::is_api_url(url) {
+            Ok(crate::buildkite::request(client, method, url))
+        } else if crate::circleci::is_api_url(url) {
+            Ok(crate::circleci::request(client, method, url))
+        } else {
+            Ok(client.request_url(method, url))
+        }
+    }
+
+    pub fn get_url(
+        client: &Agent,
+        retry: &RetryPolicy,
+        url: &Url,
+        validators: &Validators,
+    ) -> Result<Response> {
+        let request = conditional(request(client, "GET", url)?, validators);
+        retry.call(url, || request.clone()).context("Can't get url")
     }
 
-    pub fn head(client: &Agent, url: &Url) -> Result<bool> {
-        let resp = client
-            .request_url("HEAD", url)
-            .call()
+    pub fn head(client: &Agent, retry: &RetryPolicy, url: &Url) -> Result<bool> {
+        let request = request(client, "HEAD", url)?;
+        let resp = retry
+            .call(url, || request.clone())
             .context("Can't head url")?;
         Ok(is_success(resp.status()))
     }
 }
 
+/// Add the conditional headers, so that the content is only sent when it changed.
+fn conditional(mut request: ureq::Request, validators: &Validators) -> ureq::Request {
+    if let Some(etag) = &validators.etag {
+        request = request.set("If-None-Match", etag);
+    }
+    if let Some(last_modified) = &validators.last_modified {
+        request = request.set("If-Modified-Since", last_modified);
+    }
+    request
+}
+
+fn response_validators(resp: &Response) -> Validators {
+    Validators {
+        etag: resp.header("ETag").map(String::from),
+        last_modified: resp.header("Last-Modified").map(String::from),
+    }
+}
+
 fn is_success(code: u16) -> bool {
     (200..300).contains(&code)
 }
@@ -43,9 +84,21 @@ fn is_success(code: u16) -> bool {
 pub enum DecompressReader {
     Flat(File),
     Gz(GzDecoder<File>),
-    // TODO: support BZIP2 compression
+    Zstd(ZstdDecoder<File>),
+    Xz(XzDecoder<File>),
+    Bz2(BzDecoder<File>),
     Remote(UreqReader),
     Cached(logjuicer_cache::CacheReader<UreqReader>),
+    RemoteDecoder(Box<dyn Read>),
+    ZipStored(std::io::Take<File>),
+    ZipDeflated(flate2::read::DeflateDecoder<std::io::Take<File>>),
+    Journal(Box<crate::journal::JournalReader<DecompressReader>>),
+    Prefixed(Box<crate::encoding::Prefixed<DecompressReader>>),
+    Utf16(Box<crate::encoding::Utf16Reader<crate::encoding::Prefixed<DecompressReader>>>),
+    Rotated(Box<crate::rotation::RotatedReader>),
+    JsonRecords(
+        Box<crate::json::JsonRecordsReader<Box<crate::encoding::Prefixed<DecompressReader>>>>,
+    ),
 }
 use DecompressReader::*;
 
@@ -53,14 +106,28 @@ type UreqReader = Box<dyn Read + Send + Sync + 'static>;
 
 pub fn from_path(path: &Path) -> Result<DecompressReader> {
     let fp = File::open(path)?;
-    let extension = path.extension().unwrap_or_else(|| std::ffi::OsStr::new(""));
-    Ok(if extension == ".gz" {
-        Gz(GzDecoder::new(fp))
-    } else {
-        Flat(fp)
+    let extension = path.extension().and_then(std::ffi::OsStr::to_str);
+    Ok(match extension {
+        Some("gz") => Gz(GzDecoder::new(fp)),
+        Some("zst") => Zstd(ZstdDecoder::new(fp)?),
+        Some("xz") => Xz(XzDecoder::new(fp)),
+        Some("bz2") => Bz2(BzDecoder::new(fp)),
+        _ => Flat(fp),
     })
 }
 
+/// Wrap the remote reader with a decoder when the object needs to be decompressed by the client.
+pub(crate) fn decompress_url(url: &Url, reader: DecompressReader) -> Result<DecompressReader> {
+    let extension = url.path().rsplit_once('.').map(|(_, ext)| ext);
+    let reader: Box<dyn Read> = match extension {
+        Some("zst") => Box::new(ZstdDecoder::new(reader)?),
+        Some("xz") => Box::new(XzDecoder::new(reader)),
+        Some("bz2") => Box::new(BzDecoder::new(reader)),
+        _ => return Ok(reader),
+    };
+    Ok(RemoteDecoder(reader))
+}
+
 pub fn head_url(env: &Env, prefix: usize, url: &Url) -> Result<bool> {
     if let Some(cache) = &env.cache {
         match cache.head(prefix, url) {
@@ -70,34 +137,153 @@ pub fn head_url(env: &Env, prefix: usize, url: &Url) -> Result<bool> {
             }
             None => {
                 tracing::debug!("Cache miss for {}", url);
-                cache.head_set(prefix, url, remote::head(&env.client, url)?)
+                cache.head_set(
+                    prefix,
+                    url,
+                    remote::head(env.client_for(url), &env.retry(), url)?,
+                )
             }
         }
     } else {
-        remote::head(&env.client, url)
+        remote::head(env.client_for(url), &env.retry(), url)
     }
 }
 
 /// Read a url, using a prefix size for cache grouping directory.
 pub fn from_url(env: &Env, prefix: usize, url: &Url) -> Result<DecompressReader> {
+    decompress_url(url, from_url_raw(env, prefix, url)?)
+}
+
+fn from_url_raw(env: &Env, prefix: usize, url: &Url) -> Result<DecompressReader> {
+    from_response(env, prefix, url, |validators| {
+        remote::get_url(env.client_for(url), &env.retry(), url, validators)
+    })
+}
+
+/// Read an url with a custom request, e.g. to set the authentication headers.
+pub(crate) fn from_request(
+    env: &Env,
+    prefix: usize,
+    url: &Url,
+    request: ureq::Request,
+) -> Result<DecompressReader> {
+    from_response(env, prefix, url, |validators| {
+        let request = conditional(request, validators);
+        env.retry()
+            .call(url, || request.clone())
+            .context("Can't get url")
+    })
+}
+
+/// Read a response through the cache.
+/// The cached contents that have validators are revalidated, and they are downloaded again when they changed.
+fn from_response<F>(env: &Env, prefix: usize, url: &Url, get: F) -> Result<DecompressReader>
+where
+    F: FnOnce(&Validators) -> Result<Response>,
+{
     if let Some(cache) = &env.cache {
-        match cache.remote_get(prefix, url) {
-            Some(cache) => {
+        let resp = match (
+            cache.remote_get(prefix, url),
+            cache.remote_validators(prefix, url),
+        ) {
+            (Some(cached), None) => {
                 tracing::debug!("Cache hit for {}", url);
-                cache.map(Gz)
+                return cached.map(Gz);
             }
-            None => {
+            // The cached contents are not revalidated in offline mode, or more than once per run.
+            (Some(cached), Some(_)) if env.offline || env.is_revalidated(url) => {
+                tracing::debug!("Cache hit for {}", url);
+                return cached.map(Gz);
+            }
+            (Some(cached), Some(validators)) => match get(&validators) {
+                Ok(resp) if resp.status() == 304 => {
+                    tracing::debug!("Cache revalidated for {}", url);
+                    env.set_revalidated(url);
+                    return cached.map(Gz);
+                }
+                Ok(resp) => {
+                    tracing::debug!("Cache outdated for {}", url);
+                    cache.remote_drop(prefix, url)?;
+                    resp
+                }
+                // The cached content is still usable when the remote is not available.
+                Err(err) => {
+                    env.set_revalidated(url);
+                    tracing::warn!("Cache revalidation failed for {}: {:#}", url, err);
+                    return cached.map(Gz);
+                }
+            },
+            (None, _) => {
                 tracing::debug!("Cache miss for {}", url);
-                let resp = remote::get_url(&env.client, url)?;
-                let cache = cache.remote_add(prefix, url, resp.into_reader())?;
-                Ok(Cached(cache))
+                get(&Validators::default())?
             }
-        }
+        };
+        env.set_revalidated(url);
+        cache_response(
+            cache,
+            prefix,
+            url,
+            response_validators(&resp),
+            resp.into_reader(),
+        )
     } else {
-        Ok(Remote(remote::get_url(&env.client, url)?.into_reader()))
+        Ok(Remote(get(&Validators::default())?.into_reader()))
     }
 }
 
+/// Add a remote reader to the cache, along with its validators.
+fn cache_response(
+    cache: &logjuicer_cache::Cache,
+    prefix: usize,
+    url: &Url,
+    validators: Validators,
+    reader: UreqReader,
+) -> Result<DecompressReader> {
+    let reader = cache.remote_add(prefix, url, reader)?;
+    cache.remote_set_validators(prefix, url, &validators)?;
+    Ok(Cached(reader))
+}
+
+/// Check if a url is already in the cache.
+pub(crate) fn is_cached(env: &Env, prefix: usize, url: &Url) -> bool {
+    env.cache
+        .as_ref()
+        .is_some_and(|cache| cache.remote_get(prefix, url).is_some())
+}
+
+/// Download the whole content of a url, along with its validators.
+pub(crate) fn download(
+    client: &Agent,
+    retry: &RetryPolicy,
+    url: &Url,
+) -> Result<(Vec<u8>, Validators)> {
+    let mut body = Vec::new();
+    let resp = remote::get_url(client, retry, url, &Validators::default())?;
+    let validators = response_validators(&resp);
+    resp.into_reader()
+        .read_to_end(&mut body)
+        .with_context(|| format!("{}: download failed", url))?;
+    Ok((body, validators))
+}
+
+/// Read a downloaded url, adding it to the cache.
+pub(crate) fn from_body(
+    env: &Env,
+    prefix: usize,
+    url: &Url,
+    (body, validators): (Vec<u8>, Validators),
+) -> Result<DecompressReader> {
+    let reader: UreqReader = Box::new(std::io::Cursor::new(body));
+    let reader = match &env.cache {
+        Some(cache) => {
+            env.set_revalidated(url);
+            cache_response(cache, prefix, url, validators, reader)?
+        }
+        None => Remote(reader),
+    };
+    decompress_url(url, reader)
+}
+
 pub fn drop_url(env: &Env, prefix: usize, url: &Url) -> Result<()> {
     if let Some(cache) = &env.cache {
         cache.remote_drop(prefix, url)
@@ -106,18 +292,114 @@ pub fn drop_url(env: &Env, prefix: usize, url: &Url) -> Result<()> {
     }
 }
 
+/// Read the output of a command, checking its exit status at the end.
+pub(crate) struct CommandReader {
+    program: String,
+    child: Child,
+    stdout: ChildStdout,
+}
+
+impl CommandReader {
+    pub(crate) fn spawn(command: &mut Command) -> Result<CommandReader> {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", program))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Missing {} output", program))?;
+        Ok(CommandReader {
+            program,
+            child,
+            stdout,
+        })
+    }
+}
+
+impl Read for CommandReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.stdout.read(buf)?;
+        if size == 0 && !buf.is_empty() {
+            let status = self.child.wait()?;
+            if !status.success() {
+                return Err(std::io::Error::other(format!(
+                    "{} failed: {}",
+                    self.program, status
+                )));
+            }
+        }
+        Ok(size)
+    }
+}
+
 impl Read for DecompressReader {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         // TODO: refactor using the enum_dispatch crate.
         match self {
             Flat(r) => r.read(buf),
             Gz(r) => r.read(buf),
+            Zstd(r) => r.read(buf),
+            Xz(r) => r.read(buf),
+            Bz2(r) => r.read(buf),
             Remote(r) => r.read(buf),
             Cached(r) => r.read(buf),
+            RemoteDecoder(r) => r.read(buf),
+            ZipStored(r) => r.read(buf),
+            ZipDeflated(r) => r.read(buf),
+            Journal(r) => r.read(buf),
+            Prefixed(r) => r.read(buf),
+            Utf16(r) => r.read(buf),
+            Rotated(r) => r.read(buf),
+            JsonRecords(r) => r.read(buf),
         }
     }
 }
 
+#[test]
+fn test_zstd_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("job-output.txt.zst");
+    let data = zstd::encode_all("first line\nsecond line\n".as_bytes(), 0).unwrap();
+    std::fs::write(&path, data).unwrap();
+
+    let mut content = String::new();
+    from_path(&path)
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "first line\nsecond line\n");
+}
+
+#[test]
+fn test_xz_bz2_file() {
+    use std::io::Write;
+    let dir = tempfile::tempdir().unwrap();
+    let data = "first line\nsecond line\n";
+
+    let xz_path = dir.path().join("messages.xz");
+    let mut xz = xz2::write::XzEncoder::new(File::create(&xz_path).unwrap(), 6);
+    xz.write_all(data.as_bytes()).unwrap();
+    xz.finish().unwrap();
+
+    let bz2_path = dir.path().join("messages.bz2");
+    let mut bz2 =
+        bzip2::write::BzEncoder::new(File::create(&bz2_path).unwrap(), bzip2::Compression::fast());
+    bz2.write_all(data.as_bytes()).unwrap();
+    bz2.finish().unwrap();
+
+    for path in [xz_path, bz2_path] {
+        let mut content = String::new();
+        from_path(&path)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, data);
+    }
+}
+
 /*
 // Automatic decompressor implementation poc
 pub fn auto<R: Read + 'static>(mut reader: R) -> Result<Box<dyn Read>> {
@@ -135,3 +417,61 @@ pub fn auto<R: Read + 'static>(mut reader: R) -> Result<Box<dyn Read>> {
     })
 }
 */
+
+#[test]
+fn test_cache_revalidation() -> Result<()> {
+    let env = Env {
+        cache: Some(logjuicer_cache::Cache::new()?),
+        ..Env::new()
+    };
+    let mut server = mockito::Server::new();
+    let url = Url::parse(&format!("{}/logs/revalidated.log", server.url()))?;
+    env.cache.as_ref().unwrap().remote_drop(0, &url)?;
+    let read = || -> Result<String> {
+        let mut content = String::new();
+        from_url(&env, 0, &url)?.read_to_string(&mut content)?;
+        Ok(content)
+    };
+
+    let download = server
+        .mock("GET", "/logs/revalidated.log")
+        .match_header("If-None-Match", mockito::Matcher::Missing)
+        .with_header("ETag", "\"v1\"")
+        .with_body("first line\n")
+        .expect(1)
+        .create();
+    assert_eq!(read()?, "first line\n");
+    // The content is revalidated once per run.
+    assert_eq!(read()?, "first line\n");
+    download.assert();
+
+    // The cached content is used when the remote did not change.
+    env.revalidated.lock().unwrap().clear();
+    let not_modified = server
+        .mock("GET", "/logs/revalidated.log")
+        .match_header("If-None-Match", "\"v1\"")
+        .with_status(304)
+        .create();
+    assert_eq!(read()?, "first line\n");
+    not_modified.assert();
+    not_modified.remove();
+
+    // The content is downloaded again when it changed.
+    env.revalidated.lock().unwrap().clear();
+    let changed = server
+        .mock("GET", "/logs/revalidated.log")
+        .match_header("If-None-Match", "\"v1\"")
+        .with_header("ETag", "\"v2\"")
+        .with_body("second line\n")
+        .create();
+    assert_eq!(read()?, "second line\n");
+    changed.assert();
+    assert_eq!(
+        env.cache.as_ref().unwrap().remote_validators(0, &url),
+        Some(logjuicer_cache::Validators {
+            etag: Some("\"v2\"".into()),
+            last_modified: None
+        })
+    );
+    Ok(())
+}
@@ -0,0 +1,148 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the log rotation set detection.
+//!
+//! The rotated files, such as `app.log`, `app.log.1` and `app.log.2.gz`, are grouped
+//! so that they are analyzed as a single logical source, in chronological order.
+
+use anyhow::Result;
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+
+use crate::env::Env;
+use crate::prefetch::Prefetch;
+use crate::reader::DecompressReader;
+use crate::{source_open_with, Source};
+
+/// The compression extensions that may be added by the rotation tool.
+const COMPRESSION_EXTENSIONS: &[&str] = &[".gz", ".zst", ".xz", ".bz2"];
+
+/// Split a path into its rotation set name and its generation, e.g. `app.log.2.gz` is
+/// the generation 2 of `app.log`. The current file is the generation 0.
+pub fn split_generation(path: &str) -> (&str, usize) {
+    let name = COMPRESSION_EXTENSIONS
+        .iter()
+        .find_map(|ext| path.strip_suffix(ext))
+        .unwrap_or(path);
+    match name.rsplit_once('.') {
+        Some((base, generation))
+            if !base.is_empty()
+                && !base.ends_with('/')
+                && (1..=3).contains(&generation.len())
+                && generation.bytes().all(|c| c.is_ascii_digit()) =>
+        {
+            (base, generation.parse().unwrap_or(0))
+        }
+        _ => (name, 0),
+    }
+}
+
+/// Group the sources by rotation set, each group is sorted from the oldest to the current file.
+pub fn group(sources: Vec<Source>) -> Vec<Vec<Source>> {
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut groups: Vec<Vec<(usize, Source)>> = Vec::new();
+    for source in sources {
+        let (name, generation) = split_generation(source.as_str());
+        match positions.get(name) {
+            Some(pos) => groups[*pos].push((generation, source)),
+            None => {
+                positions.insert(name.to_string(), groups.len());
+                groups.push(vec![(generation, source)]);
+            }
+        }
+    }
+    groups
+        .into_iter()
+        .map(|mut group| {
+            group.sort_by_key(|(generation, _)| std::cmp::Reverse(*generation));
+            group.into_iter().map(|(_, source)| source).collect()
+        })
+        .collect()
+}
+
+/// Open a rotation set as a single reader.
+pub fn open(env: &Env, sources: &[Source]) -> Result<DecompressReader> {
+    open_with(env, &mut Prefetch::default(), sources)
+}
+
+/// Open a rotation set, using the downloads started by the prefetch pool.
+pub fn open_with(
+    env: &Env,
+    prefetch: &mut Prefetch,
+    sources: &[Source],
+) -> Result<DecompressReader> {
+    match sources {
+        [source] => source_open_with(env, prefetch, source),
+        _ => {
+            let readers = sources
+                .iter()
+                .map(|source| source_open_with(env, prefetch, source))
+                .collect::<Result<VecDeque<_>>>()?;
+            Ok(DecompressReader::Rotated(Box::new(RotatedReader {
+                readers,
+                last: None,
+            })))
+        }
+    }
+}
+
+/// A reader that concatenates the files of a rotation set.
+pub struct RotatedReader {
+    readers: VecDeque<DecompressReader>,
+    last: Option<u8>,
+}
+
+impl Read for RotatedReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while let Some(reader) = self.readers.front_mut() {
+            let size = reader.read(buf)?;
+            if size > 0 {
+                self.last = Some(buf[size - 1]);
+                return Ok(size);
+            }
+            self.readers.pop_front();
+            // Make sure the last line of a file is not merged with the first line of the next one.
+            if self.last.is_some_and(|c| c != b'\n') && !self.readers.is_empty() {
+                self.last = Some(b'\n');
+                buf[0] = b'\n';
+                return Ok(1);
+            }
+        }
+        Ok(0)
+    }
+}
+
+#[test]
+fn test_rotation() {
+    assert_eq!(split_generation("logs/app.log"), ("logs/app.log", 0));
+    assert_eq!(split_generation("logs/app.log.1"), ("logs/app.log", 1));
+    assert_eq!(split_generation("logs/app.log.2.gz"), ("logs/app.log", 2));
+    assert_eq!(split_generation("logs/app.log.gz"), ("logs/app.log", 0));
+    assert_eq!(
+        split_generation("logs/build.20231012"),
+        ("logs/build.20231012", 0)
+    );
+
+    let mk_src = |name: &str| Source::from_pathbuf(name.into());
+    let groups = group(vec![
+        mk_src("app.log"),
+        mk_src("app.log.2.gz"),
+        mk_src("other.log"),
+        mk_src("app.log.1"),
+    ]);
+    assert_eq!(
+        groups,
+        vec![
+            vec![
+                mk_src("app.log.2.gz"),
+                mk_src("app.log.1"),
+                mk_src("app.log")
+            ],
+            vec![mk_src("other.log")],
+        ]
+    );
+}
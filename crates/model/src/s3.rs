@@ -0,0 +1,75 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the S3 buckets, where the sources are the objects of a `s3://bucket/prefix/` url.
+//!
+//! The object urls are kept as the source location, so that they are cached like the http downloads.
+
+use anyhow::Result;
+use url::Url;
+
+use crate::env::Env;
+use crate::{Content, Source};
+
+lazy_static::lazy_static! {
+    static ref CLIENT: s3_bucket::Client = s3_bucket::Client::from_env();
+}
+
+pub fn is_s3_url(url: &Url) -> bool {
+    url.scheme() == "s3"
+}
+
+pub fn content_from_s3_url(url: Url) -> Result<Content> {
+    if s3_bucket::parse_s3_url(&url).is_none() {
+        Err(anyhow::anyhow!(
+            "Bad s3 url {}, expected s3://bucket/key",
+            url
+        ))
+    } else if url.path().ends_with('/') || url.path().is_empty() {
+        Ok(Content::Directory(Source::Remote(0, url)))
+    } else {
+        Ok(Content::File(Source::Remote(0, url)))
+    }
+}
+
+/// Create the request of an object.
+pub fn object_request(client: &ureq::Agent, method: &str, url: &Url) -> Result<ureq::Request> {
+    let object =
+        s3_bucket::parse_s3_url(url).ok_or_else(|| anyhow::anyhow!("Bad s3 url {}", url))?;
+    Ok(CLIENT.object_request(client, method, &object)?)
+}
+
+fn list_objects(env: &Env, url: &Url) -> Result<Vec<logjuicer_cache::UrlResult>> {
+    let prefix =
+        s3_bucket::parse_s3_url(url).ok_or_else(|| anyhow::anyhow!("Bad s3 url {}", url))?;
+    Ok(CLIENT
+        .list_objects(&env.client, &prefix.bucket, &prefix.key)?
+        .into_iter()
+        .map(|object| {
+            s3_bucket::object_url(&prefix.bucket, &object.key).map_err(|e| e.to_string().into())
+        })
+        .collect())
+}
+
+#[tracing::instrument(level = "debug", skip(env))]
+pub fn sources_iter(url: &Url, env: &Env) -> Box<dyn Iterator<Item = Result<Source>>> {
+    let base_len = url.as_str().trim_end_matches('/').len() + 1;
+    let maybe_cached = env.cache.as_ref().and_then(|cache| cache.httpdir_get(url));
+    let urls = if let Some(cached) = maybe_cached {
+        cached
+    } else {
+        list_objects(env, url).and_then(|urls| match &env.cache {
+            Some(cache) => cache.httpdir_add(url, &urls).map(|()| urls),
+            None => Ok(urls),
+        })
+    };
+
+    match urls {
+        Ok(urls) => Box::new(urls.into_iter().map(move |url_result| {
+            url_result
+                .map_err(anyhow::Error::msg)
+                .map(|url| Source::Remote(base_len, url))
+        })),
+        Err(e) => Box::new(std::iter::once(Err(e))),
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the `sftp://user@host/path/` and `scp://` urls, where the sources are the remote files.
+//!
+//! The files are listed and streamed with the `ssh` command, which uses the ssh agent and configuration,
+//! or the `LOGJUICER_SSH_KEY` identity file. The `/~/` path prefix is the remote home directory.
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use std::process::Command;
+use url::Url;
+
+use crate::env::Env;
+use crate::reader::{CommandReader, DecompressReader};
+use crate::{Content, Source};
+
+const SSH: &str = "ssh";
+
+pub fn is_sftp_url(url: &Url) -> bool {
+    matches!(url.scheme(), "sftp" | "scp")
+}
+
+pub fn content_from_sftp_url(url: Url) -> Result<Content> {
+    if matches!(url.host_str(), None | Some("")) {
+        Err(anyhow::anyhow!(
+            "Bad sftp url {}, expected sftp://user@host/path",
+            url
+        ))
+    } else if url.host_str().is_some_and(|host| host.starts_with('-'))
+        || percent_decode(url.username()).starts_with('-')
+    {
+        Err(anyhow::anyhow!(
+            "Bad sftp url {}, the host and the user can't start with '-'",
+            url
+        ))
+    } else if url.path().ends_with('/') || url.path().is_empty() {
+        Ok(Content::Directory(Source::Remote(0, url)))
+    } else {
+        Ok(Content::File(Source::Remote(0, url)))
+    }
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let hex = bytes
+            .get(pos + 1..pos + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[pos], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                pos += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                pos += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Quote a value for the remote shell.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// The remote path of an url, relative to the home directory when it starts with `/~/`.
+fn remote_path(url: &Url) -> String {
+    let path = percent_decode(url.path());
+    match path.strip_prefix("/~") {
+        Some("" | "/") => ".".to_string(),
+        Some(home) if home.starts_with('/') => home[1..].to_string(),
+        _ => path,
+    }
+}
+
+/// Create the command to run a remote command.
+fn ssh_command(ssh: &str, url: &Url, remote: &str) -> Command {
+    let mut command = Command::new(ssh);
+    command.args(["-o", "BatchMode=yes"]);
+    if let Some(port) = url.port() {
+        command.args(["-p", &port.to_string()]);
+    }
+    if let Some(key) = std::env::var_os("LOGJUICER_SSH_KEY") {
+        command.arg("-i").arg(key);
+    }
+    let host = url.host_str().unwrap_or("localhost");
+    let destination = match url.username() {
+        "" => host.to_string(),
+        user => format!("{}@{}", percent_decode(user), host),
+    };
+    command.args(["--", destination.as_str(), remote]);
+    command
+}
+
+/// List the files of a remote directory.
+fn list_files(ssh: &str, url: &Url) -> Result<Vec<logjuicer_cache::UrlResult>> {
+    let root = remote_path(url);
+    let output = ssh_command(ssh, url, &format!("find -L {} -type f", shell_quote(&root)))
+        .output()
+        .with_context(|| format!("Failed to run {}", ssh))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{}: listing failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let mut files = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let relative = line.strip_prefix(root.as_str())?.trim_start_matches('/');
+            let mut file_url = url.clone();
+            file_url
+                .path_segments_mut()
+                .ok()?
+                .pop_if_empty()
+                .extend(relative.split('/'));
+            Some(Ok(file_url))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+pub fn sources_iter(url: &Url, env: &Env) -> Box<dyn Iterator<Item = Result<Source>>> {
+    sources_iter_with(SSH, url, env)
+}
+
+fn sources_iter_with(ssh: &str, url: &Url, env: &Env) -> Box<dyn Iterator<Item = Result<Source>>> {
+    let base_len = url.as_str().trim_end_matches('/').len() + 1;
+    let maybe_cached = env.cache.as_ref().and_then(|cache| cache.httpdir_get(url));
+    let urls = if let Some(cached) = maybe_cached {
+        cached
+    } else {
+        env.check_online(url)
+            .and_then(|()| list_files(ssh, url))
+            .and_then(|urls| match &env.cache {
+                Some(cache) => cache.httpdir_add(url, &urls).map(|()| urls),
+                None => Ok(urls),
+            })
+    };
+
+    match urls {
+        Ok(urls) => Box::new(urls.into_iter().map(move |url_result| {
+            url_result
+                .map_err(anyhow::Error::msg)
+                .map(|url| Source::Remote(base_len, url))
+        })),
+        Err(e) => Box::new(std::iter::once(Err(e))),
+    }
+}
+
+/// Stream a remote file.
+pub fn open(url: &Url) -> Result<DecompressReader> {
+    open_with(SSH, url)
+}
+
+fn open_with(ssh: &str, url: &Url) -> Result<DecompressReader> {
+    let mut command = ssh_command(
+        ssh,
+        url,
+        &format!("cat -- {}", shell_quote(&remote_path(url))),
+    );
+    let reader = DecompressReader::RemoteDecoder(Box::new(CommandReader::spawn(&mut command)?));
+    if url.path().ends_with(".gz") {
+        Ok(DecompressReader::RemoteDecoder(Box::new(GzDecoder::new(
+            reader,
+        ))))
+    } else {
+        crate::reader::decompress_url(url, reader)
+    }
+}
+
+#[test]
+fn test_sftp_url() -> Result<()> {
+    use std::io::{Read, Write};
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir()?;
+    // A fake ssh which runs the remote command locally.
+    let ssh = dir.path().join("ssh");
+    std::fs::write(
+        &ssh,
+        "#!/bin/sh\nwhile [ \"$1\" != \"--\" ]; do shift; done\nshift 2\nexec sh -c \"$*\"\n",
+    )?;
+    std::fs::set_permissions(&ssh, std::fs::Permissions::from_mode(0o755))?;
+    let logs = dir.path().join("logs dir");
+    std::fs::create_dir_all(logs.join("sub"))?;
+    std::fs::write(logs.join("job-output.txt"), "first\nsecond\n")?;
+    let mut gz = flate2::write::GzEncoder::new(
+        std::fs::File::create(logs.join("sub").join("app.log.gz"))?,
+        flate2::Compression::default(),
+    );
+    gz.write_all(b"compressed\n")?;
+    gz.finish()?;
+
+    let mut url = Url::parse("sftp://user@localhost:2222/")?;
+    url.path_segments_mut()
+        .unwrap()
+        .pop_if_empty()
+        .extend(logs.iter().skip(1).map(|part| part.to_str().unwrap()))
+        .push("");
+    assert_eq!(remote_path(&url), format!("{}/", logs.display()));
+    let content = content_from_sftp_url(url.clone())?;
+    assert!(matches!(content, Content::Directory(_)));
+
+    let env = Env::new();
+    let ssh = ssh.to_str().unwrap();
+    let sources = sources_iter_with(ssh, &url, &env).collect::<Result<Vec<_>>>()?;
+    assert_eq!(
+        sources
+            .iter()
+            .map(|source| source.get_relative())
+            .collect::<Vec<_>>(),
+        vec!["job-output.txt", "sub/app.log.gz"]
+    );
+
+    for (source, expected) in sources.iter().zip(["first\nsecond\n", "compressed\n"]) {
+        let url = match source {
+            Source::Remote(_, url) => url,
+            _ => panic!("Expected a remote source"),
+        };
+        let mut lines = String::new();
+        open_with(ssh, url)?.read_to_string(&mut lines)?;
+        assert_eq!(lines, expected);
+    }
+
+    assert_eq!(
+        remote_path(&Url::parse("scp://host/~/logs/")?),
+        "logs/".to_string()
+    );
+    assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    Ok(())
+}
+
+#[test]
+fn test_sftp_option_host() -> Result<()> {
+    for url in [
+        "sftp://-oProxyCommand=id/logs/",
+        "scp://-oProxyCommand=id@localhost/logs/",
+    ] {
+        assert!(content_from_sftp_url(Url::parse(url)?).is_err(), "{}", url);
+    }
+
+    let url = Url::parse("sftp://user@host/logs/")?;
+    let command = ssh_command(SSH, &url, "true");
+    let args = command
+        .get_args()
+        .map(|arg| arg.to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(args[args.len() - 3..], ["--", "user@host", "true"]);
+    Ok(())
+}
@@ -0,0 +1,163 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the storage of the trained models, to avoid training the same baselines again.
+//!
+//! The models are keyed by a digest of the trainer settings and the baselines identifiers,
+//! and they are trained again when they are older than the `model_store.ttl_hours` configuration.
+//! The store is used by the cli and the web service when the `model_store` configuration is set.
+
+use anyhow::{Context, Result};
+use logjuicer_index::traits::{IndexBuilder, IndexReader};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::env::Env;
+use crate::{Content, Model};
+
+/// The default time to live of the stored models.
+const DEFAULT_TTL_HOURS: u64 = 24;
+
+/// A directory of trained models.
+pub struct ModelStore {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+impl ModelStore {
+    pub fn new(path: PathBuf, ttl: Duration) -> ModelStore {
+        ModelStore { path, ttl }
+    }
+
+    /// The store of the configuration, if any.
+    pub fn from_env(env: &Env) -> Option<ModelStore> {
+        env.config.model_store().map(|settings| {
+            let hours = settings.ttl_hours.unwrap_or(DEFAULT_TTL_HOURS);
+            // The expired models are still used in offline mode, as they can't be trained again.
+            let ttl = if env.offline {
+                Duration::MAX
+            } else {
+                Duration::from_secs(hours * 3600)
+            };
+            ModelStore::new(settings.path.clone(), ttl)
+        })
+    }
+
+    /// The key of the model trained with the baselines.
+    pub fn key(env: &Env, baselines: &[Content]) -> String {
+        use sha2::{Digest, Sha256};
+        let mut hasher = Sha256::new();
+        hasher.update(crate::trainer_settings(&env.config.tokenizer_settings()).as_bytes());
+        for baseline in baselines {
+            hasher.update(format!("\nbaseline={}", baseline).as_bytes());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn model_path(&self, key: &str) -> PathBuf {
+        self.path.join(format!("{}.bin", key))
+    }
+
+    fn is_expired(&self, path: &std::path::Path) -> bool {
+        match std::fs::metadata(path).and_then(|meta| meta.modified()) {
+            Ok(modified) => modified.elapsed().is_ok_and(|age| age >= self.ttl),
+            Err(_) => true,
+        }
+    }
+
+    /// Load a stored model, the expired and the invalid models are removed.
+    pub fn get<IR: IndexReader + Serialize + DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> Option<Model<IR>> {
+        let path = self.model_path(key);
+        if !path.exists() {
+            return None;
+        }
+        if self.is_expired(&path) {
+            tracing::info!(path = path.to_str(), "Removing expired model");
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        match Model::load(&path) {
+            Ok(model) => Some(model),
+            Err(e) => {
+                tracing::error!(path = path.to_str(), "Removing invalid model: {:?}", e);
+                let _ = std::fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// Store a model.
+    pub fn put<IR: IndexReader + Serialize + DeserializeOwned>(
+        &self,
+        key: &str,
+        model: &Model<IR>,
+    ) -> Result<()> {
+        std::fs::create_dir_all(&self.path).context("Failed to create the model store")?;
+        let path = self.model_path(key);
+        // Write to a temporary file first so that a model is never read half written.
+        let tmp_path = self
+            .path
+            .join(format!("{}.bin.{}.tmp", key, std::process::id()));
+        model.save(&tmp_path)?;
+        std::fs::rename(&tmp_path, &path).context("Failed to store the model")
+    }
+}
+
+/// Load the model of the baselines from the store, or train it and add it to the store.
+pub fn train<IR, IB>(env: &Env, baselines: Vec<Content>) -> Result<Model<IR>>
+where
+    IR: IndexReader + Serialize + DeserializeOwned,
+    IB: Default + IndexBuilder<Reader = IR>,
+{
+    let store = match ModelStore::from_env(env) {
+        Some(store) => store,
+        None => return Model::<IR>::train::<IB>(env, baselines),
+    };
+    let key = ModelStore::key(env, &baselines);
+    if let Some(model) = store.get(&key) {
+        tracing::info!(key = key.as_str(), "Using the stored model");
+        return Ok(model);
+    }
+    let model = Model::<IR>::train::<IB>(env, baselines)?;
+    if let Err(e) = store.put(&key, &model) {
+        // The model is still usable.
+        tracing::error!(key = key.as_str(), "Failed to store the model: {:?}", e);
+    }
+    Ok(model)
+}
+
+#[test]
+fn test_model_store() -> Result<()> {
+    use logjuicer_index::{FeaturesMatrix, FeaturesMatrixBuilder};
+
+    let logs = crate::TestLogs::new()?;
+    let baseline = logs.content("baseline", &[("app.log", "first line\nsecond line\n")])?;
+    let store_path = logs.path().join("models");
+    let env = Env {
+        config: crate::config::config_from_yaml(&format!(
+            "model_store: {{path: {}}}",
+            store_path.display()
+        )),
+        ..Env::new()
+    };
+    let baselines = vec![baseline];
+    let key = ModelStore::key(&env, &baselines);
+    assert_ne!(key, ModelStore::key(&env, &[]));
+
+    let model = train::<FeaturesMatrix, FeaturesMatrixBuilder>(&env, baselines.clone())?;
+    assert!(store_path.join(format!("{}.bin", key)).exists());
+    let stored = train::<FeaturesMatrix, FeaturesMatrixBuilder>(&env, baselines)?;
+    assert_eq!(stored.hash(), model.hash());
+    assert_eq!(stored.created_at, model.created_at);
+
+    // The expired models are removed.
+    let store = ModelStore::new(store_path.clone(), Duration::ZERO);
+    assert!(store.get::<FeaturesMatrix>(&key).is_none());
+    assert!(!store_path.join(format!("{}.bin", key)).exists());
+    Ok(())
+}
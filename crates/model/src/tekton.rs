@@ -0,0 +1,284 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the Tekton pipeline runs, where the sources are the TaskRun steps logs.
+//!
+//! The `tekton.dev/v1` resources are read with the kubeconfig credentials,
+//! and the baseline is the last successful run of the same pipeline.
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use url::Url;
+
+use crate::env::Env;
+use crate::k8s::get_json;
+use crate::{Baselines, Content, Source};
+use logjuicer_report::{TektonPipelineRun, TektonStep};
+
+const PIPELINE_LABEL: &str = "tekton.dev/pipeline";
+
+#[derive(Debug, Deserialize)]
+struct PipelineRunList {
+    items: Vec<PipelineRun>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PipelineRun {
+    metadata: Metadata,
+    #[serde(default)]
+    status: PipelineRunStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    name: String,
+    #[serde(default)]
+    labels: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PipelineRunStatus {
+    #[serde(default)]
+    conditions: Vec<Condition>,
+    completion_time: Option<String>,
+    #[serde(default)]
+    child_references: Vec<ChildReference>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Condition {
+    #[serde(rename = "type")]
+    kind: String,
+    status: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChildReference {
+    kind: String,
+    name: String,
+    pipeline_task_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskRun {
+    #[serde(default)]
+    status: TaskRunStatus,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaskRunStatus {
+    pod_name: Option<String>,
+    #[serde(default)]
+    steps: Vec<StepState>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StepState {
+    container: String,
+}
+
+impl PipelineRun {
+    fn is_successful(&self) -> bool {
+        self.status
+            .conditions
+            .iter()
+            .any(|condition| condition.kind == "Succeeded" && condition.status == "True")
+    }
+}
+
+fn tekton_url(api_url: &Url, namespace: &str, path: &str) -> Result<Url> {
+    Ok(api_url.join(&format!(
+        "apis/tekton.dev/v1/namespaces/{}/{}",
+        namespace, path
+    ))?)
+}
+
+/// Decode the `namespace/name` argument, the namespace defaults to the context namespace.
+pub fn parse_pipeline_run_spec(spec: &str, namespace: Option<&str>) -> Result<(String, String)> {
+    let (namespace, name) = match spec.split_once('/') {
+        Some((namespace, name)) => (namespace, name),
+        None => (namespace.unwrap_or("default"), spec),
+    };
+    if namespace.is_empty() || name.is_empty() {
+        Err(anyhow::anyhow!(
+            "Bad pipeline run {}, expected namespace/name",
+            spec
+        ))
+    } else {
+        Ok((namespace.to_string(), name.to_string()))
+    }
+}
+
+/// Resolve the steps of the run tasks, the tasks without a pod, e.g. skipped, are ignored.
+fn from_pipeline_run(
+    env: &Env,
+    api_url: &Url,
+    namespace: &str,
+    run: PipelineRun,
+) -> Result<TektonPipelineRun> {
+    let mut steps = Vec::new();
+    for child in &run.status.child_references {
+        if child.kind != "TaskRun" {
+            continue;
+        }
+        let task_run: TaskRun = get_json(
+            env,
+            &tekton_url(api_url, namespace, &format!("taskruns/{}", child.name))?,
+        )?;
+        if let Some(pod) = task_run.status.pod_name {
+            for step in task_run.status.steps {
+                steps.push(TektonStep {
+                    task: child.pipeline_task_name.as_str().into(),
+                    pod: pod.as_str().into(),
+                    container: step.container.into(),
+                });
+            }
+        }
+    }
+    Ok(TektonPipelineRun {
+        api_url: api_url.clone(),
+        namespace: namespace.into(),
+        pipeline: run
+            .metadata
+            .labels
+            .get(PIPELINE_LABEL)
+            .map_or("", |pipeline| pipeline.as_str())
+            .into(),
+        pipeline_run: run.metadata.name.into(),
+        steps,
+    })
+}
+
+/// Resolve the pipeline run tasks.
+pub fn content_from_pipeline_run(
+    env: &Env,
+    api_url: &Url,
+    spec: &str,
+    namespace: Option<&str>,
+) -> Result<Content> {
+    let (namespace, name) = parse_pipeline_run_spec(spec, namespace)?;
+    let run: PipelineRun = get_json(
+        env,
+        &tekton_url(api_url, &namespace, &format!("pipelineruns/{}", name))?,
+    )?;
+    Ok(Content::Tekton(Box::new(from_pipeline_run(
+        env, api_url, &namespace, run,
+    )?)))
+}
+
+/// Use the last successful run of the same pipeline.
+pub fn discover_baselines(run: &TektonPipelineRun, env: &Env) -> Result<Baselines> {
+    tracing::info!("Discovering baselines for {}", run);
+    if run.pipeline.is_empty() {
+        // The run doesn't reference a pipeline.
+        return Ok(vec![]);
+    }
+    let mut url = tekton_url(&run.api_url, &run.namespace, "pipelineruns")?;
+    url.query_pairs_mut().append_pair(
+        "labelSelector",
+        &format!("{}={}", PIPELINE_LABEL, run.pipeline),
+    );
+    let runs: PipelineRunList = get_json(env, &url)?;
+    match runs
+        .items
+        .into_iter()
+        .filter(|other| other.metadata.name != run.pipeline_run.as_ref() && other.is_successful())
+        .max_by(|a, b| a.status.completion_time.cmp(&b.status.completion_time))
+    {
+        Some(baseline) => Ok(vec![Content::Tekton(Box::new(from_pipeline_run(
+            env,
+            &run.api_url,
+            &run.namespace,
+            baseline,
+        )?))]),
+        None => Ok(vec![]),
+    }
+}
+
+pub fn sources_iter(run: &TektonPipelineRun) -> Box<dyn Iterator<Item = Result<Source>>> {
+    let sources = run
+        .steps
+        .iter()
+        .map(|step| Ok(run.log_source(step)?))
+        .collect::<Vec<_>>();
+    Box::new(sources.into_iter())
+}
+
+#[test]
+fn test_tekton_pipeline_run() -> Result<()> {
+    let env = Env::new();
+    let mut server = mockito::Server::new();
+    let pipeline_run = |name: &str, succeeded: &str, completion: &str| {
+        format!(
+            r#"{{"metadata":{{"name":"{name}","labels":{{"tekton.dev/pipeline":"build-and-test"}}}},"status":{{"conditions":[{{"type":"Succeeded","status":"{succeeded}"}}],"completionTime":"{completion}","childReferences":[{{"kind":"TaskRun","name":"{name}-build","pipelineTaskName":"build"}},{{"kind":"Run","name":"{name}-approve","pipelineTaskName":"approve"}}]}}}}"#
+        )
+    };
+    let run_mock = server
+        .mock(
+            "GET",
+            "/apis/tekton.dev/v1/namespaces/ci/pipelineruns/run-3",
+        )
+        .with_body(pipeline_run("run-3", "False", "2023-10-15T10:00:00Z"))
+        .expect(1)
+        .create();
+    let list_mock = server
+        .mock("GET", "/apis/tekton.dev/v1/namespaces/ci/pipelineruns")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "labelSelector".into(),
+            "tekton.dev/pipeline=build-and-test".into(),
+        ))
+        .with_body(format!(
+            r#"{{"items":[{},{},{}]}}"#,
+            pipeline_run("run-1", "True", "2023-10-15T08:00:00Z"),
+            pipeline_run("run-2", "True", "2023-10-15T09:00:00Z"),
+            pipeline_run("run-3", "False", "2023-10-15T10:00:00Z")
+        ))
+        .expect(1)
+        .create();
+    let mut task_run = |name: &str| {
+        server
+            .mock(
+                "GET",
+                format!("/apis/tekton.dev/v1/namespaces/ci/taskruns/{name}-build").as_str(),
+            )
+            .with_body(format!(
+                r#"{{"status":{{"podName":"{name}-build-pod","steps":[{{"name":"compile","container":"step-compile"}},{{"name":"test","container":"step-test"}}]}}}}"#
+            ))
+            .expect(1)
+            .create()
+    };
+    let target_mock = task_run("run-3");
+    let baseline_mock = task_run("run-2");
+
+    let api_url = Url::parse(&format!("{}/", server.url()))?;
+    let run = match content_from_pipeline_run(&env, &api_url, "ci/run-3", None)? {
+        Content::Tekton(run) => run,
+        _ => panic!("Expected a tekton pipeline run"),
+    };
+    assert_eq!(&*run.pipeline, "build-and-test");
+    let sources = sources_iter(&run).collect::<Result<Vec<_>>>()?;
+    assert_eq!(sources.len(), 2);
+    assert_eq!(sources[0].get_relative(), "build/step-compile");
+    assert_eq!(
+        sources[1].as_str(),
+        format!(
+            "{}/api/v1/namespaces/ci/pods/run-3-build-pod/log?container=step-test&name=build/step-test",
+            server.url()
+        )
+    );
+
+    let baselines = discover_baselines(&run, &env)?;
+    assert_eq!(baselines.len(), 1);
+    assert_eq!(baselines[0].to_string(), "Tekton(ci/run-2)");
+    run_mock.assert();
+    list_mock.assert();
+    target_mock.assert();
+    baseline_mock.assert();
+
+    assert!(parse_pipeline_run_spec("ci/", None).is_err());
+    Ok(())
+}
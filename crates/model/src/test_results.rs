@@ -0,0 +1,458 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the failing tests of the target junit XML and subunit results.
+//!
+//! The junit test cases are timed with their test suite timestamp and the previous test cases time,
+//! and the subunit v1 and v2 streams record the start and end time of each test.
+//! The tests are correlated with the anomalies in [logjuicer_report::test_results::correlate].
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime};
+use std::collections::BTreeMap;
+use std::convert::{TryFrom, TryInto};
+use std::io::Read;
+
+use crate::env::Env;
+use logjuicer_report::{FailedTest, Source};
+
+/// Check if a source contains the test results.
+pub fn is_results_source(source: &Source) -> bool {
+    let name = source
+        .get_relative()
+        .rsplit('/')
+        .next()
+        .unwrap_or("")
+        .to_lowercase();
+    let name = name.strip_suffix(".gz").unwrap_or(&name);
+    name.ends_with(".subunit")
+        || (name.ends_with(".xml")
+            && (name.contains("junit") || name.contains("test") || name.contains("result")))
+}
+
+/// Read the failing tests of the results sources, the invalid files are skipped.
+pub fn read_failed_tests(env: &Env, sources: &[Source]) -> Vec<FailedTest> {
+    sources
+        .iter()
+        .flat_map(|source| match read_source(env, source) {
+            Ok(tests) => tests,
+            Err(e) => {
+                tracing::warn!("{}: skipping the test results: {}", source, e);
+                Vec::new()
+            }
+        })
+        .collect()
+}
+
+fn read_source(env: &Env, source: &Source) -> Result<Vec<FailedTest>> {
+    let mut reader = match source {
+        Source::Local(_, path) => crate::reader::from_path(path),
+        Source::Remote(prefix, url) => crate::url_open(env, *prefix, url),
+    }?;
+    let mut content = Vec::new();
+    reader
+        .read_to_end(&mut content)
+        .context("Can't read the test results")?;
+    let tests = if content.starts_with(b"<") || content.starts_with(b"\xef\xbb\xbf<") {
+        parse_junit(&String::from_utf8_lossy(&content))
+    } else if content.first() == Some(&SUBUNIT_SIGNATURE) {
+        parse_subunit_v2(&content)?
+    } else {
+        parse_subunit_v1(&String::from_utf8_lossy(&content))
+    };
+    Ok(tests
+        .into_iter()
+        .map(|test| FailedTest {
+            name: test.name.into(),
+            source: source.clone(),
+            message: test.message.into(),
+            start: test.start,
+            end: test.end,
+            anomalies: Vec::new(),
+        })
+        .collect())
+}
+
+/// A failing test, before it is attached to its source.
+#[derive(Debug, Default, PartialEq)]
+struct TestResult {
+    name: String,
+    message: String,
+    start: Option<u64>,
+    end: Option<u64>,
+}
+
+/// The last line of a failure details, e.g. the assertion error after the traceback.
+fn short_message(details: &str) -> String {
+    details
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())
+        .unwrap_or("")
+        .to_string()
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Get the value of a tag attribute.
+fn xml_attr(tag: &str, name: &str) -> Option<String> {
+    let tag = format!(" {}", tag.replace(['\n', '\t', '\r'], " "));
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let end = tag[start..].find('"')? + start;
+    Some(xml_unescape(&tag[start..end]))
+}
+
+/// Read a junit timestamp, e.g. `2023-06-01T10:00:00` or `2023-06-01T10:00:00.123+00:00`.
+fn parse_time(value: &str) -> Option<u64> {
+    let millis = match DateTime::parse_from_rfc3339(value) {
+        Ok(date) => date.timestamp_millis(),
+        Err(_) => NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+            .ok()?
+            .timestamp_millis(),
+    };
+    u64::try_from(millis).ok()
+}
+
+fn parse_seconds(value: Option<String>) -> u64 {
+    value
+        .and_then(|value| value.parse::<f64>().ok())
+        .map_or(0, |seconds| (seconds * 1000.0) as u64)
+}
+
+/// Decode the failing test cases of a junit XML document.
+fn parse_junit(body: &str) -> Vec<TestResult> {
+    let mut results = Vec::new();
+    for suite in body.split("<testsuite").skip(1) {
+        // Skip the <testsuites> tag.
+        if suite.starts_with('s') {
+            continue;
+        }
+        let suite_tag = &suite[..suite.find('>').unwrap_or(suite.len())];
+        let mut time = xml_attr(suite_tag, "timestamp").and_then(|ts| parse_time(&ts));
+        for case in suite.split("<testcase").skip(1) {
+            let case_end = case.find("</testcase>").unwrap_or(case.len());
+            let case = &case[..case_end];
+            let tag = &case[..case.find('>').unwrap_or(case.len())];
+            let duration = parse_seconds(xml_attr(tag, "time"));
+            let start = time;
+            let end = start.map(|start| start + duration);
+            time = end;
+            let failure = ["<failure", "<error"]
+                .iter()
+                .find_map(|kind| case.find(kind).map(|pos| &case[pos..]));
+            if let Some(failure) = failure {
+                let failure_tag = &failure[..failure.find('>').unwrap_or(failure.len())];
+                let name = xml_attr(tag, "name").unwrap_or_default();
+                let name = match xml_attr(tag, "classname") {
+                    Some(classname) if !classname.is_empty() => format!("{}.{}", classname, name),
+                    _ => name,
+                };
+                let message = match xml_attr(failure_tag, "message") {
+                    Some(message) if !message.is_empty() => short_message(&message),
+                    _ => {
+                        let details = failure_tag
+                            .len()
+                            .checked_add(1)
+                            .and_then(|pos| failure.get(pos..))
+                            .unwrap_or("");
+                        let details = &details[..details.find("</").unwrap_or(details.len())];
+                        short_message(&xml_unescape(
+                            details
+                                .trim_start_matches("<![CDATA[")
+                                .trim_end_matches("]]>"),
+                        ))
+                    }
+                };
+                results.push(TestResult {
+                    name,
+                    message,
+                    start,
+                    end,
+                });
+            }
+        }
+    }
+    results
+}
+
+/// Read a subunit v1 timestamp, e.g. `2023-06-01 10:00:00.123456Z`.
+fn parse_subunit_time(value: &str) -> Option<u64> {
+    let value = value.trim().trim_end_matches('Z');
+    let date = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f").ok()?;
+    u64::try_from(date.timestamp_millis()).ok()
+}
+
+/// Decode the failing tests of a subunit v1 text stream.
+fn parse_subunit_v1(body: &str) -> Vec<TestResult> {
+    let mut results = Vec::new();
+    let mut time = None;
+    let mut starts = BTreeMap::new();
+    let mut details: Option<TestResult> = None;
+    for line in body.lines() {
+        if let Some(result) = details.as_mut() {
+            if line == "]" {
+                result.message = short_message(&result.message);
+                results.extend(details.take());
+            } else {
+                result.message.push_str(line);
+                result.message.push('\n');
+            }
+            continue;
+        }
+        let (command, value) = match line.split_once(": ") {
+            Some(parts) => parts,
+            None => continue,
+        };
+        match command {
+            "time" => time = parse_subunit_time(value),
+            "test" | "testing" => {
+                starts.insert(value.trim().to_string(), time);
+            }
+            "failure" | "error" => {
+                let (name, has_details) = match value.trim().strip_suffix('[') {
+                    Some(name) => (name.trim(), true),
+                    None => (value.trim(), false),
+                };
+                let result = TestResult {
+                    name: name.to_string(),
+                    message: String::new(),
+                    start: starts.get(name).copied().flatten(),
+                    end: time,
+                };
+                if has_details {
+                    details = Some(result);
+                } else {
+                    results.push(result);
+                }
+            }
+            _ => {}
+        }
+    }
+    results
+}
+
+const SUBUNIT_SIGNATURE: u8 = 0xb3;
+const FLAG_TEST_ID: u16 = 0x0800;
+const FLAG_TIMESTAMP: u16 = 0x0200;
+const FLAG_TAGS: u16 = 0x0080;
+const FLAG_MIME_TYPE: u16 = 0x0020;
+const FLAG_FILE_CONTENT: u16 = 0x0040;
+const STATUS_MASK: u16 = 0x0007;
+const STATUS_INPROGRESS: u16 = 2;
+const STATUS_FAIL: u16 = 6;
+
+/// A subunit v2 packet reader.
+struct Packet<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Packet<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8]> {
+        if self.data.len() < size {
+            return Err(anyhow::anyhow!("Truncated subunit packet"));
+        }
+        let (head, tail) = self.data.split_at(size);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn number(&mut self) -> Result<usize> {
+        let first = self.take(1)?[0];
+        let size = usize::from(first >> 6);
+        let rest = self.take(size)?;
+        Ok(rest.iter().fold(usize::from(first & 0x3f), |acc, byte| {
+            (acc << 8) | usize::from(*byte)
+        }))
+    }
+
+    fn utf8(&mut self) -> Result<String> {
+        let size = self.number()?;
+        Ok(String::from_utf8_lossy(self.take(size)?).into_owned())
+    }
+}
+
+/// Decode the failing tests of a subunit v2 binary stream.
+fn parse_subunit_v2(body: &[u8]) -> Result<Vec<TestResult>> {
+    let mut starts = BTreeMap::new();
+    let mut attachments: BTreeMap<String, String> = BTreeMap::new();
+    let mut results = Vec::new();
+    let mut stream = Packet { data: body };
+    while !stream.data.is_empty() {
+        let packet_start = stream.data;
+        if stream.take(1)?[0] != SUBUNIT_SIGNATURE {
+            return Err(anyhow::anyhow!("Invalid subunit packet signature"));
+        }
+        let flags = u16::from_be_bytes(stream.take(2)?.try_into()?);
+        let length = stream.number()?;
+        let header_size = packet_start.len() - stream.data.len();
+        // The length includes the header and the 4 bytes crc.
+        let mut packet = Packet {
+            data: packet_start
+                .get(header_size..length.saturating_sub(4))
+                .ok_or_else(|| anyhow::anyhow!("Invalid subunit packet length"))?,
+        };
+        stream = Packet {
+            data: packet_start
+                .get(length..)
+                .ok_or_else(|| anyhow::anyhow!("Truncated subunit packet"))?,
+        };
+        let timestamp = if flags & FLAG_TIMESTAMP != 0 {
+            let seconds = u32::from_be_bytes(packet.take(4)?.try_into()?);
+            let nanos = packet.number()?;
+            Some(u64::from(seconds) * 1000 + (nanos / 1_000_000) as u64)
+        } else {
+            None
+        };
+        let test_id = if flags & FLAG_TEST_ID != 0 {
+            packet.utf8()?
+        } else {
+            continue;
+        };
+        if flags & FLAG_TAGS != 0 {
+            for _ in 0..packet.number()? {
+                packet.utf8()?;
+            }
+        }
+        if flags & FLAG_MIME_TYPE != 0 {
+            packet.utf8()?;
+        }
+        if flags & FLAG_FILE_CONTENT != 0 {
+            let _file_name = packet.utf8()?;
+            let size = packet.number()?;
+            attachments
+                .entry(test_id.clone())
+                .or_default()
+                .push_str(&String::from_utf8_lossy(packet.take(size)?));
+        }
+        match flags & STATUS_MASK {
+            STATUS_INPROGRESS => {
+                starts.insert(test_id, timestamp);
+            }
+            STATUS_FAIL => results.push(TestResult {
+                start: starts.get(&test_id).copied().flatten(),
+                end: timestamp,
+                message: short_message(attachments.get(&test_id).map_or("", |s| s.as_str())),
+                name: test_id,
+            }),
+            _ => {}
+        }
+    }
+    Ok(results)
+}
+
+#[test]
+fn test_junit() {
+    let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<testsuites>
+  <testsuite name="pytest" errors="0" failures="2" tests="3" timestamp="2023-06-01T10:00:00.000000">
+    <testcase classname="tests.test_api" name="test_list" time="1.5" />
+    <testcase classname="tests.test_api" name="test_create" time="2.0">
+      <failure message="AssertionError: assert 404 == 200">details</failure>
+    </testcase>
+    <testcase classname="tests.test_api" name="test_delete" time="0.5"><error><![CDATA[Traceback
+ConnectionError: refused]]></error></testcase>
+  </testsuite>
+</testsuites>"#;
+    assert_eq!(
+        parse_junit(body),
+        vec![
+            TestResult {
+                name: "tests.test_api.test_create".into(),
+                message: "AssertionError: assert 404 == 200".into(),
+                start: Some(1685613601500),
+                end: Some(1685613603500),
+            },
+            TestResult {
+                name: "tests.test_api.test_delete".into(),
+                message: "ConnectionError: refused".into(),
+                start: Some(1685613603500),
+                end: Some(1685613604000),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_subunit_v1() {
+    let body = "time: 2023-06-01 10:00:00.000000Z
+test: tests.test_api.test_create
+time: 2023-06-01 10:00:02.000000Z
+failure: tests.test_api.test_create [
+Traceback (most recent call last):
+AssertionError: 404
+]
+test: tests.test_api.test_list
+success: tests.test_api.test_list
+";
+    assert_eq!(
+        parse_subunit_v1(body),
+        vec![TestResult {
+            name: "tests.test_api.test_create".into(),
+            message: "AssertionError: 404".into(),
+            start: Some(1685613600000),
+            end: Some(1685613602000),
+        }]
+    );
+}
+
+#[test]
+fn test_subunit_v2() {
+    // Encode a packet with a test id, a timestamp and an optional attachment.
+    let packet = |status: u16, seconds: u32, test_id: &str, attachment: Option<&str>| {
+        let mut flags = 0x2000 | FLAG_TEST_ID | FLAG_TIMESTAMP | status;
+        let mut fields = seconds.to_be_bytes().to_vec();
+        fields.push(0);
+        fields.push(test_id.len() as u8);
+        fields.extend_from_slice(test_id.as_bytes());
+        if let Some(attachment) = attachment {
+            flags |= FLAG_FILE_CONTENT;
+            fields.push("traceback".len() as u8);
+            fields.extend_from_slice(b"traceback");
+            fields.push(attachment.len() as u8);
+            fields.extend_from_slice(attachment.as_bytes());
+        }
+        let length = 1 + 2 + 1 + fields.len() + 4;
+        let mut data = vec![SUBUNIT_SIGNATURE];
+        data.extend_from_slice(&flags.to_be_bytes());
+        data.push(length as u8);
+        data.extend(fields);
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data
+    };
+    let mut body = packet(STATUS_INPROGRESS, 1685613600, "test_create", None);
+    body.extend(packet(
+        0,
+        1685613601,
+        "test_create",
+        Some("AssertionError: 404\n"),
+    ));
+    body.extend(packet(STATUS_FAIL, 1685613602, "test_create", None));
+    body.extend(packet(STATUS_INPROGRESS, 1685613602, "test_list", None));
+    body.extend(packet(3, 1685613603, "test_list", None));
+    assert_eq!(
+        parse_subunit_v2(&body).unwrap(),
+        vec![TestResult {
+            name: "test_create".into(),
+            message: "AssertionError: 404".into(),
+            start: Some(1685613600000),
+            end: Some(1685613602000),
+        }]
+    );
+    assert!(is_results_source(&Source::from_pathbuf(
+        "logs/testrepository.subunit.gz".into()
+    )));
+    assert!(is_results_source(&Source::from_pathbuf(
+        "logs/junit-results.xml".into()
+    )));
+    assert!(!is_results_source(&Source::from_pathbuf(
+        "logs/pom.xml".into()
+    )));
+}
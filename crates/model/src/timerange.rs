@@ -0,0 +1,73 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the time range helpers for the log services queries.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+
+/// The default baseline offset, to compare an incident window with the same window of the previous week.
+pub const BASELINE_OFFSET_DAYS: i64 = 7;
+
+/// Parse a duration such as `90s`, `15m`, `2h`, `1d` or `1w`.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let (value, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let value: i64 = value
+        .parse()
+        .with_context(|| format!("Bad duration {}", s))?;
+    match unit {
+        "s" => Ok(Duration::seconds(value)),
+        "m" => Ok(Duration::minutes(value)),
+        "h" => Ok(Duration::hours(value)),
+        "d" => Ok(Duration::days(value)),
+        "w" => Ok(Duration::weeks(value)),
+        _ => Err(anyhow::anyhow!(
+            "Bad duration {}, expected a number followed by s, m, h, d or w",
+            s
+        )),
+    }
+}
+
+/// Parse a time, either `now`, a RFC 3339 date or a duration ago, e.g. `2h`.
+pub fn parse_time(now: DateTime<Utc>, s: &str) -> Result<DateTime<Utc>> {
+    if s == "now" {
+        Ok(now)
+    } else if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        Ok(dt.with_timezone(&Utc))
+    } else {
+        Ok(now - parse_duration(s)?)
+    }
+}
+
+/// Parse the `since` and `until` time range.
+pub fn parse_range(since: &str, until: &str) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let now = Utc::now();
+    let start = parse_time(now, since)?;
+    let end = parse_time(now, until)?;
+    if start >= end {
+        return Err(anyhow::anyhow!(
+            "The time range start {} is not before its end {}",
+            start,
+            end
+        ));
+    }
+    Ok((start, end))
+}
+
+#[test]
+fn test_time_range() -> Result<()> {
+    let now = DateTime::parse_from_rfc3339("2023-10-15T12:00:00Z")?.with_timezone(&Utc);
+    assert_eq!(parse_time(now, "now")?, now);
+    assert_eq!(
+        parse_time(now, "90m")?.to_rfc3339(),
+        "2023-10-15T10:30:00+00:00"
+    );
+    assert_eq!(
+        parse_time(now, "2023-10-14T08:00:00+02:00")?.to_rfc3339(),
+        "2023-10-14T06:00:00+00:00"
+    );
+    assert!(parse_duration("1y").is_err());
+    assert!(parse_time(now, "yesterday").is_err());
+    assert!(parse_range("1h", "2h").is_err());
+    Ok(())
+}
@@ -8,12 +8,28 @@ use crate::env::Env;
 use crate::{Content, Source};
 
 pub fn content_from_url(env: &Env, url: Url) -> Result<Content> {
-    if !url.has_authority() {
+    if crate::s3::is_s3_url(&url) {
+        crate::s3::content_from_s3_url(url)
+    } else if crate::gcs::is_gs_url(&url) {
+        crate::gcs::content_from_gs_url(url)
+    } else if crate::sftp::is_sftp_url(&url) {
+        crate::sftp::content_from_sftp_url(url)
+    } else if !url.has_authority() {
         Err(anyhow::anyhow!("Bad url {}", url))
     } else if let Some(content) = crate::zuul::content_from_zuul_url(env, &url) {
         content
     } else if let Some(content) = crate::prow::content_from_prow_url(&url) {
         content
+    } else if let Some(content) = crate::jenkins::content_from_jenkins_url(env, &url) {
+        content
+    } else if let Some(content) = crate::buildkite::content_from_buildkite_url(env, &url) {
+        content
+    } else if let Some(content) = crate::circleci::content_from_circleci_url(env, &url) {
+        content
+    } else if let Some(content) = crate::koji::content_from_koji_url(env, &url) {
+        content
+    } else if let Some(content) = crate::obs::content_from_obs_url(env, &url) {
+        content
     } else if url.as_str().ends_with('/') {
         Ok(Content::Directory(Source::Remote(0, url)))
     } else {
@@ -40,7 +56,7 @@ pub fn httpdir_iter(url: &Url, env: &Env) -> Box<dyn Iterator<Item = Result<Sour
         cached
     } else {
         let request_max = 2500;
-        let urls = httpdir::list_with_client(env.client.clone(), request_max, url.clone())
+        let urls = httpdir::list_with_client(env.client_for(url).clone(), request_max, url.clone())
             .into_iter()
             // Convert httpdir error into cachable error
             .map(|url_result| url_result.map_err(|e| format!("{:?}", e).into()))
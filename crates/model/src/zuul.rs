@@ -1,12 +1,18 @@
 // Copyright (C) 2022 Red Hat
 // SPDX-License-Identifier: Apache-2.0
 
+//! This module provides the Zuul builds, where the sources are the build logs.
+//!
+//! The api requests of the configured deployments use the `ZUUL_AUTH_TOKEN` variable, and the `zuul` configuration
+//! sets the token, the headers and the tenant of each deployment.
+
 #[allow(unused_imports)]
 use anyhow::{Context, Result};
 use chrono::{NaiveDate, Utc};
 use itertools::Itertools;
 use url::Url;
 
+use crate::config::ZuulSettings;
 use crate::env::Env;
 use crate::{Baselines, Content, Source};
 use logjuicer_report::{ApiUrl, ZuulBuild};
@@ -154,9 +160,9 @@ fn logs_available(env: &Env, target: &zuul_build::Build) -> bool {
 
 pub fn discover_baselines(build: &ZuulBuild, env: &Env) -> Result<Baselines> {
     let samples = zuul_build_success_samples(build, env)?;
-    let max_builds = 1;
+    let max_builds = env.config.baseline_builds();
     let now = Utc::now().date_naive();
-    Ok(samples
+    let candidates = samples
         .into_iter()
         // Compute a score value
         .map(|target| (baseline_score(build, &target, &now), target))
@@ -164,6 +170,16 @@ pub fn discover_baselines(build: &ZuulBuild, env: &Env) -> Result<Baselines> {
         .filter(|(score, target)| score.is_some() && build.uuid != target.uuid)
         // Order by descending score
         .sorted_by(|(score1, _), (score2, _)| score2.cmp(score1))
+        .collect::<Vec<_>>();
+    let candidates = match env.config.fallback_branches() {
+        // Any branch is used, the build branch having a better score
+        [] => candidates,
+        fallback => select_branch(&build.branch, fallback, candidates, |(_, target)| {
+            target.branch.as_ref()
+        }),
+    };
+    Ok(candidates
+        .into_iter()
         // Filter stalled url
         .filter(|(_, target)| logs_available(env, target))
         // .map(|b| dbg!(b))
@@ -174,30 +190,101 @@ pub fn discover_baselines(build: &ZuulBuild, env: &Env) -> Result<Baselines> {
         .collect())
 }
 
-pub fn sources_iter(build: &ZuulBuild, env: &Env) -> Box<dyn Iterator<Item = Result<Source>>> {
+/// Keep the candidates of the branch, or of the first fallback branch which has some.
+fn select_branch<T>(
+    branch: &str,
+    fallback: &[String],
+    candidates: Vec<T>,
+    get_branch: impl Fn(&T) -> &str,
+) -> Vec<T> {
+    let selected = std::iter::once(branch)
+        .chain(fallback.iter().map(String::as_str))
+        .find(|name| candidates.iter().any(|c| get_branch(c) == *name));
+    match selected {
+        Some(name) => {
+            if name != branch {
+                tracing::info!("Using the {} branch to discover the baselines", name);
+            }
+            candidates
+                .into_iter()
+                .filter(|c| get_branch(c) == name)
+                .collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+fn read_manifest(
+    build: &ZuulBuild,
+    env: &Env,
+) -> Option<Result<zuul_build::zuul_manifest::Manifest>> {
     let prefix = build.log_url.as_str().trim_end_matches('/').len() + 1;
-    if let Ok(reader) = crate::url_open(
+    let reader = crate::url_open(
         env,
         prefix,
         &build.log_url.join("zuul-manifest.json").expect("good url"),
-    ) {
-        match serde_json::from_reader::<_, zuul_build::zuul_manifest::Manifest>(reader) {
-            Err(err) => Box::new(std::iter::once(Err(anyhow::anyhow!(
-                "zuul-manifest decode error: {}",
-                err
-            )))),
-            Ok(manifest) => Box::new(
-                manifest
-                    .to_urls(build.log_url.clone())
-                    .into_iter()
-                    .map(move |url| Ok(Source::Remote(prefix, url))),
-            ),
-        }
+    )
+    .ok()?;
+    Some(
+        serde_json::from_reader(reader)
+            .map_err(|err| anyhow::anyhow!("zuul-manifest decode error: {}", err)),
+    )
+}
+
+/// The failed tasks of the build, when the analysis is restricted to them.
+fn failed_tasks(build: &ZuulBuild, env: &Env) -> Vec<crate::ansible::FailedTask> {
+    if env.config.ansible_failed_tasks() {
+        crate::ansible::read_build_failed_tasks(env, &build.log_url)
+    } else {
+        Vec::new()
+    }
+}
+
+pub fn sources_iter(build: &ZuulBuild, env: &Env) -> Box<dyn Iterator<Item = Result<Source>>> {
+    let tasks = failed_tasks(build, env);
+    if tasks.is_empty() {
+        all_sources_iter(build, env)
     } else {
-        crate::httpdir_iter(&build.log_url, env)
+        Box::new(
+            all_sources_iter(build, env).filter(move |source| match source {
+                Ok(source) => crate::ansible::is_failed_source(&tasks, source),
+                Err(_) => true,
+            }),
+        )
+    }
+}
+
+fn all_sources_iter(build: &ZuulBuild, env: &Env) -> Box<dyn Iterator<Item = Result<Source>>> {
+    let prefix = build.log_url.as_str().trim_end_matches('/').len() + 1;
+    match read_manifest(build, env) {
+        Some(Err(err)) => Box::new(std::iter::once(Err(err))),
+        Some(Ok(manifest)) => Box::new(
+            manifest
+                .to_urls(build.log_url.clone())
+                .into_iter()
+                .map(move |url| Ok(Source::Remote(prefix, url))),
+        ),
+        None => crate::httpdir_iter(&build.log_url, env),
     }
 }
 
+/// Get the sources along with their size recorded in the zuul-manifest, when available.
+pub fn sized_sources(build: &ZuulBuild, env: &Env) -> Option<Vec<(Source, Option<u64>)>> {
+    let prefix = build.log_url.as_str().trim_end_matches('/').len() + 1;
+    let manifest = read_manifest(build, env)?.ok()?;
+    let tasks = failed_tasks(build, env);
+    Some(
+        manifest
+            .to_sized_urls(build.log_url.clone())
+            .into_iter()
+            .map(|(url, size)| (Source::Remote(prefix, url), size))
+            .filter(|(source, _)| {
+                tasks.is_empty() || crate::ansible::is_failed_source(&tasks, source)
+            })
+            .collect(),
+    )
+}
+
 fn new_content(api: ApiUrl, build: zuul_build::Build) -> Content {
     Content::Zuul(Box::new(ZuulBuild {
         api,
@@ -214,9 +301,29 @@ fn new_content(api: ApiUrl, build: zuul_build::Build) -> Content {
     }))
 }
 
+/// Create the request of an api url, with the token and headers of the configured deployment.
+/// The token is not sent to the deployments missing from the configuration.
+fn api_request(env: &Env, url: &Url) -> ureq::Request {
+    let mut request = env.client_for(url).request_url("GET", url);
+    if let Some(settings) = env.config.zuul_settings(url) {
+        let token_env = settings.token_env.as_deref().unwrap_or("ZUUL_AUTH_TOKEN");
+        if let Ok(token) = std::env::var(token_env) {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        for (name, value) in &settings.headers {
+            request = request.set(name, value);
+        }
+    }
+    request
+}
+
+fn api_get(env: &Env, url: &Url) -> Result<crate::reader::DecompressReader> {
+    crate::reader::from_request(env, 0, url, api_request(env, url))
+}
+
 fn get_build(env: &Env, api: &ApiUrl, uuid: &str) -> Result<zuul_build::Build> {
     let url = api.as_url().join(&format!("build/{}", uuid))?;
-    let reader = crate::reader::from_url(env, 0, &url)?;
+    let reader = api_get(env, &url)?;
     match zuul_build::decode_build(reader).context("Can't decode zuul api") {
         Ok(x) => Ok(x),
         Err(e) => crate::reader::drop_url(env, 0, &url).map_or_else(Err, |_| Err(e)),
@@ -224,7 +331,7 @@ fn get_build(env: &Env, api: &ApiUrl, uuid: &str) -> Result<zuul_build::Build> {
 }
 
 fn get_builds(env: &Env, url: &Url) -> Result<Vec<zuul_build::Build>> {
-    let reader = crate::reader::from_url(env, 0, url)?;
+    let reader = api_get(env, url)?;
     match zuul_build::decode_builds(reader).context("Can't decode zuul api") {
         Ok(xs) => Ok(xs),
         Err(e) => crate::reader::drop_url(env, 0, url).map_or_else(Err, |_| Err(e)),
@@ -281,9 +388,31 @@ fn get_zuul_api_url(url: &'_ Url) -> Option<Result<(ApiUrl, &'_ str)>> {
     })
 }
 
+/// The api url set in the deployment settings, instead of the inferred one.
+fn configured_api_url(settings: &ZuulSettings) -> Option<Result<ApiUrl>> {
+    match (&settings.api_url, &settings.tenant) {
+        (Some(api_url), _) => Some(ApiUrl::parse(api_url).context("Invalid zuul api_url")),
+        (None, Some(tenant)) => Some(
+            ApiUrl::parse(&format!(
+                "{}/api/tenant/{}/",
+                settings.url.trim_end_matches('/'),
+                tenant
+            ))
+            .context("Invalid zuul url"),
+        ),
+        (None, None) => None,
+    }
+}
+
 pub fn content_from_zuul_url(env: &Env, url: &Url) -> Option<Result<Content>> {
     get_zuul_api_url(url).map(|res| {
-        res.and_then(|(api, uid)| get_build(env, &api, uid).map(|build| new_content(api, build)))
+        res.and_then(|(api, uid)| {
+            let api = match env.config.zuul_settings(url).and_then(configured_api_url) {
+                Some(configured) => configured?,
+                None => api,
+            };
+            get_build(env, &api, uid).map(|build| new_content(api, build))
+        })
     })
 }
 
@@ -371,3 +500,108 @@ fn test_zuul_api() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_zuul_api_settings() -> Result<()> {
+    let mut server = mockito::Server::new();
+    std::env::set_var("LOGJUICER_TEST_ZUUL_TOKEN", "secret");
+    let env = Env {
+        config: crate::config::config_from_yaml(&format!(
+            "
+zuul:
+  - url: {}/
+    tenant: internal
+    token_env: LOGJUICER_TEST_ZUUL_TOKEN
+    headers:
+      X-Team: ci
+",
+            server.url()
+        )),
+        ..Env::new()
+    };
+    let api_path = "/api/tenant/internal/build/a498f74ab32b49ffa9c9e7463fbf8885";
+    let api_mock = server
+        .mock("GET", api_path)
+        .match_header("authorization", "Bearer secret")
+        .match_header("x-team", "ci")
+        .with_body(
+            r#"{
+              "uuid": "a498f74ab32b49ffa9c9e7463fbf8885",
+              "job_name": "tox",
+              "result": "FAILURE",
+              "voting": true,
+              "log_url": "https://localhost/42",
+              "final": true,
+              "project": "zuul/zuul",
+              "branch": "master",
+              "pipeline": "check",
+              "duration": 42,
+              "ref_url": "https://review.opendev.org/835662",
+              "ref": "refs/changes/94/22894/1",
+              "artifacts": [],
+              "end_time": "2014-07-08T09:10:11",
+              "start_time": "2014-07-05T09:10:11"
+            }"#,
+        )
+        .expect(1)
+        .create();
+
+    let build_url = Url::parse(&format!(
+        "{}/t/public/build/a498f74ab32b49ffa9c9e7463fbf8885",
+        server.url()
+    ))?;
+    crate::reader::drop_url(&env, 0, &Url::parse(&server.url())?.join(api_path)?)?;
+    match content_from_zuul_url(&env, &build_url).unwrap()? {
+        Content::Zuul(build) => assert_eq!(
+            build.api.as_str(),
+            format!("{}/api/tenant/internal/", server.url())
+        ),
+        _ => panic!("Expected a zuul build"),
+    }
+    api_mock.assert();
+    Ok(())
+}
+
+#[test]
+fn test_zuul_api_token() -> Result<()> {
+    let mut server = mockito::Server::new();
+    std::env::set_var("ZUUL_AUTH_TOKEN", "secret");
+    let env = Env {
+        config: crate::config::config_from_yaml(
+            "
+zuul:
+  - url: https://zuul.example.com/
+",
+        ),
+        ..Env::new()
+    };
+    let api_mock = server
+        .mock("GET", "/api/builds")
+        .match_header("authorization", mockito::Matcher::Missing)
+        .with_body("[]")
+        .expect(1)
+        .create();
+    let url = Url::parse(&server.url())?.join("/api/builds")?;
+    api_request(&env, &url).call()?;
+    api_mock.assert();
+
+    let request = api_request(&env, &Url::parse("https://zuul.example.com/api/builds")?);
+    assert_eq!(request.header("authorization"), Some("Bearer secret"));
+    Ok(())
+}
+
+#[test]
+fn test_select_branch() {
+    let candidates = vec![("a", "stable"), ("b", "main"), ("c", "stable")];
+    let fallback = ["main".to_string(), "stable".to_string()];
+    let select = |branch: &str, fallback: &[String]| {
+        select_branch(branch, fallback, candidates.clone(), |(_, branch)| *branch)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(select("stable", &fallback), vec!["a", "c"]);
+    assert_eq!(select("feature", &fallback), vec!["b"]);
+    assert_eq!(select("feature", &fallback[1..]), vec!["a", "c"]);
+    assert!(select("feature", &[]).is_empty());
+}
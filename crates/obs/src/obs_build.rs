@@ -0,0 +1,246 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+#![warn(missing_docs)]
+
+//! This library provides decoders for the [open build service](https://openbuildservice.org/) api.
+//!
+//! The build results and logs are read with the anonymous `/public/` routes,
+//! and the log of the last succeeded build is kept by the service for each target.
+//!
+//! Here is an example usage:
+//!
+//! ```no_run
+//! # fn main() {
+//! let url = url::Url::parse("https://build.opensuse.org/package/show/openSUSE:Factory/bash").unwrap();
+//! let package_url = obs_build::parse_package_url(&url).unwrap();
+//! let client = obs_build::Client::new(package_url.api_url.clone());
+//! for result in client.get_results(&ureq::Agent::new(), &package_url.project, &package_url.package).unwrap() {
+//!     println!("{}/{}: {}", result.repository, result.arch, result.code);
+//! }
+//! # }
+//! ```
+
+use thiserror::Error;
+use url::Url;
+
+/// The obs error.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The provided url is not usable.
+    #[error("bad api url: {0}")]
+    BadUrl(#[from] url::ParseError),
+
+    /// The api reply couldn't be decoded.
+    #[error("bad api reply: {0}")]
+    BadReply(#[from] std::io::Error),
+
+    /// The api query failed.
+    #[error("bad api query: {0}")]
+    BadQuery(#[from] Box<ureq::Error>),
+}
+
+/// The public api url.
+pub const API_URL: &str = "https://api.opensuse.org/";
+
+/// The location of a package build.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackageUrl {
+    /// The api url.
+    pub api_url: Url,
+    /// The project name, e.g. `openSUSE:Factory`.
+    pub project: Box<str>,
+    /// The package name.
+    pub package: Box<str>,
+    /// The repository and architecture of a live build log url.
+    pub target: Option<(Box<str>, Box<str>)>,
+}
+
+/// The api url of a web interface, where the `build.` host prefix is replaced with `api.`.
+fn api_url(url: &Url) -> Option<Url> {
+    let host = url.host_str()?;
+    let api_host = format!("api.{}", host.strip_prefix("build.")?);
+    Url::parse(&format!("{}://{}/", url.scheme(), api_host)).ok()
+}
+
+/// Decode a package page url, such as `https://build.opensuse.org/package/show/project/package`
+/// or `https://build.opensuse.org/package/live_build_log/project/package/repository/arch`.
+pub fn parse_package_url(url: &Url) -> Option<PackageUrl> {
+    let segments = url.path_segments()?.collect::<Vec<_>>();
+    let (project, package, target) = match segments[..] {
+        ["package", "show", project, package] | ["projects", project, "packages", package] => {
+            (project, package, None)
+        }
+        ["package", "live_build_log", project, package, repository, arch] => {
+            (project, package, Some((repository.into(), arch.into())))
+        }
+        _ => return None,
+    };
+    Some(PackageUrl {
+        api_url: api_url(url)?,
+        project: project.into(),
+        package: package.into(),
+        target,
+    })
+}
+
+/// The status of a package build for a repository and architecture.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildResult {
+    /// The repository name, e.g. `openSUSE_Tumbleweed`.
+    pub repository: String,
+    /// The architecture.
+    pub arch: String,
+    /// The package build status, e.g. `succeeded`, `failed` or `unresolvable`.
+    pub code: String,
+}
+
+/// The obs api client.
+#[derive(Clone, Debug)]
+pub struct Client {
+    /// The api url, terminated with a slash.
+    pub api_url: Url,
+}
+
+impl Client {
+    /// Create a client.
+    pub fn new(api_url: Url) -> Client {
+        Client { api_url }
+    }
+
+    /// Get the build results of a package.
+    pub fn get_results(
+        &self,
+        agent: &ureq::Agent,
+        project: &str,
+        package: &str,
+    ) -> Result<Vec<BuildResult>, Error> {
+        let mut url = self
+            .api_url
+            .join(&format!("public/build/{}/_result", project))?;
+        url.query_pairs_mut().append_pair("package", package);
+        tracing::debug!(url = url.as_str(), "Querying obs api");
+        let body = agent
+            .request_url("GET", &url)
+            .call()
+            .map_err(|e| Error::BadQuery(Box::new(e)))?
+            .into_string()?;
+        Ok(parse_results(&body, package))
+    }
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Get the value of a tag attribute.
+fn xml_attr(tag: &str, name: &str) -> Option<String> {
+    let tag = format!(" {}", tag);
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let end = tag[start..].find('"')? + start;
+    Some(xml_unescape(&tag[start..end]))
+}
+
+/// Decode a resultlist, returning the status of the package for each result.
+fn parse_results(body: &str, package: &str) -> Vec<BuildResult> {
+    body.split("<result ")
+        .skip(1)
+        .filter_map(|result| {
+            let tag = &result[..result.find('>')?];
+            let status = result
+                .split("<status ")
+                .skip(1)
+                .map(|status| &status[..status.find('>').unwrap_or(status.len())])
+                .find(|status| xml_attr(status, "package").as_deref() == Some(package))?;
+            Some(BuildResult {
+                repository: xml_attr(tag, "repository")?,
+                arch: xml_attr(tag, "arch")?,
+                code: xml_attr(status, "code")?,
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_package_url() {
+    let url = Url::parse("https://build.opensuse.org/package/show/openSUSE:Factory/bash").unwrap();
+    assert_eq!(
+        parse_package_url(&url),
+        Some(PackageUrl {
+            api_url: Url::parse(API_URL).unwrap(),
+            project: "openSUSE:Factory".into(),
+            package: "bash".into(),
+            target: None,
+        })
+    );
+    let url = Url::parse(
+        "https://build.opensuse.org/package/live_build_log/home:user/bash/openSUSE_Tumbleweed/x86_64",
+    )
+    .unwrap();
+    assert_eq!(
+        parse_package_url(&url).and_then(|package_url| package_url.target),
+        Some(("openSUSE_Tumbleweed".into(), "x86_64".into()))
+    );
+    for url in [
+        "https://build.opensuse.org/package/show/openSUSE:Factory",
+        "https://example.com/package/show/openSUSE:Factory/bash",
+    ] {
+        assert_eq!(
+            parse_package_url(&Url::parse(url).unwrap()),
+            None,
+            "{}",
+            url
+        );
+    }
+}
+
+#[test]
+fn test_get_results() {
+    let mut server = mockito::Server::new();
+    let results_mock = server
+        .mock("GET", "/public/build/home:user/_result")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "package".into(),
+            "bash".into(),
+        ))
+        .with_body(
+            r#"<resultlist state="c0ffee">
+  <result project="home:user" repository="openSUSE_Tumbleweed" arch="x86_64" code="published" state="published">
+    <status package="bash" code="failed">
+      <details>exit status 1</details>
+    </status>
+  </result>
+  <result project="home:user" repository="openSUSE_Tumbleweed" arch="aarch64" code="published" state="published">
+    <status package="bash" code="succeeded"/>
+  </result>
+</resultlist>"#,
+        )
+        .expect(1)
+        .create();
+    let client = Client::new(Url::parse(&format!("{}/", server.url())).unwrap());
+    let results = client
+        .get_results(&ureq::Agent::new(), "home:user", "bash")
+        .unwrap();
+    assert_eq!(
+        results,
+        vec![
+            BuildResult {
+                repository: "openSUSE_Tumbleweed".into(),
+                arch: "x86_64".into(),
+                code: "failed".into(),
+            },
+            BuildResult {
+                repository: "openSUSE_Tumbleweed".into(),
+                arch: "aarch64".into(),
+                code: "succeeded".into(),
+            },
+        ]
+    );
+    results_mock.assert();
+}
@@ -19,6 +19,7 @@ fn mk_report() -> Report {
                 line: line.as_str().into(),
             },
             after: vec!["after".into()],
+            targets: Vec::new(),
         })
     });
     report
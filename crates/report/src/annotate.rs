@@ -0,0 +1,95 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the triage annotations of the anomalies, to persist and share the triage work.
+//!
+//! The annotations are attached to the anomaly fingerprint, so that they are kept when the report is
+//! filtered or merged, and so that they can be applied to the report of a retry.
+
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::diff::AnomalyRef;
+use crate::{Annotation, AnnotationKind, AnomalyContext, Error, LogReport, Report};
+
+impl Annotation {
+    pub fn new(fingerprint: u64, kind: AnnotationKind, note: &str, author: &str) -> Annotation {
+        Annotation {
+            fingerprint,
+            kind,
+            note: note.into(),
+            author: author.into(),
+            created_at: SystemTime::now(),
+        }
+    }
+}
+
+impl Report {
+    /// The annotations of an anomaly, the oldest first.
+    pub fn annotations_of<'a>(
+        &'a self,
+        lr: &LogReport,
+        anomaly: &AnomalyContext,
+    ) -> impl Iterator<Item = &'a Annotation> + 'a {
+        let fingerprint = anomaly.fingerprint(&lr.source);
+        self.annotations
+            .iter()
+            .filter(move |annotation| annotation.fingerprint == fingerprint)
+    }
+
+    /// Find an anomaly using its source relative path and its line number.
+    pub fn find_anomaly(&self, path: &str, pos: usize) -> Option<AnomalyRef<'_>> {
+        let path = path.trim_start_matches('/');
+        self.log_reports
+            .iter()
+            .filter(|lr| lr.source.get_relative().trim_start_matches('/') == path)
+            .find_map(|lr| {
+                lr.anomalies
+                    .iter()
+                    .find(|anomaly| anomaly.anomaly.pos == pos)
+                    .map(|anomaly| (lr, anomaly))
+            })
+    }
+}
+
+/// Annotate the anomaly found at the path and line number of a saved report, and write the report back.
+/// This returns None, without changing the file, when the anomaly is not found.
+pub fn annotate_file(
+    file: &Path,
+    path: &str,
+    pos: usize,
+    kind: AnnotationKind,
+    note: &str,
+    author: &str,
+) -> Result<Option<Annotation>, Error> {
+    let mut report = Report::load(file)?;
+    let fingerprint = match report.find_anomaly(path, pos) {
+        Some((lr, anomaly)) => anomaly.fingerprint(&lr.source),
+        None => return Ok(None),
+    };
+    let annotation = Annotation::new(fingerprint, kind, note, author);
+    report.annotations.push(annotation.clone());
+    report.save(file)?;
+    Ok(Some(annotation))
+}
+
+#[test]
+fn test_annotate() {
+    let mut report = Report::sample();
+    let (lr, anomaly) = report.find_anomaly("c/status", 1).unwrap();
+    let annotation = Annotation::new(
+        anomaly.fingerprint(&lr.source),
+        AnnotationKind::RootCause,
+        "the disk is full",
+        "alice",
+    );
+    assert!(report.find_anomaly("c/status", 2).is_none());
+    report.annotations.push(annotation);
+
+    let (lr, anomaly) = (&report.log_reports[0], &report.log_reports[0].anomalies[0]);
+    let annotations = report
+        .annotations_of(lr, anomaly)
+        .map(|annotation| annotation.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(annotations, vec!["root-cause: the disk is full (alice)"]);
+}
@@ -0,0 +1,95 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the clustering of the similar anomalies, e.g. the same connection error found in many files.
+//!
+//! The anomalies are compared using the Jaccard index of their token sets: an anomaly joins the first cluster
+//! whose first line is similar enough, otherwise it starts a new cluster.
+
+use std::collections::HashSet;
+
+use crate::{AnomalyCluster, LogReport};
+
+/// The minimum token similarity to join a cluster.
+pub const SIMILARITY: f32 = 0.7;
+
+fn tokens(line: &str) -> HashSet<String> {
+    logjuicer_tokenizer::process(line)
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        1.0
+    } else {
+        a.intersection(b).count() as f32 / union as f32
+    }
+}
+
+/// Group the similar anomalies, the clusters with a single anomaly are not returned.
+pub fn compute(log_reports: &[LogReport]) -> Vec<AnomalyCluster> {
+    let mut clusters: Vec<(HashSet<String>, AnomalyCluster)> = Vec::new();
+    for (lr_pos, lr) in log_reports.iter().enumerate() {
+        for (anomaly_pos, anomaly) in lr.anomalies.iter().enumerate() {
+            let anomaly_tokens = tokens(&anomaly.anomaly.line);
+            let member = (lr_pos, anomaly_pos);
+            match clusters.iter_mut().find(|(cluster_tokens, _)| {
+                similarity(cluster_tokens, &anomaly_tokens) >= SIMILARITY
+            }) {
+                Some((_, cluster)) => cluster.members.push(member),
+                None => clusters.push((
+                    anomaly_tokens,
+                    AnomalyCluster {
+                        line: anomaly.anomaly.line.clone(),
+                        members: vec![member],
+                    },
+                )),
+            }
+        }
+    }
+    let mut clusters = clusters
+        .into_iter()
+        .map(|(_, cluster)| cluster)
+        .filter(|cluster| cluster.members.len() > 1)
+        .collect::<Vec<_>>();
+    // The stable sort keeps the first cluster first for the same size.
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.members.len()));
+    clusters
+}
+
+#[test]
+fn test_cluster() {
+    let mut report = crate::Report::sample();
+    let lr = report.log_reports.remove(0);
+    let anomaly = lr.anomalies[0].clone();
+    let mk_lr = |lines: &[&str]| crate::LogReport {
+        anomalies: lines
+            .iter()
+            .map(|line| {
+                let mut anomaly = anomaly.clone();
+                anomaly.anomaly.line = (*line).into();
+                anomaly
+            })
+            .collect(),
+        test_time: lr.test_time,
+        line_count: lr.line_count,
+        byte_count: lr.byte_count,
+        source: lr.source.clone(),
+        index_name: lr.index_name.clone(),
+        category: None,
+        rotated: vec![],
+    };
+    let log_reports = vec![
+        mk_lr(&[
+            "ERROR: connect to 10.0.0.1 port 5432 failed: connection refused",
+            "unexpected keyword argument",
+        ]),
+        mk_lr(&["ERROR: connect to 10.0.0.2 port 5432 failed: connection refused"]),
+    ];
+    let clusters = compute(&log_reports);
+    assert_eq!(clusters.len(), 1);
+    assert_eq!(clusters[0].members, vec![(0, 0), (1, 0)]);
+}
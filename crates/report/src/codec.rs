@@ -79,6 +79,149 @@ impl ReportEncoder {
         }
         module.set_total_line_count(report.total_line_count as u32);
         module.set_total_anomaly_count(report.total_anomaly_count as u32);
+        {
+            let mut builder = module
+                .reborrow()
+                .init_category_policies(report.category_policies.len() as u32);
+            for (idx, policy) in report.category_policies.iter().enumerate() {
+                let mut policy_builder = builder.reborrow().get(idx as u32);
+                policy_builder.set_category(policy.category.as_ref().into());
+                policy_builder.set_threshold(policy.threshold);
+                policy_builder.set_context(policy.context as u32);
+                policy_builder.set_gate(policy.gate);
+            }
+        }
+        {
+            let mut builder = module
+                .reborrow()
+                .init_run_warnings(report.warnings.len() as u32);
+            for (idx, warning) in report.warnings.iter().enumerate() {
+                let mut warning_builder = builder.reborrow().get(idx as u32);
+                warning_builder.set_kind(warning.kind.as_str().into());
+                warning_builder.set_message(warning.message.as_ref().into());
+                if let Some(source) = &warning.source {
+                    self.write_source(source, warning_builder.init_source())?;
+                }
+            }
+        }
+        {
+            let mut builder = module
+                .reborrow()
+                .init_source_stats(report.source_stats.len() as u32);
+            for (idx, stats) in report.source_stats.iter().enumerate() {
+                let mut stats_builder = builder.reborrow().get(idx as u32);
+                stats_builder.set_download_time(write_duration(&stats.download_time)?);
+                stats_builder.set_analysis_time(write_duration(&stats.analysis_time)?);
+                stats_builder.set_byte_count(stats.byte_count as u64);
+                stats_builder.set_line_count(stats.line_count as u32);
+                stats_builder.set_unique_line_count(stats.unique_line_count as u32);
+                self.write_source(&stats.source, stats_builder.init_source())?;
+            }
+        }
+        {
+            let mut builder = module.reborrow().init_summary(report.summary.len() as u32);
+            for (idx, entry) in report.summary.iter().enumerate() {
+                let mut entry_builder = builder.reborrow().get(idx as u32);
+                entry_builder.set_fingerprint(entry.fingerprint);
+                entry_builder.set_pos(entry.pos as u32);
+                entry_builder.set_line(entry.line.as_ref().into());
+                entry_builder.set_distance(entry.distance);
+                entry_builder.set_count(entry.count as u32);
+                self.write_source(&entry.source, entry_builder.init_source())?;
+            }
+        }
+        {
+            let mut builder = module
+                .reborrow()
+                .init_clusters(report.clusters.len() as u32);
+            for (idx, cluster) in report.clusters.iter().enumerate() {
+                let mut cluster_builder = builder.reborrow().get(idx as u32);
+                cluster_builder.set_line(cluster.line.as_ref().into());
+                let mut members_builder =
+                    cluster_builder.init_members(cluster.members.len() as u32);
+                for (idx, (lr_pos, anomaly_pos)) in cluster.members.iter().enumerate() {
+                    let mut member_builder = members_builder.reborrow().get(idx as u32);
+                    member_builder.set_log_report(*lr_pos as u32);
+                    member_builder.set_anomaly(*anomaly_pos as u32);
+                }
+            }
+        }
+        {
+            let mut builder = module
+                .reborrow()
+                .init_annotations(report.annotations.len() as u32);
+            for (idx, annotation) in report.annotations.iter().enumerate() {
+                let mut annotation_builder = builder.reborrow().get(idx as u32);
+                annotation_builder.set_fingerprint(annotation.fingerprint);
+                annotation_builder.set_kind(annotation.kind.as_str().into());
+                annotation_builder.set_note(annotation.note.as_ref().into());
+                annotation_builder.set_author(annotation.author.as_ref().into());
+                annotation_builder.set_created_at(write_system_time(&annotation.created_at)?);
+            }
+        }
+        {
+            let mut builder = module
+                .reborrow()
+                .init_link_templates(report.link_templates.len() as u32);
+            for (idx, link) in report.link_templates.iter().enumerate() {
+                let mut link_builder = builder.reborrow().get(idx as u32);
+                link_builder.set_provider(link.provider.as_ref().into());
+                link_builder.set_template(link.template.as_ref().into());
+            }
+        }
+        {
+            let mut builder = module
+                .reborrow()
+                .init_failed_tests(report.failed_tests.len() as u32);
+            for (idx, test) in report.failed_tests.iter().enumerate() {
+                let mut test_builder = builder.reborrow().get(idx as u32);
+                test_builder.set_name(test.name.as_ref().into());
+                test_builder.set_message(test.message.as_ref().into());
+                test_builder.set_start(test.start.unwrap_or(0));
+                test_builder.set_end(test.end.unwrap_or(0));
+                self.write_source(&test.source, test_builder.reborrow().init_source())?;
+                let mut anomalies_builder =
+                    test_builder.init_anomalies(test.anomalies.len() as u32);
+                for (idx, (lr_pos, anomaly_pos)) in test.anomalies.iter().enumerate() {
+                    let mut member_builder = anomalies_builder.reborrow().get(idx as u32);
+                    member_builder.set_log_report(*lr_pos as u32);
+                    member_builder.set_anomaly(*anomaly_pos as u32);
+                }
+            }
+        }
+        if let Some(provenance) = &report.provenance {
+            let mut builder = module.reborrow().init_provenance();
+            builder.set_version(provenance.version.as_ref().into());
+            builder.set_model_hash(provenance.model_hash.as_ref().into());
+            builder.set_model_created_at(write_system_time(&provenance.model_created_at)?);
+            builder.set_trainer(provenance.trainer.as_ref().into());
+            builder.set_baseline_count(provenance.baseline_count as u32);
+            self.write_sources(
+                &provenance.baseline_sources,
+                builder.init_baseline_sources(provenance.baseline_sources.len() as u32),
+            )?;
+        }
+        if !report.merged_targets.is_empty() {
+            let mut builder = module
+                .reborrow()
+                .init_merged_targets(report.merged_targets.len() as u32);
+            for (idx, content) in report.merged_targets.iter().enumerate() {
+                let content_builder = builder.reborrow().get(idx as u32);
+                self.write_content(content, content_builder)?;
+            }
+        }
+        capnp::serialize::write_message(write, &message)
+    }
+
+    /// Encode a single log report, for the streaming writer.
+    pub fn encode_log_report(
+        &self,
+        log_report: &LogReport,
+        write: impl capnp::io::Write,
+    ) -> Result<()> {
+        let mut message = capnp::message::Builder::new_default();
+        let mut builder = message.init_root::<schema_capnp::log_report::Builder>();
+        self.write_log_report(log_report, &mut builder)?;
         capnp::serialize::write_message(write, &message)
     }
 
@@ -101,6 +244,17 @@ impl ReportEncoder {
         }
         self.write_source(&log_report.source, builder.reborrow().init_source())?;
         builder.set_index_name(log_report.index_name.as_str().into());
+        if let Some(category) = &log_report.category {
+            builder.set_category(category.as_ref().into());
+        }
+        if !log_report.rotated.is_empty() {
+            self.write_sources(
+                &log_report.rotated,
+                builder
+                    .reborrow()
+                    .init_rotated(log_report.rotated.len() as u32),
+            )?;
+        }
         Ok(())
     }
 
@@ -129,6 +283,14 @@ impl ReportEncoder {
                 builder.set(idx as u32, ctx.as_ref().into());
             }
         }
+        if !anomaly_context.targets.is_empty() {
+            let mut builder = builder
+                .reborrow()
+                .init_targets(anomaly_context.targets.len() as u32);
+            for (idx, target) in anomaly_context.targets.iter().enumerate() {
+                builder.set(idx as u32, *target as u32);
+            }
+        }
         Ok(())
     }
 
@@ -154,6 +316,22 @@ impl ReportEncoder {
             Content::Directory(source) => self.write_source(source, builder.init_dir()),
             Content::Zuul(build) => self.write_zuul(build, builder.init_zuul()),
             Content::Prow(build) => self.write_prow(build, builder.init_prow()),
+            Content::Jenkins(build) => self.write_jenkins(build, builder.init_jenkins()),
+            Content::Kubernetes(pod) => self.write_kubernetes(pod, builder.init_kubernetes()),
+            Content::Loki(query) => self.write_loki(query, builder.init_loki()),
+            Content::Elasticsearch(query) => {
+                self.write_elasticsearch(query, builder.init_elasticsearch())
+            }
+            Content::CloudWatch(query) => self.write_cloud_watch(query, builder.init_cloud_watch()),
+            Content::Journal(query) => self.write_journal(query, builder.init_journal()),
+            Content::Container(container) => {
+                self.write_container(container, builder.init_container())
+            }
+            Content::Tekton(run) => self.write_tekton(run, builder.init_tekton()),
+            Content::Buildkite(build) => self.write_buildkite(build, builder.init_buildkite()),
+            Content::CircleCi(job) => self.write_circle_ci(job, builder.init_circle_ci()),
+            Content::Koji(build) => self.write_koji(build, builder.init_koji()),
+            Content::Obs(build) => self.write_obs(build, builder.init_obs()),
             Content::LocalZuulBuild(path, build) => {
                 let mut builder = builder.init_local_zuul();
                 builder.set_path(
@@ -201,6 +379,189 @@ impl ReportEncoder {
         Ok(())
     }
 
+    fn write_jenkins(
+        &self,
+        jenkins: &JenkinsBuild,
+        mut builder: schema_capnp::content::jenkins::Builder,
+    ) -> Result<()> {
+        builder.set_url(jenkins.url.as_str().into());
+        builder.set_job_name(jenkins.job_name.as_ref().into());
+        builder.set_number(jenkins.number);
+        builder.set_result(jenkins.result.as_ref().into());
+        Ok(())
+    }
+
+    fn write_kubernetes(
+        &self,
+        pod: &KubernetesPod,
+        mut builder: schema_capnp::content::kubernetes::Builder,
+    ) -> Result<()> {
+        builder.set_api_url(pod.api_url.as_str().into());
+        builder.set_namespace(pod.namespace.as_ref().into());
+        builder.set_pod(pod.pod.as_ref().into());
+        builder.set_previous(pod.previous);
+        let mut containers = builder.init_containers(pod.containers.len() as u32);
+        for (idx, container) in pod.containers.iter().enumerate() {
+            containers.set(idx as u32, container.as_ref().into());
+        }
+        Ok(())
+    }
+
+    fn write_loki(
+        &self,
+        query: &LokiQuery,
+        mut builder: schema_capnp::content::loki::Builder,
+    ) -> Result<()> {
+        builder.set_url(query.url.as_str().into());
+        builder.set_query(query.query.as_ref().into());
+        builder.set_start(write_datetime(&query.start)?);
+        builder.set_end(write_datetime(&query.end)?);
+        Ok(())
+    }
+
+    fn write_elasticsearch(
+        &self,
+        query: &ElasticQuery,
+        mut builder: schema_capnp::content::elasticsearch::Builder,
+    ) -> Result<()> {
+        builder.set_url(query.url.as_str().into());
+        builder.set_index(query.index.as_ref().into());
+        builder.set_query(query.query.as_ref().into());
+        builder.set_message_field(query.message_field.as_ref().into());
+        builder.set_time_field(query.time_field.as_ref().into());
+        builder.set_start(write_datetime(&query.start)?);
+        builder.set_end(write_datetime(&query.end)?);
+        Ok(())
+    }
+
+    fn write_cloud_watch(
+        &self,
+        query: &CloudWatchQuery,
+        mut builder: schema_capnp::content::cloud_watch::Builder,
+    ) -> Result<()> {
+        builder.set_log_group(query.log_group.as_ref().into());
+        builder.set_start(write_datetime(&query.start)?);
+        builder.set_end(write_datetime(&query.end)?);
+        let mut streams = builder.init_log_streams(query.log_streams.len() as u32);
+        for (idx, stream) in query.log_streams.iter().enumerate() {
+            streams.set(idx as u32, stream.as_ref().into());
+        }
+        Ok(())
+    }
+
+    fn write_journal(
+        &self,
+        query: &JournalQuery,
+        mut builder: schema_capnp::content::journal::Builder,
+    ) -> Result<()> {
+        builder.set_boot_id(query.boot_id.as_deref().unwrap_or("").into());
+        if let (Some(start), Some(end)) = (&query.start, &query.end) {
+            builder.set_start(write_datetime(start)?);
+            builder.set_end(write_datetime(end)?);
+        }
+        let mut units = builder.init_units(query.units.len() as u32);
+        for (idx, unit) in query.units.iter().enumerate() {
+            units.set(idx as u32, unit.as_ref().into());
+        }
+        Ok(())
+    }
+
+    fn write_container(
+        &self,
+        container: &Container,
+        mut builder: schema_capnp::content::container::Builder,
+    ) -> Result<()> {
+        builder.set_host(container.host.as_ref().into());
+        builder.set_id(container.id.as_ref().into());
+        builder.set_name(container.name.as_ref().into());
+        builder.set_image(container.image.as_ref().into());
+        Ok(())
+    }
+
+    fn write_tekton(
+        &self,
+        run: &TektonPipelineRun,
+        mut builder: schema_capnp::content::tekton::Builder,
+    ) -> Result<()> {
+        builder.set_api_url(run.api_url.as_str().into());
+        builder.set_namespace(run.namespace.as_ref().into());
+        builder.set_pipeline_run(run.pipeline_run.as_ref().into());
+        builder.set_pipeline(run.pipeline.as_ref().into());
+        let mut steps = builder.init_steps(run.steps.len() as u32);
+        for (idx, step) in run.steps.iter().enumerate() {
+            let mut builder = steps.reborrow().get(idx as u32);
+            builder.set_task(step.task.as_ref().into());
+            builder.set_pod(step.pod.as_ref().into());
+            builder.set_container(step.container.as_ref().into());
+        }
+        Ok(())
+    }
+
+    fn write_buildkite(
+        &self,
+        build: &BuildkiteBuild,
+        mut builder: schema_capnp::content::buildkite::Builder,
+    ) -> Result<()> {
+        builder.set_url(build.url.as_str().into());
+        builder.set_org(build.org.as_ref().into());
+        builder.set_pipeline(build.pipeline.as_ref().into());
+        builder.set_number(build.number);
+        builder.set_branch(build.branch.as_ref().into());
+        builder.set_state(build.state.as_ref().into());
+        Ok(())
+    }
+
+    fn write_circle_ci(
+        &self,
+        job: &CircleCiJob,
+        mut builder: schema_capnp::content::circle_ci::Builder,
+    ) -> Result<()> {
+        builder.set_slug(job.slug.as_ref().into());
+        builder.set_number(job.number);
+        builder.set_job_name(job.job_name.as_ref().into());
+        builder.set_workflow(job.workflow.as_ref().into());
+        builder.set_branch(job.branch.as_ref().into());
+        builder.set_status(job.status.as_ref().into());
+        Ok(())
+    }
+
+    fn write_koji(
+        &self,
+        build: &KojiBuild,
+        mut builder: schema_capnp::content::koji::Builder,
+    ) -> Result<()> {
+        builder.set_url(build.url.as_str().into());
+        builder.set_hub_url(build.hub_url.as_str().into());
+        builder.set_package(build.package.as_ref().into());
+        builder.set_nvr(build.nvr.as_ref().into());
+        builder.set_state(build.state.as_ref().into());
+        let mut logs = builder.init_logs(build.logs.len() as u32);
+        for (idx, log) in build.logs.iter().enumerate() {
+            let mut builder = logs.reborrow().get(idx as u32);
+            builder.set_arch(log.arch.as_ref().into());
+            builder.set_url(log.url.as_str().into());
+        }
+        Ok(())
+    }
+
+    fn write_obs(
+        &self,
+        build: &ObsBuild,
+        mut builder: schema_capnp::content::obs::Builder,
+    ) -> Result<()> {
+        builder.set_api_url(build.api_url.as_str().into());
+        builder.set_project(build.project.as_ref().into());
+        builder.set_package(build.package.as_ref().into());
+        builder.set_last_succeeded(build.last_succeeded);
+        let mut targets = builder.init_targets(build.targets.len() as u32);
+        for (idx, target) in build.targets.iter().enumerate() {
+            let mut builder = targets.reborrow().get(idx as u32);
+            builder.set_repository(target.repository.as_ref().into());
+            builder.set_arch(target.arch.as_ref().into());
+        }
+        Ok(())
+    }
+
     fn write_source(&self, source: &Source, builder: schema_capnp::source::Builder) -> Result<()> {
         match source {
             Source::Local(prefix, path) => {
@@ -284,6 +645,14 @@ impl ReportDecoder {
         Self
     }
 
+    /// Decode a single log report, see [ReportEncoder::encode_log_report].
+    pub fn decode_log_report(&self, reader: impl BufRead) -> Result<LogReport> {
+        let message_reader =
+            capnp::serialize::read_message(reader, capnp::message::ReaderOptions::new())?;
+        let reader = message_reader.get_root::<schema_capnp::log_report::Reader<'_>>()?;
+        self.read_log_report(&reader)
+    }
+
     pub fn decode(&self, reader: impl BufRead) -> Result<Report> {
         let message_reader =
             capnp::serialize::read_message(reader, capnp::message::ReaderOptions::new())?;
@@ -294,17 +663,35 @@ impl ReportDecoder {
                 .ok_or(capnp::Error::failed("Bad time".into()))?,
             run_time: read_duration(reader.get_run_time()),
             target: self.read_content(&reader.get_target()?)?,
-            baselines: self.read_baselines(&reader.get_baselines()?)?,
+            baselines: self.read_contents(&reader.get_baselines()?)?,
             log_reports: self.read_log_reports(&reader.get_log_reports()?)?,
             index_reports: read_hashmap!(reader.get_index_reports()?, self, read_index_report),
             unknown_files: read_hashmap!(reader.get_unknown_files()?, self, read_sources),
             read_errors: self.read_errors(&reader.get_read_errors()?)?,
             total_line_count: reader.get_total_line_count() as usize,
             total_anomaly_count: reader.get_total_anomaly_count() as usize,
+            category_policies: self.read_category_policies(&reader.get_category_policies()?)?,
+            warnings: self.read_warnings(&reader.get_run_warnings()?)?,
+            merged_targets: if reader.has_merged_targets() {
+                self.read_contents(&reader.get_merged_targets()?)?
+            } else {
+                Vec::new()
+            },
+            source_stats: self.read_source_stats(&reader.get_source_stats()?)?,
+            provenance: if reader.has_provenance() {
+                Some(self.read_provenance(&reader.get_provenance()?)?)
+            } else {
+                None
+            },
+            summary: self.read_summary(&reader.get_summary()?)?,
+            clusters: self.read_clusters(&reader.get_clusters()?)?,
+            annotations: self.read_annotations(&reader.get_annotations()?)?,
+            link_templates: self.read_link_templates(&reader.get_link_templates()?)?,
+            failed_tests: self.read_failed_tests(&reader.get_failed_tests()?)?,
         })
     }
 
-    fn read_baselines(
+    fn read_contents(
         &self,
         reader: &capnp::struct_list::Reader<schema_capnp::content::Owned>,
     ) -> Result<Vec<Content>> {
@@ -334,6 +721,16 @@ impl ReportDecoder {
             anomalies: self.read_anomalies(&reader.get_anomalies()?)?,
             source: self.read_source(&reader.get_source()?)?,
             index_name: IndexName(reader.get_index_name()?.to_str()?.into()),
+            category: if reader.has_category() {
+                Some(reader.get_category()?.to_str()?.into())
+            } else {
+                None
+            },
+            rotated: if reader.has_rotated() {
+                self.read_sources(&reader.get_rotated()?)?
+            } else {
+                Vec::new()
+            },
         })
     }
 
@@ -356,6 +753,15 @@ impl ReportDecoder {
             before: self.read_context(&reader.get_before()?)?,
             anomaly: self.read_anomaly(&reader.get_anomaly()?)?,
             after: self.read_context(&reader.get_after()?)?,
+            targets: if reader.has_targets() {
+                reader
+                    .get_targets()?
+                    .iter()
+                    .map(|target| target as usize)
+                    .collect()
+            } else {
+                Vec::new()
+            },
         })
     }
 
@@ -388,6 +794,28 @@ impl ReportDecoder {
                 let path = reader.get_path()?.to_str()?.into();
                 Content::LocalZuulBuild(path, Box::new(self.read_zuul(&reader.get_build()?)?))
             }
+            Which::Jenkins(reader) => Content::Jenkins(Box::new(self.read_jenkins(&reader?)?)),
+            Which::Kubernetes(reader) => {
+                Content::Kubernetes(Box::new(self.read_kubernetes(&reader?)?))
+            }
+            Which::Loki(reader) => Content::Loki(Box::new(self.read_loki(&reader?)?)),
+            Which::Elasticsearch(reader) => {
+                Content::Elasticsearch(Box::new(self.read_elasticsearch(&reader?)?))
+            }
+            Which::CloudWatch(reader) => {
+                Content::CloudWatch(Box::new(self.read_cloud_watch(&reader?)?))
+            }
+            Which::Journal(reader) => Content::Journal(Box::new(self.read_journal(&reader?)?)),
+            Which::Container(reader) => {
+                Content::Container(Box::new(self.read_container(&reader?)?))
+            }
+            Which::Tekton(reader) => Content::Tekton(Box::new(self.read_tekton(&reader?)?)),
+            Which::Buildkite(reader) => {
+                Content::Buildkite(Box::new(self.read_buildkite(&reader?)?))
+            }
+            Which::CircleCi(reader) => Content::CircleCi(Box::new(self.read_circle_ci(&reader?)?)),
+            Which::Koji(reader) => Content::Koji(Box::new(self.read_koji(&reader?)?)),
+            Which::Obs(reader) => Content::Obs(Box::new(self.read_obs(&reader?)?)),
         })
     }
 
@@ -419,6 +847,192 @@ impl ReportDecoder {
         })
     }
 
+    fn read_jenkins(
+        &self,
+        reader: &schema_capnp::content::jenkins::Reader,
+    ) -> Result<JenkinsBuild> {
+        Ok(JenkinsBuild {
+            url: read_url(reader.get_url()?)?,
+            job_name: reader.get_job_name()?.to_str()?.into(),
+            number: reader.get_number(),
+            result: reader.get_result()?.to_str()?.into(),
+        })
+    }
+
+    fn read_kubernetes(
+        &self,
+        reader: &schema_capnp::content::kubernetes::Reader,
+    ) -> Result<KubernetesPod> {
+        let mut containers = Vec::new();
+        for container in reader.get_containers()?.into_iter() {
+            containers.push(container?.to_str()?.into())
+        }
+        Ok(KubernetesPod {
+            api_url: read_url(reader.get_api_url()?)?,
+            namespace: reader.get_namespace()?.to_str()?.into(),
+            pod: reader.get_pod()?.to_str()?.into(),
+            containers,
+            previous: reader.get_previous(),
+        })
+    }
+
+    fn read_loki(&self, reader: &schema_capnp::content::loki::Reader) -> Result<LokiQuery> {
+        Ok(LokiQuery {
+            url: read_url(reader.get_url()?)?,
+            query: reader.get_query()?.to_str()?.into(),
+            start: read_datetime(reader.get_start())?,
+            end: read_datetime(reader.get_end())?,
+        })
+    }
+
+    fn read_elasticsearch(
+        &self,
+        reader: &schema_capnp::content::elasticsearch::Reader,
+    ) -> Result<ElasticQuery> {
+        Ok(ElasticQuery {
+            url: read_url(reader.get_url()?)?,
+            index: reader.get_index()?.to_str()?.into(),
+            query: reader.get_query()?.to_str()?.into(),
+            message_field: reader.get_message_field()?.to_str()?.into(),
+            time_field: reader.get_time_field()?.to_str()?.into(),
+            start: read_datetime(reader.get_start())?,
+            end: read_datetime(reader.get_end())?,
+        })
+    }
+
+    fn read_cloud_watch(
+        &self,
+        reader: &schema_capnp::content::cloud_watch::Reader,
+    ) -> Result<CloudWatchQuery> {
+        let mut log_streams = Vec::new();
+        for stream in reader.get_log_streams()?.into_iter() {
+            log_streams.push(stream?.to_str()?.into())
+        }
+        Ok(CloudWatchQuery {
+            log_group: reader.get_log_group()?.to_str()?.into(),
+            log_streams,
+            start: read_datetime(reader.get_start())?,
+            end: read_datetime(reader.get_end())?,
+        })
+    }
+
+    fn read_journal(
+        &self,
+        reader: &schema_capnp::content::journal::Reader,
+    ) -> Result<JournalQuery> {
+        let mut units = Vec::new();
+        for unit in reader.get_units()?.into_iter() {
+            units.push(unit?.to_str()?.into())
+        }
+        let boot_id = reader.get_boot_id()?.to_str()?;
+        let (start, end) = match (reader.get_start(), reader.get_end()) {
+            (0, 0) => (None, None),
+            (start, end) => (Some(read_datetime(start)?), Some(read_datetime(end)?)),
+        };
+        Ok(JournalQuery {
+            units,
+            boot_id: (!boot_id.is_empty()).then(|| boot_id.into()),
+            start,
+            end,
+        })
+    }
+
+    fn read_container(
+        &self,
+        reader: &schema_capnp::content::container::Reader,
+    ) -> Result<Container> {
+        Ok(Container {
+            host: reader.get_host()?.to_str()?.into(),
+            id: reader.get_id()?.to_str()?.into(),
+            name: reader.get_name()?.to_str()?.into(),
+            image: reader.get_image()?.to_str()?.into(),
+        })
+    }
+
+    fn read_tekton(
+        &self,
+        reader: &schema_capnp::content::tekton::Reader,
+    ) -> Result<TektonPipelineRun> {
+        let mut steps = Vec::new();
+        for step in reader.get_steps()?.into_iter() {
+            steps.push(TektonStep {
+                task: step.get_task()?.to_str()?.into(),
+                pod: step.get_pod()?.to_str()?.into(),
+                container: step.get_container()?.to_str()?.into(),
+            })
+        }
+        Ok(TektonPipelineRun {
+            api_url: read_url(reader.get_api_url()?)?,
+            namespace: reader.get_namespace()?.to_str()?.into(),
+            pipeline_run: reader.get_pipeline_run()?.to_str()?.into(),
+            pipeline: reader.get_pipeline()?.to_str()?.into(),
+            steps,
+        })
+    }
+
+    fn read_buildkite(
+        &self,
+        reader: &schema_capnp::content::buildkite::Reader,
+    ) -> Result<BuildkiteBuild> {
+        Ok(BuildkiteBuild {
+            url: read_url(reader.get_url()?)?,
+            org: reader.get_org()?.to_str()?.into(),
+            pipeline: reader.get_pipeline()?.to_str()?.into(),
+            number: reader.get_number(),
+            branch: reader.get_branch()?.to_str()?.into(),
+            state: reader.get_state()?.to_str()?.into(),
+        })
+    }
+
+    fn read_circle_ci(
+        &self,
+        reader: &schema_capnp::content::circle_ci::Reader,
+    ) -> Result<CircleCiJob> {
+        Ok(CircleCiJob {
+            slug: reader.get_slug()?.to_str()?.into(),
+            number: reader.get_number(),
+            job_name: reader.get_job_name()?.to_str()?.into(),
+            workflow: reader.get_workflow()?.to_str()?.into(),
+            branch: reader.get_branch()?.to_str()?.into(),
+            status: reader.get_status()?.to_str()?.into(),
+        })
+    }
+
+    fn read_koji(&self, reader: &schema_capnp::content::koji::Reader) -> Result<KojiBuild> {
+        let mut logs = Vec::new();
+        for log in reader.get_logs()?.into_iter() {
+            logs.push(KojiLogs {
+                arch: log.get_arch()?.to_str()?.into(),
+                url: read_url(log.get_url()?)?,
+            })
+        }
+        Ok(KojiBuild {
+            url: read_url(reader.get_url()?)?,
+            hub_url: read_url(reader.get_hub_url()?)?,
+            package: reader.get_package()?.to_str()?.into(),
+            nvr: reader.get_nvr()?.to_str()?.into(),
+            state: reader.get_state()?.to_str()?.into(),
+            logs,
+        })
+    }
+
+    fn read_obs(&self, reader: &schema_capnp::content::obs::Reader) -> Result<ObsBuild> {
+        let mut targets = Vec::new();
+        for target in reader.get_targets()?.into_iter() {
+            targets.push(ObsTarget {
+                repository: target.get_repository()?.to_str()?.into(),
+                arch: target.get_arch()?.to_str()?.into(),
+            })
+        }
+        Ok(ObsBuild {
+            api_url: read_url(reader.get_api_url()?)?,
+            project: reader.get_project()?.to_str()?.into(),
+            package: reader.get_package()?.to_str()?.into(),
+            targets,
+            last_succeeded: reader.get_last_succeeded(),
+        })
+    }
+
     fn read_source(&self, reader: &schema_capnp::source::Reader) -> Result<Source> {
         use schema_capnp::source::Which;
         Ok(match reader.which()? {
@@ -456,6 +1070,172 @@ impl ReportDecoder {
             sources: self.read_sources(&reader.get_sources()?)?,
         })
     }
+    fn read_category_policies(
+        &self,
+        reader: &capnp::struct_list::Reader<schema_capnp::category_policy::Owned>,
+    ) -> Result<Vec<CategoryPolicy>> {
+        let mut vec = Vec::with_capacity(reader.len() as usize);
+        for reader in reader.into_iter() {
+            vec.push(CategoryPolicy {
+                category: reader.get_category()?.to_str()?.into(),
+                threshold: reader.get_threshold(),
+                context: reader.get_context() as usize,
+                gate: reader.get_gate(),
+            });
+        }
+        Ok(vec)
+    }
+
+    fn read_warnings(
+        &self,
+        reader: &capnp::struct_list::Reader<schema_capnp::run_warning::Owned>,
+    ) -> Result<Vec<RunWarning>> {
+        let mut vec = Vec::with_capacity(reader.len() as usize);
+        for reader in reader.into_iter() {
+            let source = if reader.has_source() {
+                Some(self.read_source(&reader.get_source()?)?)
+            } else {
+                None
+            };
+            vec.push(RunWarning {
+                kind: reader.get_kind()?.to_str()?.into(),
+                message: reader.get_message()?.to_str()?.into(),
+                source,
+            });
+        }
+        Ok(vec)
+    }
+
+    fn read_provenance(&self, reader: &schema_capnp::provenance::Reader) -> Result<Provenance> {
+        Ok(Provenance {
+            version: reader.get_version()?.to_str()?.into(),
+            model_hash: reader.get_model_hash()?.to_str()?.into(),
+            model_created_at: read_system_time(reader.get_model_created_at())
+                .ok_or(capnp::Error::failed("Bad time".into()))?,
+            trainer: reader.get_trainer()?.to_str()?.into(),
+            baseline_sources: self.read_sources(&reader.get_baseline_sources()?)?,
+            baseline_count: reader.get_baseline_count() as usize,
+        })
+    }
+
+    fn read_failed_tests(
+        &self,
+        reader: &capnp::struct_list::Reader<schema_capnp::failed_test::Owned>,
+    ) -> Result<Vec<FailedTest>> {
+        let mut vec = Vec::with_capacity(reader.len() as usize);
+        for reader in reader.into_iter() {
+            vec.push(FailedTest {
+                name: reader.get_name()?.to_str()?.into(),
+                source: self.read_source(&reader.get_source()?)?,
+                message: reader.get_message()?.to_str()?.into(),
+                start: Some(reader.get_start()).filter(|ts| *ts > 0),
+                end: Some(reader.get_end()).filter(|ts| *ts > 0),
+                anomalies: reader
+                    .get_anomalies()?
+                    .into_iter()
+                    .map(|member| {
+                        (
+                            member.get_log_report() as usize,
+                            member.get_anomaly() as usize,
+                        )
+                    })
+                    .collect(),
+            });
+        }
+        Ok(vec)
+    }
+
+    fn read_link_templates(
+        &self,
+        reader: &capnp::struct_list::Reader<schema_capnp::link_template::Owned>,
+    ) -> Result<Vec<LinkTemplate>> {
+        let mut vec = Vec::with_capacity(reader.len() as usize);
+        for reader in reader.into_iter() {
+            vec.push(LinkTemplate {
+                provider: reader.get_provider()?.to_str()?.into(),
+                template: reader.get_template()?.to_str()?.into(),
+            });
+        }
+        Ok(vec)
+    }
+
+    fn read_annotations(
+        &self,
+        reader: &capnp::struct_list::Reader<schema_capnp::annotation::Owned>,
+    ) -> Result<Vec<Annotation>> {
+        let mut vec = Vec::with_capacity(reader.len() as usize);
+        for reader in reader.into_iter() {
+            vec.push(Annotation {
+                fingerprint: reader.get_fingerprint(),
+                kind: reader.get_kind()?.to_str()?.into(),
+                note: reader.get_note()?.to_str()?.into(),
+                author: reader.get_author()?.to_str()?.into(),
+                created_at: read_system_time(reader.get_created_at())
+                    .ok_or(capnp::Error::failed("Bad time".into()))?,
+            });
+        }
+        Ok(vec)
+    }
+
+    fn read_clusters(
+        &self,
+        reader: &capnp::struct_list::Reader<schema_capnp::anomaly_cluster::Owned>,
+    ) -> Result<Vec<AnomalyCluster>> {
+        let mut vec = Vec::with_capacity(reader.len() as usize);
+        for reader in reader.into_iter() {
+            vec.push(AnomalyCluster {
+                line: reader.get_line()?.to_str()?.into(),
+                members: reader
+                    .get_members()?
+                    .into_iter()
+                    .map(|member| {
+                        (
+                            member.get_log_report() as usize,
+                            member.get_anomaly() as usize,
+                        )
+                    })
+                    .collect(),
+            });
+        }
+        Ok(vec)
+    }
+
+    fn read_summary(
+        &self,
+        reader: &capnp::struct_list::Reader<schema_capnp::summary_entry::Owned>,
+    ) -> Result<Vec<SummaryEntry>> {
+        let mut vec = Vec::with_capacity(reader.len() as usize);
+        for reader in reader.into_iter() {
+            vec.push(SummaryEntry {
+                fingerprint: reader.get_fingerprint(),
+                source: self.read_source(&reader.get_source()?)?,
+                pos: reader.get_pos() as usize,
+                line: reader.get_line()?.to_str()?.into(),
+                distance: reader.get_distance(),
+                count: reader.get_count() as usize,
+            });
+        }
+        Ok(vec)
+    }
+
+    fn read_source_stats(
+        &self,
+        reader: &capnp::struct_list::Reader<schema_capnp::source_stats::Owned>,
+    ) -> Result<Vec<SourceStats>> {
+        let mut vec = Vec::with_capacity(reader.len() as usize);
+        for reader in reader.into_iter() {
+            vec.push(SourceStats {
+                source: self.read_source(&reader.get_source()?)?,
+                download_time: read_duration(reader.get_download_time()),
+                analysis_time: read_duration(reader.get_analysis_time()),
+                byte_count: reader.get_byte_count() as usize,
+                line_count: reader.get_line_count() as usize,
+                unique_line_count: reader.get_unique_line_count() as usize,
+            });
+        }
+        Ok(vec)
+    }
+
     fn read_errors(
         &self,
         reader: &capnp::struct_list::Reader<schema_capnp::read_error::Owned>,
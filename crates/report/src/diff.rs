@@ -0,0 +1,95 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the comparison of two reports, e.g. to check if a retry produced the same failure signature.
+//!
+//! The anomalies are matched using their fingerprint, so that the varying words and the line numbers are ignored.
+
+use std::collections::HashSet;
+
+use crate::{AnomalyContext, LogReport, Report};
+
+/// An anomaly with its log report.
+pub type AnomalyRef<'a> = (&'a LogReport, &'a AnomalyContext);
+
+/// The difference between two reports.
+#[derive(Debug, Default)]
+pub struct ReportDiff<'a> {
+    /// The anomalies only found in the new report.
+    pub added: Vec<AnomalyRef<'a>>,
+    /// The anomalies of the old report that disappeared.
+    pub removed: Vec<AnomalyRef<'a>>,
+    /// The anomalies of the new report that were already found in the old report.
+    pub persistent: Vec<AnomalyRef<'a>>,
+}
+
+impl<'a> ReportDiff<'a> {
+    /// Indicate if the two reports have the same failure signature.
+    pub fn is_same(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+fn fingerprints(report: &Report) -> HashSet<u64> {
+    anomalies(report)
+        .map(|(lr, anomaly)| anomaly.fingerprint(&lr.source))
+        .collect()
+}
+
+fn anomalies(report: &Report) -> impl Iterator<Item = AnomalyRef<'_>> {
+    report
+        .log_reports
+        .iter()
+        .flat_map(|lr| lr.anomalies.iter().map(move |anomaly| (lr, anomaly)))
+}
+
+/// Compare the anomalies of the old and new reports.
+pub fn diff<'a>(old: &'a Report, new: &'a Report) -> ReportDiff<'a> {
+    let old_fingerprints = fingerprints(old);
+    let new_fingerprints = fingerprints(new);
+    let mut result = ReportDiff::default();
+    for (lr, anomaly) in anomalies(new) {
+        if old_fingerprints.contains(&anomaly.fingerprint(&lr.source)) {
+            result.persistent.push((lr, anomaly));
+        } else {
+            result.added.push((lr, anomaly));
+        }
+    }
+    for (lr, anomaly) in anomalies(old) {
+        if !new_fingerprints.contains(&anomaly.fingerprint(&lr.source)) {
+            result.removed.push((lr, anomaly));
+        }
+    }
+    result
+}
+
+#[test]
+fn test_diff() {
+    let mk_report = |lines: &[&str]| {
+        let mut report = Report::sample();
+        let anomaly = report.log_reports[0].anomalies[0].clone();
+        report.log_reports[0].anomalies = lines
+            .iter()
+            .map(|line| {
+                let mut anomaly = anomaly.clone();
+                anomaly.anomaly.line = (*line).into();
+                anomaly
+            })
+            .collect();
+        report
+    };
+    let old = mk_report(&["connection refused 42", "the disk is full"]);
+    let new = mk_report(&["connection refused 43", "kernel panic detected"]);
+    let lines = |anomalies: &[AnomalyRef]| {
+        anomalies
+            .iter()
+            .map(|(_, anomaly)| anomaly.anomaly.line.to_string())
+            .collect::<Vec<_>>()
+    };
+    let result = diff(&old, &new);
+    assert_eq!(lines(&result.added), vec!["kernel panic detected"]);
+    assert_eq!(lines(&result.removed), vec!["the disk is full"]);
+    assert_eq!(lines(&result.persistent), vec!["connection refused 43"]);
+    assert!(!result.is_same());
+    assert!(diff(&old, &old).is_same());
+}
@@ -0,0 +1,117 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the logic to slice a report, to inspect the big reports in the terminal.
+
+use regex::Regex;
+
+use crate::{AnomalyContext, LogReport, Report};
+
+/// The anomalies selection.
+#[derive(Debug, Default)]
+pub struct ReportFilter {
+    /// The minimum anomaly distance.
+    pub min_distance: f32,
+    /// A source path pattern, where `*` matches any characters and `?` matches a single character.
+    pub source: Option<String>,
+    /// A regex that must match the anomaly line.
+    pub grep: Option<Regex>,
+}
+
+/// Check if the text matches a wildcard pattern.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    // The position to resume from, after the last star.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the star match one more character.
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+impl ReportFilter {
+    pub fn new(
+        min_distance: f32,
+        source: Option<String>,
+        grep: Option<&str>,
+    ) -> Result<ReportFilter, regex::Error> {
+        Ok(ReportFilter {
+            min_distance,
+            source,
+            grep: grep.map(Regex::new).transpose()?,
+        })
+    }
+
+    fn keep_source(&self, lr: &LogReport) -> bool {
+        self.source
+            .as_deref()
+            .is_none_or(|pattern| wildcard_match(pattern, lr.source.as_str()))
+    }
+
+    fn keep_anomaly(&self, anomaly: &AnomalyContext) -> bool {
+        anomaly.anomaly.distance >= self.min_distance
+            && self
+                .grep
+                .as_ref()
+                .is_none_or(|re| re.is_match(&anomaly.anomaly.line))
+    }
+
+    /// Remove the anomalies that are not selected, and the log reports without anomalies.
+    /// The summary, the clusters and the failed tests anomalies are computed again with the remaining anomalies.
+    pub fn apply(&self, report: &mut Report) {
+        report.log_reports.retain_mut(|lr| {
+            if self.keep_source(lr) {
+                lr.anomalies.retain(|anomaly| self.keep_anomaly(anomaly));
+                !lr.anomalies.is_empty()
+            } else {
+                false
+            }
+        });
+        report.summary = crate::summary::compute(&report.log_reports, crate::summary::TOP_N);
+        report.clusters = crate::cluster::compute(&report.log_reports);
+        crate::test_results::correlate(&mut report.failed_tests, &report.log_reports);
+    }
+}
+
+#[test]
+fn test_wildcard_match() {
+    assert!(wildcard_match(
+        "*job-output*",
+        "http://logs/job-output.txt.gz"
+    ));
+    assert!(wildcard_match("*.log", "/var/log/app.log"));
+    assert!(wildcard_match("app.lo?", "app.log"));
+    assert!(!wildcard_match("*.log", "/var/log/app.log.1"));
+    assert!(!wildcard_match("*job-output*", "/var/log/app.log"));
+}
+
+#[test]
+fn test_filter() {
+    let mut report = Report::sample();
+    let filter = ReportFilter {
+        min_distance: 0.6,
+        ..Default::default()
+    };
+    filter.apply(&mut report);
+    assert!(report.log_reports.is_empty());
+
+    let mut report = Report::sample();
+    let filter = ReportFilter::new(0.2, Some("*status".into()), Some("anom")).unwrap();
+    filter.apply(&mut report);
+    assert_eq!(report.anomaly_count(), 1);
+}
@@ -0,0 +1,174 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the standalone HTML rendering of the report.
+//!
+//! The page embeds its style and script, so that it can be shared without the web service:
+//! the sources are collapsible, the anomalies are highlighted in their context,
+//! and a slider hides the anomalies below a given distance.
+
+use std::io::{Result, Write};
+
+use crate::xml::escape;
+use crate::{AnomalyContext, LogReport, Report};
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 1em 2em; }
+summary { cursor: pointer; padding: 0.3em 0; }
+.count { color: #666; }
+.anomaly { font-family: monospace; white-space: pre-wrap; margin: 0.5em 0; border-left: 3px solid #ccc; }
+.line { display: flex; }
+.pos { color: #999; min-width: 5em; text-align: right; padding-right: 1em; user-select: none; }
+.ctx { color: #555; }
+.hl { background: #fee; font-weight: bold; }
+.hidden { display: none; }
+"#;
+
+const SCRIPT: &str = r#"
+const slider = document.getElementById("distance");
+const label = document.getElementById("distance-value");
+slider.addEventListener("input", () => {
+  const min = parseFloat(slider.value);
+  label.textContent = min.toFixed(2);
+  document.querySelectorAll("details.source").forEach(source => {
+    let visible = 0;
+    source.querySelectorAll(".anomaly").forEach(anomaly => {
+      const hidden = parseFloat(anomaly.dataset.distance) < min;
+      anomaly.classList.toggle("hidden", hidden);
+      if (!hidden) visible += 1;
+    });
+    source.classList.toggle("hidden", visible == 0);
+  });
+});
+"#;
+
+/// Format the line number, as a link to the original log when the href is known.
+fn line_pos(pos: usize, href: Option<&str>) -> String {
+    match href {
+        Some(href) => format!(r#"<a href="{}">{}</a>"#, escape(href), pos),
+        None => pos.to_string(),
+    }
+}
+
+fn write_line(dest: &mut impl Write, class: &str, pos: &str, line: &str) -> Result<()> {
+    writeln!(
+        dest,
+        r#"<div class="line {}"><span class="pos">{}</span><span>{}</span></div>"#,
+        class,
+        pos,
+        escape(line)
+    )
+}
+
+fn write_anomaly(
+    dest: &mut impl Write,
+    report: &Report,
+    lr: &LogReport,
+    anomaly: &AnomalyContext,
+) -> Result<()> {
+    let pos = anomaly.anomaly.pos;
+    writeln!(
+        dest,
+        r#"<div class="anomaly" data-distance="{:.2}">"#,
+        anomaly.anomaly.distance
+    )?;
+    let start = pos.saturating_sub(anomaly.before.len());
+    for (idx, line) in anomaly.before.iter().enumerate() {
+        write_line(dest, "ctx", &(start + idx).to_string(), line)?;
+    }
+    let href = report.anomaly_href(&lr.source, pos);
+    write_line(
+        dest,
+        "hl",
+        &line_pos(pos, href.as_deref()),
+        &anomaly.anomaly.line,
+    )?;
+    for (idx, line) in anomaly.after.iter().enumerate() {
+        write_line(dest, "ctx", &(pos + 1 + idx).to_string(), line)?;
+    }
+    writeln!(dest, "</div>")
+}
+
+fn write_log_report(dest: &mut impl Write, report: &Report, lr: &LogReport) -> Result<()> {
+    writeln!(
+        dest,
+        r#"<details class="source" open><summary>{} <span class="count">{}{} anomalies</span></summary>"#,
+        escape(lr.source.get_relative()),
+        lr.category
+            .as_deref()
+            .map(|category| format!("{}, ", escape(category)))
+            .unwrap_or_default(),
+        lr.anomalies.len()
+    )?;
+    for anomaly in &lr.anomalies {
+        write_anomaly(dest, report, lr, anomaly)?;
+    }
+    writeln!(dest, "</details>")
+}
+
+/// Write the report as a self-contained HTML page.
+pub fn save_writer(report: &Report, mut dest: impl Write) -> Result<()> {
+    let target = escape(&report.target.to_string());
+    writeln!(dest, "<!DOCTYPE html>")?;
+    writeln!(
+        dest,
+        r#"<html><head><meta charset="utf-8"><title>LogJuicer {target}</title><style>{STYLE}</style></head><body>"#
+    )?;
+    writeln!(dest, "<h1>{target}</h1>")?;
+    writeln!(
+        dest,
+        "<p>{} anomalies found in {} lines, compared with: {}</p>",
+        report.anomaly_count(),
+        report.total_line_count,
+        report
+            .baselines
+            .iter()
+            .map(|baseline| escape(&baseline.to_string()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )?;
+    for warning in &report.warnings {
+        writeln!(dest, "<p>warning: {}</p>", escape(&warning.to_string()))?;
+    }
+    if !report.summary.is_empty() {
+        writeln!(dest, "<h2>Top anomalies</h2><ol>")?;
+        for entry in &report.summary {
+            writeln!(
+                dest,
+                r#"<li><code>{}</code> <span class="count">{}x, distance {:.2}, {}:{}</span></li>"#,
+                escape(&entry.line),
+                entry.count,
+                entry.distance,
+                escape(entry.source.get_relative()),
+                line_pos(
+                    entry.pos,
+                    report.anomaly_href(&entry.source, entry.pos).as_deref()
+                )
+            )?;
+        }
+        writeln!(dest, "</ol>")?;
+    }
+    writeln!(
+        dest,
+        r#"<p><label for="distance">Minimum distance</label> <input type="range" id="distance" min="0" max="1" step="0.05" value="0"> <span id="distance-value">0.00</span></p>"#
+    )?;
+    // The log reports are already sorted by the model.
+    for lr in &report.log_reports {
+        write_log_report(&mut dest, report, lr)?;
+    }
+    writeln!(dest, "<script>{SCRIPT}</script></body></html>")
+}
+
+#[test]
+fn test_html() {
+    let mut report = Report::sample();
+    report.log_reports[0].anomalies[0].anomaly.line = "<script>".into();
+    let mut buf = Vec::new();
+    save_writer(&report, &mut buf).unwrap();
+    let html = String::from_utf8(buf).unwrap();
+    assert!(html.contains(r#"<div class="anomaly" data-distance="0.50">"#));
+    assert!(html.contains("&lt;script&gt;"));
+    assert!(html.contains("<li><code>anomaly</code>"));
+    assert!(html
+        .contains(r#"<div class="line ctx"><span class="pos">0</span><span>before</span></div>"#));
+}
@@ -0,0 +1,304 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the public JSON export of the report, version 1.
+//!
+//! Unlike the Report struct, which follows the internal data model, this schema is stable:
+//! new optional fields may be added, but the existing fields are never renamed, removed or changed.
+//! A breaking change requires a new `json-v2` export, so that the downstream tools can keep parsing the v1 files.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{AnomalyContext, Content, LogReport, Report, Source};
+
+/// The value of the `schema` field.
+pub const SCHEMA: &str = "logjuicer-report/v1";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportV1 {
+    /// Always [SCHEMA].
+    pub schema: String,
+    pub created_at: DateTime<Utc>,
+    pub run_time_secs: f64,
+    pub target: ContentV1,
+    pub baselines: Vec<ContentV1>,
+    pub total_line_count: usize,
+    pub total_anomaly_count: usize,
+    pub gating_anomaly_count: usize,
+    /// The most distant anomalies, grouped by fingerprint.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub summary: Vec<SummaryV1>,
+    /// The sources with anomalies, the most relevant first.
+    pub sources: Vec<SourceV1>,
+    /// The sources that could not be compared because they have no baselines.
+    pub unknown_files: Vec<String>,
+    pub read_errors: Vec<ReadErrorV1>,
+    pub warnings: Vec<WarningV1>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<ProvenanceV1>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContentV1 {
+    /// One of: file, directory, zuul, prow, local-zuul-build, jenkins, kubernetes.
+    pub kind: String,
+    /// The path or the url of the content.
+    pub location: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_name: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SourceV1 {
+    /// The path relative to the content location.
+    pub path: String,
+    /// The absolute path or the url of the file.
+    pub href: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Indicate if the anomalies fail the report.
+    pub gating: bool,
+    pub line_count: usize,
+    pub byte_count: usize,
+    pub anomalies: Vec<AnomalyV1>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnomalyV1 {
+    /// The line number, starting at 1.
+    pub line_number: usize,
+    /// The distance with the baselines, between 0.0 and 1.0.
+    pub distance: f32,
+    pub line: String,
+    /// The link to the line in the original log, only for the remote sources.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    /// The triage notes, the oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<AnnotationV1>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnnotationV1 {
+    /// One of: note, false-positive, root-cause.
+    pub kind: String,
+    pub note: String,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SummaryV1 {
+    /// The path of the first occurrence.
+    pub path: String,
+    /// The line number of the first occurrence.
+    pub line_number: usize,
+    pub line: String,
+    /// The highest distance of the occurrences.
+    pub distance: f32,
+    pub count: usize,
+    /// The hexadecimal fingerprint, also used by the SARIF export.
+    pub fingerprint: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProvenanceV1 {
+    /// The logjuicer version that created the report.
+    pub version: String,
+    pub model_hash: String,
+    pub model_created_at: DateTime<Utc>,
+    pub trainer: String,
+    /// The urls or paths of the baseline files.
+    pub baseline_sources: Vec<String>,
+    /// The number of baseline builds.
+    #[serde(default)]
+    pub baseline_count: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReadErrorV1 {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct WarningV1 {
+    pub kind: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+fn content(content: &Content) -> ContentV1 {
+    let (location, job_name) = match content {
+        Content::File(src) | Content::Directory(src) => (src.as_str().to_string(), None),
+        Content::Zuul(build) => (build.to_string(), Some(&build.job_name)),
+        Content::Prow(build) => (build.to_string(), Some(&build.job_name)),
+        Content::Jenkins(build) => (build.to_string(), Some(&build.job_name)),
+        Content::Kubernetes(pod) => (pod.to_string(), None),
+        Content::Loki(query) => (query.to_string(), None),
+        Content::Elasticsearch(query) => (query.to_string(), None),
+        Content::CloudWatch(query) => (query.to_string(), None),
+        Content::Journal(query) => (query.to_string(), None),
+        Content::Container(container) => (container.to_string(), None),
+        Content::Tekton(run) => (run.to_string(), None),
+        Content::Buildkite(build) => (build.to_string(), Some(&build.pipeline)),
+        Content::CircleCi(job) => (job.job_url(), Some(&job.job_name)),
+        Content::Koji(build) => (build.url.to_string(), Some(&build.package)),
+        Content::Obs(build) => (build.to_string(), Some(&build.package)),
+        Content::LocalZuulBuild(path, build) => {
+            (path.to_string_lossy().to_string(), Some(&build.job_name))
+        }
+    };
+    ContentV1 {
+        kind: content.kind().to_string(),
+        location,
+        job_name: job_name.map(|name| name.to_string()),
+    }
+}
+
+fn relative(source: &Source) -> String {
+    source.get_relative().trim_start_matches('/').to_string()
+}
+
+fn anomaly(report: &Report, lr: &LogReport, anomaly: &AnomalyContext) -> AnomalyV1 {
+    let to_strings = |lines: &[std::rc::Rc<str>]| lines.iter().map(|l| l.to_string()).collect();
+    AnomalyV1 {
+        line_number: anomaly.anomaly.pos,
+        distance: anomaly.anomaly.distance,
+        line: anomaly.anomaly.line.to_string(),
+        href: report.anomaly_href(&lr.source, anomaly.anomaly.pos),
+        before: to_strings(&anomaly.before),
+        after: to_strings(&anomaly.after),
+        annotations: report
+            .annotations_of(lr, anomaly)
+            .map(|annotation| AnnotationV1 {
+                kind: annotation.kind.as_str().to_string(),
+                note: annotation.note.to_string(),
+                author: annotation.author.to_string(),
+                created_at: annotation.created_at.into(),
+            })
+            .collect(),
+    }
+}
+
+fn source(report: &Report, lr: &LogReport) -> SourceV1 {
+    SourceV1 {
+        path: relative(&lr.source),
+        href: lr.source.get_href(&report.target).to_string(),
+        category: lr.category.as_deref().map(str::to_string),
+        gating: report.is_gating(lr),
+        line_count: lr.line_count,
+        byte_count: lr.byte_count,
+        anomalies: lr
+            .anomalies
+            .iter()
+            .map(|a| anomaly(report, lr, a))
+            .collect(),
+    }
+}
+
+impl ReportV1 {
+    pub fn from_report(report: &Report) -> ReportV1 {
+        ReportV1 {
+            schema: SCHEMA.to_string(),
+            created_at: report.created_at.into(),
+            run_time_secs: report.run_time.as_secs_f64(),
+            target: content(&report.target),
+            baselines: report.baselines.iter().map(content).collect(),
+            total_line_count: report.total_line_count,
+            total_anomaly_count: report.total_anomaly_count,
+            gating_anomaly_count: report.gating_anomaly_count(),
+            summary: report
+                .summary
+                .iter()
+                .map(|entry| SummaryV1 {
+                    path: relative(&entry.source),
+                    line_number: entry.pos,
+                    line: entry.line.to_string(),
+                    distance: entry.distance,
+                    count: entry.count,
+                    fingerprint: format!("{:016x}", entry.fingerprint),
+                })
+                .collect(),
+            sources: report
+                .log_reports
+                .iter()
+                .map(|lr| source(report, lr))
+                .collect(),
+            unknown_files: report
+                .unknown_files
+                .values()
+                .flatten()
+                .map(relative)
+                .collect(),
+            read_errors: report
+                .read_errors
+                .iter()
+                .map(|(source, err)| ReadErrorV1 {
+                    path: relative(source),
+                    error: err.to_string(),
+                })
+                .collect(),
+            warnings: report
+                .warnings
+                .iter()
+                .map(|warning| WarningV1 {
+                    kind: warning.kind.as_str().to_string(),
+                    message: warning.message.to_string(),
+                    path: warning.source.as_ref().map(relative),
+                })
+                .collect(),
+            provenance: report.provenance.as_ref().map(|provenance| ProvenanceV1 {
+                version: provenance.version.to_string(),
+                model_hash: provenance.model_hash.to_string(),
+                model_created_at: provenance.model_created_at.into(),
+                trainer: provenance.trainer.to_string(),
+                baseline_sources: provenance
+                    .baseline_sources
+                    .iter()
+                    .map(|source| source.as_str().to_string())
+                    .collect(),
+                baseline_count: provenance.baseline_count,
+            }),
+        }
+    }
+
+    pub fn save_writer(&self, dest: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(dest, self)
+    }
+}
+
+#[test]
+fn test_json_v1() {
+    let mut report = Report::sample();
+    report.read_errors = vec![(Source::Local(6, "/proc/missing".into()), "oops".into())];
+    let json = serde_json::to_value(ReportV1::from_report(&report)).unwrap();
+    // These assertions check the schema guarantee, they must not be changed.
+    assert_eq!(json["schema"], "logjuicer-report/v1");
+    assert_eq!(json["target"]["kind"], "file");
+    assert_eq!(json["baselines"][1]["job_name"], "zuul-demo-job");
+    let source = &json["sources"][0];
+    assert_eq!(source["path"], "c/status");
+    assert_eq!(source["href"], "/proc/status");
+    assert_eq!(source["category"], "kernel");
+    assert_eq!(source["gating"], false);
+    let anomaly = &source["anomalies"][0];
+    assert_eq!(anomaly["line_number"], 1);
+    assert_eq!(anomaly["line"], "anomaly");
+    assert_eq!(anomaly["before"][0], "before");
+    assert_eq!(json["warnings"][0]["kind"], "stale-baseline");
+    assert_eq!(json["read_errors"][0]["path"], "missing");
+    assert_eq!(json["read_errors"][0]["error"], "oops");
+    assert_eq!(json["summary"][0]["count"], 1);
+    assert_eq!(
+        json["provenance"]["baseline_sources"][0],
+        "http://localhost/status"
+    );
+
+    let decoded: ReportV1 = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded, ReportV1::from_report(&report));
+}
@@ -0,0 +1,142 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the JUnit XML serialization of the report, for the CI dashboards.
+//!
+//! Each source with anomalies is a test case, failed when its category gates the report.
+//! The read errors are test case errors, and the files without baselines are skipped test cases.
+//! The sources without anomalies are not recorded in the report, so they are not listed.
+//! The report summary is written first, as the test suite properties.
+
+use std::io::{Result, Write};
+
+use crate::xml::escape;
+use crate::{LogReport, Report};
+
+fn write_anomalies(report: &Report, lr: &LogReport, dest: &mut impl Write) -> Result<()> {
+    for anomaly in &lr.anomalies {
+        writeln!(
+            dest,
+            "{:02.0} {} | {}",
+            anomaly.anomaly.distance * 99.0,
+            anomaly.anomaly.pos,
+            escape(&anomaly.anomaly.line)
+        )?;
+        if let Some(href) = report.anomaly_href(&lr.source, anomaly.anomaly.pos) {
+            writeln!(dest, "   {}", escape(&href))?;
+        }
+    }
+    Ok(())
+}
+
+fn write_log_report(report: &Report, lr: &LogReport, dest: &mut impl Write) -> Result<()> {
+    writeln!(
+        dest,
+        r#"    <testcase classname="{}" name="{}" time="{:.3}">"#,
+        escape(lr.category.as_deref().unwrap_or(lr.index_name.as_str())),
+        escape(lr.source.get_relative()),
+        lr.test_time.as_secs_f32()
+    )?;
+    if report.is_gating(lr) {
+        write!(
+            dest,
+            r#"      <failure type="anomaly" message="{} anomalies found">"#,
+            lr.anomalies.len()
+        )?;
+        write_anomalies(report, lr, dest)?;
+        writeln!(dest, "</failure>")?;
+    } else {
+        write!(dest, "      <system-out>")?;
+        write_anomalies(report, lr, dest)?;
+        writeln!(dest, "</system-out>")?;
+    }
+    writeln!(dest, "    </testcase>")
+}
+
+/// Write the report as a JUnit XML document.
+pub fn save_writer(report: &Report, mut dest: impl Write) -> Result<()> {
+    let failures = report
+        .log_reports
+        .iter()
+        .filter(|lr| report.is_gating(lr))
+        .count();
+    let errors = report.read_errors.len();
+    let skipped = report.unknown_files.values().map(Vec::len).sum::<usize>();
+    let tests = report.log_reports.len() + errors + skipped;
+    let time = report.run_time.as_secs_f32();
+    writeln!(dest, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        dest,
+        r#"<testsuites name="logjuicer" tests="{tests}" failures="{failures}" errors="{errors}" skipped="{skipped}" time="{time:.3}">"#
+    )?;
+    writeln!(
+        dest,
+        r#"  <testsuite name="{}" tests="{tests}" failures="{failures}" errors="{errors}" skipped="{skipped}" time="{time:.3}">"#,
+        escape(&report.target.to_string())
+    )?;
+    if !report.summary.is_empty() {
+        writeln!(dest, "    <properties>")?;
+        for (idx, entry) in report.summary.iter().enumerate() {
+            writeln!(
+                dest,
+                r#"      <property name="top-anomaly-{}" value="{}x {:.2} {}:{} | {}"/>"#,
+                idx + 1,
+                entry.count,
+                entry.distance,
+                escape(entry.source.get_relative()),
+                entry.pos,
+                escape(&entry.line)
+            )?;
+        }
+        writeln!(dest, "    </properties>")?;
+    }
+    for lr in &report.log_reports {
+        write_log_report(report, lr, &mut dest)?;
+    }
+    for (source, err) in &report.read_errors {
+        writeln!(
+            dest,
+            r#"    <testcase classname="read-error" name="{}"><error message="{}"/></testcase>"#,
+            escape(source.get_relative()),
+            escape(err)
+        )?;
+    }
+    for (index_name, sources) in &report.unknown_files {
+        for source in sources {
+            writeln!(
+                dest,
+                r#"    <testcase classname="{}" name="{}"><skipped message="no baselines"/></testcase>"#,
+                escape(index_name.as_str()),
+                escape(source.get_relative())
+            )?;
+        }
+    }
+    writeln!(dest, "  </testsuite>")?;
+    writeln!(dest, "</testsuites>")
+}
+
+#[test]
+fn test_junit() {
+    let mut report = Report::sample();
+    report.read_errors = vec![(
+        crate::Source::Local(6, "/proc/missing".into()),
+        "oops".into(),
+    )];
+    let mut buf = Vec::new();
+    save_writer(&report, &mut buf).unwrap();
+    let xml = String::from_utf8(buf).unwrap();
+    assert!(xml.contains(r#"tests="3" failures="0" errors="1" skipped="1""#));
+    assert!(xml.contains(" 1 | anomaly\n</system-out>"));
+    assert!(
+        xml.contains(r#"<property name="top-anomaly-1" value="1x 0.50 c/status:1 | anomaly"/>"#)
+    );
+    assert!(xml.contains(r#"name="missing"><error message="oops"/>"#));
+
+    report.category_policies.clear();
+    report.log_reports[0].anomalies[0].anomaly.line = "a <b> & c".into();
+    let mut buf = Vec::new();
+    save_writer(&report, &mut buf).unwrap();
+    let xml = String::from_utf8(buf).unwrap();
+    assert!(xml.contains(r#"failures="1""#));
+    assert!(xml.contains("a &lt;b&gt; &amp; c"));
+}
@@ -0,0 +1,128 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the Markdown summary of the report, to be posted as a review comment.
+//!
+//! The summary is kept compact: the counts, the top anomalies of the report, and the top anomalies of each source
+//! with a link to the file.
+
+use std::io::{Result, Write};
+
+use crate::{LogReport, Report, Source};
+
+/// The number of anomalies displayed per source.
+const TOP_ANOMALIES: usize = 3;
+
+/// The number of sources displayed.
+const TOP_SOURCES: usize = 10;
+
+/// Remove the characters that would break the code span.
+fn code(line: &str) -> String {
+    let line = line.replace('`', "'");
+    match line.char_indices().nth(200) {
+        Some((pos, _)) => format!("`{}...`", &line[..pos]),
+        None => format!("`{}`", line),
+    }
+}
+
+fn link(report: &Report, source: &Source) -> String {
+    let name = source.get_relative().trim_start_matches('/');
+    match source {
+        Source::Remote(_, _) => format!("[{}]({})", name, source.get_href(&report.target)),
+        Source::Local(_, _) => format!("`{}`", name),
+    }
+}
+
+/// The line number, with a link to the original log for the remote sources.
+fn line_link(report: &Report, source: &Source, pos: usize) -> String {
+    match report.anomaly_href(source, pos) {
+        Some(href) => format!("[line {}]({})", pos, href),
+        None => format!("line {}", pos),
+    }
+}
+
+fn write_log_report(report: &Report, lr: &LogReport, dest: &mut impl Write) -> Result<()> {
+    writeln!(
+        dest,
+        "- {} ({} anomalies{})",
+        link(report, &lr.source),
+        lr.anomalies.len(),
+        lr.category
+            .as_deref()
+            .map(|category| format!(", {}", category))
+            .unwrap_or_default()
+    )?;
+    let mut anomalies = lr.anomalies.iter().collect::<Vec<_>>();
+    anomalies.sort_by(|a, b| b.anomaly.distance.total_cmp(&a.anomaly.distance));
+    for anomaly in anomalies.iter().take(TOP_ANOMALIES) {
+        writeln!(
+            dest,
+            "  - {}: {}",
+            line_link(report, &lr.source, anomaly.anomaly.pos),
+            code(&anomaly.anomaly.line)
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the report summary as Markdown.
+pub fn save_writer(report: &Report, mut dest: impl Write) -> Result<()> {
+    writeln!(dest, "### LogJuicer report")?;
+    writeln!(dest)?;
+    writeln!(
+        dest,
+        "Found {} anomalies ({} gating) in {} sources, out of {} lines.",
+        report.anomaly_count(),
+        report.gating_anomaly_count(),
+        report.log_reports.len(),
+        report.total_line_count
+    )?;
+    writeln!(dest)?;
+    if !report.summary.is_empty() {
+        writeln!(dest, "**Top anomalies**")?;
+        writeln!(dest)?;
+        for (idx, entry) in report.summary.iter().enumerate() {
+            writeln!(
+                dest,
+                "{}. {} ({}x, distance {:.2}) at {} {}",
+                idx + 1,
+                code(&entry.line),
+                entry.count,
+                entry.distance,
+                link(report, &entry.source),
+                line_link(report, &entry.source, entry.pos)
+            )?;
+        }
+        writeln!(dest)?;
+    }
+    // The log reports are already sorted by the model.
+    for lr in report.log_reports.iter().take(TOP_SOURCES) {
+        write_log_report(report, lr, &mut dest)?;
+    }
+    if report.log_reports.len() > TOP_SOURCES {
+        writeln!(
+            dest,
+            "- and {} more sources",
+            report.log_reports.len() - TOP_SOURCES
+        )?;
+    }
+    if !report.warnings.is_empty() {
+        writeln!(dest)?;
+        for warning in &report.warnings {
+            writeln!(dest, "> warning: {}", warning)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_markdown() {
+    let mut report = Report::sample();
+    report.log_reports[0].anomalies[0].anomaly.line = "a `quoted` line".into();
+    let mut buf = Vec::new();
+    save_writer(&report, &mut buf).unwrap();
+    let md = String::from_utf8(buf).unwrap();
+    assert!(md.contains("Found 1 anomalies (0 gating) in 1 sources, out of 42 lines."));
+    assert!(md.contains("1. `anomaly` (1x, distance 0.50) at `c/status` line 1\n"));
+    assert!(md.contains("- `c/status` (1 anomalies, kernel)\n  - line 1: `a 'quoted' line`\n"));
+}
@@ -0,0 +1,228 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the logic to combine the reports of multiple targets, e.g. the failed jobs of a change.
+//!
+//! The sources are matched using their index name and relative path, and the identical anomalies are
+//! deduplicated using their fingerprint. Each anomaly records the targets where it was found.
+
+use std::collections::HashMap;
+
+use crate::{AnomalyContext, LogReport, Report};
+
+/// The merged log reports, with the fingerprints of their anomalies.
+#[derive(Default)]
+struct Sources {
+    log_reports: Vec<LogReport>,
+    positions: HashMap<(String, String), usize>,
+    fingerprints: HashMap<u64, (usize, usize)>,
+}
+
+impl Sources {
+    fn add_anomaly(&mut self, lr_pos: usize, anomaly: AnomalyContext) {
+        let fingerprint = anomaly.fingerprint(&self.log_reports[lr_pos].source);
+        match self.fingerprints.get(&fingerprint) {
+            Some((lr_pos, anomaly_pos)) => {
+                let known = &mut self.log_reports[*lr_pos].anomalies[*anomaly_pos];
+                for target in anomaly.targets {
+                    if !known.targets.contains(&target) {
+                        known.targets.push(target);
+                    }
+                }
+                if anomaly.anomaly.distance > known.anomaly.distance {
+                    known.anomaly.distance = anomaly.anomaly.distance;
+                }
+            }
+            None => {
+                let anomalies = &mut self.log_reports[lr_pos].anomalies;
+                self.fingerprints
+                    .insert(fingerprint, (lr_pos, anomalies.len()));
+                anomalies.push(anomaly);
+            }
+        }
+    }
+
+    fn add(&mut self, mut lr: LogReport) {
+        let key = (
+            lr.index_name.as_str().to_string(),
+            lr.source.get_relative().to_string(),
+        );
+        let anomalies = std::mem::take(&mut lr.anomalies);
+        let lr_pos = match self.positions.get(&key) {
+            Some(pos) => {
+                let known = &mut self.log_reports[*pos];
+                known.test_time += lr.test_time;
+                *pos
+            }
+            None => {
+                self.positions.insert(key, self.log_reports.len());
+                self.log_reports.push(lr);
+                self.log_reports.len() - 1
+            }
+        };
+        for anomaly in anomalies {
+            self.add_anomaly(lr_pos, anomaly);
+        }
+    }
+}
+
+/// Combine the reports, the first report target is used as the merged report target.
+/// The reports can be merged reports themselves.
+pub fn merge(reports: Vec<Report>) -> Option<Report> {
+    let mut reports = reports.into_iter();
+    let mut merged = reports.next()?;
+    let mut sources = Sources::default();
+    let first_log_reports = std::mem::take(&mut merged.log_reports);
+    let first_count = merged.merged_targets.len();
+    if first_count == 0 {
+        merged.merged_targets.push(merged.target.clone());
+    }
+    add_log_reports(&mut sources, 0, first_count, first_log_reports);
+
+    for mut report in reports {
+        let offset = merged.merged_targets.len();
+        let targets = std::mem::take(&mut report.merged_targets);
+        let targets_count = targets.len();
+        if targets.is_empty() {
+            merged.merged_targets.push(report.target);
+        } else {
+            merged.merged_targets.extend(targets);
+        }
+        add_log_reports(&mut sources, offset, targets_count, report.log_reports);
+
+        merged.created_at = merged.created_at.max(report.created_at);
+        merged.run_time += report.run_time;
+        merged.total_line_count += report.total_line_count;
+        for baseline in report.baselines {
+            if !merged.baselines.contains(&baseline) {
+                merged.baselines.push(baseline);
+            }
+        }
+        for (index_name, index_report) in report.index_reports {
+            merged
+                .index_reports
+                .entry(index_name)
+                .or_insert(index_report);
+        }
+        for (index_name, unknown_sources) in report.unknown_files {
+            let known = merged.unknown_files.entry(index_name).or_default();
+            for source in unknown_sources {
+                if !known.contains(&source) {
+                    known.push(source);
+                }
+            }
+        }
+        merged.read_errors.extend(report.read_errors);
+        for policy in report.category_policies {
+            if !merged
+                .category_policies
+                .iter()
+                .any(|known| known.category == policy.category)
+            {
+                merged.category_policies.push(policy);
+            }
+        }
+        merged.warnings.extend(report.warnings);
+        merged.source_stats.extend(report.source_stats);
+        merged.failed_tests.extend(report.failed_tests);
+        for annotation in report.annotations {
+            if !merged.annotations.contains(&annotation) {
+                merged.annotations.push(annotation);
+            }
+        }
+    }
+
+    merged.log_reports = LogReport::sorted(sources.log_reports);
+    merged.total_anomaly_count = merged.anomaly_count();
+    merged.summary = crate::summary::compute(&merged.log_reports, crate::summary::TOP_N);
+    merged.clusters = crate::cluster::compute(&merged.log_reports);
+    crate::test_results::correlate(&mut merged.failed_tests, &merged.log_reports);
+    Some(merged)
+}
+
+/// Add the log reports of a report whose targets starts at the offset.
+/// When the report is not merged, the targets count is 0 and the anomalies belong to the report target.
+fn add_log_reports(
+    sources: &mut Sources,
+    offset: usize,
+    targets_count: usize,
+    log_reports: Vec<LogReport>,
+) {
+    for mut lr in log_reports {
+        for anomaly in lr.anomalies.iter_mut() {
+            if targets_count == 0 {
+                anomaly.targets = vec![offset];
+            } else {
+                for target in anomaly.targets.iter_mut() {
+                    *target += offset;
+                }
+            }
+        }
+        sources.add(lr);
+    }
+}
+
+#[test]
+fn test_merge() {
+    let mk_report = |target: &str, lines: &[&str]| {
+        let mut report = Report::sample();
+        report.target = crate::Content::File(crate::Source::Local(0, target.into()));
+        let anomaly = report.log_reports[0].anomalies[0].clone();
+        report.log_reports[0].anomalies = lines
+            .iter()
+            .map(|line| {
+                let mut anomaly = anomaly.clone();
+                anomaly.anomaly.line = (*line).into();
+                anomaly
+            })
+            .collect();
+        report
+    };
+    let first = mk_report(
+        "first",
+        &["error: connection refused 42", "the disk is full"],
+    );
+    let second = mk_report(
+        "second",
+        &["error: connection refused 43", "kernel panic detected"],
+    );
+    let merged = merge(vec![first, second]).unwrap();
+    assert_eq!(merged.merged_targets.len(), 2);
+    assert_eq!(merged.log_reports.len(), 1);
+    let targets = merged.log_reports[0]
+        .anomalies
+        .iter()
+        .map(|anomaly| (anomaly.anomaly.line.as_ref(), anomaly.targets.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        targets,
+        vec![
+            ("error: connection refused 42", vec![0, 1]),
+            ("the disk is full", vec![0]),
+            ("kernel panic detected", vec![1]),
+        ]
+    );
+    assert_eq!(merged.total_anomaly_count, 3);
+    assert_eq!(
+        merged
+            .target_breakdown()
+            .iter()
+            .map(|(target, count)| (target.to_string(), *count))
+            .collect::<Vec<_>>(),
+        vec![
+            ("File(local: first)".to_string(), 2),
+            ("File(local: second)".to_string(), 2)
+        ]
+    );
+
+    // Merging a merged report keeps the provenance.
+    let third = mk_report("third", &["the disk is full"]);
+    let merged = merge(vec![third, merged]).unwrap();
+    assert_eq!(merged.merged_targets.len(), 3);
+    let disk = merged.log_reports[0]
+        .anomalies
+        .iter()
+        .find(|anomaly| anomaly.anomaly.line.as_ref() == "the disk is full")
+        .unwrap();
+    assert_eq!(disk.targets, vec![0, 1]);
+}
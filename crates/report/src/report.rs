@@ -5,7 +5,7 @@ use chrono::{DateTime, Utc};
 use itertools::Itertools;
 pub use logjuicer_tokenizer::index_name::IndexName;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::path::PathBuf;
 use std::rc::Rc;
@@ -18,8 +18,23 @@ pub mod schema_capnp {
     include!("../generated/schema_capnp.rs");
 }
 
+pub mod annotate;
+pub mod cluster;
 pub mod codec;
+pub mod diff;
+pub mod filter;
+pub mod html;
+pub mod json_v1;
+pub mod junit;
+pub mod markdown;
+pub mod merge;
+pub mod redact;
 pub mod report_row;
+pub mod sarif;
+pub mod stream;
+pub mod summary;
+pub mod test_results;
+mod xml;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Report {
@@ -33,18 +48,389 @@ pub struct Report {
     pub read_errors: Vec<(Source, Box<str>)>,
     pub total_line_count: usize,
     pub total_anomaly_count: usize,
+    pub category_policies: Vec<CategoryPolicy>,
+    pub warnings: Vec<RunWarning>,
+    /// The targets of a merged report, empty otherwise.
+    #[serde(default)]
+    pub merged_targets: Vec<Content>,
+    /// The processing statistics of every target source.
+    #[serde(default)]
+    pub source_stats: Vec<SourceStats>,
+    /// How the model was created, missing for the older reports.
+    #[serde(default)]
+    pub provenance: Option<Provenance>,
+    /// The most distant anomalies, grouped by fingerprint.
+    #[serde(default)]
+    pub summary: Vec<SummaryEntry>,
+    /// The groups of similar anomalies, the biggest first.
+    #[serde(default)]
+    pub clusters: Vec<AnomalyCluster>,
+    /// The triage notes of the anomalies, see [annotate].
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// The deep link formats of the anomalies, see [Report::anomaly_href].
+    #[serde(default)]
+    pub link_templates: Vec<LinkTemplate>,
+    /// The failing tests of the junit or subunit results, with their related anomalies.
+    #[serde(default)]
+    pub failed_tests: Vec<FailedTest>,
+}
+
+/// A group of similar anomalies, see [cluster::compute].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnomalyCluster {
+    /// The line of the first anomaly.
+    pub line: Rc<str>,
+    /// The anomalies positions, as the log report index and the anomaly index.
+    pub members: Vec<(usize, usize)>,
+}
+
+impl AnomalyCluster {
+    /// Iterate over the cluster anomalies.
+    pub fn anomalies<'a>(
+        &'a self,
+        log_reports: &'a [LogReport],
+    ) -> impl Iterator<Item = (&'a LogReport, &'a AnomalyContext)> + 'a {
+        self.members
+            .iter()
+            .filter_map(move |(lr_pos, anomaly_pos)| {
+                let lr = log_reports.get(*lr_pos)?;
+                Some((lr, lr.anomalies.get(*anomaly_pos)?))
+            })
+    }
+
+    /// The number of distinct sources.
+    pub fn source_count(&self) -> usize {
+        self.members
+            .iter()
+            .map(|(lr_pos, _)| lr_pos)
+            .unique()
+            .count()
+    }
+}
+
+/// A failing test found in the target results, see [test_results::correlate].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailedTest {
+    /// The test name, e.g. `tests.test_api.TestApi.test_create`.
+    pub name: Rc<str>,
+    /// The results file.
+    pub source: Source,
+    /// The failure message, e.g. the assertion error.
+    pub message: Rc<str>,
+    /// The test start and end time, in milliseconds since the epoch, when recorded.
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+    /// The related anomalies positions, as the log report index and the anomaly index.
+    pub anomalies: Vec<(usize, usize)>,
+}
+
+impl FailedTest {
+    /// Iterate over the related anomalies.
+    pub fn anomalies<'a>(
+        &'a self,
+        log_reports: &'a [LogReport],
+    ) -> impl Iterator<Item = (&'a LogReport, &'a AnomalyContext)> + 'a {
+        self.anomalies
+            .iter()
+            .filter_map(move |(lr_pos, anomaly_pos)| {
+                let lr = log_reports.get(*lr_pos)?;
+                Some((lr, lr.anomalies.get(*anomaly_pos)?))
+            })
+    }
+}
+
+/// A group of identical anomalies, see [summary::compute].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SummaryEntry {
+    pub fingerprint: u64,
+    /// The source of the first occurrence.
+    pub source: Source,
+    /// The line number of the first occurrence.
+    pub pos: usize,
+    pub line: Rc<str>,
+    /// The highest distance of the occurrences.
+    pub distance: f32,
+    /// The number of occurrences.
+    pub count: usize,
+}
+
+/// The details needed to reproduce a report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// The logjuicer version that created the report.
+    pub version: Box<str>,
+    /// The digest of the model content.
+    pub model_hash: Box<str>,
+    pub model_created_at: SystemTime,
+    /// The model version and the tokenizer settings used to train the model.
+    pub trainer: Box<str>,
+    /// The baseline files used to train the model.
+    pub baseline_sources: Vec<Source>,
+    /// The number of baseline builds used to train the model.
+    #[serde(default)]
+    pub baseline_count: usize,
+}
+
+/// The processing statistics of a source.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceStats {
+    pub source: Source,
+    /// The time spent reading the source, e.g. to download it.
+    pub download_time: Duration,
+    /// The time spent tokenizing and searching the lines.
+    pub analysis_time: Duration,
+    pub byte_count: usize,
+    pub line_count: usize,
+    /// The count of lines that were not already seen.
+    pub unique_line_count: usize,
+}
+
+impl SourceStats {
+    pub fn total_time(&self) -> Duration {
+        self.download_time + self.analysis_time
+    }
+}
+
+/// The kind of issue that happened while creating the report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WarningKind {
+    /// A baseline is too old to be relevant.
+    StaleBaseline,
+    /// A file was skipped because it exceeds a limit.
+    SkippedFile,
+    /// A file could not be decoded.
+    Encoding,
+    /// The model was trained with different tokenizer settings.
+    TokenizerVersion,
+    /// The analysis was stopped because it exceeds a run limit.
+    LimitReached,
+    /// Any other issue.
+    Other,
+}
+
+impl WarningKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningKind::StaleBaseline => "stale-baseline",
+            WarningKind::SkippedFile => "skipped-file",
+            WarningKind::Encoding => "encoding",
+            WarningKind::TokenizerVersion => "tokenizer-version",
+            WarningKind::LimitReached => "limit-reached",
+            WarningKind::Other => "other",
+        }
+    }
+}
+
+impl From<&str> for WarningKind {
+    fn from(s: &str) -> Self {
+        match s {
+            "stale-baseline" => WarningKind::StaleBaseline,
+            "skipped-file" => WarningKind::SkippedFile,
+            "encoding" => WarningKind::Encoding,
+            "tokenizer-version" => WarningKind::TokenizerVersion,
+            "limit-reached" => WarningKind::LimitReached,
+            // Unknown kinds from newer versions are kept as Other.
+            _ => WarningKind::Other,
+        }
+    }
+}
+
+impl std::fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The triage status of an anomaly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnnotationKind {
+    /// A comment, without a status.
+    Note,
+    /// The anomaly is not relevant.
+    FalsePositive,
+    /// The anomaly explains the failure.
+    RootCause,
+}
+
+impl AnnotationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnnotationKind::Note => "note",
+            AnnotationKind::FalsePositive => "false-positive",
+            AnnotationKind::RootCause => "root-cause",
+        }
+    }
+}
+
+impl From<&str> for AnnotationKind {
+    fn from(s: &str) -> Self {
+        match s {
+            "false-positive" => AnnotationKind::FalsePositive,
+            "root-cause" => AnnotationKind::RootCause,
+            // Unknown kinds from newer versions are kept as Note.
+            _ => AnnotationKind::Note,
+        }
+    }
+}
+
+impl std::fmt::Display for AnnotationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A triage note of an anomaly.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// The annotated anomaly, see [AnomalyContext::fingerprint].
+    pub fingerprint: u64,
+    pub kind: AnnotationKind,
+    pub note: Box<str>,
+    pub author: Box<str>,
+    pub created_at: SystemTime,
+}
+
+impl std::fmt::Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if !self.note.is_empty() {
+            write!(f, ": {}", self.note)?;
+        }
+        if !self.author.is_empty() {
+            write!(f, " ({})", self.author)?;
+        }
+        Ok(())
+    }
+}
+
+/// The link template used when the provider is not configured.
+pub const DEFAULT_LINK_TEMPLATE: &str = "{url}#line-{line}";
+
+/// The deep link format of the remote sources of a provider,
+/// where `{url}` is replaced with the source url and `{line}` with the line number.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkTemplate {
+    /// The content kind, one of: file, directory, zuul, prow, jenkins, kubernetes.
+    pub provider: Box<str>,
+    pub template: Box<str>,
+}
+
+/// An issue that happened while creating the report, which may affect its quality.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunWarning {
+    pub kind: WarningKind,
+    pub message: Box<str>,
+    pub source: Option<Source>,
+}
+
+impl std::fmt::Display for RunWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{}: {}: {}", self.kind, source, self.message),
+            None => write!(f, "{}: {}", self.kind, self.message),
+        }
+    }
+}
+
+/// The analysis policy of a source category.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategoryPolicy {
+    pub category: Box<str>,
+    pub threshold: f32,
+    pub context: usize,
+    /// Indicate if the category anomalies fail the report.
+    pub gate: bool,
 }
 
 impl Report {
+    /// Set the log reports created separately, and compute the summary, the clusters and the failed tests anomalies.
+    pub fn set_log_reports(&mut self, log_reports: Vec<LogReport>) {
+        self.log_reports = LogReport::sorted(log_reports);
+        self.summary = summary::compute(&self.log_reports, summary::TOP_N);
+        self.clusters = cluster::compute(&self.log_reports);
+        test_results::correlate(&mut self.failed_tests, &self.log_reports);
+    }
+
     pub fn anomaly_count(&self) -> usize {
         self.log_reports
             .iter()
             .fold(0, |acc, lr| acc + lr.anomalies.len())
     }
 
+    /// The anomaly count per source category.
+    pub fn category_breakdown(&self) -> BTreeMap<&str, usize> {
+        let mut breakdown = BTreeMap::new();
+        for lr in &self.log_reports {
+            let category = lr.category.as_deref().unwrap_or("uncategorized");
+            *breakdown.entry(category).or_insert(0) += lr.anomalies.len();
+        }
+        breakdown
+    }
+
+    /// The anomaly count of each merged target, the shared anomalies are counted for every target.
+    pub fn target_breakdown(&self) -> Vec<(&Content, usize)> {
+        let mut counts = vec![0; self.merged_targets.len()];
+        for anomaly in self.log_reports.iter().flat_map(|lr| lr.anomalies.iter()) {
+            for target in &anomaly.targets {
+                if let Some(count) = counts.get_mut(*target) {
+                    *count += 1;
+                }
+            }
+        }
+        self.merged_targets.iter().zip(counts).collect()
+    }
+
+    /// Check if the anomalies of a log report fail the report.
+    /// The categories without a policy are gating.
+    pub fn is_gating(&self, lr: &LogReport) -> bool {
+        self.category_policies
+            .iter()
+            .find(|policy| Some(policy.category.as_ref()) == lr.category.as_deref())
+            .is_none_or(|policy| policy.gate)
+    }
+
+    /// The link to the line of a remote source, using the template of the report target provider.
+    pub fn anomaly_href(&self, source: &Source, pos: usize) -> Option<String> {
+        match source {
+            Source::Remote(_, url) => {
+                let provider = self.target.kind();
+                let template = self
+                    .link_templates
+                    .iter()
+                    .find(|link| link.provider.as_ref() == provider)
+                    .map_or(DEFAULT_LINK_TEMPLATE, |link| link.template.as_ref());
+                Some(
+                    template
+                        .replace("{url}", url.as_str())
+                        .replace("{line}", &pos.to_string()),
+                )
+            }
+            Source::Local(_, _) => None,
+        }
+    }
+
+    /// The sources that took the most time to process.
+    pub fn slowest_sources(&self, count: usize) -> Vec<&SourceStats> {
+        self.source_stats
+            .iter()
+            .sorted_by(|a, b| b.total_time().cmp(&a.total_time()))
+            .take(count)
+            .collect()
+    }
+
+    /// The anomaly count of the categories that gate the report.
+    pub fn gating_anomaly_count(&self) -> usize {
+        self.log_reports
+            .iter()
+            .filter(|lr| self.is_gating(lr))
+            .map(|lr| lr.anomalies.len())
+            .sum()
+    }
+
     pub fn sample() -> Self {
         use std::{convert::TryInto, ops::Add};
-        Report {
+        let mut report = Report {
             created_at: SystemTime::UNIX_EPOCH.add(Duration::from_secs(42 * 24 * 3600)),
             run_time: Duration::from_secs(42),
             target: Content::File(Source::Local(4, "/proc/status".into())),
@@ -55,6 +441,18 @@ impl Report {
                 )),
                 Content::Zuul(Box::new(ZuulBuild::sample("zuul-demo"))),
                 Content::Prow(Box::new(ProwBuild::sample("prow-demo"))),
+                Content::Jenkins(Box::new(JenkinsBuild::sample("jenkins-demo"))),
+                Content::Kubernetes(Box::new(KubernetesPod::sample("k8s-demo"))),
+                Content::Loki(Box::new(LokiQuery::sample("loki-demo"))),
+                Content::Elasticsearch(Box::new(ElasticQuery::sample("es-demo"))),
+                Content::CloudWatch(Box::new(CloudWatchQuery::sample("cw-demo"))),
+                Content::Journal(Box::new(JournalQuery::sample("journal-demo"))),
+                Content::Container(Box::new(Container::sample("container-demo"))),
+                Content::Tekton(Box::new(TektonPipelineRun::sample("tekton-demo"))),
+                Content::Buildkite(Box::new(BuildkiteBuild::sample("buildkite-demo"))),
+                Content::CircleCi(Box::new(CircleCiJob::sample("circleci-demo"))),
+                Content::Koji(Box::new(KojiBuild::sample("koji-demo"))),
+                Content::Obs(Box::new(ObsBuild::sample("obs-demo"))),
                 Content::LocalZuulBuild(
                     "/executor".into(),
                     Box::new(ZuulBuild::sample("local-zuul")),
@@ -72,9 +470,12 @@ impl Report {
                         line: "anomaly".into(),
                     },
                     after: vec![],
+                    targets: vec![],
                 }],
                 index_name: IndexName("test".into()),
                 source: Source::Local(4, "/proc/status".into()),
+                category: Some("kernel".into()),
+                rotated: vec![Source::Local(4, "/proc/status.1".into())],
             }],
             index_reports: HashMap::from([(
                 IndexName("i".into()),
@@ -93,7 +494,63 @@ impl Report {
             read_errors: vec![(Source::Local(1, "".into()), "oops".into())],
             total_line_count: 42,
             total_anomaly_count: 23,
-        }
+            category_policies: vec![CategoryPolicy {
+                category: "kernel".into(),
+                threshold: 0.5,
+                context: 1,
+                gate: false,
+            }],
+            warnings: vec![
+                RunWarning {
+                    kind: WarningKind::StaleBaseline,
+                    message: "the baseline is 42 days old".into(),
+                    source: None,
+                },
+                RunWarning {
+                    kind: WarningKind::Encoding,
+                    message: "invalid utf-8".into(),
+                    source: Some(Source::Local(4, "/proc/status".into())),
+                },
+            ],
+            merged_targets: vec![],
+            source_stats: vec![SourceStats {
+                source: Source::Local(4, "/proc/status".into()),
+                download_time: Duration::from_millis(42),
+                analysis_time: Duration::from_millis(84),
+                byte_count: 13,
+                line_count: 1,
+                unique_line_count: 1,
+            }],
+            provenance: Some(Provenance {
+                version: "0.9.6".into(),
+                model_hash: "e3b0c44298fc1c14".into(),
+                model_created_at: SystemTime::UNIX_EPOCH.add(Duration::from_secs(41 * 24 * 3600)),
+                trainer: "model_version=9 strip_ansi=true".into(),
+                baseline_sources: vec![Source::Remote(
+                    4,
+                    "http://localhost/status".try_into().unwrap(),
+                )],
+                baseline_count: 1,
+            }),
+            summary: vec![],
+            clusters: vec![],
+            annotations: vec![],
+            link_templates: vec![LinkTemplate {
+                provider: "zuul".into(),
+                template: "{url}#L{line}".into(),
+            }],
+            failed_tests: vec![FailedTest {
+                name: "tests.test_status".into(),
+                source: Source::Local(4, "/proc/junit.xml".into()),
+                message: "AssertionError".into(),
+                start: Some(42 * 24 * 3600 * 1000),
+                end: None,
+                anomalies: vec![(0, 0)],
+            }],
+        };
+        report.summary = summary::compute(&report.log_reports, summary::TOP_N);
+        report.clusters = cluster::compute(&report.log_reports);
+        report
     }
 }
 
@@ -107,14 +564,85 @@ pub enum Error {
     DecodeError(#[from] capnp::Error),
 }
 
+/// The compression of a saved report, the loader detects it automatically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    /// The zstd compression with its level, only available with the `zstd` feature,
+    /// because the zstd library can not be built for the web client.
+    Zstd(i32),
+}
+
+/// The zstd level used when it is not configured.
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+impl Compression {
+    /// Pick the compression from the file extension: `.gz` or `.zst`.
+    pub fn from_path(path: &Path) -> Compression {
+        match path.extension().and_then(std::ffi::OsStr::to_str) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd(DEFAULT_ZSTD_LEVEL),
+            _ => Compression::None,
+        }
+    }
+
+    /// Detect the compression from the first bytes of the file.
+    fn detect(header: &[u8]) -> Compression {
+        if header.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd(0)
+        } else {
+            Compression::None
+        }
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_unsupported() -> Error {
+    Error::IOError(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "zstd support is not enabled",
+    ))
+}
+
 impl Report {
+    /// Save the report, compressed according to the file extension.
     pub fn save(&self, path: &Path) -> Result<(), Error> {
+        self.save_compressed(path, Compression::from_path(path))
+    }
+
+    pub fn save_compressed(&self, path: &Path, compression: Compression) -> Result<(), Error> {
         let file = std::fs::File::create(path).map_err(Error::IOError)?;
-        if path.to_string_lossy().ends_with(".gz") {
-            let dest = flate2::write::GzEncoder::new(file, flate2::Compression::fast());
-            self.save_writer(dest)
-        } else {
-            self.save_writer(file)
+        self.save_writer_compressed(file, compression)
+    }
+
+    pub fn save_writer_compressed(
+        &self,
+        dest: impl std::io::Write,
+        compression: Compression,
+    ) -> Result<(), Error> {
+        match compression {
+            Compression::None => self.save_writer(dest),
+            Compression::Gzip => {
+                let mut dest = flate2::write::GzEncoder::new(dest, flate2::Compression::fast());
+                self.save_writer(&mut dest)?;
+                dest.finish()?;
+                Ok(())
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => {
+                let mut dest = zstd::Encoder::new(dest, level)?;
+                self.save_writer(&mut dest)?;
+                dest.finish()?;
+                Ok(())
+            }
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd(_) => Err(zstd_unsupported()),
         }
     }
 
@@ -124,11 +652,11 @@ impl Report {
             .map_err(Error::DecodeError)
     }
 
+    /// Load a report, the compression and the streaming format are detected automatically.
     pub fn load(path: &Path) -> Result<Report, Error> {
-        let file = std::fs::File::open(path).map_err(Error::IOError)?;
-        if path.to_string_lossy().ends_with(".gz") {
-            let src = flate2::read::GzDecoder::new(file);
-            Self::load_reader(src)
+        let mut file = std::fs::File::open(path).map_err(Error::IOError)?;
+        if stream::is_stream(&mut file)? {
+            stream::StreamReader::new(file)?.read_report()
         } else {
             Self::load_reader(file)
         }
@@ -138,10 +666,24 @@ impl Report {
         Self::load_bufreader(std::io::BufReader::new(src))
     }
 
-    pub fn load_bufreader(src: impl std::io::BufRead) -> Result<Report, Error> {
-        codec::ReportDecoder::new()
-            .decode(src)
-            .map_err(Error::DecodeError)
+    pub fn load_bufreader(mut src: impl std::io::BufRead) -> Result<Report, Error> {
+        fn decode(src: impl std::io::BufRead) -> Result<Report, Error> {
+            codec::ReportDecoder::new()
+                .decode(src)
+                .map_err(Error::DecodeError)
+        }
+        match Compression::detect(src.fill_buf()?) {
+            Compression::None => decode(src),
+            Compression::Gzip => decode(std::io::BufReader::new(flate2::bufread::GzDecoder::new(
+                src,
+            ))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(_) => {
+                decode(std::io::BufReader::new(zstd::Decoder::with_buffer(src)?))
+            }
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd(_) => Err(zstd_unsupported()),
+        }
     }
 
     pub fn load_bytes(data: &[u8]) -> Result<Report, Error> {
@@ -229,6 +771,613 @@ impl ProwBuild {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JenkinsBuild {
+    /// The build url, terminated with a slash, e.g. `https://jenkins/job/folder/job/name/42/`.
+    pub url: Url,
+    /// The full job name, e.g. `folder/name`.
+    pub job_name: Box<str>,
+    pub number: u64,
+    pub result: Box<str>,
+}
+
+impl std::fmt::Display for JenkinsBuild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.url.as_str())
+    }
+}
+
+impl JenkinsBuild {
+    /// The url of the job, where the builds are listed.
+    pub fn job_url(&self) -> Url {
+        self.url.join("../").unwrap_or_else(|_| self.url.clone())
+    }
+
+    pub fn sample(name: &str) -> Self {
+        Self {
+            url: Url::parse(&format!("http://localhost/job/{name}-job/{}/", name.len())).unwrap(),
+            job_name: format!("{name}-job").into(),
+            number: name.len() as u64,
+            result: "SUCCESS".into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BuildkiteBuild {
+    /// The build page, e.g. `https://buildkite.com/org/pipeline/builds/42`.
+    pub url: Url,
+    pub org: Box<str>,
+    pub pipeline: Box<str>,
+    pub number: u64,
+    pub branch: Box<str>,
+    pub state: Box<str>,
+}
+
+impl std::fmt::Display for BuildkiteBuild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.url.as_str())
+    }
+}
+
+impl BuildkiteBuild {
+    pub fn sample(name: &str) -> Self {
+        Self {
+            url: Url::parse(&format!(
+                "https://buildkite.com/{name}-org/{name}/builds/{}",
+                name.len()
+            ))
+            .unwrap(),
+            org: format!("{name}-org").into(),
+            pipeline: name.into(),
+            number: name.len() as u64,
+            branch: "main".into(),
+            state: "passed".into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CircleCiJob {
+    /// The project slug, e.g. `gh/org/repo`.
+    pub slug: Box<str>,
+    pub number: u64,
+    pub job_name: Box<str>,
+    pub workflow: Box<str>,
+    pub branch: Box<str>,
+    pub status: Box<str>,
+}
+
+impl std::fmt::Display for CircleCiJob {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.slug, self.number)
+    }
+}
+
+impl CircleCiJob {
+    /// The job page.
+    pub fn job_url(&self) -> String {
+        format!("https://circleci.com/{}/{}", self.slug, self.number)
+    }
+
+    pub fn sample(name: &str) -> Self {
+        Self {
+            slug: format!("gh/{name}/repo").into(),
+            number: name.len() as u64,
+            job_name: "test".into(),
+            workflow: "ci".into(),
+            branch: "main".into(),
+            status: "success".into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KubernetesPod {
+    /// The cluster api url, e.g. `https://api.cluster:6443/`.
+    pub api_url: Url,
+    pub namespace: Box<str>,
+    pub pod: Box<str>,
+    /// The containers to read, by default all the pod containers.
+    pub containers: Vec<Box<str>>,
+    /// Read the logs of the previous container instances, e.g. before a crash.
+    pub previous: bool,
+}
+
+impl std::fmt::Display for KubernetesPod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.namespace, self.pod)?;
+        if let [container] = &self.containers[..] {
+            write!(f, ":{}", container)?;
+        }
+        Ok(())
+    }
+}
+
+impl KubernetesPod {
+    /// The url of a container logs, where the container name is the source relative path.
+    pub fn log_source(&self, container: &str) -> Result<Source, url::ParseError> {
+        let previous = if self.previous { "previous=true&" } else { "" };
+        let url = self.api_url.join(&format!(
+            "api/v1/namespaces/{}/pods/{}/log?{}container={}",
+            self.namespace, self.pod, previous, container
+        ))?;
+        Ok(Source::Remote(url.as_str().len() - container.len(), url))
+    }
+
+    pub fn sample(name: &str) -> Self {
+        Self {
+            api_url: Url::parse("https://localhost:6443/").unwrap(),
+            namespace: format!("{name}-ns").into(),
+            pod: format!("{name}-pod").into(),
+            containers: vec![format!("{name}-container").into()],
+            previous: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LokiQuery {
+    /// The loki url, e.g. `http://loki:3100/`.
+    pub url: Url,
+    /// The LogQL query, e.g. `{app="nginx"} |= "error"`.
+    pub query: Box<str>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl std::fmt::Display for LokiQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.query, TimeRange(&self.start, &self.end))
+    }
+}
+
+/// Display a query time range.
+struct TimeRange<'a>(&'a DateTime<Utc>, &'a DateTime<Utc>);
+
+impl std::fmt::Display for TimeRange<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fmt = |dt: &DateTime<Utc>| dt.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        write!(f, "[{}, {}]", fmt(self.0), fmt(self.1))
+    }
+}
+
+impl LokiQuery {
+    /// The query_range url, where the encoded query is the source relative path.
+    pub fn source(&self) -> Result<Source, url::ParseError> {
+        let query: String = url::form_urlencoded::byte_serialize(self.query.as_bytes()).collect();
+        let url = self.url.join(&format!(
+            "loki/api/v1/query_range?start={}&end={}&query={}",
+            self.start.timestamp_nanos_opt().unwrap_or_default(),
+            self.end.timestamp_nanos_opt().unwrap_or_default(),
+            query
+        ))?;
+        Ok(Source::Remote(url.as_str().len() - query.len(), url))
+    }
+
+    /// The same query for an earlier time range.
+    pub fn shift(&self, offset: chrono::Duration) -> Self {
+        Self {
+            start: self.start - offset,
+            end: self.end - offset,
+            ..self.clone()
+        }
+    }
+
+    pub fn sample(name: &str) -> Self {
+        Self {
+            url: Url::parse("http://localhost:3100/").unwrap(),
+            query: format!("{{app=\"{name}\"}}").into(),
+            start: codec::read_datetime(name.len() as u64).unwrap(),
+            end: codec::read_datetime(3_600_000 + name.len() as u64).unwrap(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ElasticQuery {
+    /// The cluster url, e.g. `http://elasticsearch:9200/`.
+    pub url: Url,
+    /// The index name or pattern, e.g. `logs-*`.
+    pub index: Box<str>,
+    /// The query string, e.g. `kubernetes.namespace_name:ci AND level:error`.
+    pub query: Box<str>,
+    /// The document field of the log line, e.g. `message`.
+    pub message_field: Box<str>,
+    /// The document field of the timestamp, e.g. `@timestamp`.
+    pub time_field: Box<str>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl std::fmt::Display for ElasticQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} {}",
+            self.index,
+            self.query,
+            TimeRange(&self.start, &self.end)
+        )
+    }
+}
+
+impl ElasticQuery {
+    /// The search url, where the encoded query is the source relative path.
+    pub fn source(&self) -> Result<Source, url::ParseError> {
+        let encode =
+            |s: &str| url::form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>();
+        let query = encode(&self.query);
+        let url = self.url.join(&format!(
+            "{}/_search?start={}&end={}&time_field={}&message_field={}&query={}",
+            self.index,
+            self.start.timestamp_millis(),
+            self.end.timestamp_millis(),
+            encode(&self.time_field),
+            encode(&self.message_field),
+            query
+        ))?;
+        Ok(Source::Remote(url.as_str().len() - query.len(), url))
+    }
+
+    /// The same query for an earlier time range.
+    pub fn shift(&self, offset: chrono::Duration) -> Self {
+        Self {
+            start: self.start - offset,
+            end: self.end - offset,
+            ..self.clone()
+        }
+    }
+
+    pub fn sample(name: &str) -> Self {
+        Self {
+            url: Url::parse("http://localhost:9200/").unwrap(),
+            index: format!("{name}-logs").into(),
+            query: format!("app:{name}").into(),
+            message_field: "message".into(),
+            time_field: "@timestamp".into(),
+            start: codec::read_datetime(name.len() as u64).unwrap(),
+            end: codec::read_datetime(3_600_000 + name.len() as u64).unwrap(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CloudWatchQuery {
+    /// The log group name, e.g. `/aws/lambda/my-function`.
+    pub log_group: Box<str>,
+    /// The log streams to read, by default all the group streams.
+    pub log_streams: Vec<Box<str>>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl std::fmt::Display for CloudWatchQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.log_group)?;
+        if !self.log_streams.is_empty() {
+            write!(f, ":{}", self.log_streams.join(","))?;
+        }
+        write!(f, " {}", TimeRange(&self.start, &self.end))
+    }
+}
+
+impl CloudWatchQuery {
+    /// The `cloudwatch://logs/` urls of the events, where the encoded stream or group name is the source relative path.
+    pub fn sources(&self) -> Result<Vec<Source>, url::ParseError> {
+        let encode =
+            |s: &str| url::form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>();
+        let base = format!(
+            "cloudwatch://logs/?start={}&end={}&group={}",
+            self.start.timestamp_millis(),
+            self.end.timestamp_millis(),
+            encode(&self.log_group)
+        );
+        if self.log_streams.is_empty() {
+            let url = Url::parse(&base)?;
+            let prefix = url.as_str().len() - encode(&self.log_group).len();
+            Ok(vec![Source::Remote(prefix, url)])
+        } else {
+            self.log_streams
+                .iter()
+                .map(|stream| {
+                    let stream = encode(stream);
+                    let url = Url::parse(&format!("{}&stream={}", base, stream))?;
+                    Ok(Source::Remote(url.as_str().len() - stream.len(), url))
+                })
+                .collect()
+        }
+    }
+
+    /// The same query for an earlier time range.
+    pub fn shift(&self, offset: chrono::Duration) -> Self {
+        Self {
+            start: self.start - offset,
+            end: self.end - offset,
+            ..self.clone()
+        }
+    }
+
+    pub fn sample(name: &str) -> Self {
+        Self {
+            log_group: format!("/{name}/group").into(),
+            log_streams: vec![format!("{name}-stream").into()],
+            start: codec::read_datetime(name.len() as u64).unwrap(),
+            end: codec::read_datetime(3_600_000 + name.len() as u64).unwrap(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JournalQuery {
+    /// The systemd units to read, by default the whole journal.
+    pub units: Vec<Box<str>>,
+    /// The boot id, to read the entries of a single boot.
+    pub boot_id: Option<Box<str>>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl std::fmt::Display for JournalQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "journal")?;
+        if !self.units.is_empty() {
+            write!(f, ":{}", self.units.join(","))?;
+        }
+        if let Some(boot_id) = &self.boot_id {
+            write!(f, " boot {}", boot_id)?;
+        }
+        if let (Some(start), Some(end)) = (&self.start, &self.end) {
+            write!(f, " {}", TimeRange(start, end))?;
+        }
+        Ok(())
+    }
+}
+
+impl JournalQuery {
+    /// The `journal://localhost/` urls of the entries, where the unit name is the source relative path.
+    pub fn sources(&self) -> Result<Vec<Source>, url::ParseError> {
+        let encode =
+            |s: &str| url::form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>();
+        let mut base = "journal://localhost/?".to_string();
+        if let Some(boot_id) = &self.boot_id {
+            base.push_str(&format!("boot={}&", encode(boot_id)));
+        }
+        if let (Some(start), Some(end)) = (&self.start, &self.end) {
+            base.push_str(&format!(
+                "since={}&until={}&",
+                start.timestamp(),
+                end.timestamp()
+            ));
+        }
+        let source = |key: &str, name: &str| {
+            let name = encode(name);
+            let url = Url::parse(&format!("{}{}={}", base, key, name))?;
+            Ok(Source::Remote(url.as_str().len() - name.len(), url))
+        };
+        if self.units.is_empty() {
+            // The whole journal is a single source.
+            Ok(vec![source("name", "journal")?])
+        } else {
+            self.units.iter().map(|unit| source("unit", unit)).collect()
+        }
+    }
+
+    pub fn sample(name: &str) -> Self {
+        Self {
+            units: vec![format!("{name}.service").into()],
+            boot_id: Some(format!("{name}-boot").into()),
+            start: None,
+            end: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Container {
+    /// The docker or podman api address, e.g. `unix:///var/run/docker.sock`.
+    pub host: Box<str>,
+    pub id: Box<str>,
+    pub name: Box<str>,
+    /// The image name, which is shared by the baseline containers.
+    pub image: Box<str>,
+}
+
+impl std::fmt::Display for Container {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.image)
+    }
+}
+
+impl Container {
+    /// The `container://localhost/` url of the logs, where the image name is the source relative path.
+    pub fn source(&self) -> Result<Source, url::ParseError> {
+        let encode =
+            |s: &str| url::form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>();
+        let image = encode(&self.image);
+        let url = Url::parse(&format!(
+            "container://localhost/?host={}&id={}&image={}",
+            encode(&self.host),
+            encode(&self.id),
+            image
+        ))?;
+        Ok(Source::Remote(url.as_str().len() - image.len(), url))
+    }
+
+    pub fn sample(name: &str) -> Self {
+        Self {
+            host: "unix:///var/run/docker.sock".into(),
+            id: format!("{name}-id").into(),
+            name: name.into(),
+            image: format!("quay.io/{name}:latest").into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TektonPipelineRun {
+    /// The cluster api url, e.g. `https://api.cluster:6443/`.
+    pub api_url: Url,
+    pub namespace: Box<str>,
+    pub pipeline_run: Box<str>,
+    /// The pipeline name, empty when the run has an embedded spec.
+    pub pipeline: Box<str>,
+    pub steps: Vec<TektonStep>,
+}
+
+/// A TaskRun step, which runs in a container of the task pod.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TektonStep {
+    /// The pipeline task name.
+    pub task: Box<str>,
+    pub pod: Box<str>,
+    pub container: Box<str>,
+}
+
+impl std::fmt::Display for TektonPipelineRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.namespace, self.pipeline_run)
+    }
+}
+
+impl TektonPipelineRun {
+    /// The url of a step logs, where the pipeline task and the container name is the source relative path.
+    pub fn log_source(&self, step: &TektonStep) -> Result<Source, url::ParseError> {
+        let name = format!("{}/{}", step.task, step.container);
+        // The api ignores the extra name parameter.
+        let url = self.api_url.join(&format!(
+            "api/v1/namespaces/{}/pods/{}/log?container={}&name={}",
+            self.namespace, step.pod, step.container, name
+        ))?;
+        Ok(Source::Remote(url.as_str().len() - name.len(), url))
+    }
+
+    pub fn sample(name: &str) -> Self {
+        Self {
+            api_url: Url::parse("https://localhost:6443/").unwrap(),
+            namespace: format!("{name}-ns").into(),
+            pipeline_run: format!("{name}-run").into(),
+            pipeline: name.into(),
+            steps: vec![TektonStep {
+                task: "build".into(),
+                pod: format!("{name}-run-build-pod").into(),
+                container: "step-compile".into(),
+            }],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KojiBuild {
+    /// The build or task page, e.g. `https://koji.fedoraproject.org/koji/taskinfo?taskID=42`.
+    pub url: Url,
+    /// The hub api url, e.g. `https://koji.fedoraproject.org/kojihub`.
+    pub hub_url: Url,
+    pub package: Box<str>,
+    /// The name-version-release of the package.
+    pub nvr: Box<str>,
+    /// The build or task state, e.g. `COMPLETE` or `FAILED`.
+    pub state: Box<str>,
+    pub logs: Vec<KojiLogs>,
+}
+
+/// The logs directory of an architecture build.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KojiLogs {
+    pub arch: Box<str>,
+    /// The task work directory, or the package logs directory of a completed build.
+    pub url: Url,
+}
+
+impl std::fmt::Display for KojiBuild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.nvr)
+    }
+}
+
+impl KojiBuild {
+    /// The url of a log file, where the architecture and the file name is the source relative path.
+    pub fn log_source(&self, logs: &KojiLogs, name: &str) -> Result<Source, url::ParseError> {
+        let path = format!("{}/{}", logs.arch, name);
+        // The file server ignores the extra name parameter.
+        let url = logs.url.join(&format!("{}?name={}", name, path))?;
+        Ok(Source::Remote(url.as_str().len() - path.len(), url))
+    }
+
+    pub fn sample(name: &str) -> Self {
+        Self {
+            url: Url::parse("https://koji.fedoraproject.org/koji/taskinfo?taskID=42").unwrap(),
+            hub_url: Url::parse("https://koji.fedoraproject.org/kojihub").unwrap(),
+            package: name.into(),
+            nvr: format!("{name}-1.0-1.fc40").into(),
+            state: "FAILED".into(),
+            logs: vec![KojiLogs {
+                arch: "x86_64".into(),
+                url: Url::parse("https://kojipkgs.fedoraproject.org/work/tasks/43/43/").unwrap(),
+            }],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObsBuild {
+    /// The api url, e.g. `https://api.opensuse.org/`.
+    pub api_url: Url,
+    pub project: Box<str>,
+    pub package: Box<str>,
+    pub targets: Vec<ObsTarget>,
+    /// Read the logs of the last succeeded builds, e.g. for the baseline.
+    pub last_succeeded: bool,
+}
+
+/// A repository and architecture build of the package.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObsTarget {
+    pub repository: Box<str>,
+    pub arch: Box<str>,
+}
+
+impl std::fmt::Display for ObsBuild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.project, self.package)?;
+        if self.last_succeeded {
+            write!(f, " (last succeeded)")?;
+        }
+        Ok(())
+    }
+}
+
+impl ObsBuild {
+    /// The url of a target build log, where the repository and the architecture is the source relative path.
+    pub fn log_source(&self, target: &ObsTarget) -> Result<Source, url::ParseError> {
+        let path = format!("{}/{}/build.log", target.repository, target.arch);
+        let last_succeeded = if self.last_succeeded {
+            "lastsucceeded=1&"
+        } else {
+            ""
+        };
+        // The api ignores the extra name parameter.
+        let url = self.api_url.join(&format!(
+            "public/build/{}/{}/{}/{}/_log?{}name={}",
+            self.project, target.repository, target.arch, self.package, last_succeeded, path
+        ))?;
+        Ok(Source::Remote(url.as_str().len() - path.len(), url))
+    }
+
+    pub fn sample(name: &str) -> Self {
+        Self {
+            api_url: Url::parse("https://api.opensuse.org/").unwrap(),
+            project: "openSUSE:Factory".into(),
+            package: name.into(),
+            targets: vec![ObsTarget {
+                repository: "standard".into(),
+                arch: "x86_64".into(),
+            }],
+            last_succeeded: false,
+        }
+    }
+}
+
 /// A source of log lines.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Content {
@@ -237,6 +1386,43 @@ pub enum Content {
     Zuul(Box<ZuulBuild>),
     Prow(Box<ProwBuild>),
     LocalZuulBuild(PathBuf, Box<ZuulBuild>),
+    Jenkins(Box<JenkinsBuild>),
+    Kubernetes(Box<KubernetesPod>),
+    Loki(Box<LokiQuery>),
+    Elasticsearch(Box<ElasticQuery>),
+    CloudWatch(Box<CloudWatchQuery>),
+    Journal(Box<JournalQuery>),
+    Container(Box<Container>),
+    Tekton(Box<TektonPipelineRun>),
+    Buildkite(Box<BuildkiteBuild>),
+    CircleCi(Box<CircleCiJob>),
+    Koji(Box<KojiBuild>),
+    Obs(Box<ObsBuild>),
+}
+
+impl Content {
+    /// The name of the content provider.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Content::File(_) => "file",
+            Content::Directory(_) => "directory",
+            Content::Zuul(_) => "zuul",
+            Content::Prow(_) => "prow",
+            Content::LocalZuulBuild(_, _) => "local-zuul-build",
+            Content::Jenkins(_) => "jenkins",
+            Content::Kubernetes(_) => "kubernetes",
+            Content::Loki(_) => "loki",
+            Content::Elasticsearch(_) => "elasticsearch",
+            Content::CloudWatch(_) => "cloudwatch",
+            Content::Journal(_) => "journal",
+            Content::Container(_) => "container",
+            Content::Tekton(_) => "tekton",
+            Content::Buildkite(_) => "buildkite",
+            Content::CircleCi(_) => "circleci",
+            Content::Koji(_) => "koji",
+            Content::Obs(_) => "obs",
+        }
+    }
 }
 
 impl std::fmt::Display for Content {
@@ -246,6 +1432,18 @@ impl std::fmt::Display for Content {
             Content::Directory(src) => write!(f, "Directory({})", src),
             Content::Zuul(build) => write!(f, "Zuul({})", build),
             Content::Prow(build) => write!(f, "Prow({})", build.url.as_str()),
+            Content::Jenkins(build) => write!(f, "Jenkins({})", build.url.as_str()),
+            Content::Kubernetes(pod) => write!(f, "Kubernetes({})", pod),
+            Content::Loki(query) => write!(f, "Loki({})", query),
+            Content::Elasticsearch(query) => write!(f, "Elasticsearch({})", query),
+            Content::CloudWatch(query) => write!(f, "CloudWatch({})", query),
+            Content::Journal(query) => write!(f, "Journal({})", query),
+            Content::Container(container) => write!(f, "Container({})", container),
+            Content::Tekton(run) => write!(f, "Tekton({})", run),
+            Content::Buildkite(build) => write!(f, "Buildkite({})", build),
+            Content::CircleCi(job) => write!(f, "CircleCi({})", job),
+            Content::Koji(build) => write!(f, "Koji({})", build),
+            Content::Obs(build) => write!(f, "Obs({})", build),
             Content::LocalZuulBuild(src, _build) => {
                 write!(f, "LocalZuulBuild({:?})", src.as_os_str())
             }
@@ -310,9 +1508,27 @@ pub struct AnomalyContext {
     pub before: Vec<Rc<str>>,
     pub anomaly: Anomaly,
     pub after: Vec<Rc<str>>,
+    /// The indexes of the report merged_targets where the anomaly was found, empty when the report is not merged.
+    #[serde(default)]
+    pub targets: Vec<usize>,
+}
+
+/// A stable hash, the std hasher is not guaranteed to be the same across releases.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
 }
 
 impl AnomalyContext {
+    /// Identify the anomaly using its source relative path and its tokenized line,
+    /// so that the same anomaly is recognized across the reports.
+    pub fn fingerprint(&self, source: &Source) -> u64 {
+        let path = source.get_relative().trim_start_matches('/');
+        let tokens = logjuicer_tokenizer::process(&self.anomaly.line);
+        fnv1a(format!("{}\n{}", path, tokens).as_bytes())
+    }
+
     pub fn mean(anomalies: &[AnomalyContext]) -> f32 {
         match anomalies.len() {
             0 => 0.0,
@@ -329,6 +1545,10 @@ pub struct LogReport {
     pub anomalies: Vec<AnomalyContext>,
     pub source: Source,
     pub index_name: IndexName,
+    /// The source category, e.g. application, database, network, kernel or ci-infra.
+    pub category: Option<Box<str>>,
+    /// The rotated files, e.g. `app.log.1`, analyzed before the source in chronological order.
+    pub rotated: Vec<Source>,
 }
 
 impl LogReport {
@@ -365,9 +1585,12 @@ fn test_report_sort() {
             },
             before: Vec::new(),
             after: Vec::new(),
+            targets: Vec::new(),
         }],
         source: mk_src(name),
         index_name: IndexName::new(),
+        category: None,
+        rotated: Vec::new(),
     };
     let reports = vec![
         mk_lr("service.log"),
@@ -437,3 +1660,57 @@ impl ApiUrl {
         Ok(ApiUrl(url?))
     }
 }
+
+#[test]
+fn test_gating_anomaly_count() {
+    let mut report = Report::sample();
+    assert_eq!(report.gating_anomaly_count(), 0);
+    report.category_policies.clear();
+    assert_eq!(report.gating_anomaly_count(), 1);
+}
+
+#[test]
+fn test_anomaly_href() {
+    let mut report = Report::sample();
+    let source = Source::Remote(
+        17,
+        Url::parse("http://localhost/logs/job-output.txt").unwrap(),
+    );
+    assert_eq!(
+        report.anomaly_href(&source, 42).as_deref(),
+        Some("http://localhost/logs/job-output.txt#line-42")
+    );
+    report.target = Content::Directory(Source::Remote(
+        0,
+        Url::parse("http://localhost/logs/").unwrap(),
+    ));
+    report.link_templates.push(LinkTemplate {
+        provider: "directory".into(),
+        template: "{url}?line={line}".into(),
+    });
+    assert_eq!(
+        report.anomaly_href(&source, 42).as_deref(),
+        Some("http://localhost/logs/job-output.txt?line=42")
+    );
+    assert_eq!(report.anomaly_href(&report.log_reports[0].source, 1), None);
+}
+
+#[test]
+fn test_compression() {
+    let report = Report::sample();
+    let mut compressions = vec![Compression::None, Compression::Gzip];
+    if cfg!(feature = "zstd") {
+        compressions.push(Compression::Zstd(DEFAULT_ZSTD_LEVEL));
+    }
+    for compression in compressions {
+        let mut buf = Vec::new();
+        report
+            .save_writer_compressed(&mut buf, compression)
+            .unwrap();
+        assert_eq!(Report::load_bytes(&buf).unwrap(), report);
+    }
+    assert_eq!(
+        Compression::from_path(Path::new("report.zst")),
+        Compression::Zstd(DEFAULT_ZSTD_LEVEL)
+    );
+}
@@ -35,6 +35,8 @@ impl From<i64> for ReportID {
 pub enum ReportStatus {
     Pending,
     Completed,
+    /// The analysis was cancelled by a user.
+    Cancelled,
     Error(String),
 }
 
@@ -43,6 +45,7 @@ impl ReportStatus {
         match self {
             ReportStatus::Pending => "pending",
             ReportStatus::Completed => "done",
+            ReportStatus::Cancelled => "cancelled",
             ReportStatus::Error(e) => e.as_str(),
         }
     }
@@ -53,11 +56,25 @@ impl From<String> for ReportStatus {
         match value.as_str() {
             "pending" => ReportStatus::Pending,
             "done" => ReportStatus::Completed,
+            "cancelled" => ReportStatus::Cancelled,
             _ => ReportStatus::Error(value),
         }
     }
 }
 
+/// A report with an anomaly matching a search query.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub report_id: ReportID,
+    pub target: Box<str>,
+    pub updated_at: NaiveDateTime,
+    /// The number of matching anomalies.
+    pub matches: i64,
+    /// The source and the line of the first match.
+    pub path: Box<str>,
+    pub line: Box<str>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ReportRow {
     pub id: ReportID,
@@ -67,4 +84,60 @@ pub struct ReportRow {
     pub baseline: Box<str>,
     pub anomaly_count: i64,
     pub status: ReportStatus,
+    /// The user who submitted the report, when the api is authenticated with OpenID Connect.
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// The original report, when this report is a re-run.
+    #[serde(default)]
+    pub rerun_of: Option<i64>,
+    /// The pinned reports are not removed by the retention policy.
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+/// The phase of a running report.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportPhase {
+    /// The report is waiting for a worker.
+    Waiting,
+    /// The target and the baselines are being resolved.
+    Resolving,
+    /// The model is trained with the baselines.
+    Training,
+    /// The target is analyzed.
+    Analyzing,
+    /// The report is being stored.
+    Saving,
+    Done,
+    Cancelled,
+    Error,
+}
+
+impl ReportPhase {
+    /// Indicate if the report is finished.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            ReportPhase::Done | ReportPhase::Cancelled | ReportPhase::Error
+        )
+    }
+}
+
+/// A progress event of a running report, sent over the websocket.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportEvent {
+    /// The position of the event, starting at 0, to resume the watch after the last received event.
+    pub seq: u64,
+    pub phase: ReportPhase,
+    pub message: Box<str>,
+    /// The ratio of the analyzed files out of the discovered files, from 0 to 100.
+    /// The files are discovered while the report runs, so it can decrease when the analysis starts.
+    pub percent: u8,
+    /// The bytes read from the sources.
+    pub bytes: u64,
+    /// The number of discovered and analyzed files, for the baselines and the target.
+    pub files: u64,
+    pub analyzed: u64,
+    pub anomalies: u64,
 }
@@ -0,0 +1,265 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the SARIF 2.1 serialization of the report, for the code scanning tools.
+//!
+//! Each anomaly is a result located at its source line, with the surrounding context as the context region.
+//! The fingerprint is computed from the tokenized line, so that the same anomaly is tracked across the runs.
+
+use serde::Serialize;
+
+use crate::{AnomalyContext, LogReport, Report};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const RULE_ID: &str = "log-anomaly";
+
+#[derive(Debug, Serialize)]
+pub struct Sarif {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Debug, Serialize)]
+struct Run {
+    tool: Tool,
+    /// The report summary, listed before the results.
+    #[serde(skip_serializing_if = "RunProperties::is_empty")]
+    properties: RunProperties,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Debug, Serialize)]
+struct RunProperties {
+    summary: Vec<SummaryProperty>,
+}
+
+impl RunProperties {
+    fn is_empty(&self) -> bool {
+        self.summary.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SummaryProperty {
+    uri: String,
+    start_line: usize,
+    line: String,
+    distance: f32,
+    count: usize,
+    fingerprint: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: &'static str,
+    version: String,
+    information_uri: &'static str,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: &'static str,
+    short_description: Message,
+}
+
+#[derive(Debug, Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: &'static str,
+    level: &'static str,
+    message: Message,
+    locations: Vec<Location>,
+    partial_fingerprints: Fingerprints,
+    /// The link to the line in the original log.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hosted_viewer_uri: Option<String>,
+    properties: Properties,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+    context_region: Region,
+}
+
+#[derive(Debug, Serialize)]
+struct ArtifactLocation {
+    uri: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: usize,
+    end_line: usize,
+    snippet: Message,
+}
+
+#[derive(Debug, Serialize)]
+struct Fingerprints {
+    #[serde(rename = "logjuicerTokens/v1")]
+    tokens: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Properties {
+    distance: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<Box<str>>,
+}
+
+fn make_result(report: &Report, lr: &LogReport, anomaly: &AnomalyContext) -> SarifResult {
+    let gating = report.is_gating(lr);
+    let uri = lr.source.get_relative().trim_start_matches('/').to_string();
+    let line = anomaly.anomaly.line.to_string();
+    // The line numbers start at 1.
+    let pos = anomaly.anomaly.pos.max(1);
+    let context = anomaly
+        .before
+        .iter()
+        .chain(std::iter::once(&anomaly.anomaly.line))
+        .chain(anomaly.after.iter())
+        .map(|line| line.as_ref())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let fingerprint = anomaly.fingerprint(&lr.source);
+    SarifResult {
+        rule_id: RULE_ID,
+        level: if gating { "error" } else { "note" },
+        message: Message { text: line.clone() },
+        locations: vec![Location {
+            physical_location: PhysicalLocation {
+                artifact_location: ArtifactLocation { uri },
+                region: Region {
+                    start_line: pos,
+                    end_line: pos,
+                    snippet: Message { text: line },
+                },
+                context_region: Region {
+                    start_line: pos.saturating_sub(anomaly.before.len()).max(1),
+                    end_line: pos + anomaly.after.len(),
+                    snippet: Message { text: context },
+                },
+            },
+        }],
+        partial_fingerprints: Fingerprints {
+            tokens: format!("{:016x}", fingerprint),
+        },
+        hosted_viewer_uri: report.anomaly_href(&lr.source, anomaly.anomaly.pos),
+        properties: Properties {
+            distance: anomaly.anomaly.distance,
+            category: lr.category.clone(),
+        },
+    }
+}
+
+impl Sarif {
+    /// Convert a report, the version is the logjuicer release.
+    pub fn from_report(report: &Report, version: &str) -> Sarif {
+        let results = report
+            .log_reports
+            .iter()
+            .flat_map(|lr| {
+                lr.anomalies
+                    .iter()
+                    .map(move |anomaly| make_result(report, lr, anomaly))
+            })
+            .collect();
+        Sarif {
+            schema: SCHEMA,
+            version: "2.1.0",
+            runs: vec![Run {
+                tool: Tool {
+                    driver: Driver {
+                        name: "logjuicer",
+                        version: version.to_string(),
+                        information_uri: "https://github.com/logjuicer/logjuicer",
+                        rules: vec![Rule {
+                            id: RULE_ID,
+                            short_description: Message {
+                                text: "A log line that is not found in the baselines".into(),
+                            },
+                        }],
+                    },
+                },
+                properties: RunProperties {
+                    summary: report
+                        .summary
+                        .iter()
+                        .map(|entry| SummaryProperty {
+                            uri: entry
+                                .source
+                                .get_relative()
+                                .trim_start_matches('/')
+                                .to_string(),
+                            start_line: entry.pos.max(1),
+                            line: entry.line.to_string(),
+                            distance: entry.distance,
+                            count: entry.count,
+                            fingerprint: format!("{:016x}", entry.fingerprint),
+                        })
+                        .collect(),
+                },
+                results,
+            }],
+        }
+    }
+
+    pub fn save_writer(&self, dest: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(dest, self)
+    }
+}
+
+#[test]
+fn test_sarif() {
+    let report = Report::sample();
+    let sarif = serde_json::to_value(Sarif::from_report(&report, "0.9.6")).unwrap();
+    assert_eq!(sarif["version"], "2.1.0");
+    let result = &sarif["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], RULE_ID);
+    // The kernel category does not gate the report.
+    assert_eq!(result["level"], "note");
+    let location = &result["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "c/status");
+    assert_eq!(location["region"]["startLine"], 1);
+    assert_eq!(
+        location["contextRegion"]["snippet"]["text"],
+        "before\n...\nanomaly"
+    );
+    assert_eq!(
+        result["partialFingerprints"]["logjuicerTokens/v1"]
+            .as_str()
+            .map(str::len),
+        Some(16)
+    );
+    let summary = &sarif["runs"][0]["properties"]["summary"][0];
+    assert_eq!(summary["count"], 1);
+    assert_eq!(
+        summary["fingerprint"],
+        result["partialFingerprints"]["logjuicerTokens/v1"]
+    );
+}
@@ -0,0 +1,200 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the streaming report format, for the targets that are too big to keep the report in memory.
+//!
+//! The log reports are appended as soon as they are created, and the rest of the report is written at the end,
+//! followed by an index footer:
+//!
+//! ```text
+//! MAGIC | log report message... | report message | log report offsets (u64) | report offset (u64) | count (u64) | MAGIC
+//! ```
+//!
+//! The integers are little-endian, and the messages are the capnp encoding of the regular report.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::codec::{ReportDecoder, ReportEncoder};
+use crate::{Error, LogReport, Report};
+
+/// The first and the last bytes of a streaming report.
+pub const MAGIC: &[u8; 8] = b"LJSTREAM";
+
+/// The footer size after the offsets table: the report offset, the count and the magic.
+const FOOTER_SIZE: u64 = 24;
+
+fn invalid(message: &str) -> Error {
+    Error::IOError(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message.to_string(),
+    ))
+}
+
+/// Check if the source is a streaming report, the position is reset to the start.
+pub fn is_stream<R: Read + Seek>(src: &mut R) -> Result<bool, Error> {
+    let mut magic = Vec::with_capacity(MAGIC.len());
+    src.seek(SeekFrom::Start(0))?;
+    src.by_ref()
+        .take(MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    src.seek(SeekFrom::Start(0))?;
+    Ok(magic == MAGIC)
+}
+
+/// Write a report incrementally.
+pub struct StreamWriter<W: Write> {
+    dest: W,
+    offset: u64,
+    offsets: Vec<u64>,
+}
+
+impl<W: Write> StreamWriter<W> {
+    pub fn new(mut dest: W) -> Result<StreamWriter<W>, Error> {
+        dest.write_all(MAGIC)?;
+        Ok(StreamWriter {
+            dest,
+            offset: MAGIC.len() as u64,
+            offsets: Vec::new(),
+        })
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.dest.write_all(buf)?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Append a log report.
+    pub fn append(&mut self, log_report: &LogReport) -> Result<(), Error> {
+        let mut buf = Vec::new();
+        ReportEncoder::new().encode_log_report(log_report, &mut buf)?;
+        self.offsets.push(self.offset);
+        self.write(&buf)
+    }
+
+    /// Write the rest of the report and the index footer.
+    /// The report log reports are expected to be empty, they are not written.
+    pub fn finish(mut self, report: &Report) -> Result<W, Error> {
+        let report_offset = self.offset;
+        let mut buf = Vec::new();
+        ReportEncoder::new().encode(report, &mut buf)?;
+        for offset in &self.offsets {
+            buf.extend_from_slice(&offset.to_le_bytes());
+        }
+        buf.extend_from_slice(&report_offset.to_le_bytes());
+        buf.extend_from_slice(&(self.offsets.len() as u64).to_le_bytes());
+        buf.extend_from_slice(MAGIC);
+        self.write(&buf)?;
+        self.dest.flush()?;
+        Ok(self.dest)
+    }
+}
+
+/// Read a streaming report, the log reports can be loaded individually.
+pub struct StreamReader<R: Read + Seek> {
+    src: R,
+    report_offset: u64,
+    offsets: Vec<u64>,
+}
+
+fn read_u64<R: Read>(src: &mut R) -> Result<u64, Error> {
+    let mut buf = [0; 8];
+    src.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+impl<R: Read + Seek> StreamReader<R> {
+    pub fn new(mut src: R) -> Result<StreamReader<R>, Error> {
+        if !is_stream(&mut src)? {
+            return Err(invalid("not a streaming report"));
+        }
+        let len = src.seek(SeekFrom::End(0))?;
+        if len < MAGIC.len() as u64 + FOOTER_SIZE {
+            return Err(invalid("incomplete streaming report"));
+        }
+        src.seek(SeekFrom::Start(len - FOOTER_SIZE))?;
+        let report_offset = read_u64(&mut src)?;
+        let count = read_u64(&mut src)?;
+        let mut magic = [0; 8];
+        src.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("incomplete streaming report"));
+        }
+        // The offsets table is between the report message and the footer.
+        let table_start = count
+            .checked_mul(8)
+            .and_then(|size| (len - FOOTER_SIZE).checked_sub(size))
+            .filter(|start| *start >= MAGIC.len() as u64 && report_offset <= *start)
+            .ok_or_else(|| invalid("invalid log reports count"))?;
+        src.seek(SeekFrom::Start(table_start))?;
+        let offsets = (0..count)
+            .map(|_| read_u64(&mut src))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(StreamReader {
+            src,
+            report_offset,
+            offsets,
+        })
+    }
+
+    /// The number of log reports.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    pub fn read_log_report(&mut self, idx: usize) -> Result<LogReport, Error> {
+        let offset = *self
+            .offsets
+            .get(idx)
+            .ok_or_else(|| invalid("unknown log report"))?;
+        self.src.seek(SeekFrom::Start(offset))?;
+        Ok(ReportDecoder::new().decode_log_report(std::io::BufReader::new(&mut self.src))?)
+    }
+
+    /// Load the full report, the summary, the clusters and the failed tests anomalies are computed with the loaded log reports.
+    pub fn read_report(&mut self) -> Result<Report, Error> {
+        self.src.seek(SeekFrom::Start(self.report_offset))?;
+        let mut report = ReportDecoder::new().decode(std::io::BufReader::new(&mut self.src))?;
+        let log_reports = (0..self.len())
+            .map(|idx| self.read_log_report(idx))
+            .collect::<Result<Vec<_>, _>>()?;
+        report.set_log_reports(log_reports);
+        Ok(report)
+    }
+}
+
+#[test]
+fn test_stream() {
+    let mut report = Report::sample();
+    let log_reports = std::mem::take(&mut report.log_reports);
+    let mut writer = StreamWriter::new(std::io::Cursor::new(Vec::new())).unwrap();
+    for lr in &log_reports {
+        writer.append(lr).unwrap();
+    }
+    let mut dest = writer.finish(&report).unwrap();
+    assert!(is_stream(&mut dest).unwrap());
+
+    let mut reader = StreamReader::new(dest).unwrap();
+    assert_eq!(reader.len(), 1);
+    assert_eq!(reader.read_log_report(0).unwrap(), log_reports[0]);
+    // The summary, the clusters and the failed tests anomalies are computed on load.
+    let mut expected = Report::sample();
+    expected.set_log_reports(log_reports);
+    assert_eq!(reader.read_report().unwrap(), expected);
+}
+
+#[test]
+fn test_stream_invalid_count() {
+    let dest = StreamWriter::new(std::io::Cursor::new(Vec::new()))
+        .unwrap()
+        .finish(&Report::sample())
+        .unwrap();
+    let mut buf = dest.into_inner();
+    let count_pos = buf.len() - MAGIC.len() - 8;
+    buf[count_pos..count_pos + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(StreamReader::new(std::io::Cursor::new(buf)).is_err());
+}
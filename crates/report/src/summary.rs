@@ -0,0 +1,72 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the executive summary of the report: the most distant anomalies across all the sources.
+//!
+//! The identical anomalies are grouped using their fingerprint, so that a repeated failure is listed once with its occurrence count.
+
+use std::collections::HashMap;
+
+use crate::{LogReport, SummaryEntry};
+
+/// The number of summary entries computed when the report is created.
+pub const TOP_N: usize = 10;
+
+/// Compute the top anomalies, the most distant first.
+pub fn compute(log_reports: &[LogReport], count: usize) -> Vec<SummaryEntry> {
+    let mut entries: Vec<SummaryEntry> = Vec::new();
+    let mut positions: HashMap<u64, usize> = HashMap::new();
+    for lr in log_reports {
+        for anomaly in &lr.anomalies {
+            let fingerprint = anomaly.fingerprint(&lr.source);
+            // The merged anomalies occurred once per target.
+            let occurrences = anomaly.targets.len().max(1);
+            match positions.get(&fingerprint) {
+                Some(pos) => {
+                    let entry = &mut entries[*pos];
+                    entry.count += occurrences;
+                    entry.distance = entry.distance.max(anomaly.anomaly.distance);
+                }
+                None => {
+                    positions.insert(fingerprint, entries.len());
+                    entries.push(SummaryEntry {
+                        fingerprint,
+                        source: lr.source.clone(),
+                        pos: anomaly.anomaly.pos,
+                        line: anomaly.anomaly.line.clone(),
+                        distance: anomaly.anomaly.distance,
+                        count: occurrences,
+                    });
+                }
+            }
+        }
+    }
+    // The stable sort keeps the first occurrence first for the same distance.
+    entries.sort_by(|a, b| b.distance.total_cmp(&a.distance));
+    entries.truncate(count);
+    entries
+}
+
+#[test]
+fn test_summary() {
+    let mut report = crate::Report::sample();
+    let anomaly = report.log_reports[0].anomalies[0].clone();
+    report.log_reports[0].anomalies = [("timeout 42", 0.5), ("oops", 0.9), ("timeout 43", 0.7)]
+        .iter()
+        .map(|(line, distance)| {
+            let mut anomaly = anomaly.clone();
+            anomaly.anomaly.line = (*line).into();
+            anomaly.anomaly.distance = *distance;
+            anomaly
+        })
+        .collect();
+    let summary = compute(&report.log_reports, 10)
+        .into_iter()
+        .map(|entry| (entry.line.to_string(), entry.count, entry.distance))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![("oops".into(), 1, 0.9), ("timeout 42".into(), 2, 0.7)]
+    );
+    assert_eq!(compute(&report.log_reports, 1).len(), 1);
+}
@@ -0,0 +1,169 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the correlation of the failing tests with the anomalies.
+//!
+//! An anomaly is related to a failing test when it happened during the test run, with a margin of [MARGIN_MS],
+//! or when it mentions the test name. The anomaly time is read from the first timestamp of its line,
+//! or of the closest context line before it, e.g. `2023-06-01 10:00:00.123`, and it is assumed to be UTC.
+
+use chrono::NaiveDateTime;
+use std::convert::TryFrom;
+
+use crate::{AnomalyContext, FailedTest, LogReport};
+
+/// The time margin around the test run, in milliseconds.
+pub const MARGIN_MS: u64 = 5_000;
+
+fn is_digits(bytes: &[u8]) -> bool {
+    bytes.iter().all(u8::is_ascii_digit)
+}
+
+/// Check if the bytes start with a `YYYY-MM-DD HH:MM:SS` or `YYYY-MM-DDTHH:MM:SS` timestamp.
+fn is_timestamp(b: &[u8]) -> bool {
+    b.len() >= 19
+        && is_digits(&b[0..4])
+        && b[4] == b'-'
+        && is_digits(&b[5..7])
+        && b[7] == b'-'
+        && is_digits(&b[8..10])
+        && (b[10] == b' ' || b[10] == b'T')
+        && is_digits(&b[11..13])
+        && b[13] == b':'
+        && is_digits(&b[14..16])
+        && b[16] == b':'
+        && is_digits(&b[17..19])
+}
+
+/// Read the first timestamp of a line, in milliseconds since the epoch.
+pub fn parse_timestamp(line: &str) -> Option<u64> {
+    let bytes = line.as_bytes();
+    let pos = (0..bytes.len()).find(|pos| is_timestamp(&bytes[*pos..]))?;
+    // The timestamp is made of ascii characters, so the position is a char boundary.
+    let date = NaiveDateTime::parse_from_str(&line[pos..pos + 19], "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(&line[pos..pos + 19], "%Y-%m-%dT%H:%M:%S"))
+        .ok()?;
+    let millis = match bytes.get(pos + 19) {
+        Some(b'.' | b',') => bytes[pos + 20..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .chain(b"000".iter())
+            .take(3)
+            .fold(0, |acc, c| acc * 10 + u64::from(c - b'0')),
+        _ => 0,
+    };
+    u64::try_from(date.timestamp_millis())
+        .ok()
+        .map(|ts| ts + millis)
+}
+
+/// The time of an anomaly, using the closest timestamp.
+fn anomaly_timestamp(anomaly: &AnomalyContext) -> Option<u64> {
+    parse_timestamp(&anomaly.anomaly.line).or_else(|| {
+        anomaly
+            .before
+            .iter()
+            .rev()
+            .find_map(|line| parse_timestamp(line))
+    })
+}
+
+/// The last part of a test name, e.g. `test_create` for `tests.test_api.TestApi.test_create`.
+fn short_name(name: &str) -> &str {
+    name.rsplit(['.', ':', '/'])
+        .find(|part| !part.is_empty())
+        .unwrap_or(name)
+}
+
+impl FailedTest {
+    /// Check if a time is during the test run.
+    fn is_during(&self, timestamp: u64) -> bool {
+        match self.start {
+            Some(start) => {
+                let end = self.end.unwrap_or(start);
+                start.saturating_sub(MARGIN_MS) <= timestamp && timestamp <= end + MARGIN_MS
+            }
+            None => false,
+        }
+    }
+}
+
+/// Set the related anomalies of the failing tests.
+pub fn correlate(failed_tests: &mut [FailedTest], log_reports: &[LogReport]) {
+    let anomalies = log_reports
+        .iter()
+        .enumerate()
+        .flat_map(|(lr_pos, lr)| {
+            lr.anomalies
+                .iter()
+                .enumerate()
+                .map(move |(anomaly_pos, anomaly)| {
+                    ((lr_pos, anomaly_pos), anomaly_timestamp(anomaly), anomaly)
+                })
+        })
+        .collect::<Vec<_>>();
+    for test in failed_tests {
+        let name = short_name(&test.name);
+        test.anomalies = anomalies
+            .iter()
+            .filter(|(_, timestamp, anomaly)| {
+                timestamp.is_some_and(|ts| test.is_during(ts))
+                    || anomaly.anomaly.line.contains(name)
+            })
+            .map(|(pos, _, _)| *pos)
+            .collect();
+    }
+}
+
+#[test]
+fn test_parse_timestamp() {
+    assert_eq!(
+        parse_timestamp("2023-06-01 10:00:00.123456 | ERROR"),
+        Some(1685613600123)
+    );
+    assert_eq!(
+        parse_timestamp("[2023-06-01T10:00:01Z] ERROR"),
+        Some(1685613601000)
+    );
+    assert_eq!(parse_timestamp("2023-06-01 ERROR"), None);
+    assert_eq!(
+        short_name("tests.test_api.TestApi.test_create"),
+        "test_create"
+    );
+    assert_eq!(short_name("tests/test_api.py::test_create"), "test_create");
+}
+
+#[test]
+fn test_correlate() {
+    let mut report = crate::Report::sample();
+    let lr = &mut report.log_reports[0];
+    lr.anomalies[0].anomaly.line = "2023-06-01 10:00:03 connection refused".into();
+    lr.anomalies.push(AnomalyContext {
+        anomaly: crate::Anomaly {
+            line: "2023-06-01 11:00:00 disk full".into(),
+            ..lr.anomalies[0].anomaly.clone()
+        },
+        ..lr.anomalies[0].clone()
+    });
+    lr.anomalies.push(AnomalyContext {
+        anomaly: crate::Anomaly {
+            line: "Traceback in test_status".into(),
+            ..lr.anomalies[0].anomaly.clone()
+        },
+        ..lr.anomalies[0].clone()
+    });
+    report.failed_tests[0].start = Some(1685613600000);
+    report.failed_tests[0].end = Some(1685613601000);
+    correlate(&mut report.failed_tests, &report.log_reports);
+    assert_eq!(report.failed_tests[0].anomalies, vec![(0, 0), (0, 2)]);
+    assert_eq!(
+        report.failed_tests[0]
+            .anomalies(&report.log_reports)
+            .map(|(_, anomaly)| anomaly.anomaly.line.as_ref())
+            .collect::<Vec<_>>(),
+        vec![
+            "2023-06-01 10:00:03 connection refused",
+            "Traceback in test_status"
+        ]
+    );
+}
@@ -0,0 +1,30 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the escaping of the text written in the XML and HTML documents.
+
+/// Escape the XML special characters, and remove the characters that are not allowed.
+pub(crate) fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            '\t' | '\n' | '\r' => result.push(c),
+            c if c < ' ' => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[test]
+fn test_escape() {
+    assert_eq!(
+        escape("<a href=\"x\">it's & \u{1b}[31m\t</a>"),
+        "&lt;a href=&quot;x&quot;&gt;it&apos;s &amp; [31m\t&lt;/a&gt;"
+    );
+}
@@ -0,0 +1,426 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+#![warn(missing_docs)]
+
+//! This library provides a minimal client for the [s3](https://aws.amazon.com/s3/) compatible object stores.
+//!
+//! The objects are addressed with `s3://bucket/key` urls, and the requests are sent to the
+//! path-style endpoint, e.g. `https://s3.us-east-1.amazonaws.com/bucket/key`, so that the
+//! S3 compatible stores such as MinIO or Ceph work too.
+//! When the credentials are available, the requests are signed using the AWS signature version 4.
+//!
+//! Here is an example usage:
+//!
+//! ```no_run
+//! # fn main() {
+//! let client = s3_bucket::Client::from_env();
+//! let agent = ureq::Agent::new();
+//! for object in client.list_objects(&agent, "logs", "builds/42/").unwrap() {
+//!     println!("{}: {}", object.key, object.size);
+//! }
+//! # }
+//! ```
+
+use aws_auth::{env_var, SignatureParams};
+use serde::Deserialize;
+use std::time::SystemTime;
+use thiserror::Error;
+use url::Url;
+
+pub use aws_auth::{region_from_env, Credentials};
+
+/// The s3 error.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The provided url is not usable.
+    #[error("bad url: {0}")]
+    BadUrl(#[from] url::ParseError),
+
+    /// The api reply couldn't be read.
+    #[error("bad api reply: {0}")]
+    BadReply(#[from] std::io::Error),
+
+    /// The api query failed.
+    #[error("bad api query: {0}")]
+    BadQuery(#[from] Box<ureq::Error>),
+
+    /// The api xml reply couldn't be decoded.
+    #[error("bad xml reply: {0}")]
+    BadXml(#[from] quick_xml::DeError),
+
+    /// The request couldn't be signed.
+    #[error(transparent)]
+    BadSignature(#[from] aws_auth::Error),
+}
+
+/// The location of an object, or of an objects prefix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectUrl {
+    /// The bucket name.
+    pub bucket: String,
+    /// The object key, without the leading slash.
+    pub key: String,
+}
+
+/// Decode a `s3://bucket/key` url.
+pub fn parse_s3_url(url: &Url) -> Option<ObjectUrl> {
+    if url.scheme() != "s3" {
+        return None;
+    }
+    let bucket = url.host_str()?;
+    if bucket.is_empty() {
+        return None;
+    }
+    Some(ObjectUrl {
+        bucket: bucket.to_string(),
+        key: uri_decode(url.path().trim_start_matches('/')),
+    })
+}
+
+/// Create the `s3://bucket/key` url of an object.
+pub fn object_url(bucket: &str, key: &str) -> Result<Url, Error> {
+    Ok(Url::parse(&format!(
+        "s3://{}/{}",
+        bucket,
+        uri_encode(key, false)
+    ))?)
+}
+
+/// Encode a value as expected by the signature, where only the unreserved characters are kept.
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut result = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(byte as char)
+            }
+            b'/' if !encode_slash => result.push('/'),
+            _ => result.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    result
+}
+
+fn uri_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let decoded = if bytes[pos] == b'%' {
+            value
+                .get(pos + 1..pos + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match decoded {
+            Some(byte) => {
+                result.push(byte);
+                pos += 3;
+            }
+            None => {
+                result.push(bytes[pos]);
+                pos += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+/// A bucket object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Object {
+    /// The object key.
+    pub key: String,
+    /// The object size in bytes.
+    pub size: u64,
+}
+
+/// The object store client.
+#[derive(Clone, Debug)]
+pub struct Client {
+    /// The endpoint url, e.g. `https://s3.us-east-1.amazonaws.com/`.
+    pub endpoint: Url,
+    /// The region used for the signature.
+    pub region: String,
+    /// The credentials, the requests are anonymous when they are missing.
+    pub credentials: Option<Credentials>,
+}
+
+impl Client {
+    /// Create a client using the standard `AWS_*` environment variables.
+    /// The `AWS_ENDPOINT_URL` setting can be used for the S3 compatible stores.
+    pub fn from_env() -> Client {
+        let region = region_from_env();
+        let endpoint = env_var("AWS_ENDPOINT_URL_S3")
+            .or_else(|| env_var("AWS_ENDPOINT_URL"))
+            .and_then(|endpoint| Url::parse(&endpoint).ok())
+            .unwrap_or_else(|| {
+                Url::parse(&format!("https://s3.{}.amazonaws.com/", region))
+                    .expect("Valid region endpoint")
+            });
+        Client {
+            endpoint,
+            region,
+            credentials: Credentials::from_env(),
+        }
+    }
+
+    /// The path-style url of an object, or of the bucket when the key is empty.
+    fn http_url(&self, bucket: &str, key: &str, query: &[(&str, &str)]) -> Result<Url, Error> {
+        let mut path = format!(
+            "{}/{}",
+            self.endpoint.path().trim_end_matches('/'),
+            uri_encode(bucket, true)
+        );
+        if !key.is_empty() {
+            path.push('/');
+            path.push_str(&uri_encode(key, false));
+        }
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+        if query.is_empty() {
+            url.set_query(None);
+        } else {
+            let query = query
+                .iter()
+                .map(|(k, v)| format!("{}={}", uri_encode(k, true), uri_encode(v, true)))
+                .collect::<Vec<_>>()
+                .join("&");
+            url.set_query(Some(&query));
+        }
+        Ok(url)
+    }
+
+    fn signed_request(
+        &self,
+        agent: &ureq::Agent,
+        method: &str,
+        url: &Url,
+        payload: &[u8],
+    ) -> Result<ureq::Request, Error> {
+        let request = agent.request_url(method, url);
+        Ok(match &self.credentials {
+            None => request,
+            Some(credentials) => aws_auth::signature_headers(
+                credentials,
+                &SignatureParams {
+                    region: &self.region,
+                    service: "s3",
+                    method,
+                    url,
+                    headers: &[],
+                    payload,
+                    time: SystemTime::now(),
+                },
+            )?
+            .iter()
+            .fold(request, |request, (k, v)| request.set(k, v)),
+        })
+    }
+
+    /// Create the request of an object, for example to GET or HEAD it.
+    pub fn object_request(
+        &self,
+        agent: &ureq::Agent,
+        method: &str,
+        object: &ObjectUrl,
+    ) -> Result<ureq::Request, Error> {
+        let url = self.http_url(&object.bucket, &object.key, &[])?;
+        tracing::debug!(url = url.as_str(), "Requesting object");
+        self.signed_request(agent, method, &url, b"")
+    }
+
+    /// List the objects of a prefix, using the ListObjectsV2 api.
+    pub fn list_objects(
+        &self,
+        agent: &ureq::Agent,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<Object>, Error> {
+        let mut objects = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token));
+            }
+            let url = self.http_url(bucket, "", &query)?;
+            tracing::debug!(url = url.as_str(), "Listing objects");
+            let body = self
+                .signed_request(agent, "GET", &url, b"")?
+                .call()
+                .map_err(|e| Error::BadQuery(Box::new(e)))?
+                .into_string()?;
+            let (page, next) = parse_list_objects(&body)?;
+            objects.extend(page);
+            match next {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        Ok(objects)
+    }
+
+    /// Upload an object.
+    pub fn put_object(
+        &self,
+        agent: &ureq::Agent,
+        object: &ObjectUrl,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let url = self.http_url(&object.bucket, &object.key, &[])?;
+        tracing::debug!(url = url.as_str(), "Uploading object");
+        self.signed_request(agent, "PUT", &url, data)?
+            .send_bytes(data)
+            .map_err(|e| Error::BadQuery(Box::new(e)))?;
+        Ok(())
+    }
+
+    /// Delete an object, the missing objects are ignored.
+    pub fn delete_object(&self, agent: &ureq::Agent, object: &ObjectUrl) -> Result<(), Error> {
+        let url = self.http_url(&object.bucket, &object.key, &[])?;
+        tracing::debug!(url = url.as_str(), "Deleting object");
+        match self.signed_request(agent, "DELETE", &url, b"")?.call() {
+            Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(()),
+            Err(e) => Err(Error::BadQuery(Box::new(e))),
+        }
+    }
+}
+
+/// A ListObjectsV2 reply page.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListBucketResult {
+    #[serde(default)]
+    is_truncated: bool,
+    #[serde(default)]
+    contents: Vec<Contents>,
+    next_continuation_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Contents {
+    key: String,
+    #[serde(default)]
+    size: u64,
+}
+
+/// Decode a ListBucketResult page, returning the objects and the next continuation token.
+fn parse_list_objects(body: &str) -> Result<(Vec<Object>, Option<String>), Error> {
+    let result: ListBucketResult = quick_xml::de::from_str(body)?;
+    let objects = result
+        .contents
+        .into_iter()
+        // Skip the directory placeholders.
+        .filter(|contents| !contents.key.ends_with('/'))
+        .map(|contents| Object {
+            key: contents.key,
+            size: contents.size,
+        })
+        .collect();
+    let next = if result.is_truncated {
+        result.next_continuation_token
+    } else {
+        None
+    };
+    Ok((objects, next))
+}
+
+#[test]
+fn test_object_url() {
+    let client = Client {
+        endpoint: Url::parse("https://s3.amazonaws.com/").unwrap(),
+        region: "us-east-1".into(),
+        credentials: None,
+    };
+    let object = parse_s3_url(&object_url("logs", "builds/42/job output.txt").unwrap()).unwrap();
+    assert_eq!(object.key, "builds/42/job output.txt");
+    assert_eq!(
+        client
+            .http_url(&object.bucket, &object.key, &[])
+            .unwrap()
+            .as_str(),
+        "https://s3.amazonaws.com/logs/builds/42/job%20output.txt"
+    );
+}
+
+#[test]
+fn test_list_objects() {
+    let mut server = mockito::Server::new();
+    let first = server
+        .mock("GET", "/logs")
+        .match_query("list-type=2&prefix=builds%2F42%2F")
+        .with_body(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult><Name>logs</Name><IsTruncated>true</IsTruncated>
+<Contents><Key>builds/42/</Key><Size>0</Size></Contents>
+<Contents><Key>builds/42/job-output.txt</Key><Size>42</Size></Contents>
+<NextContinuationToken>next&amp;page</NextContinuationToken></ListBucketResult>"#,
+        )
+        .expect(1)
+        .create();
+    let second = server
+        .mock("GET", "/logs")
+        .match_query("list-type=2&prefix=builds%2F42%2F&continuation-token=next%26page")
+        .with_body(
+            r#"<ListBucketResult><IsTruncated>false</IsTruncated>
+<Contents><Key>builds/42/logs/app&amp;db.log</Key><Size>7</Size></Contents></ListBucketResult>"#,
+        )
+        .expect(1)
+        .create();
+    let client = Client {
+        endpoint: Url::parse(&server.url()).unwrap(),
+        region: "us-east-1".into(),
+        credentials: None,
+    };
+    let objects = client
+        .list_objects(&ureq::Agent::new(), "logs", "builds/42/")
+        .unwrap();
+    assert_eq!(
+        objects,
+        vec![
+            Object {
+                key: "builds/42/job-output.txt".into(),
+                size: 42
+            },
+            Object {
+                key: "builds/42/logs/app&db.log".into(),
+                size: 7
+            },
+        ]
+    );
+    first.assert();
+    second.assert();
+}
+
+#[test]
+fn test_put_delete_object() {
+    let mut server = mockito::Server::new();
+    let put = server
+        .mock("PUT", "/data/reports/42.zst")
+        .match_body("report")
+        .expect(1)
+        .create();
+    let delete = server
+        .mock("DELETE", "/data/reports/42.zst")
+        .with_status(404)
+        .expect(1)
+        .create();
+    let client = Client {
+        endpoint: Url::parse(&server.url()).unwrap(),
+        region: "us-east-1".into(),
+        credentials: None,
+    };
+    let agent = ureq::Agent::new();
+    let object = ObjectUrl {
+        bucket: "data".into(),
+        key: "reports/42.zst".into(),
+    };
+    client.put_object(&agent, &object, b"report").unwrap();
+    client.delete_object(&agent, &object).unwrap();
+    put.assert();
+    delete.assert();
+}
@@ -0,0 +1,128 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the logic to tokenize the CSV and TSV lines, such as the exported database logs.
+//!
+//! The message columns are tokenized as regular lines, and the other columns are tokenized
+//! individually, prefixed by their position, so that a value is never confused with its neighbours.
+
+/// The format of the CSV lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Format {
+    /// The column delimiter, e.g. `,` or `\t`.
+    pub delimiter: char,
+    /// The positions of the message columns, starting at 0.
+    pub message: Vec<usize>,
+}
+
+/// Split a line into its columns, returns None when the line has a single column.
+/// The quotes surrounding a value are removed.
+pub fn parse(line: &str, delimiter: char) -> Option<Vec<&str>> {
+    let mut fields = Vec::new();
+    let mut rest = line.trim_end_matches(['\r', '\n']);
+    loop {
+        let (field, next) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let end = find_closing_quote(quoted)?;
+                let next = &quoted[end + 1..];
+                if !next.is_empty() && !next.starts_with(delimiter) {
+                    return None;
+                }
+                (&quoted[..end], next)
+            }
+            None => match rest.find(delimiter) {
+                Some(pos) => (&rest[..pos], &rest[pos..]),
+                None => (rest, ""),
+            },
+        };
+        fields.push(field);
+        match next.strip_prefix(delimiter) {
+            Some(next) => rest = next,
+            None => break,
+        }
+    }
+    if fields.len() > 1 {
+        Some(fields)
+    } else {
+        None
+    }
+}
+
+// The quotes are escaped by doubling them: `"a ""quoted"" value"`.
+fn find_closing_quote(s: &str) -> Option<usize> {
+    let mut chars = s.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        if c == '"' {
+            if matches!(chars.peek(), Some((_, '"'))) {
+                chars.next();
+            } else {
+                return Some(pos);
+            }
+        }
+    }
+    None
+}
+
+/// Get the message of a line, when the format has a single message column.
+pub fn message<'a>(fields: &[&'a str], format: &Format) -> Option<&'a str> {
+    match format.message[..] {
+        [column] => fields.get(column).copied().filter(|msg| !msg.is_empty()),
+        _ => None,
+    }
+}
+
+/// Tokenize the columns of a line, applying the user-defined rules to the values.
+pub fn process(fields: &[&str], format: &Format, rules: &crate::rules::Rules) -> String {
+    let mut result = String::new();
+    for column in &format.message {
+        if let Some(value) = fields.get(*column) {
+            if !result.is_empty() {
+                result.push(' ');
+            }
+            result.push_str(&crate::rules::process(value, rules));
+        }
+    }
+    for (column, value) in fields.iter().enumerate() {
+        if value.is_empty() || format.message.contains(&column) {
+            continue;
+        }
+        if !result.is_empty() {
+            result.push(' ');
+        }
+        result.push_str(&format!("c{}=", column));
+        // The columns are often a single word, they are not checked by the global filter.
+        result.push_str(&crate::process_words(&rules.apply(value)));
+    }
+    result
+}
+
+#[test]
+fn test_csv() {
+    let format = Format {
+        delimiter: ',',
+        message: vec![3],
+    };
+    let line = r#"2023-10-12 08:12:01.123 UTC,postgres,42,"relation ""users"" does not exist, retry",ERROR"#;
+    let fields = parse(line, format.delimiter).unwrap();
+    assert_eq!(
+        fields,
+        vec![
+            "2023-10-12 08:12:01.123 UTC",
+            "postgres",
+            "42",
+            r#"relation ""users"" does not exist, retry"#,
+            "ERROR"
+        ]
+    );
+    assert_eq!(
+        message(&fields, &format),
+        Some(r#"relation ""users"" does not exist, retry"#)
+    );
+    let tokens = process(&fields, &format, &Default::default());
+    assert!(tokens.starts_with("relation"));
+    assert!(tokens.contains("c1=postgres"));
+
+    assert_eq!(parse("a\tb\t", '\t'), Some(vec!["a", "b", ""]));
+    assert_eq!(parse("Starting service", ','), None);
+    assert_eq!(parse(r#""unterminated,value"#, ','), None);
+}
@@ -29,6 +29,10 @@ fn test_is_hexadecimal() {
     assert_eq!(
         IndexName::from_path("config-update/015da2b/job-output.json.gz"),
         IndexName("config-update/job-output.json".into())
+    );
+    assert_eq!(
+        IndexName::from_path("config-update/015da2b/job-output.txt.zst"),
+        IndexName("config-update/job-output".into())
     )
 }
 
@@ -117,16 +121,25 @@ fn test_vowel_remove() {
     )
 }
 
+/// The compression extensions, they are not part of the index name.
+const COMPRESSION_EXTENSIONS: &[&str] = &[".gz", ".bz2", ".xz", ".zst"];
+
+fn strip_compression(base: &str) -> &str {
+    COMPRESSION_EXTENSIONS
+        .iter()
+        .find_map(|ext| base.strip_suffix(ext))
+        .unwrap_or(base)
+}
+
 fn clean_name(base: &str) -> String {
     if base.starts_with("instance-00") {
         "instance".to_string()
     } else {
-        remove_non_vowel_component(base)
+        remove_non_vowel_component(strip_compression(base))
             .replace(
                 |c: char| !c.is_ascii_alphabetic() && !matches!(c, '.' | '-'),
                 "",
             )
-            .trim_end_matches(".gz")
             .trim_end_matches(".txt")
             .trim_matches(|c| matches!(c, '.' | '_' | '-'))
             .to_string()
@@ -193,6 +206,10 @@ fn log_model_name() {
             "ironic/app.log",
             ["ironic/app.log.txt.gz", "ironic/app.log.1.gz"],
         ),
+        (
+            "journal/messages",
+            ["journal/messages.bz2", "journal/messages.xz"],
+        ),
     ])
     .for_each(|(expected_model, paths)| {
         IntoIterator::into_iter(paths).for_each(|path| {
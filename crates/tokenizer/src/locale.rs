@@ -0,0 +1,66 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the logic to detect the localized lines, such as: `Aucun fichier ou dossier de ce type`.
+//!
+//! The detection is a simple heuristic: a line is localized when more than an eighth of its words
+//! contains non ascii letters. This works well for non latin scripts and accented languages,
+//! but some localized messages written with plain ascii letters are not detected.
+
+fn is_word(word: &str) -> bool {
+    word.chars().any(char::is_alphabetic)
+}
+
+fn is_localized_word(word: &str) -> bool {
+    word.chars().any(|c| c.is_alphabetic() && !c.is_ascii())
+}
+
+/// Check if a line is written in a non english language.
+pub fn is_localized(line: &str) -> bool {
+    let mut words = 0;
+    let mut localized = 0;
+    for word in line.split_whitespace().filter(|word| is_word(word)) {
+        words += 1;
+        if is_localized_word(word) {
+            localized += 1;
+        }
+    }
+    localized * 8 > words
+}
+
+/// Replace the localized words of the tokens with a single `%LOCALE` token.
+pub fn normalize(tokens: &str) -> String {
+    let mut result = String::with_capacity(tokens.len());
+    let mut prev_localized = false;
+    for word in tokens.split(' ') {
+        let localized = is_localized_word(word);
+        if localized && prev_localized {
+            continue;
+        }
+        if !result.is_empty() {
+            result.push(' ');
+        }
+        result.push_str(if localized { "%LOCALE" } else { word });
+        prev_localized = localized;
+    }
+    result
+}
+
+#[test]
+fn test_locale() {
+    assert!(is_localized(
+        "ls: impossible d'accéder à '/tmp/foo': Aucun fichier ou dossier de ce type"
+    ));
+    assert!(is_localized("ファイルが見つかりません: /tmp/foo"));
+    assert!(!is_localized(
+        "ls: cannot access '/tmp/foo': No such file or directory"
+    ));
+    assert!(!is_localized(
+        "Connecting to café.example.com on port 8080 for the user"
+    ));
+    assert!(!is_localized("42"));
+    assert_eq!(
+        normalize("ls: %ID ファイルが 見つかりません foo"),
+        "ls: %ID %LOCALE foo"
+    );
+}
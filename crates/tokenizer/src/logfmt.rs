@@ -0,0 +1,116 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the logic to tokenize logfmt lines, such as: `level=info msg="started" port=80`.
+//!
+//! The values are tokenized individually, prefixed by their key, so that a value is never
+//! confused with another field. The timestamp fields are ignored.
+
+/// The keys of the human readable message, by order of preference.
+pub const MESSAGE_KEYS: &[&str] = &["msg", "message"];
+
+/// The keys that are always different.
+const SKIP_KEYS: &[&str] = &["time", "ts", "timestamp", "t"];
+
+/// A logfmt line field.
+pub type Field<'a> = (&'a str, &'a str);
+
+/// Parse a logfmt line, returns None when the line does not look like logfmt.
+pub fn parse(line: &str) -> Option<Vec<Field<'_>>> {
+    let mut fields = Vec::new();
+    let mut rest = line.trim();
+    while !rest.is_empty() {
+        let key_end = rest.find(|c: char| c == '=' || c.is_whitespace() || c == '"')?;
+        let key = &rest[..key_end];
+        if key.is_empty() || !rest[key_end..].starts_with('=') {
+            return None;
+        }
+        rest = &rest[key_end + 1..];
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let value_end = find_closing_quote(quoted)?;
+            rest = &quoted[value_end + 1..];
+            &quoted[..value_end]
+        } else {
+            let value_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let value = &rest[..value_end];
+            rest = &rest[value_end..];
+            value
+        };
+        fields.push((key, value));
+        rest = rest.trim_start();
+    }
+    // A single pair is likely a regular line containing a `=`.
+    if fields.len() > 1 {
+        Some(fields)
+    } else {
+        None
+    }
+}
+
+fn find_closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (pos, c) in s.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(pos),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Get the message of a logfmt line.
+pub fn message<'a>(fields: &[Field<'a>]) -> Option<&'a str> {
+    MESSAGE_KEYS.iter().find_map(|key| {
+        fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| *value)
+    })
+}
+
+/// Tokenize the fields of a logfmt line, applying the user-defined rules to the values.
+pub fn process(fields: &[Field<'_>], rules: &crate::rules::Rules) -> String {
+    let mut result = String::new();
+    for (key, value) in fields {
+        if SKIP_KEYS.contains(key) {
+            continue;
+        }
+        if !result.is_empty() {
+            result.push(' ');
+        }
+        result.push_str(key);
+        result.push('=');
+        // The values are often a single word, they are not checked by the global filter.
+        result.push_str(&crate::process_words(&rules.apply(value)));
+    }
+    result
+}
+
+#[test]
+fn test_logfmt() {
+    let line = r#"time=2023-10-12T08:12:01Z level=error msg="dial tcp: \"db\" refused" retry=3"#;
+    let fields = parse(line).unwrap();
+    assert_eq!(
+        fields,
+        vec![
+            ("time", "2023-10-12T08:12:01Z"),
+            ("level", "error"),
+            ("msg", r#"dial tcp: \"db\" refused"#),
+            ("retry", "3"),
+        ]
+    );
+    assert_eq!(message(&fields), Some(r#"dial tcp: \"db\" refused"#));
+    assert_eq!(
+        process(&fields, &Default::default()),
+        "level=error error%A error%B error%C error%D msg=dial %ID %ID refused retry=%ID"
+    );
+    let info = parse(r#"level=info msg="dial tcp: \"db\" refused" retry=3"#).unwrap();
+    assert_ne!(
+        process(&fields, &Default::default()),
+        process(&info, &Default::default())
+    );
+    assert_eq!(parse("Starting service"), None);
+    assert_eq!(parse("export KEY=value"), None);
+    assert_eq!(parse(r#"level=info msg="unterminated"#), None);
+}
@@ -0,0 +1,131 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module provides the user-defined normalization rules.
+//!
+//! The rules replace the site specific words, such as the request ids or the tenant names,
+//! with a fixed token before the tokenization, for example: `tenant-[a-z]+` becomes `%TENANT`.
+//!
+//! The preserved patterns, such as the HTTP status or the compiler error codes, are kept verbatim
+//! instead of being normalized, for example: `E0308` becomes `%KEEP=E0308` instead of `%ID`.
+
+use regex::Regex;
+use std::borrow::Cow;
+
+/// The prefix of the preserved words, which are not modified by the tokenizer.
+pub const PRESERVE_PREFIX: &str = "%KEEP=";
+
+/// A list of regex replacements and preserved patterns.
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    replace: Vec<(Regex, String)>,
+    preserve: Vec<Regex>,
+}
+
+impl Rules {
+    /// Create the rules from a list of (pattern, token).
+    pub fn new<P: AsRef<str>, T: AsRef<str>>(rules: &[(P, T)]) -> Result<Rules, regex::Error> {
+        let replace = rules
+            .iter()
+            .map(|(pattern, token)| {
+                Ok((
+                    Regex::new(pattern.as_ref())?,
+                    // Surround the token with spaces so that it is processed as a single word.
+                    format!(" {} ", token.as_ref()),
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Rules {
+            replace,
+            preserve: Vec::new(),
+        })
+    }
+
+    /// Add the patterns to be preserved verbatim.
+    pub fn with_preserve<P: AsRef<str>>(mut self, patterns: &[P]) -> Result<Rules, regex::Error> {
+        self.preserve = patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self)
+    }
+
+    /// Check if there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.replace.is_empty() && self.preserve.is_empty()
+    }
+
+    /// The rules (pattern, token).
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.replace
+            .iter()
+            .map(|(re, token)| (re.as_str(), token.trim()))
+    }
+
+    /// The preserved patterns.
+    pub fn preserved(&self) -> impl Iterator<Item = &str> {
+        self.preserve.iter().map(|re| re.as_str())
+    }
+
+    /// Apply the rules to a line, the line is copied only when a rule matches.
+    /// The replacements are applied first, then the preserved words are marked.
+    pub fn apply<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let mut result = Cow::Borrowed(line);
+        for (re, token) in &self.replace {
+            if let Cow::Owned(replaced) = re.replace_all(&result, token.as_str()) {
+                result = Cow::Owned(replaced);
+            }
+        }
+        for re in &self.preserve {
+            let replaced = re.replace_all(&result, |captures: &regex::Captures| {
+                let word = captures[0].split_whitespace().collect::<Vec<_>>().join("_");
+                format!(" {}{} ", PRESERVE_PREFIX, word)
+            });
+            if let Cow::Owned(replaced) = replaced {
+                result = Cow::Owned(replaced);
+            }
+        }
+        result
+    }
+}
+
+/// The tokenizer entry point, applying the user-defined rules first.
+pub fn process(line: &str, rules: &Rules) -> String {
+    crate::process(&rules.apply(line))
+}
+
+#[test]
+fn test_rules() {
+    let rules = Rules::new(&[("tenant-[a-z]+", "%TENANT"), (r"rq\.[0-9a-z]+", "%RQ")]).unwrap();
+    assert_eq!(
+        process("Deleting tenant-acme volumes for rq.4a2b", &rules),
+        "Deleting %TENANT volumes %RQ"
+    );
+    assert_eq!(
+        process("Deleting tenant-acme volumes", &rules),
+        process("Deleting tenant-globex volumes", &rules)
+    );
+    assert_eq!(
+        process("Deleting tenant-acme volumes", &Rules::default()),
+        crate::process("Deleting tenant-acme volumes")
+    );
+}
+
+#[test]
+fn test_preserve() {
+    let rules = Rules::default()
+        .with_preserve(&[r"\bE[0-9]{4}\b", r"HTTP [1-5][0-9]{2}"])
+        .unwrap();
+    assert_eq!(
+        process("error[E0308]: mismatched types", &rules),
+        "error error%A error%B error%C error%D %KEEP=E0308 mismatched types"
+    );
+    assert_ne!(
+        process("error[E0308]: mismatched types", &rules),
+        process("error[E0599]: mismatched types", &rules)
+    );
+    assert_eq!(
+        process("Request returned HTTP 503", &rules),
+        "Request returned %KEEP=HTTP_503"
+    );
+}
@@ -0,0 +1,238 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the logic to recognize the stack traces of Python, Java, Go and Rust.
+//!
+//! The frames are tokenized with their function and file names only, the addresses,
+//! line numbers and offsets are removed. The [Tracker] follows the lines of a trace,
+//! so that the whole block can be reported as a single anomaly.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// The language of a stack trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    /// A `Traceback (most recent call last):` block.
+    Python,
+    /// An exception followed by `at ...` frames.
+    Java,
+    /// A `panic:` followed by the goroutines.
+    Go,
+    /// A `thread 'main' panicked at` followed by the backtrace.
+    Rust,
+}
+
+/// Check if a line starts a stack trace.
+pub fn header(line: &str) -> Option<Language> {
+    lazy_static! {
+        static ref JAVA: Regex = Regex::new(concat!(
+            r#"^(Exception in thread "[^"]*" )?"#,
+            r"([a-zA-Z_$][\w$]*\.)+[A-Z][\w$]*(Exception|Error)(: .*)?$"
+        ))
+        .unwrap();
+        static ref GO: Regex =
+            Regex::new(r"^(panic: |fatal error: |goroutine \d+ \[.*\]:$)").unwrap();
+    }
+    let line = line.trim();
+    if line.ends_with("Traceback (most recent call last):") {
+        Some(Language::Python)
+    } else if ((line.contains("Exception") || line.contains("Error")) && JAVA.is_match(line))
+        || java_continuation(line)
+    {
+        Some(Language::Java)
+    } else if ["panic: ", "fatal error: ", "goroutine "]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+        && GO.is_match(line)
+    {
+        Some(Language::Go)
+    } else if line.starts_with("thread '") && line.contains("' panicked at") {
+        Some(Language::Rust)
+    } else {
+        None
+    }
+}
+
+fn java_continuation(line: &str) -> bool {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(
+            r"^(at [\w$.<>/]+\(|\.\.\. \d+ (more|common frames omitted)|Caused by: |Suppressed: )"
+        )
+        .unwrap();
+    }
+    ["at ", "... ", "Caused by: ", "Suppressed: "]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+        && RE.is_match(line)
+}
+
+fn is_python_exception(line: &str) -> bool {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^[A-Za-z_][\w.]*(: .*)?$").unwrap();
+    }
+    RE.is_match(line)
+}
+
+/// Check if a line continues a stack trace.
+fn is_continuation(language: Language, line: &str) -> bool {
+    let indented = line.starts_with([' ', '\t']);
+    let line = line.trim();
+    match language {
+        Language::Python => indented,
+        Language::Java => java_continuation(line),
+        Language::Go => {
+            line.is_empty()
+                || indented
+                || line.starts_with("goroutine ")
+                || line.starts_with("created by ")
+                || line.starts_with("[signal ")
+                || frame(line).is_some()
+        }
+        Language::Rust => {
+            line.starts_with("stack backtrace:")
+                || line.starts_with("note: ")
+                || (indented && frame(line).is_some())
+        }
+    }
+}
+
+/// Follow the lines of the stack traces.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    trace: Option<Language>,
+}
+
+impl Tracker {
+    /// Check if a line is part of the stack trace started by the previous lines.
+    pub fn is_continuation(&mut self, line: &str) -> bool {
+        if let Some(language) = self.trace {
+            if is_continuation(language, line) {
+                return true;
+            }
+            if language == Language::Python && is_python_exception(line) {
+                // The final exception line ends the trace.
+                self.trace = None;
+                return true;
+            }
+        }
+        self.trace = header(line);
+        false
+    }
+}
+
+/// Check if a line may be a frame, to skip the regexes for most of the lines.
+fn is_frame_like(line: &str) -> bool {
+    line.starts_with("File \"")
+        || line.starts_with("at ")
+        || line.ends_with(')')
+        || line.contains(".go:")
+        || (line.starts_with(|c: char| c.is_ascii_digit()) && line.contains("::"))
+}
+
+/// Tokenize a stack trace frame, returns None when the line is not a frame.
+pub fn frame(line: &str) -> Option<String> {
+    if !is_frame_like(line) {
+        return None;
+    }
+    lazy_static! {
+        static ref PYTHON: Regex =
+            Regex::new(r#"^File "(?P<file>[^"]+)", line \d+(, in (?P<func>\S+))?$"#).unwrap();
+        static ref JAVA: Regex =
+            Regex::new(r"^at (?P<func>[\w$.<>/]+)\((?P<file>[^:)]*)(:\d+)?\)( .*)?$").unwrap();
+        static ref GO_FUNC: Regex =
+            Regex::new(r"^(?P<func>[\w./*()-]+?)\(((0x[0-9a-f]+|\.\.\.|\{.*\})(, )?)*\)$").unwrap();
+        static ref GO_FILE: Regex =
+            Regex::new(r"^(?P<file>/\S+\.go):\d+( \+0x[0-9a-f]+)?$").unwrap();
+        static ref RUST_FUNC: Regex =
+            Regex::new(r"^\d+:( +0x[0-9a-f]+ -)? (?P<func>\S*?::\S+?)(::h[0-9a-f]{16})?$").unwrap();
+        static ref RUST_FILE: Regex = Regex::new(r"^at (?P<file>\S+?):\d+(:\d+)?$").unwrap();
+    }
+    let captures = [
+        &*PYTHON,
+        &*JAVA,
+        &*GO_FUNC,
+        &*GO_FILE,
+        &*RUST_FUNC,
+        &*RUST_FILE,
+    ]
+    .iter()
+    .find_map(|re| re.captures(line))?;
+    let mut result = "%FRAME".to_string();
+    if let Some(func) = captures.name("func") {
+        result.push(' ');
+        result.push_str(func.as_str());
+    }
+    if let Some(file) = captures.name("file") {
+        let file = crate::process_words(file.as_str());
+        if !file.is_empty() {
+            result.push(' ');
+            result.push_str(&file);
+        }
+    }
+    Some(result)
+}
+
+#[test]
+fn test_frame() {
+    assert_eq!(
+        frame(r#"File "/usr/lib/python3.9/site-packages/nodepool/cmd.py", line 144, in validate"#),
+        frame(r#"File "/usr/lib/python3.9/site-packages/nodepool/cmd.py", line 42, in validate"#),
+    );
+    assert_eq!(
+        frame("at org.example.Service.start(Service.java:42) ~[app.jar:1.0]"),
+        Some("%FRAME org.example.Service.start Service.java".into())
+    );
+    assert_eq!(
+        frame("main.(*Server).Run(0xc000010000, 0x1)"),
+        Some("%FRAME main.(*Server).Run".into())
+    );
+    assert_eq!(
+        frame("/home/user/src/server.go:42 +0x1d"),
+        frame("/home/user/src/server.go:51 +0x2f"),
+    );
+    assert_eq!(
+        frame("12: 0x55d5c0a4b2f3 - std::rt::lang_start::h4f8b1c0b0b0f3d0a"),
+        Some("%FRAME std::rt::lang_start".into())
+    );
+    assert_eq!(frame("Starting service"), None);
+    assert!(!is_frame_like("Starting service (pid 42) on port 8080"));
+    assert_eq!(frame("3: error"), None);
+}
+
+#[test]
+fn test_tracker() {
+    let lines = [
+        ("Starting service", false),
+        ("Traceback (most recent call last):", false),
+        (r#"  File "service.py", line 12, in <module>"#, true),
+        ("    main()", true),
+        ("ValueError: invalid literal", true),
+        ("Stopping service", false),
+        (
+            "Exception in thread \"main\" java.lang.IllegalStateException: oops",
+            false,
+        ),
+        ("\tat org.example.Main.main(Main.java:5)", true),
+        ("Caused by: java.io.IOException: closed", true),
+        ("\t... 3 more", true),
+        ("panic: runtime error: index out of range", false),
+        ("", true),
+        ("goroutine 1 [running]:", true),
+        ("main.main()", true),
+        ("\t/src/main.go:12 +0x1d", true),
+        ("exit status 2", false),
+        ("thread 'main' panicked at 'oops', src/main.rs:4:5", false),
+        ("stack backtrace:", true),
+        ("   0: std::panicking::begin_panic", true),
+        (
+            "             at /rustc/library/std/src/panicking.rs:616:12",
+            true,
+        ),
+        ("Stopping service", false),
+    ];
+    let mut tracker = Tracker::default();
+    for (line, expected) in lines {
+        assert_eq!(tracker.is_continuation(line), expected, "{}", line);
+    }
+}
@@ -0,0 +1,76 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the logic to strip the syslog headers, such as: `<34>Oct 11 22:14:15 host su: message`.
+//!
+//! Both the RFC3164 and RFC5424 formats are supported. The priority, timestamp and hostname are removed
+//! so that the files collected from different hosts can be compared.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Check if a line starts like a syslog header, with a priority, a month or a year,
+/// so that the regexes are skipped for most of the lines.
+fn is_header_like(line: &str) -> bool {
+    match line.as_bytes() {
+        [b'<', ..] => true,
+        [m, o, n, b' ', ..] => {
+            m.is_ascii_uppercase() && o.is_ascii_lowercase() && n.is_ascii_lowercase()
+        }
+        [y1, y2, y3, y4, b'-', ..] => [y1, y2, y3, y4].iter().all(|c| c.is_ascii_digit()),
+        _ => false,
+    }
+}
+
+/// Remove the syslog header of a line, keeping the application tag.
+pub fn strip_header(line: &str) -> &str {
+    lazy_static! {
+        // The RFC5424 header: <PRI>VERSION TIMESTAMP HOSTNAME
+        static ref RFC5424: Regex = Regex::new(r"^<\d{1,3}>1 \S+ \S+ ").unwrap();
+        // The RFC3164 header, which must be followed by a tag to avoid matching regular lines
+        static ref RFC3164: Regex = Regex::new(concat!(
+            r"^(?:<\d{1,3}>)?",
+            r"(?:[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}|\d{4}-\d{2}-\d{2}T[\d:.]+(?:Z|[+-]\d{2}:?\d{2})?)",
+            r" \S+ (?P<tag>[^\s:\[]+(?:\[\d+\])?: )"
+        ))
+        .unwrap();
+    }
+    if !is_header_like(line) {
+        line
+    } else if let Some(m) = RFC5424.find(line) {
+        &line[m.end()..]
+    } else if let Some(tag) = RFC3164.captures(line).and_then(|caps| caps.name("tag")) {
+        &line[tag.start()..]
+    } else {
+        line
+    }
+}
+
+#[test]
+fn test_strip_header() {
+    assert_eq!(
+        strip_header("<34>Oct 11 22:14:15 mymachine su: 'su root' failed"),
+        "su: 'su root' failed"
+    );
+    assert_eq!(
+        strip_header("Jan  2 01:02:03 node-1.example.com systemd[1]: Started sshd"),
+        "systemd[1]: Started sshd"
+    );
+    assert_eq!(
+        strip_header("2023-10-11T22:14:15.003+00:00 node-2 kernel: oops"),
+        "kernel: oops"
+    );
+    assert_eq!(
+        strip_header("<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 hello"),
+        "evntslog - ID47 hello"
+    );
+    // Regular lines are kept as is
+    for line in [
+        "2023-10-11T22:14:15Z INFO starting server",
+        "Traceback (most recent call last):",
+        "",
+    ] {
+        assert_eq!(strip_header(line), line);
+    }
+    assert!(!is_header_like("Started the server on node-1: ok"));
+}
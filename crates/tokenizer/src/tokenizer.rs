@@ -40,7 +40,13 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use regex::Split;
 
+pub mod csv;
 pub mod index_name;
+pub mod locale;
+pub mod logfmt;
+pub mod rules;
+pub mod stacktrace;
+pub mod syslog;
 
 fn words(line: &str) -> Split {
     lazy_static! {
@@ -349,6 +355,83 @@ fn test_is_random_path() {
     )
 }
 
+/// The alphabet of the Kubernetes generated names, without vowels to avoid bad words.
+const K8S_ALPHABET: &str = "bcdfghjklmnpqrstvwxz2456789";
+
+/// Remove the replica-set and pod hash suffixes, e.g. `nova-api-7d9f8b6c5d-x2k4p`.
+fn trim_pod_hash(word: &str) -> Option<&str> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(&format!(
+            "^([a-z0-9][-a-z0-9.]*?)((-[{0}]{{6,10}})?-[{0}]{{5}})$",
+            K8S_ALPHABET
+        ))
+        .unwrap();
+    }
+    let captures = RE.captures(word)?;
+    // A digit is required to not match regular words, e.g. `sync-crwth`.
+    if captures[2].contains(|c: char| c.is_ascii_digit()) {
+        captures.get(1).map(|name| name.as_str())
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_trim_pod_hash() {
+    assert_eq!(trim_pod_hash("nova-api-7d9f8b6c5d-x2k4p"), Some("nova-api"));
+    assert_eq!(trim_pod_hash("kube-proxy-5wz8q"), Some("kube-proxy"));
+    assert_eq!(trim_pod_hash("check-certs"), None);
+    tokens_eq!(
+        "Killing container in pod nova-api-7d9f8b6c5d-x2k4p",
+        "Killing container in pod nova-api-58dd4f6b9c-q7m2z"
+    );
+}
+
+/// Check if a word is a container runtime id, e.g. `containerd://4a2b...`.
+fn is_container_id(word: &str) -> bool {
+    lazy_static! {
+        static ref RE: Regex = Regex::new("^(containerd|docker|cri-o)://[0-9a-f]{12,64}$").unwrap();
+    }
+    RE.is_match(word)
+}
+
+/// Check if a word is an IPv4 or IPv6 address with a port, e.g. `10.244.1.5:8080`,
+/// or a comma separated list of such addresses.
+fn is_ip_port(word: &str) -> bool {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(&format!(
+            "^{0}(,{0})*:?$",
+            concat!(
+                "(",
+                r"[0-9]{1,3}(\.[0-9]{1,3}){3}",
+                // The opening bracket is removed by trim_quote_and_punctuation
+                r"|\[?[0-9a-fA-F:]+\]",
+                r"):[0-9]{1,5}"
+            )
+        ))
+        .unwrap();
+    }
+    RE.is_match(word)
+}
+
+#[test]
+fn test_k8s_literals() {
+    assert!(is_container_id("containerd://4a2b7d9f8b6c"));
+    assert!(is_ip_port("10.244.1.5:8080"));
+    assert!(is_ip_port("[fd00::21e]:5672"));
+    assert!(is_ip_port("10.96.0.1:443:"));
+    assert!(is_ip_port("10.42.0.51:7900,10.42.0.52:7900"));
+    assert!(!is_ip_port("10.244.1.5"));
+    tokens_eq!(
+        "Container ID: cri-o://9f8b6c5d7d9f8b6c5d7d9f8b6c5d7d9f",
+        "Container ID: cri-o://4a2b7d9f8b6c"
+    );
+    tokens_eq!(
+        "dial tcp 10.96.0.1:443: i/o timeout",
+        "dial tcp 10.96.12.7:6443: i/o timeout"
+    );
+}
+
 #[cfg(test)]
 mod re_tests {
     use super::*;
@@ -410,9 +493,31 @@ mod re_tests {
     }
 }
 
+/// Check if a word is already a token, e.g. inserted or preserved by the user-defined [rules].
+fn is_token(word: &str) -> bool {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^%[A-Z][A-Z0-9_]*$").unwrap();
+    }
+    RE.is_match(word) || word.starts_with(rules::PRESERVE_PREFIX)
+}
+
+#[test]
+fn test_is_token() {
+    assert!(is_token("%TENANT"));
+    assert!(is_token("%KEEP=E0308"));
+    assert!(!is_token("%tenant"));
+    assert!(!is_token("90%"));
+}
+
 fn parse_literal(word: &str) -> Option<&str> {
-    if is_date(word) {
+    if is_token(word) {
+        Some(word)
+    } else if is_date(word) {
         Some("%DATE")
+    } else if is_container_id(word) {
+        Some("%CONTAINER")
+    } else if is_ip_port(word) {
+        Some("%IPPORT")
     } else if is_hash(word) {
         Some("%HASH")
     } else if is_uid(word) {
@@ -480,6 +585,10 @@ fn do_process(base_word: &str, iter: &mut Split, result: &mut String) -> bool {
         // e.g. `"systemd[42]"`
         do_process(strip, iter, result);
         result.push_str("%PID");
+    } else if let Some(name) = trim_pod_hash(word) {
+        // e.g. `nova-api-7d9f8b6c5d-x2k4p`
+        do_process(name, iter, result);
+        result.push_str("%POD");
     } else if contains_odd_char(word) {
         result.push_str("%ODD")
     } else if let Some((key, value)) = is_key_value(word) {
@@ -545,7 +654,16 @@ pub fn process(line: &str) -> String {
         return "%GL_FILTER".to_string();
     }
 
-    // split the line into space separated words.
+    // the stack trace frames only keep the function and file names
+    if let Some(frame) = stacktrace::frame(line) {
+        return frame;
+    }
+
+    process_words(line)
+}
+
+/// Tokenize the space separated words.
+fn process_words(line: &str) -> String {
     let mut result = String::with_capacity(line.len());
     let mut iter = words(line);
     while let Some(word) = iter.next() {
@@ -597,7 +715,7 @@ mod tests {
         );
         assert_eq!(
             process("** 192.168.24.1:8787/tripleovictoria/openstack-heat-api:175194d1801ec25367354976a18e3725-updated-20220125105210 **"),
-            "%ID/ tripleovictoria/ openstack- heat- %EQ %ID- updated- %ID"
+            "%IPPORT/ tripleovictoria/ openstack- heat- %EQ %ID- updated- %ID"
         );
     }
     #[test]
@@ -621,7 +739,7 @@ mod tests {
     fn test_process03() {
         assert_eq!(
             process("2022-01-25T14:09:24.422Z|00014|jsonrpc|WARN|tcp:[fd00:fd00:fd00:2000::21e]:50504: receive error: Connection reset by peer"),
-            "%ID- %ID- %ID| %ID| jsonrpc| WARN WARN%A WARN%B WARN%C WARN%D| %ID%EQ %ID receive error error%A error%B error%C error%D%EQ Connection reset peer"
+            "%ID- %ID- %ID| %ID| jsonrpc| WARN WARN%A WARN%B WARN%C WARN%D| %ID%EQ %IPPORT receive error error%A error%B error%C error%D%EQ Connection reset peer"
         );
         assert_eq!(
             process("Event ID: 3e75e420-761f-11ec-8d18-a0957bd68c36"),
@@ -633,7 +751,7 @@ mod tests {
         );
         assert_eq!(
             process("File \"nodepool/cmd/config_validator.py\", line 144, in validate"),
-            "File nodepool/ %ID/ config_validator.py line %ID validate"
+            "%FRAME validate nodepool/ %ID/ config_validator.py"
         );
         assert_eq!(
             process("controller |             \"after\": \"3}QP5CJuNBP65S%c:y>o\"",),
@@ -736,7 +854,7 @@ mod tests {
     fn test_process_amqp() {
         assert_eq!(
             process("closing AMQP connection <0.4375.0> ([fd00:fd00:fd00:2000::40]:33588 -> [fd00:fd00:fd00:2000::21e]:5672 - nova-compute:8:08b39730-b2e6-4d1f-bcc1-318f9bcfd7c6, vhost: '/', user: 'guest')"),
-            "closing AMQP connection %ID %ID %ID nova- compute%EQ %ID vhost%EQ user%EQ guest"
+            "closing AMQP connection %ID %IPPORT %IPPORT nova- compute%EQ %ID vhost%EQ user%EQ guest"
         );
     }
 
@@ -774,7 +892,7 @@ mod tests {
 
     #[test]
     fn test_consonant() {
-        assert_eq!(process("Name: install-pb96q"), "Name%EQ install- %ID")
+        assert_eq!(process("Name: install-pb96q"), "Name%EQ install%POD")
     }
 
     #[test]
@@ -792,7 +910,7 @@ mod tests {
         );
         assert_eq!(
             process("Name: logserver-6cc7669744-bf2b2"),
-            "Name%EQ logserver- %ID",
+            "Name%EQ logserver%POD",
         );
     }
 
@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS reports
+(
+    id              BIGSERIAL PRIMARY KEY,
+    created_at      TIMESTAMP NOT NULL,
+    updated_at      TIMESTAMP NOT NULL,
+    target          TEXT      NOT NULL,
+    baseline        TEXT      NOT NULL,
+    anomaly_count   BIGINT    NOT NULL,
+    status          TEXT      NOT NULL,
+    idempotency_key TEXT UNIQUE,
+    owner           TEXT,
+    rerun_of        BIGINT,
+    attempts        BIGINT    NOT NULL DEFAULT 0,
+    pinned          BOOLEAN   NOT NULL DEFAULT FALSE
+);
+CREATE INDEX IF NOT EXISTS reports_target ON reports (target, baseline);
+CREATE TABLE IF NOT EXISTS models
+(
+    name  TEXT PRIMARY KEY,
+    owner TEXT
+);
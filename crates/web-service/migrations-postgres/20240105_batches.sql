@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS batches
+(
+    id         BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL,
+    owner      TEXT
+);
+ALTER TABLE reports ADD COLUMN batch_id BIGINT;
+CREATE INDEX IF NOT EXISTS reports_batch_id ON reports (batch_id);
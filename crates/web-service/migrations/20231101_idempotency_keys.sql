@@ -0,0 +1,2 @@
+ALTER TABLE reports ADD COLUMN idempotency_key TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS reports_idempotency_key ON reports (idempotency_key);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS models
+(
+    name  TEXT PRIMARY KEY NOT NULL,
+    owner TEXT
+);
@@ -0,0 +1 @@
+ALTER TABLE reports ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE reports ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS anomalies
+(
+    report_id   INTEGER NOT NULL,
+    fingerprint TEXT    NOT NULL,
+    path        TEXT    NOT NULL,
+    line        TEXT    NOT NULL
+);
+CREATE INDEX IF NOT EXISTS anomalies_report_id ON anomalies (report_id);
+CREATE INDEX IF NOT EXISTS anomalies_fingerprint ON anomalies (fingerprint);
@@ -3,16 +3,27 @@
 
 //! This module contains the HTTP logic.
 
-use axum::routing::{get, put};
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{delete, get, post, put};
 use axum::{middleware::Next, response::IntoResponse};
 use std::str::FromStr;
 use tower_http::services::ServeDir;
 use tower_http::trace::{self, TraceLayer};
 
+mod auth;
 mod database;
+mod gerrit;
+mod github;
+mod models;
+mod retention;
 mod routes;
+mod storage;
+mod transcode;
 mod worker;
 
+/// The maximum size of the uploaded reports and models.
+const MAX_UPLOAD_SIZE: usize = 512 * 1024 * 1024;
+
 fn collect_vstat() {
     if let Ok(statvfs) = rustix::fs::statvfs("data") {
         if let Some(used) = statvfs
@@ -36,7 +47,12 @@ async fn main() {
         .compact()
         .init();
 
-    let builder = metrics_exporter_prometheus::PrometheusBuilder::new();
+    let builder = metrics_exporter_prometheus::PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            metrics_exporter_prometheus::Matcher::Full("logjuicer_report_duration_seconds".into()),
+            &[10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 3600.0],
+        )
+        .expect("invalid buckets");
     let handle = builder
         .install_recorder()
         .expect("failed to install Prometheus recorder");
@@ -56,14 +72,118 @@ async fn main() {
     );
     metrics::describe_counter!("http_request", "HTTP request count");
     metrics::describe_counter!("http_request_error", "HTTP request error count");
+    metrics::describe_counter!("logjuicer_reports_submitted", "Submitted report count");
+    metrics::describe_counter!("logjuicer_reports_completed", "Completed report count");
+    metrics::describe_counter!("logjuicer_reports_failed", "Failed report count");
+    metrics::describe_counter!("logjuicer_reports_cancelled", "Cancelled report count");
+    metrics::describe_counter!(
+        "logjuicer_processed_bytes",
+        metrics::Unit::Bytes,
+        "Bytes read by the reports."
+    );
+    metrics::describe_histogram!(
+        "logjuicer_report_duration_seconds",
+        metrics::Unit::Seconds,
+        "Report processing duration."
+    );
+    metrics::describe_gauge!("logjuicer_queue_depth", "Reports waiting for a worker.");
+    metrics::describe_gauge!("logjuicer_workers_active", "Workers processing a report.");
+    metrics::describe_gauge!(
+        "logjuicer_worker_utilization",
+        "Ratio of the workers processing a report."
+    );
+    metrics::describe_gauge!(
+        "logjuicer_database_bytes",
+        metrics::Unit::Bytes,
+        "Database size in bytes."
+    );
+    metrics::describe_gauge!("logjuicer_reports", "Report count by status.");
 
-    let workers = worker::Workers::new().await;
+    if storage::init() {
+        tracing::info!("Using the object storage");
+    }
+    let workers_config =
+        worker::WorkersConfig::from_env().expect("failed to read the workers settings");
+    let gerrit = gerrit::Gerrit::from_env().expect("failed to read the Gerrit settings");
+    if gerrit.is_some() {
+        tracing::info!("Commenting the Zuul build reports on Gerrit");
+    }
+    let github = github::GitHub::from_env().expect("failed to read the GitHub settings");
+    if github.is_some() {
+        tracing::info!("Reporting the Prow pull request jobs as GitHub check runs");
+    }
+    let workers = worker::Workers::new(workers_config, gerrit, github).await;
+    let auth = tokio::task::spawn_blocking(auth::Auth::from_env)
+        .await
+        .unwrap()
+        .expect("failed to setup the authentication");
+    if !auth.is_enabled() {
+        tracing::warn!("The api is not authenticated, set LOGJUICER_API_TOKENS or LOGJUICER_OIDC_ISSUER to require a token");
+    }
+    // The write routes need a user, the read routes are public.
+    let require_user = axum::middleware::from_fn_with_state(auth, auth::require_user);
+    let metrics_workers = workers.clone();
+
+    let retention = retention::Retention::from_env().expect("failed to read the retention policy");
+    if retention.is_enabled() {
+        tokio::spawn(retention.run(workers.clone()));
+    }
 
     let mut app = axum::Router::new()
         .route("/ready", get(|| async { "ok" }))
-        .route("/api/reports", get(routes::reports_list))
-        .route("/api/report/:report_id", get(routes::report_get))
-        .route("/api/report/new", put(routes::report_new))
+        .route("/healthz", get(routes::healthz))
+        .route("/readyz", get(routes::readyz))
+        .route(
+            "/api/reports",
+            get(routes::reports_list).merge(post(routes::report_batch).layer(require_user.clone())),
+        )
+        .route("/api/batch/:batch_id", get(routes::batch_get))
+        .route("/api/search", get(routes::search))
+        .route(
+            "/api/report/:report_id",
+            get(routes::report_get)
+                .merge(delete(routes::report_delete).layer(require_user.clone())),
+        )
+        .route(
+            "/api/report/:report_id/run",
+            delete(routes::report_cancel).layer(require_user.clone()),
+        )
+        .route(
+            "/api/report/:report_id/pin",
+            put(routes::report_pin)
+                .delete(routes::report_unpin)
+                .layer(require_user.clone()),
+        )
+        .route(
+            "/api/report/:report_id/rerun",
+            put(routes::report_rerun).layer(require_user.clone()),
+        )
+        .route(
+            "/api/report/:report_id/categories",
+            get(routes::report_categories),
+        )
+        .route(
+            "/api/report/new",
+            put(routes::report_new).layer(require_user.clone()),
+        )
+        .route(
+            "/api/report/estimate",
+            get(routes::report_estimate).layer(require_user.clone()),
+        )
+        .route(
+            "/api/report/upload",
+            put(routes::report_upload)
+                .layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE))
+                .layer(require_user.clone()),
+        )
+        .route(
+            "/api/models/:name",
+            put(routes::model_upload)
+                .layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE))
+                .layer(require_user.clone()),
+        )
+        .route("/api/models/:name/blob", get(routes::model_blob))
+        .route("/api/models/:name/preview", get(routes::model_preview))
         .route("/wsapi/report/:report_id", get(routes::report_watch))
         .route(
             "/metrics",
@@ -71,7 +191,11 @@ async fn main() {
                 // Collect information just before handle '/metrics'
                 collector.collect();
                 collect_vstat();
-                std::future::ready(handle.render())
+                let (workers, handle) = (metrics_workers.clone(), handle.clone());
+                async move {
+                    workers.collect_metrics().await;
+                    handle.render()
+                }
             }),
         )
         .with_state(workers)
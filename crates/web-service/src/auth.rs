@@ -0,0 +1,322 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the authentication of the write requests.
+//!
+//! The static tokens are read from the `LOGJUICER_API_TOKENS` environment variable, separated by commas,
+//! and from the `LOGJUICER_API_TOKENS_FILE` file, one token per line. The write requests must then
+//! provide one of them with the `Authorization: Bearer $TOKEN` header, while the read requests remain public.
+//!
+//! The OpenID Connect access tokens are accepted when the `LOGJUICER_OIDC_ISSUER` is set: they are validated
+//! with the provider userinfo endpoint, and the user `sub` claim, or the `LOGJUICER_OIDC_USER_CLAIM` claim,
+//! is recorded as the owner of the submitted reports and of the uploaded models.
+//! The `preferred_username` claim is only displayed in the logs.
+//! The user identifiers listed in `LOGJUICER_ADMINS`, separated by commas, and the static tokens can manage all the reports.
+//! Without any token or issuer, the api is not authenticated.
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use logjuicer_model::env::Env;
+
+type Error = (StatusCode, String);
+
+/// The duration of the validated OIDC tokens, before asking the provider again.
+const USERINFO_TTL: Duration = Duration::from_secs(300);
+
+/// The digests of the accepted tokens, to avoid comparing the secrets directly.
+#[derive(Clone, Default)]
+pub struct Tokens(Arc<Vec<[u8; 32]>>);
+
+fn digest(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+impl Tokens {
+    pub fn new<'a>(tokens: impl Iterator<Item = &'a str>) -> Tokens {
+        Tokens(Arc::new(
+            tokens
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .map(digest)
+                .collect(),
+        ))
+    }
+
+    /// Read the tokens from the environment.
+    pub fn from_env() -> std::io::Result<Tokens> {
+        let mut tokens = std::env::var("LOGJUICER_API_TOKENS").unwrap_or_default();
+        if let Ok(path) = std::env::var("LOGJUICER_API_TOKENS_FILE") {
+            tokens.push('\n');
+            tokens.push_str(&std::fs::read_to_string(path)?);
+        }
+        Ok(Tokens::new(tokens.split([',', '\n'])))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    fn contains(&self, token: &str) -> bool {
+        self.0.contains(&digest(token))
+    }
+}
+
+/// The author of a write request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct User {
+    /// The OIDC user identifier, none for the static tokens and the unauthenticated api.
+    pub id: Option<String>,
+    /// The OIDC user name, to display the user.
+    pub name: Option<String>,
+    /// Indicate if the user can manage the reports of the other users.
+    pub admin: bool,
+}
+
+impl User {
+    /// The user of the static tokens and of the unauthenticated api.
+    fn service() -> User {
+        User {
+            id: None,
+            name: None,
+            admin: true,
+        }
+    }
+
+    /// Check if the user can delete or re-run a report, or replace a model.
+    pub fn can_manage(&self, owner: Option<&str>) -> bool {
+        self.admin || (owner.is_some() && owner == self.id.as_deref())
+    }
+}
+
+/// The OpenID Connect provider.
+pub struct Oidc {
+    userinfo_endpoint: url::Url,
+    /// The claim identifying the users.
+    user_claim: String,
+    admins: Vec<String>,
+    env: Env,
+    /// The users of the validated tokens digest.
+    users: Mutex<HashMap<[u8; 32], (User, Instant)>>,
+}
+
+impl Oidc {
+    /// Discover the provider of the `LOGJUICER_OIDC_ISSUER` environment variable, when it is set.
+    pub fn from_env() -> anyhow::Result<Option<Oidc>> {
+        let issuer = match std::env::var("LOGJUICER_OIDC_ISSUER") {
+            Ok(issuer) => issuer,
+            Err(_) => return Ok(None),
+        };
+        let env = Env::new();
+        let url = url::Url::parse(&format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        ))?;
+        let config: serde_json::Value =
+            env.client_for(&url).get(url.as_str()).call()?.into_json()?;
+        let userinfo_endpoint = config["userinfo_endpoint"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("{}: missing userinfo_endpoint", url))?
+            .parse()?;
+        let user_claim =
+            std::env::var("LOGJUICER_OIDC_USER_CLAIM").unwrap_or_else(|_| "sub".into());
+        let admins = std::env::var("LOGJUICER_ADMINS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|admin| admin.trim().to_string())
+            .filter(|admin| !admin.is_empty())
+            .collect();
+        Ok(Some(Oidc {
+            userinfo_endpoint,
+            user_claim,
+            admins,
+            env,
+            users: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// Create the user of the userinfo claims.
+    fn user_from_claims(&self, claims: &serde_json::Value) -> Option<User> {
+        // The user name can be changed by the users, it must not be used to identify them.
+        let id = claims[self.user_claim.as_str()].as_str()?;
+        let name = claims["preferred_username"].as_str().unwrap_or(id);
+        Some(User {
+            admin: self.admins.iter().any(|admin| admin == id),
+            id: Some(id.into()),
+            name: Some(name.into()),
+        })
+    }
+
+    /// Validate an access token with the userinfo endpoint.
+    fn userinfo(&self, token: &str) -> Result<User, String> {
+        let claims: serde_json::Value = self
+            .env
+            .client_for(&self.userinfo_endpoint)
+            .get(self.userinfo_endpoint.as_str())
+            .set("Authorization", &format!("Bearer {}", token))
+            .call()
+            .map_err(|e| e.to_string())?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+        self.user_from_claims(&claims)
+            .ok_or_else(|| format!("missing {} claim", self.user_claim))
+    }
+
+    async fn user(self: &Arc<Self>, token: &str) -> Result<User, String> {
+        let key = digest(token);
+        {
+            let mut users = self.users.lock().unwrap();
+            users.retain(|_, (_, validated_at)| validated_at.elapsed() < USERINFO_TTL);
+            if let Some((user, _)) = users.get(&key) {
+                return Ok(user.clone());
+            }
+        }
+        let oidc = self.clone();
+        let token = token.to_string();
+        let user = tokio::task::spawn_blocking(move || oidc.userinfo(&token))
+            .await
+            .map_err(|e| e.to_string())??;
+        tracing::info!(user = user.name, id = user.id, "Validated an oidc token");
+        self.users
+            .lock()
+            .unwrap()
+            .insert(key, (user.clone(), Instant::now()));
+        Ok(user)
+    }
+}
+
+/// The authentication methods of the write requests.
+#[derive(Clone, Default)]
+pub struct Auth {
+    tokens: Tokens,
+    oidc: Option<Arc<Oidc>>,
+}
+
+impl Auth {
+    pub fn from_env() -> anyhow::Result<Auth> {
+        Ok(Auth {
+            tokens: Tokens::from_env()?,
+            oidc: Oidc::from_env()?.map(Arc::new),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.tokens.is_enabled() || self.oidc.is_some()
+    }
+
+    /// Check the bearer token of a request.
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<User, Error> {
+        if !self.is_enabled() {
+            return Ok(User::service());
+        }
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or((StatusCode::UNAUTHORIZED, "Missing bearer token".to_string()))?;
+        if self.tokens.contains(token) {
+            return Ok(User::service());
+        }
+        match &self.oidc {
+            Some(oidc) => oidc.user(token).await.map_err(|err| {
+                tracing::info!("Invalid oidc token: {}", err);
+                (StatusCode::FORBIDDEN, "Invalid bearer token".into())
+            }),
+            None => Err((StatusCode::FORBIDDEN, "Invalid bearer token".into())),
+        }
+    }
+}
+
+/// The middleware of the routes that need a user, which is added to the request extensions.
+pub async fn require_user<B>(
+    State(auth): State<Auth>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    match auth.authenticate(req.headers()).await {
+        Ok(user) => {
+            req.extensions_mut().insert(user);
+            next.run(req).await
+        }
+        Err(err) => err.into_response(),
+    }
+}
+
+#[tokio::test]
+async fn test_tokens() {
+    let headers = |value: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+        headers
+    };
+    assert_eq!(
+        Auth::default().authenticate(&HeaderMap::new()).await,
+        Ok(User::service())
+    );
+
+    let auth = Auth {
+        tokens: Tokens::new("secret, other,".split(',')),
+        oidc: None,
+    };
+    assert!(auth.is_enabled());
+    assert!(auth.authenticate(&headers("Bearer secret")).await.is_ok());
+    assert!(auth.authenticate(&headers("Bearer other")).await.is_ok());
+    assert_eq!(
+        auth.authenticate(&HeaderMap::new()).await.unwrap_err().0,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        auth.authenticate(&headers("Bearer nope"))
+            .await
+            .unwrap_err()
+            .0,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        auth.authenticate(&headers("secret")).await.unwrap_err().0,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[test]
+fn test_user() {
+    let mut oidc = Oidc {
+        userinfo_endpoint: "https://sso.example.com/userinfo".parse().unwrap(),
+        user_claim: "sub".into(),
+        admins: vec!["42".into()],
+        env: Env::new(),
+        users: Mutex::new(HashMap::new()),
+    };
+    let alice = oidc
+        .user_from_claims(&serde_json::json!({"sub": "42", "preferred_username": "alice"}))
+        .unwrap();
+    // Another user can pick the same name, it does not grant the admin role.
+    let bob = oidc
+        .user_from_claims(&serde_json::json!({"sub": "7", "preferred_username": "alice"}))
+        .unwrap();
+    assert_eq!(alice.id.as_deref(), Some("42"));
+    assert_eq!(alice.name.as_deref(), Some("alice"));
+    assert!(alice.can_manage(Some("7")));
+    assert!(!bob.admin);
+    assert!(bob.can_manage(Some("7")));
+    assert!(!bob.can_manage(Some("alice")));
+    assert!(!bob.can_manage(None));
+    assert!(User::service().can_manage(None));
+    assert!(oidc
+        .user_from_claims(&serde_json::json!({"preferred_username": "alice"}))
+        .is_none());
+
+    oidc.user_claim = "email".into();
+    let carol = oidc
+        .user_from_claims(&serde_json::json!({"sub": "8", "email": "carol@example.com"}))
+        .unwrap();
+    assert_eq!(carol.id.as_deref(), Some("carol@example.com"));
+    assert_eq!(carol.name.as_deref(), Some("carol@example.com"));
+}
@@ -3,47 +3,610 @@
 
 //! This module contains the database logic.
 
-use sqlx::types::chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{NaiveDateTime, Utc};
+use sqlx::QueryBuilder;
 
-use logjuicer_report::report_row::{ReportID, ReportRow, ReportStatus};
+use logjuicer_report::report_row::{ReportID, ReportRow, ReportStatus, SearchResult};
+use logjuicer_report::Report;
 
+mod postgres;
+
+/// The location of the database file.
+const DB_PATH: &str = "data/logjuicer.sqlite";
+
+/// The recorded inputs of a report.
+pub struct ReportSource {
+    pub target: String,
+    pub baseline: String,
+    pub owner: Option<String>,
+    pub status: ReportStatus,
+}
+
+/// A report of a batch.
+#[derive(Debug, Serialize)]
+pub struct BatchReport {
+    pub id: ReportID,
+    pub target: String,
+    pub status: ReportStatus,
+    pub anomaly_count: i64,
+}
+
+/// A group of reports submitted together.
+pub struct Batch {
+    pub created_at: NaiveDateTime,
+    pub owner: Option<String>,
+    pub reports: Vec<BatchReport>,
+}
+
+/// A report to process again.
+pub struct PendingReport {
+    pub id: ReportID,
+    pub target: String,
+    pub baseline: String,
+    /// The number of times the report was requeued.
+    pub attempts: i64,
+}
+
+/// The columns of the report list order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportSort {
+    #[default]
+    Id,
+    CreatedAt,
+    UpdatedAt,
+    AnomalyCount,
+    Target,
+}
+
+impl ReportSort {
+    fn column(&self) -> &'static str {
+        match self {
+            ReportSort::Id => "id",
+            ReportSort::CreatedAt => "created_at",
+            ReportSort::UpdatedAt => "updated_at",
+            ReportSort::AnomalyCount => "anomaly_count",
+            ReportSort::Target => "target",
+        }
+    }
+}
+
+/// The selection of the report list, the most recent reports are listed first by default.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReportFilter {
+    /// The status name, `error` matches all the failed reports.
+    pub status: Option<String>,
+    /// A part of the target, ignoring the case.
+    pub target: Option<String>,
+    /// The oldest creation date.
+    pub since: Option<NaiveDateTime>,
+    /// The creation date limit, excluded.
+    pub until: Option<NaiveDateTime>,
+    pub min_anomaly: Option<i64>,
+    pub max_anomaly: Option<i64>,
+    pub sort: ReportSort,
+    pub ascending: bool,
+    pub limit: Option<i64>,
+    pub offset: i64,
+}
+
+/// The case insensitive pattern matching a part of a value.
+fn like_pattern(value: &str) -> String {
+    let value = value
+        .to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", value)
+}
+
+/// An anomaly recorded for the search.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AnomalyRow {
+    /// The hexadecimal fingerprint, as displayed in the json export.
+    pub fingerprint: String,
+    pub path: String,
+    pub line: String,
+}
+
+/// The maximum length of the recorded anomaly lines.
+const MAX_LINE_LENGTH: usize = 1024;
+
+/// The anomalies of a report, once per fingerprint.
+pub fn report_anomalies(report: &Report) -> Vec<AnomalyRow> {
+    let mut seen = std::collections::HashSet::new();
+    let mut anomalies = Vec::new();
+    for log_report in &report.log_reports {
+        for anomaly in &log_report.anomalies {
+            let fingerprint = anomaly.fingerprint(&log_report.source);
+            if seen.insert(fingerprint) {
+                let line = &anomaly.anomaly.line;
+                let end = (0..=MAX_LINE_LENGTH.min(line.len()))
+                    .rev()
+                    .find(|pos| line.is_char_boundary(*pos))
+                    .unwrap_or(0);
+                anomalies.push(AnomalyRow {
+                    fingerprint: format!("{:016x}", fingerprint),
+                    path: log_report.source.get_relative().to_string(),
+                    line: line[..end].to_string(),
+                })
+            }
+        }
+    }
+    anomalies
+}
+
+/// Check if a search query is a fingerprint.
+fn is_fingerprint(query: &str) -> bool {
+    query.len() == 16 && query.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The query of the reports with an anomaly matching the fingerprint or a part of the line.
+fn search_query<'a, DB: sqlx::Database>(query: &str, limit: i64) -> QueryBuilder<'a, DB>
+where
+    String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+{
+    let mut builder = QueryBuilder::new(
+        "select a.report_id, r.target, r.updated_at, count(*) as matches, min(a.path) as path, min(a.line) as line \
+         from anomalies a join reports r on r.id = a.report_id where ",
+    );
+    if is_fingerprint(query) {
+        builder
+            .push("a.fingerprint = ")
+            .push_bind(query.to_lowercase());
+    } else {
+        builder
+            .push("lower(a.line) like ")
+            .push_bind(like_pattern(query))
+            .push(" escape '\\'");
+    }
+    builder
+        .push(" group by a.report_id, r.target, r.updated_at order by a.report_id desc limit ")
+        .push_bind(limit);
+    builder
+}
+
+/// The number of anomalies inserted by a query.
+const INSERT_BATCH_SIZE: usize = 500;
+
+fn insert_anomalies_query<'a, DB: sqlx::Database>(
+    report_id: ReportID,
+    anomalies: &[AnomalyRow],
+) -> QueryBuilder<'a, DB>
+where
+    String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+{
+    let mut builder =
+        QueryBuilder::new("insert into anomalies (report_id, fingerprint, path, line) ");
+    builder.push_values(anomalies, |mut row, anomaly| {
+        row.push_bind(report_id.0)
+            .push_bind(anomaly.fingerprint.clone())
+            .push_bind(anomaly.path.clone())
+            .push_bind(anomaly.line.clone());
+    });
+    builder
+}
+
+fn sqlite_search_result(row: sqlx::sqlite::SqliteRow) -> sqlx::Result<SearchResult> {
+    use sqlx::Row;
+    Ok(SearchResult {
+        report_id: row.try_get::<i64, _>("report_id")?.into(),
+        target: row.try_get::<String, _>("target")?.into(),
+        updated_at: row.try_get("updated_at")?,
+        matches: row.try_get("matches")?,
+        path: row.try_get::<String, _>("path")?.into(),
+        line: row.try_get::<String, _>("line")?.into(),
+    })
+}
+
+/// The columns of the [ReportRow].
+const REPORT_ROW_COLUMNS: &str =
+    "select id, created_at, updated_at, target, baseline, anomaly_count, status, owner, rerun_of, pinned from reports";
+
+impl ReportFilter {
+    /// Add the where clause of the filter.
+    fn push_where<'a, DB: sqlx::Database>(&self, query: &mut QueryBuilder<'a, DB>)
+    where
+        String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+        i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+        NaiveDateTime: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    {
+        query.push(" where 1 = 1");
+        match self.status.as_deref() {
+            Some("error") => {
+                query.push(" and status not in ('pending', 'done', 'cancelled')");
+            }
+            Some(status) => {
+                query.push(" and status = ").push_bind(status.to_string());
+            }
+            None => {}
+        }
+        if let Some(target) = &self.target {
+            query
+                .push(" and lower(target) like ")
+                .push_bind(like_pattern(target))
+                .push(" escape '\\'");
+        }
+        if let Some(since) = self.since {
+            query.push(" and created_at >= ").push_bind(since);
+        }
+        if let Some(until) = self.until {
+            query.push(" and created_at < ").push_bind(until);
+        }
+        if let Some(min) = self.min_anomaly {
+            query.push(" and anomaly_count >= ").push_bind(min);
+        }
+        if let Some(max) = self.max_anomaly {
+            query.push(" and anomaly_count <= ").push_bind(max);
+        }
+    }
+
+    /// Add the order and the page of the list.
+    fn push_page<'a, DB: sqlx::Database>(&self, query: &mut QueryBuilder<'a, DB>)
+    where
+        i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    {
+        let order = if self.ascending { "asc" } else { "desc" };
+        query.push(format!(" order by {} {}", self.sort.column(), order));
+        if self.sort != ReportSort::Id {
+            query.push(format!(", id {}", order));
+        }
+        if self.limit.is_some() || self.offset > 0 {
+            query
+                .push(" limit ")
+                .push_bind(self.limit.unwrap_or(i64::MAX))
+                .push(" offset ")
+                .push_bind(self.offset);
+        }
+    }
+
+    /// The query of the selected reports.
+    fn list_query<'a, DB: sqlx::Database>(&self) -> QueryBuilder<'a, DB>
+    where
+        String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+        i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+        NaiveDateTime: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    {
+        let mut query = QueryBuilder::new(REPORT_ROW_COLUMNS);
+        self.push_where(&mut query);
+        self.push_page(&mut query);
+        query
+    }
+
+    /// The query of the number of selected reports, ignoring the page.
+    fn count_query<'a, DB: sqlx::Database>(&self) -> QueryBuilder<'a, DB>
+    where
+        String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+        i64: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+        NaiveDateTime: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    {
+        let mut query = QueryBuilder::new("select count(*) from reports");
+        self.push_where(&mut query);
+        query
+    }
+}
+
+fn sqlite_report_row(row: sqlx::sqlite::SqliteRow) -> sqlx::Result<ReportRow> {
+    use sqlx::Row;
+    Ok(ReportRow {
+        id: row.try_get::<i64, _>("id")?.into(),
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        target: row.try_get::<String, _>("target")?.into(),
+        baseline: row.try_get::<String, _>("baseline")?.into(),
+        anomaly_count: row.try_get("anomaly_count")?,
+        status: row.try_get::<String, _>("status")?.into(),
+        owner: row.try_get("owner")?,
+        rerun_of: row.try_get("rerun_of")?,
+        pinned: row.try_get("pinned")?,
+    })
+}
+
+/// The reports database, the embedded sqlite database by default,
+/// or a PostgreSQL server when `LOGJUICER_DATABASE_URL` is set, to share the reports between replicas.
 #[derive(Clone)]
-pub struct Db(sqlx::SqlitePool);
+pub enum Db {
+    Sqlite(sqlx::SqlitePool),
+    Postgres(sqlx::PgPool),
+}
 
 impl Db {
     pub async fn new() -> sqlx::Result<Db> {
-        let db_url = "sqlite://data/logjuicer.sqlite?mode=rwc";
-        let pool = sqlx::SqlitePool::connect(db_url).await?;
-        sqlx::migrate!("./migrations").run(&pool).await?;
-        let db = Db(pool);
-        db.clean_pending().await?;
-        Ok(db)
+        match std::env::var("LOGJUICER_DATABASE_URL") {
+            Ok(url) if url.starts_with("postgres") => {
+                tracing::info!("Using the PostgreSQL database");
+                postgres::connect(&url).await.map(Db::Postgres)
+            }
+            _ => {
+                let db_url = format!("sqlite://{}?mode=rwc", DB_PATH);
+                let pool = sqlx::SqlitePool::connect(&db_url).await?;
+                sqlx::migrate!("./migrations").run(&pool).await?;
+                Ok(Db::Sqlite(pool))
+            }
+        }
     }
 
-    async fn clean_pending(&self) -> sqlx::Result<()> {
+    /// The reports that were interrupted by a restart of the service.
+    pub async fn get_pending_reports(&self) -> sqlx::Result<Vec<PendingReport>> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::get_pending_reports(pool).await,
+        };
         let status = ReportStatus::Pending.as_str();
-        sqlx::query!("delete from reports where status = ?", status)
-            .execute(&self.0)
+        sqlx::query!(
+            "select id, target, baseline, attempts from reports where status = ?",
+            status
+        )
+        .map(|row| PendingReport {
+            id: row.id.into(),
+            target: row.target,
+            baseline: row.baseline,
+            attempts: row.attempts,
+        })
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Record a new processing attempt of a report.
+    pub async fn increment_attempts(&self, report_id: ReportID) -> sqlx::Result<()> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::increment_attempts(pool, report_id).await,
+        };
+        sqlx::query!(
+            "update reports set attempts = attempts + 1 where id = ?",
+            report_id.0
+        )
+        .execute(pool)
+        .await
+        .map(|_| ())
+    }
+
+    /// The selected reports and the total number of matching reports.
+    pub async fn list_reports(&self, filter: &ReportFilter) -> sqlx::Result<(Vec<ReportRow>, i64)> {
+        match self {
+            Db::Sqlite(pool) => {
+                let reports = filter
+                    .list_query::<sqlx::Sqlite>()
+                    .build()
+                    .try_map(sqlite_report_row)
+                    .fetch_all(pool)
+                    .await?;
+                let total = filter
+                    .count_query::<sqlx::Sqlite>()
+                    .build_query_scalar()
+                    .fetch_one(pool)
+                    .await?;
+                Ok((reports, total))
+            }
+            Db::Postgres(pool) => {
+                let reports = filter
+                    .list_query::<sqlx::Postgres>()
+                    .build()
+                    .try_map(postgres::report_row)
+                    .fetch_all(pool)
+                    .await?;
+                let total = filter
+                    .count_query::<sqlx::Postgres>()
+                    .build_query_scalar()
+                    .fetch_one(pool)
+                    .await?;
+                Ok((reports, total))
+            }
+        }
+    }
+
+    /// Record the anomalies of a report for the search.
+    pub async fn insert_anomalies(
+        &self,
+        report_id: ReportID,
+        anomalies: &[AnomalyRow],
+    ) -> sqlx::Result<()> {
+        for batch in anomalies.chunks(INSERT_BATCH_SIZE) {
+            match self {
+                Db::Sqlite(pool) => {
+                    insert_anomalies_query::<sqlx::Sqlite>(report_id, batch)
+                        .build()
+                        .execute(pool)
+                        .await?;
+                }
+                Db::Postgres(pool) => {
+                    insert_anomalies_query::<sqlx::Postgres>(report_id, batch)
+                        .build()
+                        .execute(pool)
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The most recent reports with an anomaly matching the query.
+    pub async fn search_anomalies(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> sqlx::Result<Vec<SearchResult>> {
+        match self {
+            Db::Sqlite(pool) => {
+                search_query::<sqlx::Sqlite>(query, limit)
+                    .build()
+                    .try_map(sqlite_search_result)
+                    .fetch_all(pool)
+                    .await
+            }
+            Db::Postgres(pool) => {
+                search_query::<sqlx::Postgres>(query, limit)
+                    .build()
+                    .try_map(postgres::search_result)
+                    .fetch_all(pool)
+                    .await
+            }
+        }
+    }
+
+    /// The owner and the status of a report.
+    pub async fn get_report_owner(
+        &self,
+        report_id: ReportID,
+    ) -> sqlx::Result<Option<(Option<String>, ReportStatus)>> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::get_report_owner(pool, report_id).await,
+        };
+        sqlx::query!(
+            "select owner, status from reports where id = ?",
+            report_id.0
+        )
+        .map(|row| (row.owner, row.status.into()))
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// The owner of a model, none when the model was never uploaded.
+    pub async fn get_model_owner(&self, name: &str) -> sqlx::Result<Option<Option<String>>> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::get_model_owner(pool, name).await,
+        };
+        sqlx::query!("select owner from models where name = ?", name)
+            .map(|row| row.owner)
+            .fetch_optional(pool)
             .await
-            .map(|_| ())
     }
 
-    pub async fn get_reports(&self) -> sqlx::Result<Vec<ReportRow>> {
-        sqlx::query_as!(
-        ReportRow,
-        "select id, created_at, updated_at, target, baseline, anomaly_count, status from reports order by id desc"
-    )
-        .fetch_all(&self.0)
+    /// Record the owner of a new model, the first uploader keeps the model.
+    pub async fn claim_model(&self, name: &str, owner: Option<&str>) -> sqlx::Result<()> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::claim_model(pool, name, owner).await,
+        };
+        sqlx::query!(
+            "insert into models (name, owner) values (?, ?) on conflict (name) do nothing",
+            name,
+            owner
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The target, the baseline, the owner and the status of a report, to run it again.
+    pub async fn get_report_source(
+        &self,
+        report_id: ReportID,
+    ) -> sqlx::Result<Option<ReportSource>> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::get_report_source(pool, report_id).await,
+        };
+        sqlx::query!(
+            "select target, baseline, owner, status from reports where id = ?",
+            report_id.0
+        )
+        .map(|row| ReportSource {
+            target: row.target,
+            baseline: row.baseline,
+            owner: row.owner,
+            status: row.status.into(),
+        })
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// The finished reports that are not pinned, the most recent first.
+    pub async fn get_unpinned_reports(&self) -> sqlx::Result<Vec<(ReportID, NaiveDateTime)>> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::get_unpinned_reports(pool).await,
+        };
+        let status = ReportStatus::Pending.as_str();
+        sqlx::query!(
+            "select id, updated_at from reports where status != ? and pinned = false order by id desc",
+            status
+        )
+        .map(|row| (row.id.into(), row.updated_at))
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Exempt a report from the retention policy.
+    pub async fn set_pinned(&self, report_id: ReportID, pinned: bool) -> sqlx::Result<()> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::set_pinned(pool, report_id, pinned).await,
+        };
+        sqlx::query!(
+            "update reports set pinned = ? where id = ?",
+            pinned,
+            report_id.0
+        )
+        .execute(pool)
         .await
+        .map(|_| ())
+    }
+
+    pub async fn delete_report(&self, report_id: ReportID) -> sqlx::Result<()> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::delete_report(pool, report_id).await,
+        };
+        sqlx::query!("delete from anomalies where report_id = ?", report_id.0)
+            .execute(pool)
+            .await?;
+        sqlx::query!("delete from reports where id = ?", report_id.0)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    /// Check the database connection.
+    pub async fn ping(&self) -> sqlx::Result<()> {
+        use sqlx::Connection;
+        match self {
+            Db::Sqlite(pool) => pool.acquire().await?.ping().await,
+            Db::Postgres(pool) => pool.acquire().await?.ping().await,
+        }
+    }
+
+    /// The number of reports of each status.
+    pub async fn count_reports(&self) -> sqlx::Result<Vec<(ReportStatus, i64)>> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::count_reports(pool).await,
+        };
+        sqlx::query!("select status, count(*) as count from reports group by status")
+            .map(|row| (row.status.into(), row.count))
+            .fetch_all(pool)
+            .await
+    }
+
+    /// The size of the database files, including the write-ahead log.
+    pub async fn size(&self) -> sqlx::Result<u64> {
+        if let Db::Postgres(pool) = self {
+            return postgres::size(pool).await;
+        }
+        Ok([DB_PATH.to_string(), format!("{}-wal", DB_PATH)]
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|meta| meta.len())
+            .sum())
     }
 
     pub async fn get_report_status(
         &self,
         report_id: ReportID,
     ) -> sqlx::Result<Option<ReportStatus>> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::get_report_status(pool, report_id).await,
+        };
         sqlx::query!("select status from reports where id = ?", report_id.0)
             .map(|row| row.status.into())
-            .fetch_optional(&self.0)
+            .fetch_optional(pool)
             .await
     }
 
@@ -52,13 +615,34 @@ impl Db {
         target: &str,
         baseline: &str,
     ) -> sqlx::Result<Option<(ReportID, ReportStatus)>> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::lookup_report(pool, target, baseline).await,
+        };
         sqlx::query!(
-            "select id, status from reports where target = ? and baseline = ?",
+            "select id, status from reports where target = ? and baseline = ? order by id desc",
             target,
             baseline
         )
         .map(|row| (row.id.into(), row.status.into()))
-        .fetch_optional(&self.0)
+        .fetch_optional(pool)
+        .await
+    }
+
+    pub async fn lookup_idempotency_key(
+        &self,
+        key: &str,
+    ) -> sqlx::Result<Option<(ReportID, ReportStatus)>> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::lookup_idempotency_key(pool, key).await,
+        };
+        sqlx::query!(
+            "select id, status from reports where idempotency_key = ?",
+            key
+        )
+        .map(|row| (row.id.into(), row.status.into()))
+        .fetch_optional(pool)
         .await
     }
 
@@ -68,6 +652,12 @@ impl Db {
         anomaly_count: usize,
         status: &ReportStatus,
     ) -> sqlx::Result<()> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => {
+                return postgres::update_report(pool, report_id, anomaly_count, status).await
+            }
+        };
         let now = Utc::now();
         let count = anomaly_count as i64;
         let status = status.as_str();
@@ -78,27 +668,274 @@ impl Db {
             status,
             report_id.0
         )
-        .execute(&self.0)
+        .execute(pool)
         .await
         .map(|_| ())
     }
 
-    pub async fn initialize_report(&self, target: &str, baseline: &str) -> sqlx::Result<ReportID> {
+    /// Create the pending reports of a batch, in a single transaction.
+    pub async fn create_batch(
+        &self,
+        targets: &[String],
+        baseline: &str,
+        owner: Option<&str>,
+    ) -> sqlx::Result<(i64, Vec<ReportID>)> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => {
+                return postgres::create_batch(pool, targets, baseline, owner).await
+            }
+        };
+        let now_utc = Utc::now();
+        let status = ReportStatus::Pending.as_str();
+        let mut tx = pool.begin().await?;
+        let batch_id = sqlx::query!(
+            "insert into batches (created_at, owner) values (?, ?)",
+            now_utc,
+            owner
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        let mut report_ids = Vec::with_capacity(targets.len());
+        for target in targets {
+            let id = sqlx::query!(
+                "insert into reports (created_at, updated_at, target, baseline, anomaly_count, status, owner, batch_id)
+                          values (?, ?, ?, ?, ?, ?, ?, ?)",
+                now_utc,
+                now_utc,
+                target,
+                baseline,
+                0,
+                status,
+                owner,
+                batch_id
+            )
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+            report_ids.push(id.into());
+        }
+        tx.commit().await?;
+        Ok((batch_id, report_ids))
+    }
+
+    /// The reports of a batch.
+    pub async fn get_batch(&self, batch_id: i64) -> sqlx::Result<Option<Batch>> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => return postgres::get_batch(pool, batch_id).await,
+        };
+        let batch = sqlx::query!(
+            "select created_at, owner from batches where id = ?",
+            batch_id
+        )
+        .fetch_optional(pool)
+        .await?;
+        let batch = match batch {
+            Some(batch) => batch,
+            None => return Ok(None),
+        };
+        let reports = sqlx::query!(
+            "select id, target, status, anomaly_count from reports where batch_id = ? order by id",
+            batch_id
+        )
+        .map(|row| BatchReport {
+            id: row.id.into(),
+            target: row.target,
+            status: row.status.into(),
+            anomaly_count: row.anomaly_count,
+        })
+        .fetch_all(pool)
+        .await?;
+        Ok(Some(Batch {
+            created_at: batch.created_at,
+            owner: batch.owner,
+            reports,
+        }))
+    }
+
+    pub async fn initialize_report(
+        &self,
+        target: &str,
+        baseline: &str,
+        idempotency_key: Option<&str>,
+        owner: Option<&str>,
+        rerun_of: Option<ReportID>,
+    ) -> sqlx::Result<ReportID> {
+        let pool = match self {
+            Db::Sqlite(pool) => pool,
+            Db::Postgres(pool) => {
+                return postgres::initialize_report(
+                    pool,
+                    target,
+                    baseline,
+                    idempotency_key,
+                    owner,
+                    rerun_of,
+                )
+                .await
+            }
+        };
         let now_utc = Utc::now();
         let status = ReportStatus::Pending.as_str();
+        let rerun_of = rerun_of.map(|report_id| report_id.0);
         let id = sqlx::query!(
-            "insert into reports (created_at, updated_at, target, baseline, anomaly_count, status)
-                      values (?, ?, ?, ?, ?, ?)",
+            "insert into reports (created_at, updated_at, target, baseline, anomaly_count, status, idempotency_key, owner, rerun_of)
+                      values (?, ?, ?, ?, ?, ?, ?, ?, ?)",
             now_utc,
             now_utc,
             target,
             baseline,
             0,
-            status
+            status,
+            idempotency_key,
+            owner,
+            rerun_of
         )
-        .execute(&self.0)
+        .execute(pool)
         .await?
         .last_insert_rowid();
         Ok(id.into())
     }
 }
+
+#[tokio::test]
+async fn test_list_reports() -> sqlx::Result<()> {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await?;
+    sqlx::migrate!("./migrations").run(&pool).await?;
+    let db = Db::Sqlite(pool);
+    for (target, count, status) in [
+        ("https://zuul/build/1", 3, ReportStatus::Completed),
+        ("https://zuul/build/2", 42, ReportStatus::Completed),
+        (
+            "https://ci_100%/job/3",
+            0,
+            ReportStatus::Error("oops".into()),
+        ),
+    ] {
+        let report_id = db
+            .initialize_report(target, "auto", None, None, None)
+            .await?;
+        db.update_report(report_id, count, &status).await?;
+    }
+    let ids = |(reports, total): (Vec<ReportRow>, i64)| {
+        (
+            reports.iter().map(|report| report.id.0).collect::<Vec<_>>(),
+            total,
+        )
+    };
+
+    assert_eq!(
+        ids(db.list_reports(&ReportFilter::default()).await?),
+        (vec![3, 2, 1], 3)
+    );
+    let filter = ReportFilter {
+        target: Some("ZUUL".into()),
+        sort: ReportSort::AnomalyCount,
+        limit: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(ids(db.list_reports(&filter).await?), (vec![2], 2));
+    let filter = ReportFilter {
+        offset: 1,
+        ..filter
+    };
+    assert_eq!(ids(db.list_reports(&filter).await?), (vec![1], 2));
+    let filter = ReportFilter {
+        target: Some("100%".into()),
+        ..Default::default()
+    };
+    assert_eq!(ids(db.list_reports(&filter).await?), (vec![3], 1));
+    let filter = ReportFilter {
+        status: Some("error".into()),
+        ..Default::default()
+    };
+    assert_eq!(ids(db.list_reports(&filter).await?), (vec![3], 1));
+    let filter = ReportFilter {
+        status: Some("done".into()),
+        min_anomaly: Some(1),
+        max_anomaly: Some(10),
+        since: Some(Utc::now().naive_utc() - chrono::Duration::days(1)),
+        ..Default::default()
+    };
+    assert_eq!(ids(db.list_reports(&filter).await?), (vec![1], 1));
+
+    let anomaly = |fingerprint: &str, line: &str| AnomalyRow {
+        fingerprint: fingerprint.into(),
+        path: "job-output.txt".into(),
+        line: line.into(),
+    };
+    db.insert_anomalies(
+        1.into(),
+        &[
+            anomaly("00000000deadbeef", "ERROR: Disk full"),
+            anomaly("0000000000c0ffee", "Traceback: 100% failed"),
+        ],
+    )
+    .await?;
+    db.insert_anomalies(2.into(), &[anomaly("00000000deadbeef", "error: disk FULL")])
+        .await?;
+    let search = |query: &'static str| {
+        let db = &db;
+        async move {
+            db.search_anomalies(query, 10).await.map(|results| {
+                results
+                    .iter()
+                    .map(|result| (result.report_id.0, result.matches))
+                    .collect::<Vec<_>>()
+            })
+        }
+    };
+    assert_eq!(search("disk full").await?, vec![(2, 1), (1, 1)]);
+    assert_eq!(search("00000000DEADBEEF").await?, vec![(2, 1), (1, 1)]);
+    assert_eq!(search("100%").await?, vec![(1, 1)]);
+    assert_eq!(search("r: ").await?, vec![(2, 1), (1, 1)]);
+    assert_eq!(search("missing").await?, vec![]);
+    db.delete_report(1.into()).await?;
+    assert_eq!(search("disk full").await?, vec![(2, 1)]);
+
+    let targets = [
+        "https://zuul/build/4".to_string(),
+        "https://zuul/build/5".to_string(),
+    ];
+    let (batch_id, report_ids) = db.create_batch(&targets, "auto", Some("alice")).await?;
+    assert_eq!(report_ids, vec![4.into(), 5.into()]);
+    db.update_report(report_ids[0], 2, &ReportStatus::Completed)
+        .await?;
+    let batch = db.get_batch(batch_id).await?.unwrap();
+    assert_eq!(batch.owner.as_deref(), Some("alice"));
+    assert_eq!(
+        batch
+            .reports
+            .iter()
+            .map(|report| (report.id.0, report.status.clone(), report.anomaly_count))
+            .collect::<Vec<_>>(),
+        vec![
+            (4, ReportStatus::Completed, 2),
+            (5, ReportStatus::Pending, 0)
+        ]
+    );
+    assert!(db.get_batch(batch_id + 1).await?.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_model_owner() -> sqlx::Result<()> {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await?;
+    sqlx::migrate!("./migrations").run(&pool).await?;
+    let db = Db::Sqlite(pool);
+    assert_eq!(db.get_model_owner("tox").await?, None);
+    db.claim_model("tox", Some("42")).await?;
+    db.claim_model("tox", Some("7")).await?;
+    assert_eq!(db.get_model_owner("tox").await?, Some(Some("42".into())));
+    db.claim_model("legacy", None).await?;
+    assert_eq!(db.get_model_owner("legacy").await?, Some(None));
+    Ok(())
+}
@@ -0,0 +1,337 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the PostgreSQL queries of the [super::Db].
+//!
+//! The queries are checked at runtime because the sqlx offline data only covers the sqlite database.
+//! The schema is created by the migrations of the `migrations-postgres` directory.
+
+use sqlx::types::chrono::{NaiveDateTime, Utc};
+use sqlx::{PgPool, Row};
+
+use logjuicer_report::report_row::{ReportID, ReportRow, ReportStatus, SearchResult};
+
+use super::{Batch, BatchReport, PendingReport, ReportSource};
+
+pub async fn connect(url: &str) -> sqlx::Result<PgPool> {
+    let pool = PgPool::connect(url).await?;
+    sqlx::migrate!("./migrations-postgres").run(&pool).await?;
+    Ok(pool)
+}
+
+pub async fn get_pending_reports(pool: &PgPool) -> sqlx::Result<Vec<PendingReport>> {
+    sqlx::query("select id, target, baseline, attempts from reports where status = $1")
+        .bind(ReportStatus::Pending.as_str())
+        .try_map(|row: sqlx::postgres::PgRow| {
+            Ok(PendingReport {
+                id: row.try_get::<i64, _>("id")?.into(),
+                target: row.try_get("target")?,
+                baseline: row.try_get("baseline")?,
+                attempts: row.try_get("attempts")?,
+            })
+        })
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn increment_attempts(pool: &PgPool, report_id: ReportID) -> sqlx::Result<()> {
+    sqlx::query("update reports set attempts = attempts + 1 where id = $1")
+        .bind(report_id.0)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+pub fn report_row(row: sqlx::postgres::PgRow) -> sqlx::Result<ReportRow> {
+    Ok(ReportRow {
+        id: row.try_get::<i64, _>("id")?.into(),
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        target: row.try_get::<String, _>("target")?.into(),
+        baseline: row.try_get::<String, _>("baseline")?.into(),
+        anomaly_count: row.try_get("anomaly_count")?,
+        status: row.try_get::<String, _>("status")?.into(),
+        owner: row.try_get("owner")?,
+        rerun_of: row.try_get("rerun_of")?,
+        pinned: row.try_get("pinned")?,
+    })
+}
+
+pub fn search_result(row: sqlx::postgres::PgRow) -> sqlx::Result<SearchResult> {
+    Ok(SearchResult {
+        report_id: row.try_get::<i64, _>("report_id")?.into(),
+        target: row.try_get::<String, _>("target")?.into(),
+        updated_at: row.try_get("updated_at")?,
+        matches: row.try_get("matches")?,
+        path: row.try_get::<String, _>("path")?.into(),
+        line: row.try_get::<String, _>("line")?.into(),
+    })
+}
+
+pub async fn get_report_owner(
+    pool: &PgPool,
+    report_id: ReportID,
+) -> sqlx::Result<Option<(Option<String>, ReportStatus)>> {
+    sqlx::query("select owner, status from reports where id = $1")
+        .bind(report_id.0)
+        .try_map(|row: sqlx::postgres::PgRow| {
+            Ok((
+                row.try_get("owner")?,
+                row.try_get::<String, _>("status")?.into(),
+            ))
+        })
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn get_model_owner(pool: &PgPool, name: &str) -> sqlx::Result<Option<Option<String>>> {
+    sqlx::query_scalar("select owner from models where name = $1")
+        .bind(name)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn claim_model(pool: &PgPool, name: &str, owner: Option<&str>) -> sqlx::Result<()> {
+    sqlx::query("insert into models (name, owner) values ($1, $2) on conflict (name) do nothing")
+        .bind(name)
+        .bind(owner)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_report_source(
+    pool: &PgPool,
+    report_id: ReportID,
+) -> sqlx::Result<Option<ReportSource>> {
+    sqlx::query("select target, baseline, owner, status from reports where id = $1")
+        .bind(report_id.0)
+        .try_map(|row: sqlx::postgres::PgRow| {
+            Ok(ReportSource {
+                target: row.try_get("target")?,
+                baseline: row.try_get("baseline")?,
+                owner: row.try_get("owner")?,
+                status: row.try_get::<String, _>("status")?.into(),
+            })
+        })
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn get_unpinned_reports(pool: &PgPool) -> sqlx::Result<Vec<(ReportID, NaiveDateTime)>> {
+    sqlx::query(
+        "select id, updated_at from reports where status != $1 and pinned = false order by id desc",
+    )
+    .bind(ReportStatus::Pending.as_str())
+    .try_map(|row: sqlx::postgres::PgRow| {
+        Ok((
+            row.try_get::<i64, _>("id")?.into(),
+            row.try_get("updated_at")?,
+        ))
+    })
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn set_pinned(pool: &PgPool, report_id: ReportID, pinned: bool) -> sqlx::Result<()> {
+    sqlx::query("update reports set pinned = $1 where id = $2")
+        .bind(pinned)
+        .bind(report_id.0)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+pub async fn delete_report(pool: &PgPool, report_id: ReportID) -> sqlx::Result<()> {
+    sqlx::query("delete from anomalies where report_id = $1")
+        .bind(report_id.0)
+        .execute(pool)
+        .await?;
+    sqlx::query("delete from reports where id = $1")
+        .bind(report_id.0)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+pub async fn count_reports(pool: &PgPool) -> sqlx::Result<Vec<(ReportStatus, i64)>> {
+    sqlx::query("select status, count(*) as count from reports group by status")
+        .try_map(|row: sqlx::postgres::PgRow| {
+            Ok((
+                row.try_get::<String, _>("status")?.into(),
+                row.try_get("count")?,
+            ))
+        })
+        .fetch_all(pool)
+        .await
+}
+
+/// The size of the database, as reported by the server.
+pub async fn size(pool: &PgPool) -> sqlx::Result<u64> {
+    let size: i64 = sqlx::query_scalar("select pg_database_size(current_database())")
+        .fetch_one(pool)
+        .await?;
+    Ok(size as u64)
+}
+
+pub async fn get_report_status(
+    pool: &PgPool,
+    report_id: ReportID,
+) -> sqlx::Result<Option<ReportStatus>> {
+    sqlx::query_scalar::<_, String>("select status from reports where id = $1")
+        .bind(report_id.0)
+        .fetch_optional(pool)
+        .await
+        .map(|status| status.map(ReportStatus::from))
+}
+
+async fn lookup(
+    query: sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments>,
+    pool: &PgPool,
+) -> sqlx::Result<Option<(ReportID, ReportStatus)>> {
+    query
+        .try_map(|row: sqlx::postgres::PgRow| {
+            Ok((
+                row.try_get::<i64, _>("id")?.into(),
+                row.try_get::<String, _>("status")?.into(),
+            ))
+        })
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn lookup_report(
+    pool: &PgPool,
+    target: &str,
+    baseline: &str,
+) -> sqlx::Result<Option<(ReportID, ReportStatus)>> {
+    let query = sqlx::query(
+        "select id, status from reports where target = $1 and baseline = $2 order by id desc limit 1",
+    )
+    .bind(target)
+    .bind(baseline);
+    lookup(query, pool).await
+}
+
+pub async fn lookup_idempotency_key(
+    pool: &PgPool,
+    key: &str,
+) -> sqlx::Result<Option<(ReportID, ReportStatus)>> {
+    let query = sqlx::query("select id, status from reports where idempotency_key = $1").bind(key);
+    lookup(query, pool).await
+}
+
+pub async fn update_report(
+    pool: &PgPool,
+    report_id: ReportID,
+    anomaly_count: usize,
+    status: &ReportStatus,
+) -> sqlx::Result<()> {
+    sqlx::query("update reports set updated_at = $1, anomaly_count = $2, status = $3 where id = $4")
+        .bind(Utc::now().naive_utc())
+        .bind(anomaly_count as i64)
+        .bind(status.as_str())
+        .bind(report_id.0)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+pub async fn create_batch(
+    pool: &PgPool,
+    targets: &[String],
+    baseline: &str,
+    owner: Option<&str>,
+) -> sqlx::Result<(i64, Vec<ReportID>)> {
+    let now_utc = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+    let batch_id: i64 =
+        sqlx::query_scalar("insert into batches (created_at, owner) values ($1, $2) returning id")
+            .bind(now_utc)
+            .bind(owner)
+            .fetch_one(&mut *tx)
+            .await?;
+    let mut report_ids = Vec::with_capacity(targets.len());
+    for target in targets {
+        let id: i64 = sqlx::query_scalar(
+            "insert into reports (created_at, updated_at, target, baseline, anomaly_count, status, owner, batch_id)
+                          values ($1, $2, $3, $4, $5, $6, $7, $8) returning id",
+        )
+        .bind(now_utc)
+        .bind(now_utc)
+        .bind(target)
+        .bind(baseline)
+        .bind(0i64)
+        .bind(ReportStatus::Pending.as_str())
+        .bind(owner)
+        .bind(batch_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        report_ids.push(id.into());
+    }
+    tx.commit().await?;
+    Ok((batch_id, report_ids))
+}
+
+pub async fn get_batch(pool: &PgPool, batch_id: i64) -> sqlx::Result<Option<Batch>> {
+    let batch = sqlx::query("select created_at, owner from batches where id = $1")
+        .bind(batch_id)
+        .try_map(|row: sqlx::postgres::PgRow| {
+            Ok((
+                row.try_get::<NaiveDateTime, _>("created_at")?,
+                row.try_get::<Option<String>, _>("owner")?,
+            ))
+        })
+        .fetch_optional(pool)
+        .await?;
+    let (created_at, owner) = match batch {
+        Some(batch) => batch,
+        None => return Ok(None),
+    };
+    let reports = sqlx::query(
+        "select id, target, status, anomaly_count from reports where batch_id = $1 order by id",
+    )
+    .bind(batch_id)
+    .try_map(|row: sqlx::postgres::PgRow| {
+        Ok(BatchReport {
+            id: row.try_get::<i64, _>("id")?.into(),
+            target: row.try_get("target")?,
+            status: row.try_get::<String, _>("status")?.into(),
+            anomaly_count: row.try_get("anomaly_count")?,
+        })
+    })
+    .fetch_all(pool)
+    .await?;
+    Ok(Some(Batch {
+        created_at,
+        owner,
+        reports,
+    }))
+}
+
+pub async fn initialize_report(
+    pool: &PgPool,
+    target: &str,
+    baseline: &str,
+    idempotency_key: Option<&str>,
+    owner: Option<&str>,
+    rerun_of: Option<ReportID>,
+) -> sqlx::Result<ReportID> {
+    let now_utc = Utc::now().naive_utc();
+    let id: i64 = sqlx::query_scalar(
+        "insert into reports (created_at, updated_at, target, baseline, anomaly_count, status, idempotency_key, owner, rerun_of)
+                      values ($1, $2, $3, $4, $5, $6, $7, $8, $9) returning id",
+    )
+    .bind(now_utc)
+    .bind(now_utc)
+    .bind(target)
+    .bind(baseline)
+    .bind(0i64)
+    .bind(ReportStatus::Pending.as_str())
+    .bind(idempotency_key)
+    .bind(owner)
+    .bind(rerun_of.map(|report_id| report_id.0))
+    .fetch_one(pool)
+    .await?;
+    Ok(id.into())
+}
@@ -0,0 +1,293 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the GitHub integration, to report the Prow pull request jobs as check runs.
+//!
+//! The integration is enabled with the `LOGJUICER_GITHUB_APP_ID` and `LOGJUICER_GITHUB_APP_KEY` environment
+//! variables, the id and the private key file of a GitHub App installed on the repositories with the
+//! `checks: write` permission. The check run is created on the current head of the pull request, with the anomaly
+//! count, the Markdown summary of the report, and a link to the report page of the `LOGJUICER_PUBLIC_URL` web interface.
+//! The `LOGJUICER_GITHUB_API_URL` variable can be used to change the api endpoint for GitHub Enterprise.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use logjuicer_report::report_row::ReportID;
+use logjuicer_report::{Content, Report};
+
+use crate::gerrit::with_slash;
+
+/// The maximum length of the check run summary.
+const MAX_SUMMARY: usize = 65535;
+
+/// The duration of the installation tokens, they are valid for one hour.
+const TOKEN_TTL: Duration = Duration::from_secs(3000);
+
+/// The GitHub App.
+pub struct GitHub {
+    app_id: String,
+    key_pair: ring::signature::RsaKeyPair,
+    /// The api url, terminated with a slash.
+    api_url: String,
+    /// The web interface url, terminated with a slash.
+    public_url: String,
+    agent: ureq::Agent,
+    /// The installation tokens per repository.
+    tokens: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+/// A check run to create.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CheckRun {
+    /// The repository, as `owner/name`.
+    repo: String,
+    pr: u64,
+    name: String,
+    title: String,
+    summary: String,
+    details_url: String,
+}
+
+#[derive(Deserialize)]
+struct Installation {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct AccessToken {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct PullRequest {
+    head: PullRequestHead,
+}
+
+#[derive(Deserialize)]
+struct PullRequestHead {
+    sha: String,
+}
+
+fn base64url(data: &[u8]) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(data)
+}
+
+/// Read the private key, GitHub provides the PKCS#1 format, but PKCS#8 is also accepted.
+fn read_key(pem: &[u8]) -> Result<ring::signature::RsaKeyPair, String> {
+    let mut reader = pem;
+    if let Some(der) = rustls_pemfile::rsa_private_keys(&mut reader)
+        .map_err(|e| e.to_string())?
+        .pop()
+    {
+        return ring::signature::RsaKeyPair::from_der(&der).map_err(|e| e.to_string());
+    }
+    let mut reader = pem;
+    match rustls_pemfile::pkcs8_private_keys(&mut reader)
+        .map_err(|e| e.to_string())?
+        .pop()
+    {
+        Some(der) => ring::signature::RsaKeyPair::from_pkcs8(&der).map_err(|e| e.to_string()),
+        None => Err("missing private key".into()),
+    }
+}
+
+/// The GitHub repository of a Prow project, which joins the owner and the name with an underscore.
+/// The owner can't contain an underscore, but the name can.
+fn prow_repository(project: &str) -> Option<String> {
+    project
+        .split_once('_')
+        .filter(|(owner, name)| !owner.is_empty() && !name.is_empty())
+        .map(|(owner, name)| format!("{}/{}", owner, name))
+}
+
+impl GitHub {
+    /// Read the settings from the environment, returning None when the integration is disabled.
+    pub fn from_env() -> Result<Option<GitHub>, String> {
+        GitHub::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Result<Option<GitHub>, String> {
+        let app_id = match get("LOGJUICER_GITHUB_APP_ID").filter(|id| !id.is_empty()) {
+            Some(app_id) => app_id,
+            None => return Ok(None),
+        };
+        let require = |name: &str| {
+            get(name)
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| format!("{}: missing value for the GitHub integration", name))
+        };
+        let key_path = require("LOGJUICER_GITHUB_APP_KEY")?;
+        let key_pair = std::fs::read(&key_path)
+            .map_err(|e| e.to_string())
+            .and_then(|pem| read_key(&pem))
+            .map_err(|e| format!("LOGJUICER_GITHUB_APP_KEY: {}: {}", key_path, e))?;
+        Ok(Some(GitHub {
+            app_id,
+            key_pair,
+            api_url: with_slash(
+                get("LOGJUICER_GITHUB_API_URL").unwrap_or_else(|| "https://api.github.com".into()),
+            ),
+            public_url: with_slash(require("LOGJUICER_PUBLIC_URL")?),
+            agent: logjuicer_model::env::Env::new().client,
+            tokens: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// The check run of a report, when its target is a pull request job.
+    pub fn check_run(&self, report_id: ReportID, report: &Report) -> Option<CheckRun> {
+        let build = match &report.target {
+            Content::Prow(build) if build.pr > 0 => build,
+            _ => return None,
+        };
+        let mut summary = Vec::new();
+        logjuicer_report::markdown::save_writer(report, &mut summary).ok()?;
+        let mut summary = String::from_utf8_lossy(&summary).into_owned();
+        if let Some((pos, _)) = summary.char_indices().nth(MAX_SUMMARY) {
+            summary.truncate(pos);
+        }
+        Some(CheckRun {
+            repo: prow_repository(&build.project)?,
+            pr: build.pr,
+            name: format!("logjuicer/{}", build.job_name),
+            title: format!("{} anomalies", report.anomaly_count()),
+            summary,
+            details_url: format!("{}report/{}", self.public_url, report_id),
+        })
+    }
+
+    /// Create the signed JWT authenticating the app.
+    fn jwt(&self) -> Result<String, String> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let header = base64url(br#"{"alg":"RS256","typ":"JWT"}"#);
+        let claims = base64url(
+            serde_json::json!({
+                // Allow some clock drift with the GitHub servers.
+                "iat": now - 60,
+                "exp": now + 540,
+                "iss": self.app_id,
+            })
+            .to_string()
+            .as_bytes(),
+        );
+        let message = format!("{}.{}", header, claims);
+        let mut signature = vec![0; self.key_pair.public_modulus_len()];
+        self.key_pair
+            .sign(
+                &ring::signature::RSA_PKCS1_SHA256,
+                &ring::rand::SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(format!("{}.{}", message, base64url(&signature)))
+    }
+
+    fn request(&self, method: &str, path: &str, token: &str) -> ureq::Request {
+        self.agent
+            .request(method, &format!("{}{}", self.api_url, path))
+            .set("Accept", "application/vnd.github+json")
+            .set("X-GitHub-Api-Version", "2022-11-28")
+            .set("Authorization", &format!("Bearer {}", token))
+    }
+
+    /// Get an installation token for the repository, the token is renewed before it expires.
+    fn token(&self, repo: &str) -> Result<String, String> {
+        let mut tokens = self.tokens.lock().expect("Tokens lock");
+        match tokens.get(repo) {
+            Some((token, expires)) if *expires > Instant::now() => Ok(token.clone()),
+            _ => {
+                let jwt = self.jwt()?;
+                let installation: Installation = self
+                    .request("GET", &format!("repos/{}/installation", repo), &jwt)
+                    .call()
+                    .map_err(|e| format!("{}: the app is not installed: {}", repo, e))?
+                    .into_json()
+                    .map_err(|e| e.to_string())?;
+                let access: AccessToken = self
+                    .request(
+                        "POST",
+                        &format!("app/installations/{}/access_tokens", installation.id),
+                        &jwt,
+                    )
+                    .call()
+                    .map_err(|e| format!("{}: token request failed: {}", repo, e))?
+                    .into_json()
+                    .map_err(|e| e.to_string())?;
+                tokens.insert(
+                    repo.to_string(),
+                    (access.token.clone(), Instant::now() + TOKEN_TTL),
+                );
+                Ok(access.token)
+            }
+        }
+    }
+
+    /// Create the check run on the head commit of the pull request.
+    pub fn post(&self, check: &CheckRun) -> Result<(), String> {
+        let token = self.token(&check.repo)?;
+        let pr: PullRequest = self
+            .request(
+                "GET",
+                &format!("repos/{}/pulls/{}", check.repo, check.pr),
+                &token,
+            )
+            .call()
+            .map_err(|e| format!("{}#{}: {}", check.repo, check.pr, e))?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+        self.request("POST", &format!("repos/{}/check-runs", check.repo), &token)
+            .send_json(serde_json::json!({
+                "name": check.name,
+                "head_sha": pr.head.sha,
+                "status": "completed",
+                // The anomalies are not necessarily failures.
+                "conclusion": "neutral",
+                "details_url": check.details_url,
+                "output": {
+                    "title": check.title,
+                    "summary": check.summary,
+                },
+            }))
+            .map(|_| ())
+            .map_err(|e| format!("{}#{}: check run failed: {}", check.repo, check.pr, e))
+    }
+}
+
+#[test]
+fn test_prow_repository() {
+    assert_eq!(
+        prow_repository("openstack-k8s-operators_ci-framework"),
+        Some("openstack-k8s-operators/ci-framework".into())
+    );
+    assert_eq!(
+        prow_repository("org_repo_name"),
+        Some("org/repo_name".into())
+    );
+    assert_eq!(prow_repository("tbd"), None);
+}
+
+#[test]
+fn test_github_settings() {
+    let vars = |vars: &'static [(&str, &str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        }
+    };
+    assert!(GitHub::from_vars(vars(&[])).unwrap().is_none());
+    assert!(GitHub::from_vars(vars(&[("LOGJUICER_GITHUB_APP_ID", "42")])).is_err());
+    assert!(GitHub::from_vars(vars(&[
+        ("LOGJUICER_GITHUB_APP_ID", "42"),
+        ("LOGJUICER_GITHUB_APP_KEY", "/missing/key.pem"),
+        ("LOGJUICER_PUBLIC_URL", "https://logjuicer.example.com"),
+    ]))
+    .is_err());
+    assert!(read_key(b"not a key").is_err());
+}
@@ -0,0 +1,102 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the model storage logic.
+//!
+//! The models are trained with the `logjuicer train` command and uploaded by the users,
+//! so that the agents can analyze their logs without shipping them to the service.
+//! The models content hash is computed from the served file to support conditional requests.
+//! The models are also kept in the object storage, when it is enabled.
+
+use sha2::{Digest, Sha256};
+use std::io::Seek;
+use tokio::fs::File;
+
+use logjuicer_model::{FeaturesMatrix, Model};
+
+/// Check that a model name does not escape the models directory.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Get the model path, making sure the name does not escape the models directory.
+fn model_path(name: &str) -> Option<String> {
+    if is_valid_name(name) {
+        Some(format!("data/models/{}.bin", name))
+    } else {
+        None
+    }
+}
+
+/// Validate and store a model, returning its content hash.
+pub fn save_model(name: &str, data: &[u8]) -> Result<String, String> {
+    let path = model_path(name).ok_or_else(|| format!("Invalid model name: {}", name))?;
+    Model::<FeaturesMatrix>::check_reader(data).map_err(|e| format!("Invalid model: {:#}", e))?;
+    std::fs::create_dir_all("data/models").map_err(|e| format!("Storage error: {}", e))?;
+    let hash = format!("{:x}", Sha256::digest(data));
+    // Write to a temporary file first so that a model is never read half written.
+    let tmp_path = format!("{}.tmp", path);
+    std::fs::write(&tmp_path, data)
+        .and_then(|()| std::fs::rename(&tmp_path, &path))
+        .map_err(|e| format!("Storage error: {}", e))?;
+    crate::storage::upload(&path).map_err(|e| format!("Storage error: {}", e))?;
+    Ok(hash)
+}
+
+/// Download a model missing from the local directory, returning true when the model is available.
+fn restore_model(path: &str) -> bool {
+    crate::storage::restore(path)
+}
+
+/// Check if a model is stored.
+pub async fn model_exists(name: &str) -> bool {
+    match model_path(name) {
+        Some(path) => tokio::task::spawn_blocking(move || restore_model(&path))
+            .await
+            .unwrap_or(false),
+        None => false,
+    }
+}
+
+/// Open a stored model, returning its content hash.
+pub async fn open_model(name: &str) -> Option<(File, String)> {
+    let path = model_path(name)?;
+    tokio::task::spawn_blocking(move || {
+        if !restore_model(&path) {
+            return None;
+        }
+        // The hash is read from the opened file, so that it always matches the served content,
+        // even when the model is replaced concurrently.
+        let mut file = std::fs::File::open(&path).ok()?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher).ok()?;
+        file.rewind().ok()?;
+        Some((File::from_std(file), format!("{:x}", hasher.finalize())))
+    })
+    .await
+    .ok()?
+}
+
+/// Load a stored model.
+pub fn load_model(name: &str) -> Result<Model<FeaturesMatrix>, String> {
+    let path = model_path(name).ok_or_else(|| format!("Invalid model name: {}", name))?;
+    if !restore_model(&path) {
+        return Err(format!("Model not found: {}", name));
+    }
+    Model::load(std::path::Path::new(&path)).map_err(|e| format!("Invalid model: {:#}", e))
+}
+
+#[test]
+fn test_model_path() {
+    assert_eq!(
+        model_path("tox-py311"),
+        Some("data/models/tox-py311.bin".into())
+    );
+    assert_eq!(model_path("../logjuicer.sqlite"), None);
+    assert_eq!(model_path(".hidden"), None);
+    assert_eq!(model_path(""), None);
+}
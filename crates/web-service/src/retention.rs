@@ -0,0 +1,192 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the retention policy of the reports.
+//!
+//! The policy is configured with the `LOGJUICER_RETENTION_DAYS`, `LOGJUICER_RETENTION_COUNT` and
+//! `LOGJUICER_RETENTION_BYTES` environment variables, and it runs every `LOGJUICER_RETENTION_INTERVAL` seconds.
+//! The oldest finished reports exceeding one of the limits are removed, unless they are pinned.
+
+use sqlx::types::chrono::{NaiveDateTime, Utc};
+use std::time::Duration;
+
+use logjuicer_report::report_row::ReportID;
+
+use crate::transcode;
+use crate::worker::Workers;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Retention {
+    /// The maximum age of a report, in days.
+    pub max_age_days: Option<u64>,
+    /// The maximum number of reports.
+    pub max_count: Option<usize>,
+    /// The maximum size of the stored reports.
+    pub max_bytes: Option<u64>,
+    /// The duration between two runs.
+    pub interval: Duration,
+}
+
+/// A report that can be removed.
+struct Candidate {
+    id: ReportID,
+    updated_at: NaiveDateTime,
+    size: u64,
+}
+
+impl Retention {
+    /// Read the policy from the environment.
+    pub fn from_env() -> Result<Retention, String> {
+        Retention::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Result<Retention, String> {
+        let read = |name: &str| -> Result<Option<u64>, String> {
+            get(name)
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|e| format!("{}: invalid value {:?}: {}", name, value, e))
+                })
+                .transpose()
+        };
+        let interval = read("LOGJUICER_RETENTION_INTERVAL")?.unwrap_or(3600);
+        if interval == 0 {
+            return Err("LOGJUICER_RETENTION_INTERVAL: the interval can't be zero".into());
+        }
+        Ok(Retention {
+            max_age_days: read("LOGJUICER_RETENTION_DAYS")?,
+            max_count: read("LOGJUICER_RETENTION_COUNT")?.map(|v| v as usize),
+            max_bytes: read("LOGJUICER_RETENTION_BYTES")?,
+            interval: Duration::from_secs(interval),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_days.is_some() || self.max_count.is_some() || self.max_bytes.is_some()
+    }
+
+    /// Select the reports to remove, the candidates are sorted from the most recent.
+    fn select(&self, candidates: &[Candidate], now: NaiveDateTime) -> Vec<ReportID> {
+        let mut kept_count = 0;
+        let mut kept_bytes = 0;
+        candidates
+            .iter()
+            .filter(|candidate| {
+                let expired = self
+                    .max_age_days
+                    .is_some_and(|days| (now - candidate.updated_at).num_days() >= days as i64);
+                let too_many = self.max_count.is_some_and(|max| kept_count >= max);
+                let too_big = self
+                    .max_bytes
+                    .is_some_and(|max| kept_bytes + candidate.size > max);
+                if expired || too_many || too_big {
+                    true
+                } else {
+                    kept_count += 1;
+                    kept_bytes += candidate.size;
+                    false
+                }
+            })
+            .map(|candidate| candidate.id)
+            .collect()
+    }
+
+    /// Remove the reports exceeding the limits.
+    pub async fn collect(&self, workers: &Workers) -> Result<usize, String> {
+        let reports = workers
+            .db
+            .get_unpinned_reports()
+            .await
+            .map_err(|e| e.to_string())?;
+        let candidates = tokio::task::spawn_blocking(move || {
+            reports
+                .into_iter()
+                .map(|(id, updated_at)| Candidate {
+                    id,
+                    updated_at,
+                    size: transcode::report_size(id),
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+        let selected = self.select(&candidates, Utc::now().naive_utc());
+        for report_id in &selected {
+            tracing::info!(report_id = report_id.0, "Removing an expired report");
+            workers
+                .db
+                .delete_report(*report_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            let report_id = *report_id;
+            tokio::task::spawn_blocking(move || transcode::remove_report(report_id))
+                .await
+                .map_err(|e| e.to_string())?
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(selected.len())
+    }
+
+    /// Apply the policy periodically.
+    pub async fn run(self, workers: Workers) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            match self.collect(&workers).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("Removed {} reports", count),
+                Err(err) => tracing::error!("Report retention failed: {}", err),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_retention() {
+    let now = Utc::now().naive_utc();
+    let candidate = |id: i64, days: i64, size: u64| Candidate {
+        id: ReportID(id),
+        updated_at: now - chrono::Duration::days(days),
+        size,
+    };
+    let candidates = [
+        candidate(4, 0, 10),
+        candidate(3, 1, 10),
+        candidate(2, 10, 10),
+        candidate(1, 40, 10),
+    ];
+    let ids = |retention: Retention| {
+        retention
+            .select(&candidates, now)
+            .into_iter()
+            .map(|id| id.0)
+            .collect::<Vec<_>>()
+    };
+    assert!(ids(Retention::default()).is_empty());
+    let by_age = Retention {
+        max_age_days: Some(30),
+        ..Retention::default()
+    };
+    assert_eq!(ids(by_age), vec![1]);
+    let by_count = Retention {
+        max_count: Some(2),
+        ..Retention::default()
+    };
+    assert_eq!(ids(by_count), vec![2, 1]);
+    let by_size = Retention {
+        max_bytes: Some(35),
+        ..Retention::default()
+    };
+    assert_eq!(ids(by_size), vec![1]);
+
+    let retention = Retention::from_vars(|name| match name {
+        "LOGJUICER_RETENTION_DAYS" => Some("30".into()),
+        _ => None,
+    })
+    .unwrap();
+    assert!(retention.is_enabled());
+    assert_eq!(retention.interval, Duration::from_secs(3600));
+    assert!(Retention::from_vars(|_| Some("forever".into())).is_err());
+}
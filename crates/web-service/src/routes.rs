@@ -3,16 +3,23 @@
 
 //! This module contains the http handler logic.
 
-use axum::extract::{Path, Query, State, WebSocketUpgrade};
-use axum::http::StatusCode;
+use axum::body::Bytes;
+use axum::extract::{Extension, Path, Query, State, WebSocketUpgrade};
+use axum::http::{header, HeaderMap, StatusCode};
 use axum::response::Json;
 use futures::TryFutureExt;
 use hyper::Body;
-use tokio::fs::File;
-use tokio_util::codec::{BytesCodec, FramedRead};
+use itertools::Itertools;
+use std::collections::BTreeMap;
 
-use logjuicer_report::report_row::{ReportID, ReportRow, ReportStatus};
+use logjuicer_model::process::LinePreview;
+use logjuicer_report::report_row::{ReportID, ReportRow, ReportStatus, SearchResult};
+use logjuicer_report::Report;
 
+use crate::auth::User;
+use crate::database::{report_anomalies, BatchReport, ReportFilter, ReportSort};
+use crate::models;
+use crate::transcode::{self, Encoding};
 use crate::worker::Workers;
 
 type Error = (StatusCode, String);
@@ -26,23 +33,141 @@ fn handle_db_error(err: sqlx::Error) -> Error {
     )
 }
 
-pub async fn reports_list(State(workers): State<Workers>) -> Result<Json<Vec<ReportRow>>> {
-    let reports = workers.db.get_reports().await.map_err(handle_db_error)?;
-    Ok(Json(reports))
+/// The report list parameters.
+#[derive(Deserialize, Default)]
+pub struct ReportsQuery {
+    status: Option<String>,
+    target: Option<String>,
+    /// The creation date range, as `YYYY-MM-DD`, both included.
+    since: Option<String>,
+    until: Option<String>,
+    min_anomaly: Option<i64>,
+    max_anomaly: Option<i64>,
+    sort: Option<ReportSort>,
+    /// The sort order, `asc` or `desc`.
+    order: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// The maximum number of reports of a page.
+const MAX_PAGE_SIZE: i64 = 1000;
+
+impl ReportsQuery {
+    fn filter(self) -> std::result::Result<ReportFilter, String> {
+        let date = |name: &str, value: Option<String>| {
+            value
+                .map(|value| {
+                    sqlx::types::chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                        .map_err(|e| format!("Invalid {} date {}: {}", name, value, e))
+                })
+                .transpose()
+        };
+        if let Some(status) = &self.status {
+            if !["pending", "done", "cancelled", "error"].contains(&status.as_str()) {
+                return Err(format!("Unknown status: {}", status));
+            }
+        }
+        let ascending = match self.order.as_deref() {
+            None | Some("desc") => false,
+            Some("asc") => true,
+            Some(order) => return Err(format!("Unknown order: {}", order)),
+        };
+        let limit = match self.limit {
+            Some(limit) if !(1..=MAX_PAGE_SIZE).contains(&limit) => {
+                return Err(format!("The limit must be between 1 and {}", MAX_PAGE_SIZE))
+            }
+            limit => limit,
+        };
+        Ok(ReportFilter {
+            since: date("since", self.since)?.and_then(|date| date.and_hms_opt(0, 0, 0)),
+            until: date("until", self.until)?
+                .and_then(|date| date.succ_opt())
+                .and_then(|date| date.and_hms_opt(0, 0, 0)),
+            status: self.status,
+            target: self.target.filter(|target| !target.is_empty()),
+            min_anomaly: self.min_anomaly,
+            max_anomaly: self.max_anomaly,
+            sort: self.sort.unwrap_or_default(),
+            ascending,
+            limit,
+            offset: self.offset.unwrap_or(0).max(0),
+        })
+    }
+}
+
+/// List the reports, the total number of matching reports is returned in the `X-Total-Count` header.
+pub async fn reports_list(
+    State(workers): State<Workers>,
+    Query(args): Query<ReportsQuery>,
+) -> Result<([(&'static str, String); 1], Json<Vec<ReportRow>>)> {
+    let filter = args.filter().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let (reports, total) = workers
+        .db
+        .list_reports(&filter)
+        .await
+        .map_err(handle_db_error)?;
+    Ok(([("x-total-count", total.to_string())], Json(reports)))
+}
+
+/// The anomaly search parameters.
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    /// A part of the anomaly line, or a fingerprint.
+    q: String,
+    limit: Option<i64>,
+}
+
+/// The minimum length of a search query.
+const MIN_SEARCH_LENGTH: usize = 3;
+
+/// Search the reports with a matching anomaly.
+pub async fn search(
+    State(workers): State<Workers>,
+    Query(args): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchResult>>> {
+    let query = args.q.trim();
+    if query.chars().count() < MIN_SEARCH_LENGTH {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "The query must be at least {} characters long",
+                MIN_SEARCH_LENGTH
+            ),
+        ));
+    }
+    let limit = args.limit.unwrap_or(100);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("The limit must be between 1 and {}", MAX_PAGE_SIZE),
+        ));
+    }
+    let results = workers
+        .db
+        .search_anomalies(query, limit)
+        .await
+        .map_err(handle_db_error)?;
+    Ok(Json(results))
 }
 
 pub async fn report_get(
     State(workers): State<Workers>,
     Path(report_id): Path<ReportID>,
+    headers: HeaderMap,
 ) -> Result<hyper::Response<Body>> {
-    let fp = format!("data/{}.gz", report_id);
-    if let Ok(file) = File::open(&fp).await {
-        // The file exists, stream its content...
-        let stream = FramedRead::new(file, BytesCodec::new());
-        let body = Body::wrap_stream(stream);
-        Ok(hyper::Response::builder()
-            .header("Content-Encoding", "gzip")
-            .body(body)
+    if let Some((file, stored)) = transcode::open_report(report_id).await {
+        // The file exists, stream its content using the accepted encoding...
+        let accept = headers
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|value| value.to_str().ok());
+        let encoding = Encoding::negotiate(accept, stored);
+        let mut response = hyper::Response::builder();
+        if let Some(name) = encoding.header() {
+            response = response.header(header::CONTENT_ENCODING, name);
+        }
+        Ok(response
+            .body(transcode::report_body(file, stored, encoding))
             .unwrap())
     } else if let Some(status) = workers
         .db
@@ -51,14 +176,22 @@ pub async fn report_get(
         .map_err(handle_db_error)?
     {
         match status {
-            ReportStatus::Pending => Err((
-                StatusCode::NOT_FOUND,
-                "Report is pending, try again later".into(),
-            )),
+            ReportStatus::Pending => match partial_report(&workers, report_id).await? {
+                // The analysis is running, return the anomalies found so far.
+                Some(body) => Ok(hyper::Response::builder()
+                    .header("x-report-partial", "true")
+                    .body(Body::from(body))
+                    .unwrap()),
+                None => Err((
+                    StatusCode::NOT_FOUND,
+                    "Report is pending, try again later".into(),
+                )),
+            },
             ReportStatus::Error(s) => Err((
                 StatusCode::NOT_FOUND,
                 format!("Report creation failed:\n {s}"),
             )),
+            ReportStatus::Cancelled => Err((StatusCode::NOT_FOUND, "Report was cancelled".into())),
             ReportStatus::Completed => {
                 Err((StatusCode::NOT_FOUND, "Report is file is missing".into()))
             }
@@ -68,46 +201,743 @@ pub async fn report_get(
     }
 }
 
+/// Encode the partial report of a running analysis.
+async fn partial_report(workers: &Workers, report_id: ReportID) -> Result<Option<Vec<u8>>> {
+    let monitor = match workers.subscribe(report_id) {
+        Some(monitor) => monitor,
+        None => return Ok(None),
+    };
+    tokio::task::spawn_blocking(move || match monitor.partial_report()? {
+        Some(report) => {
+            let mut body = Vec::new();
+            report.save_writer(&mut body).map_err(|e| e.to_string())?;
+            Ok(Some(body))
+        }
+        None => Ok(None),
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?
+    .map_err(|e: String| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+pub async fn report_categories(
+    Path(report_id): Path<ReportID>,
+) -> Result<Json<BTreeMap<String, usize>>> {
+    tokio::task::spawn_blocking(move || {
+        transcode::load_report(report_id).map(|report| {
+            report
+                .category_breakdown()
+                .into_iter()
+                .map(|(category, count)| (category.to_string(), count))
+                .collect()
+        })
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?
+    .map(Json)
+    .map_err(|e| (StatusCode::NOT_FOUND, format!("Report error: {}", e)))
+}
+
 use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize)]
 pub struct NewReportQuery {
     target: String,
     baseline: Option<String>,
+    /// The extra targets, separated by spaces, to combine their reports with the target report.
+    targets: Option<String>,
+    /// The run limits, they can only lower the service limits.
+    max_bytes: Option<u64>,
+    max_sources: Option<usize>,
+    max_file_bytes: Option<u64>,
+    timeout_secs: Option<u64>,
+}
+
+impl NewReportQuery {
+    /// All the report targets, separated by spaces.
+    fn targets(&self) -> String {
+        match &self.targets {
+            Some(targets) => std::iter::once(self.target.as_str())
+                .chain(targets.split_whitespace())
+                .join(" "),
+            None => self.target.clone(),
+        }
+    }
+
+    fn limits(&self) -> logjuicer_model::config::Limits {
+        logjuicer_model::config::Limits {
+            max_bytes: self.max_bytes,
+            max_sources: self.max_sources,
+            max_file_bytes: self.max_file_bytes,
+            timeout_secs: self.timeout_secs,
+            ..Default::default()
+        }
+    }
+}
+
+/// Read the optional Idempotency-Key header, used by the clients to safely retry a submission.
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<&str>> {
+    match headers.get("idempotency-key").map(|value| value.to_str()) {
+        None => Ok(None),
+        Some(Ok(key)) if !key.is_empty() && key.len() <= 255 => Ok(Some(key)),
+        Some(_) => Err((
+            StatusCode::BAD_REQUEST,
+            "Invalid Idempotency-Key header".into(),
+        )),
+    }
+}
+
+/// Lookup the report created by a previous submission using the same key.
+async fn replay_submission(
+    workers: &Workers,
+    key: Option<&str>,
+) -> Result<Option<(ReportID, ReportStatus)>> {
+    match key {
+        Some(key) => {
+            let report = workers
+                .db
+                .lookup_idempotency_key(key)
+                .await
+                .map_err(handle_db_error)?;
+            if report.is_some() {
+                tracing::info!(key, "Replaying a submission");
+            }
+            Ok(report)
+        }
+        None => Ok(None),
+    }
+}
+
+/// Create a new report, handling the concurrent submissions using the same key.
+async fn initialize_report(
+    workers: &Workers,
+    target: &str,
+    baseline: &str,
+    key: Option<&str>,
+    user: &User,
+) -> Result<std::result::Result<ReportID, (ReportID, ReportStatus)>> {
+    match workers
+        .db
+        .initialize_report(target, baseline, key, user.id.as_deref(), None)
+        .await
+    {
+        Ok(report_id) => Ok(Ok(report_id)),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            match replay_submission(workers, key).await? {
+                Some(report) => Ok(Err(report)),
+                None => Err((
+                    StatusCode::CONFLICT,
+                    "Concurrent submission, try again later".into(),
+                )),
+            }
+        }
+        Err(err) => Err(handle_db_error(err)),
+    }
 }
 
 pub async fn report_new(
     State(workers): State<Workers>,
+    Extension(user): Extension<User>,
     Query(args): Query<NewReportQuery>,
+    headers: HeaderMap,
 ) -> Result<Json<(ReportID, ReportStatus)>> {
+    let key = idempotency_key(&headers)?;
+    if let Some(report) = replay_submission(&workers, key).await? {
+        return Ok(Json(report));
+    }
     let baseline = args.baseline.as_deref().unwrap_or("auto");
+    let target = args.targets();
     let report = workers
         .db
-        .lookup_report(&args.target, baseline)
+        .lookup_report(&target, baseline)
         .await
         .map_err(handle_db_error)?;
     match report {
         Some(report) => Ok(Json(report)),
         None => {
-            tracing::info!(target = args.target, "Creating a new report");
-            let report_id = workers
+            workers
+                .check_capacity(1)
+                .map_err(|e| (StatusCode::TOO_MANY_REQUESTS, e))?;
+            tracing::info!(target = target, "Creating a new report");
+            let report_id = match initialize_report(&workers, &target, baseline, key, &user).await?
+            {
+                Ok(report_id) => report_id,
+                Err(report) => return Ok(Json(report)),
+            };
+            workers.submit(report_id, &target, args.baseline.as_deref(), &args.limits());
+            Ok(Json((report_id, ReportStatus::Pending)))
+        }
+    }
+}
+
+/// The reports to create together.
+#[derive(Deserialize)]
+pub struct BatchRequest {
+    targets: Vec<String>,
+    baseline: Option<String>,
+    /// The run limits of every report, they can only lower the service limits.
+    max_bytes: Option<u64>,
+    max_sources: Option<usize>,
+    max_file_bytes: Option<u64>,
+    timeout_secs: Option<u64>,
+}
+
+/// The maximum number of reports of a batch.
+const MAX_BATCH_SIZE: usize = 100;
+
+impl BatchRequest {
+    /// The distinct targets, in the submission order.
+    fn targets(&self) -> std::result::Result<Vec<String>, String> {
+        let targets: Vec<String> = self
+            .targets
+            .iter()
+            .map(|target| target.trim())
+            .filter(|target| !target.is_empty())
+            .unique()
+            .map(String::from)
+            .collect();
+        if targets.is_empty() {
+            Err("The batch needs at least one target".into())
+        } else if targets.len() > MAX_BATCH_SIZE {
+            Err(format!(
+                "The batch can't contain more than {} targets",
+                MAX_BATCH_SIZE
+            ))
+        } else {
+            Ok(targets)
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BatchCreated {
+    batch_id: i64,
+    reports: Vec<ReportID>,
+}
+
+/// Create the reports of several targets at once, either all of them are created or none.
+pub async fn report_batch(
+    State(workers): State<Workers>,
+    Extension(user): Extension<User>,
+    Json(args): Json<BatchRequest>,
+) -> Result<Json<BatchCreated>> {
+    let targets = args.targets().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    workers
+        .check_capacity(targets.len())
+        .map_err(|e| (StatusCode::TOO_MANY_REQUESTS, e))?;
+    let baseline = args.baseline.as_deref().unwrap_or("auto");
+    let (batch_id, reports) = workers
+        .db
+        .create_batch(&targets, baseline, user.id.as_deref())
+        .await
+        .map_err(handle_db_error)?;
+    tracing::info!(batch_id, "Creating a batch of {} reports", reports.len());
+    let limits = logjuicer_model::config::Limits {
+        max_bytes: args.max_bytes,
+        max_sources: args.max_sources,
+        max_file_bytes: args.max_file_bytes,
+        timeout_secs: args.timeout_secs,
+        ..Default::default()
+    };
+    for (report_id, target) in reports.iter().zip(&targets) {
+        workers.submit(*report_id, target, args.baseline.as_deref(), &limits);
+    }
+    Ok(Json(BatchCreated { batch_id, reports }))
+}
+
+/// The aggregate status of a batch.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BatchSummary {
+    /// True when all the reports are finished.
+    done: bool,
+    pending: usize,
+    completed: usize,
+    cancelled: usize,
+    failed: usize,
+    anomaly_count: i64,
+}
+
+impl BatchSummary {
+    fn new(reports: &[BatchReport]) -> BatchSummary {
+        let mut summary = BatchSummary::default();
+        for report in reports {
+            match report.status {
+                ReportStatus::Pending => summary.pending += 1,
+                ReportStatus::Completed => summary.completed += 1,
+                ReportStatus::Cancelled => summary.cancelled += 1,
+                ReportStatus::Error(_) => summary.failed += 1,
+            }
+            summary.anomaly_count += report.anomaly_count;
+        }
+        summary.done = summary.pending == 0;
+        summary
+    }
+}
+
+#[derive(Serialize)]
+pub struct BatchStatus {
+    batch_id: i64,
+    created_at: sqlx::types::chrono::NaiveDateTime,
+    owner: Option<String>,
+    #[serde(flatten)]
+    summary: BatchSummary,
+    reports: Vec<BatchReport>,
+}
+
+/// Get the status of the reports of a batch.
+pub async fn batch_get(
+    State(workers): State<Workers>,
+    Path(batch_id): Path<i64>,
+) -> Result<Json<BatchStatus>> {
+    let batch = workers
+        .db
+        .get_batch(batch_id)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Batch not found".to_string()))?;
+    Ok(Json(BatchStatus {
+        batch_id,
+        created_at: batch.created_at,
+        owner: batch.owner,
+        summary: BatchSummary::new(&batch.reports),
+        reports: batch.reports,
+    }))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RerunReportQuery {
+    /// The new baseline, the original baseline is used by default.
+    baseline: Option<String>,
+    /// The run limits, they can only lower the service limits.
+    max_bytes: Option<u64>,
+    max_sources: Option<usize>,
+    max_file_bytes: Option<u64>,
+    timeout_secs: Option<u64>,
+}
+
+/// Run a finished report again, creating a new report linked to the original one.
+pub async fn report_rerun(
+    State(workers): State<Workers>,
+    Extension(user): Extension<User>,
+    Path(report_id): Path<ReportID>,
+    Query(args): Query<RerunReportQuery>,
+) -> Result<Json<(ReportID, ReportStatus)>> {
+    let source = workers
+        .db
+        .get_report_source(report_id)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Report not found".to_string()))?;
+    if !user.can_manage(source.owner.as_deref()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only the report owner or an admin can re-run it".into(),
+        ));
+    }
+    if source.status == ReportStatus::Pending {
+        return Err((StatusCode::CONFLICT, "The report is still running".into()));
+    }
+    let baseline = args.baseline.unwrap_or(source.baseline);
+    if baseline.starts_with("model:") {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "The uploaded reports can't be re-run".into(),
+        ));
+    }
+    workers
+        .check_capacity(1)
+        .map_err(|e| (StatusCode::TOO_MANY_REQUESTS, e))?;
+    tracing::info!(target = source.target, "Re-running report {}", report_id);
+    let new_id = workers
+        .db
+        .initialize_report(
+            &source.target,
+            &baseline,
+            None,
+            user.id.as_deref(),
+            Some(report_id),
+        )
+        .await
+        .map_err(handle_db_error)?;
+    let limits = logjuicer_model::config::Limits {
+        max_bytes: args.max_bytes,
+        max_sources: args.max_sources,
+        max_file_bytes: args.max_file_bytes,
+        timeout_secs: args.timeout_secs,
+        ..Default::default()
+    };
+    let baseline = Some(baseline.as_str()).filter(|baseline| *baseline != "auto");
+    workers.submit(new_id, &source.target, baseline, &limits);
+    Ok(Json((new_id, ReportStatus::Pending)))
+}
+
+type HealthChecks = BTreeMap<&'static str, std::result::Result<String, String>>;
+
+/// Render the health checks, the service is unavailable when one of them failed.
+fn health_response(checks: HealthChecks) -> (StatusCode, Json<BTreeMap<&'static str, String>>) {
+    let status = if checks.values().all(|check| check.is_ok()) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let checks = checks
+        .into_iter()
+        .map(|(name, check)| match check {
+            Ok(msg) => (name, msg),
+            Err(err) => (name, format!("error: {}", err)),
+        })
+        .collect();
+    (status, Json(checks))
+}
+
+/// Check that the data directory is writable.
+fn check_data_dir() -> std::result::Result<String, String> {
+    let path = format!("data/.healthz-{}", std::process::id());
+    std::fs::write(&path, b"ok")
+        .and_then(|_| std::fs::remove_file(&path))
+        .map(|_| "writable".into())
+        .map_err(|e| e.to_string())
+}
+
+/// The liveness probe, checking that the worker pool can process the reports.
+pub async fn healthz(
+    State(workers): State<Workers>,
+) -> (StatusCode, Json<BTreeMap<&'static str, String>>) {
+    health_response(BTreeMap::from([("workers", workers.pool_status())]))
+}
+
+/// The readiness probe, checking the database, the data directory and the worker pool.
+pub async fn readyz(
+    State(workers): State<Workers>,
+) -> (StatusCode, Json<BTreeMap<&'static str, String>>) {
+    let database = workers
+        .db
+        .ping()
+        .await
+        .map(|_| "connected".into())
+        .map_err(|e| e.to_string());
+    health_response(BTreeMap::from([
+        ("database", database),
+        ("data", check_data_dir()),
+        ("workers", workers.pool_status()),
+    ]))
+}
+
+pub async fn report_estimate(
+    State(workers): State<Workers>,
+    Query(args): Query<NewReportQuery>,
+) -> Result<Json<logjuicer_model::estimate::Estimate>> {
+    workers
+        .estimate(args.targets(), args.baseline)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UploadReportQuery {
+    target: String,
+    model: String,
+}
+
+pub async fn report_upload(
+    State(workers): State<Workers>,
+    Extension(user): Extension<User>,
+    Query(args): Query<UploadReportQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<(ReportID, ReportStatus)>> {
+    let key = idempotency_key(&headers)?;
+    if let Some(report) = replay_submission(&workers, key).await? {
+        return Ok(Json(report));
+    }
+    // The uploaded report is redacted like the processed ones, the agent may not have the same rules.
+    let redactor = workers.redactor().clone();
+    let load_report = move |body: &[u8]| {
+        Report::load_bytes(body).map(|mut report| {
+            redactor.apply(&mut report);
+            report
+        })
+    };
+    // The report can't be held across the awaits, it is decoded again to be saved.
+    let (count, anomalies) = load_report(&body)
+        .map(|report| (report.anomaly_count(), report_anomalies(&report)))
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid report: {}", e)))?;
+    let baseline = format!("model:{}", args.model);
+    tracing::info!(target = args.target, baseline, "Uploading a report");
+    let report_id = match initialize_report(&workers, &args.target, &baseline, key, &user).await? {
+        Ok(report_id) => report_id,
+        Err(report) => return Ok(Json(report)),
+    };
+    let status = tokio::task::spawn_blocking(move || {
+        match load_report(&body).and_then(|report| transcode::save_report(report_id, &report)) {
+            Ok(()) => ReportStatus::Completed,
+            Err(err) => ReportStatus::Error(format!("Save error: {}", err)),
+        }
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+    workers
+        .db
+        .update_report(report_id, count, &status)
+        .await
+        .map_err(handle_db_error)?;
+    if status == ReportStatus::Completed {
+        workers
+            .db
+            .insert_anomalies(report_id, &anomalies)
+            .await
+            .map_err(handle_db_error)?;
+    }
+    Ok(Json((report_id, status)))
+}
+
+/// Delete a report, only its owner or an admin can delete it.
+pub async fn report_delete(
+    State(workers): State<Workers>,
+    Extension(user): Extension<User>,
+    Path(report_id): Path<ReportID>,
+) -> Result<StatusCode> {
+    let (owner, status) = workers
+        .db
+        .get_report_owner(report_id)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Report not found".to_string()))?;
+    if !user.can_manage(owner.as_deref()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only the report owner or an admin can delete it".into(),
+        ));
+    }
+    if status == ReportStatus::Pending {
+        return Err((StatusCode::CONFLICT, "The report is running".into()));
+    }
+    tracing::info!(report_id = report_id.0, "Deleting a report");
+    workers
+        .db
+        .delete_report(report_id)
+        .await
+        .map_err(handle_db_error)?;
+    tokio::task::spawn_blocking(move || transcode::remove_report(report_id))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Storage error: {}", e),
+            )
+        })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn set_pinned(
+    workers: &Workers,
+    user: &User,
+    report_id: ReportID,
+    pinned: bool,
+) -> Result<StatusCode> {
+    let (owner, _) = workers
+        .db
+        .get_report_owner(report_id)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Report not found".to_string()))?;
+    if !user.can_manage(owner.as_deref()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only the report owner or an admin can pin it".into(),
+        ));
+    }
+    workers
+        .db
+        .set_pinned(report_id, pinned)
+        .await
+        .map_err(handle_db_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Exempt a report from the retention policy.
+pub async fn report_pin(
+    State(workers): State<Workers>,
+    Extension(user): Extension<User>,
+    Path(report_id): Path<ReportID>,
+) -> Result<StatusCode> {
+    set_pinned(&workers, &user, report_id, true).await
+}
+
+pub async fn report_unpin(
+    State(workers): State<Workers>,
+    Extension(user): Extension<User>,
+    Path(report_id): Path<ReportID>,
+) -> Result<StatusCode> {
+    set_pinned(&workers, &user, report_id, false).await
+}
+
+/// Cancel a running report, only its owner or an admin can cancel it.
+pub async fn report_cancel(
+    State(workers): State<Workers>,
+    Extension(user): Extension<User>,
+    Path(report_id): Path<ReportID>,
+) -> Result<StatusCode> {
+    let (owner, _) = workers
+        .db
+        .get_report_owner(report_id)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Report not found".to_string()))?;
+    if !user.can_manage(owner.as_deref()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only the report owner or an admin can cancel it".into(),
+        ));
+    }
+    if workers.cancel(report_id) {
+        tracing::info!(report_id = report_id.0, "Cancelling a report");
+        // The worker marks the report as cancelled when it stops.
+        Ok(StatusCode::ACCEPTED)
+    } else {
+        Err((StatusCode::CONFLICT, "The report is not running".into()))
+    }
+}
+
+pub async fn model_blob(
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> Result<hyper::Response<Body>> {
+    match models::open_model(&name).await {
+        Some((file, hash)) => {
+            // The content hash is used as the entity tag, so that clients can revalidate their cache.
+            let etag = format!("\"{}\"", hash);
+            let response = hyper::Response::builder().header(header::ETAG, &etag);
+            let is_cached = headers
+                .get(header::IF_NONE_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| {
+                    value
+                        .split(',')
+                        .any(|tag| tag.trim() == etag || tag.trim() == "*")
+                });
+            Ok(if is_cached {
+                response
+                    .status(StatusCode::NOT_MODIFIED)
+                    .body(Body::empty())
+                    .unwrap()
+            } else {
+                let response = match file.metadata().await {
+                    Ok(metadata) => response.header(header::CONTENT_LENGTH, metadata.len()),
+                    Err(_) => response,
+                };
+                response
+                    .header(header::CONTENT_TYPE, "application/octet-stream")
+                    .body(transcode::file_body(file))
+                    .unwrap()
+            })
+        }
+        None => Err((StatusCode::NOT_FOUND, "Model Not Found".into())),
+    }
+}
+
+/// Upload a model, only its owner or an admin can replace it.
+pub async fn model_upload(
+    State(workers): State<Workers>,
+    Extension(user): Extension<User>,
+    Path(name): Path<String>,
+    body: Bytes,
+) -> Result<(StatusCode, String)> {
+    if !models::is_valid_name(&name) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid model name: {}", name),
+        ));
+    }
+    let owner = match workers
+        .db
+        .get_model_owner(&name)
+        .await
+        .map_err(handle_db_error)?
+    {
+        Some(owner) => owner,
+        None => {
+            // The models stored before their owners were recorded are managed by the admins.
+            let owner = if models::model_exists(&name).await {
+                None
+            } else {
+                user.id.as_deref()
+            };
+            workers
                 .db
-                .initialize_report(&args.target, baseline)
+                .claim_model(&name, owner)
                 .await
                 .map_err(handle_db_error)?;
-            workers.submit(report_id, &args.target, args.baseline.as_deref());
-            Ok(Json((report_id, ReportStatus::Pending)))
+            workers
+                .db
+                .get_model_owner(&name)
+                .await
+                .map_err(handle_db_error)?
+                .flatten()
         }
+    };
+    if !user.can_manage(owner.as_deref()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only the model owner or an admin can replace it".into(),
+        ));
+    }
+    let hash = tokio::task::spawn_blocking(move || models::save_model(&name, &body))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    Ok((StatusCode::CREATED, hash))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PreviewQuery {
+    line: String,
+    /// The source path, used to select the index and the category policy.
+    path: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PreviewResponse {
+    index: String,
+    #[serde(flatten)]
+    preview: LinePreview,
+}
+
+pub async fn model_preview(
+    State(workers): State<Workers>,
+    Path(name): Path<String>,
+    Query(args): Query<PreviewQuery>,
+) -> Result<Json<PreviewResponse>> {
+    let path = args.path.unwrap_or_default();
+    match workers
+        .preview_line(name, path, args.line)
+        .await
+        .map_err(|e| (StatusCode::NOT_FOUND, e))?
+    {
+        Some((index, preview)) => Ok(Json(PreviewResponse { index, preview })),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            "The model does not have an index for this path".into(),
+        )),
     }
 }
 
+#[derive(Deserialize)]
+pub struct WatchQuery {
+    /// The sequence number of the last received event, to resume a watch.
+    after: Option<u64>,
+}
+
 pub async fn report_watch(
     ws: WebSocketUpgrade,
     Path(report_id): Path<ReportID>,
+    Query(args): Query<WatchQuery>,
     State(workers): State<Workers>,
 ) -> Result<axum::response::Response> {
     match workers.subscribe(report_id) {
         Some(monitor) => Ok(ws.on_upgrade(move |socket| {
-            do_report_watch(monitor, socket)
+            do_report_watch(monitor, socket, args.after.map_or(0, |seq| seq + 1))
                 .unwrap_or_else(|err| tracing::warn!("websocket handler error: {}", err))
         })),
         None => Err((
@@ -118,28 +948,47 @@ pub async fn report_watch(
 }
 
 use axum::extract::ws::{Message, WebSocket};
+/// Send the events of a report, starting at the `next` sequence number.
 pub async fn do_report_watch(
     monitor: crate::worker::ProcessMonitor,
     mut ws: WebSocket,
+    mut next: u64,
 ) -> std::result::Result<(), axum::Error> {
+    use tokio::sync::broadcast::error::RecvError;
+    // Subscribe before reading the stored events, so that none is missed.
     let mut monitor_rx = monitor.chan.subscribe();
-    {
-        let events = monitor.events.read().await;
-        if events.is_empty() {
-            ws.send("Waiting to start...".into()).await?;
-        } else {
-            // Send previous events
-            for event in events.iter() {
-                ws.send(Message::Text(event.to_string())).await?;
+    let events = monitor.events.clone();
+    // Release the sender, so that the channel closes when the report is finished.
+    drop(monitor);
+    loop {
+        // Replay the stored events, this also catches up the live events missed by a lagging receiver.
+        let replay: Vec<_> = events
+            .read()
+            .await
+            .iter()
+            .skip(next as usize)
+            .cloned()
+            .collect();
+        for event in replay {
+            ws.send(Message::Text(event.to_string())).await?;
+            next += 1;
+        }
+        loop {
+            match monitor_rx.recv().await {
+                // The events already replayed are skipped.
+                Ok((seq, _)) if seq < next => {}
+                Ok((seq, msg)) if seq == next => {
+                    ws.send(Message::Text(msg.to_string())).await?;
+                    next += 1;
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => break,
+                Err(RecvError::Closed) => {
+                    ws.close().await?;
+                    return Ok(());
+                }
             }
-        };
-    }
-
-    while let Ok(msg) = monitor_rx.recv().await {
-        ws.send(Message::Text(msg.to_string())).await?;
+        }
     }
-    ws.close().await?;
-    Ok(())
 }
 
 pub fn generate_html(url_base_path: &str, version: &str) -> String {
@@ -152,3 +1001,88 @@ pub fn generate_html(url_base_path: &str, version: &str) -> String {
 </head><body><script type="module">import init from '{url}js?v={version}';init('{url}wasm?v={version}');</script></body></html>"#
     )
 }
+
+#[test]
+fn test_reports_query() {
+    let filter = ReportsQuery {
+        status: Some("error".into()),
+        since: Some("2023-12-01".into()),
+        until: Some("2023-12-31".into()),
+        sort: Some(ReportSort::AnomalyCount),
+        order: Some("asc".into()),
+        limit: Some(50),
+        offset: Some(100),
+        ..Default::default()
+    }
+    .filter()
+    .unwrap();
+    assert_eq!(
+        filter.since.unwrap().to_string(),
+        "2023-12-01 00:00:00".to_string()
+    );
+    assert_eq!(
+        filter.until.unwrap().to_string(),
+        "2024-01-01 00:00:00".to_string()
+    );
+    assert!(filter.ascending);
+    assert_eq!((filter.limit, filter.offset), (Some(50), 100));
+
+    let invalid = |query: ReportsQuery| query.filter().is_err();
+    assert!(invalid(ReportsQuery {
+        status: Some("unknown".into()),
+        ..Default::default()
+    }));
+    assert!(invalid(ReportsQuery {
+        since: Some("yesterday".into()),
+        ..Default::default()
+    }));
+    assert!(invalid(ReportsQuery {
+        limit: Some(0),
+        ..Default::default()
+    }));
+}
+
+#[test]
+fn test_batch() {
+    let request = |targets: Vec<String>| BatchRequest {
+        targets,
+        baseline: None,
+        max_bytes: None,
+        max_sources: None,
+        max_file_bytes: None,
+        timeout_secs: None,
+    };
+    assert_eq!(
+        request(vec!["a".into(), " b ".into(), "".into(), "a".into()]).targets(),
+        Ok(vec!["a".to_string(), "b".to_string()])
+    );
+    assert!(request(vec![" ".into()]).targets().is_err());
+    assert!(
+        request((0..=MAX_BATCH_SIZE).map(|i| i.to_string()).collect())
+            .targets()
+            .is_err()
+    );
+
+    let report = |status: ReportStatus, anomaly_count: i64| BatchReport {
+        id: 1.into(),
+        target: "a".into(),
+        status,
+        anomaly_count,
+    };
+    assert_eq!(
+        BatchSummary::new(&[
+            report(ReportStatus::Completed, 3),
+            report(ReportStatus::Pending, 0),
+            report(ReportStatus::Error("oops".into()), 0),
+        ]),
+        BatchSummary {
+            done: false,
+            pending: 1,
+            completed: 1,
+            cancelled: 0,
+            failed: 1,
+            anomaly_count: 3,
+        }
+    );
+    assert!(BatchSummary::new(&[report(ReportStatus::Cancelled, 0)]).done);
+}
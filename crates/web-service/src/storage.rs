@@ -0,0 +1,156 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the object storage of the reports and the models.
+//!
+//! The files are always written to the local `data/` directory, which acts as a cache.
+//! When the `LOGJUICER_S3_BUCKET` environment variable is set, they are also uploaded to an S3 compatible
+//! object storage, and they are downloaded again when they are missing from the local directory,
+//! for example after the service was moved to a new host.
+//! The objects keys are the files path relative to the data directory, with the optional `LOGJUICER_S3_PREFIX`.
+//! The client is configured with the standard `AWS_*` environment variables, see [s3_bucket::Client::from_env].
+
+use s3_bucket::{Client, ObjectUrl};
+
+/// The local directory of the stored files.
+const DATA_DIR: &str = "data/";
+
+/// An object storage bucket.
+struct Bucket {
+    client: Client,
+    agent: ureq::Agent,
+    bucket: String,
+    prefix: String,
+}
+
+lazy_static::lazy_static! {
+    static ref BUCKET: Option<Bucket> = Bucket::from_env();
+}
+
+/// Setup the object storage, returning true when it is enabled.
+pub fn init() -> bool {
+    BUCKET.is_some()
+}
+
+impl Bucket {
+    fn from_env() -> Option<Bucket> {
+        let bucket = std::env::var("LOGJUICER_S3_BUCKET")
+            .ok()
+            .filter(|bucket| !bucket.is_empty())?;
+        Some(Bucket {
+            client: Client::from_env(),
+            agent: logjuicer_model::env::Env::new().client,
+            bucket,
+            prefix: std::env::var("LOGJUICER_S3_PREFIX").unwrap_or_default(),
+        })
+    }
+
+    /// The object of a local file.
+    fn object(&self, path: &str) -> ObjectUrl {
+        ObjectUrl {
+            bucket: self.bucket.clone(),
+            key: format!(
+                "{}{}",
+                self.prefix,
+                path.strip_prefix(DATA_DIR).unwrap_or(path)
+            ),
+        }
+    }
+
+    fn download(&self, path: &str) -> Result<Option<Vec<u8>>, String> {
+        let request = self
+            .client
+            .object_request(&self.agent, "GET", &self.object(path))
+            .map_err(|e| e.to_string())?;
+        match request.call() {
+            Ok(resp) => {
+                let mut data = Vec::new();
+                std::io::Read::read_to_end(&mut resp.into_reader(), &mut data)
+                    .map_err(|e| e.to_string())?;
+                Ok(Some(data))
+            }
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Upload a local file to the object storage, when it is enabled.
+pub fn upload(path: &str) -> Result<(), String> {
+    match &*BUCKET {
+        Some(bucket) => {
+            let data = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+            bucket
+                .client
+                .put_object(&bucket.agent, &bucket.object(path), &data)
+                .map_err(|e| format!("{}: upload failed: {}", path, e))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Download a file missing from the local directory, returning true when the file is available.
+pub fn restore(path: &str) -> bool {
+    if std::path::Path::new(path).exists() {
+        return true;
+    }
+    let bucket = match &*BUCKET {
+        Some(bucket) => bucket,
+        None => return false,
+    };
+    match bucket.download(path) {
+        Ok(Some(data)) => {
+            // Write to a temporary file first so that a file is never read half written.
+            let tmp_path = format!("{}.{}.tmp", path, std::process::id());
+            let result = std::path::Path::new(path)
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|()| std::fs::write(&tmp_path, data))
+                .and_then(|()| std::fs::rename(&tmp_path, path));
+            match result {
+                Ok(()) => true,
+                Err(err) => {
+                    tracing::error!(path, "Failed to restore the object: {}", err);
+                    false
+                }
+            }
+        }
+        Ok(None) => false,
+        Err(err) => {
+            tracing::error!(path, "Failed to download the object: {}", err);
+            false
+        }
+    }
+}
+
+/// Remove a file from the object storage, when it is enabled.
+pub fn remove(path: &str) -> Result<(), String> {
+    match &*BUCKET {
+        Some(bucket) => bucket
+            .client
+            .delete_object(&bucket.agent, &bucket.object(path))
+            .map_err(|e| format!("{}: delete failed: {}", path, e)),
+        None => Ok(()),
+    }
+}
+
+#[test]
+fn test_object_key() {
+    let bucket = Bucket {
+        client: Client {
+            endpoint: "http://localhost:9000/".parse().unwrap(),
+            region: "us-east-1".into(),
+            credentials: None,
+        },
+        agent: ureq::Agent::new(),
+        bucket: "logjuicer".into(),
+        prefix: "prod/".into(),
+    };
+    assert_eq!(
+        bucket.object("data/models/tox.bin"),
+        ObjectUrl {
+            bucket: "logjuicer".into(),
+            key: "prod/models/tox.bin".into()
+        }
+    );
+}
@@ -0,0 +1,173 @@
+// Copyright (C) 2023 Red Hat
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module contains the report storage encoding logic.
+//!
+//! Reports are stored with zstd, and they are transcoded on the fly to
+//! the encoding accepted by the client, without buffering the whole file.
+
+use async_compression::tokio::bufread::{GzipDecoder, GzipEncoder, ZstdDecoder};
+use hyper::Body;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, BufReader};
+use tokio_util::codec::{BytesCodec, FramedRead};
+
+use logjuicer_report::report_row::ReportID;
+use logjuicer_report::{Compression, Report, DEFAULT_ZSTD_LEVEL};
+
+/// The content encoding of a report stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Identity,
+    Gzip,
+    Zstd,
+}
+
+impl Encoding {
+    /// The Content-Encoding header value.
+    pub fn header(&self) -> Option<&'static str> {
+        match self {
+            Encoding::Identity => None,
+            Encoding::Gzip => Some("gzip"),
+            Encoding::Zstd => Some("zstd"),
+        }
+    }
+
+    /// Pick the encoding to serve a stored report, based on the Accept-Encoding header value.
+    /// The stored encoding is preferred to avoid transcoding, then gzip, then identity.
+    pub fn negotiate(accept: Option<&str>, stored: Encoding) -> Encoding {
+        let accepted: Vec<&str> = accept
+            .unwrap_or("")
+            .split(',')
+            .filter_map(|value| {
+                let mut parts = value.split(';').map(str::trim);
+                let name = parts.next()?;
+                let rejected = parts.any(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .is_some_and(|q| q <= 0.0)
+                });
+                if rejected {
+                    None
+                } else {
+                    Some(name)
+                }
+            })
+            .collect();
+        let is_accepted = |encoding: Encoding| {
+            encoding
+                .header()
+                .is_some_and(|name| accepted.iter().any(|a| *a == name || *a == "*"))
+        };
+        if is_accepted(stored) {
+            stored
+        } else if is_accepted(Encoding::Gzip) {
+            Encoding::Gzip
+        } else {
+            Encoding::Identity
+        }
+    }
+}
+
+fn report_path(report_id: ReportID, encoding: Encoding) -> String {
+    match encoding {
+        Encoding::Zstd => format!("data/{}.zst", report_id),
+        Encoding::Gzip => format!("data/{}.gz", report_id),
+        Encoding::Identity => format!("data/{}.bin", report_id),
+    }
+}
+
+/// Save a report to the local storage, and to the object storage when it is enabled.
+pub fn save_report(report_id: ReportID, report: &Report) -> Result<(), logjuicer_report::Error> {
+    let path = report_path(report_id, Encoding::Zstd);
+    report.save_compressed(
+        std::path::Path::new(&path),
+        Compression::Zstd(DEFAULT_ZSTD_LEVEL),
+    )?;
+    crate::storage::upload(&path)
+        .map_err(|e| logjuicer_report::Error::IOError(std::io::Error::other(e)))
+}
+
+/// Load a stored report, the compression is detected by the report loader.
+pub fn load_report(report_id: ReportID) -> Result<Report, logjuicer_report::Error> {
+    let zstd_path = report_path(report_id, Encoding::Zstd);
+    let path = if crate::storage::restore(&zstd_path) {
+        zstd_path
+    } else {
+        report_path(report_id, Encoding::Gzip)
+    };
+    Report::load(std::path::Path::new(&path))
+}
+
+/// Remove the stored files of a report.
+pub fn remove_report(report_id: ReportID) -> std::io::Result<()> {
+    for encoding in [Encoding::Zstd, Encoding::Gzip, Encoding::Identity] {
+        match std::fs::remove_file(report_path(report_id, encoding)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+    crate::storage::remove(&report_path(report_id, Encoding::Zstd)).map_err(std::io::Error::other)
+}
+
+/// The size of the stored files of a report.
+pub fn report_size(report_id: ReportID) -> u64 {
+    [Encoding::Zstd, Encoding::Gzip, Encoding::Identity]
+        .iter()
+        .filter_map(|encoding| std::fs::metadata(report_path(report_id, *encoding)).ok())
+        .map(|meta| meta.len())
+        .sum()
+}
+
+/// Open a stored report, falling back to the gzip files written by older versions.
+pub async fn open_report(report_id: ReportID) -> Option<(File, Encoding)> {
+    let zstd_path = report_path(report_id, Encoding::Zstd);
+    // Download the report from the object storage when it is missing.
+    let _ = tokio::task::spawn_blocking(move || crate::storage::restore(&zstd_path)).await;
+    for encoding in [Encoding::Zstd, Encoding::Gzip] {
+        if let Ok(file) = File::open(report_path(report_id, encoding)).await {
+            return Some((file, encoding));
+        }
+    }
+    None
+}
+
+/// Create a streaming body for a file.
+pub fn file_body(file: File) -> Body {
+    Body::wrap_stream(FramedRead::new(file, BytesCodec::new()))
+}
+
+/// Create a streaming body that converts the stored file into the requested encoding.
+pub fn report_body(file: File, stored: Encoding, encoding: Encoding) -> Body {
+    if stored == encoding {
+        // No transcoding needed, stream the file content as is.
+        return file_body(file);
+    }
+    let decoded: Box<dyn AsyncRead + Send + Unpin> = match stored {
+        Encoding::Identity => Box::new(file),
+        Encoding::Gzip => Box::new(GzipDecoder::new(BufReader::new(file))),
+        Encoding::Zstd => Box::new(ZstdDecoder::new(BufReader::new(file))),
+    };
+    match encoding {
+        Encoding::Gzip => Body::wrap_stream(FramedRead::new(
+            GzipEncoder::new(BufReader::new(decoded)),
+            BytesCodec::new(),
+        )),
+        _ => Body::wrap_stream(FramedRead::new(decoded, BytesCodec::new())),
+    }
+}
+
+#[test]
+fn test_negotiate() {
+    use Encoding::*;
+    assert_eq!(Encoding::negotiate(None, Zstd), Identity);
+    assert_eq!(Encoding::negotiate(Some("gzip, deflate, br"), Zstd), Gzip);
+    assert_eq!(Encoding::negotiate(Some("gzip, zstd"), Zstd), Zstd);
+    assert_eq!(
+        Encoding::negotiate(Some("zstd;q=0, gzip;q=0.5"), Zstd),
+        Gzip
+    );
+    assert_eq!(Encoding::negotiate(Some("identity"), Gzip), Identity);
+    assert_eq!(Encoding::negotiate(Some("*"), Gzip), Gzip);
+}
@@ -4,13 +4,22 @@
 use itertools::Itertools;
 use std::collections::BTreeMap;
 use std::sync::Arc;
-use std::sync::RwLock;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
 
-use logjuicer_model::env::Env;
-use logjuicer_report::report_row::{ReportID, ReportStatus};
-use logjuicer_report::Report;
+use logjuicer_model::config::Limits;
+use logjuicer_model::env::{CancelToken, Cancellation, Env};
+use logjuicer_model::estimate::Estimate;
+use logjuicer_model::process::LinePreview;
+use logjuicer_model::progress::{Progress, ProgressCounters};
+use logjuicer_report::codec::{ReportDecoder, ReportEncoder};
+use logjuicer_report::redact::Redactor;
+use logjuicer_report::report_row::{ReportEvent, ReportID, ReportPhase, ReportStatus};
+use logjuicer_report::{LogReport, Report};
 
 use crate::database::Db;
+use crate::gerrit::Gerrit;
+use crate::github::GitHub;
 
 #[derive(Clone)]
 pub struct Workers {
@@ -22,19 +31,143 @@ pub struct Workers {
     env: Arc<Env>,
     /// The local database of reports.
     pub db: Db,
+    /// The pool settings.
+    config: WorkersConfig,
+    /// The Gerrit server to comment the reports on.
+    gerrit: Option<Arc<Gerrit>>,
+    /// The GitHub App to create the check runs.
+    github: Option<Arc<GitHub>>,
 }
 
-const MAX_LOGJUICER_PROCESS: usize = 2;
+/// The settings of the worker pool.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkersConfig {
+    /// The number of reports processed at the same time, from `LOGJUICER_WORKERS`.
+    pub pool_size: usize,
+    /// The number of reports waiting for a worker, from `LOGJUICER_MAX_QUEUE`.
+    pub max_queue: usize,
+    /// The maximum duration of a report, the longer ones are cancelled, from `LOGJUICER_REPORT_TIMEOUT` in seconds.
+    pub report_timeout: Duration,
+}
+
+impl Default for WorkersConfig {
+    fn default() -> Self {
+        WorkersConfig {
+            pool_size: 2,
+            max_queue: 32,
+            report_timeout: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl WorkersConfig {
+    /// Read the settings from the environment, using the defaults for the unset variables.
+    pub fn from_env() -> Result<WorkersConfig, String> {
+        WorkersConfig::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Result<WorkersConfig, String> {
+        let read = |name: &str| -> Result<Option<u64>, String> {
+            get(name)
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|e| format!("{}: invalid value {:?}: {}", name, value, e))
+                })
+                .transpose()
+        };
+        let default = WorkersConfig::default();
+        let config = WorkersConfig {
+            pool_size: read("LOGJUICER_WORKERS")?.map_or(default.pool_size, |v| v as usize),
+            max_queue: read("LOGJUICER_MAX_QUEUE")?.map_or(default.max_queue, |v| v as usize),
+            report_timeout: read("LOGJUICER_REPORT_TIMEOUT")?
+                .map_or(default.report_timeout, Duration::from_secs),
+        };
+        if config.pool_size == 0 {
+            Err("LOGJUICER_WORKERS: the pool needs at least one worker".into())
+        } else {
+            Ok(config)
+        }
+    }
+}
+
+/// The number of times a pending report is requeued after a restart, before it is marked as failed.
+const MAX_ATTEMPTS: i64 = 3;
 
 impl Workers {
-    pub async fn new() -> Self {
-        // TODO: requeue pending build
-        Workers {
+    pub async fn new(
+        config: WorkersConfig,
+        gerrit: Option<Gerrit>,
+        github: Option<GitHub>,
+    ) -> Self {
+        tracing::info!(
+            "Using {} workers, {} queued reports and a {}s report timeout",
+            config.pool_size,
+            config.max_queue,
+            config.report_timeout.as_secs()
+        );
+        let workers = Workers {
             db: Db::new().await.unwrap(),
-            pool: threadpool::ThreadPool::new(MAX_LOGJUICER_PROCESS),
+            pool: threadpool::ThreadPool::new(config.pool_size),
             env: Arc::new(Env::new()),
             running: Arc::new(RwLock::new(BTreeMap::new())),
+            config,
+            gerrit: gerrit.map(Arc::new),
+            github: github.map(Arc::new),
+        };
+        workers.requeue_pending().await.unwrap();
+        workers
+    }
+
+    /// Submit the reports that were interrupted by a restart, or mark them as failed after too many attempts.
+    async fn requeue_pending(&self) -> sqlx::Result<()> {
+        for report in self.db.get_pending_reports().await? {
+            let status = if report.baseline.starts_with("model:") {
+                // The upload was interrupted before the report was saved.
+                Some("Upload interrupted".to_string())
+            } else if report.attempts >= MAX_ATTEMPTS {
+                Some(format!(
+                    "The report was interrupted {} times",
+                    report.attempts + 1
+                ))
+            } else {
+                None
+            };
+            match status {
+                Some(err) => {
+                    tracing::warn!(report_id = report.id.0, "Failing pending report: {}", err);
+                    self.db
+                        .update_report(report.id, 0, &ReportStatus::Error(err))
+                        .await?
+                }
+                None => {
+                    tracing::info!(report_id = report.id.0, "Requeuing pending report");
+                    self.db.increment_attempts(report.id).await?;
+                    let baseline = Some(report.baseline.as_str()).filter(|b| *b != "auto");
+                    self.submit(report.id, &report.target, baseline, &Limits::default());
+                }
+            }
         }
+        Ok(())
+    }
+
+    /// Check that new reports can be queued.
+    pub fn check_capacity(&self, count: usize) -> Result<(), String> {
+        let queued = self.pool.queued_count();
+        if queued + count > self.config.max_queue {
+            Err(format!(
+                "The queue is full, {} reports are waiting, try again later",
+                queued
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The secrets redaction of the uploaded reports.
+    pub fn redactor(&self) -> &Redactor {
+        self.env.config.redactor()
     }
 
     pub fn subscribe(&self, report_id: ReportID) -> Option<ProcessMonitor> {
@@ -42,52 +175,227 @@ impl Workers {
         running.get(&report_id).cloned()
     }
 
+    /// Cancel a running report, it stops at the next source or chunk of lines.
+    pub fn cancel(&self, report_id: ReportID) -> bool {
+        match self.running.read().unwrap().get(&report_id) {
+            Some(monitor) => {
+                monitor.cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The state of the worker pool.
+    pub fn pool_status(&self) -> Result<String, String> {
+        let max = self.pool.max_count();
+        if max == 0 {
+            Err("the worker pool is empty".into())
+        } else {
+            Ok(format!(
+                "{}/{} active, {} queued",
+                self.pool.active_count(),
+                max,
+                self.pool.queued_count()
+            ))
+        }
+    }
+
+    /// Update the gauges of the workers and the database, before rendering the metrics.
+    pub async fn collect_metrics(&self) {
+        let active = self.pool.active_count();
+        let max = self.pool.max_count();
+        metrics::gauge!("logjuicer_queue_depth", self.pool.queued_count() as f64);
+        metrics::gauge!("logjuicer_workers_active", active as f64);
+        metrics::gauge!("logjuicer_worker_utilization", active as f64 / max as f64);
+        match self.db.size().await {
+            Ok(size) => metrics::gauge!("logjuicer_database_bytes", size as f64),
+            Err(err) => tracing::error!("Failed to get the database size: {}", err),
+        }
+        match self.db.count_reports().await {
+            Ok(counts) => {
+                let (mut pending, mut done, mut cancelled, mut error) = (0, 0, 0, 0);
+                for (status, count) in counts {
+                    match status {
+                        ReportStatus::Pending => pending += count,
+                        ReportStatus::Completed => done += count,
+                        ReportStatus::Cancelled => cancelled += count,
+                        ReportStatus::Error(_) => error += count,
+                    }
+                }
+                metrics::gauge!("logjuicer_reports", pending as f64, "status" => "pending");
+                metrics::gauge!("logjuicer_reports", done as f64, "status" => "done");
+                metrics::gauge!("logjuicer_reports", cancelled as f64, "status" => "cancelled");
+                metrics::gauge!("logjuicer_reports", error as f64, "status" => "error");
+            }
+            Err(err) => tracing::error!("Failed to count the reports: {}", err),
+        }
+    }
+
+    /// Estimate the size of a target and its baselines, without processing them.
+    pub async fn estimate(
+        &self,
+        target: String,
+        baseline: Option<String>,
+    ) -> Result<Estimate, String> {
+        let env = self.env.clone();
+        tokio::task::spawn_blocking(move || estimate_report(&env, &target, baseline.as_deref()))
+            .await
+            .map_err(|e| format!("estimate crashed: {}", e))?
+    }
+
+    /// Score a line with a stored model, to explain why it is, or is not, an anomaly.
+    pub async fn preview_line(
+        &self,
+        model: String,
+        path: String,
+        line: String,
+    ) -> Result<Option<(String, LinePreview)>, String> {
+        let env = self.env.clone();
+        tokio::task::spawn_blocking(move || {
+            crate::models::load_model(&model).map(|model| {
+                model
+                    .preview_line(&env, &path, &line)
+                    .map(|(index, preview)| (index.to_string(), preview))
+            })
+        })
+        .await
+        .map_err(|e| format!("preview crashed: {}", e))?
+    }
+
     // TODO: deny this clippy warning
     #[allow(clippy::map_entry)]
-    pub fn submit(&self, report_id: ReportID, target: &str, baseline: Option<&str>) {
+    pub fn submit(
+        &self,
+        report_id: ReportID,
+        target: &str,
+        baseline: Option<&str>,
+        limits: &Limits,
+    ) {
         let mut running_init_write = self.running.write().unwrap();
         // Check if the report is being processed
         if !running_init_write.contains_key(&report_id) {
             println!("Submiting");
-            let monitor = ProcessMonitor::new();
+            metrics::increment_counter!("logjuicer_reports_submitted");
+            let monitor = ProcessMonitor::new(self.config.report_timeout);
             running_init_write.insert(report_id, monitor.clone());
             std::mem::drop(running_init_write);
 
-            // Prepare worker variables
-            let env = self.env.clone();
+            // Prepare worker variables, each report gets its own cancellation token and progress.
+            let mut env = Env {
+                cancel: monitor.cancel.clone(),
+                progress: Arc::new(monitor.clone()),
+                ..Env::new()
+            };
+            env.config
+                .set_limits(self.env.config.limits().restrict(limits));
             let target = target.to_string();
             let baseline = baseline.map(|s| s.to_string());
             let running = self.running.clone();
             let db = self.db.clone();
+            let gerrit = self.gerrit.clone();
+            let github = self.github.clone();
             let handle = tokio::runtime::Handle::current();
 
             // Submit the execution to the thread pool
             self.pool.execute(move || {
+                let start_time = std::time::Instant::now();
                 let baseline = baseline.as_deref();
-                let (status, count) = match process_report_safe(&env, &target, baseline, &monitor) {
-                    Ok(report) => {
-                        let count = report.anomaly_count();
-                        let fp = format!("data/{}.gz", report_id);
-                        let status = if let Err(err) = report.save(std::path::Path::new(&fp)) {
-                            monitor.emit(format!("Error: saving failed: {}", err).into());
-                            ReportStatus::Error(format!("Save error: {}", err))
-                        } else {
-                            monitor.emit("Done".into());
-                            ReportStatus::Completed
-                        };
-                        (status, count)
+                let mut comment = None;
+                let mut check_run = None;
+                let (status, count, anomalies) =
+                    match process_report_safe(&env, &target, baseline, &monitor) {
+                        Ok(report) => {
+                            let count = report.anomaly_count();
+                            let anomalies = crate::database::report_anomalies(&report);
+                            comment = gerrit
+                                .as_ref()
+                                .and_then(|gerrit| gerrit.comment(report_id, &report));
+                            check_run = github
+                                .as_ref()
+                                .and_then(|github| github.check_run(report_id, &report));
+                            monitor.emit(ReportPhase::Saving, "Saving the report");
+                            let status = if let Err(err) =
+                                crate::transcode::save_report(report_id, &report)
+                            {
+                                monitor.emit(
+                                    ReportPhase::Error,
+                                    format!("Error: saving failed: {}", err),
+                                );
+                                ReportStatus::Error(format!("Save error: {}", err))
+                            } else {
+                                monitor.emit(ReportPhase::Done, "Done");
+                                ReportStatus::Completed
+                            };
+                            (status, count, anomalies)
+                        }
+                        Err(_) if monitor.cancel.check() == Err(Cancellation::Requested) => {
+                            monitor.emit(ReportPhase::Cancelled, "Cancelled");
+                            (ReportStatus::Cancelled, 0, Vec::new())
+                        }
+                        Err(e) => {
+                            monitor.emit(ReportPhase::Error, format!("Error: {}", e));
+                            (ReportStatus::Error(e), 0, Vec::new())
+                        }
+                    };
+                match status {
+                    ReportStatus::Completed => {
+                        metrics::increment_counter!("logjuicer_reports_completed")
                     }
-                    Err(e) => {
-                        monitor.emit(format!("Error: {}", e).into());
-                        (ReportStatus::Error(e), 0)
+                    ReportStatus::Cancelled => {
+                        metrics::increment_counter!("logjuicer_reports_cancelled")
                     }
-                };
+                    _ => metrics::increment_counter!("logjuicer_reports_failed"),
+                }
+                metrics::histogram!(
+                    "logjuicer_report_duration_seconds",
+                    start_time.elapsed().as_secs_f64()
+                );
+                metrics::counter!("logjuicer_processed_bytes", monitor.progress.bytes() as u64);
                 // Remove the monitor
                 let _ = running.write().unwrap().remove(&report_id);
                 // Record the result into the db
-                handle.spawn(
-                    async move { db.update_report(report_id, count, &status).await.unwrap() },
-                );
+                handle.spawn(async move {
+                    db.update_report(report_id, count, &status).await.unwrap();
+                    if status == ReportStatus::Completed {
+                        if let Err(err) = db.insert_anomalies(report_id, &anomalies).await {
+                            tracing::error!(
+                                report_id = report_id.0,
+                                "Failed to record the anomalies: {}",
+                                err
+                            );
+                        }
+                    }
+                    // Comment on the change once the report is available
+                    if let (Some(gerrit), Some(comment), ReportStatus::Completed) =
+                        (gerrit, comment, &status)
+                    {
+                        let result = tokio::task::spawn_blocking(move || gerrit.post(&comment))
+                            .await
+                            .unwrap();
+                        if let Err(err) = result {
+                            tracing::error!(
+                                report_id = report_id.0,
+                                "Failed to comment on Gerrit: {}",
+                                err
+                            );
+                        }
+                    }
+                    if let (Some(github), Some(check_run), ReportStatus::Completed) =
+                        (github, check_run, &status)
+                    {
+                        let result = tokio::task::spawn_blocking(move || github.post(&check_run))
+                            .await
+                            .unwrap();
+                        if let Err(err) = result {
+                            tracing::error!(
+                                report_id = report_id.0,
+                                "Failed to create the GitHub check run: {}",
+                                err
+                            );
+                        }
+                    }
+                });
             })
         } else {
             println!("Already submitted");
@@ -97,23 +405,173 @@ impl Workers {
 
 #[derive(Clone)]
 pub struct ProcessMonitor {
+    /// The encoded events, the index is the event sequence number.
     pub events: Arc<tokio::sync::RwLock<Vec<Arc<str>>>>,
-    pub chan: tokio::sync::broadcast::Sender<Arc<str>>,
+    /// The live events with their sequence number, the slow receivers may miss some of them.
+    pub chan: tokio::sync::broadcast::Sender<(u64, Arc<str>)>,
+    /// The cancellation of the report process.
+    pub cancel: CancelToken,
+    /// The progress of the report process.
+    pub progress: Arc<ProgressCounters>,
+    /// The current phase, used by the progress events.
+    phase: Arc<Mutex<ReportPhase>>,
+    /// The results of the targets analyzed so far.
+    partial: Arc<Mutex<Vec<PartialReport>>>,
+}
+
+/// The results of a target analysis. They are kept encoded because the report values can't be shared
+/// between threads.
+struct PartialReport {
+    /// The report without results, set when the analysis starts.
+    report: Vec<u8>,
+    /// The log reports created so far.
+    log_reports: Vec<Vec<u8>>,
 }
 
 impl ProcessMonitor {
-    fn new() -> Self {
+    fn new(timeout: Duration) -> Self {
         let (chan, _) = tokio::sync::broadcast::channel(16);
-        ProcessMonitor {
+        let monitor = ProcessMonitor {
             events: Arc::new(tokio::sync::RwLock::new(Vec::new())),
             chan,
+            cancel: CancelToken::new().with_timeout(timeout),
+            progress: Arc::new(ProgressCounters::new()),
+            phase: Arc::new(Mutex::new(ReportPhase::Waiting)),
+            partial: Arc::new(Mutex::new(Vec::new())),
+        };
+        // The first event tells the clients that the report is queued.
+        let waiting = encode_event(&monitor.event(0, ReportPhase::Waiting, "Waiting to start..."));
+        monitor
+            .events
+            .try_write()
+            .expect("New events lock")
+            .push(waiting);
+        monitor
+    }
+
+    /// Create an event with the current progress.
+    pub fn event(&self, seq: u64, phase: ReportPhase, message: &str) -> ReportEvent {
+        let files = self.progress.files();
+        let analyzed = self.progress.analyzed();
+        let percent = if phase == ReportPhase::Done {
+            100
+        } else {
+            (analyzed * 100)
+                .checked_div(files)
+                .map_or(0, |percent| percent.min(100) as u8)
+        };
+        ReportEvent {
+            seq,
+            phase,
+            message: message.into(),
+            percent,
+            bytes: self.progress.bytes() as u64,
+            files: files as u64,
+            analyzed: analyzed as u64,
+            anomalies: self.progress.anomalies() as u64,
         }
     }
 
-    fn emit(&self, msg: Arc<str>) {
+    /// Send an event to the websocket clients, the phase is kept for the following progress events.
+    fn emit(&self, phase: ReportPhase, message: impl AsRef<str>) {
+        *self.phase.lock().unwrap() = phase;
+        // The events lock is kept while sending, so that the sequence numbers are sent in order.
+        let mut events = self.events.blocking_write();
+        let seq = events.len() as u64;
+        let msg = encode_event(&self.event(seq, phase, message.as_ref()));
         println!("Emitting {}", msg);
-        self.events.blocking_write().push(msg.clone());
-        let _ = self.chan.send(msg);
+        events.push(msg.clone());
+        let _ = self.chan.send((seq, msg));
+    }
+
+    /// Send the current progress, without a message.
+    fn emit_progress(&self) {
+        let phase = *self.phase.lock().unwrap();
+        self.emit(phase, "")
+    }
+
+    /// Start the partial report of a new target, the results of the previous targets are kept.
+    fn start_partial(&self, report: &Report) -> Result<(), String> {
+        let mut buf = Vec::new();
+        ReportEncoder::new()
+            .encode(report, &mut buf)
+            .map_err(|e| format!("partial report: {}", e))?;
+        self.partial.lock().unwrap().push(PartialReport {
+            report: buf,
+            log_reports: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Add a log report to the partial report of the current target.
+    fn add_partial(&self, log_report: &LogReport) -> Result<(), String> {
+        let mut buf = Vec::new();
+        ReportEncoder::new()
+            .encode_log_report(log_report, &mut buf)
+            .map_err(|e| format!("partial report: {}", e))?;
+        self.partial
+            .lock()
+            .unwrap()
+            .last_mut()
+            .ok_or_else(|| "partial report: the analysis did not start".to_string())?
+            .log_reports
+            .push(buf);
+        Ok(())
+    }
+
+    /// Decode the report with the anomalies found so far, returning None when the analysis did not start.
+    /// The reports of the targets are combined, like the final report.
+    pub fn partial_report(&self) -> Result<Option<Report>, String> {
+        let partial = self.partial.lock().unwrap();
+        let decoder = ReportDecoder::new();
+        let mut reports = partial
+            .iter()
+            .map(|partial| {
+                let mut report = decoder
+                    .decode(partial.report.as_slice())
+                    .map_err(|e| e.to_string())?;
+                let log_reports = partial
+                    .log_reports
+                    .iter()
+                    .map(|buf| decoder.decode_log_report(buf.as_slice()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| e.to_string())?;
+                report.set_log_reports(log_reports);
+                Ok(report)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if reports.len() > 1 {
+            Ok(logjuicer_report::merge::merge(reports))
+        } else {
+            Ok(reports.pop())
+        }
+    }
+}
+
+/// Encode an event as a websocket text message.
+pub fn encode_event(event: &ReportEvent) -> Arc<str> {
+    serde_json::to_string(event)
+        .expect("Event serialization")
+        .into()
+}
+
+impl Progress for ProcessMonitor {
+    fn files_discovered(&self, count: usize) {
+        self.progress.files_discovered(count);
+        self.emit_progress()
+    }
+
+    fn bytes_downloaded(&self, source: &logjuicer_report::Source, count: usize) {
+        self.progress.bytes_downloaded(source, count)
+    }
+
+    fn file_analyzed(&self, source: &logjuicer_report::Source) {
+        self.progress.file_analyzed(source);
+        self.emit_progress()
+    }
+
+    fn anomalies_found(&self, source: &logjuicer_report::Source, count: usize) {
+        self.progress.anomalies_found(source, count)
     }
 }
 
@@ -141,25 +599,44 @@ fn process_report(
     monitor: &ProcessMonitor,
 ) -> Result<Report, String> {
     match baseline {
-        None => monitor.emit(format!("Running `logjuicer url {}`", target).into()),
-        Some(baseline) => {
-            monitor.emit(format!("Running `logjuicer diff {} {}`", baseline, target).into())
-        }
+        None => monitor.emit(
+            ReportPhase::Resolving,
+            format!("Running `logjuicer url {}`", target),
+        ),
+        Some(baseline) => monitor.emit(
+            ReportPhase::Resolving,
+            format!("Running `logjuicer diff {} {}`", baseline, target),
+        ),
     }
 
-    use logjuicer_report::Content;
-    fn check_content(content: &Content) -> Result<(), String> {
-        match content {
-            Content::Zuul(_) | logjuicer_report::Content::Prow(_) => Ok(()),
-            _ => Err("Only zuul or prow build are supported".to_string()),
-        }
+    // The targets are separated by spaces, their reports are combined.
+    let mut reports = target
+        .split_whitespace()
+        .map(|target| process_target(env, target, baseline, monitor))
+        .collect::<Result<Vec<_>, String>>()?;
+    if reports.len() == 1 {
+        Ok(reports.remove(0))
+    } else {
+        logjuicer_report::merge::merge(reports).ok_or_else(|| "No target provided".to_string())
     }
+}
 
+fn process_target(
+    env: &Env,
+    target: &str,
+    baseline: Option<&str>,
+    monitor: &ProcessMonitor,
+) -> Result<Report, String> {
+    // The report may be cancelled before it started.
+    env.cancel.check().map_err(|e| e.to_string())?;
     let input = logjuicer_model::Input::Url(target.into());
     let content =
         logjuicer_model::content_from_input(env, input).map_err(|e| format!("{:?}", e))?;
 
-    monitor.emit(format!("Content resolved: {}", content).into());
+    monitor.emit(
+        ReportPhase::Resolving,
+        format!("Content resolved: {}", content),
+    );
     check_content(&content)?;
 
     let baselines = match baseline {
@@ -172,17 +649,183 @@ fn process_report(
             .map_err(|e| format!("discovery failed: {:?}", e))?,
     };
 
-    monitor.emit(format!("Baseline found: {}", baselines.iter().format(", ")).into());
+    monitor.emit(
+        ReportPhase::Training,
+        format!("Baseline found: {}", baselines.iter().format(", ")),
+    );
     baselines.iter().try_for_each(check_content)?;
 
-    let model = logjuicer_model::Model::<logjuicer_model::FeaturesMatrix>::train::<
+    let model = logjuicer_model::store::train::<
+        logjuicer_model::FeaturesMatrix,
         logjuicer_model::FeaturesMatrixBuilder,
     >(env, baselines)
     .map_err(|e| format!("training failed: {:?}", e))?;
 
-    monitor.emit("Starting analysis".into());
-    let report = model
-        .report(env, content)
+    monitor.emit(ReportPhase::Analyzing, "Starting analysis");
+    // The log reports are also added to the partial report, to preview the anomalies before the end.
+    monitor.start_partial(&model.pending_report(env, content.clone()))?;
+    let mut log_reports = Vec::new();
+    let mut report = model
+        .report_with(env, content, |log_report| {
+            monitor
+                .add_partial(&log_report)
+                .map_err(anyhow::Error::msg)?;
+            log_reports.push(log_report);
+            Ok(())
+        })
         .map_err(|e| format!("report failed: {:?}", e))?;
+    report.set_log_reports(log_reports);
     Ok(report)
 }
+
+fn check_content(content: &logjuicer_report::Content) -> Result<(), String> {
+    use logjuicer_report::Content;
+    match content {
+        Content::Zuul(_)
+        | Content::Prow(_)
+        | Content::Jenkins(_)
+        | Content::Buildkite(_)
+        | Content::CircleCi(_)
+        | Content::Koji(_)
+        | Content::Obs(_) => Ok(()),
+        _ => Err(
+            "Only zuul, prow, jenkins, buildkite, circleci, koji or obs build are supported"
+                .to_string(),
+        ),
+    }
+}
+
+fn estimate_report(env: &Env, target: &str, baseline: Option<&str>) -> Result<Estimate, String> {
+    let mut contents = Vec::new();
+    for target in target.split_whitespace() {
+        let input = logjuicer_model::Input::Url(target.into());
+        let content =
+            logjuicer_model::content_from_input(env, input).map_err(|e| format!("{:?}", e))?;
+        // The estimate reads the contents, they are checked like the processed ones.
+        check_content(&content)?;
+        match baseline {
+            Some(baseline) => {
+                let input = logjuicer_model::Input::Url(baseline.into());
+                contents.push(
+                    logjuicer_model::content_from_input(env, input)
+                        .map_err(|e| format!("baseline: {:?}", e))?,
+                )
+            }
+            None => contents.extend(
+                logjuicer_model::content_discover_baselines(&content, env)
+                    .map_err(|e| format!("discovery failed: {:?}", e))?,
+            ),
+        };
+        contents.push(content);
+    }
+    contents.iter().try_for_each(check_content)?;
+    logjuicer_model::estimate::estimate(env, &contents)
+        .map_err(|e| format!("estimate failed: {:?}", e))
+}
+
+#[test]
+fn test_workers_config() {
+    let vars = |vars: &'static [(&str, &str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        }
+    };
+    assert_eq!(
+        WorkersConfig::from_vars(vars(&[])),
+        Ok(WorkersConfig::default())
+    );
+    assert_eq!(
+        WorkersConfig::from_vars(vars(&[
+            ("LOGJUICER_WORKERS", "4"),
+            ("LOGJUICER_MAX_QUEUE", "0"),
+            ("LOGJUICER_REPORT_TIMEOUT", " 600 ")
+        ])),
+        Ok(WorkersConfig {
+            pool_size: 4,
+            max_queue: 0,
+            report_timeout: Duration::from_secs(600),
+        })
+    );
+    assert!(WorkersConfig::from_vars(vars(&[("LOGJUICER_WORKERS", "0")])).is_err());
+    assert!(WorkersConfig::from_vars(vars(&[("LOGJUICER_MAX_QUEUE", "many")])).is_err());
+}
+
+#[test]
+fn test_monitor_event() {
+    let monitor = ProcessMonitor::new(Duration::from_secs(60));
+    let source = logjuicer_report::Source::Local(0, "job-output.txt".into());
+    monitor.progress.files_discovered(4);
+    monitor.progress.file_analyzed(&source);
+    monitor.progress.bytes_downloaded(&source, 1024);
+    let event = monitor.event(1, ReportPhase::Training, "Progress");
+    assert_eq!(
+        (event.percent, event.files, event.analyzed, event.bytes),
+        (25, 4, 1, 1024)
+    );
+    assert_eq!(monitor.event(2, ReportPhase::Done, "Done").percent, 100);
+
+    let monitor = ProcessMonitor::new(Duration::from_secs(60));
+    assert_eq!(
+        monitor.events.blocking_read()[0].as_ref(),
+        r#"{"seq":0,"phase":"waiting","message":"Waiting to start...","percent":0,"bytes":0,"files":0,"analyzed":0,"anomalies":0}"#
+    );
+    // A slow receiver misses the live events, but they are all stored with their sequence number.
+    let mut rx = monitor.chan.subscribe();
+    for _ in 0..20 {
+        monitor.emit_progress();
+    }
+    assert!(matches!(
+        rx.try_recv(),
+        Err(tokio::sync::broadcast::error::TryRecvError::Lagged(4))
+    ));
+    assert_eq!(rx.try_recv().unwrap().0, 5);
+    let events = monitor.events.blocking_read();
+    assert_eq!(events.len(), 21);
+    assert!(events[20].starts_with(r#"{"seq":20,"#));
+}
+
+#[test]
+fn test_partial_report() {
+    let monitor = ProcessMonitor::new(Duration::from_secs(60));
+    assert_eq!(monitor.partial_report(), Ok(None));
+    let mut report = Report::sample();
+    let log_reports = std::mem::take(&mut report.log_reports);
+    monitor.start_partial(&report).unwrap();
+    assert_eq!(
+        monitor.partial_report().unwrap().unwrap().anomaly_count(),
+        0
+    );
+    for log_report in &log_reports {
+        monitor.add_partial(log_report).unwrap();
+    }
+    // The summary, the clusters and the failed tests anomalies are computed on load.
+    let mut expected = Report::sample();
+    let expected_log_reports = std::mem::take(&mut expected.log_reports);
+    expected.set_log_reports(expected_log_reports);
+    assert_eq!(monitor.partial_report(), Ok(Some(expected)));
+
+    // The results of the previous target are kept.
+    monitor.start_partial(&report).unwrap();
+    monitor.add_partial(&log_reports[0]).unwrap();
+    let merged = monitor.partial_report().unwrap().unwrap();
+    assert_eq!(merged.merged_targets.len(), 2);
+    assert_eq!(merged.anomaly_count(), 1);
+    assert_eq!(merged.log_reports[0].anomalies[0].targets, vec![0, 1]);
+}
+
+#[test]
+fn test_estimate_check_content() {
+    let env = Env::new();
+    let metadata = "http://169.254.169.254/latest/meta-data/";
+    for target in [metadata, "http://localhost/a.log http://localhost/b.log"] {
+        assert_eq!(
+            estimate_report(&env, target, Some(metadata)).map(|_| ()),
+            Err(
+                "Only zuul, prow, jenkins, buildkite, circleci, koji or obs build are supported"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -11,7 +11,7 @@ use std::rc::Rc;
 use wasm_bindgen_futures::spawn_local;
 use web_sys::HtmlInputElement;
 
-use logjuicer_report::report_row::{ReportID, ReportRow, ReportStatus};
+use logjuicer_report::report_row::{ReportEvent, ReportID, ReportPhase, ReportRow, ReportStatus};
 
 use crate::dom_utils::*;
 use crate::state::{App, Route};
@@ -26,15 +26,27 @@ fn render_report_row(state: &Rc<App>, report: &ReportRow) -> Dom {
         ReportStatus::Completed => {
             link!(state.to_url(Route::Report(report.id)), {.text("read")})
         }
+        ReportStatus::Cancelled => {
+            link!(state.to_url(Route::Report(report.id)), {.text("cancelled")})
+        }
         ReportStatus::Error(err) => {
             link!(state.to_url(Route::Report(report.id)), {.text("error").attr("title", &err)})
         }
     };
+    let rerun_of = report.rerun_of.map(|original| {
+        let original = ReportID(original);
+        html!("span", {.class("ml-1").children(&mut [
+            text("(re-run of "),
+            link!(state.to_url(Route::Report(original)), {.text(&format!("#{}", original))}),
+            text(")"),
+        ])})
+    });
     html!("tr", {.class(["border-b", "px-6"]).children(&mut [
-            html!("td", {.class(TH_CLASS).child(status)}),
+            html!("td", {.class(TH_CLASS).child(status).children(rerun_of)}),
             html!("td", {.class(TH_CLASS).text(&format!("{}", report.anomaly_count))}),
             html!("td", {.class(TH_CLASS).text(&report.target)}),
             html!("td", {.class(TH_CLASS).text(&report.baseline)}),
+            html!("td", {.class(TH_CLASS).text(report.owner.as_deref().unwrap_or(""))}),
             html!("td", {.class(TH_CLASS).text(&format!("{}", report.updated_at))}),
         ])
     })
@@ -51,6 +63,7 @@ fn render_report_rows(state: &Rc<App>, reports: &[ReportRow]) -> Dom {
             html!("th", {.class(TH_CLASS).text("Anomaly")}),
             html!("th", {.class(TH_CLASS).text("URL")}),
             html!("th", {.class(TH_CLASS).text("Baseline")}),
+            html!("th", {.class(TH_CLASS).text("Owner")}),
             html!("th", {.class(TH_CLASS).text("Updated At")}),
         ])}),
         html!("tbody", {.children(&mut tbody)}),
@@ -182,6 +195,7 @@ pub fn do_render_new(state: &Rc<App>, target: String) -> Dom {
                 state.replace_url(Route::Report(*report_id));
                 None
             },
+            Some(Ok((_, ReportStatus::Cancelled))) => Some(html!("div", {.text("The report was cancelled")})),
             Some(Ok((_, ReportStatus::Error(e)))) => Some(html!("div", {.children(&mut [
                 text("Processing error: "),
                 text(e)
@@ -201,20 +215,79 @@ async fn request_new_report(path: &str) -> Result<(ReportID, ReportStatus), Stri
     Ok(data)
 }
 
+async fn request_cancel_report(url: &str) -> Result<(), String> {
+    let resp = gloo_net::http::Request::delete(url)
+        .send()
+        .await
+        .map_err(|e| format!("request err: {}", e))?;
+    if resp.ok() {
+        Ok(())
+    } else {
+        Err(format!(
+            "api {} {}",
+            resp.status(),
+            resp.text().await.unwrap_or("".into()),
+        ))
+    }
+}
+
 use futures::StreamExt;
 use futures_signals::signal_vec::SignalVecExt;
 use gloo_net::websocket::futures::WebSocket;
 use gloo_net::websocket::Message;
+fn phase_name(phase: ReportPhase) -> &'static str {
+    match phase {
+        ReportPhase::Waiting => "Waiting",
+        ReportPhase::Resolving => "Resolving the target",
+        ReportPhase::Training => "Training the baselines",
+        ReportPhase::Analyzing => "Analyzing the target",
+        ReportPhase::Saving => "Saving the report",
+        ReportPhase::Done => "Done",
+        ReportPhase::Cancelled => "Cancelled",
+        ReportPhase::Error => "Failed",
+    }
+}
+
+fn render_progress(event: &ReportEvent) -> Dom {
+    let counts = format!(
+        "{} - {}/{} files, {:.2} MB, {} anomalies",
+        phase_name(event.phase),
+        event.analyzed,
+        event.files,
+        logjuicer_report::bytes_to_mb(event.bytes as usize),
+        event.anomalies
+    );
+    let width = format!("{}%", event.percent);
+    html!("div", {.class(["m-2", "ml-4"]).children(&mut [
+        html!("div", {.class(["w-full", "h-4", "rounded", "bg-slate-200"]).child(
+            html!("div", {.class(["h-4", "rounded", "bg-blue-500"]).style("width", width.as_str())})
+        )}),
+        html!("div", {.class(["font-mono", "mt-1"]).text(&counts)}),
+    ])})
+}
+
 pub fn do_render_run(state: &Rc<App>, report_id: ReportID) -> Dom {
     let infos: MutableVec<Rc<String>> = MutableVec::new();
+    let progress: Mutable<Option<ReportEvent>> = Mutable::new(None);
     let url = state.ws_report_url(report_id);
     let mut ws = WebSocket::open(&url).unwrap();
 
     let final_id = report_id;
-    let handler = clone!(state => clone!(infos => async move {
+    let handler = clone!(state => clone!(infos, progress => async move {
         while let Some(Ok(Message::Text(msg))) = ws.next().await {
-            let done = msg == "Done";
-            infos.lock_mut().push_cloned(Rc::new(msg));
+            let event: ReportEvent = match serde_json::from_str(&msg) {
+                Ok(event) => event,
+                Err(err) => {
+                    infos.lock_mut().push_cloned(Rc::new(format!("Invalid event {}: {}", msg, err)));
+                    continue;
+                }
+            };
+            let done = event.phase == ReportPhase::Done;
+            // The progress updates don't have a message
+            if !event.message.is_empty() {
+                infos.lock_mut().push_cloned(Rc::new(event.message.to_string()));
+            }
+            progress.set(Some(event));
             if done {
                 gloo_timers::future::TimeoutFuture::new(500).await;
                 state.replace_url(Route::Report(final_id));
@@ -229,5 +302,19 @@ pub fn do_render_run(state: &Rc<App>, report_id: ReportID) -> Dom {
         .signal_vec_cloned()
         .map(|ev| html!("pre", {.class(["font-mono", "m-2", "ml-4"]).text(&ev)}));
 
-    html!("div", {.future(handler).class("px-2").children_signal_vec(sig)})
+    let cancel_url = state.cancel_report_url(report_id);
+    let cancel = html!("button", {.class(["whitespace-nowrap", "rounded", "m-2", "px-2", "py-1", "text-white", "font-bold", "bg-blue-500", "hover:bg-blue-700"])
+        .text("Cancel")
+        .event(clone!(infos => move |_: events::Click| {
+            spawn_local(clone!(infos, cancel_url => async move {
+                if let Err(err) = request_cancel_report(&cancel_url).await {
+                    infos.lock_mut().push_cloned(Rc::new(format!("Cancel failed: {}", err)));
+                }
+            }));
+        }))
+    });
+
+    let progress = progress.signal_ref(|event| event.as_ref().map(render_progress));
+
+    html!("div", {.future(handler).class("px-2").child(cancel).child_signal(progress).children_signal_vec(sig)})
 }
@@ -9,7 +9,9 @@ use gloo_console::log;
 use std::rc::Rc;
 use wasm_bindgen_futures::spawn_local;
 
-use logjuicer_report::{bytes_to_mb, Content, IndexName, LogReport, Report, Source};
+use logjuicer_report::{
+    bytes_to_mb, AnomalyCluster, Content, FailedTest, IndexName, LogReport, Report, Source,
+};
 
 use crate::dom_utils::{data_attr, data_attr_html, render_link};
 use crate::selection::Selection;
@@ -45,6 +47,22 @@ fn render_content(content: &Content) -> Dom {
             render_link(&zuul_build.build_url(),
                         &format!("zuul<job={}, project={}, branch={}, result={}>", zuul_build.job_name, zuul_build.project, zuul_build.branch, zuul_build.result))
         ])}),
+        Content::Jenkins(jenkins_build) => html!("div", {.children(&mut [
+            render_link(jenkins_build.url.as_str(),
+                        &format!("jenkins<job={}, build={}, result={}>", jenkins_build.job_name, jenkins_build.number, jenkins_build.result))
+        ])}),
+        Content::Buildkite(buildkite_build) => html!("div", {.children(&mut [
+            render_link(buildkite_build.url.as_str(),
+                        &format!("buildkite<pipeline={}, build={}, state={}>", buildkite_build.pipeline, buildkite_build.number, buildkite_build.state))
+        ])}),
+        Content::CircleCi(circleci_job) => html!("div", {.children(&mut [
+            render_link(&circleci_job.job_url(),
+                        &format!("circleci<job={}, workflow={}, branch={}, status={}>", circleci_job.job_name, circleci_job.workflow, circleci_job.branch, circleci_job.status))
+        ])}),
+        Content::Koji(koji_build) => html!("div", {.children(&mut [
+            render_link(koji_build.url.as_str(),
+                        &format!("koji<nvr={}, state={}>", koji_build.nvr, koji_build.state))
+        ])}),
         _ => html!("div", {.text(&content.to_string())}),
     }
 }
@@ -82,6 +100,14 @@ fn render_line(gl_pos: &mut usize, pos: usize, distance: f32, line: &str) -> Dom
     ])})
 }
 
+/// Link to the anomaly line when the source is remote, otherwise to the file.
+fn render_anomaly_link(report: &Report, source: &Source, pos: usize, text: &str) -> Dom {
+    match report.anomaly_href(source, pos) {
+        Some(href) => render_link(&href, text),
+        None => render_link(source.get_href(&report.target), text),
+    }
+}
+
 fn log_name(path: &str) -> &str {
     match path.rsplit_once('/') {
         Some((_, name)) => name,
@@ -108,6 +134,19 @@ fn render_log_report(gl_pos: &mut usize, report: &Report, log_report: &LogReport
         }
         None => infos.push(data_attr("Unknown Index", index_name)),
     };
+    if let Some(category) = &log_report.category {
+        infos.push(data_attr("Category", category));
+    }
+    if !log_report.rotated.is_empty() {
+        let mut rotated: Vec<Dom> = log_report
+            .rotated
+            .iter()
+            .map(
+                |source| html!("div", {.class("pr-2").children(&mut [render_source_link(source)])}),
+            )
+            .collect();
+        infos.push(data_attr_html("Rotated files", &mut rotated));
+    }
     infos.push(data_attr(
         "Test time",
         &format!("{} ms", log_report.test_time.as_millis()),
@@ -193,9 +232,131 @@ fn render_unknown(target: &Content, source: &Source, index: &IndexName) -> Dom {
     )
 }
 
+fn render_summary(report: &Report) -> Dom {
+    let mut entries = report
+        .summary
+        .iter()
+        .map(|entry| {
+            html!("li", {.children(&mut [
+                html!("span", {.class(["font-mono", "break-all"]).text(&entry.line)}),
+                html!("span", {.class(["pl-2", "text-slate-500"]).text(&format!("{}x, distance {:.2}, ", entry.count, entry.distance))}),
+                render_anomaly_link(report, &entry.source, entry.pos, &format!("{}:{}", log_name(entry.source.get_relative()), entry.pos)),
+            ])})
+        })
+        .collect::<Vec<Dom>>();
+    html!("div", {.class(["pl-1", "pt-2", "pb-2", "max-w-full"]).children(&mut [
+        html!("div", {.class("font-bold").text("Top anomalies")}),
+        html!("ol", {.class(["list-decimal", "pl-6"]).children(&mut entries)}),
+    ])})
+}
+
+fn render_targets(report: &Report) -> Dom {
+    let mut entries = report
+        .target_breakdown()
+        .into_iter()
+        .map(|(content, count)| {
+            html!("li", {.children(&mut [html!("div", {.class("flex").children(&mut [
+                render_content(content),
+                html!("span", {.class(["pl-2", "text-slate-500"]).text(&format!("{} anomalies", count))}),
+            ])})])})
+        })
+        .collect::<Vec<Dom>>();
+    html!("div", {.class(["pl-1", "pt-2", "pb-2", "max-w-full"]).children(&mut [
+        html!("div", {.class("font-bold").text("Targets")}),
+        html!("ol", {.class(["list-decimal", "pl-6"]).children(&mut entries)}),
+    ])})
+}
+
+fn render_cluster(report: &Report, cluster: &AnomalyCluster) -> Dom {
+    let toggle_members = Mutable::new(false);
+    let handler = clone!(toggle_members => move |_: dominator::events::Click| {
+        toggle_members.set(!toggle_members.get());
+    });
+    let mut members = cluster
+        .anomalies(&report.log_reports)
+        .map(|(lr, anomaly)| {
+            html!("div", {.class("pl-4").children(&mut [
+                render_anomaly_link(report, &lr.source, anomaly.anomaly.pos, &format!("{}:{}", lr.source.get_relative(), anomaly.anomaly.pos))
+            ])})
+        })
+        .collect::<Vec<Dom>>();
+    html!("div", {.children(&mut [
+        html!("div", {.class("cursor-pointer").event(handler).children(&mut [
+            html!("span", {.class("text-slate-500").text(&format!("{} anomalies in {} sources: ", cluster.members.len(), cluster.source_count()))}),
+            html!("span", {.class(["font-mono", "break-all"]).text(&cluster.line)}),
+        ])}),
+        html!("div", {.visible_signal(toggle_members.signal()).children(&mut members)}),
+    ])})
+}
+
+fn render_clusters(report: &Report) -> Dom {
+    let mut clusters = report
+        .clusters
+        .iter()
+        .map(|cluster| render_cluster(report, cluster))
+        .collect::<Vec<Dom>>();
+    html!("div", {.class(["pl-1", "pt-2", "pb-2", "max-w-full"]).children(&mut [
+        html!("div", {.class("font-bold").text("Clusters (click to expand)")}),
+        html!("div", {.children(&mut clusters)}),
+    ])})
+}
+
+fn render_failed_test(report: &Report, test: &FailedTest) -> Dom {
+    let mut anomalies = test
+        .anomalies(&report.log_reports)
+        .map(|(lr, anomaly)| {
+            html!("div", {.class(["pl-4", "font-mono", "break-all"]).children(&mut [
+                render_anomaly_link(report, &lr.source, anomaly.anomaly.pos, &format!("{}:{}", lr.source.get_relative(), anomaly.anomaly.pos)),
+                html!("span", {.class("pl-2").text(&anomaly.anomaly.line)}),
+            ])})
+        })
+        .collect::<Vec<Dom>>();
+    html!("div", {.children(&mut [
+        html!("div", {.children(&mut [
+            html!("span", {.class("font-mono").text(&test.name)}),
+            html!("span", {.class(["pl-2", "text-slate-500"]).text(&format!("{} ({} related anomalies)", test.message, test.anomalies.len()))}),
+        ])}),
+        html!("div", {.children(&mut anomalies)}),
+    ])})
+}
+
+fn render_failed_tests(report: &Report) -> Dom {
+    let mut tests = report
+        .failed_tests
+        .iter()
+        .map(|test| render_failed_test(report, test))
+        .collect::<Vec<Dom>>();
+    html!("div", {.class(["pl-1", "pt-2", "pb-2", "max-w-full"]).children(&mut [
+        html!("div", {.class("font-bold").text("Failed tests")}),
+        html!("div", {.children(&mut tests)}),
+    ])})
+}
+
 fn render_report(report: &Report) -> Dom {
     let mut childs = Vec::new();
 
+    if !report.merged_targets.is_empty() {
+        childs.push(render_targets(report));
+    }
+    if !report.summary.is_empty() {
+        childs.push(render_summary(report));
+    }
+    if !report.clusters.is_empty() {
+        childs.push(render_clusters(report));
+    }
+    if !report.failed_tests.is_empty() {
+        childs.push(render_failed_tests(report));
+    }
+
+    if !report.warnings.is_empty() {
+        let mut warnings = report
+            .warnings
+            .iter()
+            .map(|warning| html!("div", {.text(&format!("⚠ {}", warning))}))
+            .collect::<Vec<Dom>>();
+        childs.push(html!("div", {.class(["pl-1", "pt-2", "pb-2", "bg-yellow-100", "max-w-full"]).children(&mut warnings)}));
+    }
+
     let mut gl_pos = 0;
     for lr in &report.log_reports {
         childs.push(render_log_report(&mut gl_pos, report, lr))
@@ -237,6 +398,7 @@ pub fn render_report_card(report: &Report, toggle_info: &Mutable<bool>) -> Dom {
         data_attr("Created at", &render_time(&report.created_at)),
         data_attr("Run time",   &format!("{:.2} sec", report.run_time.as_secs_f32())),
         data_attr("Result",     &result),
+        data_attr("Gating anomalies", &report.gating_anomaly_count().to_string()),
     ]).class_signal("tooltip-visible", toggle_info.signal())})
 }
 
@@ -98,6 +98,10 @@ impl App {
         format!("{}api/report/{}", self.base_path, report_id)
     }
 
+    pub fn cancel_report_url(&self, report_id: ReportID) -> String {
+        format!("{}api/report/{}/run", self.base_path, report_id)
+    }
+
     pub fn new_report_url(&self, target: &str, baseline: Option<&str>) -> String {
         let base = &self.base_path;
         match baseline {
@@ -16,10 +16,20 @@ pub struct Tree {
     pub mimetype: Box<str>,
     #[serde(default)]
     pub children: Vec<Tree>,
+    #[serde(default)]
+    pub size: Option<u64>,
 }
 
 impl Manifest {
-    pub fn to_urls(&self, mut url: Url) -> Vec<Url> {
+    pub fn to_urls(&self, url: Url) -> Vec<Url> {
+        self.to_sized_urls(url)
+            .into_iter()
+            .map(|(url, _)| url)
+            .collect()
+    }
+
+    /// Get the file urls along with their size, when available.
+    pub fn to_sized_urls(&self, mut url: Url) -> Vec<(Url, Option<u64>)> {
         let mut res = vec![];
         if !url.path().ends_with('/') {
             url.set_path(&format!("{}/", url.path()));
@@ -31,14 +41,14 @@ impl Manifest {
     }
 }
 
-fn go_tree(tree: &Tree, res: &mut Vec<Url>, url: &Url) {
+fn go_tree(tree: &Tree, res: &mut Vec<(Url, Option<u64>)>, url: &Url) {
     if tree.mimetype.as_ref() == "application/directory" {
         let dir_url = url.join(&format!("{}/", tree.name)).expect("good url");
         for child in tree.children.iter() {
             go_tree(child, res, &dir_url)
         }
     } else {
-        res.push(url.join(&tree.name).expect("good url"))
+        res.push((url.join(&tree.name).expect("good url"), tree.size))
     }
 }
 
@@ -84,4 +94,12 @@ fn test_decodes_manifest() {
             "http://localhost/build/job-output.txt"
         ]
     );
+    assert_eq!(
+        manifest
+            .to_sized_urls(Url::parse("http://localhost/build").unwrap())
+            .iter()
+            .map(|(_, size)| *size)
+            .collect::<Vec<Option<u64>>>(),
+        vec![Some(14195), Some(33375)]
+    );
 }